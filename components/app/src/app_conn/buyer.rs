@@ -45,6 +45,10 @@ pub fn request_close_payment(payment_id: PaymentId) -> AppRequest {
     AppRequest::RequestClosePayment(payment_id)
 }
 
+pub fn cancel_payment(payment_id: PaymentId) -> AppRequest {
+    AppRequest::CancelPayment(payment_id)
+}
+
 pub fn ack_close_payment(payment_id: PaymentId, ack_uid: Uid) -> AppRequest {
     let ack_close_payment = AckClosePayment {
         payment_id,
@@ -1,13 +1,30 @@
 use proto::crypto::{PublicKey, Signature};
 
 use proto::app_server::messages::{
-    AppRequest, CloseFriendCurrency, NamedRelayAddress, OpenFriendCurrency, RelayAddress,
+    AppRequest, CloseFriendCurrency, NamedRelayAddress, NodeReport, OpenFriendCurrency,
+    RelayAddress,
 };
 use proto::funder::messages::{
     AddFriend, Currency, Rate, RemoveFriendCurrency, ResetFriendChannel, SetFriendCurrencyMaxDebt,
-    SetFriendCurrencyRate, SetFriendName, SetFriendRelays,
+    SetFriendCurrencyRate, SetFriendName, SetFriendRelays, SetRelayName,
 };
 use proto::index_server::messages::NamedIndexServerAddress;
+use proto::report::messages::ChannelStatusReport;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResetFriendChannelError {
+    FriendNotFound,
+    /// Only an inconsistent channel can be reset
+    ChannelConsistent,
+    /// The remote side did not send its reset terms yet
+    MissingRemoteResetTerms,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopFriendCurrencyError {
+    FriendNotFound,
+    CurrencyNotConfigured,
+}
 
 pub fn add_relay(named_relay_address: NamedRelayAddress) -> AppRequest {
     AppRequest::AddRelay(named_relay_address)
@@ -17,6 +34,14 @@ pub fn remove_relay(relay_public_key: PublicKey) -> AppRequest {
     AppRequest::RemoveRelay(relay_public_key)
 }
 
+pub fn set_relay_name(relay_public_key: PublicKey, name: String) -> AppRequest {
+    let set_relay_name = SetRelayName {
+        relay_public_key,
+        name,
+    };
+    AppRequest::SetRelayName(set_relay_name)
+}
+
 pub fn add_friend(
     friend_public_key: PublicKey,
     relays: Vec<RelayAddress>,
@@ -58,6 +83,12 @@ pub fn disable_friend(friend_public_key: PublicKey) -> AppRequest {
     AppRequest::DisableFriend(friend_public_key)
 }
 
+/// Stop new requests with a friend, while letting in-flight transactions complete.
+/// Use `enable_friend()` to resume.
+pub fn pause_friend(friend_public_key: PublicKey) -> AppRequest {
+    AppRequest::PauseFriend(friend_public_key)
+}
+
 pub fn remove_friend_currency(friend_public_key: PublicKey, currency: Currency) -> AppRequest {
     AppRequest::RemoveFriendCurrency(RemoveFriendCurrency {
         friend_public_key,
@@ -79,6 +110,55 @@ pub fn close_friend_currency(friend_public_key: PublicKey, currency: Currency) -
     })
 }
 
+/// Stop trading a currency with a friend, leaving all other currencies untouched.
+/// Returns a sequence of requests that should be sent (and acked) in order, according to the
+/// state of the friend in `node_report`:
+///
+/// First, requests in the currency are closed (Canceling pending requests of this currency only).
+/// Then:
+/// - A currency that is not active is removed.
+/// - An active currency (Wanted by both sides) can not be removed, because its mutual credit is
+///   kept for the lifetime of the channel. Instead, its remote max debt is set to zero, so that
+///   the friend can not take any new credit in this currency.
+pub fn stop_friend_currency(
+    node_report: &NodeReport,
+    friend_public_key: &PublicKey,
+    currency: &Currency,
+) -> Result<Vec<AppRequest>, StopFriendCurrencyError> {
+    let friend_report = node_report
+        .funder_report
+        .friends
+        .get(friend_public_key)
+        .ok_or(StopFriendCurrencyError::FriendNotFound)?;
+
+    if !friend_report
+        .currency_configs
+        .iter()
+        .any(|currency_config| &currency_config.currency == currency)
+    {
+        return Err(StopFriendCurrencyError::CurrencyNotConfigured);
+    }
+
+    let is_active = match &friend_report.channel_status {
+        ChannelStatusReport::Consistent(channel_consistent_report) => channel_consistent_report
+            .currency_reports
+            .iter()
+            .any(|currency_report| &currency_report.currency == currency),
+        ChannelStatusReport::Inconsistent(_) => false,
+    };
+
+    let last_request = if is_active {
+        set_friend_currency_max_debt(friend_public_key.clone(), currency.clone(), 0)
+    } else {
+        remove_friend_currency(friend_public_key.clone(), currency.clone())
+    };
+
+    Ok(vec![
+        close_friend_currency(friend_public_key.clone(), currency.clone()),
+        last_request,
+    ])
+}
+
 pub fn set_friend_currency_max_debt(
     friend_public_key: PublicKey,
     currency: Currency,
@@ -117,6 +197,38 @@ pub fn reset_friend_channel(friend_public_key: PublicKey, reset_token: Signature
     AppRequest::ResetFriendChannel(reset_friend_channel)
 }
 
+/// Create a request to reset the channel with a friend, according to the remote reset terms
+/// found in `node_report`.
+/// The reset token is required as a proof that we already received the remote reset terms.
+pub fn reset_friend_channel_from_report(
+    node_report: &NodeReport,
+    friend_public_key: &PublicKey,
+) -> Result<AppRequest, ResetFriendChannelError> {
+    let friend_report = node_report
+        .funder_report
+        .friends
+        .get(friend_public_key)
+        .ok_or(ResetFriendChannelError::FriendNotFound)?;
+
+    let reset_token = match &friend_report.channel_status {
+        ChannelStatusReport::Consistent(_) => {
+            return Err(ResetFriendChannelError::ChannelConsistent)
+        }
+        ChannelStatusReport::Inconsistent(channel_inconsistent_report) => {
+            &channel_inconsistent_report
+                .opt_remote_reset_terms
+                .as_ref()
+                .ok_or(ResetFriendChannelError::MissingRemoteResetTerms)?
+                .reset_token
+        }
+    };
+
+    Ok(reset_friend_channel(
+        friend_public_key.clone(),
+        reset_token.clone(),
+    ))
+}
+
 pub fn add_index_server(named_index_server: NamedIndexServerAddress) -> AppRequest {
     AppRequest::AddIndexServer(named_index_server)
 }
@@ -124,3 +236,162 @@ pub fn add_index_server(named_index_server: NamedIndexServerAddress) -> AppReque
 pub fn remove_index_server(index_public_key: PublicKey) -> AppRequest {
     AppRequest::RemoveIndexServer(index_public_key)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryFrom;
+
+    use proto::funder::messages::InconsistencyReason;
+    use proto::report::messages::{
+        ChannelConsistentReport, ChannelInconsistentReport, CurrencyConfigReport, CurrencyReport,
+        FriendLivenessReport, FriendReport, FriendStatusReport, McBalanceReport, ResetTermsReport,
+    };
+
+    fn node_report_with_channel(
+        friend_public_key: &PublicKey,
+        channel_status: ChannelStatusReport,
+    ) -> NodeReport {
+        node_report_with_currencies(friend_public_key, Vec::new(), channel_status)
+    }
+
+    fn node_report_with_currencies(
+        friend_public_key: &PublicKey,
+        currency_configs: Vec<CurrencyConfigReport>,
+        channel_status: ChannelStatusReport,
+    ) -> NodeReport {
+        let friend_report = FriendReport {
+            name: "friend".to_owned(),
+            remote_relays: Vec::new(),
+            currency_configs,
+            opt_last_incoming_move_token: None,
+            liveness: FriendLivenessReport::Online,
+            idle_ticks: 0,
+            channel_status,
+            status: FriendStatusReport::Enabled,
+        };
+        let mut node_report = NodeReport::dummy();
+        node_report
+            .funder_report
+            .friends
+            .insert(friend_public_key.clone(), friend_report);
+        node_report
+    }
+
+    #[test]
+    fn test_reset_friend_channel_from_report() {
+        let friend_public_key = PublicKey::from(&[0xbb; PublicKey::len()]);
+        let reset_token = Signature::from(&[0xcc; Signature::len()]);
+
+        let inconsistent = |opt_remote_reset_terms| {
+            ChannelStatusReport::Inconsistent(ChannelInconsistentReport {
+                local_reset_terms: Vec::new(),
+                opt_remote_reset_terms,
+                reason: InconsistencyReason::RemoteReported,
+            })
+        };
+
+        // The reset request uses the token from the remote reset terms:
+        let node_report = node_report_with_channel(
+            &friend_public_key,
+            inconsistent(Some(ResetTermsReport {
+                reset_token: reset_token.clone(),
+                balance_for_reset: Vec::new(),
+            })),
+        );
+        assert_eq!(
+            reset_friend_channel_from_report(&node_report, &friend_public_key),
+            Ok(reset_friend_channel(
+                friend_public_key.clone(),
+                reset_token.clone()
+            ))
+        );
+
+        // Unknown friend:
+        let other_public_key = PublicKey::from(&[0xdd; PublicKey::len()]);
+        assert_eq!(
+            reset_friend_channel_from_report(&node_report, &other_public_key),
+            Err(ResetFriendChannelError::FriendNotFound)
+        );
+
+        // Remote reset terms were not received yet:
+        let node_report = node_report_with_channel(&friend_public_key, inconsistent(None));
+        assert_eq!(
+            reset_friend_channel_from_report(&node_report, &friend_public_key),
+            Err(ResetFriendChannelError::MissingRemoteResetTerms)
+        );
+
+        // A consistent channel can not be reset:
+        let node_report = node_report_with_channel(
+            &friend_public_key,
+            ChannelStatusReport::Consistent(ChannelConsistentReport {
+                currency_reports: Vec::new(),
+            }),
+        );
+        assert_eq!(
+            reset_friend_channel_from_report(&node_report, &friend_public_key),
+            Err(ResetFriendChannelError::ChannelConsistent)
+        );
+    }
+
+    #[test]
+    fn test_stop_friend_currency() {
+        let friend_public_key = PublicKey::from(&[0xbb; PublicKey::len()]);
+        let currency1 = Currency::try_from("FST1".to_owned()).unwrap();
+        let currency2 = Currency::try_from("FST2".to_owned()).unwrap();
+        let currency3 = Currency::try_from("FST3".to_owned()).unwrap();
+
+        let currency_config = |currency: &Currency| CurrencyConfigReport {
+            currency: currency.clone(),
+            rate: Rate::new(),
+            remote_max_debt: 100,
+            is_open: true,
+        };
+
+        // currency1 is active, currency2 is only configured locally:
+        let node_report = node_report_with_currencies(
+            &friend_public_key,
+            vec![currency_config(&currency1), currency_config(&currency2)],
+            ChannelStatusReport::Consistent(ChannelConsistentReport {
+                currency_reports: vec![CurrencyReport {
+                    currency: currency1.clone(),
+                    balance: McBalanceReport {
+                        balance: 5,
+                        local_pending_debt: 0,
+                        remote_pending_debt: 0,
+                    },
+                }],
+            }),
+        );
+
+        // An active currency is closed, and no new credit is given:
+        assert_eq!(
+            stop_friend_currency(&node_report, &friend_public_key, &currency1),
+            Ok(vec![
+                close_friend_currency(friend_public_key.clone(), currency1.clone()),
+                set_friend_currency_max_debt(friend_public_key.clone(), currency1.clone(), 0),
+            ])
+        );
+
+        // An inactive currency is closed and removed:
+        assert_eq!(
+            stop_friend_currency(&node_report, &friend_public_key, &currency2),
+            Ok(vec![
+                close_friend_currency(friend_public_key.clone(), currency2.clone()),
+                remove_friend_currency(friend_public_key.clone(), currency2.clone()),
+            ])
+        );
+
+        assert_eq!(
+            stop_friend_currency(&node_report, &friend_public_key, &currency3),
+            Err(StopFriendCurrencyError::CurrencyNotConfigured)
+        );
+
+        let other_public_key = PublicKey::from(&[0xdd; PublicKey::len()]);
+        assert_eq!(
+            stop_friend_currency(&node_report, &other_public_key, &currency1),
+            Err(StopFriendCurrencyError::FriendNotFound)
+        );
+    }
+}
@@ -1,8 +1,8 @@
 use proto::crypto::{PublicKey, Uid};
 
 use proto::app_server::messages::AppRequest;
-use proto::funder::messages::Currency;
-use proto::index_server::messages::{Edge, RequestRoutes};
+use proto::funder::messages::{Currency, Rate};
+use proto::index_server::messages::{Edge, MultiRoute, RequestRoutes};
 
 pub fn request_routes(
     request_routes_id: Uid,
@@ -11,6 +11,7 @@ pub fn request_routes(
     source: PublicKey,
     destination: PublicKey,
     opt_exclude: Option<(PublicKey, PublicKey)>,
+    opt_max_hops: Option<u64>,
 ) -> AppRequest {
     let opt_exclude = opt_exclude.map(|(from_public_key, to_public_key)| Edge {
         from_public_key,
@@ -24,7 +25,88 @@ pub fn request_routes(
         source,
         destination,
         opt_exclude,
+        opt_max_hops,
     };
 
     AppRequest::RequestRoutes(request_routes)
 }
+
+/// Remove routes that are too expensive for sending `dest_payment` credits.
+///
+/// A route is too expensive if the fee for sending all of `dest_payment` through it is larger than
+/// `max_fee_rate.calc_fee(dest_payment)`. For example, `Rate::from_percent(1.0)` only keeps routes
+/// that charge at most 1% of `dest_payment`.
+/// Multi routes that are left without any routes are removed.
+pub fn filter_routes_by_fee(
+    multi_routes: Vec<MultiRoute>,
+    dest_payment: u128,
+    max_fee_rate: &Rate,
+) -> Vec<MultiRoute> {
+    let max_fee = max_fee_rate
+        .calc_fee(dest_payment)
+        .unwrap_or(u128::max_value());
+
+    multi_routes
+        .into_iter()
+        .filter_map(|mut multi_route| {
+            multi_route.routes.retain(|route| {
+                route
+                    .rate
+                    .calc_fee(dest_payment)
+                    .map_or(false, |fee| fee <= max_fee)
+            });
+            if multi_route.routes.is_empty() {
+                None
+            } else {
+                Some(multi_route)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use proto::funder::messages::FriendsRoute;
+    use proto::index_server::messages::RouteCapacityRate;
+
+    fn route_capacity_rate(route_len: u8, rate: Rate) -> RouteCapacityRate {
+        RouteCapacityRate {
+            route: FriendsRoute {
+                public_keys: (0..route_len)
+                    .map(|i| PublicKey::from(&[i; PublicKey::len()]))
+                    .collect(),
+            },
+            capacity: 1000,
+            rate,
+        }
+    }
+
+    #[test]
+    fn test_filter_routes_by_fee() {
+        // A short route, with a single mediator charging a flat fee of 1:
+        let cheap_route = route_capacity_rate(3, Rate { mul: 0, add: 1 });
+        // A long route, where each of the 10 mediators charges a flat fee of 1:
+        let expensive_route = route_capacity_rate(12, Rate { mul: 0, add: 10 });
+
+        let multi_routes = vec![
+            MultiRoute {
+                routes: vec![cheap_route.clone(), expensive_route.clone()],
+            },
+            MultiRoute {
+                routes: vec![expensive_route],
+            },
+        ];
+
+        // Allow fees of up to 1% of the payment:
+        let max_fee_rate = Rate::from_percent(1.0).unwrap();
+        let filtered = filter_routes_by_fee(multi_routes, 500, &max_fee_rate);
+        assert_eq!(
+            filtered,
+            vec![MultiRoute {
+                routes: vec![cheap_route]
+            }]
+        );
+    }
+}
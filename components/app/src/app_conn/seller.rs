@@ -12,6 +12,23 @@ pub fn add_invoice(
         invoice_id,
         currency,
         total_dest_payment,
+        opt_expiry_ticks: None,
+    };
+    AppRequest::AddInvoice(add_invoice)
+}
+
+/// Add an invoice that is canceled automatically after `expiry_ticks` timer ticks.
+pub fn add_invoice_with_expiry(
+    invoice_id: InvoiceId,
+    currency: Currency,
+    total_dest_payment: u128,
+    expiry_ticks: u64,
+) -> AppRequest {
+    let add_invoice = AddInvoice {
+        invoice_id,
+        currency,
+        total_dest_payment,
+        opt_expiry_ticks: Some(expiry_ticks),
     };
     AppRequest::AddInvoice(add_invoice)
 }
@@ -0,0 +1,554 @@
+use std::collections::HashMap;
+
+use futures::channel::{mpsc, oneshot};
+use futures::task::{Spawn, SpawnError, SpawnExt};
+use futures::{future, stream, SinkExt, StreamExt};
+
+use proto::app_server::messages::{AppRequest, AppServerToApp, AppToAppServer};
+use proto::crypto::{PaymentId, Uid};
+use proto::funder::messages::{CancelReason, FriendsRoute, RequestResult, TransactionResult};
+
+use timer::TimerClient;
+
+use crate::app_conn::buyer;
+use crate::connect::ConnPairApp;
+use crate::gen::gen_uid;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum AppBuyerError {
+    /// The connection to the node was lost
+    ConnectionLost,
+    /// A transaction with the same request id is still pending
+    DuplicateRequestId,
+    RequestTimerStreamError,
+    TimerClosed,
+}
+
+type TransactionResponse = Result<TransactionResult, AppBuyerError>;
+
+/// Is a transaction that was canceled for this reason likely to succeed if it is retried?
+///
+/// The reason is an unauthenticated hint: It is not signed, and any node along the route may
+/// report any reason. It only decides whether another attempt is worthwhile, and never whether a
+/// retry is safe. A transaction canceled by a remote node can no longer be collected, so a false
+/// reason can at most cause a bounded number of futile attempts.
+///
+/// A `Timeout` is never retried: Our node gives up on a stale transaction without waiting for
+/// the remote side, so the original transaction might still be collected, and a retry could pay
+/// twice. A local timeout is always reported as `Timeout`, so a remote node can not turn it into
+/// a retry.
+fn is_transient_cancel(cancel_reason: &CancelReason) -> bool {
+    match cancel_reason {
+        CancelReason::ClosedRequests => true,
+        CancelReason::Timeout | CancelReason::NoRoute | CancelReason::InsufficientTrust => false,
+    }
+}
+
+#[derive(Debug)]
+struct TransactionRequest {
+    request_id: Uid,
+    app_to_app_server: AppToAppServer,
+    response_sender: oneshot::Sender<TransactionResponse>,
+}
+
+#[derive(Debug)]
+enum AppBuyerEvent {
+    Transaction(TransactionRequest),
+    Request(AppToAppServer),
+    AppServerToApp(AppServerToApp),
+    ServerClosed,
+}
+
+/// A handle for sending buyer requests to a node.
+///
+/// Every transaction created through `create_transaction()` resolves independently to its own
+/// `TransactionResult`, matched by `request_id`. Therefore multiple transactions (for example,
+/// one for every route of a multi-route payment) may be pending at the same time.
+#[derive(Debug, Clone)]
+pub struct AppBuyer {
+    events_sender: mpsc::Sender<AppBuyerEvent>,
+}
+
+impl AppBuyer {
+    /// Send a request to the node, without waiting for any response.
+    /// Returns the app_request_id of the sent request.
+    pub async fn send_request(&mut self, app_request: AppRequest) -> Result<Uid, AppBuyerError> {
+        let app_request_id = gen_uid();
+        let app_to_app_server = AppToAppServer {
+            app_request_id: app_request_id.clone(),
+            app_request,
+        };
+        self.events_sender
+            .send(AppBuyerEvent::Request(app_to_app_server))
+            .await
+            .map_err(|_| AppBuyerError::ConnectionLost)?;
+        Ok(app_request_id)
+    }
+
+    /// Cancel a payment: Transactions of this payment that were not yet sent are canceled, and no
+    /// new transactions may be created.
+    ///
+    /// Transactions that were already sent can not be canceled, and their credits remain frozen
+    /// until the remote side resolves them. They are listed in a `ResponseCancelPayment`. The
+    /// payment is closed (`ResponseClosePayment`) only after all of them are resolved.
+    pub async fn cancel_payment(&mut self, payment_id: PaymentId) -> Result<Uid, AppBuyerError> {
+        self.send_request(buyer::cancel_payment(payment_id)).await
+    }
+
+    /// Create a transaction, and wait for its result.
+    pub async fn create_transaction(
+        &mut self,
+        payment_id: PaymentId,
+        request_id: Uid,
+        route: FriendsRoute,
+        dest_payment: u128,
+        fees: u128,
+    ) -> Result<TransactionResult, AppBuyerError> {
+        let app_request =
+            buyer::create_transaction(payment_id, request_id.clone(), route, dest_payment, fees);
+        let app_to_app_server = AppToAppServer {
+            app_request_id: gen_uid(),
+            app_request,
+        };
+
+        let (response_sender, response_receiver) = oneshot::channel();
+        let transaction_request = TransactionRequest {
+            request_id,
+            app_to_app_server,
+            response_sender,
+        };
+        self.events_sender
+            .send(AppBuyerEvent::Transaction(transaction_request))
+            .await
+            .map_err(|_| AppBuyerError::ConnectionLost)?;
+
+        response_receiver
+            .await
+            .map_err(|_| AppBuyerError::ConnectionLost)?
+    }
+
+    /// Create a transaction, and wait for its result. If the transaction is canceled for a
+    /// transient reason (For example, a friend along the route does not accept requests at the
+    /// moment), it is created again with a fresh request_id, after waiting `backoff_ticks` ticks
+    /// of `timer_client`. Timeouts are not retried, as the original transaction might still
+    /// complete.
+    ///
+    /// At most `attempts` transactions are created. Definitive failures (and failures without a
+    /// known reason) are returned without retrying.
+    pub async fn create_transaction_retry(
+        &mut self,
+        payment_id: PaymentId,
+        route: FriendsRoute,
+        dest_payment: u128,
+        fees: u128,
+        attempts: usize,
+        mut timer_client: TimerClient,
+        backoff_ticks: usize,
+    ) -> Result<TransactionResult, AppBuyerError> {
+        let mut attempt = 0usize;
+        loop {
+            attempt = attempt.saturating_add(1);
+            let transaction_result = self
+                .create_transaction(
+                    payment_id.clone(),
+                    gen_uid(),
+                    route.clone(),
+                    dest_payment,
+                    fees,
+                )
+                .await?;
+
+            let is_transient = match &transaction_result.result {
+                RequestResult::Failure(Some(cancel_reason)) => is_transient_cancel(cancel_reason),
+                RequestResult::Failure(None)
+                | RequestResult::Success
+                | RequestResult::Complete(_) => false,
+            };
+            if !is_transient || attempt >= attempts {
+                return Ok(transaction_result);
+            }
+
+            // Request a new timer stream for every backoff, so that ticks do not pile up while
+            // waiting for the transaction result:
+            let mut timer_stream = timer_client
+                .request_timer_stream("create_transaction_retry".to_owned())
+                .await
+                .map_err(|_| AppBuyerError::RequestTimerStreamError)?;
+            for _ in 0..backoff_ticks {
+                timer_stream
+                    .next()
+                    .await
+                    .ok_or(AppBuyerError::TimerClosed)?;
+            }
+        }
+    }
+}
+
+/// Spawn a service that sends buyer requests over `conn_pair`, and dispatches incoming
+/// transaction results to the pending `AppBuyer::create_transaction()` calls.
+///
+/// Returns an `AppBuyer` handle, and a receiver for all other messages from the node
+/// (Including transaction results that do not match any pending transaction).
+pub fn create_app_buyer<S>(
+    conn_pair: ConnPairApp,
+    spawner: &S,
+) -> Result<(AppBuyer, mpsc::Receiver<AppServerToApp>), SpawnError>
+where
+    S: Spawn,
+{
+    let (mut sender, receiver) = conn_pair.split();
+    let (events_sender, events_receiver) = mpsc::channel(0);
+    let (mut app_sender, app_receiver) = mpsc::channel(0);
+
+    let from_server = receiver
+        .map(AppBuyerEvent::AppServerToApp)
+        .chain(stream::once(future::ready(AppBuyerEvent::ServerClosed)));
+    let mut incoming_events = stream::select(from_server, events_receiver);
+
+    spawner.spawn(async move {
+        // Pending transactions, by request_id:
+        let mut pending: HashMap<Uid, oneshot::Sender<TransactionResponse>> = HashMap::new();
+        while let Some(event) = incoming_events.next().await {
+            match event {
+                AppBuyerEvent::Transaction(transaction_request) => {
+                    let TransactionRequest {
+                        request_id,
+                        app_to_app_server,
+                        response_sender,
+                    } = transaction_request;
+                    // Discard transactions that were abandoned by their callers:
+                    pending.retain(|_, response_sender| !response_sender.is_canceled());
+                    if pending.contains_key(&request_id) {
+                        let _ = response_sender.send(Err(AppBuyerError::DuplicateRequestId));
+                        continue;
+                    }
+                    // Register the transaction before sending the request, so that its result
+                    // can not be missed:
+                    pending.insert(request_id, response_sender);
+                    if sender.send(app_to_app_server).await.is_err() {
+                        return;
+                    }
+                }
+                AppBuyerEvent::Request(app_to_app_server) => {
+                    if sender.send(app_to_app_server).await.is_err() {
+                        return;
+                    }
+                }
+                AppBuyerEvent::AppServerToApp(AppServerToApp::TransactionResult(
+                    transaction_result,
+                )) => match pending.remove(&transaction_result.request_id) {
+                    Some(response_sender) => {
+                        let _ = response_sender.send(Ok(transaction_result));
+                    }
+                    None => {
+                        let _ = app_sender
+                            .send(AppServerToApp::TransactionResult(transaction_result))
+                            .await;
+                    }
+                },
+                AppBuyerEvent::AppServerToApp(app_server_to_app) => {
+                    let _ = app_sender.send(app_server_to_app).await;
+                }
+                AppBuyerEvent::ServerClosed => return,
+            }
+        }
+    })?;
+
+    Ok((AppBuyer { events_sender }, app_receiver))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::{Arc, Mutex};
+
+    use futures::executor::LocalPool;
+
+    use proto::app_server::messages::ReportMutations;
+    use proto::crypto::PublicKey;
+
+    use timer::create_timer_incoming;
+
+    #[test]
+    fn test_app_buyer_concurrent_transactions() {
+        let mut local_pool = LocalPool::new();
+
+        let (app_sender, mut node_receiver) = mpsc::channel(0);
+        let (mut node_sender, app_receiver) = mpsc::channel(0);
+        let conn_pair = ConnPairApp::from_raw(app_sender, app_receiver);
+
+        let (app_buyer, mut app_receiver) =
+            create_app_buyer(conn_pair, &local_pool.spawner()).unwrap();
+
+        // A mock node that answers both transactions in reverse order:
+        local_pool
+            .spawner()
+            .spawn(async move {
+                let mut request_ids = Vec::new();
+                for _ in 0..2 {
+                    let app_to_app_server: AppToAppServer = node_receiver.next().await.unwrap();
+                    match app_to_app_server.app_request {
+                        AppRequest::CreateTransaction(create_transaction) => {
+                            request_ids.push(create_transaction.request_id)
+                        }
+                        _ => unreachable!(),
+                    }
+                }
+                // An unrelated message, that should be forwarded:
+                node_sender
+                    .send(AppServerToApp::ReportMutations(ReportMutations {
+                        opt_app_request_id: None,
+                        mutations: Vec::new(),
+                    }))
+                    .await
+                    .unwrap();
+                for (index, request_id) in request_ids.into_iter().enumerate().rev() {
+                    let result = if index == 0 {
+                        RequestResult::Success
+                    } else {
+                        RequestResult::Failure(None)
+                    };
+                    node_sender
+                        .send(AppServerToApp::TransactionResult(TransactionResult {
+                            request_id,
+                            result,
+                        }))
+                        .await
+                        .unwrap();
+                }
+                // Keep the connection open until the app is done:
+                let _ = node_receiver.next().await;
+            })
+            .unwrap();
+
+        let request_id0 = Uid::from(&[0; Uid::len()]);
+        let request_id1 = Uid::from(&[1; Uid::len()]);
+        let route = FriendsRoute {
+            public_keys: vec![
+                PublicKey::from(&[0xaa; PublicKey::len()]),
+                PublicKey::from(&[0xbb; PublicKey::len()]),
+            ],
+        };
+
+        let mut app_buyer0 = app_buyer.clone();
+        let mut app_buyer1 = app_buyer;
+        let payment_id = PaymentId::from(&[2; PaymentId::len()]);
+        let (res0, res1) = local_pool.run_until(future::join(
+            app_buyer0.create_transaction(
+                payment_id.clone(),
+                request_id0.clone(),
+                route.clone(),
+                10,
+                0,
+            ),
+            app_buyer1.create_transaction(payment_id, request_id1.clone(), route, 20, 0),
+        ));
+
+        assert_eq!(
+            res0,
+            Ok(TransactionResult {
+                request_id: request_id0,
+                result: RequestResult::Success,
+            })
+        );
+        assert_eq!(
+            res1,
+            Ok(TransactionResult {
+                request_id: request_id1,
+                result: RequestResult::Failure(None),
+            })
+        );
+
+        // Other messages are forwarded:
+        match local_pool.run_until(app_receiver.next()).unwrap() {
+            AppServerToApp::ReportMutations(_) => {}
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_app_buyer_create_transaction_retry() {
+        let mut local_pool = LocalPool::new();
+        let (mut tick_sender, tick_receiver) = mpsc::channel::<()>(0);
+        let timer_client = create_timer_incoming(tick_receiver, local_pool.spawner()).unwrap();
+
+        let (app_sender, mut node_receiver) = mpsc::channel(0);
+        let (mut node_sender, app_receiver) = mpsc::channel(0);
+        let conn_pair = ConnPairApp::from_raw(app_sender, app_receiver);
+
+        let (mut app_buyer, _app_receiver) =
+            create_app_buyer(conn_pair, &local_pool.spawner()).unwrap();
+
+        // A mock node that cancels the first transaction transiently, lets the second one
+        // succeed, and fails all the following transactions definitively:
+        let request_ids = Arc::new(Mutex::new(Vec::new()));
+        let c_request_ids = request_ids.clone();
+        local_pool
+            .spawner()
+            .spawn(async move {
+                while let Some(app_to_app_server) = node_receiver.next().await {
+                    let request_id = match app_to_app_server.app_request {
+                        AppRequest::CreateTransaction(create_transaction) => {
+                            create_transaction.request_id
+                        }
+                        _ => unreachable!(),
+                    };
+                    let mut request_ids = c_request_ids.lock().unwrap();
+                    let result = match request_ids.len() {
+                        0 => RequestResult::Failure(Some(CancelReason::ClosedRequests)),
+                        1 => RequestResult::Success,
+                        _ => RequestResult::Failure(Some(CancelReason::InsufficientTrust)),
+                    };
+                    request_ids.push(request_id.clone());
+                    drop(request_ids);
+                    node_sender
+                        .send(AppServerToApp::TransactionResult(TransactionResult {
+                            request_id,
+                            result,
+                        }))
+                        .await
+                        .unwrap();
+                }
+            })
+            .unwrap();
+
+        // Keep the timer alive:
+        local_pool
+            .spawner()
+            .spawn(async move { while tick_sender.send(()).await.is_ok() {} })
+            .unwrap();
+
+        let route = FriendsRoute {
+            public_keys: vec![
+                PublicKey::from(&[0xaa; PublicKey::len()]),
+                PublicKey::from(&[0xbb; PublicKey::len()]),
+            ],
+        };
+        let payment_id = PaymentId::from(&[2; PaymentId::len()]);
+
+        // The first attempt is canceled transiently, and the second one succeeds:
+        let res = local_pool.run_until(app_buyer.create_transaction_retry(
+            payment_id.clone(),
+            route.clone(),
+            10,
+            0,
+            3,
+            timer_client.clone(),
+            2,
+        ));
+        let transaction_result = res.unwrap();
+        assert_eq!(transaction_result.result, RequestResult::Success);
+        {
+            let request_ids = request_ids.lock().unwrap();
+            assert_eq!(request_ids.len(), 2);
+            // Every attempt uses a fresh request_id:
+            assert_ne!(request_ids[0], request_ids[1]);
+            assert_eq!(transaction_result.request_id, request_ids[1]);
+        }
+
+        // A definitive failure is not retried:
+        let res = local_pool.run_until(app_buyer.create_transaction_retry(
+            payment_id,
+            route,
+            10,
+            0,
+            3,
+            timer_client,
+            2,
+        ));
+        assert_eq!(
+            res.unwrap().result,
+            RequestResult::Failure(Some(CancelReason::InsufficientTrust))
+        );
+        assert_eq!(request_ids.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_app_buyer_create_transaction_retry_timeout() {
+        let mut local_pool = LocalPool::new();
+        let (mut tick_sender, tick_receiver) = mpsc::channel::<()>(0);
+        let timer_client = create_timer_incoming(tick_receiver, local_pool.spawner()).unwrap();
+
+        let (app_sender, mut node_receiver) = mpsc::channel(0);
+        let (mut node_sender, app_receiver) = mpsc::channel(0);
+        let conn_pair = ConnPairApp::from_raw(app_sender, app_receiver);
+
+        let (mut app_buyer, mut app_receiver) =
+            create_app_buyer(conn_pair, &local_pool.spawner()).unwrap();
+
+        // A mock node that reports a timeout for the first transaction, and later lets the same
+        // transaction succeed:
+        let request_ids = Arc::new(Mutex::new(Vec::new()));
+        let c_request_ids = request_ids.clone();
+        local_pool
+            .spawner()
+            .spawn(async move {
+                while let Some(app_to_app_server) = node_receiver.next().await {
+                    let request_id = match app_to_app_server.app_request {
+                        AppRequest::CreateTransaction(create_transaction) => {
+                            create_transaction.request_id
+                        }
+                        _ => unreachable!(),
+                    };
+                    c_request_ids.lock().unwrap().push(request_id.clone());
+                    for result in vec![
+                        RequestResult::Failure(Some(CancelReason::Timeout)),
+                        RequestResult::Success,
+                    ] {
+                        node_sender
+                            .send(AppServerToApp::TransactionResult(TransactionResult {
+                                request_id: request_id.clone(),
+                                result,
+                            }))
+                            .await
+                            .unwrap();
+                    }
+                }
+            })
+            .unwrap();
+
+        // Keep the timer alive:
+        local_pool
+            .spawner()
+            .spawn(async move { while tick_sender.send(()).await.is_ok() {} })
+            .unwrap();
+
+        let route = FriendsRoute {
+            public_keys: vec![
+                PublicKey::from(&[0xaa; PublicKey::len()]),
+                PublicKey::from(&[0xbb; PublicKey::len()]),
+            ],
+        };
+        let payment_id = PaymentId::from(&[2; PaymentId::len()]);
+
+        // The timeout is returned, without creating another transaction:
+        let res = local_pool.run_until(app_buyer.create_transaction_retry(
+            payment_id,
+            route,
+            10,
+            0,
+            3,
+            timer_client,
+            2,
+        ));
+        let transaction_result = res.unwrap();
+        assert_eq!(
+            transaction_result.result,
+            RequestResult::Failure(Some(CancelReason::Timeout))
+        );
+
+        // The original transaction succeeds later, and it is the only transaction we paid for:
+        match local_pool.run_until(app_receiver.next()).unwrap() {
+            AppServerToApp::TransactionResult(late_transaction_result) => {
+                assert_eq!(
+                    late_transaction_result.request_id,
+                    transaction_result.request_id
+                );
+                assert_eq!(late_transaction_result.result, RequestResult::Success);
+            }
+            _ => unreachable!(),
+        }
+        assert_eq!(request_ids.lock().unwrap().len(), 1);
+    }
+}
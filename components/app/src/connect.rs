@@ -2,12 +2,11 @@ use std::time::Duration;
 
 use futures::task::Spawn;
 
-use common::conn::ConnPair;
+use common::conn::{ConnPair, FutTransform};
 use common::int_convert::usize_to_u64;
 
 use proto::app_server::messages::{AppPermissions, AppServerToApp, AppToAppServer, NodeReport};
-use proto::consts::MAX_FRAME_LENGTH;
-use proto::consts::TICK_MS;
+use proto::consts::{MAX_FRAME_LENGTH, PROTOCOL_VERSION, TICK_MS};
 use proto::crypto::PublicKey;
 use proto::net::messages::NetAddress;
 
@@ -16,9 +15,11 @@ use crypto::rand::system_random;
 use identity::IdentityClient;
 use net::TcpConnector;
 use timer::create_timer;
+use timer::utils::future_timeout;
 
-use app_client::app_connect_to_node;
-use connection::create_secure_connector;
+use app_client::setup_connection;
+use connection::{create_encrypt_keepalive, CONN_TIMEOUT_TICKS};
+use version::{negotiate_version, NegotiateVersionError, VersionPolicy};
 
 /// A connection of an App to a Node
 pub type ConnPairApp = ConnPair<AppToAppServer, AppServerToApp>;
@@ -26,15 +27,52 @@ pub type ConnPairApp = ConnPair<AppToAppServer, AppServerToApp>;
 pub type AppConnTuple = (AppPermissions, NodeReport, ConnPairApp);
 
 #[derive(Debug)]
-pub struct ConnectError;
+pub enum ConnectError {
+    CreateTimerError,
+    /// Could not open a network connection to the node
+    NetConnectError,
+    /// The node did not declare its protocol version in time
+    VersionTimeout,
+    NegotiateVersionError(NegotiateVersionError),
+    /// The node's protocol version is not allowed by the version policy
+    IncompatibleVersion(u32),
+    EncryptSetupError,
+    SetupConnectionError,
+}
 
 /// Connect to a remote offset-node.
+/// The node must use exactly our protocol version.
 pub async fn connect<S>(
     node_public_key: PublicKey,
     node_net_address: NetAddress,
     app_identity_client: IdentityClient,
     spawner: S,
 ) -> Result<AppConnTuple, ConnectError>
+where
+    S: Spawn + Clone + Send + 'static,
+{
+    connect_with_version_policy(
+        node_public_key,
+        node_net_address,
+        app_identity_client,
+        VersionPolicy::Exact(PROTOCOL_VERSION),
+        spawner,
+    )
+    .await
+}
+
+/// Connect to a remote offset-node, allowing only node protocol versions that match
+/// `version_policy`.
+///
+/// Returns `ConnectError::IncompatibleVersion` if the node declared a version outside of the
+/// policy.
+pub async fn connect_with_version_policy<S>(
+    node_public_key: PublicKey,
+    node_net_address: NetAddress,
+    app_identity_client: IdentityClient,
+    version_policy: VersionPolicy,
+    spawner: S,
+) -> Result<AppConnTuple, ConnectError>
 where
     S: Spawn + Clone + Send + 'static,
 {
@@ -43,20 +81,46 @@ where
 
     // Get a timer client:
     let dur = Duration::from_millis(usize_to_u64(TICK_MS).unwrap());
-    let timer_client = create_timer(dur, spawner.clone()).map_err(|_| ConnectError)?;
+    let mut timer_client =
+        create_timer(dur, spawner.clone()).map_err(|_| ConnectError::CreateTimerError)?;
 
     // A tcp connector, Used to connect to remote servers:
-    let tcp_connector = TcpConnector::new(MAX_FRAME_LENGTH, spawner.clone());
+    let mut tcp_connector = TcpConnector::new(MAX_FRAME_LENGTH, spawner.clone());
+    let conn_pair = tcp_connector
+        .transform(node_net_address)
+        .await
+        .ok_or(ConnectError::NetConnectError)?;
 
-    let secure_connector = create_secure_connector(
-        tcp_connector,
-        timer_client,
-        app_identity_client,
-        rng,
-        spawner.clone(),
-    );
+    // Exchange protocol versions with the node:
+    let timer_stream = timer_client
+        .request_timer_stream("connect_with_version_policy".to_owned())
+        .await
+        .map_err(|_| ConnectError::CreateTimerError)?;
+    let negotiate_fut = Box::pin(negotiate_version(
+        conn_pair,
+        PROTOCOL_VERSION,
+        version_policy,
+    ));
+    let (_remote_version, conn_pair) =
+        future_timeout(negotiate_fut, timer_stream, CONN_TIMEOUT_TICKS)
+            .await
+            .ok_or(ConnectError::VersionTimeout)?
+            .map_err(|e| match e {
+                NegotiateVersionError::IncompatibleVersion(remote_version) => {
+                    ConnectError::IncompatibleVersion(remote_version)
+                }
+                e => ConnectError::NegotiateVersionError(e),
+            })?;
+
+    // Encrypt and keepalive:
+    let mut encrypt_keepalive =
+        create_encrypt_keepalive(timer_client, app_identity_client, rng, spawner.clone());
+    let (_public_key, conn_pair) = encrypt_keepalive
+        .transform((Some(node_public_key), conn_pair))
+        .await
+        .ok_or(ConnectError::EncryptSetupError)?;
 
-    app_connect_to_node(secure_connector, node_public_key, node_net_address, spawner)
+    setup_connection(conn_pair, spawner)
         .await
-        .map_err(|_| ConnectError)
+        .map_err(|_| ConnectError::SetupConnectionError)
 }
@@ -1,6 +1,14 @@
+use std::convert::TryFrom;
+
+use base64::{self, URL_SAFE_NO_PAD};
+
 use crypto::rand::{system_random, RandGen};
 
-use proto::crypto::{InvoiceId, PaymentId, Uid};
+use proto::crypto::{InvoiceId, PaymentId, PublicKey, Uid};
+use proto::file::InvoiceFile;
+use proto::funder::messages::Currency;
+
+use signature::canonical::CanonicalSerialize;
 
 // TODO: Use Gen trait here instead?
 
@@ -27,3 +35,106 @@ pub fn gen_payment_id() -> PaymentId {
 
     PaymentId::rand_gen(&rng)
 }
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ShareableInvoiceError {
+    Base64DecodeError,
+    InvalidLength,
+    InvalidCurrency,
+}
+
+/// Encode an invoice as a compact string, suitable for embedding inside URLs or QR codes.
+/// The string is a URL-safe base64 encoding of the canonical serialization of the invoice.
+pub fn invoice_to_shareable_string(invoice_file: &InvoiceFile) -> String {
+    let mut data = Vec::new();
+    data.extend_from_slice(&invoice_file.invoice_id);
+    data.extend_from_slice(&invoice_file.dest_public_key);
+    data.extend_from_slice(&invoice_file.dest_payment.canonical_serialize());
+    // Currency has variable length, so we put it last:
+    data.extend_from_slice(&invoice_file.currency.canonical_serialize());
+
+    base64::encode_config(&data, URL_SAFE_NO_PAD)
+}
+
+/// Parse an invoice created by `invoice_to_shareable_string`
+pub fn invoice_from_shareable_string(
+    shareable_string: &str,
+) -> Result<InvoiceFile, ShareableInvoiceError> {
+    let data = base64::decode_config(shareable_string, URL_SAFE_NO_PAD)
+        .map_err(|_| ShareableInvoiceError::Base64DecodeError)?;
+
+    let fixed_len = InvoiceId::len() + PublicKey::len() + 16;
+    if data.len() <= fixed_len {
+        return Err(ShareableInvoiceError::InvalidLength);
+    }
+
+    let (invoice_id_data, rest) = data.split_at(InvoiceId::len());
+    let (public_key_data, rest) = rest.split_at(PublicKey::len());
+    let (dest_payment_data, currency_data) = rest.split_at(16);
+
+    let mut dest_payment_bytes = [0u8; 16];
+    dest_payment_bytes.copy_from_slice(dest_payment_data);
+
+    let currency_str = String::from_utf8(currency_data.to_vec())
+        .map_err(|_| ShareableInvoiceError::InvalidCurrency)?;
+
+    Ok(InvoiceFile {
+        // Lengths were verified above, so these conversions can not fail:
+        invoice_id: InvoiceId::try_from(invoice_id_data).unwrap(),
+        dest_public_key: PublicKey::try_from(public_key_data).unwrap(),
+        dest_payment: u128::from_be_bytes(dest_payment_bytes),
+        currency: Currency::try_from(currency_str)
+            .map_err(|_| ShareableInvoiceError::InvalidCurrency)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_invoice_file() -> InvoiceFile {
+        InvoiceFile {
+            invoice_id: InvoiceId::from(&[1u8; InvoiceId::len()]),
+            currency: Currency::try_from("FST".to_owned()).unwrap(),
+            dest_public_key: PublicKey::from(&[0xbb; PublicKey::len()]),
+            dest_payment: 0x1234_5678_9abc_def0u128,
+        }
+    }
+
+    #[test]
+    fn test_shareable_invoice_roundtrip() {
+        let invoice_file = example_invoice_file();
+        let shareable_string = invoice_to_shareable_string(&invoice_file);
+        assert_eq!(
+            invoice_from_shareable_string(&shareable_string).unwrap(),
+            invoice_file
+        );
+    }
+
+    #[test]
+    fn test_shareable_invoice_url_safe() {
+        let shareable_string = invoice_to_shareable_string(&example_invoice_file());
+        // Only unreserved URL characters (RFC 3986) are allowed:
+        assert!(shareable_string
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+    }
+
+    #[test]
+    fn test_shareable_invoice_invalid() {
+        assert_eq!(
+            invoice_from_shareable_string("not a valid string!"),
+            Err(ShareableInvoiceError::Base64DecodeError)
+        );
+
+        let shareable_string = invoice_to_shareable_string(&example_invoice_file());
+        let mut data = base64::decode_config(&shareable_string, URL_SAFE_NO_PAD).unwrap();
+        // Remove the currency:
+        data.truncate(data.len() - "FST".len());
+        let truncated = base64::encode_config(&data, URL_SAFE_NO_PAD);
+        assert_eq!(
+            invoice_from_shareable_string(&truncated),
+            Err(ShareableInvoiceError::InvalidLength)
+        );
+    }
+}
@@ -29,7 +29,8 @@ where
     S: Spawn,
 {
     // Parse identity file:
-    let identity_file: IdentityFile = deserialize_from_string(&fs::read_to_string(&idfile_path)?)?;
+    let identity_file: IdentityFile = deserialize_from_string(&fs::read_to_string(&idfile_path)?)
+        .map_err(|e| e.with_path(idfile_path))?;
     let identity = SoftwareEd25519Identity::from_private_key(&identity_file.private_key)
         .map_err(|_| IdentityFromFileError::LoadIdentityError)?;
 
@@ -40,3 +41,28 @@ where
         .map_err(|_| IdentityFromFileError::CreateIdentityError)?;
     Ok(IdentityClient::new(sender))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::executor::LocalPool;
+
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_identity_from_file_malformed() {
+        let dir = tempdir().unwrap();
+        let idfile_path = dir.path().join("malformed.ident");
+        fs::write(&idfile_path, "{\"private_key\": 3}").unwrap();
+
+        let local_pool = LocalPool::new();
+        match identity_from_file(&idfile_path, local_pool.spawner()) {
+            Err(IdentityFromFileError::StringSerdeError(e)) => {
+                // The error mentions the malformed file:
+                assert!(e.to_string().contains(&*idfile_path.to_string_lossy()));
+            }
+            _ => unreachable!(),
+        }
+    }
+}
@@ -10,13 +10,21 @@
 extern crate log;
 
 mod app_conn;
+mod buyer;
 mod connect;
 mod identity;
+mod log_conn;
+mod ping;
+mod reconnect;
+mod seller;
 mod types;
 
 /// Utils for random generation of types
 pub mod gen;
 
+/// Offline route search over node reports
+pub mod route_search;
+
 /// Utils for serializing and deserializing
 pub mod ser_utils {
     pub use common::ser_utils::*;
@@ -32,7 +40,8 @@ pub mod common {
         Signature, Uid,
     };
     pub use proto::funder::messages::{
-        Commit, Currency, FriendsRoute, PaymentStatus, PaymentStatusSuccess, Rate, Receipt,
+        Commit, CommitMismatch, Currency, FriendStatus, FriendsRoute, ParseStatusError,
+        PaymentStatus, PaymentStatusSuccess, Rate, Receipt, RequestsStatus,
     };
     pub use proto::index_server::messages::{
         MultiRoute, NamedIndexServerAddress, RouteCapacityRate,
@@ -46,34 +55,56 @@ pub use proto::file;
 /// Offset connection
 pub mod conn {
     pub use super::app_conn::{buyer, config, routes, seller};
-    pub use super::connect::{connect, AppConnTuple, ConnPairApp, ConnectError};
+    pub use super::buyer::{create_app_buyer, AppBuyer, AppBuyerError};
+    pub use super::connect::{
+        connect, connect_with_version_policy, AppConnTuple, ConnPairApp, ConnectError,
+    };
     pub use super::identity::{identity_from_file, IdentityFromFileError};
+    pub use super::log_conn::{log_conn_pair, AppMessageFilter, AppMessageKind};
+    pub use super::ping::{ping, PingError};
+    pub use super::reconnect::{NodeConnector, ReconnectingAppConn, ReconnectingAppConnError};
+    pub use super::seller::{AppSeller, AppSellerError};
     pub use proto::app_server::messages::{
-        AppPermissions, AppRequest, AppServerToApp, AppToAppServer,
+        AppPermissions, AppRequest, AppRequestKind, AppServerToApp, AppToAppServer,
+    };
+    pub use proto::funder::messages::{
+        CancelReason, CommitInvoiceResult, FriendRelaysResult, RequestResult,
+        ResponseCancelPayment, ResponseClosePayment, ResponseCommitInvoice, ResponseFriendRelays,
+        TransactionResult,
     };
-    pub use proto::funder::messages::{RequestResult, ResponseClosePayment};
     pub use proto::index_client::messages::{ClientResponseRoutes, ResponseRoutesResult};
+    pub use version::{NegotiateVersionError, VersionPolicy};
 }
 
 // TODO: Possibly reduce what we export from report in the future?
 /// Report related types
 pub mod report {
     pub use proto::report::messages::{
-        AddFriendReport, ChannelConsistentReport, ChannelInconsistentReport, ChannelStatusReport,
-        CurrencyConfigReport, CurrencyReport, FriendLivenessReport, FriendReport,
-        FriendStatusReport, FunderReport, McBalanceReport, MoveTokenHashedReport,
+        AddFriendReport, AmbiguousFriendName, ChannelConsistentReport, ChannelInconsistentReport,
+        ChannelStatusReport, CurrencyConfigReport, CurrencyInfo, CurrencyReport,
+        FriendLivenessReport, FriendReport, FriendReportMutation, FriendStatusReport, FunderReport,
+        FunderReportMutateError, FunderReportMutation, McBalanceReport, MoveTokenHashedReport,
         RequestsStatusReport, ResetTermsReport,
     };
 
     pub use proto::funder::messages::{
-        BalanceInfo, CountersInfo, CurrencyBalance, CurrencyBalanceInfo, McInfo, TokenInfo,
+        BalanceInfo, CountersInfo, CurrencyBalance, CurrencyBalanceInfo, InconsistencyReason,
+        McInfo, TokenInfo,
     };
 
-    pub use proto::app_server::messages::NodeReport;
+    pub use proto::app_server::messages::{
+        NodeReport, NodeReportMutateError, NodeReportMutation, ReportMutations,
+        ReportMutationsError,
+    };
     pub use proto::index_client::messages::{AddIndexServer, IndexClientReport};
+    pub use proto::report::convert::friend_max_payable;
+    pub use proto::report::diff::{funder_report_diff, FunderReportChange};
 }
 
 /// Verification functions
 pub mod verify {
-    pub use signature::verify::{verify_commit, verify_move_token_hashed_report, verify_receipt};
+    pub use signature::verify::{
+        verify_commit, verify_move_token_hashed_report, verify_move_token_hashed_report_signer,
+        verify_receipt,
+    };
 }
@@ -0,0 +1,183 @@
+use std::collections::HashSet;
+
+use futures::{future, SinkExt, StreamExt};
+
+use common::conn::SinkError;
+
+use proto::app_server::messages::{AppRequestKind, AppServerToApp, AppToAppServer};
+
+use crate::connect::ConnPairApp;
+
+/// The kind of a message passed between an app and a node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AppMessageKind {
+    /// A request sent by the app, by the permission it requires
+    Request(AppRequestKind),
+    TransactionResult,
+    ResponseClosePayment,
+    ReportMutations,
+    ResponseRoutes,
+    ResponseCommitInvoice,
+    ResponseFriendRelays,
+    ResponseCancelPayment,
+}
+
+impl AppMessageKind {
+    fn from_app_to_app_server(app_to_app_server: &AppToAppServer) -> Self {
+        AppMessageKind::Request(app_to_app_server.app_request.kind())
+    }
+
+    fn from_app_server_to_app(app_server_to_app: &AppServerToApp) -> Self {
+        match app_server_to_app {
+            AppServerToApp::TransactionResult(_) => AppMessageKind::TransactionResult,
+            AppServerToApp::ResponseClosePayment(_) => AppMessageKind::ResponseClosePayment,
+            AppServerToApp::ReportMutations(_) => AppMessageKind::ReportMutations,
+            AppServerToApp::ResponseRoutes(_) => AppMessageKind::ResponseRoutes,
+            AppServerToApp::ResponseCommitInvoice(_) => AppMessageKind::ResponseCommitInvoice,
+            AppServerToApp::ResponseFriendRelays(_) => AppMessageKind::ResponseFriendRelays,
+            AppServerToApp::ResponseCancelPayment(_) => AppMessageKind::ResponseCancelPayment,
+        }
+    }
+}
+
+/// The kinds of messages to log
+#[derive(Debug, Clone)]
+pub struct AppMessageFilter {
+    kinds: HashSet<AppMessageKind>,
+}
+
+impl AppMessageFilter {
+    /// Log only messages of the given kinds
+    pub fn new(kinds: &[AppMessageKind]) -> Self {
+        AppMessageFilter {
+            kinds: kinds.iter().cloned().collect(),
+        }
+    }
+
+    /// Log messages of all kinds
+    pub fn all() -> Self {
+        AppMessageFilter::new(&[
+            AppMessageKind::Request(AppRequestKind::Routes),
+            AppMessageKind::Request(AppRequestKind::Buyer),
+            AppMessageKind::Request(AppRequestKind::Seller),
+            AppMessageKind::Request(AppRequestKind::Config),
+            AppMessageKind::Request(AppRequestKind::Ping),
+            AppMessageKind::TransactionResult,
+            AppMessageKind::ResponseClosePayment,
+            AppMessageKind::ReportMutations,
+            AppMessageKind::ResponseRoutes,
+            AppMessageKind::ResponseCommitInvoice,
+            AppMessageKind::ResponseFriendRelays,
+            AppMessageKind::ResponseCancelPayment,
+        ])
+    }
+
+    pub fn allows(&self, kind: AppMessageKind) -> bool {
+        self.kinds.contains(&kind)
+    }
+}
+
+/// Call `on_message` for every message passed through `conn_pair` that is allowed by `filter`.
+/// Messages are passed through unchanged.
+fn inspect_conn_pair<F>(
+    conn_pair: ConnPairApp,
+    filter: AppMessageFilter,
+    on_message: F,
+) -> ConnPairApp
+where
+    F: Fn(AppMessageKind, String) + Clone + Send + 'static,
+{
+    let (sender, receiver) = conn_pair.split();
+
+    let c_filter = filter.clone();
+    let c_on_message = on_message.clone();
+    let sender = sender.with(move |app_to_app_server: AppToAppServer| {
+        let kind = AppMessageKind::from_app_to_app_server(&app_to_app_server);
+        if c_filter.allows(kind) {
+            c_on_message(kind, format!("App -> Node: {:?}", app_to_app_server));
+        }
+        future::ready(Ok::<_, SinkError>(app_to_app_server))
+    });
+
+    let receiver = receiver.inspect(move |app_server_to_app| {
+        let kind = AppMessageKind::from_app_server_to_app(app_server_to_app);
+        if filter.allows(kind) {
+            on_message(kind, format!("Node -> App: {:?}", app_server_to_app));
+        }
+    });
+
+    ConnPairApp::from_raw(sender, receiver)
+}
+
+/// Log (at debug level) every message passed between an app and a node that is allowed by
+/// `filter`. Useful for debugging app <-> node traffic. Does not affect the flow of messages.
+pub fn log_conn_pair(conn_pair: ConnPairApp, filter: AppMessageFilter) -> ConnPairApp {
+    inspect_conn_pair(conn_pair, filter, |_kind, message| {
+        log::debug!("{}", message)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::{Arc, Mutex};
+
+    use futures::channel::mpsc;
+    use futures::executor::block_on;
+
+    use proto::app_server::messages::{AppRequest, ReportMutations};
+    use proto::crypto::{PaymentId, PublicKey, Uid};
+
+    #[test]
+    fn test_inspect_conn_pair_filter() {
+        let (app_sender, mut node_receiver) = mpsc::channel(8);
+        let (mut node_sender, app_receiver) = mpsc::channel(8);
+        let conn_pair = ConnPairApp::from_raw(app_sender, app_receiver);
+
+        let logged = Arc::new(Mutex::new(Vec::new()));
+        let c_logged = logged.clone();
+        let filter = AppMessageFilter::new(&[
+            AppMessageKind::Request(AppRequestKind::Config),
+            AppMessageKind::ReportMutations,
+        ]);
+        let conn_pair = inspect_conn_pair(conn_pair, filter, move |kind, _message| {
+            c_logged.lock().unwrap().push(kind)
+        });
+        let (mut sender, mut receiver) = conn_pair.split();
+
+        let config_request = AppToAppServer::new(
+            Uid::from(&[0; Uid::len()]),
+            AppRequest::EnableFriend(PublicKey::from(&[0xbb; PublicKey::len()])),
+        );
+        let buyer_request = AppToAppServer::new(
+            Uid::from(&[1; Uid::len()]),
+            AppRequest::RequestClosePayment(PaymentId::from(&[2; PaymentId::len()])),
+        );
+        let report_mutations = || {
+            AppServerToApp::ReportMutations(ReportMutations {
+                opt_app_request_id: None,
+                mutations: Vec::new(),
+            })
+        };
+
+        block_on(async {
+            sender.send(config_request.clone()).await.unwrap();
+            sender.send(buyer_request.clone()).await.unwrap();
+            node_sender.send(report_mutations()).await.unwrap();
+
+            // All messages pass through, whether logged or not:
+            assert_eq!(node_receiver.next().await.unwrap(), config_request);
+            assert_eq!(node_receiver.next().await.unwrap(), buyer_request);
+            assert_eq!(receiver.next().await.unwrap(), report_mutations());
+        });
+
+        assert_eq!(
+            *logged.lock().unwrap(),
+            vec![
+                AppMessageKind::Request(AppRequestKind::Config),
+                AppMessageKind::ReportMutations,
+            ]
+        );
+    }
+}
@@ -0,0 +1,173 @@
+use std::mem;
+
+use futures::future::{self, Either};
+use futures::{stream, SinkExt, StreamExt};
+
+use proto::app_server::messages::{AppRequest, AppServerToApp, AppToAppServer};
+
+use timer::TimerClient;
+
+use crate::connect::ConnPairApp;
+use crate::gen::gen_uid;
+
+#[derive(Debug)]
+pub enum PingError {
+    RequestTimerStreamError,
+    TimerClosed,
+    ConnectionLost,
+    Timeout,
+}
+
+/// Check if a connected node is responsive.
+///
+/// Sends a ping request, and waits for the node to acknowledge it. Returns the round trip time,
+/// measured in ticks of `timer_client`. Fails if no acknowledgement arrives within `max_ticks`
+/// ticks.
+///
+/// Any other message received from the node while waiting is kept, and will be the next message
+/// received from `conn_pair`.
+pub async fn ping(
+    conn_pair: &mut ConnPairApp,
+    mut timer_client: TimerClient,
+    max_ticks: usize,
+) -> Result<usize, PingError> {
+    let mut timer_stream = timer_client
+        .request_timer_stream("ping".to_owned())
+        .await
+        .map_err(|_| PingError::RequestTimerStreamError)?;
+
+    let app_request_id = gen_uid();
+    let app_to_app_server = AppToAppServer {
+        app_request_id: app_request_id.clone(),
+        app_request: AppRequest::Ping,
+    };
+    conn_pair
+        .sender
+        .send(app_to_app_server)
+        .await
+        .map_err(|_| PingError::ConnectionLost)?;
+
+    let mut unrelated = Vec::new();
+    let mut ticks = 0usize;
+    let res = loop {
+        match future::select(timer_stream.next(), conn_pair.receiver.next()).await {
+            Either::Left((Some(_tick), _)) => {
+                ticks = ticks.saturating_add(1);
+                if ticks >= max_ticks {
+                    break Err(PingError::Timeout);
+                }
+            }
+            Either::Left((None, _)) => break Err(PingError::TimerClosed),
+            Either::Right((Some(app_server_to_app), _)) => {
+                if let AppServerToApp::ReportMutations(report_mutations) = &app_server_to_app {
+                    if report_mutations.opt_app_request_id == Some(app_request_id.clone()) {
+                        break Ok(ticks);
+                    }
+                }
+                unrelated.push(app_server_to_app);
+            }
+            Either::Right((None, _)) => break Err(PingError::ConnectionLost),
+        }
+    };
+
+    // Pass the messages we are not interested in back to the caller:
+    if !unrelated.is_empty() {
+        let receiver = mem::replace(&mut conn_pair.receiver, Box::pin(stream::empty()));
+        conn_pair.receiver = Box::pin(stream::iter(unrelated).chain(receiver));
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::channel::mpsc;
+    use futures::executor::LocalPool;
+    use futures::task::SpawnExt;
+
+    use proto::app_server::messages::ReportMutations;
+
+    use timer::create_timer_incoming;
+
+    #[test]
+    fn test_ping_round_trip() {
+        let mut local_pool = LocalPool::new();
+        let (mut tick_sender, tick_receiver) = mpsc::channel::<()>(0);
+        let timer_client = create_timer_incoming(tick_receiver, local_pool.spawner()).unwrap();
+
+        let (app_sender, mut node_receiver) = mpsc::channel(0);
+        let (mut node_sender, app_receiver) = mpsc::channel(0);
+        let mut conn_pair = ConnPairApp::from_raw(app_sender, app_receiver);
+
+        // A mock node that acknowledges the ping request after 3 ticks:
+        local_pool
+            .spawner()
+            .spawn(async move {
+                let app_to_app_server: AppToAppServer = node_receiver.next().await.unwrap();
+                assert_eq!(app_to_app_server.app_request, AppRequest::Ping);
+                for _ in 0..3 {
+                    tick_sender.send(()).await.unwrap();
+                }
+                // An unrelated message, that should be passed through:
+                node_sender
+                    .send(AppServerToApp::ReportMutations(ReportMutations {
+                        opt_app_request_id: None,
+                        mutations: Vec::new(),
+                    }))
+                    .await
+                    .unwrap();
+                node_sender
+                    .send(AppServerToApp::ReportMutations(ReportMutations {
+                        opt_app_request_id: Some(app_to_app_server.app_request_id),
+                        mutations: Vec::new(),
+                    }))
+                    .await
+                    .unwrap();
+                // Keep the timer alive:
+                while tick_sender.send(()).await.is_ok() {}
+            })
+            .unwrap();
+
+        let res = local_pool.run_until(ping(&mut conn_pair, timer_client, 0x10));
+        match res {
+            Ok(ticks) => assert!(ticks <= 3),
+            Err(_) => unreachable!(),
+        }
+
+        // The unrelated message is still available:
+        let app_server_to_app = local_pool.run_until(conn_pair.receiver.next()).unwrap();
+        match app_server_to_app {
+            AppServerToApp::ReportMutations(report_mutations) => {
+                assert_eq!(report_mutations.opt_app_request_id, None)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_ping_timeout() {
+        let mut local_pool = LocalPool::new();
+        let (mut tick_sender, tick_receiver) = mpsc::channel::<()>(0);
+        let timer_client = create_timer_incoming(tick_receiver, local_pool.spawner()).unwrap();
+
+        let (app_sender, mut node_receiver) = mpsc::channel(0);
+        let (_node_sender, app_receiver) = mpsc::channel(0);
+        let mut conn_pair = ConnPairApp::from_raw(app_sender, app_receiver);
+
+        // A mock node that never responds:
+        local_pool
+            .spawner()
+            .spawn(async move {
+                let _app_to_app_server: AppToAppServer = node_receiver.next().await.unwrap();
+                while tick_sender.send(()).await.is_ok() {}
+            })
+            .unwrap();
+
+        let res = local_pool.run_until(ping(&mut conn_pair, timer_client, 4));
+        match res {
+            Err(PingError::Timeout) => {}
+            _ => unreachable!(),
+        }
+    }
+}
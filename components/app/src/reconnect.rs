@@ -0,0 +1,404 @@
+use std::collections::{HashSet, VecDeque};
+
+use futures::task::Spawn;
+use futures::{SinkExt, StreamExt};
+
+use common::conn::{BoxFuture, FutTransform};
+
+use proto::app_server::messages::{
+    AppPermissions, AppRequest, AppServerToApp, AppToAppServer, NodeReport,
+};
+use proto::crypto::{PublicKey, Uid};
+use proto::net::messages::NetAddress;
+
+use identity::IdentityClient;
+
+use crate::connect::{connect, AppConnTuple, ConnPairApp};
+use crate::gen::gen_uid;
+
+#[derive(Debug)]
+pub enum ReconnectingAppConnError {
+    /// Failed to connect to the node.
+    /// The node might be temporarily unreachable. A new connection attempt is made on the next
+    /// operation.
+    ConnectError,
+    /// Connection to the node was lost.
+    /// Contains the ids of requests that were sent, but were not yet acknowledged by the node.
+    /// It is not known if the node has processed those requests.
+    ConnectionLost(Vec<Uid>),
+}
+
+impl ReconnectingAppConnError {
+    /// Can the failed operation be attempted again?
+    /// A new connection will be established on the next attempt.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ReconnectingAppConnError::ConnectError
+            | ReconnectingAppConnError::ConnectionLost(_) => true,
+        }
+    }
+}
+
+/// Connects to a remote node, using stored credentials.
+#[derive(Clone)]
+pub struct NodeConnector<S> {
+    node_public_key: PublicKey,
+    node_net_address: NetAddress,
+    app_identity_client: IdentityClient,
+    spawner: S,
+}
+
+impl<S> NodeConnector<S> {
+    pub fn new(
+        node_public_key: PublicKey,
+        node_net_address: NetAddress,
+        app_identity_client: IdentityClient,
+        spawner: S,
+    ) -> Self {
+        NodeConnector {
+            node_public_key,
+            node_net_address,
+            app_identity_client,
+            spawner,
+        }
+    }
+}
+
+impl<S> FutTransform for NodeConnector<S>
+where
+    S: Spawn + Clone + Send + 'static,
+{
+    type Input = ();
+    type Output = Option<AppConnTuple>;
+
+    fn transform(&mut self, _input: Self::Input) -> BoxFuture<'_, Self::Output> {
+        Box::pin(async move {
+            connect(
+                self.node_public_key.clone(),
+                self.node_net_address.clone(),
+                self.app_identity_client.clone(),
+                self.spawner.clone(),
+            )
+            .await
+            .ok()
+        })
+    }
+}
+
+/// A connection to a node that is reestablished automatically when lost.
+///
+/// After a reconnection, the node report is replaced by the fresh report sent by the node, as
+/// report mutations might have been missed while disconnected.
+///
+/// Requests can be built using the helpers in `conn::{buyer, config, routes, seller}`, and sent
+/// using `send_request()` or `request()`.
+pub struct ReconnectingAppConn<C> {
+    connector: C,
+    opt_conn_pair: Option<ConnPairApp>,
+    app_permissions: AppPermissions,
+    node_report: NodeReport,
+    /// Requests that were sent but not yet acknowledged by the node
+    inflight_requests: HashSet<Uid>,
+    /// Messages received while waiting for an acknowledgement in `request()`.
+    /// Returned by `recv()` before any new message.
+    pending_messages: VecDeque<AppServerToApp>,
+}
+
+impl<C> ReconnectingAppConn<C>
+where
+    C: FutTransform<Input = (), Output = Option<AppConnTuple>>,
+{
+    /// Connect to the node for the first time
+    pub async fn new(mut connector: C) -> Result<Self, ReconnectingAppConnError> {
+        let (app_permissions, node_report, conn_pair) = connector
+            .transform(())
+            .await
+            .ok_or(ReconnectingAppConnError::ConnectError)?;
+
+        Ok(ReconnectingAppConn {
+            connector,
+            opt_conn_pair: Some(conn_pair),
+            app_permissions,
+            node_report,
+            inflight_requests: HashSet::new(),
+            pending_messages: VecDeque::new(),
+        })
+    }
+
+    pub fn app_permissions(&self) -> &AppPermissions {
+        &self.app_permissions
+    }
+
+    /// The current node report, kept up to date with received report mutations.
+    pub fn node_report(&self) -> &NodeReport {
+        &self.node_report
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.opt_conn_pair.is_some()
+    }
+
+    async fn reconnect(&mut self) -> Result<(), ReconnectingAppConnError> {
+        let (app_permissions, node_report, conn_pair) = self
+            .connector
+            .transform(())
+            .await
+            .ok_or(ReconnectingAppConnError::ConnectError)?;
+
+        self.app_permissions = app_permissions;
+        self.node_report = node_report;
+        self.opt_conn_pair = Some(conn_pair);
+        Ok(())
+    }
+
+    /// Drop the current connection, failing all requests in flight
+    fn disconnect(&mut self) -> ReconnectingAppConnError {
+        self.opt_conn_pair = None;
+        ReconnectingAppConnError::ConnectionLost(self.inflight_requests.drain().collect())
+    }
+
+    async fn conn_pair(&mut self) -> Result<&mut ConnPairApp, ReconnectingAppConnError> {
+        if self.opt_conn_pair.is_none() {
+            self.reconnect().await?;
+        }
+        Ok(self.opt_conn_pair.as_mut().unwrap())
+    }
+
+    /// Send a request to the node, reconnecting first if required.
+    pub async fn send(
+        &mut self,
+        app_to_app_server: AppToAppServer,
+    ) -> Result<(), ReconnectingAppConnError> {
+        let app_request_id = app_to_app_server.app_request_id.clone();
+        let conn_pair = self.conn_pair().await?;
+        if conn_pair.sender.send(app_to_app_server).await.is_err() {
+            // The request never reached the node, so it is not reported as in flight:
+            return Err(self.disconnect());
+        }
+        self.inflight_requests.insert(app_request_id);
+        Ok(())
+    }
+
+    /// Send a request to the node, without waiting for any response.
+    /// Returns the app_request_id of the sent request.
+    pub async fn send_request(
+        &mut self,
+        app_request: AppRequest,
+    ) -> Result<Uid, ReconnectingAppConnError> {
+        let app_request_id = gen_uid();
+        self.send(AppToAppServer::new(app_request_id.clone(), app_request))
+            .await?;
+        Ok(app_request_id)
+    }
+
+    /// Send a request to the node, and wait until the node acknowledges it.
+    ///
+    /// Any other message received from the node while waiting is kept, and will be returned by
+    /// the next calls to `recv()`.
+    pub async fn request(
+        &mut self,
+        app_request: AppRequest,
+    ) -> Result<(), ReconnectingAppConnError> {
+        let app_request_id = self.send_request(app_request).await?;
+        loop {
+            let app_server_to_app = self.recv_conn().await?;
+            if let AppServerToApp::ReportMutations(report_mutations) = &app_server_to_app {
+                if report_mutations.opt_app_request_id == Some(app_request_id.clone()) {
+                    return Ok(());
+                }
+            }
+            self.pending_messages.push_back(app_server_to_app);
+        }
+    }
+
+    /// Receive the next message from the node, reconnecting first if required.
+    /// If the connection is lost while waiting, all requests in flight fail.
+    pub async fn recv(&mut self) -> Result<AppServerToApp, ReconnectingAppConnError> {
+        if let Some(app_server_to_app) = self.pending_messages.pop_front() {
+            return Ok(app_server_to_app);
+        }
+        self.recv_conn().await
+    }
+
+    /// Receive the next message from the current connection, applying any report mutations.
+    async fn recv_conn(&mut self) -> Result<AppServerToApp, ReconnectingAppConnError> {
+        let conn_pair = self.conn_pair().await?;
+        let app_server_to_app = match conn_pair.receiver.next().await {
+            Some(app_server_to_app) => app_server_to_app,
+            None => return Err(self.disconnect()),
+        };
+
+        if let AppServerToApp::ReportMutations(report_mutations) = &app_server_to_app {
+            if let Some(app_request_id) = &report_mutations.opt_app_request_id {
+                self.inflight_requests.remove(app_request_id);
+            }
+            for mutation in &report_mutations.mutations {
+                if self.node_report.mutate(mutation).is_err() {
+                    log::warn!("ReconnectingAppConn::recv(): Failed to mutate node report");
+                }
+            }
+        }
+        Ok(app_server_to_app)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::channel::mpsc;
+    use futures::executor::block_on;
+    use futures::future;
+
+    use common::conn::FuncFutTransform;
+
+    use proto::app_server::messages::{AppRequest, ReportMutations};
+
+    type ServerConn = (mpsc::Sender<AppServerToApp>, mpsc::Receiver<AppToAppServer>);
+
+    /// A connector where every connection attempt hands the node side of the new connection to
+    /// `server_conn_sender`.
+    fn dummy_connector(
+        server_conn_sender: mpsc::UnboundedSender<ServerConn>,
+    ) -> impl FutTransform<Input = (), Output = Option<AppConnTuple>> {
+        FuncFutTransform::new(move |()| {
+            let (app_sender, server_receiver) = mpsc::channel(8);
+            let (server_sender, app_receiver) = mpsc::channel(8);
+            server_conn_sender
+                .unbounded_send((server_sender, server_receiver))
+                .unwrap();
+
+            let app_permissions = AppPermissions::full();
+            let conn_pair = ConnPairApp::from_raw(app_sender, app_receiver);
+            Box::pin(future::ready(Some((
+                app_permissions,
+                NodeReport::dummy(),
+                conn_pair,
+            )))) as BoxFuture<'static, _>
+        })
+    }
+
+    #[test]
+    fn test_reconnecting_app_conn_reconnect() {
+        let (server_conn_sender, mut server_conns) = mpsc::unbounded::<ServerConn>();
+        let connector = dummy_connector(server_conn_sender);
+
+        block_on(async move {
+            let mut conn = ReconnectingAppConn::new(connector).await.unwrap();
+            let (mut server_sender, mut server_receiver) = server_conns.next().await.unwrap();
+
+            let relay_public_key = PublicKey::from(&[0xbb; PublicKey::len()]);
+            let app_request_id = Uid::from(&[1; Uid::len()]);
+            conn.send(AppToAppServer::new(
+                app_request_id.clone(),
+                AppRequest::RemoveRelay(relay_public_key.clone()),
+            ))
+            .await
+            .unwrap();
+            assert_eq!(
+                server_receiver.next().await.unwrap().app_request_id,
+                app_request_id
+            );
+
+            // Node acknowledges the request:
+            server_sender
+                .send(AppServerToApp::ReportMutations(ReportMutations {
+                    opt_app_request_id: Some(app_request_id.clone()),
+                    mutations: Vec::new(),
+                }))
+                .await
+                .unwrap();
+            conn.recv().await.unwrap();
+
+            // Send another request, and drop the connection before it is acknowledged:
+            let app_request_id = Uid::from(&[2; Uid::len()]);
+            conn.send(AppToAppServer::new(
+                app_request_id.clone(),
+                AppRequest::RemoveRelay(relay_public_key.clone()),
+            ))
+            .await
+            .unwrap();
+            drop(server_sender);
+            drop(server_receiver);
+
+            // The request in flight fails with a retryable error:
+            match conn.recv().await {
+                Err(ReconnectingAppConnError::ConnectionLost(request_ids)) => {
+                    assert_eq!(request_ids, vec![app_request_id.clone()])
+                }
+                _ => unreachable!(),
+            };
+            assert!(!conn.is_connected());
+
+            // Retry the request. A new connection should be established:
+            conn.send(AppToAppServer::new(
+                app_request_id.clone(),
+                AppRequest::RemoveRelay(relay_public_key.clone()),
+            ))
+            .await
+            .unwrap();
+            assert!(conn.is_connected());
+
+            let (_server_sender, mut server_receiver) = server_conns.next().await.unwrap();
+            assert_eq!(
+                server_receiver.next().await.unwrap().app_request_id,
+                app_request_id
+            );
+        });
+    }
+
+    #[test]
+    fn test_reconnecting_app_conn_request() {
+        let (server_conn_sender, mut server_conns) = mpsc::unbounded::<ServerConn>();
+        let connector = dummy_connector(server_conn_sender);
+
+        block_on(async move {
+            let mut conn = ReconnectingAppConn::new(connector).await.unwrap();
+            let (mut server_sender, mut server_receiver) = server_conns.next().await.unwrap();
+
+            let relay_public_key = PublicKey::from(&[0xbb; PublicKey::len()]);
+            let unrelated = || {
+                AppServerToApp::ReportMutations(ReportMutations {
+                    opt_app_request_id: None,
+                    mutations: Vec::new(),
+                })
+            };
+
+            // A node that sends an unrelated message before acknowledging the request:
+            let request_fut = conn.request(AppRequest::RemoveRelay(relay_public_key.clone()));
+            let node_fut = async {
+                let app_to_app_server = server_receiver.next().await.unwrap();
+                server_sender.send(unrelated()).await.unwrap();
+                server_sender
+                    .send(AppServerToApp::ReportMutations(ReportMutations {
+                        opt_app_request_id: Some(app_to_app_server.app_request_id),
+                        mutations: Vec::new(),
+                    }))
+                    .await
+                    .unwrap();
+            };
+            let (request_res, ()) = future::join(request_fut, node_fut).await;
+            request_res.unwrap();
+
+            // The unrelated message is kept:
+            assert_eq!(conn.recv().await.unwrap(), unrelated());
+
+            // A request that could not be sent is not reported as in flight:
+            drop(server_sender);
+            drop(server_receiver);
+            match conn
+                .send_request(AppRequest::RemoveRelay(relay_public_key.clone()))
+                .await
+            {
+                Err(ReconnectingAppConnError::ConnectionLost(request_ids)) => {
+                    assert!(request_ids.is_empty())
+                }
+                _ => unreachable!(),
+            };
+            assert!(!conn.is_connected());
+
+            // A failed reconnection attempt may be retried:
+            assert!(ReconnectingAppConnError::ConnectError.is_retryable());
+        });
+    }
+}
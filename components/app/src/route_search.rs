@@ -0,0 +1,274 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, VecDeque};
+
+use proto::app_server::messages::NodeReport;
+use proto::consts::MAX_ROUTE_LEN;
+use proto::crypto::PublicKey;
+use proto::funder::messages::{Currency, FriendsRoute};
+use proto::index_client::messages::FriendInfo;
+use proto::report::convert::funder_report_to_index_client_state;
+
+/// A route found by `route_search()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteCandidate {
+    pub route: FriendsRoute,
+    /// Minimal capacity along the route
+    pub capacity: u128,
+    /// Total fees paid to the mediators along the route
+    pub fees: u128,
+}
+
+/// A single hop `a -> b` of a route, as seen by `b`:
+/// How much `b` can receive from `a`, and the rate `b` charges for forwarding requests from `a`.
+type Hops = HashMap<PublicKey, HashMap<PublicKey, FriendInfo>>;
+
+/// Collect all the hops of a given currency from the reports, indexed by the sending side.
+fn collect_hops<B>(node_reports: &[NodeReport<B>], currency: &Currency) -> Hops
+where
+    B: Clone,
+{
+    let mut hops = Hops::new();
+    for node_report in node_reports {
+        let funder_report = &node_report.funder_report;
+        let index_client_state = funder_report_to_index_client_state(funder_report);
+        for ((friend_public_key, friend_currency), friend_info) in index_client_state.friends {
+            if &friend_currency != currency {
+                continue;
+            }
+            hops.entry(friend_public_key)
+                .or_insert_with(HashMap::new)
+                .insert(funder_report.local_public_key.clone(), friend_info);
+        }
+    }
+    hops
+}
+
+/// Calculate the capacity and the total fees of a route.
+/// Returns `None` if `dest_payment` (together with the fees) can not be pushed through the route.
+fn route_capacity_fees(
+    hops: &Hops,
+    route: &[PublicKey],
+    dest_payment: u128,
+) -> Option<(u128, u128)> {
+    let route_hops = route
+        .windows(2)
+        .map(|pair| hops.get(&pair[0])?.get(&pair[1]))
+        .collect::<Option<Vec<_>>>()?;
+
+    let capacity = route_hops
+        .iter()
+        .map(|friend_info| friend_info.recv_capacity)
+        .min()?;
+
+    // Every mediator charges a fee according to the rate it set for the node that
+    // sent it the request. We go backwards, because every hop also has to carry the fees of the
+    // mediators that come after it.
+    let mut left_fees = 0u128;
+    for (index, friend_info) in route_hops.iter().enumerate().rev() {
+        if index + 1 < route_hops.len() {
+            let fee = friend_info.rate.calc_fee(dest_payment)?;
+            left_fees = left_fees.checked_add(fee)?;
+        }
+        if dest_payment.checked_add(left_fees)? > friend_info.recv_capacity {
+            return None;
+        }
+    }
+    Some((capacity, left_fees))
+}
+
+/// Search for routes of a given currency from `src_public_key` to `dest_public_key`, using
+/// only the information in `node_reports` (Without an index server).
+///
+/// A node report describes the hops arriving at the reporting node. Therefore a route can only
+/// be found if the reports of all the nodes along the route (except for the source) are
+/// provided.
+///
+/// Returns up to `max_routes` routes that can pay `dest_payment` credits to the destination,
+/// cheapest first. Routes with equal fees are ordered by higher capacity, and then by shorter
+/// length.
+///
+/// Note that all the simple routes are explored, so this function is meant for small graphs.
+pub fn route_search<B>(
+    node_reports: &[NodeReport<B>],
+    currency: &Currency,
+    src_public_key: &PublicKey,
+    dest_public_key: &PublicKey,
+    dest_payment: u128,
+    max_routes: usize,
+) -> Vec<RouteCandidate>
+where
+    B: Clone,
+{
+    let hops = collect_hops(node_reports, currency);
+
+    let mut candidates = Vec::new();
+    let mut queue = VecDeque::new();
+    queue.push_back(vec![src_public_key.clone()]);
+
+    while let Some(route) = queue.pop_front() {
+        let last = route.last().unwrap();
+        if last == dest_public_key {
+            if let Some((capacity, fees)) = route_capacity_fees(&hops, &route, dest_payment) {
+                candidates.push(RouteCandidate {
+                    route: FriendsRoute { public_keys: route },
+                    capacity,
+                    fees,
+                });
+            }
+            continue;
+        }
+        if route.len() >= MAX_ROUTE_LEN {
+            continue;
+        }
+        let next_hops = match hops.get(last) {
+            Some(next_hops) => next_hops,
+            None => continue,
+        };
+        for (next_public_key, friend_info) in next_hops {
+            if route.contains(next_public_key) || friend_info.recv_capacity < dest_payment {
+                continue;
+            }
+            let mut new_route = route.clone();
+            new_route.push(next_public_key.clone());
+            queue.push_back(new_route);
+        }
+    }
+
+    candidates.sort_by(|a, b| {
+        (
+            a.fees,
+            Reverse(a.capacity),
+            a.route.public_keys.len(),
+            &a.route.public_keys,
+        )
+            .cmp(&(
+                b.fees,
+                Reverse(b.capacity),
+                b.route.public_keys.len(),
+                &b.route.public_keys,
+            ))
+    });
+    candidates.truncate(max_routes);
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryFrom;
+
+    use proto::app_server::messages::RelayAddress;
+    use proto::funder::messages::Rate;
+    use proto::report::messages::{
+        ChannelConsistentReport, ChannelStatusReport, CurrencyConfigReport, CurrencyReport,
+        FriendLivenessReport, FriendReport, FriendStatusReport, McBalanceReport,
+    };
+
+    fn pk(index: u8) -> PublicKey {
+        PublicKey::from(&[index; PublicKey::len()])
+    }
+
+    /// A report of node `index`, that can receive `recv_capacity` credits from each of the
+    /// given friends, charging the given rate for forwarding requests from that friend.
+    fn node_report(
+        index: u8,
+        currency: &Currency,
+        friends: &[(u8, u128, Rate)],
+    ) -> NodeReport<u32> {
+        let mut node_report = NodeReport::dummy();
+        node_report.funder_report.local_public_key = pk(index);
+        node_report.funder_report.friends = friends
+            .iter()
+            .map(|(friend_index, recv_capacity, rate)| {
+                let friend_report = FriendReport {
+                    name: format!("node{}", friend_index),
+                    remote_relays: Vec::<RelayAddress<u32>>::new(),
+                    currency_configs: vec![CurrencyConfigReport {
+                        currency: currency.clone(),
+                        rate: rate.clone(),
+                        remote_max_debt: *recv_capacity,
+                        is_open: true,
+                    }],
+                    opt_last_incoming_move_token: None,
+                    liveness: FriendLivenessReport::Online,
+                    idle_ticks: 0,
+                    channel_status: ChannelStatusReport::Consistent(ChannelConsistentReport {
+                        currency_reports: vec![CurrencyReport {
+                            currency: currency.clone(),
+                            balance: McBalanceReport {
+                                balance: 0,
+                                local_pending_debt: 0,
+                                remote_pending_debt: 0,
+                            },
+                        }],
+                    }),
+                    status: FriendStatusReport::Enabled,
+                };
+                (pk(*friend_index), friend_report)
+            })
+            .collect();
+        node_report
+    }
+
+    #[test]
+    fn test_route_search() {
+        let currency = Currency::try_from("FST".to_owned()).unwrap();
+        let cheap = Rate { mul: 0, add: 1 };
+        let expensive = Rate { mul: 0, add: 5 };
+
+        /*
+         *        1
+         *      /   \
+         *     0     3
+         *      \   /
+         *        2
+         *
+         * Node 1 is cheap but has low capacity. Node 2 is expensive.
+         */
+        let node_reports = vec![
+            node_report(1, &currency, &[(0, 30, cheap.clone())]),
+            node_report(2, &currency, &[(0, 100, expensive.clone())]),
+            node_report(3, &currency, &[(1, 30, Rate::new()), (2, 100, Rate::new())]),
+        ];
+
+        let candidates = route_search(&node_reports, &currency, &pk(0), &pk(3), 20, 8);
+        assert_eq!(
+            candidates,
+            vec![
+                RouteCandidate {
+                    route: FriendsRoute {
+                        public_keys: vec![pk(0), pk(1), pk(3)],
+                    },
+                    capacity: 30,
+                    fees: 1,
+                },
+                RouteCandidate {
+                    route: FriendsRoute {
+                        public_keys: vec![pk(0), pk(2), pk(3)],
+                    },
+                    capacity: 100,
+                    fees: 5,
+                },
+            ]
+        );
+
+        // Only the expensive route can carry a large payment:
+        let candidates = route_search(&node_reports, &currency, &pk(0), &pk(3), 50, 8);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].route.public_keys, vec![pk(0), pk(2), pk(3)]);
+
+        // The first hop has to carry the fees too:
+        let candidates = route_search(&node_reports, &currency, &pk(0), &pk(3), 30, 8);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].fees, 5);
+
+        // Limit the amount of returned routes:
+        let candidates = route_search(&node_reports, &currency, &pk(0), &pk(3), 20, 1);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].fees, 1);
+
+        // Node 4 did not provide a report, so no route to it is known:
+        assert!(route_search(&node_reports, &currency, &pk(0), &pk(4), 20, 8).is_empty());
+    }
+}
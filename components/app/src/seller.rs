@@ -0,0 +1,276 @@
+use futures::{SinkExt, StreamExt};
+
+use proto::app_server::messages::{AppRequest, AppServerToApp, AppToAppServer, NodeReport};
+use proto::crypto::{InvoiceId, PublicKey, Uid};
+use proto::file::InvoiceFile;
+use proto::funder::messages::{Commit, CommitInvoiceResult, Currency};
+
+use crate::app_conn::seller;
+use crate::connect::ConnPairApp;
+use crate::gen::{gen_invoice_id, gen_uid};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum AppSellerError {
+    /// The connection to the node was lost before the request was acknowledged
+    ConnectionLost,
+    /// The incoming transactions of the invoice pay less than its total. Contains the missing
+    /// amount.
+    CommitShortfall(u128),
+    /// The node rejected the commit for another reason (For example, an invalid commit)
+    CommitFailed,
+}
+
+/// A handle for sending seller requests to a node.
+///
+/// Every request waits until it is acknowledged by the node. Other messages received from the
+/// node while waiting are discarded, so the connection should not be shared with other users.
+pub struct AppSeller {
+    conn_pair: ConnPairApp,
+    /// The public key of the node. Used as the destination of created invoices.
+    local_public_key: PublicKey,
+}
+
+impl AppSeller {
+    /// Create an `AppSeller` over a connection to a node.
+    /// `node_report` is the report received from the node when the connection was established.
+    pub fn new(conn_pair: ConnPairApp, node_report: &NodeReport) -> Self {
+        AppSeller {
+            conn_pair,
+            local_public_key: node_report.funder_report.local_public_key.clone(),
+        }
+    }
+
+    /// Send a request to the node.
+    async fn send_request(&mut self, app_request: AppRequest) -> Result<Uid, AppSellerError> {
+        let app_request_id = gen_uid();
+        let app_to_app_server = AppToAppServer {
+            app_request_id: app_request_id.clone(),
+            app_request,
+        };
+        self.conn_pair
+            .sender
+            .send(app_to_app_server)
+            .await
+            .map_err(|_| AppSellerError::ConnectionLost)?;
+        Ok(app_request_id)
+    }
+
+    /// Send a request to the node, and wait until it is acknowledged.
+    async fn request(&mut self, app_request: AppRequest) -> Result<(), AppSellerError> {
+        let app_request_id = self.send_request(app_request).await?;
+        while let Some(app_server_to_app) = self.conn_pair.receiver.next().await {
+            if let AppServerToApp::ReportMutations(report_mutations) = app_server_to_app {
+                if report_mutations.opt_app_request_id == Some(app_request_id.clone()) {
+                    return Ok(());
+                }
+            }
+        }
+        Err(AppSellerError::ConnectionLost)
+    }
+
+    /// Add a new invoice, with a randomly generated invoice id.
+    /// Returns the invoice details, to be handed (out of band) to the buyer.
+    pub async fn add_invoice(
+        &mut self,
+        currency: Currency,
+        total_dest_payment: u128,
+    ) -> Result<InvoiceFile, AppSellerError> {
+        let invoice_id = gen_invoice_id();
+        self.request(seller::add_invoice(
+            invoice_id.clone(),
+            currency.clone(),
+            total_dest_payment,
+        ))
+        .await?;
+
+        Ok(InvoiceFile {
+            invoice_id,
+            currency,
+            dest_public_key: self.local_public_key.clone(),
+            dest_payment: total_dest_payment,
+        })
+    }
+
+    pub async fn cancel_invoice(&mut self, invoice_id: InvoiceId) -> Result<(), AppSellerError> {
+        self.request(seller::cancel_invoice(invoice_id)).await
+    }
+
+    /// Commit an invoice, collecting all of its incoming transactions.
+    ///
+    /// A commit is all or nothing: The node collects the incoming transactions of the invoice
+    /// only if together they pay the full total of the invoice. Otherwise (For example, if some
+    /// of the routes of a multi-route payment have failed) the node ignores the commit, and the
+    /// invoice remains open until it is canceled or expires. In that case the missing amount is
+    /// returned.
+    pub async fn commit_invoice(&mut self, commit: Commit) -> Result<(), AppSellerError> {
+        let invoice_id = commit.invoice_id.clone();
+        self.send_request(seller::commit_invoice(commit)).await?;
+
+        // Wait for the outcome of the commit. It arrives after the request is acknowledged:
+        while let Some(app_server_to_app) = self.conn_pair.receiver.next().await {
+            if let AppServerToApp::ResponseCommitInvoice(response_commit_invoice) =
+                app_server_to_app
+            {
+                if response_commit_invoice.invoice_id != invoice_id {
+                    continue;
+                }
+                return match response_commit_invoice.result {
+                    CommitInvoiceResult::Success => Ok(()),
+                    CommitInvoiceResult::NotFullyPaid(missing) => {
+                        Err(AppSellerError::CommitShortfall(missing))
+                    }
+                    CommitInvoiceResult::Failure => Err(AppSellerError::CommitFailed),
+                };
+            }
+        }
+        Err(AppSellerError::ConnectionLost)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryFrom;
+
+    use futures::channel::mpsc;
+    use futures::executor::LocalPool;
+    use futures::task::SpawnExt;
+
+    use proto::app_server::messages::ReportMutations;
+    use proto::crypto::{HashResult, HashedLock, PlainLock, Signature};
+    use proto::funder::messages::ResponseCommitInvoice;
+
+    #[test]
+    fn test_app_seller_add_invoice() {
+        let mut local_pool = LocalPool::new();
+
+        let (app_sender, mut node_receiver) = mpsc::channel(0);
+        let (mut node_sender, app_receiver) = mpsc::channel(0);
+        let conn_pair = ConnPairApp::from_raw(app_sender, app_receiver);
+
+        let node_report = NodeReport::dummy();
+        let local_public_key = node_report.funder_report.local_public_key.clone();
+        let mut app_seller = AppSeller::new(conn_pair, &node_report);
+
+        // A mock node that acknowledges the request, and reports the added invoice:
+        let (mut invoice_sender, mut invoice_receiver) = mpsc::channel(1);
+        local_pool
+            .spawner()
+            .spawn(async move {
+                let app_to_app_server: AppToAppServer = node_receiver.next().await.unwrap();
+                match app_to_app_server.app_request {
+                    AppRequest::AddInvoice(add_invoice) => {
+                        invoice_sender.send(add_invoice).await.unwrap()
+                    }
+                    _ => unreachable!(),
+                }
+                node_sender
+                    .send(AppServerToApp::ReportMutations(ReportMutations {
+                        opt_app_request_id: Some(app_to_app_server.app_request_id),
+                        mutations: Vec::new(),
+                    }))
+                    .await
+                    .unwrap();
+                // Keep the connection open until the app is done:
+                let _ = node_receiver.next().await;
+            })
+            .unwrap();
+
+        let currency = Currency::try_from("FST".to_owned()).unwrap();
+        let invoice_file = local_pool
+            .run_until(app_seller.add_invoice(currency.clone(), 100))
+            .unwrap();
+
+        assert_eq!(invoice_file.dest_public_key, local_public_key);
+        assert_eq!(invoice_file.dest_payment, 100);
+        assert_eq!(invoice_file.currency, currency);
+
+        // The invoice matches the invoice added at the node:
+        let add_invoice = local_pool.run_until(invoice_receiver.next()).unwrap();
+        assert_eq!(add_invoice.invoice_id, invoice_file.invoice_id);
+        assert_eq!(add_invoice.total_dest_payment, 100);
+    }
+
+    #[test]
+    fn test_app_seller_commit_shortfall() {
+        let mut local_pool = LocalPool::new();
+
+        let (app_sender, mut node_receiver) = mpsc::channel(0);
+        let (mut node_sender, app_receiver) = mpsc::channel(0);
+        let conn_pair = ConnPairApp::from_raw(app_sender, app_receiver);
+
+        let node_report = NodeReport::dummy();
+        let mut app_seller = AppSeller::new(conn_pair, &node_report);
+
+        // A mock node that acknowledges every request, and reports the received requests.
+        // Only 60 out of the 100 credits of the invoice arrived, so commits are not fully paid:
+        let (mut request_sender, mut request_receiver) = mpsc::channel(8);
+        local_pool
+            .spawner()
+            .spawn(async move {
+                while let Some(app_to_app_server) = node_receiver.next().await {
+                    let app_to_app_server: AppToAppServer = app_to_app_server;
+                    let opt_invoice_id = match &app_to_app_server.app_request {
+                        AppRequest::CommitInvoice(commit) => Some(commit.invoice_id.clone()),
+                        _ => None,
+                    };
+                    request_sender
+                        .send(app_to_app_server.app_request)
+                        .await
+                        .unwrap();
+                    node_sender
+                        .send(AppServerToApp::ReportMutations(ReportMutations {
+                            opt_app_request_id: Some(app_to_app_server.app_request_id),
+                            mutations: Vec::new(),
+                        }))
+                        .await
+                        .unwrap();
+                    if let Some(invoice_id) = opt_invoice_id {
+                        node_sender
+                            .send(AppServerToApp::ResponseCommitInvoice(
+                                ResponseCommitInvoice {
+                                    invoice_id,
+                                    result: CommitInvoiceResult::NotFullyPaid(40),
+                                },
+                            ))
+                            .await
+                            .unwrap();
+                    }
+                }
+            })
+            .unwrap();
+
+        let currency = Currency::try_from("FST".to_owned()).unwrap();
+        let invoice_file = local_pool
+            .run_until(app_seller.add_invoice(currency.clone(), 100))
+            .unwrap();
+
+        // A valid commit for one route of a multi-route payment, paying 60 out of 100 credits:
+        let commit = Commit {
+            response_hash: HashResult::from(&[1; HashResult::len()]),
+            src_plain_lock: PlainLock::from(&[2; PlainLock::len()]),
+            dest_hashed_lock: HashedLock::from(&[3; HashedLock::len()]),
+            dest_payment: 60,
+            total_dest_payment: 100,
+            invoice_id: invoice_file.invoice_id.clone(),
+            currency,
+            signature: Signature::from(&[4; Signature::len()]),
+        };
+        assert_eq!(
+            local_pool.run_until(app_seller.commit_invoice(commit)),
+            Err(AppSellerError::CommitShortfall(40))
+        );
+
+        // The commit was sent to the node, which reported the missing amount:
+        drop(app_seller);
+        let app_requests = local_pool.run_until(request_receiver.collect::<Vec<_>>());
+        assert_eq!(app_requests.len(), 2);
+        match &app_requests[1] {
+            AppRequest::CommitInvoice(commit) => {
+                assert_eq!(commit.invoice_id, invoice_file.invoice_id)
+            }
+            _ => unreachable!(),
+        }
+    }
+}
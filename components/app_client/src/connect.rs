@@ -25,8 +25,8 @@ pub enum SetupConnectionError {
     FirstMessageNotNodeReport,
 }
 
-/// Connect to an offset-node
-async fn setup_connection<S>(
+/// Set up an app connection over an already secured connection to an offset-node
+pub async fn setup_connection<S>(
     conn_pair: ConnPairVec,
     spawner: S,
 ) -> Result<AppConnTuple, SetupConnectionError>
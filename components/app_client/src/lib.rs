@@ -10,4 +10,6 @@
 
 mod connect;
 
-pub use self::connect::{app_connect_to_node, AppConnectError};
+pub use self::connect::{
+    app_connect_to_node, setup_connection, AppConnectError, SetupConnectionError,
+};
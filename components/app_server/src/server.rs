@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::fmt::Debug;
 use std::marker::Unpin;
 
@@ -6,10 +6,10 @@ use futures::channel::{mpsc, oneshot};
 use futures::task::{Spawn, SpawnExt};
 use futures::{future, stream, Sink, SinkExt, Stream, StreamExt};
 
-use common::conn::{sink_to_sender, BoxStream, ConnPair};
+use common::conn::{BoxStream, ConnPair};
 use common::select_streams::select_streams;
 // use common::mutable_state::MutableState;
-use proto::crypto::{PaymentId, Uid};
+use proto::crypto::{InvoiceId, PaymentId, PublicKey, Uid};
 
 use proto::funder::messages::{
     FriendStatus, FunderControl, FunderIncomingControl, FunderOutgoingControl, RequestsStatus,
@@ -25,7 +25,9 @@ use proto::index_client::messages::{
     AppServerToIndexClient, IndexClientRequest, IndexClientToAppServer,
 };
 
-const APP_SENDER_BUFFER: usize = 0x20;
+/// Amount of recent request ids we remember for every app public key.
+/// Used to detect requests that were sent more than once.
+const MAX_RECENT_REQUESTS: usize = 0x100;
 
 pub type ConnPairServer<B> = ConnPair<AppServerToApp<B>, AppToAppServer<B>>;
 
@@ -38,6 +40,7 @@ pub type IncomingAppConnection<B> = (
 
 #[derive(Debug)]
 pub struct IncomingAppConnection<B> {
+    pub app_public_key: PublicKey,
     pub app_permissions: AppPermissions,
     // The server has to send the `NodeReport` first. Only then communication with the App becomes
     // possible.
@@ -69,30 +72,125 @@ pub enum AppServerEvent<B: Clone> {
     FromApp((u128, Option<AppToAppServer<B>>)), // None means that app was closed
 }
 
+/// What we remember about a request recently sent by an app
+#[derive(Debug, Clone)]
+struct RecentRequest<B: Clone> {
+    is_acked: bool,
+    /// Responses that were already sent for this request
+    responses: Vec<AppServerToApp<B>>,
+}
+
+/// Bounded memory of the request ids recently sent by an app (Over all of its connections).
+/// For every request id we remember whether the request was already acknowledged, and the
+/// responses that were sent for it.
+#[derive(Debug)]
+struct RecentRequests<B: Clone> {
+    order: VecDeque<Uid>,
+    requests: HashMap<Uid, RecentRequest<B>>,
+}
+
+impl<B> RecentRequests<B>
+where
+    B: Clone,
+{
+    fn new() -> Self {
+        RecentRequests {
+            order: VecDeque::new(),
+            requests: HashMap::new(),
+        }
+    }
+
+    /// Remember a request id.
+    /// Returns what we remember about the request id if it was already seen.
+    fn insert(&mut self, app_request_id: &Uid) -> Option<RecentRequest<B>> {
+        if let Some(recent_request) = self.requests.get(app_request_id) {
+            return Some(recent_request.clone());
+        }
+        if self.order.len() >= MAX_RECENT_REQUESTS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.requests.remove(&oldest);
+            }
+        }
+        self.order.push_back(app_request_id.clone());
+        self.requests.insert(
+            app_request_id.clone(),
+            RecentRequest {
+                is_acked: false,
+                responses: Vec::new(),
+            },
+        );
+        None
+    }
+
+    fn set_acked(&mut self, app_request_id: &Uid) {
+        if let Some(recent_request) = self.requests.get_mut(app_request_id) {
+            recent_request.is_acked = true;
+        }
+    }
+
+    fn add_response(&mut self, app_request_id: &Uid, response: AppServerToApp<B>) {
+        if let Some(recent_request) = self.requests.get_mut(app_request_id) {
+            recent_request.responses.push(response);
+        }
+    }
+}
+
+/// The app request that waits for a response
+#[derive(Debug, Clone)]
+struct RequestOrigin {
+    /// The connection the response is sent to
+    app_id: u128,
+    app_public_key: PublicKey,
+    app_request_id: Uid,
+}
+
 // TODO: Possibly remove Clone annotation here?
 pub struct App<B: Clone> {
+    public_key: PublicKey,
     permissions: AppPermissions,
     opt_sender: Option<mpsc::Sender<AppServerToApp<B>>>,
+    /// Dropping this closes the connection to the app, discarding all queued messages
+    _close_sender: oneshot::Sender<()>,
 }
 
 impl<B> App<B>
 where
     B: Clone,
 {
-    pub fn new(permissions: AppPermissions, sender: mpsc::Sender<AppServerToApp<B>>) -> Self {
+    pub fn new(
+        public_key: PublicKey,
+        permissions: AppPermissions,
+        sender: mpsc::Sender<AppServerToApp<B>>,
+        close_sender: oneshot::Sender<()>,
+    ) -> Self {
         App {
+            public_key,
             permissions,
             opt_sender: Some(sender),
+            _close_sender: close_sender,
         }
     }
 
-    pub async fn send(&mut self, message: AppServerToApp<B>) {
-        if let Some(mut sender) = self.opt_sender.take() {
-            if let Ok(()) = sender.send(message).await {
-                self.opt_sender = Some(sender);
+    /// Queue a message for sending to the app.
+    /// If the queue of the app is full, the app is marked as disconnected. This way a lagging app
+    /// can not make messages pile up, or block the communication with other apps.
+    pub fn send(&mut self, message: AppServerToApp<B>) {
+        let sender = match &mut self.opt_sender {
+            Some(sender) => sender,
+            None => return,
+        };
+        if let Err(e) = sender.try_send(message) {
+            if e.is_full() {
+                warn!("App::send(): Too many queued messages. Disconnecting app.");
             }
+            self.opt_sender = None;
         }
     }
+
+    /// Was the app disconnected? A disconnected app should be removed.
+    fn is_disconnected(&self) -> bool {
+        self.opt_sender.is_none()
+    }
 }
 
 pub struct AppServer<B: Clone, TF, TIC, S> {
@@ -106,49 +204,23 @@ pub struct AppServer<B: Clone, TF, TIC, S> {
     /// Required because an app (with one public key) might have multiple connections.
     app_counter: u128,
     apps: HashMap<u128, App<B>>,
+    /// Maximum amount of messages queued for sending to a single app
+    max_app_queued_messages: usize,
+    /// Recent requests of every app, by the app's public key.
+    /// Kept across connections, so that a request retried after a reconnect is not executed
+    /// again.
+    recent_requests: HashMap<PublicKey, RecentRequests<B>>,
     /// Data structures to track ongoing requests.
     /// This allows us to multiplex requests/responses to multiple apps:
-    route_requests: HashMap<Uid, u128>,
-    close_payment_requests: HashMap<PaymentId, u128>,
-    transactions: HashMap<Uid, u128>,
+    route_requests: HashMap<Uid, RequestOrigin>,
+    close_payment_requests: HashMap<PaymentId, RequestOrigin>,
+    commit_invoice_requests: HashMap<InvoiceId, RequestOrigin>,
+    friend_relays_requests: HashMap<PublicKey, RequestOrigin>,
+    cancel_payment_requests: HashMap<PaymentId, RequestOrigin>,
+    transactions: HashMap<Uid, RequestOrigin>,
     spawner: S,
 }
 
-/// Check if we should process an app_request from an app with certain permissions
-fn check_request_permissions<B>(
-    app_permissions: &AppPermissions,
-    app_request: &AppRequest<B>,
-) -> bool {
-    match app_request {
-        AppRequest::AddRelay(_) => app_permissions.config,
-        AppRequest::RemoveRelay(_) => app_permissions.config,
-        AppRequest::CreatePayment(_) => app_permissions.buyer,
-        AppRequest::CreateTransaction(_) => app_permissions.buyer,
-        AppRequest::RequestClosePayment(_) => app_permissions.buyer,
-        AppRequest::AckClosePayment(_) => app_permissions.buyer,
-
-        AppRequest::AddInvoice(_) => app_permissions.seller,
-        AppRequest::CancelInvoice(_) => app_permissions.seller,
-        AppRequest::CommitInvoice(_) => app_permissions.seller,
-
-        AppRequest::AddFriend(_) => app_permissions.config,
-        AppRequest::SetFriendRelays(_) => app_permissions.config,
-        AppRequest::SetFriendName(_) => app_permissions.config,
-        AppRequest::RemoveFriend(_) => app_permissions.config,
-        AppRequest::EnableFriend(_) => app_permissions.config,
-        AppRequest::DisableFriend(_) => app_permissions.config,
-        AppRequest::OpenFriendCurrency(_) => app_permissions.config,
-        AppRequest::CloseFriendCurrency(_) => app_permissions.config,
-        AppRequest::SetFriendCurrencyMaxDebt(_) => app_permissions.config,
-        AppRequest::SetFriendCurrencyRate(_) => app_permissions.config,
-        AppRequest::RemoveFriendCurrency(_) => app_permissions.config,
-        AppRequest::ResetFriendChannel(_) => app_permissions.config,
-        AppRequest::RequestRoutes(_) => app_permissions.routes,
-        AppRequest::AddIndexServer(_) => app_permissions.config,
-        AppRequest::RemoveIndexServer(_) => app_permissions.config,
-    }
-}
-
 impl<B, TF, TIC, S> AppServer<B, TF, TIC, S>
 where
     B: Clone + PartialEq + Eq + Debug + Send + Sync + 'static,
@@ -161,6 +233,7 @@ where
         to_index_client: TIC,
         from_app_sender: mpsc::Sender<(u128, Option<AppToAppServer<B>>)>,
         node_report: NodeReport<B>,
+        max_app_queued_messages: usize,
         spawner: S,
     ) -> Self {
         AppServer {
@@ -171,8 +244,13 @@ where
             incoming_connections_closed: false,
             app_counter: 0,
             apps: HashMap::new(),
+            max_app_queued_messages,
+            recent_requests: HashMap::new(),
             route_requests: HashMap::new(),
             close_payment_requests: HashMap::new(),
+            commit_invoice_requests: HashMap::new(),
+            friend_relays_requests: HashMap::new(),
+            cancel_payment_requests: HashMap::new(),
             transactions: HashMap::new(),
             spawner,
         }
@@ -184,6 +262,7 @@ where
         incoming_app_connection: IncomingAppConnection<B>,
     ) -> Result<(), AppServerError> {
         let IncomingAppConnection {
+            app_public_key,
             app_permissions,
             report_sender,
         } = incoming_app_connection;
@@ -197,7 +276,7 @@ where
         let conn_pair = conn_pair_receiver
             .await
             .map_err(|_| AppServerError::ObtainConnPairError)?;
-        let (sender, receiver) = conn_pair.split();
+        let (mut sender, receiver) = conn_pair.split();
 
         let app_counter = self.app_counter;
         let receiver =
@@ -211,12 +290,25 @@ where
             let _ = from_app_sender.send((app_counter, None)).await;
         };
 
+        // Forward queued messages to the app:
+        let (app_sender, app_receiver) = mpsc::channel(self.max_app_queued_messages);
+        let forward_fut = async move {
+            let mut app_receiver = app_receiver.map(Ok);
+            let _ = sender.send_all(&mut app_receiver).await;
+        };
+
+        // Communicate with the app in both directions, until the app is removed
+        // (`close_sender` is dropped):
+        let (close_sender, close_receiver) = oneshot::channel::<()>();
+        let conn_fut = async move {
+            let both_fut = future::join(send_all_fut, forward_fut);
+            let _ = future::select(Box::pin(both_fut), close_receiver).await;
+        };
         self.spawner
-            .spawn(send_all_fut)
+            .spawn(conn_fut)
             .map_err(|_| AppServerError::SpawnError)?;
 
-        let sender = sink_to_sender(sender, APP_SENDER_BUFFER, &self.spawner);
-        let app = App::new(app_permissions, sender);
+        let app = App::new(app_public_key, app_permissions, app_sender, close_sender);
 
         self.apps.insert(self.app_counter, app);
         self.app_counter = self.app_counter.wrapping_add(1);
@@ -234,12 +326,48 @@ where
         Ok(())
     }
 
+    /// Send a response to the app that issued the request.
+    /// The response is remembered, so that it can be sent again if the app retries the request
+    /// (For example, after reconnecting).
+    fn send_response(&mut self, request_origin: RequestOrigin, response: AppServerToApp<B>) {
+        if let Some(recent_requests) = self.recent_requests.get_mut(&request_origin.app_public_key)
+        {
+            recent_requests.add_response(&request_origin.app_request_id, response.clone());
+        }
+        if let Some(app) = self.apps.get_mut(&request_origin.app_id) {
+            app.send(response);
+        }
+    }
+
+    /// Send the future responses of a retried request to the connection of the retried request.
+    fn reroute_requests(&mut self, request_origin: &RequestOrigin) {
+        let pending_origins = self
+            .route_requests
+            .values_mut()
+            .chain(self.close_payment_requests.values_mut())
+            .chain(self.commit_invoice_requests.values_mut())
+            .chain(self.friend_relays_requests.values_mut())
+            .chain(self.cancel_payment_requests.values_mut())
+            .chain(self.transactions.values_mut());
+        for pending_origin in pending_origins {
+            if pending_origin.app_public_key == request_origin.app_public_key
+                && pending_origin.app_request_id == request_origin.app_request_id
+            {
+                pending_origin.app_id = request_origin.app_id;
+            }
+        }
+    }
+
     /// Send node report mutations to all connected apps
     pub async fn broadcast_node_report_mutations(&mut self, report_mutations: ReportMutations<B>) {
+        if let Some(app_request_id) = &report_mutations.opt_app_request_id {
+            for recent_requests in self.recent_requests.values_mut() {
+                recent_requests.set_acked(app_request_id);
+            }
+        }
         // Send node report mutations to all connected apps
         for app in &mut self.apps.values_mut() {
-            app.send(AppServerToApp::ReportMutations(report_mutations.clone()))
-                .await;
+            app.send(AppServerToApp::ReportMutations(report_mutations.clone()));
         }
     }
 
@@ -250,38 +378,82 @@ where
         match funder_message {
             FunderOutgoingControl::TransactionResult(transaction_result) => {
                 // Find the app that issued the request, and forward the response to this app:
-                let app_id = if let Some(app_id) =
+                let request_origin = if let Some(request_origin) =
                     self.transactions.remove(&transaction_result.request_id)
                 {
-                    app_id
+                    request_origin
                 } else {
                     warn!("TransactionResult: Could not find app that initiated CreateTransaction");
                     return Ok(());
                 };
-                if let Some(app) = self.apps.get_mut(&app_id) {
-                    app.send(AppServerToApp::TransactionResult(
-                        transaction_result.clone(),
-                    ))
-                    .await;
-                }
+                self.send_response(
+                    request_origin,
+                    AppServerToApp::TransactionResult(transaction_result),
+                );
             }
             FunderOutgoingControl::ResponseClosePayment(response_close_payment) => {
                 // Find the app that issued the request, and forward the response to this app:
-                let app_id = if let Some(app_id) = self
+                let request_origin = if let Some(request_origin) = self
                     .close_payment_requests
                     .remove(&response_close_payment.payment_id)
                 {
-                    app_id
+                    request_origin
                 } else {
                     warn!("ResponseClosePayment: Could not find app that initiated RequestClosePayment");
                     return Ok(());
                 };
-                if let Some(app) = self.apps.get_mut(&app_id) {
-                    app.send(AppServerToApp::ResponseClosePayment(
-                        response_close_payment.clone(),
-                    ))
-                    .await;
-                }
+                self.send_response(
+                    request_origin,
+                    AppServerToApp::ResponseClosePayment(response_close_payment),
+                );
+            }
+            FunderOutgoingControl::ResponseCommitInvoice(response_commit_invoice) => {
+                // Find the app that issued the request, and forward the response to this app:
+                let request_origin = if let Some(request_origin) = self
+                    .commit_invoice_requests
+                    .remove(&response_commit_invoice.invoice_id)
+                {
+                    request_origin
+                } else {
+                    warn!("ResponseCommitInvoice: Could not find app that initiated CommitInvoice");
+                    return Ok(());
+                };
+                self.send_response(
+                    request_origin,
+                    AppServerToApp::ResponseCommitInvoice(response_commit_invoice),
+                );
+            }
+            FunderOutgoingControl::ResponseFriendRelays(response_friend_relays) => {
+                // Find the app that issued the request, and forward the response to this app:
+                let request_origin = if let Some(request_origin) = self
+                    .friend_relays_requests
+                    .remove(&response_friend_relays.friend_public_key)
+                {
+                    request_origin
+                } else {
+                    warn!("ResponseFriendRelays: Could not find app that initiated the request");
+                    return Ok(());
+                };
+                self.send_response(
+                    request_origin,
+                    AppServerToApp::ResponseFriendRelays(response_friend_relays),
+                );
+            }
+            FunderOutgoingControl::ResponseCancelPayment(response_cancel_payment) => {
+                // Find the app that issued the request, and forward the response to this app:
+                let request_origin = if let Some(request_origin) = self
+                    .cancel_payment_requests
+                    .remove(&response_cancel_payment.payment_id)
+                {
+                    request_origin
+                } else {
+                    warn!("ResponseCancelPayment: Could not find app that initiated CancelPayment");
+                    return Ok(());
+                };
+                self.send_response(
+                    request_origin,
+                    AppServerToApp::ResponseCancelPayment(response_cancel_payment),
+                );
             }
             FunderOutgoingControl::ReportMutations(funder_report_mutations) => {
                 let mut index_mutations = Vec::new();
@@ -342,24 +514,21 @@ where
             }
             IndexClientToAppServer::ResponseRoutes(client_response_routes) => {
                 // We search for the app that issued the request, and send it the response.
-                let app_id = if let Some(app_id) = self
+                let request_origin = if let Some(request_origin) = self
                     .route_requests
                     .remove(&client_response_routes.request_id)
                 {
-                    app_id
+                    request_origin
                 } else {
                     warn!(
                         "ResponseRoutes: Could not find the app that issued RequestRoutes request"
                     );
                     return Ok(());
                 };
-
-                if let Some(app) = self.apps.get_mut(&app_id) {
-                    app.send(AppServerToApp::ResponseRoutes(
-                        client_response_routes.clone(),
-                    ))
-                    .await;
-                }
+                self.send_response(
+                    request_origin,
+                    AppServerToApp::ResponseRoutes(client_response_routes),
+                );
             }
         };
         Ok(())
@@ -372,18 +541,33 @@ where
     ) -> Result<(), AppServerError> {
         match opt_app_message {
             None => {
-                // Remove the application. We assert that this application exists
-                // in our apps map:
-                self.apps.remove(&app_id).unwrap();
-                if self.apps.is_empty() && self.incoming_connections_closed {
-                    return Err(AppServerError::AllAppsClosed);
-                }
-                Ok(())
+                // Remove the application. The application might have been removed already,
+                // if it was lagging:
+                self.apps.remove(&app_id);
+                self.check_all_apps_closed()
             }
             Some(app_message) => self.handle_app_message(app_id, app_message).await,
         }
     }
 
+    /// Remove apps that were disconnected while sending them messages (For example, lagging
+    /// apps). Removing an app also stops receiving messages from it.
+    pub fn remove_disconnected_apps(&mut self) -> Result<(), AppServerError> {
+        let num_apps = self.apps.len();
+        self.apps.retain(|_app_id, app| !app.is_disconnected());
+        if self.apps.len() < num_apps {
+            self.check_all_apps_closed()?;
+        }
+        Ok(())
+    }
+
+    fn check_all_apps_closed(&self) -> Result<(), AppServerError> {
+        if self.apps.is_empty() && self.incoming_connections_closed {
+            return Err(AppServerError::AllAppsClosed);
+        }
+        Ok(())
+    }
+
     fn check_app_permissions(&self, app_id: u128, app_message: &AppToAppServer<B>) -> bool {
         // Get the relevant application:
         let app = match self.apps.get(&app_id) {
@@ -395,7 +579,7 @@ where
         };
 
         // Make sure this message is allowed for this application:
-        if !check_request_permissions(&app.permissions, &app_message.app_request) {
+        if !app.permissions.allows(app_message.app_request.kind()) {
             warn!(
                 "App {:?} does not have permissions for {:?}",
                 app_id, app_message
@@ -424,6 +608,41 @@ where
             app_request_id,
         } = app_message;
 
+        // A request id we have seen recently means that the app retried a request.
+        // We do not execute the request again. If the original request was already acknowledged,
+        // we acknowledge again (The original mutations were already sent to the app), and send
+        // again the responses that were already sent. Responses that did not arrive yet will be
+        // sent to the connection of the retried request.
+        // Explaining the unwrap(): The app exists, as checked by check_app_permissions().
+        let app = self.apps.get_mut(&app_id).unwrap();
+        let request_origin = RequestOrigin {
+            app_id,
+            app_public_key: app.public_key.clone(),
+            app_request_id: app_request_id.clone(),
+        };
+        let recent_requests = self
+            .recent_requests
+            .entry(app.public_key.clone())
+            .or_insert_with(RecentRequests::new);
+        if let Some(recent_request) = recent_requests.insert(&app_request_id) {
+            warn!(
+                "App {:?} sent a duplicate request: {:?}",
+                app_id, app_request_id
+            );
+            if recent_request.is_acked {
+                let report_mutations = ReportMutations {
+                    opt_app_request_id: Some(app_request_id),
+                    mutations: Vec::new(),
+                };
+                app.send(AppServerToApp::ReportMutations(report_mutations));
+            }
+            for response in recent_request.responses {
+                app.send(response);
+            }
+            self.reroute_requests(&request_origin);
+            return Ok(());
+        }
+
         macro_rules! to_funder {
             ( $x:expr ) => {{
                 use FunderControl::*;
@@ -449,23 +668,73 @@ where
             // Requests that go to funder:
             AddRelay(x) => to_funder!(AddRelay(x)),
             RemoveRelay(x) => to_funder!(RemoveRelay(x)),
+            SetRelayName(x) => to_funder!(SetRelayName(x)),
             CreatePayment(x) => to_funder!(CreatePayment(x)),
             RequestClosePayment(payment_id) => {
                 if self
                     .close_payment_requests
-                    .insert(payment_id.clone(), app_id)
+                    .insert(payment_id.clone(), request_origin)
                     .is_some()
                 {
                     warn!("RequestClosePayment: payment_id clash.");
                 }
                 to_funder!(RequestClosePayment(payment_id))
             }
+            CancelPayment(payment_id) => {
+                // The funder answers with a ResponseCancelPayment, and later with a
+                // ResponseClosePayment, just like RequestClosePayment:
+                if self
+                    .cancel_payment_requests
+                    .insert(payment_id.clone(), request_origin.clone())
+                    .is_some()
+                {
+                    warn!("CancelPayment: payment_id clash.");
+                }
+                if self
+                    .close_payment_requests
+                    .insert(payment_id.clone(), request_origin)
+                    .is_some()
+                {
+                    warn!("CancelPayment: payment_id clash.");
+                }
+                to_funder!(CancelPayment(payment_id))
+            }
             AckClosePayment(x) => to_funder!(AckClosePayment(x)),
             AddInvoice(x) => to_funder!(AddInvoice(x)),
             CancelInvoice(x) => to_funder!(CancelInvoice(x)),
-            CommitInvoice(x) => to_funder!(CommitInvoice(x)),
-            AddFriend(x) => to_funder!(AddFriend(x)),
-            SetFriendRelays(x) => to_funder!(SetFriendRelays(x)),
+            CommitInvoice(commit) => {
+                // Keep track of which application issued this request:
+                if self
+                    .commit_invoice_requests
+                    .insert(commit.invoice_id.clone(), request_origin)
+                    .is_some()
+                {
+                    warn!("CommitInvoice: invoice_id clash.");
+                }
+                to_funder!(CommitInvoice(commit))
+            }
+            AddFriend(add_friend) => {
+                // Keep track of which application issued this request:
+                if self
+                    .friend_relays_requests
+                    .insert(add_friend.friend_public_key.clone(), request_origin)
+                    .is_some()
+                {
+                    warn!("AddFriend: friend_public_key clash.");
+                }
+                to_funder!(AddFriend(add_friend))
+            }
+            SetFriendRelays(set_friend_relays) => {
+                // Keep track of which application issued this request:
+                if self
+                    .friend_relays_requests
+                    .insert(set_friend_relays.friend_public_key.clone(), request_origin)
+                    .is_some()
+                {
+                    warn!("SetFriendRelays: friend_public_key clash.");
+                }
+                to_funder!(SetFriendRelays(set_friend_relays))
+            }
             SetFriendName(x) => to_funder!(SetFriendName(x)),
             SetFriendCurrencyMaxDebt(x) => to_funder!(SetFriendCurrencyMaxDebt(x)),
             SetFriendCurrencyRate(x) => to_funder!(SetFriendCurrencyRate(x)),
@@ -474,7 +743,7 @@ where
             CreateTransaction(create_transaction) => {
                 // Keep track of which application issued this request:
                 self.transactions
-                    .insert(create_transaction.request_id.clone(), app_id);
+                    .insert(create_transaction.request_id.clone(), request_origin);
                 to_funder!(CreateTransaction(create_transaction))
             }
             RemoveFriend(friend_public_key) => {
@@ -495,6 +764,13 @@ where
                 };
                 to_funder!(SetFriendStatus(set_friend_status))
             }
+            PauseFriend(friend_public_key) => {
+                let set_friend_status = SetFriendStatus {
+                    friend_public_key,
+                    status: FriendStatus::Paused,
+                };
+                to_funder!(SetFriendStatus(set_friend_status))
+            }
             OpenFriendCurrency(open_friend_currency) => {
                 let set_requests_status = SetFriendCurrencyRequestsStatus {
                     friend_public_key: open_friend_currency.friend_public_key,
@@ -519,13 +795,27 @@ where
                 // Keep track of which application issued this request:
                 if self
                     .route_requests
-                    .insert(request_routes.request_id.clone(), app_id)
+                    .insert(request_routes.request_id.clone(), request_origin)
                     .is_some()
                 {
                     warn!("RequestRoutes: request_id clash.");
                 }
                 to_index_client!(RequestRoutes(request_routes))
             }
+
+            // Requests handled by the app server itself:
+            Ping => {
+                let app = self.apps.get_mut(&app_id).unwrap();
+                if let Some(recent_requests) = self.recent_requests.get_mut(&app.public_key) {
+                    recent_requests.set_acked(&app_request_id);
+                }
+                let report_mutations = ReportMutations {
+                    opt_app_request_id: Some(app_request_id),
+                    mutations: Vec::new(),
+                };
+                app.send(AppServerToApp::ReportMutations(report_mutations));
+                Ok(())
+            }
         }
     }
 }
@@ -537,6 +827,7 @@ pub async fn app_server_loop<B, FF, TF, FIC, TIC, IC, S>(
     to_index_client: TIC,
     incoming_connections: IC,
     initial_node_report: NodeReport<B>,
+    max_app_queued_messages: usize,
     spawner: S,
 ) -> Result<(), AppServerError>
 where
@@ -554,6 +845,7 @@ where
         to_index_client,
         from_app_sender,
         initial_node_report,
+        max_app_queued_messages,
         spawner,
     );
 
@@ -608,6 +900,7 @@ where
                 app_server.handle_from_app(app_id, opt_app_message).await?
             }
         }
+        app_server.remove_disconnected_apps()?;
     }
     Ok(())
 }
@@ -43,6 +43,7 @@ where
 
     let (report_sender, report_receiver) = oneshot::channel();
     let incoming_app_connection = IncomingAppConnection {
+        app_public_key: PublicKey::from(&[0xd0; PublicKey::len()]),
         app_permissions,
         report_sender,
     };
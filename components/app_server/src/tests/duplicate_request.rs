@@ -0,0 +1,290 @@
+use futures::channel::{mpsc, oneshot};
+use futures::executor::{block_on, ThreadPool};
+use futures::task::Spawn;
+use futures::{SinkExt, StreamExt};
+
+use common::conn::ConnPair;
+
+use proto::crypto::{PaymentId, PublicKey, Uid};
+
+use proto::app_server::messages::{AppPermissions, AppRequest, AppServerToApp, AppToAppServer};
+use proto::funder::messages::{
+    AddFriend, CreateTransaction, FriendsRoute, FunderControl, FunderOutgoingControl,
+    RequestResult, TransactionResult,
+};
+use proto::report::messages::FunderReportMutations;
+
+use super::utils::{dummy_named_relay_address, spawn_dummy_app_server};
+use crate::server::IncomingAppConnection;
+
+/// Open a new connection to the App Server, for the app with the given public key
+async fn connect_app(
+    connections_sender: &mut mpsc::Sender<IncomingAppConnection<u32>>,
+    app_public_key: PublicKey,
+) -> (
+    mpsc::Sender<AppToAppServer<u32>>,
+    mpsc::Receiver<AppServerToApp<u32>>,
+) {
+    let (app_sender, app_server_receiver) = mpsc::channel(0);
+    let (app_server_sender, app_receiver) = mpsc::channel(0);
+    let server_conn_pair = ConnPair::from_raw(app_server_sender, app_server_receiver);
+
+    let app_permissions = AppPermissions {
+        routes: true,
+        buyer: true,
+        seller: true,
+        config: true,
+    };
+
+    let (report_sender, report_receiver) = oneshot::channel();
+    let incoming_app_connection = IncomingAppConnection {
+        app_public_key,
+        app_permissions,
+        report_sender,
+    };
+
+    connections_sender
+        .send(incoming_app_connection)
+        .await
+        .unwrap();
+
+    let (_report, conn_sender) = report_receiver.await.unwrap();
+    conn_sender.send(server_conn_pair).unwrap();
+
+    (app_sender, app_receiver)
+}
+
+async fn task_app_server_loop_duplicate_request<S>(spawner: S)
+where
+    S: Spawn + Clone + Send + 'static,
+{
+    let (
+        mut funder_sender,
+        mut funder_receiver,
+        _index_client_sender,
+        _index_client_receiver,
+        mut connections_sender,
+        _initial_node_report,
+    ) = spawn_dummy_app_server(spawner.clone());
+
+    let app_public_key = PublicKey::from(&[0xd0; PublicKey::len()]);
+    let (mut app_sender, mut app_receiver) =
+        connect_app(&mut connections_sender, app_public_key.clone()).await;
+
+    let add_friend_request_id = Uid::from(&[22; Uid::len()]);
+    let add_friend: AddFriend<u32> = AddFriend {
+        friend_public_key: PublicKey::from(&[0xcc; PublicKey::len()]),
+        relays: vec![dummy_named_relay_address(3).into()],
+        name: "friend_name".to_owned(),
+    };
+    let add_friend_command = AppToAppServer::new(
+        add_friend_request_id.clone(),
+        AppRequest::AddFriend(add_friend.clone()),
+    );
+    app_sender.send(add_friend_command.clone()).await.unwrap();
+
+    // AddFriend command should be forwarded to the Funder:
+    let to_funder_message = funder_receiver.next().await.unwrap();
+    assert_eq!(to_funder_message.app_request_id, add_friend_request_id);
+    match to_funder_message.funder_control {
+        FunderControl::AddFriend(received_add_friend) => {
+            assert_eq!(received_add_friend, add_friend)
+        }
+        _ => unreachable!(),
+    };
+
+    // Send the same command again, before the Funder acknowledged the first one.
+    // The duplicate should not be forwarded to the Funder:
+    app_sender.send(add_friend_command.clone()).await.unwrap();
+
+    let add_relay_request_id = Uid::from(&[23; Uid::len()]);
+    app_sender
+        .send(AppToAppServer::new(
+            add_relay_request_id.clone(),
+            AppRequest::AddRelay(dummy_named_relay_address(0)),
+        ))
+        .await
+        .unwrap();
+
+    let to_funder_message = funder_receiver.next().await.unwrap();
+    assert_eq!(to_funder_message.app_request_id, add_relay_request_id);
+    match to_funder_message.funder_control {
+        FunderControl::AddRelay(address) => assert_eq!(address, dummy_named_relay_address(0)),
+        _ => unreachable!(),
+    };
+
+    // The Funder acknowledges the AddFriend command:
+    funder_sender
+        .send(FunderOutgoingControl::ReportMutations(
+            FunderReportMutations {
+                opt_app_request_id: Some(add_friend_request_id.clone()),
+                mutations: Vec::new(),
+            },
+        ))
+        .await
+        .unwrap();
+
+    let to_app_message = app_receiver.next().await.unwrap();
+    match to_app_message {
+        AppServerToApp::ReportMutations(report_mutations) => {
+            assert_eq!(
+                report_mutations.opt_app_request_id,
+                Some(add_friend_request_id.clone())
+            );
+        }
+        _ => unreachable!(),
+    }
+
+    // The app reconnects, and sends the same command again, after it was acknowledged.
+    // The App Server should acknowledge it again, without forwarding it to the Funder:
+    drop(app_sender);
+    drop(app_receiver);
+    let (mut app_sender, mut app_receiver) =
+        connect_app(&mut connections_sender, app_public_key).await;
+    app_sender.send(add_friend_command).await.unwrap();
+
+    let to_app_message = app_receiver.next().await.unwrap();
+    match to_app_message {
+        AppServerToApp::ReportMutations(report_mutations) => {
+            assert_eq!(
+                report_mutations.opt_app_request_id,
+                Some(add_friend_request_id.clone())
+            );
+            assert!(report_mutations.mutations.is_empty());
+        }
+        _ => unreachable!(),
+    }
+
+    // The next message the Funder receives is a new command.
+    // This means that the friend was added only once:
+    let add_relay_request_id = Uid::from(&[24; Uid::len()]);
+    app_sender
+        .send(AppToAppServer::new(
+            add_relay_request_id.clone(),
+            AppRequest::AddRelay(dummy_named_relay_address(1)),
+        ))
+        .await
+        .unwrap();
+
+    let to_funder_message = funder_receiver.next().await.unwrap();
+    assert_eq!(to_funder_message.app_request_id, add_relay_request_id);
+    match to_funder_message.funder_control {
+        FunderControl::AddRelay(address) => assert_eq!(address, dummy_named_relay_address(1)),
+        _ => unreachable!(),
+    };
+}
+
+#[test]
+fn test_app_server_loop_duplicate_request() {
+    let thread_pool = ThreadPool::new().unwrap();
+    block_on(task_app_server_loop_duplicate_request(thread_pool.clone()));
+}
+
+async fn task_app_server_loop_duplicate_request_response<S>(spawner: S)
+where
+    S: Spawn + Clone + Send + 'static,
+{
+    let (
+        mut funder_sender,
+        mut funder_receiver,
+        _index_client_sender,
+        _index_client_receiver,
+        mut connections_sender,
+        _initial_node_report,
+    ) = spawn_dummy_app_server(spawner.clone());
+
+    let app_public_key = PublicKey::from(&[0xd0; PublicKey::len()]);
+    let (mut app_sender, app_receiver) =
+        connect_app(&mut connections_sender, app_public_key.clone()).await;
+
+    let create_transaction = CreateTransaction {
+        payment_id: PaymentId::from(&[1; PaymentId::len()]),
+        request_id: Uid::from(&[3; Uid::len()]),
+        route: FriendsRoute {
+            public_keys: vec![PublicKey::from(&[0xcc; PublicKey::len()])],
+        },
+        dest_payment: 20,
+        fees: 4,
+    };
+    let create_transaction_command = AppToAppServer::new(
+        Uid::from(&[22; Uid::len()]),
+        AppRequest::CreateTransaction(create_transaction.clone()),
+    );
+    app_sender
+        .send(create_transaction_command.clone())
+        .await
+        .unwrap();
+
+    let to_funder_message = funder_receiver.next().await.unwrap();
+    match to_funder_message.funder_control {
+        FunderControl::CreateTransaction(received_create_transaction) => {
+            assert_eq!(received_create_transaction, create_transaction)
+        }
+        _ => unreachable!(),
+    };
+
+    // The app reconnects before the transaction result arrives, and retries the request:
+    drop(app_sender);
+    drop(app_receiver);
+    let (mut app_sender, mut app_receiver) =
+        connect_app(&mut connections_sender, app_public_key.clone()).await;
+    app_sender
+        .send(create_transaction_command.clone())
+        .await
+        .unwrap();
+
+    // The transaction result is sent to the new connection:
+    let transaction_result = TransactionResult {
+        request_id: create_transaction.request_id.clone(),
+        result: RequestResult::Success,
+    };
+    funder_sender
+        .send(FunderOutgoingControl::TransactionResult(
+            transaction_result.clone(),
+        ))
+        .await
+        .unwrap();
+
+    match app_receiver.next().await.unwrap() {
+        AppServerToApp::TransactionResult(received_transaction_result) => {
+            assert_eq!(received_transaction_result, transaction_result)
+        }
+        _ => unreachable!(),
+    };
+
+    // The app reconnects again, and retries the request after the result was sent.
+    // The result is sent again:
+    drop(app_sender);
+    drop(app_receiver);
+    let (mut app_sender, mut app_receiver) =
+        connect_app(&mut connections_sender, app_public_key).await;
+    app_sender.send(create_transaction_command).await.unwrap();
+
+    match app_receiver.next().await.unwrap() {
+        AppServerToApp::TransactionResult(received_transaction_result) => {
+            assert_eq!(received_transaction_result, transaction_result)
+        }
+        _ => unreachable!(),
+    };
+
+    // The Funder received the transaction only once:
+    let add_relay_request_id = Uid::from(&[24; Uid::len()]);
+    app_sender
+        .send(AppToAppServer::new(
+            add_relay_request_id.clone(),
+            AppRequest::AddRelay(dummy_named_relay_address(1)),
+        ))
+        .await
+        .unwrap();
+
+    let to_funder_message = funder_receiver.next().await.unwrap();
+    assert_eq!(to_funder_message.app_request_id, add_relay_request_id);
+}
+
+#[test]
+fn test_app_server_loop_duplicate_request_response() {
+    let thread_pool = ThreadPool::new().unwrap();
+    block_on(task_app_server_loop_duplicate_request_response(
+        thread_pool.clone(),
+    ));
+}
@@ -5,12 +5,15 @@ use futures::{SinkExt, StreamExt};
 
 use common::conn::ConnPair;
 
-use proto::crypto::Uid;
+use proto::crypto::{PublicKey, Uid};
 
 use proto::app_server::messages::{
     AppPermissions, AppRequest, AppServerToApp, AppToAppServer, NodeReportMutation,
 };
-use proto::funder::messages::{FunderControl, FunderOutgoingControl};
+use proto::funder::messages::{
+    FriendRelaysResult, FriendStatus, FunderControl, FunderOutgoingControl, ResponseFriendRelays,
+    SetFriendRelays,
+};
 use proto::report::messages::{FunderReportMutation, FunderReportMutations};
 
 use super::utils::{dummy_named_relay_address, spawn_dummy_app_server};
@@ -42,6 +45,7 @@ where
 
     let (report_sender, report_receiver) = oneshot::channel();
     let incoming_app_connection = IncomingAppConnection {
+        app_public_key: PublicKey::from(&[0xd0; PublicKey::len()]),
         app_permissions,
         report_sender,
     };
@@ -106,6 +110,59 @@ where
         }
         _ => unreachable!(),
     }
+
+    // Pause a friend through the app:
+    let friend_public_key = PublicKey::from(&[0xee; PublicKey::len()]);
+    let funder_command = AppToAppServer::new(
+        Uid::from(&[23; Uid::len()]),
+        AppRequest::PauseFriend(friend_public_key.clone()),
+    );
+    app_sender.send(funder_command).await.unwrap();
+
+    // The friend status should be set to Paused:
+    let to_funder_message = funder_receiver.next().await.unwrap();
+    assert_eq!(
+        to_funder_message.app_request_id,
+        Uid::from(&[23; Uid::len()])
+    );
+    match to_funder_message.funder_control {
+        FunderControl::SetFriendStatus(set_friend_status) => {
+            assert_eq!(set_friend_status.friend_public_key, friend_public_key);
+            assert_eq!(set_friend_status.status, FriendStatus::Paused);
+        }
+        _ => unreachable!(),
+    };
+
+    // Set the relays of a friend through the app:
+    let funder_command = AppToAppServer::new(
+        Uid::from(&[24; Uid::len()]),
+        AppRequest::SetFriendRelays(SetFriendRelays {
+            friend_public_key: friend_public_key.clone(),
+            relays: Vec::new(),
+        }),
+    );
+    app_sender.send(funder_command).await.unwrap();
+    let to_funder_message = funder_receiver.next().await.unwrap();
+    match to_funder_message.funder_control {
+        FunderControl::SetFriendRelays(_) => {}
+        _ => unreachable!(),
+    };
+
+    // The funder rejects the relays, and the app is told about it:
+    let response_friend_relays = ResponseFriendRelays {
+        friend_public_key,
+        result: FriendRelaysResult::TooManyRelays,
+    };
+    funder_sender
+        .send(FunderOutgoingControl::ResponseFriendRelays(
+            response_friend_relays.clone(),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(
+        app_receiver.next().await.unwrap(),
+        AppServerToApp::ResponseFriendRelays(response_friend_relays)
+    );
 }
 
 #[test]
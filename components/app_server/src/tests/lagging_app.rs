@@ -0,0 +1,92 @@
+use futures::channel::{mpsc, oneshot};
+use futures::executor::{block_on, ThreadPool};
+use futures::task::Spawn;
+use futures::{SinkExt, StreamExt};
+
+use common::conn::ConnPair;
+
+use proto::app_server::messages::{AppPermissions, AppRequest, AppServerToApp, AppToAppServer};
+use proto::crypto::{PublicKey, Uid};
+use proto::index_client::messages::{IndexClientReportMutations, IndexClientToAppServer};
+
+use crate::server::IncomingAppConnection;
+
+use super::utils::{spawn_dummy_app_server, TEST_MAX_APP_QUEUED_MESSAGES};
+
+/// Amount of messages sent to the lagging app.
+/// Much more than the app server is willing to queue for a single app.
+const NUM_MESSAGES: usize = 8 * TEST_MAX_APP_QUEUED_MESSAGES;
+
+async fn task_app_server_loop_lagging_app<S>(spawner: S)
+where
+    S: Spawn + Clone + Send + 'static,
+{
+    let (
+        _funder_sender,
+        _funder_receiver,
+        mut index_client_sender,
+        _index_client_receiver,
+        mut connections_sender,
+        _initial_node_report,
+    ) = spawn_dummy_app_server(spawner.clone());
+
+    let (mut app_sender, app_server_receiver) = mpsc::channel(0);
+    let (app_server_sender, mut app_receiver) = mpsc::channel(0);
+    let server_conn_pair = ConnPair::from_raw(app_server_sender, app_server_receiver);
+
+    let app_permissions = AppPermissions {
+        routes: true,
+        buyer: true,
+        seller: true,
+        config: true,
+    };
+
+    let (report_sender, report_receiver) = oneshot::channel();
+    let incoming_app_connection = IncomingAppConnection {
+        app_public_key: PublicKey::from(&[0xd0; PublicKey::len()]),
+        app_permissions,
+        report_sender,
+    };
+
+    connections_sender
+        .send(incoming_app_connection)
+        .await
+        .unwrap();
+
+    let (_report, conn_sender) = report_receiver.await.unwrap();
+    conn_sender.send(server_conn_pair).unwrap();
+
+    // The app does not read any messages, while the index client keeps sending report
+    // mutations. The app server should not block:
+    for _ in 0..NUM_MESSAGES {
+        index_client_sender
+            .send(IndexClientToAppServer::ReportMutations(
+                IndexClientReportMutations {
+                    opt_app_request_id: None,
+                    mutations: Vec::new(),
+                },
+            ))
+            .await
+            .unwrap();
+    }
+
+    // The lagging app was disconnected, and only received some of the messages:
+    let mut num_received = 0usize;
+    while let Some(app_server_to_app) = app_receiver.next().await {
+        match app_server_to_app {
+            AppServerToApp::ReportMutations(_) => num_received += 1,
+            _ => unreachable!(),
+        }
+    }
+    assert!(num_received < NUM_MESSAGES);
+
+    // The lagging app was removed, and the app server does not receive its requests anymore:
+    let app_to_app_server = AppToAppServer::new(Uid::from(&[22; Uid::len()]), AppRequest::Ping);
+    assert!(app_sender.send(app_to_app_server).await.is_err());
+}
+
+#[test]
+fn test_app_server_loop_lagging_app() {
+    let thread_pool = ThreadPool::new().unwrap();
+    block_on(task_app_server_loop_lagging_app(thread_pool.clone()));
+}
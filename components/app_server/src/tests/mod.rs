@@ -1,6 +1,9 @@
 mod all_apps_closed;
+mod duplicate_request;
 mod funder_command;
 mod index_client_command;
+mod lagging_app;
+mod ping;
 mod request_routes;
 mod request_send_funds;
 mod two_apps;
@@ -0,0 +1,72 @@
+use futures::channel::{mpsc, oneshot};
+use futures::executor::{block_on, ThreadPool};
+use futures::task::Spawn;
+use futures::{SinkExt, StreamExt};
+
+use common::conn::ConnPair;
+
+use proto::crypto::{PublicKey, Uid};
+
+use proto::app_server::messages::{AppPermissions, AppRequest, AppServerToApp, AppToAppServer};
+
+use super::utils::spawn_dummy_app_server;
+use crate::server::IncomingAppConnection;
+
+async fn task_app_server_loop_ping<S>(spawner: S)
+where
+    S: Spawn + Clone + Send + 'static,
+{
+    let (
+        _funder_sender,
+        _funder_receiver,
+        _index_client_sender,
+        _index_client_receiver,
+        mut connections_sender,
+        _initial_node_report,
+    ) = spawn_dummy_app_server(spawner.clone());
+
+    let (mut app_sender, app_server_receiver) = mpsc::channel(0);
+    let (app_server_sender, mut app_receiver) = mpsc::channel(0);
+    let server_conn_pair = ConnPair::from_raw(app_server_sender, app_server_receiver);
+
+    // An app without any permissions may still ping:
+    let (report_sender, report_receiver) = oneshot::channel();
+    let incoming_app_connection = IncomingAppConnection {
+        app_public_key: PublicKey::from(&[0xd0; PublicKey::len()]),
+        app_permissions: AppPermissions::read_only(),
+        report_sender,
+    };
+
+    connections_sender
+        .send(incoming_app_connection)
+        .await
+        .unwrap();
+
+    let (_report, conn_sender) = report_receiver.await.unwrap();
+    conn_sender.send(server_conn_pair).unwrap();
+
+    let ping_request_id = Uid::from(&[22; Uid::len()]);
+    app_sender
+        .send(AppToAppServer::<u32>::new(
+            ping_request_id.clone(),
+            AppRequest::Ping,
+        ))
+        .await
+        .unwrap();
+
+    // The App Server acknowledges the ping by itself:
+    let to_app_message = app_receiver.next().await.unwrap();
+    match to_app_message {
+        AppServerToApp::ReportMutations(report_mutations) => {
+            assert_eq!(report_mutations.opt_app_request_id, Some(ping_request_id));
+            assert!(report_mutations.mutations.is_empty());
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn test_app_server_loop_ping() {
+    let thread_pool = ThreadPool::new().unwrap();
+    block_on(task_app_server_loop_ping(thread_pool.clone()));
+}
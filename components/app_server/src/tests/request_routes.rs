@@ -45,6 +45,7 @@ where
 
     let (report_sender, report_receiver) = oneshot::channel();
     let incoming_app_connection = IncomingAppConnection {
+        app_public_key: PublicKey::from(&[0xd0; PublicKey::len()]),
         app_permissions,
         report_sender,
     };
@@ -71,6 +72,7 @@ where
     };
     let (report_sender, report_receiver) = oneshot::channel();
     let incoming_app_connection = IncomingAppConnection {
+        app_public_key: PublicKey::from(&[0xd1; PublicKey::len()]),
         app_permissions,
         report_sender,
     };
@@ -96,6 +98,7 @@ where
         source: PublicKey::from(&[0xee; PublicKey::len()]),
         destination: PublicKey::from(&[0xff; PublicKey::len()]),
         opt_exclude: None,
+        opt_max_hops: None,
     };
 
     let to_app_server = AppToAppServer::new(
@@ -44,6 +44,7 @@ where
 
     let (report_sender, report_receiver) = oneshot::channel();
     let incoming_app_connection = IncomingAppConnection {
+        app_public_key: PublicKey::from(&[0xd0; PublicKey::len()]),
         app_permissions,
         report_sender,
     };
@@ -71,6 +72,7 @@ where
 
     let (report_sender, report_receiver) = oneshot::channel();
     let incoming_app_connection = IncomingAppConnection {
+        app_public_key: PublicKey::from(&[0xd1; PublicKey::len()]),
         app_permissions,
         report_sender,
     };
@@ -148,7 +150,7 @@ where
     // Funder returns a TransactionResult that is not related to any open request.
     let transaction_result = TransactionResult {
         request_id: Uid::from(&[2; Uid::len()]),
-        result: RequestResult::Failure,
+        result: RequestResult::Failure(None),
     };
     funder_sender
         .send(FunderOutgoingControl::TransactionResult(transaction_result))
@@ -162,7 +164,7 @@ where
     // Funder returns a response that corresponds to the open request:
     let transaction_result = TransactionResult {
         request_id: Uid::from(&[3; Uid::len()]),
-        result: RequestResult::Failure,
+        result: RequestResult::Failure(None),
     };
     funder_sender
         .send(FunderOutgoingControl::TransactionResult(
@@ -186,7 +188,7 @@ where
     // has a matching id:
     let transaction_result = TransactionResult {
         request_id: Uid::from(&[3; Uid::len()]),
-        result: RequestResult::Failure,
+        result: RequestResult::Failure(None),
     };
     funder_sender
         .send(FunderOutgoingControl::TransactionResult(transaction_result))
@@ -16,6 +16,9 @@ use proto::report::messages::FunderReport;
 
 use crate::server::{app_server_loop, IncomingAppConnection};
 
+/// Maximum amount of messages queued for a single app in tests.
+pub const TEST_MAX_APP_QUEUED_MESSAGES: usize = 0x20;
+
 /// A helper function to quickly create a dummy NamedRelayAddress.
 pub fn dummy_named_relay_address(index: u8) -> NamedRelayAddress<u32> {
     NamedRelayAddress {
@@ -85,6 +88,7 @@ where
     let initial_node_report = NodeReport {
         funder_report,
         index_client_report,
+        currency_infos: Vec::new(),
     };
 
     let fut_loop = app_server_loop(
@@ -94,6 +98,7 @@ where
         to_index_client,
         incoming_connections,
         initial_node_report.clone(),
+        TEST_MAX_APP_QUEUED_MESSAGES,
         spawner.clone(),
     )
     .map_err(|e| error!("app_server_loop() error: {:?}", e))
@@ -4,7 +4,7 @@ use std::marker::Unpin;
 
 use futures::channel::mpsc;
 use futures::task::{Spawn, SpawnExt};
-use futures::{FutureExt, SinkExt, Stream, StreamExt, TryFutureExt};
+use futures::{stream, FutureExt, SinkExt, Stream, StreamExt, TryFutureExt};
 
 use common::conn::{BoxFuture, ConnPair, ConnPairVec, FuncFutTransform, FutTransform};
 use common::transform_pool::transform_pool_loop;
@@ -25,7 +25,7 @@ use identity::IdentityClient;
 
 use connection::create_version_encrypt_keepalive;
 
-use index_server::{index_server, IndexServerError};
+use index_server::{index_server, AdminConn, IndexServerError};
 
 #[derive(Clone)]
 struct ConnTransformer<CT, S> {
@@ -322,6 +322,8 @@ where
         trusted_servers,
         incoming_server_conns,
         incoming_client_conns,
+        // Admin connections are not exposed over the network:
+        stream::empty::<AdminConn>(),
         server_connector,
         timer_client,
         INDEX_NODE_TIMEOUT_TICKS,
@@ -81,7 +81,9 @@ pub fn load_trusted_servers(dir_path: &Path) -> Result<Vec<IndexServerFile>, Ind
         if path.is_dir() {
             continue;
         }
-        res_trusted.push(deserialize_from_string(&fs::read_to_string(&path)?)?);
+        res_trusted.push(
+            deserialize_from_string(&fs::read_to_string(&path)?).map_err(|e| e.with_path(&path))?,
+        );
     }
     Ok(res_trusted)
 }
@@ -94,7 +96,8 @@ pub fn stindex(st_index_cmd: StIndexCmd) -> Result<(), IndexServerBinError> {
         trusted,
     } = st_index_cmd;
 
-    let identity_file: IdentityFile = deserialize_from_string(&fs::read_to_string(&idfile)?)?;
+    let identity_file: IdentityFile =
+        deserialize_from_string(&fs::read_to_string(&idfile)?).map_err(|e| e.with_path(&idfile))?;
     let identity = SoftwareEd25519Identity::from_private_key(&identity_file.private_key)
         .map_err(|_| IndexServerBinError::LoadIdentityError)?;
 
@@ -1,4 +1,4 @@
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 use std::fs::{self, File};
 use std::io::Write;
 use std::path::PathBuf;
@@ -12,10 +12,13 @@ use crypto::rand::{system_random, RandGen};
 
 use proto::app_server::messages::AppPermissions;
 use proto::crypto::PrivateKey;
+use proto::funder::messages::{Currency, CurrencyError};
 use proto::net::messages::{NetAddress, NetAddressError};
+use proto::report::messages::CurrencyInfo;
 
 use database::file_db::FileDb;
-use node::NodeState;
+use database::AtomicDb;
+use node::{NodeMutation, NodeState};
 
 use proto::file::{
     IdentityFile, IndexServerFile, NodeAddressFile, NodeEntryFile, RelayAddressFile, TrustedAppFile,
@@ -125,6 +128,25 @@ pub struct NodeEntryCmd {
     pub output_path: PathBuf,
 }
 
+#[derive(Debug, StructOpt)]
+pub struct SetCurrencyInfoCmd {
+    /// Node database file path
+    #[structopt(parse(from_os_str), short = "d", long = "database")]
+    pub database_path: PathBuf,
+    /// Currency name
+    #[structopt(short = "c", long = "currency")]
+    pub currency_name: String,
+    /// Amount of digits displayed after the decimal point
+    #[structopt(long = "decimals", default_value = "0")]
+    pub decimals: u8,
+    /// Symbol displayed after amounts of the currency
+    #[structopt(short = "s", long = "symbol", default_value = "")]
+    pub symbol: String,
+    /// Remove the display metadata of the currency instead of setting it
+    #[structopt(long = "remove")]
+    pub remove: bool,
+}
+
 /// stmgr: offSeT ManaGeR
 /// A util for managing Offset entities and files
 #[derive(Debug, StructOpt)]
@@ -152,6 +174,10 @@ pub enum StMgrCmd {
     /// A node entry allows to remotely log into a node.
     #[structopt(name = "node-entry")]
     NodeEntry(NodeEntryCmd),
+    /// Set the display metadata of a currency in a node database.
+    /// Should be used while the node is not running.
+    #[structopt(name = "set-currency-info")]
+    SetCurrencyInfo(SetCurrencyInfoCmd),
 }
 
 fn init_node_db(
@@ -169,7 +195,8 @@ fn init_node_db(
     }
 
     // Parse identity file:
-    let identity_file: IdentityFile = deserialize_from_string(&fs::read_to_string(&idfile_path)?)?;
+    let identity_file: IdentityFile = deserialize_from_string(&fs::read_to_string(&idfile_path)?)
+        .map_err(|e| e.with_path(&idfile_path))?;
     let identity = SoftwareEd25519Identity::from_private_key(&identity_file.private_key)
         .map_err(|_| InitNodeDbError::LoadIdentityError)?;
     let local_public_key = identity.get_public_key();
@@ -233,7 +260,8 @@ fn app_ticket(
 ) -> Result<(), AppTicketError> {
     // Obtain app's public key:
     // - Parse identity file:
-    let identity_file: IdentityFile = deserialize_from_string(&fs::read_to_string(&idfile_path)?)?;
+    let identity_file: IdentityFile = deserialize_from_string(&fs::read_to_string(&idfile_path)?)
+        .map_err(|e| e.with_path(&idfile_path))?;
     let identity = SoftwareEd25519Identity::from_private_key(&identity_file.private_key)
         .map_err(|_| AppTicketError::LoadIdentityError)?;
 
@@ -255,7 +283,8 @@ fn app_ticket(
     // Store app ticket to file:
     let trusted_app_file = TrustedAppFile {
         public_key,
-        permissions,
+        opt_template: None,
+        permissions: permissions.into(),
     };
 
     let mut file = File::create(output_path)?;
@@ -287,7 +316,8 @@ fn relay_ticket(
     }
 
     // Parse identity file:
-    let identity_file: IdentityFile = deserialize_from_string(&fs::read_to_string(&idfile_path)?)?;
+    let identity_file: IdentityFile = deserialize_from_string(&fs::read_to_string(&idfile_path)?)
+        .map_err(|e| e.with_path(&idfile_path))?;
     let identity = SoftwareEd25519Identity::from_private_key(&identity_file.private_key)
         .map_err(|_| RelayTicketError::LoadIdentityError)?;
 
@@ -328,7 +358,8 @@ fn index_ticket(
     }
 
     // Parse identity file:
-    let identity_file: IdentityFile = deserialize_from_string(&fs::read_to_string(&idfile_path)?)?;
+    let identity_file: IdentityFile = deserialize_from_string(&fs::read_to_string(&idfile_path)?)
+        .map_err(|e| e.with_path(&idfile_path))?;
     let identity = SoftwareEd25519Identity::from_private_key(&identity_file.private_key)
         .map_err(|_| IndexTicketError::LoadIdentityError)?;
     let public_key = identity.get_public_key();
@@ -367,7 +398,8 @@ fn node_ticket(
     }
 
     // Parse identity file:
-    let identity_file: IdentityFile = deserialize_from_string(&fs::read_to_string(&idfile_path)?)?;
+    let identity_file: IdentityFile = deserialize_from_string(&fs::read_to_string(&idfile_path)?)
+        .map_err(|e| e.with_path(&idfile_path))?;
     let identity = SoftwareEd25519Identity::from_private_key(&identity_file.private_key)
         .map_err(|_| NodeTicketError::LoadIdentityError)?;
     let public_key = identity.get_public_key();
@@ -399,13 +431,15 @@ fn node_entry(
 
     // Parse identity file:
     let node_identity_file: IdentityFile =
-        deserialize_from_string(&fs::read_to_string(&node_idfile_path)?)?;
+        deserialize_from_string(&fs::read_to_string(&node_idfile_path)?)
+            .map_err(|e| e.with_path(&node_idfile_path))?;
     let node_identity = SoftwareEd25519Identity::from_private_key(&node_identity_file.private_key)
         .map_err(|_| NodeTicketError::LoadIdentityError)?;
     let node_public_key = node_identity.get_public_key();
 
     let app_identity_file: IdentityFile =
-        deserialize_from_string(&fs::read_to_string(&app_idfile_path)?)?;
+        deserialize_from_string(&fs::read_to_string(&app_idfile_path)?)
+            .map_err(|e| e.with_path(&app_idfile_path))?;
 
     let node_entry_file = NodeEntryFile {
         node_public_key,
@@ -418,6 +452,43 @@ fn node_entry(
     Ok(())
 }
 
+#[derive(Debug, From)]
+pub enum SetCurrencyInfoError {
+    LoadDbError,
+    MutateDbError,
+    CurrencyError(CurrencyError),
+}
+
+/// Set (or remove) the display metadata of a currency.
+/// The metadata is purely presentational, and is shown in the reports of the node.
+fn set_currency_info(
+    SetCurrencyInfoCmd {
+        database_path,
+        currency_name,
+        decimals,
+        symbol,
+        remove,
+    }: SetCurrencyInfoCmd,
+) -> Result<(), SetCurrencyInfoError> {
+    let currency = Currency::try_from(currency_name)?;
+
+    let mut file_db = FileDb::<NodeState<NetAddress>>::load(database_path)
+        .map_err(|_| SetCurrencyInfoError::LoadDbError)?;
+
+    let mutation = if remove {
+        NodeMutation::RemoveCurrencyInfo(currency)
+    } else {
+        NodeMutation::SetCurrencyInfo(CurrencyInfo {
+            currency,
+            decimals,
+            symbol,
+        })
+    };
+    file_db
+        .mutate_db(&[mutation])
+        .map_err(|_| SetCurrencyInfoError::MutateDbError)
+}
+
 #[allow(clippy::enum_variant_names)]
 #[derive(Debug, From)]
 pub enum StmError {
@@ -427,6 +498,7 @@ pub enum StmError {
     RelayTicketError(RelayTicketError),
     IndexTicketError(IndexTicketError),
     NodeTicketError(NodeTicketError),
+    SetCurrencyInfoError(SetCurrencyInfoError),
 }
 
 pub fn stmgr(st_mgr_cmd: StMgrCmd) -> Result<(), StmError> {
@@ -438,6 +510,7 @@ pub fn stmgr(st_mgr_cmd: StMgrCmd) -> Result<(), StmError> {
         StMgrCmd::IndexTicket(i) => index_ticket(i)?,
         StMgrCmd::NodeTicket(i) => node_ticket(i)?,
         StMgrCmd::NodeEntry(i) => node_entry(i)?,
+        StMgrCmd::SetCurrencyInfo(i) => set_currency_info(i)?,
     }
 
     Ok(())
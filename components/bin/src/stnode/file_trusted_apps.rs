@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
 use futures::StreamExt;
 
@@ -16,17 +17,100 @@ use proto::ser_string::{deserialize_from_string, StringSerdeError};
 
 use crate::stnode::net_node::TrustedApps;
 
+/// Name of the subdirectory (inside the trusted apps directory) that contains permission templates
+const TEMPLATES_DIR: &str = "templates";
+
 #[derive(Debug, From)]
 enum FileTrustedAppsError {
     AsyncStdIoError(async_std::io::Error),
     StringSerdeError(StringSerdeError),
+    /// The trusted app file refers to a template that does not exist
+    UnknownTemplate(String),
 }
 
-/// Load all trusted applications files from a given directory.
-async fn load_trusted_apps(
+/// Trusted applications loaded from a directory
+#[derive(Debug)]
+struct LoadedTrustedApps {
+    trusted: HashMap<PublicKey, AppPermissions>,
+    /// Files that could not be read as trusted app files, and were skipped
+    invalid_files: Vec<PathBuf>,
+}
+
+/// A change in the set of trusted applications, between two loads of the trusted directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TrustedAppChange {
+    Added(PublicKey, AppPermissions),
+    Removed(PublicKey),
+    PermissionsChanged(PublicKey, AppPermissions),
+}
+
+/// Load all permission templates from the templates subdirectory of the trusted apps directory.
+/// Every file in the templates directory is a template, named after the file's name.
+/// Malformed templates are logged and skipped.
+async fn load_templates(
     dir_path: &Path,
-) -> Result<HashMap<PublicKey, AppPermissions>, FileTrustedAppsError> {
-    let mut res_trusted = HashMap::new();
+) -> Result<HashMap<String, AppPermissions>, FileTrustedAppsError> {
+    let mut templates = HashMap::new();
+    let templates_path = dir_path.join(TEMPLATES_DIR);
+    if !templates_path.is_dir().await {
+        return Ok(templates);
+    }
+
+    let mut dir = fs::read_dir(&templates_path).await?;
+    while let Some(entry) = dir.next().await {
+        let entry = entry?;
+        let path = entry.path();
+        if path.is_dir().await {
+            continue;
+        }
+
+        let data = fs::read_to_string(&path).await?;
+        match deserialize_from_string::<AppPermissions>(&data) {
+            Ok(permissions) => {
+                let template_name = entry.file_name().to_string_lossy().into_owned();
+                templates.insert(template_name, permissions);
+            }
+            Err(e) => error!(
+                "Skipping malformed permissions template {}: {:?}",
+                path.display(),
+                e
+            ),
+        }
+    }
+    Ok(templates)
+}
+
+/// Load a trusted app file, and calculate the effective permissions of the app.
+async fn load_trusted_app_file(
+    path: &Path,
+    templates: &HashMap<String, AppPermissions>,
+) -> Result<(PublicKey, AppPermissions), FileTrustedAppsError> {
+    let data = fs::read_to_string(path).await?;
+    let trusted_app_file: TrustedAppFile =
+        deserialize_from_string(&data).map_err(|e| e.with_path(path.as_ref()))?;
+
+    // Permissions that are not specified explicitly are taken from the template.
+    // An app without a template has only the permissions that are specified explicitly.
+    let template = match &trusted_app_file.opt_template {
+        Some(template_name) => templates
+            .get(template_name)
+            .cloned()
+            .ok_or_else(|| FileTrustedAppsError::UnknownTemplate(template_name.clone()))?,
+        None => AppPermissions::read_only(),
+    };
+    Ok((
+        trusted_app_file.public_key,
+        trusted_app_file.permissions.merge(&template),
+    ))
+}
+
+/// Load all trusted applications files from a given directory.
+/// Malformed files are logged and skipped, so that they do not affect the other trusted apps.
+async fn load_trusted_apps(dir_path: &Path) -> Result<LoadedTrustedApps, FileTrustedAppsError> {
+    let templates = load_templates(dir_path).await?;
+
+    let mut trusted = HashMap::new();
+    let mut invalid_files = Vec::new();
     let mut dir = fs::read_dir(dir_path).await?;
     while let Some(entry) = dir.next().await {
         let entry = entry?;
@@ -35,11 +119,50 @@ async fn load_trusted_apps(
             continue;
         }
 
-        let trusted_app_file: TrustedAppFile =
-            deserialize_from_string(&fs::read_to_string(&path).await?)?;
-        res_trusted.insert(trusted_app_file.public_key, trusted_app_file.permissions);
+        match load_trusted_app_file(&path, &templates).await {
+            Ok((public_key, permissions)) => {
+                trusted.insert(public_key, permissions);
+            }
+            Err(e) => {
+                error!(
+                    "Skipping malformed trusted app file {}: {:?}",
+                    path.display(),
+                    e
+                );
+                invalid_files.push(path);
+            }
+        }
+    }
+    Ok(LoadedTrustedApps {
+        trusted,
+        invalid_files,
+    })
+}
+
+/// Calculate the changes between two sets of trusted applications.
+fn diff_trusted_apps(
+    old_trusted: &HashMap<PublicKey, AppPermissions>,
+    new_trusted: &HashMap<PublicKey, AppPermissions>,
+) -> Vec<TrustedAppChange> {
+    let mut changes = Vec::new();
+    for (public_key, permissions) in new_trusted {
+        match old_trusted.get(public_key) {
+            None => changes.push(TrustedAppChange::Added(
+                public_key.clone(),
+                permissions.clone(),
+            )),
+            Some(old_permissions) if old_permissions != permissions => changes.push(
+                TrustedAppChange::PermissionsChanged(public_key.clone(), permissions.clone()),
+            ),
+            Some(_) => {}
+        }
+    }
+    for public_key in old_trusted.keys() {
+        if !new_trusted.contains_key(public_key) {
+            changes.push(TrustedAppChange::Removed(public_key.clone()));
+        }
     }
-    Ok(res_trusted)
+    changes
 }
 
 /// Trusted apps checker that is stored as files in a directory.
@@ -50,16 +173,54 @@ async fn load_trusted_apps(
 ///     - trusted_app_file2
 ///     - trusted_app_file3
 ///     - ...
+///     - templates (Optional)
+///         - template_name1
+///         - template_name2
+///         - ...
+///
+/// Where each trusted_app_file corresponds to the permissions of one app, and each template file
+/// contains full permissions that can be shared between apps.
 ///
-/// Where each trusted_app_file corresponds to the permissions of one app.
+/// A trusted app file may refer to a template by its name. Permissions specified explicitly in the
+/// trusted app file override the permissions of the template. Permissions specified neither in the
+/// trusted app file nor in a template are denied. Trusted app files referring to a missing
+/// template are skipped.
+///
+/// The directory is reloaded on every incoming app connection. Changes to the set of trusted apps
+/// since the previous load are logged.
 #[derive(Debug, Clone)]
 pub struct FileTrustedApps {
     trusted_apps_path: PathBuf,
+    /// Trusted apps seen at the previous load, shared between all clones
+    last_trusted: Arc<Mutex<HashMap<PublicKey, AppPermissions>>>,
 }
 
 impl FileTrustedApps {
     pub fn new(trusted_apps_path: PathBuf) -> Self {
-        Self { trusted_apps_path }
+        Self {
+            trusted_apps_path,
+            last_trusted: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Log the changes since the previous load, and remember the new set of trusted apps.
+    fn update_trusted(&self, new_trusted: &HashMap<PublicKey, AppPermissions>) {
+        let mut last_trusted = self.last_trusted.lock().unwrap();
+        for change in diff_trusted_apps(&last_trusted, new_trusted) {
+            match change {
+                TrustedAppChange::Added(public_key, permissions) => {
+                    info!("Trusted app added: {:?}, {:?}", public_key, permissions)
+                }
+                TrustedAppChange::Removed(public_key) => {
+                    info!("Trusted app removed: {:?}", public_key)
+                }
+                TrustedAppChange::PermissionsChanged(public_key, permissions) => info!(
+                    "Trusted app permissions changed: {:?}, {:?}",
+                    public_key, permissions
+                ),
+            }
+        }
+        *last_trusted = new_trusted.clone();
     }
 }
 
@@ -70,14 +231,163 @@ impl TrustedApps for FileTrustedApps {
         app_public_key: &'a PublicKey,
     ) -> BoxFuture<'a, Option<AppPermissions>> {
         Box::pin(async move {
-            let trusted_map = match load_trusted_apps(&self.trusted_apps_path).await {
-                Ok(trusted_map) => trusted_map,
+            let loaded = match load_trusted_apps(&self.trusted_apps_path).await {
+                Ok(loaded) => loaded,
                 Err(e) => {
                     error!("load_trusted_apps() failed: {:?}", e);
                     return None;
                 }
             };
-            trusted_map.get(app_public_key).cloned()
+            self.update_trusted(&loaded.trusted);
+            loaded.trusted.get(app_public_key).cloned()
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::executor::block_on;
+
+    use tempfile::tempdir;
+
+    use proto::file::AppPermissionsFile;
+    use proto::ser_string::serialize_to_string;
+
+    fn permissions(config: bool) -> AppPermissions {
+        AppPermissions {
+            routes: true,
+            buyer: true,
+            seller: true,
+            config,
+        }
+    }
+
+    #[test]
+    fn test_load_trusted_apps_skips_malformed() {
+        let dir = tempdir().unwrap();
+
+        let trusted_app_file = TrustedAppFile {
+            public_key: PublicKey::from(&[0xaa; PublicKey::len()]),
+            opt_template: None,
+            permissions: permissions(true).into(),
+        };
+        std::fs::write(
+            dir.path().join("app0"),
+            serialize_to_string(&trusted_app_file).unwrap(),
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("malformed"), "not a trusted app file").unwrap();
+
+        let dir_path = PathBuf::from(dir.path().to_path_buf());
+        let loaded = block_on(load_trusted_apps(&dir_path)).unwrap();
+
+        // The valid file is loaded, and the malformed file is skipped:
+        assert_eq!(loaded.trusted.len(), 1);
+        assert_eq!(
+            loaded.trusted.get(&trusted_app_file.public_key),
+            Some(&permissions(true))
+        );
+        assert_eq!(loaded.invalid_files, vec![dir_path.join("malformed")]);
+    }
+
+    #[test]
+    fn test_load_trusted_apps_template() {
+        let dir = tempdir().unwrap();
+        let pk = |i: u8| PublicKey::from(&[i; PublicKey::len()]);
+
+        std::fs::create_dir(dir.path().join(TEMPLATES_DIR)).unwrap();
+        std::fs::write(
+            dir.path().join(TEMPLATES_DIR).join("shop"),
+            serialize_to_string(&permissions(false)).unwrap(),
+        )
+        .unwrap();
+
+        let write_app = |file_name: &str, trusted_app_file: TrustedAppFile| {
+            std::fs::write(
+                dir.path().join(file_name),
+                serialize_to_string(&trusted_app_file).unwrap(),
+            )
+            .unwrap();
+        };
+
+        // Uses the template as is:
+        write_app(
+            "app0",
+            TrustedAppFile {
+                public_key: pk(0),
+                opt_template: Some("shop".to_owned()),
+                permissions: AppPermissionsFile::default(),
+            },
+        );
+        // Overrides some of the template's permissions:
+        write_app(
+            "app1",
+            TrustedAppFile {
+                public_key: pk(1),
+                opt_template: Some("shop".to_owned()),
+                permissions: AppPermissionsFile {
+                    buyer: Some(false),
+                    config: Some(true),
+                    ..AppPermissionsFile::default()
+                },
+            },
+        );
+        // Refers to a missing template:
+        write_app(
+            "app2",
+            TrustedAppFile {
+                public_key: pk(2),
+                opt_template: Some("missing".to_owned()),
+                permissions: AppPermissionsFile::default(),
+            },
+        );
+
+        let dir_path = PathBuf::from(dir.path().to_path_buf());
+        let loaded = block_on(load_trusted_apps(&dir_path)).unwrap();
+
+        assert_eq!(loaded.trusted.len(), 2);
+        assert_eq!(loaded.trusted.get(&pk(0)), Some(&permissions(false)));
+        assert_eq!(
+            loaded.trusted.get(&pk(1)),
+            Some(&AppPermissions {
+                routes: true,
+                buyer: false,
+                seller: true,
+                config: true,
+            })
+        );
+        assert_eq!(loaded.invalid_files, vec![dir_path.join("app2")]);
+    }
+
+    #[test]
+    fn test_diff_trusted_apps() {
+        let pk = |i: u8| PublicKey::from(&[i; PublicKey::len()]);
+
+        let mut old_trusted = HashMap::new();
+        old_trusted.insert(pk(0), permissions(true));
+        old_trusted.insert(pk(1), permissions(true));
+        old_trusted.insert(pk(2), permissions(true));
+
+        let mut new_trusted = HashMap::new();
+        new_trusted.insert(pk(1), permissions(true));
+        new_trusted.insert(pk(2), permissions(false));
+        new_trusted.insert(pk(3), permissions(true));
+
+        let mut changes = diff_trusted_apps(&old_trusted, &new_trusted);
+        changes.sort_by_key(|change| match change {
+            TrustedAppChange::Added(public_key, _)
+            | TrustedAppChange::Removed(public_key)
+            | TrustedAppChange::PermissionsChanged(public_key, _) => public_key.clone(),
+        });
+        assert_eq!(
+            changes,
+            vec![
+                TrustedAppChange::Removed(pk(0)),
+                TrustedAppChange::PermissionsChanged(pk(2), permissions(false)),
+                TrustedAppChange::Added(pk(3), permissions(true)),
+            ]
+        );
+    }
+}
@@ -6,6 +6,7 @@ use futures::task::{Spawn, SpawnExt};
 use futures::{FutureExt, SinkExt, Stream, StreamExt, TryFutureExt};
 
 use common::conn::{BoxFuture, ConnPair, ConnPairVec, FuncFutTransform, FutTransform};
+use common::conn_limiter::conn_limiter;
 use common::transform_pool::transform_pool_loop;
 
 use crypto::rand::CryptoRandom;
@@ -136,6 +137,7 @@ where
                 .ok()?;
 
             Some(IncomingAppConnection {
+                app_public_key: public_key,
                 app_permissions: app_permissions.clone(),
                 report_sender,
             })
@@ -217,6 +219,10 @@ where
     TA: TrustedApps + Send + Clone + 'static,
     S: Spawn + Clone + Send + 'static,
 {
+    // Close incoming app connections beyond the allowed amount of open app connections:
+    let incoming_app_raw_conns =
+        conn_limiter(incoming_app_raw_conns, node_config.max_incoming_app_conns);
+
     // TODO: Move this number somewhere else?
     let max_concurrent_incoming_apps = 0x10;
     let (_pool_handle, incoming_apps) = transform_incoming_apps(
@@ -1,7 +1,7 @@
 use std::fmt::Debug;
 use std::fs;
 use std::net::SocketAddr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 use derive_more::From;
@@ -16,7 +16,7 @@ use structopt::StructOpt;
 use common::conn::Listener;
 use common::int_convert::usize_to_u64;
 
-use crypto::identity::SoftwareEd25519Identity;
+use crypto::identity::{Identity, SoftwareEd25519Identity};
 use crypto::rand::system_random;
 
 use identity::{create_identity, IdentityClient};
@@ -26,14 +26,17 @@ use database::file_db::FileDb;
 use database::{database_loop, AtomicDb, DatabaseClient};
 
 use net::{TcpConnector, TcpListener};
+use proto::app_server::messages::NamedRelayAddress;
 use proto::consts::{
-    KEEPALIVE_TICKS, MAX_FRAME_LENGTH, MAX_NODE_RELAYS, MAX_OPERATIONS_IN_BATCH, TICKS_TO_REKEY,
-    TICK_MS,
+    INDEX_MUTATIONS_BATCH_TICKS, KEEPALIVE_TICKS, MAX_APP_QUEUED_MESSAGES, MAX_FRAME_LENGTH,
+    MAX_MOVE_TOKEN_RETRANSMITS, MAX_NODE_RELAYS, MAX_OPERATIONS_IN_BATCH,
+    PENDING_TRANSACTION_TIMEOUT_TICKS, TICKS_TO_REKEY, TICK_MS,
 };
+use proto::crypto::PublicKey;
 use proto::net::messages::NetAddress;
 use proto::ser_string::{deserialize_from_string, StringSerdeError};
 
-use proto::file::IdentityFile;
+use proto::file::{IdentityFile, RelayAddressFile};
 
 use node::{NodeConfig, NodeState};
 
@@ -54,6 +57,8 @@ const MAX_OPEN_INDEX_CLIENT_REQUESTS: usize = 0x8;
 /// The amount of ticks we are willing to wait until a connection is established (Through
 /// the relay)
 const CONN_TIMEOUT_TICKS: usize = 0x8;
+/// Maximum amount of concurrently open incoming app connections.
+const MAX_INCOMING_APP_CONNS: usize = 0x20;
 /*
 /// Maximum amount of concurrent applications
 /// going through the incoming connection transform at the same time
@@ -67,6 +72,9 @@ pub enum NodeBinError {
     CreateThreadPoolError,
     CreateTimerError,
     LoadDbError,
+    CreateDbError,
+    /// The database does not exist, and creating a new database was not requested
+    DbNotFound,
     SpawnError,
     NetNodeError(NetNodeError),
     // SerializeError(SerializeError),
@@ -94,6 +102,75 @@ pub struct StNodeCmd {
     /// Directory path of trusted applications
     #[structopt(parse(from_os_str), short = "t", long = "trusted")]
     pub trusted: PathBuf,
+    /// Relay ticket file path. May be specified multiple times.
+    /// Relays are only added if the database does not exist yet, in which case a new database is
+    /// created.
+    #[structopt(parse(from_os_str), long = "relay")]
+    pub relays: Vec<PathBuf>,
+    /// Create a new database if the database does not exist yet.
+    /// Implied if initial relays are given.
+    #[structopt(long = "init")]
+    pub init: bool,
+    /// Duration of a single time tick, in milliseconds. Defaults to the protocol's tick duration.
+    #[structopt(long = "tick-ms")]
+    pub opt_tick_ms: Option<u64>,
+}
+
+/// Get the duration of a single time tick.
+/// Falls back to the protocol's default tick duration if no duration was specified.
+fn tick_duration(opt_tick_ms: Option<u64>) -> Duration {
+    let tick_ms = opt_tick_ms.unwrap_or_else(|| usize_to_u64(TICK_MS).unwrap());
+    Duration::from_millis(tick_ms)
+}
+
+/// Load relay ticket files. Every relay is named after its file name.
+fn load_relays(relay_paths: &[PathBuf]) -> Result<Vec<NamedRelayAddress>, NodeBinError> {
+    let mut relays = Vec::new();
+    for relay_path in relay_paths {
+        let relay_file: RelayAddressFile =
+            deserialize_from_string(&fs::read_to_string(relay_path)?)
+                .map_err(|e| e.with_path(relay_path))?;
+        let name = relay_path
+            .file_stem()
+            .map(|file_stem| file_stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| relay_file.address.to_string());
+        relays.push(NamedRelayAddress {
+            public_key: relay_file.public_key,
+            address: relay_file.address,
+            name,
+        });
+    }
+    Ok(relays)
+}
+
+/// Load the node database. If the database does not exist, create a new database
+/// containing the given initial relays. A new database is only created if it was requested
+/// (Using `init`, or by giving initial relays), so that a wrong database path does not silently
+/// start a node with an empty state.
+fn load_or_create_db(
+    database: &Path,
+    local_public_key: PublicKey,
+    relay_paths: &[PathBuf],
+    init: bool,
+) -> Result<FileDb<NodeState<NetAddress>>, NodeBinError> {
+    if database.exists() {
+        if !relay_paths.is_empty() {
+            warn!("Database already exists. Ignoring initial relays.");
+        }
+        return FileDb::load(database.to_path_buf()).map_err(|_| NodeBinError::LoadDbError);
+    }
+
+    if !init && relay_paths.is_empty() {
+        error!(
+            "Database {:?} does not exist. Use --init to create a new database.",
+            database
+        );
+        return Err(NodeBinError::DbNotFound);
+    }
+
+    let mut initial_state = NodeState::<NetAddress>::new(local_public_key);
+    initial_state.funder_state.relays = load_relays(relay_paths)?.into_iter().collect();
+    FileDb::create(database.to_path_buf(), initial_state).map_err(|_| NodeBinError::CreateDbError)
 }
 
 pub fn stnode(st_node_cmd: StNodeCmd) -> Result<(), NodeBinError> {
@@ -102,10 +179,14 @@ pub fn stnode(st_node_cmd: StNodeCmd) -> Result<(), NodeBinError> {
         laddr,
         database,
         trusted,
+        relays,
+        init,
+        opt_tick_ms,
     } = st_node_cmd;
 
     // Parse identity file:
-    let identity_file: IdentityFile = deserialize_from_string(&fs::read_to_string(&idfile)?)?;
+    let identity_file: IdentityFile =
+        deserialize_from_string(&fs::read_to_string(&idfile)?).map_err(|e| e.with_path(&idfile))?;
     let identity = SoftwareEd25519Identity::from_private_key(&identity_file.private_key)
         .map_err(|_| NodeBinError::LoadIdentityError)?;
 
@@ -124,9 +205,8 @@ pub fn stnode(st_node_cmd: StNodeCmd) -> Result<(), NodeBinError> {
     let identity_client = IdentityClient::new(sender);
 
     // Get a timer client:
-    let dur = Duration::from_millis(usize_to_u64(TICK_MS).unwrap());
-    let timer_client =
-        create_timer(dur, thread_pool.clone()).map_err(|_| NodeBinError::CreateTimerError)?;
+    let timer_client = create_timer(tick_duration(opt_tick_ms), thread_pool.clone())
+        .map_err(|_| NodeBinError::CreateTimerError)?;
 
     // Fill in node configuration:
     let node_config = NodeConfig {
@@ -150,8 +230,22 @@ pub fn stnode(st_node_cmd: StNodeCmd) -> Result<(), NodeBinError> {
         max_pending_user_requests: MAX_PENDING_USER_REQUESTS,
         /// Maximum amount of concurrent index client requests:
         max_open_index_client_requests: MAX_OPEN_INDEX_CLIENT_REQUESTS,
+        /// The amount of ticks the index client collects mutations before sending them.
+        index_mutations_batch_ticks: INDEX_MUTATIONS_BATCH_TICKS,
         /// Maximum amount of relays a node may use.
         max_node_relays: MAX_NODE_RELAYS,
+        /// The amount of ticks we wait for a response to a pending request before canceling it.
+        pending_transaction_timeout_ticks: PENDING_TRANSACTION_TIMEOUT_TICKS,
+        /// Maximum amount of old move tokens we accept from a friend in a row.
+        max_move_token_retransmits: MAX_MOVE_TOKEN_RETRANSMITS,
+        /// Optional tap over the messages passed between the Channeler and the Funder.
+        opt_message_tracer: None,
+        /// Optional sink for the outcomes of payments made by this node.
+        opt_payment_event_sender: None,
+        /// Maximum amount of concurrently open incoming app connections.
+        max_incoming_app_conns: MAX_INCOMING_APP_CONNS,
+        /// Maximum amount of messages queued for sending to a single app.
+        max_app_queued_messages: MAX_APP_QUEUED_MESSAGES,
         /*
         /// Maximum amount of incoming app connections we set up at the same time
         // max_concurrent_incoming_apps: MAX_CONCURRENT_INCOMING_APPS,
@@ -164,9 +258,8 @@ pub fn stnode(st_node_cmd: StNodeCmd) -> Result<(), NodeBinError> {
     // Obtain secure cryptographic random:
     let rng = system_random();
 
-    // Load database:
-    let atomic_db =
-        FileDb::<NodeState<NetAddress>>::load(database).map_err(|_| NodeBinError::LoadDbError)?;
+    // Load database (Or create a new one, if it does not exist):
+    let atomic_db = load_or_create_db(&database, identity.get_public_key(), &relays, init)?;
 
     // Start listening to apps:
     let app_tcp_listener = TcpListener::new(MAX_FRAME_LENGTH, thread_pool.clone());
@@ -205,3 +298,123 @@ pub fn stnode(st_node_cmd: StNodeCmd) -> Result<(), NodeBinError> {
 
     block_on(node_fut).map_err(NodeBinError::NetNodeError)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryFrom;
+    use std::time::Instant;
+
+    use futures::executor::LocalPool;
+    use futures::StreamExt;
+
+    use tempfile::tempdir;
+
+    use proto::ser_string::serialize_to_string;
+
+    #[test]
+    fn test_load_or_create_db_initial_relays() {
+        let dir = tempdir().unwrap();
+
+        let relay_file = RelayAddressFile {
+            public_key: PublicKey::from(&[0xbb; PublicKey::len()]),
+            address: NetAddress::try_from("127.0.0.1:1337".to_owned()).unwrap(),
+        };
+        let relay_path = dir.path().join("relay0.ticket");
+        fs::write(&relay_path, serialize_to_string(&relay_file).unwrap()).unwrap();
+
+        let local_public_key = PublicKey::from(&[0xaa; PublicKey::len()]);
+        let database = dir.path().join("node.db");
+        let atomic_db = load_or_create_db(
+            &database,
+            local_public_key.clone(),
+            &[relay_path.clone()],
+            false,
+        )
+        .unwrap();
+
+        let expected_relays = vec![NamedRelayAddress {
+            public_key: relay_file.public_key.clone(),
+            address: relay_file.address.clone(),
+            name: "relay0".to_owned(),
+        }];
+        let funder_state = &atomic_db.get_state().funder_state;
+        assert_eq!(funder_state.local_public_key, local_public_key);
+        assert_eq!(
+            funder_state.relays.iter().cloned().collect::<Vec<_>>(),
+            expected_relays
+        );
+        drop(atomic_db);
+
+        // Relays are not added to an existing database:
+        let other_relay_path = dir.path().join("relay1.ticket");
+        fs::write(&other_relay_path, serialize_to_string(&relay_file).unwrap()).unwrap();
+        let atomic_db =
+            load_or_create_db(&database, local_public_key, &[other_relay_path], false).unwrap();
+        assert_eq!(
+            atomic_db
+                .get_state()
+                .funder_state
+                .relays
+                .iter()
+                .cloned()
+                .collect::<Vec<_>>(),
+            expected_relays
+        );
+    }
+
+    #[test]
+    fn test_load_or_create_db_missing() {
+        let dir = tempdir().unwrap();
+        let local_public_key = PublicKey::from(&[0xaa; PublicKey::len()]);
+        let database = dir.path().join("node.db");
+
+        // A missing database is not created implicitly:
+        match load_or_create_db(&database, local_public_key.clone(), &[], false) {
+            Err(NodeBinError::DbNotFound) => {}
+            _ => unreachable!(),
+        }
+        assert!(!database.exists());
+
+        // A new empty database is created on request:
+        let atomic_db = load_or_create_db(&database, local_public_key.clone(), &[], true).unwrap();
+        let funder_state = &atomic_db.get_state().funder_state;
+        assert_eq!(funder_state.local_public_key, local_public_key);
+        assert!(funder_state.relays.is_empty());
+        drop(atomic_db);
+        assert!(database.exists());
+    }
+
+    #[test]
+    fn test_tick_duration_default() {
+        assert_eq!(
+            tick_duration(None),
+            Duration::from_millis(usize_to_u64(TICK_MS).unwrap())
+        );
+        assert_eq!(tick_duration(Some(7)), Duration::from_millis(7));
+    }
+
+    #[test]
+    fn test_node_timer_custom_tick_duration() {
+        const TICKS: u32 = 5;
+
+        let thread_pool = ThreadPool::new().unwrap();
+        let dur = tick_duration(Some(20));
+        let mut timer_client = create_timer(dur, thread_pool).unwrap();
+
+        let mut local_pool = LocalPool::new();
+        let timer_stream = local_pool
+            .run_until(timer_client.request_timer_stream("test_node_timer".to_owned()))
+            .unwrap();
+
+        let start = Instant::now();
+        let ticks = local_pool.run_until(timer_stream.take(TICKS as usize).collect::<Vec<_>>());
+        let elapsed = start.elapsed();
+
+        assert_eq!(ticks.len(), TICKS as usize);
+        // Ticks should arrive at the configured cadence, and not at the default one:
+        assert!(elapsed >= dur * (TICKS - 1));
+        assert!(elapsed < dur * TICKS * 4);
+    }
+}
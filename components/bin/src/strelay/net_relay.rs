@@ -7,9 +7,10 @@ use futures::{FutureExt, Stream, TryFutureExt};
 use derive_more::*;
 
 use common::conn::{BoxFuture, ConnPairVec, FutTransform};
+use common::conn_limiter::conn_limiter;
 use common::transform_pool::transform_pool_loop;
 
-use proto::consts::{KEEPALIVE_TICKS, RELAY_CONN_TIMEOUT_TICKS};
+use proto::consts::{KEEPALIVE_TICKS, RELAY_CONN_TIMEOUT_TICKS, RELAY_IDLE_TIMEOUT_TICKS};
 use proto::crypto::PublicKey;
 
 use crypto::rand::CryptoRandom;
@@ -17,9 +18,9 @@ use crypto::rand::CryptoRandom;
 use identity::IdentityClient;
 use timer::TimerClient;
 
-use connection::create_version_encrypt_keepalive;
+use connection::{create_version_encrypt_keepalive, IdleTimeoutTransform};
 
-use relay::{relay_server, RelayServerError};
+use relay::{relay_server, RelayServerError, RelayStats};
 
 #[derive(Debug, From)]
 pub enum NetRelayServerError {
@@ -29,12 +30,16 @@ pub enum NetRelayServerError {
 
 /// Start a secure channel without knowing the identity of the remote
 /// side ahead of time.
+///
+/// Connections that receive nothing (Not even keepalives) for `RELAY_IDLE_TIMEOUT_TICKS` are
+/// closed.
 #[derive(Clone)]
 struct AnonSecureChannel<R, S> {
     timer_client: TimerClient,
     identity_client: IdentityClient,
     rng: R,
     spawner: S,
+    idle_transform: IdleTimeoutTransform,
 }
 
 impl<R, S> AnonSecureChannel<R, S> {
@@ -44,11 +49,14 @@ impl<R, S> AnonSecureChannel<R, S> {
         rng: R,
         spawner: S,
     ) -> Self {
+        let idle_transform =
+            IdleTimeoutTransform::new(timer_client.clone(), RELAY_IDLE_TIMEOUT_TICKS);
         Self {
             timer_client,
             identity_client,
             rng,
             spawner,
+            idle_transform,
         }
     }
 }
@@ -69,7 +77,12 @@ where
             self.spawner.clone(),
         );
 
+        let mut idle_transform = self.idle_transform.clone();
+
         Box::pin(async move {
+            // The idle timeout is applied below the keepalive layer, so that keepalives from the
+            // remote side count as activity:
+            let conn_pair = idle_transform.transform(conn_pair).await?;
             let (public_key, conn_pair) = conn_transform.transform((None, conn_pair)).await?;
             Some((public_key, conn_pair))
         })
@@ -82,6 +95,7 @@ pub async fn net_relay_server<IRC, R, S>(
     timer_client: TimerClient,
     rng: R,
     max_concurrent_encrypt: usize,
+    max_conns: usize,
     spawner: S,
 ) -> Result<(), NetRelayServerError>
 where
@@ -89,6 +103,9 @@ where
     R: CryptoRandom + Clone + 'static,
     S: Spawn + Clone + Send + Sync + 'static,
 {
+    // Close incoming connections beyond the allowed amount of open connections:
+    let incoming_raw_conns = conn_limiter(incoming_raw_conns, max_conns);
+
     let (enc_conns_sender, incoming_enc_conns) = mpsc::channel::<(PublicKey, ConnPairVec)>(0);
 
     let transform = AnonSecureChannel::new(
@@ -116,6 +133,7 @@ where
         timer_client,
         RELAY_CONN_TIMEOUT_TICKS,
         KEEPALIVE_TICKS,
+        RelayStats::new(),
         spawner.clone(),
     )
     .await?;
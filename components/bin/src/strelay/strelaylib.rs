@@ -32,6 +32,10 @@ use proto::ser_string::{deserialize_from_string, StringSerdeError};
 /// We set this number to avoid DoS from half finished encrypted channel negotiations.
 pub const MAX_CONCURRENT_ENCRYPT: usize = 0x200;
 
+/// Default maximum amount of concurrently open incoming connections.
+/// Connections beyond this amount are closed immediately.
+pub const MAX_CONNS: usize = 0x1000;
+
 #[allow(clippy::enum_variant_names)]
 #[derive(Debug, From)]
 pub enum RelayServerBinError {
@@ -55,13 +59,21 @@ pub struct StRelayCmd {
     /// Listening address (Example: 0.0.0.0:1337)
     #[structopt(short = "l", long = "laddr")]
     pub laddr: SocketAddr,
+    /// Maximum amount of concurrently open incoming connections
+    #[structopt(long = "max-conns")]
+    pub opt_max_conns: Option<usize>,
 }
 
 pub fn strelay(st_relay_cmd: StRelayCmd) -> Result<(), RelayServerBinError> {
-    let StRelayCmd { idfile, laddr } = st_relay_cmd;
+    let StRelayCmd {
+        idfile,
+        laddr,
+        opt_max_conns,
+    } = st_relay_cmd;
 
     // Parse identity file:
-    let identity_file: IdentityFile = deserialize_from_string(&fs::read_to_string(&idfile)?)?;
+    let identity_file: IdentityFile =
+        deserialize_from_string(&fs::read_to_string(&idfile)?).map_err(|e| e.with_path(&idfile))?;
     let identity = SoftwareEd25519Identity::from_private_key(&identity_file.private_key)
         .map_err(|_| RelayServerBinError::LoadIdentityError)?;
 
@@ -90,6 +102,7 @@ pub fn strelay(st_relay_cmd: StRelayCmd) -> Result<(), RelayServerBinError> {
         timer_client,
         rng,
         MAX_CONCURRENT_ENCRYPT,
+        opt_max_conns.unwrap_or(MAX_CONNS),
         thread_pool,
     );
 
@@ -0,0 +1,170 @@
+use core::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use futures::task::{Context, Poll};
+use futures::{future, Sink, Stream, StreamExt};
+
+use crate::conn::ConnPair;
+
+/// Releases a connection slot when dropped.
+struct ConnGuard {
+    num_conns: Arc<AtomicUsize>,
+}
+
+impl Drop for ConnGuard {
+    fn drop(&mut self) {
+        self.num_conns.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// A sender or a receiver of a connection, holding the connection's slot.
+struct Tracked<T> {
+    inner: T,
+    _guard: Arc<ConnGuard>,
+}
+
+impl<T> Stream for Tracked<T>
+where
+    T: Stream + Unpin,
+{
+    type Item = T::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, context: &mut Context) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(context)
+    }
+}
+
+impl<T, I> Sink<I> for Tracked<T>
+where
+    T: Sink<I> + Unpin,
+{
+    type Error = T::Error;
+
+    fn poll_ready(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.inner).poll_ready(context)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: I) -> Result<(), Self::Error> {
+        Pin::new(&mut self.inner).start_send(item)
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.inner).poll_flush(context)
+    }
+
+    fn poll_close(
+        mut self: Pin<&mut Self>,
+        context: &mut Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.inner).poll_close(context)
+    }
+}
+
+/// Limit the amount of concurrently open incoming connections to `max_conns`.
+/// A connection is considered open until both its sender and its receiver are dropped.
+/// Incoming connections beyond the limit are closed immediately.
+pub fn conn_limiter<IC, SendItem, RecvItem>(
+    incoming_conns: IC,
+    max_conns: usize,
+) -> impl Stream<Item = ConnPair<SendItem, RecvItem>> + Unpin
+where
+    IC: Stream<Item = ConnPair<SendItem, RecvItem>> + Unpin,
+    SendItem: 'static,
+    RecvItem: 'static,
+{
+    let num_conns = Arc::new(AtomicUsize::new(0));
+    incoming_conns.filter_map(move |conn_pair| {
+        if num_conns.load(Ordering::SeqCst) >= max_conns {
+            warn!(
+                "conn_limiter(): Too many open connections ({}). Closing incoming connection.",
+                max_conns
+            );
+            // Dropping the connection closes it:
+            return future::ready(None);
+        }
+
+        num_conns.fetch_add(1, Ordering::SeqCst);
+        let guard = Arc::new(ConnGuard {
+            num_conns: num_conns.clone(),
+        });
+
+        let (sender, receiver) = conn_pair.split();
+        future::ready(Some(ConnPair::from_box(
+            Box::pin(Tracked {
+                inner: sender,
+                _guard: guard.clone(),
+            }),
+            Box::pin(Tracked {
+                inner: receiver,
+                _guard: guard,
+            }),
+        )))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::channel::mpsc;
+    use futures::executor::block_on;
+    use futures::future::Either;
+    use futures::SinkExt;
+
+    type ClientConn = (mpsc::Sender<u32>, mpsc::Receiver<u32>);
+
+    /// Create a connection. Returns the client side and the server side of the connection.
+    fn create_conn() -> (ClientConn, ConnPair<u32, u32>) {
+        let (client_sender, server_receiver) = mpsc::channel(1);
+        let (server_sender, client_receiver) = mpsc::channel(1);
+        (
+            (client_sender, client_receiver),
+            ConnPair::from_raw(server_sender, server_receiver),
+        )
+    }
+
+    #[test]
+    fn test_conn_limiter() {
+        block_on(async move {
+            let (mut conns_sender, incoming_conns) = mpsc::channel(0);
+            let mut limited_conns = conn_limiter(incoming_conns, 2);
+
+            let (_client_conn1, server_conn1) = create_conn();
+            conns_sender.send(server_conn1).await.unwrap();
+            let accepted_conn1 = limited_conns.next().await.unwrap();
+
+            let (mut client_conn2, server_conn2) = create_conn();
+            conns_sender.send(server_conn2).await.unwrap();
+            let mut accepted_conn2 = limited_conns.next().await.unwrap();
+
+            // Accepted connections work normally:
+            client_conn2.0.send(5).await.unwrap();
+            assert_eq!(accepted_conn2.receiver.next().await.unwrap(), 5);
+            accepted_conn2.sender.send(6).await.unwrap();
+            assert_eq!(client_conn2.1.next().await.unwrap(), 6);
+
+            // The limit is reached. The third connection is closed immediately:
+            let (mut client_conn3, server_conn3) = create_conn();
+            conns_sender.send(server_conn3).await.unwrap();
+            match future::select(limited_conns.next(), client_conn3.1.next()).await {
+                Either::Left(_) => unreachable!(),
+                Either::Right((opt_item, _)) => assert!(opt_item.is_none()),
+            };
+
+            // Closing one connection frees a slot for a new connection:
+            drop(accepted_conn1);
+            let (mut client_conn4, server_conn4) = create_conn();
+            conns_sender.send(server_conn4).await.unwrap();
+            let mut accepted_conn4 = limited_conns.next().await.unwrap();
+            accepted_conn4.sender.send(7).await.unwrap();
+            assert_eq!(client_conn4.1.next().await.unwrap(), 7);
+        });
+    }
+}
@@ -28,6 +28,7 @@ pub mod async_test_utils;
 pub mod caller_info;
 // pub mod canonical_serialize;
 pub mod conn;
+pub mod conn_limiter;
 pub mod dummy_connector;
 pub mod dummy_listener;
 pub mod futures_compat;
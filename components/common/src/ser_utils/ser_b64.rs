@@ -7,24 +7,68 @@ use serde::de::{Error, Visitor};
 use serde::ser::Serializer;
 use serde::Deserializer;
 
-use base64::{self, URL_SAFE_NO_PAD};
+use base64::{self, Config, URL_SAFE_NO_PAD};
 
 pub fn serialize<T, S>(item: &T, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
     T: AsRef<[u8]>,
 {
-    let base64_str = base64::encode_config(&item.as_ref(), URL_SAFE_NO_PAD);
-    serializer.serialize_str(&base64_str)
+    serialize_config(item, serializer, URL_SAFE_NO_PAD)
 }
 
 pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: for<'t> TryFrom<&'t [u8]>,
+{
+    deserialize_config(deserializer, URL_SAFE_NO_PAD)
+}
+
+/// Standard base64 (With padding), for interoperability with external tools.
+/// Usage: `#[serde(with = "ser_b64::standard")]`
+pub mod standard {
+    use std::convert::TryFrom;
+
+    use serde::ser::Serializer;
+    use serde::Deserializer;
+
+    use base64::STANDARD;
+
+    pub fn serialize<T, S>(item: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: AsRef<[u8]>,
+    {
+        super::serialize_config(item, serializer, STANDARD)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: for<'t> TryFrom<&'t [u8]>,
+    {
+        super::deserialize_config(deserializer, STANDARD)
+    }
+}
+
+fn serialize_config<T, S>(item: &T, serializer: S, config: Config) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    T: AsRef<[u8]>,
+{
+    let base64_str = base64::encode_config(&item.as_ref(), config);
+    serializer.serialize_str(&base64_str)
+}
+
+fn deserialize_config<'de, T, D>(deserializer: D, config: Config) -> Result<T, D::Error>
 where
     D: Deserializer<'de>,
     T: for<'t> TryFrom<&'t [u8]>,
 {
     struct ItemVisitor<T> {
         item: PhantomData<T>,
+        config: Config,
     }
 
     impl<'de, T> Visitor<'de> for ItemVisitor<T>
@@ -41,12 +85,15 @@ where
         where
             E: Error,
         {
-            let vec = base64::decode_config(&str_item, URL_SAFE_NO_PAD)
+            let vec = base64::decode_config(&str_item, self.config)
                 .map_err(|err| Error::custom(err.to_string()))?;
             T::try_from(&vec).map_err(|_| Error::custom("Length mismatch"))
         }
     }
 
-    let visitor = ItemVisitor { item: PhantomData };
+    let visitor = ItemVisitor {
+        item: PhantomData,
+        config,
+    };
     deserializer.deserialize_str(visitor)
 }
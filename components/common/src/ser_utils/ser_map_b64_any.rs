@@ -8,13 +8,70 @@ use serde::de::{Deserialize, Error, MapAccess, Visitor};
 use serde::ser::{Serialize, SerializeMap, Serializer};
 use serde::Deserializer;
 
-use base64::{self, URL_SAFE_NO_PAD};
+use base64::{self, Config, URL_SAFE_NO_PAD};
 
 // A util for serializing HashMaps with keys that are not strings.
 // For example: JSON serialization does not allow keys that are not strings.
 // SerHashMap first converts the key to a base64 string, and only then serializes.
 
 pub fn serialize<S, K, V, M>(input_map: M, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    K: Serialize + AsRef<[u8]> + Eq + Hash,
+    V: Serialize,
+    M: IntoIterator<Item = (K, V)>,
+{
+    serialize_config(input_map, serializer, URL_SAFE_NO_PAD)
+}
+
+pub fn deserialize<'de, D, K, V, M>(deserializer: D) -> Result<M, D::Error>
+where
+    D: Deserializer<'de>,
+    K: Deserialize<'de> + for<'t> TryFrom<&'t [u8]> + Eq + Hash,
+    V: Deserialize<'de>,
+    M: Default + Extend<(K, V)>,
+{
+    deserialize_config(deserializer, URL_SAFE_NO_PAD)
+}
+
+/// Standard base64 (With padding) keys, for interoperability with external tools.
+/// Usage: `#[serde(with = "ser_map_b64_any::standard")]`
+pub mod standard {
+    use std::convert::TryFrom;
+    use std::hash::Hash;
+
+    use serde::de::Deserialize;
+    use serde::ser::{Serialize, Serializer};
+    use serde::Deserializer;
+
+    use base64::STANDARD;
+
+    pub fn serialize<S, K, V, M>(input_map: M, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        K: Serialize + AsRef<[u8]> + Eq + Hash,
+        V: Serialize,
+        M: IntoIterator<Item = (K, V)>,
+    {
+        super::serialize_config(input_map, serializer, STANDARD)
+    }
+
+    pub fn deserialize<'de, D, K, V, M>(deserializer: D) -> Result<M, D::Error>
+    where
+        D: Deserializer<'de>,
+        K: Deserialize<'de> + for<'t> TryFrom<&'t [u8]> + Eq + Hash,
+        V: Deserialize<'de>,
+        M: Default + Extend<(K, V)>,
+    {
+        super::deserialize_config(deserializer, STANDARD)
+    }
+}
+
+fn serialize_config<S, K, V, M>(
+    input_map: M,
+    serializer: S,
+    config: Config,
+) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
     K: Serialize + AsRef<[u8]> + Eq + Hash,
@@ -25,13 +82,13 @@ where
     let pairs: Vec<_> = input_map.into_iter().collect();
     let mut map = serializer.serialize_map(Some(pairs.len()))?;
     for (k, v) in pairs.into_iter() {
-        let string_k = base64::encode_config(k.as_ref(), URL_SAFE_NO_PAD);
+        let string_k = base64::encode_config(k.as_ref(), config);
         map.serialize_entry(&string_k, &v)?;
     }
     map.end()
 }
 
-pub fn deserialize<'de, D, K, V, M>(deserializer: D) -> Result<M, D::Error>
+fn deserialize_config<'de, D, K, V, M>(deserializer: D, config: Config) -> Result<M, D::Error>
 where
     D: Deserializer<'de>,
     K: Deserialize<'de> + for<'t> TryFrom<&'t [u8]> + Eq + Hash,
@@ -42,6 +99,7 @@ where
         key: PhantomData<K>,
         value: PhantomData<V>,
         map: PhantomData<M>,
+        config: Config,
     }
 
     impl<'de, K, V, M> Visitor<'de> for MapVisitor<K, V, M>
@@ -62,7 +120,7 @@ where
         {
             let mut res_map = M::default();
             while let Some((k_string, v)) = map.next_entry::<String, V>()? {
-                let vec = base64::decode_config(&k_string, URL_SAFE_NO_PAD)
+                let vec = base64::decode_config(&k_string, self.config)
                     .map_err(|err| Error::custom(err.to_string()))?;
                 let k = K::try_from(&vec).map_err(|_| Error::custom("Length mismatch"))?;
 
@@ -76,6 +134,7 @@ where
         key: PhantomData,
         value: PhantomData,
         map: PhantomData,
+        config,
     };
     deserializer.deserialize_map(visitor)
 }
@@ -7,29 +7,78 @@ use serde::de::{Deserialize, Error, Visitor};
 use serde::ser::{Serialize, Serializer};
 use serde::Deserializer;
 
-use base64::{self, URL_SAFE_NO_PAD};
+use base64::{self, Config, URL_SAFE_NO_PAD};
 
 pub fn serialize<T, S>(opt_item: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    T: Serialize + AsRef<[u8]>,
+{
+    serialize_config(opt_item, serializer, URL_SAFE_NO_PAD)
+}
+
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + for<'t> TryFrom<&'t [u8]>,
+{
+    deserialize_config(deserializer, URL_SAFE_NO_PAD)
+}
+
+/// Standard base64 (With padding), for interoperability with external tools.
+/// Usage: `#[serde(with = "ser_option_b64::standard")]`
+pub mod standard {
+    use std::convert::TryFrom;
+
+    use serde::de::Deserialize;
+    use serde::ser::{Serialize, Serializer};
+    use serde::Deserializer;
+
+    use base64::STANDARD;
+
+    pub fn serialize<T, S>(opt_item: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Serialize + AsRef<[u8]>,
+    {
+        super::serialize_config(opt_item, serializer, STANDARD)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Deserialize<'de> + for<'t> TryFrom<&'t [u8]>,
+    {
+        super::deserialize_config(deserializer, STANDARD)
+    }
+}
+
+fn serialize_config<T, S>(
+    opt_item: &Option<T>,
+    serializer: S,
+    config: Config,
+) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
     T: Serialize + AsRef<[u8]>,
 {
     match opt_item {
         Some(item) => {
-            let string_item = base64::encode_config(item.as_ref(), URL_SAFE_NO_PAD);
+            let string_item = base64::encode_config(item.as_ref(), config);
             serializer.serialize_some(&string_item)
         }
         None => serializer.serialize_none(),
     }
 }
 
-pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+fn deserialize_config<'de, T, D>(deserializer: D, config: Config) -> Result<Option<T>, D::Error>
 where
     D: Deserializer<'de>,
     T: Deserialize<'de> + for<'t> TryFrom<&'t [u8]>,
 {
     struct ItemVisitor<T> {
         item: PhantomData<T>,
+        config: Config,
     }
 
     impl<'de, T> Visitor<'de> for ItemVisitor<T>
@@ -55,6 +104,7 @@ where
         {
             struct B64Visitor<T> {
                 item: PhantomData<T>,
+                config: Config,
             }
 
             impl<'de, T> Visitor<'de> for B64Visitor<T>
@@ -71,17 +121,23 @@ where
                 where
                     E: Error,
                 {
-                    let vec = base64::decode_config(&str_item, URL_SAFE_NO_PAD)
+                    let vec = base64::decode_config(&str_item, self.config)
                         .map_err(|err| Error::custom(err.to_string()))?;
                     T::try_from(&vec).map_err(|_| Error::custom("Length mismatch"))
                 }
             }
 
-            let b64_visitor = B64Visitor { item: PhantomData };
+            let b64_visitor = B64Visitor {
+                item: PhantomData,
+                config: self.config,
+            };
             Ok(Some(deserializer.deserialize_string(b64_visitor)?))
         }
     }
 
-    let visitor = ItemVisitor { item: PhantomData };
+    let visitor = ItemVisitor {
+        item: PhantomData,
+        config,
+    };
     deserializer.deserialize_option(visitor)
 }
@@ -67,3 +67,14 @@ struct MySeqStrStruct {
     #[serde(with = "ser_seq_str")]
     my_hash_set: HashSet<String>,
 }
+
+#[allow(unused)]
+#[derive(Serialize, Deserialize)]
+struct MyStandardB64Struct {
+    #[serde(with = "ser_b64::standard")]
+    my_array: [u8; 32],
+    #[serde(with = "ser_option_b64::standard")]
+    my_opt: Option<[u8; 16]>,
+    #[serde(with = "ser_map_b64_any::standard")]
+    my_map: HashMap<[u8; 32], String>,
+}
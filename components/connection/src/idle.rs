@@ -0,0 +1,163 @@
+use futures::{future, stream, Stream, StreamExt};
+
+use common::conn::{BoxFuture, ConnPairVec, FutTransform};
+use timer::{TimerClient, TimerTick};
+
+enum IdleEvent {
+    Message(Vec<u8>),
+    ReceiverClosed,
+    TimerTick,
+}
+
+/// Close the receiver of a connection if nothing was received from the remote side for
+/// `idle_timeout_ticks` ticks.
+fn idle_timeout<TS>(
+    conn_pair_vec: ConnPairVec,
+    timer_stream: TS,
+    idle_timeout_ticks: usize,
+) -> ConnPairVec
+where
+    TS: Stream<Item = TimerTick> + Unpin + Send + 'static,
+{
+    let (sender, receiver) = conn_pair_vec.split();
+
+    let receiver = receiver
+        .map(IdleEvent::Message)
+        .chain(stream::once(future::ready(IdleEvent::ReceiverClosed)));
+    let timer_stream = timer_stream.map(|_| IdleEvent::TimerTick);
+
+    let mut idle_ticks = 0usize;
+    let receiver = stream::select(receiver, timer_stream)
+        .take_while(move |idle_event| {
+            let is_open = match idle_event {
+                IdleEvent::Message(_) => {
+                    idle_ticks = 0;
+                    true
+                }
+                IdleEvent::ReceiverClosed => false,
+                IdleEvent::TimerTick => {
+                    idle_ticks = idle_ticks.saturating_add(1);
+                    if idle_ticks >= idle_timeout_ticks {
+                        warn!("idle_timeout(): Closing idle connection");
+                        false
+                    } else {
+                        true
+                    }
+                }
+            };
+            future::ready(is_open)
+        })
+        .filter_map(|idle_event| {
+            future::ready(match idle_event {
+                IdleEvent::Message(msg) => Some(msg),
+                IdleEvent::ReceiverClosed | IdleEvent::TimerTick => None,
+            })
+        });
+
+    ConnPairVec::from_raw(sender, receiver)
+}
+
+/// Close connections that receive nothing from the remote side for `idle_timeout_ticks` ticks.
+///
+/// Should be applied below the keepalive layer, so that keepalives sent by the remote side count
+/// as activity. Only received messages are counted: Our own keepalives are sent regardless of the
+/// state of the remote side.
+#[derive(Debug, Clone)]
+pub struct IdleTimeoutTransform {
+    timer_client: TimerClient,
+    idle_timeout_ticks: usize,
+}
+
+impl IdleTimeoutTransform {
+    pub fn new(timer_client: TimerClient, idle_timeout_ticks: usize) -> Self {
+        Self {
+            timer_client,
+            idle_timeout_ticks,
+        }
+    }
+}
+
+impl FutTransform for IdleTimeoutTransform {
+    type Input = ConnPairVec;
+    type Output = Option<ConnPairVec>;
+
+    fn transform(&mut self, conn_pair_vec: Self::Input) -> BoxFuture<'_, Self::Output> {
+        Box::pin(async move {
+            let timer_stream = match self
+                .timer_client
+                .request_timer_stream("IdleTimeoutTransform::transform".to_owned())
+                .await
+            {
+                Ok(timer_stream) => timer_stream,
+                Err(e) => {
+                    error!(
+                        "IdleTimeoutTransform: request_timer_stream() error: {:?}",
+                        e
+                    );
+                    return None;
+                }
+            };
+            Some(idle_timeout(
+                conn_pair_vec,
+                timer_stream,
+                self.idle_timeout_ticks,
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::channel::mpsc;
+    use futures::SinkExt;
+
+    use common::test_executor::TestExecutor;
+    use timer::create_timer_incoming;
+
+    async fn task_idle_timeout_transform(test_executor: TestExecutor) {
+        // Create a mock time service:
+        let (mut tick_sender, tick_receiver) = mpsc::channel::<()>(0);
+        let timer_client = create_timer_incoming(tick_receiver, test_executor.clone()).unwrap();
+
+        let (local_sender, mut remote_receiver) = mpsc::channel::<Vec<u8>>(0);
+        let (mut remote_sender, local_receiver) = mpsc::channel::<Vec<u8>>(0);
+
+        let idle_timeout_ticks = 4;
+        let mut idle_transform = IdleTimeoutTransform::new(timer_client, idle_timeout_ticks);
+        let mut conn_pair = idle_transform
+            .transform(ConnPairVec::from_raw(local_sender, local_receiver))
+            .await
+            .unwrap();
+
+        // The connection is not closed before the idle period is over:
+        for _ in 0..idle_timeout_ticks - 1 {
+            tick_sender.send(()).await.unwrap();
+            test_executor.wait().await;
+        }
+        remote_sender.send(vec![1, 2, 3]).await.unwrap();
+        assert_eq!(conn_pair.receiver.next().await.unwrap(), vec![1, 2, 3]);
+
+        for _ in 0..idle_timeout_ticks - 1 {
+            tick_sender.send(()).await.unwrap();
+            test_executor.wait().await;
+        }
+
+        // Outgoing messages do not count as activity:
+        conn_pair.sender.send(vec![4, 5, 6]).await.unwrap();
+        assert_eq!(remote_receiver.next().await.unwrap(), vec![4, 5, 6]);
+
+        // Nothing was received, and the connection is closed after the idle period:
+        tick_sender.send(()).await.unwrap();
+        test_executor.wait().await;
+        assert!(conn_pair.receiver.next().await.is_none());
+    }
+
+    #[test]
+    fn test_idle_timeout_transform() {
+        let test_executor = TestExecutor::new();
+        let res = test_executor.run(task_idle_timeout_transform(test_executor.clone()));
+        assert!(res.is_output());
+    }
+}
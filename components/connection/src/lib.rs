@@ -10,9 +10,14 @@
 #[macro_use]
 extern crate log;
 
+mod idle;
+mod record_replay;
 mod timeout;
 mod transforms;
 
+pub use self::idle::IdleTimeoutTransform;
+pub use self::record_replay::{RecordReplayMode, RecordReplayTransform};
 pub use self::transforms::{
     create_encrypt_keepalive, create_secure_connector, create_version_encrypt_keepalive,
+    CONN_TIMEOUT_TICKS,
 };
@@ -0,0 +1,289 @@
+use std::convert::TryFrom;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use futures::channel::mpsc;
+use futures::task::{Spawn, SpawnExt};
+use futures::{future, Sink, SinkExt, Stream, StreamExt};
+
+use common::conn::{BoxFuture, ConnPairVec, FutTransform};
+use common::int_convert::{u32_to_usize, usize_to_u32};
+
+/// Direction of a recorded frame, as seen by the user of the connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    /// Sent by the user
+    Outgoing,
+    /// Received by the user
+    Incoming,
+}
+
+impl Direction {
+    fn to_byte(self) -> u8 {
+        match self {
+            Direction::Outgoing => 0,
+            Direction::Incoming => 1,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Direction::Outgoing),
+            1 => Some(Direction::Incoming),
+            _ => None,
+        }
+    }
+}
+
+/// Append a single frame to a log.
+/// Record format: direction (1 byte) || frame length (u32, big endian) || frame
+fn write_record(log: &Mutex<File>, direction: Direction, frame: &[u8]) -> io::Result<()> {
+    let frame_len = usize_to_u32(frame.len())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Frame too long"))?;
+
+    let mut record = vec![direction.to_byte()];
+    record.extend_from_slice(&frame_len.to_be_bytes());
+    record.extend_from_slice(frame);
+    log.lock().unwrap().write_all(&record)
+}
+
+/// Parse a log created by `write_record`.
+/// Returns None if the log is malformed.
+fn parse_records(mut data: &[u8]) -> Option<Vec<(Direction, Vec<u8>)>> {
+    let mut records = Vec::new();
+    while !data.is_empty() {
+        if data.len() < 5 {
+            return None;
+        }
+        let direction = Direction::from_byte(data[0])?;
+        let frame_len = u32_to_usize(u32::from_be_bytes(<[u8; 4]>::try_from(&data[1..5]).ok()?))?;
+        data = &data[5..];
+        if data.len() < frame_len {
+            return None;
+        }
+        records.push((direction, data[..frame_len].to_vec()));
+        data = &data[frame_len..];
+    }
+    Some(records)
+}
+
+#[derive(Debug, Clone)]
+pub enum RecordReplayMode {
+    /// Pass all frames through, logging them into the given file
+    Record(PathBuf),
+    /// Ignore the given connection, and play the other side of the connection from the given log
+    Replay(PathBuf),
+}
+
+/// A transform for reproducing a connection deterministically, for debugging.
+///
+/// In record mode, all the frames sent and received through a connection are logged into a
+/// file. In replay mode, the user side of the connection is driven from such a log: Recorded
+/// incoming frames are sent to the user in the recorded order, and every recorded outgoing frame
+/// is expected to be sent by the user before replay proceeds. The connection is closed when the
+/// log ends, or if the user sends a frame different from the recorded one.
+///
+/// Every transformed connection overwrites the log file, so a `RecordReplayTransform` should be
+/// used for a single connection.
+#[derive(Clone)]
+pub struct RecordReplayTransform<S> {
+    mode: RecordReplayMode,
+    spawner: S,
+}
+
+impl<S> RecordReplayTransform<S>
+where
+    S: Spawn,
+{
+    pub fn new(mode: RecordReplayMode, spawner: S) -> Self {
+        RecordReplayTransform { mode, spawner }
+    }
+
+    fn spawn_record(&mut self, log_path: &Path, conn_pair: ConnPairVec) -> ConnPairVec {
+        let (user_sender, from_user) = mpsc::channel::<Vec<u8>>(0);
+        let (to_user, user_receiver) = mpsc::channel::<Vec<u8>>(0);
+
+        let log = match File::create(log_path) {
+            Ok(file) => Arc::new(Mutex::new(file)),
+            Err(e) => {
+                error!("RecordReplayTransform: Failed to create log file: {:?}", e);
+                // Close the connection:
+                return ConnPairVec::from_raw(user_sender, user_receiver);
+            }
+        };
+
+        let (sender, receiver) = conn_pair.split();
+
+        let outgoing_fut = forward_record(from_user, sender, Direction::Outgoing, log.clone());
+        if let Err(e) = self.spawner.spawn(outgoing_fut) {
+            error!("RecordReplayTransform: spawn() failed: {:?}", e);
+        }
+
+        let incoming_fut = forward_record(receiver, to_user, Direction::Incoming, log);
+        if let Err(e) = self.spawner.spawn(incoming_fut) {
+            error!("RecordReplayTransform: spawn() failed: {:?}", e);
+        }
+
+        ConnPairVec::from_raw(user_sender, user_receiver)
+    }
+
+    fn spawn_replay(&mut self, log_path: &Path) -> ConnPairVec {
+        let (user_sender, from_user) = mpsc::channel::<Vec<u8>>(0);
+        let (to_user, user_receiver) = mpsc::channel::<Vec<u8>>(0);
+
+        let opt_records = match fs::read(log_path) {
+            Ok(data) => parse_records(&data),
+            Err(e) => {
+                error!("RecordReplayTransform: Failed to read log file: {:?}", e);
+                None
+            }
+        };
+        let records = match opt_records {
+            Some(records) => records,
+            None => {
+                error!("RecordReplayTransform: Invalid log file");
+                // Close the connection:
+                return ConnPairVec::from_raw(user_sender, user_receiver);
+            }
+        };
+
+        if let Err(e) = self.spawner.spawn(replay(records, from_user, to_user)) {
+            error!("RecordReplayTransform: spawn() failed: {:?}", e);
+        }
+
+        ConnPairVec::from_raw(user_sender, user_receiver)
+    }
+}
+
+/// Forward frames from `source` to `dest`, recording every frame into `log` on the way.
+async fn forward_record<St, Si>(
+    mut source: St,
+    mut dest: Si,
+    direction: Direction,
+    log: Arc<Mutex<File>>,
+) where
+    St: Stream<Item = Vec<u8>> + Unpin,
+    Si: Sink<Vec<u8>> + Unpin,
+{
+    while let Some(frame) = source.next().await {
+        if let Err(e) = write_record(&log, direction, &frame) {
+            warn!("RecordReplayTransform: Failed to record frame: {:?}", e);
+        }
+        if dest.send(frame).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Play the remote side of a connection from a list of recorded frames.
+async fn replay(
+    records: Vec<(Direction, Vec<u8>)>,
+    mut from_user: mpsc::Receiver<Vec<u8>>,
+    mut to_user: mpsc::Sender<Vec<u8>>,
+) {
+    for (index, (direction, frame)) in records.into_iter().enumerate() {
+        match direction {
+            Direction::Incoming => {
+                if to_user.send(frame).await.is_err() {
+                    return;
+                }
+            }
+            Direction::Outgoing => match from_user.next().await {
+                Some(user_frame) if user_frame == frame => {}
+                Some(_) => {
+                    warn!(
+                        "RecordReplayTransform: Frame {} differs from the recorded frame",
+                        index
+                    );
+                    return;
+                }
+                None => return,
+            },
+        }
+    }
+}
+
+impl<S> FutTransform for RecordReplayTransform<S>
+where
+    S: Spawn + Send,
+{
+    type Input = ConnPairVec;
+    type Output = ConnPairVec;
+
+    fn transform(&mut self, input: Self::Input) -> BoxFuture<'_, Self::Output> {
+        let conn_pair = match self.mode.clone() {
+            RecordReplayMode::Record(log_path) => self.spawn_record(&log_path, input),
+            // The given connection is not used. It is closed when dropped:
+            RecordReplayMode::Replay(log_path) => self.spawn_replay(&log_path),
+        };
+        Box::pin(future::ready(conn_pair))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::executor::{block_on, ThreadPool};
+
+    use tempfile::tempdir;
+
+    async fn task_record_replay<S>(log_path: PathBuf, spawner: S)
+    where
+        S: Spawn + Clone + Send,
+    {
+        // Record an exchange with a mock remote side:
+        let (local_sender, mut remote_receiver) = mpsc::channel::<Vec<u8>>(0);
+        let (mut remote_sender, local_receiver) = mpsc::channel::<Vec<u8>>(0);
+        let remote_conn_pair = ConnPairVec::from_raw(local_sender, local_receiver);
+
+        let mut record_transform =
+            RecordReplayTransform::new(RecordReplayMode::Record(log_path.clone()), spawner.clone());
+        let (mut sender, mut receiver) = record_transform.transform(remote_conn_pair).await.split();
+
+        sender.send(vec![1, 2, 3]).await.unwrap();
+        assert_eq!(remote_receiver.next().await.unwrap(), vec![1, 2, 3]);
+        remote_sender.send(vec![4, 5]).await.unwrap();
+        assert_eq!(receiver.next().await.unwrap(), vec![4, 5]);
+        sender.send(vec![6]).await.unwrap();
+        assert_eq!(remote_receiver.next().await.unwrap(), vec![6]);
+
+        // Replay the exchange, without a remote side:
+        let (dummy_sender, dummy_receiver) = mpsc::channel::<Vec<u8>>(0);
+        let dummy_conn_pair = ConnPairVec::from_raw(dummy_sender, dummy_receiver);
+
+        let mut replay_transform =
+            RecordReplayTransform::new(RecordReplayMode::Replay(log_path), spawner);
+        let (mut sender, mut receiver) = replay_transform.transform(dummy_conn_pair).await.split();
+
+        sender.send(vec![1, 2, 3]).await.unwrap();
+        assert_eq!(receiver.next().await.unwrap(), vec![4, 5]);
+        sender.send(vec![6]).await.unwrap();
+
+        // The log ended, so the connection is closed:
+        assert!(receiver.next().await.is_none());
+    }
+
+    #[test]
+    fn test_record_replay() {
+        let dir = tempdir().unwrap();
+        let log_path = dir.path().join("conn.log");
+        let thread_pool = ThreadPool::new().unwrap();
+        block_on(task_record_replay(log_path, thread_pool));
+    }
+
+    #[test]
+    fn test_parse_records_malformed() {
+        assert_eq!(parse_records(&[]), Some(Vec::new()));
+        // Invalid direction:
+        assert_eq!(parse_records(&[2, 0, 0, 0, 0]), None);
+        // Truncated frame:
+        assert_eq!(parse_records(&[0, 0, 0, 0, 2, 7]), None);
+        assert_eq!(
+            parse_records(&[1, 0, 0, 0, 1, 7]),
+            Some(vec![(Direction::Incoming, vec![7])])
+        );
+    }
+}
@@ -1,17 +1,43 @@
-use ring::digest::{digest, SHA512_256};
+use ring::digest::{digest, Context, Digest, SHA512_256};
 
 use proto::crypto::HashResult;
 
 /// Calculate SHA512/256 over the given data.
 pub fn sha_512_256(data: &[u8]) -> HashResult {
-    let mut inner = [0x00; HashResult::len()];
+    digest_to_hash_result(digest(&SHA512_256, data))
+}
 
-    let digest_res = digest(&SHA512_256, data);
+fn digest_to_hash_result(digest_res: Digest) -> HashResult {
+    let mut inner = [0x00; HashResult::len()];
     inner.copy_from_slice(digest_res.as_ref());
-
     HashResult::from(&inner)
 }
 
+/// Calculate SHA512/256 incrementally, over data given in parts.
+/// Allows hashing data without first collecting it into one buffer.
+#[derive(Clone)]
+pub struct HashContext {
+    context: Context,
+}
+
+impl HashContext {
+    pub fn new() -> Self {
+        HashContext {
+            context: Context::new(&SHA512_256),
+        }
+    }
+
+    /// Add more data to be hashed
+    pub fn update(&mut self, data: &[u8]) {
+        self.context.update(data);
+    }
+
+    /// Calculate SHA512/256 over all the data given so far.
+    pub fn finalize(self) -> HashResult {
+        digest_to_hash_result(self.context.finish())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -30,4 +56,20 @@ mod tests {
 
         assert_eq!(hash_res.as_ref(), expected);
     }
+
+    #[test]
+    fn hash_context_matches_one_shot() {
+        let data = b"This is a test! This is only a test!";
+
+        // Split the data in every possible point:
+        for split in 0..=data.len() {
+            let mut hash_context = HashContext::new();
+            hash_context.update(&data[..split]);
+            hash_context.update(&data[split..]);
+            assert_eq!(hash_context.finalize(), sha_512_256(&data[..]));
+        }
+
+        // No data at all:
+        assert_eq!(HashContext::new().finalize(), sha_512_256(&[]));
+    }
 }
@@ -5,6 +5,11 @@ use proto::crypto::{HashedLock, PlainLock};
 pub trait HashLock {
     /// Lock the plain hash
     fn hash_lock(&self) -> HashedLock;
+
+    /// Check if the plain hash opens the given hashed lock
+    fn verify(&self, hashed_lock: &HashedLock) -> bool {
+        &self.hash_lock() == hashed_lock
+    }
 }
 
 impl HashLock for PlainLock {
@@ -31,4 +36,14 @@ mod tests {
         let hashed_lock2 = plain_lock.hash_lock();
         assert_eq!(hashed_lock1, hashed_lock2);
     }
+
+    #[test]
+    fn test_hash_lock_verify() {
+        let plain_lock = PlainLock::from(&[1u8; PlainLock::len()]);
+        let hashed_lock = plain_lock.hash_lock();
+        assert!(plain_lock.verify(&hashed_lock));
+
+        let other_plain_lock = PlainLock::from(&[2u8; PlainLock::len()]);
+        assert!(!other_plain_lock.verify(&hashed_lock));
+    }
 }
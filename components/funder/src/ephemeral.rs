@@ -1,19 +1,35 @@
+use super::idle_ticks::{IdleTicks, IdleTicksMutation};
+use super::invoice_age::{InvoiceAgeMutation, InvoiceAges};
 use super::liveness::{Liveness, LivenessMutation};
+use super::pending_age::{PendingAgeMutation, PendingAges};
+use super::retransmits::{Retransmits, RetransmitsMutation};
 
 #[derive(Clone, Default)]
 pub struct Ephemeral {
     pub liveness: Liveness,
+    pub pending_ages: PendingAges,
+    pub invoice_ages: InvoiceAges,
+    pub idle_ticks: IdleTicks,
+    pub retransmits: Retransmits,
 }
 
 #[derive(Debug)]
 pub enum EphemeralMutation {
     LivenessMutation(LivenessMutation),
+    PendingAgeMutation(PendingAgeMutation),
+    InvoiceAgeMutation(InvoiceAgeMutation),
+    IdleTicksMutation(IdleTicksMutation),
+    RetransmitsMutation(RetransmitsMutation),
 }
 
 impl Ephemeral {
     pub fn new() -> Ephemeral {
         Ephemeral {
             liveness: Liveness::new(),
+            pending_ages: PendingAges::new(),
+            invoice_ages: InvoiceAges::new(),
+            idle_ticks: IdleTicks::new(),
+            retransmits: Retransmits::new(),
         }
     }
 
@@ -22,6 +38,18 @@ impl Ephemeral {
             EphemeralMutation::LivenessMutation(liveness_mutation) => {
                 self.liveness.mutate(liveness_mutation)
             }
+            EphemeralMutation::PendingAgeMutation(pending_age_mutation) => {
+                self.pending_ages.mutate(pending_age_mutation)
+            }
+            EphemeralMutation::InvoiceAgeMutation(invoice_age_mutation) => {
+                self.invoice_ages.mutate(invoice_age_mutation)
+            }
+            EphemeralMutation::IdleTicksMutation(idle_ticks_mutation) => {
+                self.idle_ticks.mutate(idle_ticks_mutation)
+            }
+            EphemeralMutation::RetransmitsMutation(retransmits_mutation) => {
+                self.retransmits.mutate(retransmits_mutation)
+            }
         }
     }
 }
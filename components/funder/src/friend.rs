@@ -7,10 +7,10 @@ use common::ser_utils::{ser_b64, ser_map_str_any, ser_string};
 use signature::canonical::CanonicalSerialize;
 
 use proto::app_server::messages::{NamedRelayAddress, RelayAddress};
-use proto::crypto::PublicKey;
+use proto::crypto::{PublicKey, Uid};
 use proto::funder::messages::{
-    CancelSendFundsOp, CollectSendFundsOp, Currency, FriendStatus, Rate, RequestSendFundsOp,
-    ResetTerms, ResponseSendFundsOp,
+    CancelSendFundsOp, CollectSendFundsOp, Currency, FriendStatus, InconsistencyReason, Rate,
+    RequestSendFundsOp, ResetTerms, ResponseSendFundsOp,
 };
 
 use crate::token_channel::{TcMutation, TokenChannel};
@@ -68,6 +68,8 @@ pub struct ChannelInconsistent {
     pub opt_last_incoming_move_token: Option<MoveTokenHashed>,
     pub local_reset_terms: ResetTerms,
     pub opt_remote_reset_terms: Option<ResetTerms>,
+    /// The reason the channel became inconsistent
+    pub reason: InconsistencyReason,
 }
 
 #[derive(Arbitrary, PartialEq, Eq, Clone, Serialize, Deserialize, Debug)]
@@ -139,7 +141,9 @@ pub struct FriendState<B: Clone> {
     pub sent_local_relays: SentLocalRelays<B>,
     /// Locally maintained name of the remote friend node.
     pub name: String,
-    /// Local configurations for currencies relationship with this friend
+    /// Local configurations for currencies relationship with this friend.
+    /// Only currencies configured here are ever activated, regardless of the currencies
+    /// proposed by the remote friend.
     #[serde(with = "ser_map_str_any")]
     pub currency_configs: ImHashMap<Currency, CurrencyConfig>,
     /// Friend status. If disabled, we don't attempt to connect to this friend. (Friend will think
@@ -163,6 +167,7 @@ pub enum FriendMutation<B: Clone> {
     PopFrontPendingBackwardsOp,
     PushBackPendingUserRequest((Currency, RequestSendFundsOp)),
     PopFrontPendingUserRequest,
+    RemovePendingUserRequest(Uid), // request_id
     RemovePendingRequestsCurrency(Currency),
     RemovePendingUserRequestsCurrency(Currency),
     RemovePendingRequests,
@@ -329,6 +334,16 @@ where
                     unreachable!();
                 }
             }
+            FriendMutation::RemovePendingUserRequest(request_id) => {
+                // Remove a user request that was not yet sent to the friend.
+                if let ChannelStatus::Consistent(channel_consistent) = &mut self.channel_status {
+                    channel_consistent
+                        .pending_user_requests
+                        .retain(|(_, request)| &request.request_id != request_id);
+                } else {
+                    unreachable!();
+                }
+            }
             FriendMutation::RemovePendingRequestsCurrency(currency) => {
                 // Remove all pending outgoing messages for a certain currency.
                 if let ChannelStatus::Consistent(channel_consistent) = &mut self.channel_status {
@@ -3,7 +3,7 @@ use std::hash::Hash;
 
 use futures::channel::mpsc;
 use futures::stream::select;
-use futures::{future, stream, SinkExt, StreamExt};
+use futures::{future, stream, SinkExt, Stream, StreamExt};
 
 use signature::canonical::CanonicalSerialize;
 
@@ -16,6 +16,7 @@ use proto::funder::messages::{FunderIncomingControl, FunderOutgoingControl};
 
 use crate::ephemeral::Ephemeral;
 use crate::handler::funder_handle_message;
+use crate::payment_events::{payment_event, PaymentEvent};
 use crate::state::{FunderMutation, FunderState};
 use crate::types::{FunderIncoming, FunderIncomingComm, FunderOutgoingComm};
 
@@ -23,6 +24,7 @@ use crate::types::{FunderIncoming, FunderIncomingComm, FunderOutgoingComm};
 pub enum FunderError {
     IncomingControlClosed,
     IncomingCommClosed,
+    IncomingTimerClosed,
     IncomingMessagesError,
     DbError,
     SendControlError,
@@ -34,13 +36,15 @@ pub enum FunderEvent<B> {
     FunderIncoming(FunderIncoming<B>),
     IncomingControlClosed,
     IncomingCommClosed,
+    IncomingTimerClosed,
 }
 
-pub async fn inner_funder_loop<B, R>(
+pub async fn inner_funder_loop<B, R, TS>(
     mut identity_client: IdentityClient,
     rng: R,
     incoming_control: mpsc::Receiver<FunderIncomingControl<B>>,
     incoming_comm: mpsc::Receiver<FunderIncomingComm<B>>,
+    incoming_timer: TS,
     control_sender: mpsc::Sender<FunderOutgoingControl<B>>,
     comm_sender: mpsc::Sender<FunderOutgoingComm<B>>,
     mut funder_state: FunderState<B>,
@@ -48,11 +52,15 @@ pub async fn inner_funder_loop<B, R>(
     max_operations_in_batch: usize,
     max_node_relays: usize,
     max_pending_user_requests: usize,
+    pending_transaction_timeout_ticks: usize,
+    max_move_token_retransmits: usize,
+    mut opt_payment_event_sender: Option<mpsc::Sender<PaymentEvent>>,
     mut opt_event_sender: Option<mpsc::Sender<FunderEvent<B>>>,
 ) -> Result<(), FunderError>
 where
     B: Clone + PartialEq + Eq + CanonicalSerialize + Debug + Hash,
     R: CryptoRandom + 'static,
+    TS: Stream + Unpin,
 {
     // Transform error type:
     let mut comm_sender = comm_sender.sink_map_err(|_| ());
@@ -74,17 +82,26 @@ where
             FunderEvent::FunderIncoming(FunderIncoming::Comm(incoming_comm_msg))
         })
         .chain(stream::once(future::ready(FunderEvent::IncomingCommClosed)));
+    let incoming_timer = incoming_timer
+        .map(|_| FunderEvent::FunderIncoming(FunderIncoming::TimerTick))
+        .chain(stream::once(future::ready(
+            FunderEvent::IncomingTimerClosed,
+        )));
     // Chain the Init message first:
     let mut incoming_messages = stream::once(future::ready(FunderEvent::FunderIncoming(
         FunderIncoming::Init,
     )))
-    .chain(select(incoming_control, incoming_comm));
+    .chain(select(
+        incoming_control,
+        select(incoming_comm, incoming_timer),
+    ));
 
     while let Some(funder_event) = incoming_messages.next().await {
         // Read one message from incoming messages:
         let funder_incoming = match funder_event.clone() {
             FunderEvent::IncomingControlClosed => return Err(FunderError::IncomingControlClosed),
             FunderEvent::IncomingCommClosed => return Err(FunderError::IncomingCommClosed),
+            FunderEvent::IncomingTimerClosed => return Err(FunderError::IncomingTimerClosed),
             FunderEvent::FunderIncoming(funder_incoming) => funder_incoming,
         };
 
@@ -96,6 +113,8 @@ where
             max_node_relays,
             max_operations_in_batch,
             max_pending_user_requests,
+            pending_transaction_timeout_ticks,
+            max_move_token_retransmits,
             funder_incoming,
         )
         .await;
@@ -109,9 +128,13 @@ where
             }
         };
 
+        let mut payment_events = Vec::new();
         if !handler_output.funder_mutations.is_empty() {
             // Mutate our funder_state in memory:
             for mutation in &handler_output.funder_mutations {
+                if opt_payment_event_sender.is_some() {
+                    payment_events.extend(payment_event(&funder_state, mutation));
+                }
                 funder_state.mutate(mutation);
             }
             // If there are any mutations, send them to the database:
@@ -140,6 +163,27 @@ where
             .await
             .map_err(|_| FunderError::SendControlError)?;
 
+        // Send payment events, only after the related mutations were saved to the database.
+        // We never wait for the receiver of the payment events. A receiver that lags behind is
+        // disconnected, so that it can not block the funder:
+        if let Some(mut payment_event_sender) = opt_payment_event_sender.take() {
+            let mut is_connected = true;
+            for payment_event in payment_events {
+                if let Err(e) = payment_event_sender.try_send(payment_event) {
+                    if e.is_full() {
+                        warn!("inner_funder_loop(): Too many queued payment events. Disconnecting");
+                    } else {
+                        warn!("inner_funder_loop(): Payment events receiver was closed");
+                    }
+                    is_connected = false;
+                    break;
+                }
+            }
+            if is_connected {
+                opt_payment_event_sender = Some(payment_event_sender);
+            }
+        }
+
         if let Some(ref mut event_sender) = opt_event_sender {
             event_sender.send(funder_event).await.unwrap();
         }
@@ -148,28 +192,41 @@ where
     Ok(())
 }
 
-pub async fn funder_loop<B, R>(
+/// Run the Funder.
+/// Every item received from `incoming_timer` is considered to be one time tick.
+///
+/// If `opt_payment_event_sender` is provided, a `PaymentEvent` is sent through it whenever a
+/// payment (for which this node is the buyer) succeeds or is canceled.
+/// The funder never waits for the receiver: If the channel is full, the sender is dropped and no
+/// more events are sent.
+pub async fn funder_loop<B, R, TS>(
     identity_client: IdentityClient,
     rng: R,
     incoming_control: mpsc::Receiver<FunderIncomingControl<B>>,
     incoming_comm: mpsc::Receiver<FunderIncomingComm<B>>,
+    incoming_timer: TS,
     control_sender: mpsc::Sender<FunderOutgoingControl<B>>,
     comm_sender: mpsc::Sender<FunderOutgoingComm<B>>,
     max_operations_in_batch: usize,
     max_node_relays: usize,
     max_pending_user_requests: usize,
+    pending_transaction_timeout_ticks: usize,
+    max_move_token_retransmits: usize,
     funder_state: FunderState<B>,
     db_client: DatabaseClient<FunderMutation<B>>,
+    opt_payment_event_sender: Option<mpsc::Sender<PaymentEvent>>,
 ) -> Result<(), FunderError>
 where
     B: Clone + PartialEq + Eq + CanonicalSerialize + Debug + Hash,
     R: CryptoRandom + 'static,
+    TS: Stream + Unpin,
 {
     inner_funder_loop(
         identity_client,
         rng,
         incoming_control,
         incoming_comm,
+        incoming_timer,
         control_sender,
         comm_sender,
         funder_state,
@@ -177,6 +234,9 @@ where
         max_operations_in_batch,
         max_node_relays,
         max_pending_user_requests,
+        pending_transaction_timeout_ticks,
+        max_move_token_retransmits,
+        opt_payment_event_sender,
         None,
     )
     .await
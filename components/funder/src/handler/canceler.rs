@@ -2,12 +2,12 @@ use std::fmt::Debug;
 
 use signature::canonical::CanonicalSerialize;
 
-use crypto::rand::{CryptoRandom, RandGen};
+use crypto::rand::CryptoRandom;
 
-use proto::crypto::{PublicKey, Uid};
+use proto::crypto::{InvoiceId, PublicKey, Uid};
 use proto::funder::messages::{
-    Currency, FunderOutgoingControl, PaymentStatus, PaymentStatusSuccess, RequestResult,
-    RequestSendFundsOp, ResponseClosePayment, TransactionResult,
+    CancelReason, Currency, FunderOutgoingControl, RequestResult, RequestSendFundsOp,
+    ResponseClosePayment, TransactionResult,
 };
 
 use crate::handler::state_wrap::MutableFunderState;
@@ -15,7 +15,7 @@ use crate::handler::types::SendCommands;
 use crate::handler::utils::find_request_origin;
 
 use crate::friend::{BackwardsOp, ChannelStatus, FriendMutation};
-use crate::state::{FunderMutation, Payment, PaymentStage};
+use crate::state::{FunderMutation, OpenInvoice};
 use crate::types::{create_cancel_send_funds, create_pending_transaction};
 
 #[derive(Debug)]
@@ -33,10 +33,11 @@ pub fn reply_with_cancel<B>(
     remote_public_key: &PublicKey,
     currency: &Currency,
     request_id: &Uid,
+    reason: CancelReason,
 ) where
     B: Clone + CanonicalSerialize + PartialEq + Eq + Debug,
 {
-    let cancel_send_funds = create_cancel_send_funds(request_id.clone());
+    let cancel_send_funds = create_cancel_send_funds(request_id.clone(), reason);
     let friend_mutation = FriendMutation::PushBackPendingBackwardsOp((
         currency.clone(),
         BackwardsOp::Cancel(cancel_send_funds),
@@ -47,6 +48,40 @@ pub fn reply_with_cancel<B>(
     send_commands.set_try_send(remote_public_key);
 }
 
+/// Cancel all pending transactions related to an open invoice, and remove the invoice.
+pub fn cancel_invoice<B>(
+    m_state: &mut MutableFunderState<B>,
+    send_commands: &mut SendCommands,
+    invoice_id: &InvoiceId,
+    open_invoice: &OpenInvoice,
+    reason: CancelReason,
+) where
+    B: Clone + CanonicalSerialize + PartialEq + Eq + Debug,
+{
+    // Cancel all pending transactions related to this invoice
+    for request_id in &open_invoice.incoming_transactions {
+        // Explaining the unwrap() below:
+        // We expect that the origin of this request must be from an existing friend.
+        // We can not be the originator of this request.
+        let friend_public_key =
+            find_request_origin(m_state.state(), &open_invoice.currency, &request_id)
+                .unwrap()
+                .clone();
+        reply_with_cancel(
+            m_state,
+            send_commands,
+            &friend_public_key,
+            &open_invoice.currency,
+            &request_id,
+            reason.clone(),
+        );
+    }
+
+    // Remove invoice:
+    let funder_mutation = FunderMutation::RemoveInvoice(invoice_id.clone());
+    m_state.mutate(funder_mutation);
+}
+
 /// Remove a local transaction (Where this node is the buyer side)
 pub fn remove_transaction<B, R>(
     m_state: &mut MutableFunderState<B>,
@@ -73,71 +108,22 @@ pub fn remove_transaction<B, R>(
         .unwrap()
         .clone();
 
-    // Remove transaction:
-    let funder_mutation = FunderMutation::RemoveTransaction(request_id.clone());
-    m_state.mutate(funder_mutation);
-
-    let Payment {
-        src_plain_lock,
-        stage,
-    } = payment;
-
     // Update payment:
     // - Decrease num_transactions
     // - Possibly remove payment
-    let (opt_new_stage, opt_payment_status) = match stage {
-        PaymentStage::NewTransactions(new_transactions) => {
-            let mut new_new_transactions = new_transactions.clone();
-            new_new_transactions.num_transactions =
-                new_transactions.num_transactions.checked_sub(1).unwrap();
-            (
-                Some(PaymentStage::NewTransactions(new_new_transactions)),
-                None,
-            )
-        }
-        PaymentStage::InProgress(num_transactions) => {
-            assert!(num_transactions > 0);
-            let new_num_transactions = num_transactions.checked_sub(1).unwrap();
-            if new_num_transactions > 0 {
-                (Some(PaymentStage::InProgress(new_num_transactions)), None)
-            } else {
-                let ack_uid = Uid::rand_gen(rng);
-                (
-                    Some(PaymentStage::Canceled(ack_uid.clone())),
-                    Some(PaymentStatus::Canceled(ack_uid)),
-                )
-            }
-        }
-        PaymentStage::Success(num_transactions, receipt, ack_uid) => {
-            let new_num_transactions = num_transactions.checked_sub(1).unwrap();
-            (
-                Some(PaymentStage::Success(
-                    new_num_transactions,
-                    receipt.clone(),
-                    ack_uid.clone(),
-                )),
-                Some(PaymentStatus::Success(PaymentStatusSuccess {
-                    receipt,
-                    ack_uid,
-                })),
-            )
-        }
-        PaymentStage::Canceled(_) => {
-            unreachable!();
-        }
-        PaymentStage::AfterSuccessAck(num_transactions) => {
-            let new_num_transactions = num_transactions.checked_sub(1).unwrap();
-            (
-                if new_num_transactions > 0 {
-                    Some(PaymentStage::AfterSuccessAck(new_num_transactions))
-                } else {
-                    None
-                },
-                None,
-            )
+    // This is checked before any mutation is applied, so that a failure leaves the state intact:
+    let (opt_new_payment, opt_payment_status) = match payment.decrement_transactions(rng) {
+        Ok(output) => output,
+        Err(e) => {
+            error!("remove_transaction(): {:?}", e);
+            return;
         }
     };
 
+    // Remove transaction:
+    let funder_mutation = FunderMutation::RemoveTransaction(request_id.clone());
+    m_state.mutate(funder_mutation);
+
     // Possibly send back a ResponseClosePayment:
     if let Some(payment_status) = opt_payment_status {
         let response_close_payment = ResponseClosePayment {
@@ -149,11 +135,7 @@ pub fn remove_transaction<B, R>(
         ));
     }
 
-    let funder_mutation = if let Some(new_stage) = opt_new_stage {
-        let new_payment = Payment {
-            src_plain_lock,
-            stage: new_stage,
-        };
+    let funder_mutation = if let Some(new_payment) = opt_new_payment {
         FunderMutation::UpdatePayment((open_transaction.payment_id, new_payment))
     } else {
         FunderMutation::RemovePayment(open_transaction.payment_id)
@@ -170,6 +152,7 @@ pub fn cancel_local_pending_transactions<B, R>(
     outgoing_control: &mut Vec<FunderOutgoingControl<B>>,
     rng: &R,
     friend_public_key: &PublicKey,
+    reason: CancelReason,
 ) where
     B: Clone + CanonicalSerialize + PartialEq + Eq + Debug,
     R: CryptoRandom,
@@ -188,48 +171,66 @@ pub fn cancel_local_pending_transactions<B, R>(
         let pending_local_transactions = mutual_credit.state().pending_transactions.local.clone();
 
         // Prepare a list of all remote requests that we need to cancel:
-        for (local_request_id, pending_local_transaction) in pending_local_transactions {
-            let opt_origin_public_key =
-                find_request_origin(m_state.state(), &currency, &local_request_id).cloned();
-            match opt_origin_public_key {
-                Some(origin_public_key) => {
-                    // We have found the friend that is the origin of this request.
-                    // We send him a cancel message.
-                    let cancel_send_funds =
-                        create_cancel_send_funds(pending_local_transaction.request_id);
-                    let friend_mutation = FriendMutation::PushBackPendingBackwardsOp((
-                        currency.clone(),
-                        BackwardsOp::Cancel(cancel_send_funds),
-                    ));
-                    let funder_mutation = FunderMutation::FriendMutation((
-                        origin_public_key.clone(),
-                        friend_mutation,
-                    ));
-                    m_state.mutate(funder_mutation);
-                    // TODO: Should we add currency as argument to set_try_send()?
-                    send_commands.set_try_send(&origin_public_key);
-                }
-                None => {
-                    // We are the origin of this request.
-                    // We send a cancel message through the control:
-                    let transaction_result = TransactionResult {
-                        request_id: pending_local_transaction.request_id.clone(),
-                        result: RequestResult::Failure,
-                    };
-                    outgoing_control
-                        .push(FunderOutgoingControl::TransactionResult(transaction_result));
-                    remove_transaction(
-                        m_state,
-                        outgoing_control,
-                        rng,
-                        &pending_local_transaction.request_id,
-                    );
-                }
-            };
+        for local_request_id in pending_local_transactions.keys() {
+            cancel_local_pending_transaction(
+                m_state,
+                send_commands,
+                outgoing_control,
+                rng,
+                &currency,
+                local_request_id,
+                reason.clone(),
+            );
         }
     }
 }
 
+/// Cancel the origin of a single outgoing local request, without waiting for the remote side to
+/// answer.
+/// If the request was forwarded from another friend, a Cancel message is queued to that friend.
+/// Otherwise, we are the origin of the request, and a failure is reported through the control.
+pub fn cancel_local_pending_transaction<B, R>(
+    m_state: &mut MutableFunderState<B>,
+    send_commands: &mut SendCommands,
+    outgoing_control: &mut Vec<FunderOutgoingControl<B>>,
+    rng: &R,
+    currency: &Currency,
+    local_request_id: &Uid,
+    reason: CancelReason,
+) where
+    B: Clone + CanonicalSerialize + PartialEq + Eq + Debug,
+    R: CryptoRandom,
+{
+    let opt_origin_public_key =
+        find_request_origin(m_state.state(), currency, local_request_id).cloned();
+    match opt_origin_public_key {
+        Some(origin_public_key) => {
+            // We have found the friend that is the origin of this request.
+            // We send him a cancel message.
+            let cancel_send_funds = create_cancel_send_funds(local_request_id.clone(), reason);
+            let friend_mutation = FriendMutation::PushBackPendingBackwardsOp((
+                currency.clone(),
+                BackwardsOp::Cancel(cancel_send_funds),
+            ));
+            let funder_mutation =
+                FunderMutation::FriendMutation((origin_public_key.clone(), friend_mutation));
+            m_state.mutate(funder_mutation);
+            // TODO: Should we add currency as argument to set_try_send()?
+            send_commands.set_try_send(&origin_public_key);
+        }
+        None => {
+            // We are the origin of this request.
+            // We send a cancel message through the control:
+            let transaction_result = TransactionResult {
+                request_id: local_request_id.clone(),
+                result: RequestResult::Failure(Some(reason)),
+            };
+            outgoing_control.push(FunderOutgoingControl::TransactionResult(transaction_result));
+            remove_transaction(m_state, outgoing_control, rng, local_request_id);
+        }
+    };
+}
+
 /// Cancel a pending request
 pub fn cancel_request<B, R>(
     m_state: &mut MutableFunderState<B>,
@@ -238,6 +239,7 @@ pub fn cancel_request<B, R>(
     rng: &R,
     currency: &Currency,
     pending_request: &RequestSendFundsOp,
+    reason: CancelReason,
 ) where
     B: Clone + CanonicalSerialize + PartialEq + Eq + Debug,
     R: CryptoRandom,
@@ -247,7 +249,8 @@ pub fn cancel_request<B, R>(
     match opt_origin_public_key {
         Some(origin_public_key) => {
             let pending_local_transaction = create_pending_transaction(&pending_request);
-            let cancel_send_funds = create_cancel_send_funds(pending_local_transaction.request_id);
+            let cancel_send_funds =
+                create_cancel_send_funds(pending_local_transaction.request_id, reason);
             let friend_mutation = FriendMutation::PushBackPendingBackwardsOp((
                 currency.clone(),
                 BackwardsOp::Cancel(cancel_send_funds),
@@ -261,7 +264,7 @@ pub fn cancel_request<B, R>(
             // We are the origin of this request:
             let transaction_result = TransactionResult {
                 request_id: pending_request.request_id.clone(),
-                result: RequestResult::Failure,
+                result: RequestResult::Failure(Some(reason)),
             };
             outgoing_control.push(FunderOutgoingControl::TransactionResult(transaction_result));
             remove_transaction(m_state, outgoing_control, rng, &pending_request.request_id);
@@ -300,6 +303,7 @@ pub fn cancel_pending_requests<B, R>(
     rng: &R,
     friend_public_key: &PublicKey,
     currency_choice: &CurrencyChoice,
+    reason: CancelReason,
 ) where
     B: Clone + CanonicalSerialize + PartialEq + Eq + Debug,
     R: CryptoRandom,
@@ -327,6 +331,7 @@ pub fn cancel_pending_requests<B, R>(
             rng,
             &currency,
             &pending_request,
+            reason.clone(),
         );
     }
 
@@ -346,6 +351,7 @@ pub fn cancel_pending_requests<B, R>(
             rng,
             &currency,
             &pending_user_request,
+            reason.clone(),
         );
     }
 
@@ -380,6 +386,7 @@ pub fn cancel_nonuser_pending_requests<B, R>(
     rng: &R,
     friend_public_key: &PublicKey,
     currency_choice: &CurrencyChoice,
+    reason: CancelReason,
 ) where
     B: Clone + CanonicalSerialize + PartialEq + Eq + Debug,
     R: CryptoRandom,
@@ -405,6 +412,7 @@ pub fn cancel_nonuser_pending_requests<B, R>(
             rng,
             &currency,
             &pending_request,
+            reason.clone(),
         );
     }
 
@@ -418,3 +426,48 @@ pub fn cancel_nonuser_pending_requests<B, R>(
         FunderMutation::FriendMutation((friend_public_key.clone(), friend_mutation));
     m_state.mutate(funder_mutation);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crypto::test_utils::DummyRandom;
+
+    use proto::crypto::{PaymentId, PlainLock};
+
+    use crate::state::{FunderState, Payment, PaymentStage};
+
+    #[test]
+    fn test_remove_transaction_invalid_payment() {
+        let local_public_key = PublicKey::from(&[0xaa; PublicKey::len()]);
+        let mut state = FunderState::<u32>::new(local_public_key, Vec::new());
+
+        // A canceled payment has no open transactions left, so its transaction count can not be
+        // decremented:
+        let payment_id = PaymentId::from(&[1; PaymentId::len()]);
+        let payment = Payment {
+            src_plain_lock: PlainLock::from(&[2; PlainLock::len()]),
+            stage: PaymentStage::Canceled(Uid::from(&[3; Uid::len()])),
+        };
+        state.mutate(&FunderMutation::UpdatePayment((
+            payment_id.clone(),
+            payment,
+        )));
+
+        let request_id = Uid::from(&[4; Uid::len()]);
+        state.mutate(&FunderMutation::AddTransaction((
+            request_id.clone(),
+            payment_id.clone(),
+        )));
+
+        let mut m_state = MutableFunderState::new(state);
+        let mut outgoing_control = Vec::new();
+        let rng = DummyRandom::new(&[1u8]);
+        remove_transaction(&mut m_state, &mut outgoing_control, &rng, &request_id);
+
+        // Nothing was changed:
+        let (_initial_state, mutations, _final_state) = m_state.done();
+        assert!(mutations.is_empty());
+        assert!(outgoing_control.is_empty());
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::fmt::Debug;
 
 use signature::canonical::CanonicalSerialize;
@@ -5,6 +6,7 @@ use signature::canonical::CanonicalSerialize;
 use crypto::hash_lock::HashLock;
 use crypto::rand::{CryptoRandom, RandGen};
 
+use proto::consts::MAX_FRIEND_RELAYS;
 use proto::crypto::{InvoiceId, PaymentId, PlainLock, PublicKey, Uid};
 
 use crate::friend::{BackwardsOp, ChannelStatus, CurrencyConfig, FriendMutation};
@@ -12,24 +14,29 @@ use crate::state::{FunderMutation, NewTransactions, Payment, PaymentStage};
 
 use proto::app_server::messages::{NamedRelayAddress, RelayAddress};
 use proto::funder::messages::{
-    AckClosePayment, AddFriend, AddInvoice, ChannelerUpdateFriend, CollectSendFundsOp, Commit,
-    CreatePayment, CreateTransaction, FriendStatus, FunderControl, FunderOutgoingControl,
-    PaymentStatus, PaymentStatusSuccess, RemoveFriend, RemoveFriendCurrency, RequestResult,
-    RequestSendFundsOp, ResetFriendChannel, ResponseClosePayment, SetFriendCurrencyMaxDebt,
-    SetFriendCurrencyRate, SetFriendCurrencyRequestsStatus, SetFriendName, SetFriendRelays,
-    SetFriendStatus, TransactionResult,
+    AckClosePayment, AddFriend, AddInvoice, CancelReason, ChannelerUpdateFriend,
+    CollectSendFundsOp, Commit, CommitInvoiceResult, CreatePayment, CreateTransaction,
+    FriendRelaysResult, FriendStatus, FunderControl, FunderOutgoingControl, PaymentStatus,
+    PaymentStatusSuccess, RemoveFriend, RemoveFriendCurrency, RequestResult, RequestSendFundsOp,
+    ResetFriendChannel, ResponseCancelPayment, ResponseClosePayment, ResponseCommitInvoice,
+    ResponseFriendRelays, SetFriendCurrencyMaxDebt, SetFriendCurrencyRate,
+    SetFriendCurrencyRequestsStatus, SetFriendName, SetFriendRelays, SetFriendStatus, SetRelayName,
+    TransactionResult,
 };
 use signature::verify::verify_commit;
 
 use crate::ephemeral::Ephemeral;
 use crate::handler::canceler::{
-    cancel_local_pending_transactions, cancel_nonuser_pending_requests, cancel_pending_requests,
-    reply_with_cancel, CurrencyChoice,
+    cancel_invoice, cancel_local_pending_transactions, cancel_nonuser_pending_requests,
+    cancel_pending_requests, remove_transaction, CurrencyChoice,
 };
 use crate::handler::prepare::prepare_commit;
 use crate::handler::state_wrap::{MutableEphemeral, MutableFunderState};
 use crate::handler::types::SendCommands;
-use crate::handler::utils::{find_local_pending_transaction, find_request_origin, is_friend_ready};
+use crate::handler::utils::{
+    find_local_pending_transaction, find_remote_pending_transaction, find_request_origin,
+    is_friend_ready,
+};
 
 use crate::types::ChannelerConfig;
 
@@ -45,6 +52,8 @@ pub enum HandleControlError {
     PendingUserRequestsFull,
     FriendNotReady,
     MaxNodeRelaysReached,
+    RelayDoesNotExist,
+    MaxFriendRelaysExceeded,
     PaymentAlreadyOpen,
     OpenPaymentNotFound,
     NewTransactionsNotAllowed,
@@ -54,6 +63,9 @@ pub enum HandleControlError {
     InvoiceAlreadyExists,
     InvoiceDoesNotExist,
     InvalidCommit,
+    /// The incoming transactions of the invoice do not cover its total_dest_payment.
+    /// Contains the missing amount.
+    InvoiceNotFullyPaid(u128),
     FriendCurrencyDoesNotExist,
     CanNotRemoveActiveCurrency,
     CurrencyNotConfigured,
@@ -179,6 +191,7 @@ fn disable_friend<B, R>(
         rng,
         friend_public_key,
         &CurrencyChoice::All,
+        CancelReason::ClosedRequests,
     );
 
     // Notify Channeler:
@@ -186,6 +199,40 @@ fn disable_friend<B, R>(
     outgoing_channeler_config.push(channeler_config);
 }
 
+/// Keep the connection to the friend, but stop sending new requests.
+/// Requests that were already sent to the friend may still complete.
+fn pause_friend<B, R>(
+    m_state: &mut MutableFunderState<B>,
+    send_commands: &mut SendCommands,
+    outgoing_control: &mut Vec<FunderOutgoingControl<B>>,
+    outgoing_channeler_config: &mut Vec<ChannelerConfig<RelayAddress<B>>>,
+    rng: &R,
+    friend_public_key: &PublicKey,
+    friend_relays: &[RelayAddress<B>],
+) where
+    B: Clone + PartialEq + Eq + CanonicalSerialize + Debug,
+    R: CryptoRandom,
+{
+    // Cancel requests that were not yet sent to this friend:
+    cancel_pending_requests(
+        m_state,
+        send_commands,
+        outgoing_control,
+        rng,
+        friend_public_key,
+        &CurrencyChoice::All,
+        CancelReason::ClosedRequests,
+    );
+
+    // Make sure we stay connected (The friend might have been disabled before):
+    enable_friend(
+        m_state,
+        outgoing_channeler_config,
+        friend_public_key,
+        friend_relays,
+    );
+}
+
 fn control_add_relay<B>(
     m_state: &mut MutableFunderState<B>,
     send_commands: &mut SendCommands,
@@ -256,10 +303,63 @@ fn control_remove_relay<B>(
     }
 }
 
-fn control_add_friend<B>(m_state: &mut MutableFunderState<B>, add_friend: AddFriend<B>)
+fn control_set_relay_name<B>(
+    m_state: &mut MutableFunderState<B>,
+    set_relay_name: SetRelayName,
+) -> Result<(), HandleControlError>
+where
+    B: Clone + PartialEq + Eq + CanonicalSerialize + Debug,
+{
+    // Make sure that the relay exists:
+    let named_relay_address = m_state
+        .state()
+        .relays
+        .iter()
+        .find(|named_relay_address| {
+            named_relay_address.public_key == set_relay_name.relay_public_key
+        })
+        .ok_or(HandleControlError::RelayDoesNotExist)?;
+
+    // If the newly proposed name is the same as the old one, we do nothing:
+    if named_relay_address.name == set_relay_name.name {
+        return Ok(());
+    }
+
+    // The relay address does not change, therefore there is no need to notify the Channeler or
+    // our friends. Adding a relay with the same public key replaces the existing relay:
+    let funder_mutation =
+        FunderMutation::AddRelay(named_relay_address.clone().rename(set_relay_name.name));
+    m_state.mutate(funder_mutation);
+
+    Ok(())
+}
+
+/// Remove relays with duplicate public keys (keeping the first occurrence), and make sure that
+/// the amount of remaining relays does not exceed `MAX_FRIEND_RELAYS`.
+fn check_friend_relays<B>(
+    relays: Vec<RelayAddress<B>>,
+) -> Result<Vec<RelayAddress<B>>, HandleControlError> {
+    let mut seen_public_keys = HashSet::new();
+    let relays = relays
+        .into_iter()
+        .filter(|relay_address| seen_public_keys.insert(relay_address.public_key.clone()))
+        .collect::<Vec<_>>();
+
+    if relays.len() > MAX_FRIEND_RELAYS {
+        return Err(HandleControlError::MaxFriendRelaysExceeded);
+    }
+    Ok(relays)
+}
+
+fn control_add_friend<B>(
+    m_state: &mut MutableFunderState<B>,
+    mut add_friend: AddFriend<B>,
+) -> Result<(), HandleControlError>
 where
     B: Clone + PartialEq + Eq + CanonicalSerialize + Debug,
 {
+    add_friend.relays = check_friend_relays(add_friend.relays)?;
+
     if !m_state
         .state()
         .friends
@@ -270,6 +370,7 @@ where
     } else {
         warn!("control_add_friend(): Attempt to add the same friend twice!");
     }
+    Ok(())
 }
 
 /// This is a violent operation, as it removes all the known state with the remote friend.
@@ -308,6 +409,7 @@ where
         outgoing_control,
         rng,
         &remove_friend.friend_public_key,
+        CancelReason::ClosedRequests,
     );
 
     let funder_mutation = FunderMutation::RemoveFriend(remove_friend.friend_public_key.clone());
@@ -366,6 +468,15 @@ where
             rng,
             &friend_public_key,
         ),
+        FriendStatus::Paused => pause_friend(
+            m_state,
+            send_commands,
+            outgoing_control,
+            outgoing_channeler_config,
+            rng,
+            friend_public_key,
+            &friend_address,
+        ),
     };
 
     Ok(())
@@ -417,6 +528,7 @@ where
             rng,
             &set_friend_currency_requests_status.friend_public_key,
             &CurrencyChoice::One(set_friend_currency_requests_status.currency.clone()),
+            CancelReason::ClosedRequests,
         );
     }
 
@@ -441,11 +553,13 @@ where
 fn control_set_friend_relays<B>(
     m_state: &mut MutableFunderState<B>,
     outgoing_channeler_config: &mut Vec<ChannelerConfig<RelayAddress<B>>>,
-    set_friend_relays: SetFriendRelays<B>,
+    mut set_friend_relays: SetFriendRelays<B>,
 ) -> Result<(), HandleControlError>
 where
     B: Clone + PartialEq + Eq + CanonicalSerialize + Debug,
 {
+    set_friend_relays.relays = check_friend_relays(set_friend_relays.relays)?;
+
     // Make sure that friend exists:
     let friend = m_state
         .state()
@@ -469,7 +583,7 @@ where
     ));
     m_state.mutate(funder_mutation);
 
-    if let FriendStatus::Enabled = friend_status {
+    if friend_status.is_connected() {
         // Notify Channeler to change the friend's address:
         let update_friend = ChannelerUpdateFriend {
             friend_public_key: set_friend_relays.friend_public_key.clone(),
@@ -863,9 +977,15 @@ where
         create_transaction.clone(),
     ) {
         error!("control_create_transaction_inner() failed: {:?}", e);
+        let opt_reason = match e {
+            HandleControlError::FriendDoesNotExist | HandleControlError::FriendNotReady => {
+                Some(CancelReason::NoRoute)
+            }
+            _ => None,
+        };
         let transaction_result = TransactionResult {
             request_id: create_transaction.request_id,
-            result: RequestResult::Failure,
+            result: RequestResult::Failure(opt_reason),
         };
 
         outgoing_control.push(FunderOutgoingControl::TransactionResult(transaction_result));
@@ -919,6 +1039,8 @@ where
             assert!(*num_transactions > 0);
             (PaymentStage::InProgress(*num_transactions), None)
         }
+        // The receipt is kept until the user acks it. Repeated requests (For example, after the
+        // app reconnects) get back the same receipt, without changing the payment's state:
         PaymentStage::Success(num_transactions, receipt, ack_uid) => (
             PaymentStage::Success(*num_transactions, receipt.clone(), ack_uid.clone()),
             Some(PaymentStatus::Success(PaymentStatusSuccess {
@@ -962,6 +1084,78 @@ where
     Ok(())
 }
 
+/// Cancel a payment: No new transactions are allowed, and all the transactions of this payment
+/// that were not yet sent to a friend are canceled immediately.
+///
+/// Transactions that were already sent can only be canceled by the remote side, and their credits
+/// remain frozen until then. Those transactions are reported back to the app (Using a
+/// ResponseCancelPayment). The payment is closed (Using a ResponseClosePayment) only once all of
+/// its transactions are resolved.
+fn control_cancel_payment<B, R>(
+    m_state: &mut MutableFunderState<B>,
+    outgoing_control: &mut Vec<FunderOutgoingControl<B>>,
+    rng: &R,
+    payment_id: PaymentId,
+) -> Result<(), HandleControlError>
+where
+    B: Clone + PartialEq + Eq + CanonicalSerialize + Debug,
+    R: CryptoRandom,
+{
+    // Stop new transactions for this payment:
+    control_request_close_payment(m_state, outgoing_control, rng, payment_id.clone())?;
+
+    // Collect all the transactions of this payment that are still waiting to be sent:
+    let mut queued_requests = Vec::new();
+    for (friend_public_key, friend) in &m_state.state().friends {
+        let channel_consistent = match &friend.channel_status {
+            ChannelStatus::Inconsistent(_) => continue,
+            ChannelStatus::Consistent(channel_consistent) => channel_consistent,
+        };
+        for (_currency, user_request) in &channel_consistent.pending_user_requests {
+            let is_payment_request = m_state
+                .state()
+                .open_transactions
+                .get(&user_request.request_id)
+                .map(|open_transaction| open_transaction.payment_id == payment_id)
+                .unwrap_or(false);
+            if is_payment_request {
+                queued_requests.push((friend_public_key.clone(), user_request.request_id.clone()));
+            }
+        }
+    }
+
+    for (friend_public_key, request_id) in queued_requests {
+        let friend_mutation = FriendMutation::RemovePendingUserRequest(request_id.clone());
+        let funder_mutation = FunderMutation::FriendMutation((friend_public_key, friend_mutation));
+        m_state.mutate(funder_mutation);
+
+        let transaction_result = TransactionResult {
+            request_id: request_id.clone(),
+            result: RequestResult::Failure(None),
+        };
+        outgoing_control.push(FunderOutgoingControl::TransactionResult(transaction_result));
+        remove_transaction(m_state, outgoing_control, rng, &request_id);
+    }
+
+    // Report the transactions that are already in flight, and could not be canceled:
+    let mut in_flight: Vec<Uid> = m_state
+        .state()
+        .open_transactions
+        .iter()
+        .filter(|(_request_id, open_transaction)| open_transaction.payment_id == payment_id)
+        .map(|(request_id, _open_transaction)| request_id.clone())
+        .collect();
+    in_flight.sort();
+
+    outgoing_control.push(FunderOutgoingControl::ResponseCancelPayment(
+        ResponseCancelPayment {
+            payment_id,
+            in_flight,
+        },
+    ));
+    Ok(())
+}
+
 fn control_ack_close_payment<B>(
     m_state: &mut MutableFunderState<B>,
     ack_close_payment: AckClosePayment,
@@ -1044,6 +1238,7 @@ where
         add_invoice.currency,
         add_invoice.total_dest_payment,
         dest_plain_lock,
+        add_invoice.opt_expiry_ticks,
     ));
     m_state.mutate(funder_mutation);
 
@@ -1065,27 +1260,13 @@ where
         .ok_or(HandleControlError::InvoiceDoesNotExist)?
         .clone();
 
-    // Cancel all pending transactions related to this invoice
-    for request_id in &open_invoice.incoming_transactions {
-        // Explaining the unwrap() below:
-        // We expect that the origin of this request must be from an existing friend.
-        // We can not be the originator of this request.
-        let friend_public_key =
-            find_request_origin(m_state.state(), &open_invoice.currency, &request_id)
-                .unwrap()
-                .clone();
-        reply_with_cancel(
-            m_state,
-            send_commands,
-            &friend_public_key,
-            &open_invoice.currency,
-            &request_id,
-        );
-    }
-
-    // Remove invoice:
-    let funder_mutation = FunderMutation::RemoveInvoice(invoice_id);
-    m_state.mutate(funder_mutation);
+    cancel_invoice(
+        m_state,
+        send_commands,
+        &invoice_id,
+        &open_invoice,
+        CancelReason::ClosedRequests,
+    );
 
     Ok(())
 }
@@ -1115,6 +1296,25 @@ where
         return Err(HandleControlError::InvalidCommit);
     }
 
+    // A commit is all or nothing: The incoming transactions of the invoice are collected only if
+    // together they pay the full total_dest_payment. This may not be the case for a multi-route
+    // payment where some of the routes have failed. Such a commit is rejected, and the invoice
+    // remains open, so that it can still be canceled (or expire).
+    let total_paid = open_invoice
+        .incoming_transactions
+        .iter()
+        .filter_map(|request_id| {
+            find_remote_pending_transaction(m_state.state(), &open_invoice.currency, request_id)
+        })
+        .fold(0u128, |total_paid, pending_transaction| {
+            total_paid.saturating_add(pending_transaction.dest_payment)
+        });
+    if total_paid < open_invoice.total_dest_payment {
+        return Err(HandleControlError::InvoiceNotFullyPaid(
+            open_invoice.total_dest_payment - total_paid,
+        ));
+    }
+
     // Push collect messages for all pending requests
     for request_id in &open_invoice.incoming_transactions {
         let friend_public_key = if let Some(friend_public_key) =
@@ -1151,6 +1351,27 @@ where
     Ok(())
 }
 
+/// Let the user know the outcome of setting the relays of a friend
+fn push_response_friend_relays<B>(
+    outgoing_control: &mut Vec<FunderOutgoingControl<B>>,
+    friend_public_key: PublicKey,
+    res: &Result<(), HandleControlError>,
+) where
+    B: Clone,
+{
+    let result = match res {
+        Ok(()) => FriendRelaysResult::Success,
+        Err(HandleControlError::MaxFriendRelaysExceeded) => FriendRelaysResult::TooManyRelays,
+        Err(_) => FriendRelaysResult::Failure,
+    };
+    outgoing_control.push(FunderOutgoingControl::ResponseFriendRelays(
+        ResponseFriendRelays {
+            friend_public_key,
+            result,
+        },
+    ));
+}
+
 pub fn handle_control_message<B, R>(
     m_state: &mut MutableFunderState<B>,
     m_ephemeral: &mut MutableEphemeral,
@@ -1197,9 +1418,15 @@ where
             Ok(())
         }
 
+        FunderControl::SetRelayName(set_relay_name) => {
+            control_set_relay_name(m_state, set_relay_name)
+        }
+
         FunderControl::AddFriend(add_friend) => {
-            control_add_friend(m_state, add_friend);
-            Ok(())
+            let friend_public_key = add_friend.friend_public_key.clone();
+            let res = control_add_friend(m_state, add_friend);
+            push_response_friend_relays(outgoing_control, friend_public_key, &res);
+            res
         }
 
         FunderControl::RemoveFriend(remove_friend) => control_remove_friend(
@@ -1231,7 +1458,11 @@ where
         }
 
         FunderControl::SetFriendRelays(set_friend_relays) => {
-            control_set_friend_relays(m_state, outgoing_channeler_config, set_friend_relays)
+            let friend_public_key = set_friend_relays.friend_public_key.clone();
+            let res =
+                control_set_friend_relays(m_state, outgoing_channeler_config, set_friend_relays);
+            push_response_friend_relays(outgoing_control, friend_public_key, &res);
+            res
         }
 
         FunderControl::SetFriendName(set_friend_name) => {
@@ -1260,6 +1491,9 @@ where
         FunderControl::RequestClosePayment(payment_id) => {
             control_request_close_payment(m_state, outgoing_control, rng, payment_id)
         }
+        FunderControl::CancelPayment(payment_id) => {
+            control_cancel_payment(m_state, outgoing_control, rng, payment_id)
+        }
         FunderControl::AckClosePayment(ack_close_payment) => {
             control_ack_close_payment(m_state, ack_close_payment)
         }
@@ -1270,7 +1504,22 @@ where
             control_cancel_invoice(m_state, send_commands, invoice_id)
         }
         FunderControl::CommitInvoice(commit) => {
-            control_commit_invoice(m_state, send_commands, &commit)
+            let res = control_commit_invoice(m_state, send_commands, &commit);
+            // Let the user know the outcome of the commit:
+            let result = match &res {
+                Ok(()) => CommitInvoiceResult::Success,
+                Err(HandleControlError::InvoiceNotFullyPaid(missing)) => {
+                    CommitInvoiceResult::NotFullyPaid(*missing)
+                }
+                Err(_) => CommitInvoiceResult::Failure,
+            };
+            outgoing_control.push(FunderOutgoingControl::ResponseCommitInvoice(
+                ResponseCommitInvoice {
+                    invoice_id: commit.invoice_id,
+                    result,
+                },
+            ));
+            res
         }
     }
 }
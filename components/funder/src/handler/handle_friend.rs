@@ -5,15 +5,16 @@ use signature::canonical::CanonicalSerialize;
 
 use crypto::rand::{CryptoRandom, RandGen};
 
+use proto::consts::MAX_OPERATIONS_IN_BATCH;
 use proto::crypto::{PublicKey, Signature, Uid};
 
 use proto::app_server::messages::RelayAddress;
 use proto::funder::messages::{
-    BalanceInfo, CancelSendFundsOp, ChannelerUpdateFriend, CollectSendFundsOp, CountersInfo,
-    Currency, CurrencyBalance, CurrencyBalanceInfo, FriendMessage, FunderOutgoingControl, McInfo,
-    MoveTokenRequest, PaymentStatus, PaymentStatusSuccess, PendingTransaction, RequestResult,
-    RequestSendFundsOp, ResetTerms, ResponseClosePayment, ResponseSendFundsOp, TokenInfo,
-    TransactionResult,
+    BalanceInfo, CancelReason, CancelSendFundsOp, ChannelerUpdateFriend, CollectSendFundsOp,
+    CountersInfo, Currency, CurrencyBalance, CurrencyBalanceInfo, FriendMessage,
+    FunderOutgoingControl, InconsistencyReason, McInfo, MoveTokenRequest, PaymentStatus,
+    PaymentStatusSuccess, PendingTransaction, RequestResult, RequestSendFundsOp, ResetTerms,
+    ResponseClosePayment, ResponseSendFundsOp, TokenInfo, TransactionResult,
 };
 use signature::signature_buff::hash_token_info;
 use signature::verify::verify_move_token;
@@ -32,7 +33,9 @@ use crate::friend::{
 };
 use crate::state::{FunderMutation, FunderState, Payment, PaymentStage};
 
-use crate::ephemeral::Ephemeral;
+use crate::ephemeral::{Ephemeral, EphemeralMutation};
+use crate::idle_ticks::IdleTicksMutation;
+use crate::retransmits::RetransmitsMutation;
 
 use crate::handler::canceler::{
     cancel_local_pending_transactions, cancel_pending_requests, remove_transaction,
@@ -117,11 +120,11 @@ pub fn try_reset_channel<B>(
 
     // Obtain token channel, and get current
     let remote_token_info = TokenInfo {
-        mc: McInfo {
-            local_public_key: friend_public_key.clone(),
-            remote_public_key: m_state.state().local_public_key.clone(),
-            balances: balances_for_reset,
-        },
+        mc: McInfo::new(
+            friend_public_key.clone(),
+            m_state.state().local_public_key.clone(),
+            balances_for_reset,
+        ),
         counters: CountersInfo {
             inconsistency_counter: local_reset_terms.inconsistency_counter,
             move_token_counter: 0,
@@ -302,6 +305,20 @@ fn handle_request_send_funds<B>(
 ) where
     B: Clone + PartialEq + Eq + CanonicalSerialize + Debug,
 {
+    // We do not accept new requests from a paused friend:
+    let friend = m_state.state().friends.get(&remote_public_key).unwrap();
+    if !friend.status.accepts_requests() {
+        reply_with_cancel(
+            m_state,
+            send_commands,
+            remote_public_key,
+            currency,
+            &request_send_funds.request_id,
+            CancelReason::ClosedRequests,
+        );
+        return;
+    }
+
     if request_send_funds.route.is_empty() {
         // We are the destination of this request.
 
@@ -313,6 +330,7 @@ fn handle_request_send_funds<B>(
                     remote_public_key,
                     currency,
                     &request_send_funds.request_id,
+                    CancelReason::ClosedRequests,
                 );
                 return;
             }
@@ -374,6 +392,7 @@ fn handle_request_send_funds<B>(
             remote_public_key,
             currency,
             &request_send_funds.request_id,
+            CancelReason::ClosedRequests,
         );
         return;
     }
@@ -439,6 +458,7 @@ fn handle_request_send_funds<B>(
                 remote_public_key,
                 currency,
                 &request_id,
+                CancelReason::NoRoute,
             );
             return;
         }
@@ -472,6 +492,7 @@ fn handle_request_send_funds_cancel<B>(
         remote_public_key,
         currency,
         &request_send_funds.request_id,
+        CancelReason::InsufficientTrust,
     );
 }
 
@@ -490,6 +511,19 @@ fn handle_response_send_funds<B>(
             // We couldn't find any external origin.
             // It means that we are the origin of this request
 
+            if !m_state
+                .state()
+                .open_transactions
+                .contains_key(&response_send_funds.request_id)
+            {
+                // The transaction was already canceled (It was stale).
+                warn!(
+                    "handle_response_send_funds(): Response for a canceled transaction: {:?}",
+                    response_send_funds.request_id
+                );
+                return;
+            }
+
             // Keep the response:
             let funder_mutation =
                 FunderMutation::SetTransactionResponse(response_send_funds.clone());
@@ -554,6 +588,15 @@ fn handle_cancel_send_funds<B, R>(
         None => {
             // We are the origin of this request, and we got a cancellation.
 
+            if !m_state
+                .state()
+                .open_transactions
+                .contains_key(&cancel_send_funds.request_id)
+            {
+                // The transaction was already canceled (It was stale).
+                return;
+            }
+
             // Update buyer transactions (requests that were originated by us):
             remove_transaction(
                 m_state,
@@ -566,7 +609,7 @@ fn handle_cancel_send_funds<B, R>(
             outgoing_control.push(FunderOutgoingControl::TransactionResult(
                 TransactionResult {
                     request_id: pending_transaction.request_id,
-                    result: RequestResult::Failure,
+                    result: RequestResult::Failure(cancel_send_funds.opt_reason),
                 },
             ));
         }
@@ -601,11 +644,21 @@ fn handle_collect_send_funds<B, R>(
     match find_request_origin(m_state.state(), currency, &collect_send_funds.request_id).cloned() {
         None => {
             // We are the origin of this request, and we got a Collect message
-            let open_transaction = m_state
+            let open_transaction = match m_state
                 .state()
                 .open_transactions
                 .get(&collect_send_funds.request_id)
-                .unwrap();
+            {
+                Some(open_transaction) => open_transaction,
+                None => {
+                    // The transaction was already canceled (It was stale).
+                    warn!(
+                        "handle_collect_send_funds(): Collect for a canceled transaction: {:?}",
+                        collect_send_funds.request_id
+                    );
+                    return;
+                }
+            };
 
             let payment = m_state
                 .state()
@@ -813,6 +866,7 @@ fn handle_move_token_error<B, R>(
     outgoing_control: &mut Vec<FunderOutgoingControl<B>>,
     rng: &R,
     remote_public_key: &PublicKey,
+    reason: InconsistencyReason,
 ) where
     B: Clone + PartialEq + Eq + CanonicalSerialize + Debug,
     R: CryptoRandom,
@@ -833,6 +887,7 @@ fn handle_move_token_error<B, R>(
         outgoing_control,
         rng,
         remote_public_key,
+        CancelReason::NoRoute,
     );
 
     // Cancel all pending requests to this friend:
@@ -843,6 +898,7 @@ fn handle_move_token_error<B, R>(
         rng,
         remote_public_key,
         &CurrencyChoice::All,
+        CancelReason::NoRoute,
     );
 
     // Keep outgoing InconsistencyError message details in memory:
@@ -850,6 +906,7 @@ fn handle_move_token_error<B, R>(
         opt_last_incoming_move_token,
         local_reset_terms,
         opt_remote_reset_terms: None,
+        reason,
     };
     let friend_mutation = FriendMutation::SetInconsistent(channel_inconsistent);
     let funder_mutation =
@@ -884,6 +941,17 @@ fn handle_move_token_success<B, R>(
         ReceiveMoveTokenOutput::Received(move_token_received) => {
             send_commands.set_try_send(remote_public_key);
 
+            // The friend is active, restart counting idle ticks:
+            if m_ephemeral
+                .ephemeral()
+                .idle_ticks
+                .get_idle_ticks(remote_public_key)
+                > 0
+            {
+                let idle_ticks_mutation = IdleTicksMutation::SetActive(remote_public_key.clone());
+                m_ephemeral.mutate(EphemeralMutation::IdleTicksMutation(idle_ticks_mutation));
+            }
+
             let MoveTokenReceived {
                 // incoming_messages,
                 mutations,
@@ -970,6 +1038,44 @@ fn handle_move_token_success<B, R>(
     }
 }
 
+/// Count consecutive old move tokens (Duplicates or retransmission requests) received from a
+/// friend. A new move token resets the count.
+///
+/// Returns false if the friend has sent more than `max_move_token_retransmits` old move tokens
+/// in a row. A value of 0 means no limit.
+fn count_retransmit<B>(
+    m_ephemeral: &mut MutableEphemeral,
+    max_move_token_retransmits: usize,
+    remote_public_key: &PublicKey,
+    receive_move_token_output: &ReceiveMoveTokenOutput<B>,
+) -> bool {
+    let retransmits = m_ephemeral
+        .ephemeral()
+        .retransmits
+        .get_retransmits(remote_public_key);
+
+    match receive_move_token_output {
+        ReceiveMoveTokenOutput::Duplicate | ReceiveMoveTokenOutput::RetransmitOutgoing(_) => {
+            let retransmits = retransmits.saturating_add(1);
+            if max_move_token_retransmits > 0 && retransmits > max_move_token_retransmits {
+                let retransmits_mutation = RetransmitsMutation::Remove(remote_public_key.clone());
+                m_ephemeral.mutate(EphemeralMutation::RetransmitsMutation(retransmits_mutation));
+                return false;
+            }
+            let retransmits_mutation =
+                RetransmitsMutation::SetRetransmits((remote_public_key.clone(), retransmits));
+            m_ephemeral.mutate(EphemeralMutation::RetransmitsMutation(retransmits_mutation));
+        }
+        ReceiveMoveTokenOutput::Received(_) => {
+            if retransmits > 0 {
+                let retransmits_mutation = RetransmitsMutation::Remove(remote_public_key.clone());
+                m_ephemeral.mutate(EphemeralMutation::RetransmitsMutation(retransmits_mutation));
+            }
+        }
+    }
+    true
+}
+
 fn handle_move_token_request<B, R>(
     m_state: &mut MutableFunderState<B>,
     m_ephemeral: &mut MutableEphemeral,
@@ -977,6 +1083,7 @@ fn handle_move_token_request<B, R>(
     outgoing_control: &mut Vec<FunderOutgoingControl<B>>,
     outgoing_channeler_config: &mut Vec<ChannelerConfig<RelayAddress<B>>>,
     rng: &R,
+    max_move_token_retransmits: usize,
     remote_public_key: &PublicKey,
     friend_move_token_request: MoveTokenRequest<B>,
 ) -> Result<(), HandleFriendError>
@@ -990,8 +1097,35 @@ where
         None => Err(HandleFriendError::FriendDoesNotExist),
     }?;
 
+    let num_operations: usize = friend_move_token_request
+        .move_token
+        .currencies_operations
+        .iter()
+        .map(|currency_operations| currency_operations.operations.len())
+        .sum();
+
     let token_channel = match &friend.channel_status {
-        ChannelStatus::Consistent(channel_consistent) => &channel_consistent.token_channel,
+        ChannelStatus::Consistent(channel_consistent) => {
+            // Reject a move token with too many operations before doing any work on its
+            // operations. The limit is a protocol constant, and does not depend on our local
+            // configuration:
+            if num_operations > MAX_OPERATIONS_IN_BATCH {
+                warn!(
+                    "handle_move_token_request(): Too many operations in move token: {} > {}",
+                    num_operations, MAX_OPERATIONS_IN_BATCH
+                );
+                handle_move_token_error(
+                    m_state,
+                    send_commands,
+                    outgoing_control,
+                    rng,
+                    remote_public_key,
+                    InconsistencyReason::TooManyOperations,
+                );
+                return Ok(());
+            }
+            &channel_consistent.token_channel
+        }
         ChannelStatus::Inconsistent(channel_inconsistent) => {
             let local_reset_terms = channel_inconsistent.local_reset_terms.clone();
             try_reset_channel(
@@ -1018,6 +1152,26 @@ where
 
     match receive_move_token_res {
         Ok(receive_move_token_output) => {
+            if !count_retransmit(
+                m_ephemeral,
+                max_move_token_retransmits,
+                remote_public_key,
+                &receive_move_token_output,
+            ) {
+                warn!(
+                    "handle_move_token_request(): More than {} retransmitted move tokens in a row",
+                    max_move_token_retransmits
+                );
+                handle_move_token_error(
+                    m_state,
+                    send_commands,
+                    outgoing_control,
+                    rng,
+                    remote_public_key,
+                    InconsistencyReason::TooManyRetransmits,
+                );
+                return Ok(());
+            }
             handle_move_token_success(
                 m_state,
                 m_ephemeral,
@@ -1041,6 +1195,7 @@ where
                 outgoing_control,
                 rng,
                 remote_public_key,
+                InconsistencyReason::InvalidMoveToken,
             );
         }
     };
@@ -1070,7 +1225,7 @@ where
 
     // Obtain information about our reset terms:
     let friend = m_state.state().friends.get(remote_public_key).unwrap();
-    let (channel_was_consistent, new_local_reset_terms, opt_last_incoming_move_token) =
+    let (channel_was_consistent, new_local_reset_terms, opt_last_incoming_move_token, reason) =
         match &friend.channel_status {
             ChannelStatus::Consistent(channel_consistent) => {
                 let token_channel = &channel_consistent.token_channel;
@@ -1081,12 +1236,15 @@ where
                     true,
                     gen_reset_terms(&token_channel, rng),
                     token_channel.get_last_incoming_move_token_hashed().cloned(),
+                    InconsistencyReason::RemoteReported,
                 )
             }
+            // Keep the reason for which the channel originally became inconsistent:
             ChannelStatus::Inconsistent(channel_inconsistent) => (
                 false,
                 channel_inconsistent.local_reset_terms.clone(),
                 channel_inconsistent.opt_last_incoming_move_token.clone(),
+                channel_inconsistent.reason.clone(),
             ),
         };
 
@@ -1099,6 +1257,7 @@ where
             rng,
             remote_public_key,
             &CurrencyChoice::All,
+            CancelReason::NoRoute,
         );
     }
 
@@ -1107,6 +1266,7 @@ where
         opt_last_incoming_move_token,
         local_reset_terms: new_local_reset_terms,
         opt_remote_reset_terms: Some(new_remote_reset_terms),
+        reason,
     };
     let friend_mutation = FriendMutation::SetInconsistent(channel_inconsistent);
     let funder_mutation =
@@ -1127,6 +1287,7 @@ pub fn handle_friend_message<B, R>(
     outgoing_control: &mut Vec<FunderOutgoingControl<B>>,
     outgoing_channeler_config: &mut Vec<ChannelerConfig<RelayAddress<B>>>,
     rng: &R,
+    max_move_token_retransmits: usize,
     remote_public_key: &PublicKey,
     friend_message: FriendMessage<B>,
 ) -> Result<(), HandleFriendError>
@@ -1148,6 +1309,7 @@ where
             outgoing_control,
             outgoing_channeler_config,
             rng,
+            max_move_token_retransmits,
             remote_public_key,
             friend_move_token_request,
         ),
@@ -4,11 +4,13 @@ use std::fmt::Debug;
 use proto::app_server::messages::RelayAddress;
 use proto::funder::messages::{ChannelerUpdateFriend, FriendStatus};
 
-use crate::handler::state_wrap::MutableFunderState;
+use crate::handler::handle_timer::track_waiting_transactions;
+use crate::handler::state_wrap::{MutableEphemeral, MutableFunderState};
 use crate::types::ChannelerConfig;
 
 pub fn handle_init<B>(
     m_state: &MutableFunderState<B>,
+    m_ephemeral: &mut MutableEphemeral,
     outgoing_channeler_config: &mut Vec<ChannelerConfig<RelayAddress<B>>>,
 ) where
     B: Clone + CanonicalSerialize + PartialEq + Eq + Debug,
@@ -16,7 +18,7 @@ pub fn handle_init<B>(
     let mut enabled_friends = Vec::new();
     for friend in m_state.state().friends.values() {
         match friend.status {
-            FriendStatus::Enabled => {
+            FriendStatus::Enabled | FriendStatus::Paused => {
                 let channeler_add_friend = ChannelerUpdateFriend {
                     friend_public_key: friend.remote_public_key.clone(),
                     friend_relays: friend.remote_relays.clone(),
@@ -49,6 +51,9 @@ pub fn handle_init<B>(
         // Notify Channeler:
         outgoing_channeler_config.push(ChannelerConfig::UpdateFriend(enabled_friend));
     }
+
+    // Ages of pending transactions are not persisted. Start counting again:
+    track_waiting_transactions(m_state, m_ephemeral);
 }
 
 #[cfg(test)]
@@ -61,6 +66,7 @@ mod tests {
     use crate::friend::FriendMutation;
     use crate::state::{FunderMutation, FunderState};
 
+    use crate::ephemeral::Ephemeral;
     use crate::handler::state_wrap::MutableFunderState;
     use crate::handler::tests::utils::{dummy_named_relay_address, dummy_relay_address};
 
@@ -88,8 +94,13 @@ mod tests {
         state.mutate(&funder_mutation);
 
         let mut m_state = MutableFunderState::new(state);
+        let mut m_ephemeral = MutableEphemeral::new(Ephemeral::new());
         let mut outgoing_channeler_config = Vec::new();
-        handle_init(&mut m_state, &mut outgoing_channeler_config);
+        handle_init(
+            &mut m_state,
+            &mut m_ephemeral,
+            &mut outgoing_channeler_config,
+        );
 
         let (_initial_state, mutations, _final_state) = m_state.done();
         assert!(mutations.is_empty());
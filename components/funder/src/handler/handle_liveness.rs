@@ -4,12 +4,13 @@ use signature::canonical::CanonicalSerialize;
 
 use crypto::rand::CryptoRandom;
 
-use proto::funder::messages::{FriendStatus, FunderOutgoingControl};
+use proto::funder::messages::{CancelReason, FriendStatus, FunderOutgoingControl};
 
 use crate::types::IncomingLivenessMessage;
 
 use crate::ephemeral::EphemeralMutation;
 use crate::liveness::LivenessMutation;
+use crate::retransmits::RetransmitsMutation;
 
 use crate::handler::canceler::{cancel_pending_requests, CurrencyChoice};
 use crate::handler::state_wrap::{MutableEphemeral, MutableFunderState};
@@ -42,7 +43,7 @@ where
                 None => Err(HandleLivenessError::FriendDoesNotExist),
             }?;
             match friend.status {
-                FriendStatus::Enabled => Ok(()),
+                FriendStatus::Enabled | FriendStatus::Paused => Ok(()),
                 FriendStatus::Disabled => Err(HandleLivenessError::FriendIsDisabled),
             }?;
 
@@ -71,6 +72,10 @@ where
             let ephemeral_mutation = EphemeralMutation::LivenessMutation(liveness_mutation);
             m_ephemeral.mutate(ephemeral_mutation);
 
+            // Retransmissions are counted per connection:
+            let retransmits_mutation = RetransmitsMutation::Remove(friend_public_key.clone());
+            m_ephemeral.mutate(EphemeralMutation::RetransmitsMutation(retransmits_mutation));
+
             // If the friend does not exist, we have nothing more to do here:
             if m_state.state().friends.get(&friend_public_key).is_none() {
                 return Ok(());
@@ -84,6 +89,7 @@ where
                 rng,
                 &friend_public_key,
                 &CurrencyChoice::All,
+                CancelReason::NoRoute,
             );
         }
     };
@@ -0,0 +1,779 @@
+use std::fmt::Debug;
+
+use signature::canonical::CanonicalSerialize;
+
+use crypto::rand::CryptoRandom;
+
+use proto::funder::messages::{CancelReason, FunderOutgoingControl, TransactionStage};
+
+use crate::ephemeral::EphemeralMutation;
+use crate::friend::ChannelStatus;
+use crate::idle_ticks::IdleTicksMutation;
+use crate::invoice_age::InvoiceAgeMutation;
+use crate::pending_age::{PendingAgeMutation, PendingKey};
+use crate::state::FunderState;
+
+use crate::handler::canceler::{cancel_invoice, cancel_local_pending_transaction};
+use crate::handler::state_wrap::{MutableEphemeral, MutableFunderState};
+use crate::handler::types::SendCommands;
+
+/// Is the local pending transaction identified by `pending_key` still waiting for a response
+/// from the remote side?
+fn is_waiting<B>(state: &FunderState<B>, pending_key: &PendingKey) -> bool
+where
+    B: Clone + CanonicalSerialize + PartialEq + Eq + Debug,
+{
+    let (friend_public_key, currency, request_id) = pending_key;
+    let friend = match state.friends.get(friend_public_key) {
+        Some(friend) => friend,
+        None => return false,
+    };
+    let channel_consistent = match &friend.channel_status {
+        ChannelStatus::Inconsistent(_) => return false,
+        ChannelStatus::Consistent(channel_consistent) => channel_consistent,
+    };
+    let mutual_credit = match channel_consistent
+        .token_channel
+        .get_mutual_credits()
+        .get(currency)
+    {
+        Some(mutual_credit) => mutual_credit,
+        None => return false,
+    };
+    match mutual_credit
+        .state()
+        .pending_transactions
+        .local
+        .get(request_id)
+    {
+        Some(pending_transaction) => match pending_transaction.stage {
+            TransactionStage::Request => true,
+            TransactionStage::Response(..) => false,
+        },
+        None => false,
+    }
+}
+
+/// Advance the age of all open invoices that have an expiry by one tick.
+///
+/// An invoice that reaches its expiry is canceled: All the incoming transactions of the invoice
+/// are canceled and the invoice is removed, so that it can no longer be committed.
+fn expire_invoices<B>(
+    m_state: &mut MutableFunderState<B>,
+    m_ephemeral: &mut MutableEphemeral,
+    send_commands: &mut SendCommands,
+) where
+    B: Clone + CanonicalSerialize + PartialEq + Eq + Debug,
+{
+    // Forget about invoices that are no longer open:
+    let done_invoice_ids = m_ephemeral
+        .ephemeral()
+        .invoice_ages
+        .ages
+        .keys()
+        .filter(|invoice_id| !m_state.state().open_invoices.contains_key(invoice_id))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    for invoice_id in done_invoice_ids {
+        let invoice_age_mutation = InvoiceAgeMutation::Remove(invoice_id);
+        m_ephemeral.mutate(EphemeralMutation::InvoiceAgeMutation(invoice_age_mutation));
+    }
+
+    let expiring_invoices = m_state
+        .state()
+        .open_invoices
+        .iter()
+        .filter_map(|(invoice_id, open_invoice)| {
+            Some((invoice_id.clone(), open_invoice.opt_expiry_ticks?))
+        })
+        .collect::<Vec<_>>();
+
+    for (invoice_id, expiry_ticks) in expiring_invoices {
+        let new_age = m_ephemeral.ephemeral().invoice_ages.get_age(&invoice_id) + 1;
+        if new_age < expiry_ticks {
+            let invoice_age_mutation = InvoiceAgeMutation::SetAge((invoice_id, new_age));
+            m_ephemeral.mutate(EphemeralMutation::InvoiceAgeMutation(invoice_age_mutation));
+            continue;
+        }
+
+        warn!(
+            "handle_timer_tick(): Canceling expired invoice: {:?}",
+            invoice_id
+        );
+        let open_invoice = m_state
+            .state()
+            .open_invoices
+            .get(&invoice_id)
+            .unwrap()
+            .clone();
+        cancel_invoice(
+            m_state,
+            send_commands,
+            &invoice_id,
+            &open_invoice,
+            CancelReason::Timeout,
+        );
+
+        let invoice_age_mutation = InvoiceAgeMutation::Remove(invoice_id);
+        m_ephemeral.mutate(EphemeralMutation::InvoiceAgeMutation(invoice_age_mutation));
+    }
+}
+
+/// Advance the current tick used for counting friends idle ticks.
+/// This does not create any report mutation: Idle ticks are calculated when a report is created.
+fn advance_idle_ticks<B>(m_state: &MutableFunderState<B>, m_ephemeral: &mut MutableEphemeral)
+where
+    B: Clone + CanonicalSerialize + PartialEq + Eq + Debug,
+{
+    // Forget about friends that were removed:
+    let removed_friends = m_ephemeral
+        .ephemeral()
+        .idle_ticks
+        .last_activity
+        .keys()
+        .filter(|friend_public_key| !m_state.state().friends.contains_key(friend_public_key))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    for friend_public_key in removed_friends {
+        let idle_ticks_mutation = IdleTicksMutation::Remove(friend_public_key);
+        m_ephemeral.mutate(EphemeralMutation::IdleTicksMutation(idle_ticks_mutation));
+    }
+
+    // Start counting for new friends:
+    let new_friends = m_state
+        .state()
+        .friends
+        .keys()
+        .filter(|friend_public_key| {
+            !m_ephemeral
+                .ephemeral()
+                .idle_ticks
+                .last_activity
+                .contains_key(friend_public_key)
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+
+    for friend_public_key in new_friends {
+        let idle_ticks_mutation = IdleTicksMutation::SetActive(friend_public_key);
+        m_ephemeral.mutate(EphemeralMutation::IdleTicksMutation(idle_ticks_mutation));
+    }
+
+    m_ephemeral.mutate(EphemeralMutation::IdleTicksMutation(
+        IdleTicksMutation::Tick,
+    ));
+}
+
+/// Advance the age of all local pending transactions that are waiting for a response by one
+/// tick. Only the transactions tracked in the ephemeral `PendingAges` are visited.
+///
+/// A transaction that waits for a response for `pending_transaction_timeout_ticks` ticks is
+/// considered stale, and is canceled:
+/// - A forwarded request is canceled upstream, freeing the credits frozen by the origin of the
+///   request.
+/// - A request originated by us is reported to the user as failed (With a `Timeout` reason), and
+///   is removed from its payment.
+///
+/// The transaction itself remains pending in front of the remote friend, as only the remote side
+/// may remove it. Any later answer for this transaction is discarded. If the remote side collects
+/// it anyway, the credits are paid to the remote side, and can not be collected upstream.
+///
+/// A value of 0 for `pending_transaction_timeout_ticks` disables stale transactions cancellation.
+fn cancel_stale_transactions<B, R>(
+    m_state: &mut MutableFunderState<B>,
+    m_ephemeral: &mut MutableEphemeral,
+    send_commands: &mut SendCommands,
+    outgoing_control: &mut Vec<FunderOutgoingControl<B>>,
+    rng: &R,
+    pending_transaction_timeout_ticks: usize,
+) where
+    B: Clone + CanonicalSerialize + PartialEq + Eq + Debug,
+    R: CryptoRandom,
+{
+    if pending_transaction_timeout_ticks == 0 {
+        return;
+    }
+
+    let pending_ages = m_ephemeral.ephemeral().pending_ages.ages.clone();
+    for (pending_key, age) in pending_ages {
+        if !is_waiting(m_state.state(), &pending_key) {
+            // The remote side has answered (Or the channel was reset):
+            let pending_age_mutation = PendingAgeMutation::Remove(pending_key);
+            m_ephemeral.mutate(EphemeralMutation::PendingAgeMutation(pending_age_mutation));
+            continue;
+        }
+
+        if age >= pending_transaction_timeout_ticks {
+            // Already canceled:
+            continue;
+        }
+        let new_age = age + 1;
+        let pending_age_mutation = PendingAgeMutation::SetAge((pending_key.clone(), new_age));
+        m_ephemeral.mutate(EphemeralMutation::PendingAgeMutation(pending_age_mutation));
+
+        if new_age == pending_transaction_timeout_ticks {
+            let (_friend_public_key, currency, request_id) = pending_key;
+            warn!(
+                "handle_timer_tick(): Canceling stale transaction: {:?}",
+                request_id
+            );
+            cancel_local_pending_transaction(
+                m_state,
+                send_commands,
+                outgoing_control,
+                rng,
+                &currency,
+                &request_id,
+                CancelReason::Timeout,
+            );
+        }
+    }
+}
+
+/// Start tracking the age of all local pending transactions that are waiting for a response.
+/// Used after a restart, as ages are kept in memory only.
+pub fn track_waiting_transactions<B>(
+    m_state: &MutableFunderState<B>,
+    m_ephemeral: &mut MutableEphemeral,
+) where
+    B: Clone + CanonicalSerialize + PartialEq + Eq + Debug,
+{
+    for (friend_public_key, friend) in &m_state.state().friends {
+        let channel_consistent = match &friend.channel_status {
+            ChannelStatus::Inconsistent(_) => continue,
+            ChannelStatus::Consistent(channel_consistent) => channel_consistent,
+        };
+        for (currency, mutual_credit) in channel_consistent.token_channel.get_mutual_credits() {
+            for (request_id, pending_transaction) in
+                &mutual_credit.state().pending_transactions.local
+            {
+                if let TransactionStage::Response(..) = pending_transaction.stage {
+                    continue;
+                }
+                let pending_key = (
+                    friend_public_key.clone(),
+                    currency.clone(),
+                    request_id.clone(),
+                );
+                let pending_age_mutation = PendingAgeMutation::SetAge((pending_key, 0));
+                m_ephemeral.mutate(EphemeralMutation::PendingAgeMutation(pending_age_mutation));
+            }
+        }
+    }
+}
+
+/// Handle a timer tick: Advance friends idle ticks, expire open invoices and cancel stale
+/// transactions.
+pub fn handle_timer_tick<B, R>(
+    m_state: &mut MutableFunderState<B>,
+    m_ephemeral: &mut MutableEphemeral,
+    send_commands: &mut SendCommands,
+    outgoing_control: &mut Vec<FunderOutgoingControl<B>>,
+    rng: &R,
+    pending_transaction_timeout_ticks: usize,
+) where
+    B: Clone + CanonicalSerialize + PartialEq + Eq + Debug,
+    R: CryptoRandom,
+{
+    advance_idle_ticks(m_state, m_ephemeral);
+    expire_invoices(m_state, m_ephemeral, send_commands);
+    cancel_stale_transactions(
+        m_state,
+        m_ephemeral,
+        send_commands,
+        outgoing_control,
+        rng,
+        pending_transaction_timeout_ticks,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryFrom;
+
+    use crypto::hash_lock::HashLock;
+    use crypto::identity::{Identity, SoftwareEd25519Identity};
+    use crypto::rand::RandGen;
+    use crypto::test_utils::DummyRandom;
+
+    use proto::crypto::{
+        HashedLock, InvoiceId, PaymentId, PlainLock, PrivateKey, PublicKey, RandValue, Uid,
+    };
+    use proto::funder::messages::{
+        AddFriend, Commit, CommitInvoiceResult, Currency, FriendsRoute, FunderControl,
+        PaymentStatus, PendingTransaction, RequestResult, ResponseSendFundsOp,
+        UnsignedResponseSendFundsOp,
+    };
+
+    use signature::signature_buff::create_response_signature_buffer;
+
+    use crate::ephemeral::Ephemeral;
+    use crate::friend::{BackwardsOp, FriendMutation};
+    use crate::handler::handle_control::{handle_control_message, HandleControlError};
+    use crate::handler::prepare::prepare_commit;
+    use crate::mutual_credit::types::McMutation;
+    use crate::state::{FunderMutation, FunderState, Payment, PaymentStage};
+    use crate::token_channel::TcMutation;
+
+    use crate::handler::tests::utils::{dummy_named_relay_address, dummy_relay_address};
+
+    const TIMEOUT_TICKS: usize = 4;
+
+    /// Add a friend with an active mutual credit for `currency`
+    fn add_friend_with_currency(
+        state: &mut FunderState<u32>,
+        friend_public_key: &PublicKey,
+        currency: &Currency,
+    ) {
+        let add_friend = AddFriend {
+            friend_public_key: friend_public_key.clone(),
+            relays: vec![dummy_relay_address(1)],
+            name: "friend".into(),
+        };
+        state.mutate(&FunderMutation::AddFriend(add_friend));
+
+        let tc_mutations = vec![
+            TcMutation::SetLocalActiveCurrencies(vec![currency.clone()]),
+            TcMutation::SetRemoteActiveCurrencies(vec![currency.clone()]),
+            TcMutation::AddMutualCredit(currency.clone()),
+        ];
+        for tc_mutation in tc_mutations {
+            let friend_mutation = FriendMutation::TcMutation(tc_mutation);
+            state.mutate(&FunderMutation::FriendMutation((
+                friend_public_key.clone(),
+                friend_mutation,
+            )));
+        }
+    }
+
+    fn mc_mutate(
+        state: &mut FunderState<u32>,
+        friend_public_key: &PublicKey,
+        currency: &Currency,
+        mc_mutation: McMutation,
+    ) {
+        let tc_mutation = TcMutation::McMutation((currency.clone(), mc_mutation));
+        let friend_mutation = FriendMutation::TcMutation(tc_mutation);
+        state.mutate(&FunderMutation::FriendMutation((
+            friend_public_key.clone(),
+            friend_mutation,
+        )));
+    }
+
+    fn dummy_pending_transaction(request_id: Uid, route: Vec<PublicKey>) -> PendingTransaction {
+        PendingTransaction {
+            request_id,
+            route: FriendsRoute { public_keys: route },
+            dest_payment: 10,
+            total_dest_payment: 10,
+            invoice_id: InvoiceId::from(&[0; InvoiceId::len()]),
+            left_fees: 0,
+            src_hashed_lock: HashedLock::from(&[1; HashedLock::len()]),
+            stage: TransactionStage::Request,
+        }
+    }
+
+    /// Apply `num_ticks` timer ticks over the given state and ephemeral.
+    fn apply_ticks(
+        state: &mut FunderState<u32>,
+        ephemeral: &mut Ephemeral,
+        num_ticks: usize,
+    ) -> (SendCommands, Vec<FunderOutgoingControl<u32>>) {
+        let rng = DummyRandom::new(&[1u8]);
+        let mut send_commands = SendCommands::new();
+        let mut outgoing_control = Vec::new();
+
+        for _ in 0..num_ticks {
+            let mut m_state = MutableFunderState::new(state.clone());
+            let mut m_ephemeral = MutableEphemeral::new(ephemeral.clone());
+            handle_timer_tick(
+                &mut m_state,
+                &mut m_ephemeral,
+                &mut send_commands,
+                &mut outgoing_control,
+                &rng,
+                TIMEOUT_TICKS,
+            );
+            let (_initial_state, _funder_mutations, final_state) = m_state.done();
+            let (_ephemeral_mutations, final_ephemeral) = m_ephemeral.done();
+            *state = final_state;
+            *ephemeral = final_ephemeral;
+        }
+        (send_commands, outgoing_control)
+    }
+
+    /// Start tracking the age of all waiting local pending transactions, as done on startup.
+    fn track_waiting(state: &FunderState<u32>, ephemeral: &mut Ephemeral) {
+        let m_state = MutableFunderState::new(state.clone());
+        let mut m_ephemeral = MutableEphemeral::new(ephemeral.clone());
+        track_waiting_transactions(&m_state, &mut m_ephemeral);
+        let (_ephemeral_mutations, final_ephemeral) = m_ephemeral.done();
+        *ephemeral = final_ephemeral;
+    }
+
+    #[test]
+    fn test_handle_timer_tick_stale_forwarded_request() {
+        let local_pk = PublicKey::from(&[0xaa; PublicKey::len()]);
+        let upstream_pk = PublicKey::from(&[0xbb; PublicKey::len()]);
+        let downstream_pk = PublicKey::from(&[0xcc; PublicKey::len()]);
+        let currency = Currency::try_from("FST".to_owned()).unwrap();
+
+        let mut state =
+            FunderState::<u32>::new(local_pk.clone(), vec![dummy_named_relay_address(0)]);
+        add_friend_with_currency(&mut state, &upstream_pk, &currency);
+        add_friend_with_currency(&mut state, &downstream_pk, &currency);
+
+        // A request from upstream_pk was forwarded to downstream_pk.
+        // downstream_pk never answers.
+        let request_id = Uid::from(&[3; Uid::len()]);
+        let pending_transaction = dummy_pending_transaction(
+            request_id.clone(),
+            vec![upstream_pk.clone(), local_pk.clone(), downstream_pk.clone()],
+        );
+        mc_mutate(
+            &mut state,
+            &upstream_pk,
+            &currency,
+            McMutation::InsertRemotePendingTransaction(pending_transaction.clone()),
+        );
+        mc_mutate(
+            &mut state,
+            &downstream_pk,
+            &currency,
+            McMutation::InsertLocalPendingTransaction(pending_transaction),
+        );
+
+        let mut ephemeral = Ephemeral::new();
+        track_waiting(&state, &mut ephemeral);
+
+        // Nothing happens before the timeout:
+        let (send_commands, outgoing_control) =
+            apply_ticks(&mut state, &mut ephemeral, TIMEOUT_TICKS - 1);
+        assert!(send_commands.send_commands.is_empty());
+        assert!(outgoing_control.is_empty());
+
+        // The stale request is canceled upstream:
+        let (send_commands, outgoing_control) = apply_ticks(&mut state, &mut ephemeral, 1);
+        assert!(outgoing_control.is_empty());
+        assert!(
+            send_commands
+                .send_commands
+                .get(&upstream_pk)
+                .unwrap()
+                .try_send
+        );
+
+        let friend = state.friends.get(&upstream_pk).unwrap();
+        let channel_consistent = match &friend.channel_status {
+            ChannelStatus::Consistent(channel_consistent) => channel_consistent,
+            ChannelStatus::Inconsistent(_) => unreachable!(),
+        };
+        assert_eq!(channel_consistent.pending_backwards_ops.len(), 1);
+        match &channel_consistent.pending_backwards_ops[0] {
+            (cur, BackwardsOp::Cancel(cancel_send_funds)) => {
+                assert_eq!(cur, &currency);
+                assert_eq!(cancel_send_funds.request_id, request_id);
+                assert_eq!(cancel_send_funds.opt_reason, Some(CancelReason::Timeout));
+            }
+            _ => unreachable!(),
+        }
+
+        // The request is canceled only once:
+        let (send_commands, outgoing_control) = apply_ticks(&mut state, &mut ephemeral, 8);
+        assert!(send_commands.send_commands.is_empty());
+        assert!(outgoing_control.is_empty());
+
+        // Once the remote side removes the transaction, we forget about it:
+        mc_mutate(
+            &mut state,
+            &downstream_pk,
+            &currency,
+            McMutation::RemoveLocalPendingTransaction(request_id),
+        );
+        let _ = apply_ticks(&mut state, &mut ephemeral, 1);
+        assert!(ephemeral.pending_ages.ages.is_empty());
+    }
+
+    #[test]
+    fn test_handle_timer_tick_stale_local_request() {
+        let local_pk = PublicKey::from(&[0xaa; PublicKey::len()]);
+        let downstream_pk = PublicKey::from(&[0xcc; PublicKey::len()]);
+        let currency = Currency::try_from("FST".to_owned()).unwrap();
+
+        let mut state =
+            FunderState::<u32>::new(local_pk.clone(), vec![dummy_named_relay_address(0)]);
+        add_friend_with_currency(&mut state, &downstream_pk, &currency);
+
+        // We have sent a request to downstream_pk, and a response never arrives:
+        let request_id = Uid::from(&[3; Uid::len()]);
+        let payment_id = PaymentId::from(&[4; PaymentId::len()]);
+        let src_plain_lock = PlainLock::from(&[5; PlainLock::len()]);
+
+        state.mutate(&FunderMutation::UpdatePayment((
+            payment_id.clone(),
+            Payment {
+                src_plain_lock: src_plain_lock.clone(),
+                stage: PaymentStage::InProgress(1),
+            },
+        )));
+        state.mutate(&FunderMutation::AddTransaction((
+            request_id.clone(),
+            payment_id.clone(),
+        )));
+
+        let mut pending_transaction = dummy_pending_transaction(
+            request_id.clone(),
+            vec![local_pk.clone(), downstream_pk.clone()],
+        );
+        pending_transaction.src_hashed_lock = src_plain_lock.hash_lock();
+        mc_mutate(
+            &mut state,
+            &downstream_pk,
+            &currency,
+            McMutation::InsertLocalPendingTransaction(pending_transaction),
+        );
+
+        let mut ephemeral = Ephemeral::new();
+        track_waiting(&state, &mut ephemeral);
+
+        // Nothing happens before the timeout:
+        let (_send_commands, outgoing_control) =
+            apply_ticks(&mut state, &mut ephemeral, TIMEOUT_TICKS - 1);
+        assert!(outgoing_control.is_empty());
+
+        // The user is notified about the failure of the transaction, and the payment is closed:
+        let (_send_commands, outgoing_control) = apply_ticks(&mut state, &mut ephemeral, 1);
+        assert_eq!(outgoing_control.len(), 2);
+        match &outgoing_control[0] {
+            FunderOutgoingControl::TransactionResult(transaction_result) => {
+                assert_eq!(transaction_result.request_id, request_id);
+                assert_eq!(
+                    transaction_result.result,
+                    RequestResult::Failure(Some(CancelReason::Timeout))
+                );
+            }
+            _ => unreachable!(),
+        }
+        match &outgoing_control[1] {
+            FunderOutgoingControl::ResponseClosePayment(response_close_payment) => {
+                assert_eq!(response_close_payment.payment_id, payment_id);
+                match response_close_payment.status {
+                    PaymentStatus::Canceled(_) => {}
+                    _ => unreachable!(),
+                }
+            }
+            _ => unreachable!(),
+        }
+        assert!(state.open_transactions.is_empty());
+
+        // The failure is reported only once:
+        let (_send_commands, outgoing_control) = apply_ticks(&mut state, &mut ephemeral, 8);
+        assert!(outgoing_control.is_empty());
+    }
+
+    /// Open an invoice of 10 credits with an expiry, add an incoming transaction paying
+    /// `dest_payment` credits, and create a valid Commit for it.
+    fn open_expiring_invoice(
+        state: &mut FunderState<u32>,
+        identity: &SoftwareEd25519Identity,
+        invoice_id: &InvoiceId,
+        currency: &Currency,
+        expiry_ticks: u64,
+        dest_payment: u128,
+    ) -> Commit {
+        let dest_plain_lock = PlainLock::from(&[6; PlainLock::len()]);
+        state.mutate(&FunderMutation::AddInvoice((
+            invoice_id.clone(),
+            currency.clone(),
+            10,
+            dest_plain_lock.clone(),
+            Some(expiry_ticks),
+        )));
+
+        let src_plain_lock = PlainLock::from(&[5; PlainLock::len()]);
+        state.mutate(&FunderMutation::SetInvoiceSrcHashedLock((
+            invoice_id.clone(),
+            src_plain_lock.hash_lock(),
+        )));
+
+        let mut pending_transaction =
+            dummy_pending_transaction(Uid::from(&[3; Uid::len()]), Vec::new());
+        pending_transaction.invoice_id = invoice_id.clone();
+        pending_transaction.src_hashed_lock = src_plain_lock.hash_lock();
+        pending_transaction.dest_payment = dest_payment;
+
+        // The transaction was received from a friend:
+        let friend_pk = PublicKey::from(&[0xbb; PublicKey::len()]);
+        add_friend_with_currency(state, &friend_pk, currency);
+        mc_mutate(
+            state,
+            &friend_pk,
+            currency,
+            McMutation::InsertRemotePendingTransaction(pending_transaction.clone()),
+        );
+        state.mutate(&FunderMutation::AddIncomingTransaction((
+            invoice_id.clone(),
+            pending_transaction.request_id.clone(),
+        )));
+
+        let u_response_send_funds = UnsignedResponseSendFundsOp {
+            request_id: pending_transaction.request_id.clone(),
+            dest_hashed_lock: dest_plain_lock.hash_lock(),
+            is_complete: true,
+            rand_nonce: RandValue::from(&[7; RandValue::len()]),
+        };
+        let signature_buff = create_response_signature_buffer(
+            currency,
+            u_response_send_funds.clone(),
+            &pending_transaction,
+        );
+        let response_send_funds = ResponseSendFundsOp {
+            request_id: u_response_send_funds.request_id,
+            dest_hashed_lock: u_response_send_funds.dest_hashed_lock,
+            is_complete: u_response_send_funds.is_complete,
+            rand_nonce: u_response_send_funds.rand_nonce,
+            signature: identity.sign(&signature_buff),
+        };
+
+        prepare_commit(
+            currency.clone(),
+            &response_send_funds,
+            &pending_transaction,
+            src_plain_lock,
+        )
+    }
+
+    fn apply_commit(
+        state: &mut FunderState<u32>,
+        ephemeral: &mut Ephemeral,
+        outgoing_control: &mut Vec<FunderOutgoingControl<u32>>,
+        commit: Commit,
+    ) -> Result<(), HandleControlError> {
+        let rng = DummyRandom::new(&[1u8]);
+        let mut m_state = MutableFunderState::new(state.clone());
+        let mut m_ephemeral = MutableEphemeral::new(ephemeral.clone());
+        let res = handle_control_message(
+            &mut m_state,
+            &mut m_ephemeral,
+            &mut SendCommands::new(),
+            outgoing_control,
+            &mut Vec::new(),
+            &rng,
+            16,
+            16,
+            FunderControl::CommitInvoice(commit),
+        );
+        let (_initial_state, _funder_mutations, final_state) = m_state.done();
+        let (_ephemeral_mutations, final_ephemeral) = m_ephemeral.done();
+        *state = final_state;
+        *ephemeral = final_ephemeral;
+        res
+    }
+
+    #[test]
+    fn test_handle_timer_tick_commit_before_invoice_expiry() {
+        let rng = DummyRandom::new(&[2u8]);
+        let pkcs8 = PrivateKey::rand_gen(&rng);
+        let identity = SoftwareEd25519Identity::from_private_key(&pkcs8).unwrap();
+        let currency = Currency::try_from("FST".to_owned()).unwrap();
+        let invoice_id = InvoiceId::from(&[8; InvoiceId::len()]);
+
+        let mut state = FunderState::<u32>::new(
+            identity.get_public_key(),
+            vec![dummy_named_relay_address(0)],
+        );
+        let commit = open_expiring_invoice(&mut state, &identity, &invoice_id, &currency, 3, 10);
+
+        let mut ephemeral = Ephemeral::new();
+        let _ = apply_ticks(&mut state, &mut ephemeral, 2);
+        assert!(state.open_invoices.contains_key(&invoice_id));
+        assert_eq!(ephemeral.invoice_ages.get_age(&invoice_id), 2);
+
+        apply_commit(&mut state, &mut ephemeral, &mut Vec::new(), commit).unwrap();
+        assert!(state.open_invoices.is_empty());
+
+        // The age of the committed invoice is forgotten:
+        let _ = apply_ticks(&mut state, &mut ephemeral, 1);
+        assert!(ephemeral.invoice_ages.ages.is_empty());
+    }
+
+    #[test]
+    fn test_handle_timer_tick_commit_after_invoice_expiry() {
+        let rng = DummyRandom::new(&[2u8]);
+        let pkcs8 = PrivateKey::rand_gen(&rng);
+        let identity = SoftwareEd25519Identity::from_private_key(&pkcs8).unwrap();
+        let currency = Currency::try_from("FST".to_owned()).unwrap();
+        let invoice_id = InvoiceId::from(&[8; InvoiceId::len()]);
+
+        let mut state = FunderState::<u32>::new(
+            identity.get_public_key(),
+            vec![dummy_named_relay_address(0)],
+        );
+        let commit = open_expiring_invoice(&mut state, &identity, &invoice_id, &currency, 3, 10);
+
+        // The invoice is canceled once it expires:
+        let mut ephemeral = Ephemeral::new();
+        let _ = apply_ticks(&mut state, &mut ephemeral, 3);
+        assert!(state.open_invoices.is_empty());
+        assert!(ephemeral.invoice_ages.ages.is_empty());
+
+        match apply_commit(&mut state, &mut ephemeral, &mut Vec::new(), commit) {
+            Err(HandleControlError::InvoiceDoesNotExist) => {}
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_commit_invoice_not_fully_paid() {
+        let rng = DummyRandom::new(&[2u8]);
+        let pkcs8 = PrivateKey::rand_gen(&rng);
+        let identity = SoftwareEd25519Identity::from_private_key(&pkcs8).unwrap();
+        let currency = Currency::try_from("FST".to_owned()).unwrap();
+        let invoice_id = InvoiceId::from(&[8; InvoiceId::len()]);
+
+        let mut state = FunderState::<u32>::new(
+            identity.get_public_key(),
+            vec![dummy_named_relay_address(0)],
+        );
+        // Only 4 out of the 10 credits of the invoice were paid:
+        let commit = open_expiring_invoice(&mut state, &identity, &invoice_id, &currency, 3, 4);
+
+        // The partial commit is rejected, and nothing is collected:
+        let mut ephemeral = Ephemeral::new();
+        let mut outgoing_control = Vec::new();
+        match apply_commit(&mut state, &mut ephemeral, &mut outgoing_control, commit) {
+            Err(HandleControlError::InvoiceNotFullyPaid(6)) => {}
+            _ => unreachable!(),
+        }
+        // The user is told about the missing amount:
+        assert_eq!(outgoing_control.len(), 1);
+        match &outgoing_control[0] {
+            FunderOutgoingControl::ResponseCommitInvoice(response_commit_invoice) => {
+                assert_eq!(response_commit_invoice.invoice_id, invoice_id);
+                assert_eq!(
+                    response_commit_invoice.result,
+                    CommitInvoiceResult::NotFullyPaid(6)
+                );
+            }
+            _ => unreachable!(),
+        }
+        let friend = state
+            .friends
+            .get(&PublicKey::from(&[0xbb; PublicKey::len()]))
+            .unwrap();
+        let channel_consistent = match &friend.channel_status {
+            ChannelStatus::Consistent(channel_consistent) => channel_consistent,
+            ChannelStatus::Inconsistent(_) => unreachable!(),
+        };
+        assert!(channel_consistent.pending_backwards_ops.is_empty());
+
+        // The invoice remains open, until it expires:
+        assert!(state.open_invoices.contains_key(&invoice_id));
+        let _ = apply_ticks(&mut state, &mut ephemeral, 3);
+        assert!(state.open_invoices.is_empty());
+    }
+}
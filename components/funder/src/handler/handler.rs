@@ -1,3 +1,4 @@
+use std::cmp::min;
 use std::fmt::Debug;
 use std::hash::Hash;
 
@@ -6,6 +7,7 @@ use signature::canonical::CanonicalSerialize;
 use crypto::rand::CryptoRandom;
 
 use proto::app_server::messages::RelayAddress;
+use proto::consts::MAX_OPERATIONS_IN_BATCH;
 use proto::crypto::Uid;
 use proto::funder::messages::FunderOutgoingControl;
 use proto::report::messages::{FunderReportMutation, FunderReportMutations};
@@ -18,6 +20,7 @@ use crate::handler::handle_control::handle_control_message;
 use crate::handler::handle_friend::{handle_friend_message, HandleFriendError};
 use crate::handler::handle_init::handle_init;
 use crate::handler::handle_liveness::{handle_liveness_message, HandleLivenessError};
+use crate::handler::handle_timer::handle_timer_tick;
 use crate::handler::sender::create_friend_messages;
 use crate::handler::state_wrap::{MutableEphemeral, MutableFunderState};
 use crate::handler::types::SendCommands;
@@ -55,6 +58,8 @@ pub fn funder_handle_incoming<B, R>(
     rng: &R,
     max_node_relays: usize,
     max_pending_user_requests: usize,
+    pending_transaction_timeout_ticks: usize,
+    max_move_token_retransmits: usize,
     funder_incoming: FunderIncoming<B>,
 ) -> Result<FunderHandleIncomingOutput<B>, FunderHandlerError>
 where
@@ -67,7 +72,7 @@ where
 
     let opt_app_request_id = match funder_incoming {
         FunderIncoming::Init => {
-            handle_init(&m_state, &mut outgoing_channeler_config);
+            handle_init(&m_state, &mut m_ephemeral, &mut outgoing_channeler_config);
             None
         }
 
@@ -110,6 +115,7 @@ where
                         &mut outgoing_control,
                         &mut outgoing_channeler_config,
                         rng,
+                        max_move_token_retransmits,
                         &origin_public_key,
                         friend_message,
                     )
@@ -118,6 +124,18 @@ where
             };
             None
         }
+
+        FunderIncoming::TimerTick => {
+            handle_timer_tick(
+                &mut m_state,
+                &mut m_ephemeral,
+                &mut send_commands,
+                &mut outgoing_control,
+                rng,
+                pending_transaction_timeout_ticks,
+            );
+            None
+        }
     };
 
     Ok((
@@ -167,6 +185,8 @@ pub async fn funder_handle_message<'a, B, R>(
     max_node_relays: usize,
     max_operations_in_batch: usize,
     max_pending_user_requests: usize,
+    pending_transaction_timeout_ticks: usize,
+    max_move_token_retransmits: usize,
     funder_incoming: FunderIncoming<B>,
 ) -> Result<FunderHandlerOutput<B>, FunderHandlerError>
 where
@@ -184,6 +204,8 @@ where
             rng,
             max_node_relays,
             max_pending_user_requests,
+            pending_transaction_timeout_ticks,
+            max_move_token_retransmits,
             funder_incoming,
         )?;
 
@@ -197,11 +219,12 @@ where
     // Send all possible messages according to SendCommands
     // TODO: Maybe we should output outgoing_comms instead of friend_messages and
     // outgoing_channeler_config. When we merge the two, we might be out of order!
+    // Remote friends reject move tokens with more operations than the protocol allows:
     let (friend_messages, outgoing_channeler_config) = create_friend_messages(
         &mut m_state,
-        m_ephemeral.ephemeral(),
+        &mut m_ephemeral,
         &send_commands,
-        max_operations_in_batch,
+        min(max_operations_in_batch, MAX_OPERATIONS_IN_BATCH),
         identity_client,
         rng,
     )
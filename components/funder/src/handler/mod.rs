@@ -3,6 +3,7 @@ mod handle_control;
 mod handle_friend;
 mod handle_init;
 mod handle_liveness;
+mod handle_timer;
 mod handler;
 mod prepare;
 mod sender;
@@ -9,6 +9,7 @@ use signature::canonical::CanonicalSerialize;
 use crypto::rand::{CryptoRandom, RandGen};
 
 use proto::app_server::messages::{NamedRelayAddress, RelayAddress};
+use proto::consts::MAX_FRAME_LENGTH;
 use proto::crypto::{PublicKey, RandValue};
 use proto::funder::messages::{
     BalanceInfo, ChannelerUpdateFriend, CountersInfo, Currency, CurrencyBalanceInfo,
@@ -26,21 +27,31 @@ use crate::friend::{
 };
 use crate::token_channel::{SendMoveTokenOutput, SetDirection, TcMutation, TokenChannel};
 
-use crate::ephemeral::Ephemeral;
-use crate::handler::state_wrap::MutableFunderState;
+use crate::ephemeral::{Ephemeral, EphemeralMutation};
+use crate::handler::state_wrap::{MutableEphemeral, MutableFunderState};
 use crate::handler::types::{FriendSendCommands, SendCommands};
+use crate::pending_age::PendingAgeMutation;
 use crate::state::{FunderMutation, FunderState};
 
 pub type OutgoingMessage<B> = (PublicKey, FriendMessage<B>);
 
+/// Maximum size (canonical serialization) of the contents (operations, relays and active
+/// currencies) we put into a single move token. Operations that do not fit are left for a later
+/// move token.
+/// We leave a wide margin below MAX_FRAME_LENGTH for the rest of the move token fields and for
+/// the serialization and encryption overhead.
+const MAX_MOVE_TOKEN_CONTENT_LEN: usize = MAX_FRAME_LENGTH / 2;
+
 #[derive(Debug)]
 enum PendingQueueError {
     MaxOperationsReached,
+    MaxContentLenReached,
 }
 
 #[derive(Debug)]
 enum CollectOutgoingError {
     MaxOperationsReached,
+    MaxContentLenReached,
 }
 
 #[derive(Debug)]
@@ -61,6 +72,12 @@ struct PendingMoveToken<B> {
     opt_active_currencies: Option<Vec<Currency>>,
     token_wanted: bool,
     max_operations_in_batch: usize,
+    /// Estimated size of the move token contents, measured by canonical serialization.
+    content_len: usize,
+    /// Amount of operations kept for backwards operations. Requests may not use them.
+    reserved_operations: usize,
+    /// Content length kept for backwards operations. Requests may not use it.
+    reserved_content_len: usize,
     /// Can we send this move token with empty operations list
     /// and empty opt_local_address?
     may_send_empty: bool,
@@ -82,6 +99,9 @@ where
             opt_active_currencies: None,
             token_wanted: false,
             max_operations_in_batch,
+            content_len: 0,
+            reserved_operations: 0,
+            reserved_content_len: 0,
             may_send_empty,
         }
     }
@@ -95,16 +115,34 @@ where
         operation: &FriendTcOp,
         m_state: &mut MutableFunderState<B>,
     ) -> Result<(), PendingQueueError> {
+        // Requests may not use the room reserved for backwards operations:
+        let (reserved_operations, reserved_content_len) = match operation {
+            FriendTcOp::RequestSendFunds(_) => {
+                (self.reserved_operations, self.reserved_content_len)
+            }
+            _ => (0, 0),
+        };
+
         // Make sure we do not have too many operations queued:
         let num_operations: usize = self
             .pending_currencies
             .values()
             .map(|pending_currency| pending_currency.operations.len())
             .sum();
-        if num_operations >= self.max_operations_in_batch {
+        if num_operations.saturating_add(reserved_operations) >= self.max_operations_in_batch {
             return Err(PendingQueueError::MaxOperationsReached);
         }
 
+        // Make sure that the move token will not grow too large to be sent:
+        let mut added_len = operation.canonical_serialize().len();
+        if !self.pending_currencies.contains_key(currency) {
+            added_len += currency.canonical_serialize().len();
+        }
+        let new_content_len = self.content_len.saturating_add(added_len);
+        if new_content_len.saturating_add(reserved_content_len) > MAX_MOVE_TOKEN_CONTENT_LEN {
+            return Err(PendingQueueError::MaxContentLenReached);
+        }
+
         let friend = m_state
             .state()
             .friends
@@ -134,11 +172,18 @@ where
                 warn!("Request already exists: {:?}", operation);
                 Ok(vec![])
             }
+            Err(QueueOperationError::RequestDoesNotExist) => {
+                // This can happen if we have canceled a stale request, and only then received
+                // a late answer for it. We discard the operation.
+                warn!("Request does not exist: {:?}", operation);
+                return Ok(());
+            }
             Err(_) => unreachable!(),
         }?;
 
         // Add operation:
         pending_currency.operations.push(operation.clone());
+        self.content_len = new_content_len;
 
         /*
         // Apply mutations:
@@ -154,12 +199,53 @@ where
         Ok(())
     }
 
-    fn set_local_relays(&mut self, local_relays: Vec<RelayAddress<B>>) {
+    /// Keep room for the given backwards operations, so that they are not starved by requests.
+    /// At most half of the batch is reserved.
+    fn reserve_backwards_ops<'a>(
+        &mut self,
+        backwards_ops: impl Iterator<Item = &'a (Currency, BackwardsOp)>,
+    ) {
+        let max_reserved_operations = (self.max_operations_in_batch + 1) / 2;
+        let mut reserved_operations = 0usize;
+        let mut reserved_content_len = 0usize;
+        for (currency, backwards_op) in backwards_ops.take(max_reserved_operations) {
+            let operation = backwards_op_to_friend_tc_op(backwards_op.clone());
+            reserved_operations += 1;
+            reserved_content_len = reserved_content_len
+                .saturating_add(operation.canonical_serialize().len())
+                .saturating_add(currency.canonical_serialize().len());
+        }
+        self.reserved_operations = reserved_operations;
+        self.reserved_content_len = reserved_content_len.min(MAX_MOVE_TOKEN_CONTENT_LEN / 2);
+    }
+
+    /// Add the size of non operation contents (relays, active currencies) to the move token.
+    /// Fails if the contents do not fit into the move token.
+    fn add_content_len(&mut self, added_len: usize) -> Result<(), PendingQueueError> {
+        let new_content_len = self.content_len.saturating_add(added_len);
+        if new_content_len > MAX_MOVE_TOKEN_CONTENT_LEN {
+            return Err(PendingQueueError::MaxContentLenReached);
+        }
+        self.content_len = new_content_len;
+        Ok(())
+    }
+
+    fn set_local_relays(
+        &mut self,
+        local_relays: Vec<RelayAddress<B>>,
+    ) -> Result<(), PendingQueueError> {
+        self.add_content_len(local_relays.canonical_serialize().len())?;
         self.opt_local_relays = Some(local_relays);
+        Ok(())
     }
 
-    fn set_active_currencies(&mut self, active_currencies: Vec<Currency>) {
+    fn set_active_currencies(
+        &mut self,
+        active_currencies: Vec<Currency>,
+    ) -> Result<(), PendingQueueError> {
+        self.add_content_len(active_currencies.canonical_serialize().len())?;
         self.opt_active_currencies = Some(active_currencies);
+        Ok(())
     }
 }
 
@@ -269,11 +355,11 @@ pub async fn apply_local_reset<'a, B, R>(
         .collect();
 
     let token_info = TokenInfo {
-        mc: McInfo {
-            local_public_key: m_state.state().local_public_key.clone(),
-            remote_public_key: friend_public_key.clone(),
+        mc: McInfo::new(
+            m_state.state().local_public_key.clone(),
+            friend_public_key.clone(),
             balances,
-        },
+        ),
         counters: CountersInfo {
             inconsistency_counter: remote_reset_terms.inconsistency_counter,
             move_token_counter,
@@ -509,7 +595,8 @@ where
 {
     match pending_move_token.queue_operation(currency, operation, m_state) {
         Ok(()) => Ok(()),
-        Err(PendingQueueError::MaxOperationsReached) => {
+        Err(PendingQueueError::MaxOperationsReached)
+        | Err(PendingQueueError::MaxContentLenReached) => {
             pending_move_token.token_wanted = true;
             // We will send this message next time we have the token:
             Err(CollectOutgoingError::MaxOperationsReached)
@@ -532,6 +619,18 @@ fn backwards_op_to_friend_tc_op(backwards_op: BackwardsOp) -> FriendTcOp {
 /// Given a friend with an incoming move token state, create the largest possible move token to
 /// send to the remote side.
 /// Requests that fail to be processed are moved to the cancel queues of the relevant friends.
+///
+/// Pending operations are drained in the following priority order, until the batch is full
+/// (Either by the amount of operations, or by the size of the move token):
+/// 1. User requests (Payments originating from this node)
+/// 2. Pending requests (Requests forwarded from other friends)
+/// 3. Backwards operations (Response, Cancel, Collect)
+///
+/// While backwards operations are pending, up to half of the batch is reserved for them, so that a
+/// steady flow of requests can not delay settlement indefinitely.
+///
+/// Operations that do not fit into the move token remain queued, and the token is requested back
+/// (`token_wanted`), so that they will be sent in a later move token.
 fn collect_outgoing_move_token<'a, B>(
     m_state: &'a mut MutableFunderState<B>,
     outgoing_channeler_config: &'a mut Vec<ChannelerConfig<RelayAddress<B>>>,
@@ -545,9 +644,9 @@ where
     /*
     - Check if last sent local address is up to date.
     - Collect as many operations as possible (Not more than max ops per batch)
-        1. Responses (response, cancel, collect)
+        1. User pending requests
         2. Pending requests
-        3. User pending requests
+        3. Responses (response, cancel, collect)
     - When adding requests, check the following:
         - Valid from credits point of view.
     - If a request is not valid, queue a Cancel message to relevant friend.
@@ -604,7 +703,15 @@ where
 
     // Update friend.sent_local_relays accordingly:
     if let Some(new_sent_local_relays) = opt_new_sent_local_relays {
-        pending_move_token.set_local_relays(local_relays);
+        // Relays are the first contents of the move token. They do not fit only if there are
+        // too many of them:
+        if pending_move_token.set_local_relays(local_relays).is_err() {
+            error!(
+                "collect_outgoing_move_token(): Local relays do not fit into a move token: {:?}",
+                friend_public_key
+            );
+            return Err(CollectOutgoingError::MaxContentLenReached);
+        }
         let friend_mutation = FriendMutation::SetSentLocalRelays(new_sent_local_relays);
         let funder_mutation =
             FunderMutation::FriendMutation((friend_public_key.clone(), friend_mutation));
@@ -636,7 +743,16 @@ where
             .get_active_currencies()
             .calc_active()
             .is_subset(&wanted_local_currencies));
-        pending_move_token.set_active_currencies(wanted_local_currencies.into_iter().collect());
+        if pending_move_token
+            .set_active_currencies(wanted_local_currencies.into_iter().collect())
+            .is_err()
+        {
+            error!(
+                "collect_outgoing_move_token(): Active currencies do not fit into a move token: {:?}",
+                friend_public_key
+            );
+            return Err(CollectOutgoingError::MaxContentLenReached);
+        }
     }
 
     let friend = m_state.state().friends.get(friend_public_key).unwrap();
@@ -645,14 +761,19 @@ where
         ChannelStatus::Inconsistent(_) => unreachable!(),
     };
 
-    // Send pending responses (Response, Cancel, Collect)
-    // TODO: Possibly replace this clone with something more efficient later:
-    let mut pending_backwards_ops = channel_consistent.pending_backwards_ops.clone();
-    while let Some((currency, pending_backwards_op)) = pending_backwards_ops.pop_front() {
-        let pending_op = backwards_op_to_friend_tc_op(pending_backwards_op);
-        queue_operation(m_state, pending_move_token, &currency, &pending_op)?;
+    // Keep room for backwards operations:
+    pending_move_token.reserve_backwards_ops(channel_consistent.pending_backwards_ops.iter());
 
-        let friend_mutation = FriendMutation::PopFrontPendingBackwardsOp;
+    // Send as many pending user requests as possible:
+    let mut requests_full = false;
+    let mut pending_user_requests = channel_consistent.pending_user_requests.clone();
+    while let Some((currency, request_send_funds)) = pending_user_requests.pop_front() {
+        let pending_op = FriendTcOp::RequestSendFunds(request_send_funds);
+        if queue_operation(m_state, pending_move_token, &currency, &pending_op).is_err() {
+            requests_full = true;
+            break;
+        }
+        let friend_mutation = FriendMutation::PopFrontPendingUserRequest;
         let funder_mutation =
             FunderMutation::FriendMutation((friend_public_key.clone(), friend_mutation));
         m_state.mutate(funder_mutation);
@@ -668,8 +789,14 @@ where
     // TODO: Possibly replace this clone with something more efficient later:
     let mut pending_requests = channel_consistent.pending_requests.clone();
     while let Some((currency, pending_request)) = pending_requests.pop_front() {
+        // Do not let forwarded requests overtake user requests that did not fit:
+        if requests_full {
+            break;
+        }
         let pending_op = FriendTcOp::RequestSendFunds(pending_request);
-        queue_operation(m_state, pending_move_token, &currency, &pending_op)?;
+        if queue_operation(m_state, pending_move_token, &currency, &pending_op).is_err() {
+            break;
+        }
         let friend_mutation = FriendMutation::PopFrontPendingRequest;
         let funder_mutation =
             FunderMutation::FriendMutation((friend_public_key.clone(), friend_mutation));
@@ -682,12 +809,14 @@ where
         ChannelStatus::Inconsistent(_) => unreachable!(),
     };
 
-    // Send as many pending user requests as possible:
-    let mut pending_user_requests = channel_consistent.pending_user_requests.clone();
-    while let Some((currency, request_send_funds)) = pending_user_requests.pop_front() {
-        let pending_op = FriendTcOp::RequestSendFunds(request_send_funds);
+    // Send pending responses (Response, Cancel, Collect)
+    // TODO: Possibly replace this clone with something more efficient later:
+    let mut pending_backwards_ops = channel_consistent.pending_backwards_ops.clone();
+    while let Some((currency, pending_backwards_op)) = pending_backwards_ops.pop_front() {
+        let pending_op = backwards_op_to_friend_tc_op(pending_backwards_op);
         queue_operation(m_state, pending_move_token, &currency, &pending_op)?;
-        let friend_mutation = FriendMutation::PopFrontPendingUserRequest;
+
+        let friend_mutation = FriendMutation::PopFrontPendingBackwardsOp;
         let funder_mutation =
             FunderMutation::FriendMutation((friend_public_key.clone(), friend_mutation));
         m_state.mutate(funder_mutation);
@@ -726,6 +855,7 @@ where
 
 async fn send_move_token<'a, B, R>(
     m_state: &'a mut MutableFunderState<B>,
+    m_ephemeral: &'a mut MutableEphemeral,
     friend_public_key: PublicKey,
     pending_move_token: PendingMoveToken<B>,
     identity_client: &'a mut IdentityClient,
@@ -772,7 +902,21 @@ async fn send_move_token<'a, B, R>(
             currency,
             operations: pending_currency.operations,
         })
-        .collect();
+        .collect::<Vec<_>>();
+
+    // Requests we send start waiting for a response from the remote side:
+    let mut sent_pending_keys = Vec::new();
+    for currency_operations in &currencies_operations {
+        for operation in &currency_operations.operations {
+            if let FriendTcOp::RequestSendFunds(request_send_funds) = operation {
+                sent_pending_keys.push((
+                    friend_public_key.clone(),
+                    currency_operations.currency.clone(),
+                    request_send_funds.request_id.clone(),
+                ));
+            }
+        }
+    }
 
     // let (u_move_token, token_info) =
     let SendMoveTokenOutput {
@@ -796,6 +940,11 @@ async fn send_move_token<'a, B, R>(
         m_state.mutate(funder_mutation);
     }
 
+    for pending_key in sent_pending_keys {
+        let pending_age_mutation = PendingAgeMutation::SetAge((pending_key, 0));
+        m_ephemeral.mutate(EphemeralMutation::PendingAgeMutation(pending_age_mutation));
+    }
+
     // Apply final SetDirection mutation (Can not be created from inside of the TokenChannel
     // because a signature is required.
     let move_token = sign_move_token(unsigned_move_token, identity_client).await;
@@ -857,7 +1006,7 @@ fn init_cancel_pending_move_token<B>(
 /// Send all possible messages according to SendCommands
 pub async fn create_friend_messages<'a, B, R>(
     m_state: &'a mut MutableFunderState<B>,
-    ephemeral: &'a Ephemeral,
+    m_ephemeral: &'a mut MutableEphemeral,
     send_commands: &'a SendCommands,
     max_operations_in_batch: usize,
     identity_client: &'a mut IdentityClient,
@@ -877,7 +1026,11 @@ where
     // First iteration:
     let cancel_public_keys = HashSet::new();
     for (friend_public_key, friend_send_commands) in &send_commands.send_commands {
-        if !ephemeral.liveness.is_online(friend_public_key) {
+        if !m_ephemeral
+            .ephemeral()
+            .liveness
+            .is_online(friend_public_key)
+        {
             continue;
         }
         send_friend_iter1(
@@ -897,7 +1050,7 @@ where
     // Create PendingMoveToken-s for all the friends that were queued
     // new pending messages during `send_friend_iter1`:
     init_cancel_pending_move_token(
-        ephemeral,
+        m_ephemeral.ephemeral(),
         max_operations_in_batch,
         &cancel_public_keys,
         &mut pending_move_tokens,
@@ -905,15 +1058,22 @@ where
 
     // Second iteration (Attempt to queue Cancel-s created in the first iteration):
     for (friend_public_key, pending_move_token) in &mut pending_move_tokens {
-        assert!(ephemeral.liveness.is_online(&friend_public_key));
+        assert!(m_ephemeral
+            .ephemeral()
+            .liveness
+            .is_online(&friend_public_key));
         let _ = append_cancels_to_move_token(m_state, friend_public_key, pending_move_token);
     }
 
     // Send all pending move tokens:
     for (friend_public_key, pending_move_token) in pending_move_tokens.into_iter() {
-        assert!(ephemeral.liveness.is_online(&friend_public_key));
+        assert!(m_ephemeral
+            .ephemeral()
+            .liveness
+            .is_online(&friend_public_key));
         send_move_token(
             m_state,
+            m_ephemeral,
             friend_public_key,
             pending_move_token,
             identity_client,
@@ -925,3 +1085,368 @@ where
 
     (outgoing_messages, outgoing_channeler_config)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryFrom;
+
+    use proto::consts::{MAX_NET_ADDRESS_LENGTH, MAX_NODE_RELAYS, MAX_ROUTE_LEN};
+    use proto::crypto::{HashResult, HashedLock, InvoiceId, Signature, Uid};
+    use proto::funder::messages::{
+        AddFriend, CancelReason, FriendsRoute, MoveToken, RequestSendFundsOp,
+    };
+    use proto::net::messages::NetAddress;
+    use proto::proto_ser::ProtoSerialize;
+
+    use crate::mutual_credit::types::McMutation;
+    use crate::types::{create_cancel_send_funds, create_pending_transaction};
+
+    fn dummy_request(index: u8, friend_public_key: &PublicKey) -> RequestSendFundsOp {
+        RequestSendFundsOp {
+            request_id: Uid::from(&[index; Uid::len()]),
+            src_hashed_lock: HashedLock::from(&[index; HashedLock::len()]),
+            route: FriendsRoute {
+                public_keys: vec![friend_public_key.clone()],
+            },
+            dest_payment: 10,
+            total_dest_payment: 10,
+            invoice_id: InvoiceId::from(&[index; InvoiceId::len()]),
+            left_fees: 0,
+        }
+    }
+
+    #[test]
+    fn test_collect_outgoing_move_token_user_requests_first() {
+        let currency = Currency::try_from("FST".to_owned()).unwrap();
+        // The local public key is larger, so we begin holding the token:
+        let local_public_key = PublicKey::from(&[0xbb; PublicKey::len()]);
+        let friend_public_key = PublicKey::from(&[0xaa; PublicKey::len()]);
+
+        let mut m_state = MutableFunderState::new(FunderState::<u32>::new(
+            local_public_key.clone(),
+            Vec::new(),
+        ));
+        m_state.mutate(FunderMutation::AddFriend(AddFriend {
+            friend_public_key: friend_public_key.clone(),
+            relays: Vec::new(),
+            name: "friend".to_owned(),
+        }));
+
+        let friend_mutations = vec![
+            FriendMutation::UpdateCurrencyConfig((currency.clone(), CurrencyConfig::new())),
+            FriendMutation::TcMutation(TcMutation::SetLocalActiveCurrencies(
+                vec![currency.clone()],
+            )),
+            FriendMutation::TcMutation(TcMutation::SetRemoteActiveCurrencies(vec![
+                currency.clone()
+            ])),
+            FriendMutation::TcMutation(TcMutation::AddMutualCredit(currency.clone())),
+            // A request from the friend is pending, and is about to be canceled:
+            FriendMutation::TcMutation(TcMutation::McMutation((
+                currency.clone(),
+                McMutation::InsertRemotePendingTransaction(create_pending_transaction(
+                    &dummy_request(4, &local_public_key),
+                )),
+            ))),
+            FriendMutation::TcMutation(TcMutation::McMutation((
+                currency.clone(),
+                McMutation::SetRemotePendingDebt(10),
+            ))),
+            // A forwarded request was queued before the user request:
+            FriendMutation::PushBackPendingRequest((
+                currency.clone(),
+                dummy_request(1, &friend_public_key),
+            )),
+            FriendMutation::PushBackPendingUserRequest((
+                currency.clone(),
+                dummy_request(2, &friend_public_key),
+            )),
+            FriendMutation::PushBackPendingUserRequest((
+                currency.clone(),
+                dummy_request(3, &friend_public_key),
+            )),
+            // The cancel was queued before all requests were sent:
+            FriendMutation::PushBackPendingBackwardsOp((
+                currency.clone(),
+                BackwardsOp::Cancel(create_cancel_send_funds(
+                    Uid::from(&[4; Uid::len()]),
+                    CancelReason::NoRoute,
+                )),
+            )),
+        ];
+        for friend_mutation in friend_mutations {
+            m_state.mutate(FunderMutation::FriendMutation((
+                friend_public_key.clone(),
+                friend_mutation,
+            )));
+        }
+
+        // Only three operations fit into a single move token:
+        let mut pending_move_token = PendingMoveToken::new(friend_public_key.clone(), 3, false);
+        let mut outgoing_channeler_config = Vec::new();
+        let res = collect_outgoing_move_token(
+            &mut m_state,
+            &mut outgoing_channeler_config,
+            &friend_public_key,
+            &mut pending_move_token,
+            false,
+        );
+        res.unwrap();
+        assert!(pending_move_token.token_wanted);
+
+        // The user requests were included first. The last place is reserved for the cancel:
+        let operations = &pending_move_token.pending_currencies[&currency].operations;
+        assert_eq!(
+            operations,
+            &vec![
+                FriendTcOp::RequestSendFunds(dummy_request(2, &friend_public_key)),
+                FriendTcOp::RequestSendFunds(dummy_request(3, &friend_public_key)),
+                FriendTcOp::CancelSendFunds(create_cancel_send_funds(
+                    Uid::from(&[4; Uid::len()]),
+                    CancelReason::NoRoute,
+                )),
+            ]
+        );
+
+        // The forwarded request remains queued for the next move token:
+        let friend = m_state.state().friends.get(&friend_public_key).unwrap();
+        match &friend.channel_status {
+            ChannelStatus::Consistent(channel_consistent) => {
+                assert!(channel_consistent.pending_user_requests.is_empty());
+                assert_eq!(channel_consistent.pending_requests.len(), 1);
+                assert!(channel_consistent.pending_backwards_ops.is_empty());
+            }
+            ChannelStatus::Inconsistent(_) => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_collect_outgoing_move_token_backwards_ops_not_starved() {
+        let currency = Currency::try_from("FST".to_owned()).unwrap();
+        // The local public key is larger, so we begin holding the token:
+        let local_public_key = PublicKey::from(&[0xbb; PublicKey::len()]);
+        let friend_public_key = PublicKey::from(&[0xaa; PublicKey::len()]);
+
+        let mut m_state = MutableFunderState::new(FunderState::<u32>::new(
+            local_public_key.clone(),
+            Vec::new(),
+        ));
+        m_state.mutate(FunderMutation::AddFriend(AddFriend {
+            friend_public_key: friend_public_key.clone(),
+            relays: Vec::new(),
+            name: "friend".to_owned(),
+        }));
+
+        let mut friend_mutations = vec![
+            FriendMutation::UpdateCurrencyConfig((currency.clone(), CurrencyConfig::new())),
+            FriendMutation::TcMutation(TcMutation::SetLocalActiveCurrencies(
+                vec![currency.clone()],
+            )),
+            FriendMutation::TcMutation(TcMutation::SetRemoteActiveCurrencies(vec![
+                currency.clone()
+            ])),
+            FriendMutation::TcMutation(TcMutation::AddMutualCredit(currency.clone())),
+            FriendMutation::TcMutation(TcMutation::McMutation((
+                currency.clone(),
+                McMutation::SetRemotePendingDebt(30),
+            ))),
+        ];
+
+        // Three requests from the friend are pending, and are about to be canceled:
+        for index in 0x80..0x83 {
+            friend_mutations.push(FriendMutation::TcMutation(TcMutation::McMutation((
+                currency.clone(),
+                McMutation::InsertRemotePendingTransaction(create_pending_transaction(
+                    &dummy_request(index, &local_public_key),
+                )),
+            ))));
+            friend_mutations.push(FriendMutation::PushBackPendingBackwardsOp((
+                currency.clone(),
+                BackwardsOp::Cancel(create_cancel_send_funds(
+                    Uid::from(&[index; Uid::len()]),
+                    CancelReason::NoRoute,
+                )),
+            )));
+        }
+
+        // The request queues hold much more than a single batch:
+        for index in 0..0x40 {
+            friend_mutations.push(FriendMutation::PushBackPendingUserRequest((
+                currency.clone(),
+                dummy_request(index, &friend_public_key),
+            )));
+            friend_mutations.push(FriendMutation::PushBackPendingRequest((
+                currency.clone(),
+                dummy_request(0x40 + index, &friend_public_key),
+            )));
+        }
+
+        for friend_mutation in friend_mutations {
+            m_state.mutate(FunderMutation::FriendMutation((
+                friend_public_key.clone(),
+                friend_mutation,
+            )));
+        }
+
+        let mut pending_move_token = PendingMoveToken::new(friend_public_key.clone(), 4, false);
+        let mut outgoing_channeler_config = Vec::new();
+        collect_outgoing_move_token(
+            &mut m_state,
+            &mut outgoing_channeler_config,
+            &friend_public_key,
+            &mut pending_move_token,
+            false,
+        )
+        .unwrap();
+        assert!(pending_move_token.token_wanted);
+
+        // Half of the batch is kept for the cancels, despite the full request queues:
+        let operations = &pending_move_token.pending_currencies[&currency].operations;
+        assert_eq!(
+            operations,
+            &vec![
+                FriendTcOp::RequestSendFunds(dummy_request(0, &friend_public_key)),
+                FriendTcOp::RequestSendFunds(dummy_request(1, &friend_public_key)),
+                FriendTcOp::CancelSendFunds(create_cancel_send_funds(
+                    Uid::from(&[0x80; Uid::len()]),
+                    CancelReason::NoRoute,
+                )),
+                FriendTcOp::CancelSendFunds(create_cancel_send_funds(
+                    Uid::from(&[0x81; Uid::len()]),
+                    CancelReason::NoRoute,
+                )),
+            ]
+        );
+
+        let friend = m_state.state().friends.get(&friend_public_key).unwrap();
+        match &friend.channel_status {
+            ChannelStatus::Consistent(channel_consistent) => {
+                assert_eq!(channel_consistent.pending_user_requests.len(), 0x40 - 2);
+                assert_eq!(channel_consistent.pending_requests.len(), 0x40);
+                assert_eq!(channel_consistent.pending_backwards_ops.len(), 1);
+            }
+            ChannelStatus::Inconsistent(_) => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_collect_outgoing_move_token_max_content_len() {
+        let currency = Currency::try_from("FST".to_owned()).unwrap();
+        // The local public key is larger, so we begin holding the token:
+        let local_public_key = PublicKey::from(&[0xbb; PublicKey::len()]);
+        let friend_public_key = PublicKey::from(&[0xaa; PublicKey::len()]);
+
+        // The maximum amount of local relays, all with long addresses.
+        // The relays are sent in the move token together with the requests:
+        let local_relays: Vec<_> = (0..MAX_NODE_RELAYS)
+            .map(|i| NamedRelayAddress {
+                public_key: PublicKey::from(&[i as u8; PublicKey::len()]),
+                address: NetAddress::try_from("a".repeat(MAX_NET_ADDRESS_LENGTH)).unwrap(),
+                name: format!("relay{}", i),
+            })
+            .collect();
+
+        let mut m_state = MutableFunderState::new(FunderState::<NetAddress>::new(
+            local_public_key.clone(),
+            local_relays,
+        ));
+        m_state.mutate(FunderMutation::AddFriend(AddFriend {
+            friend_public_key: friend_public_key.clone(),
+            relays: Vec::new(),
+            name: "friend".to_owned(),
+        }));
+
+        let mut friend_mutations = vec![
+            FriendMutation::UpdateCurrencyConfig((currency.clone(), CurrencyConfig::new())),
+            FriendMutation::TcMutation(TcMutation::SetLocalActiveCurrencies(
+                vec![currency.clone()],
+            )),
+            FriendMutation::TcMutation(TcMutation::SetRemoteActiveCurrencies(vec![
+                currency.clone()
+            ])),
+            FriendMutation::TcMutation(TcMutation::AddMutualCredit(currency.clone())),
+        ];
+
+        // Requests with the longest possible route.
+        // Together, all the requests are larger than a single frame:
+        let route_public_keys: Vec<_> = (0..MAX_ROUTE_LEN - 1)
+            .map(|i| PublicKey::from(&[i as u8; PublicKey::len()]))
+            .collect();
+        let num_requests = 0x400;
+        for i in 0..num_requests {
+            let mut request = dummy_request(0, &friend_public_key);
+            let mut request_id = [0u8; Uid::len()];
+            request_id[0] = (i / 0x100) as u8;
+            request_id[1] = (i % 0x100) as u8;
+            request.request_id = Uid::from(&request_id);
+            request.route = FriendsRoute {
+                public_keys: route_public_keys.clone(),
+            };
+            friend_mutations.push(FriendMutation::PushBackPendingUserRequest((
+                currency.clone(),
+                request,
+            )));
+        }
+        for friend_mutation in friend_mutations {
+            m_state.mutate(FunderMutation::FriendMutation((
+                friend_public_key.clone(),
+                friend_mutation,
+            )));
+        }
+
+        // The amount of operations is not a limiting factor here:
+        let mut pending_move_token =
+            PendingMoveToken::new(friend_public_key.clone(), num_requests, false);
+        let mut outgoing_channeler_config = Vec::new();
+        let res = collect_outgoing_move_token(
+            &mut m_state,
+            &mut outgoing_channeler_config,
+            &friend_public_key,
+            &mut pending_move_token,
+            false,
+        );
+        res.unwrap();
+        assert!(pending_move_token.token_wanted);
+        assert!(pending_move_token.opt_local_relays.is_some());
+
+        // The batch was trimmed:
+        let operations = pending_move_token.pending_currencies[&currency]
+            .operations
+            .clone();
+        assert!(!operations.is_empty());
+        assert!(operations.len() < num_requests);
+
+        // The whole message fits into a single frame:
+        let move_token = MoveToken {
+            old_token: Signature::from(&[0; Signature::len()]),
+            currencies_operations: vec![CurrencyOperations {
+                currency: currency.clone(),
+                operations: operations.clone(),
+            }],
+            opt_local_relays: pending_move_token.opt_local_relays,
+            opt_active_currencies: pending_move_token.opt_active_currencies,
+            info_hash: HashResult::from(&[0; HashResult::len()]),
+            rand_nonce: RandValue::from(&[0; RandValue::len()]),
+            new_token: Signature::from(&[0; Signature::len()]),
+        };
+        let friend_message = FriendMessage::MoveTokenRequest(MoveTokenRequest {
+            move_token,
+            token_wanted: true,
+        });
+        assert!(friend_message.proto_serialize().len() < MAX_FRAME_LENGTH);
+
+        // The rest of the requests remain queued for the next move token:
+        let friend = m_state.state().friends.get(&friend_public_key).unwrap();
+        match &friend.channel_status {
+            ChannelStatus::Consistent(channel_consistent) => {
+                assert_eq!(
+                    channel_consistent.pending_user_requests.len() + operations.len(),
+                    num_requests
+                );
+            }
+            ChannelStatus::Inconsistent(_) => unreachable!(),
+        }
+    }
+}
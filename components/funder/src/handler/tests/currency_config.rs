@@ -0,0 +1,153 @@
+use super::utils::{apply_funder_incoming, dummy_relay_address};
+
+use std::convert::TryFrom;
+
+use futures::executor::{LocalPool, ThreadPool};
+use futures::task::SpawnExt;
+use futures::{future, FutureExt};
+
+use identity::{create_identity, IdentityClient};
+
+use crypto::identity::SoftwareEd25519Identity;
+use crypto::rand::{RandGen, RngContainer};
+use crypto::test_utils::DummyRandom;
+
+use proto::crypto::{PrivateKey, PublicKey, Uid};
+use proto::funder::messages::{
+    AddFriend, Currency, FunderControl, FunderIncomingControl, Rate, RequestsStatus,
+    SetFriendCurrencyMaxDebt, SetFriendCurrencyRate, SetFriendCurrencyRequestsStatus,
+};
+
+use crate::ephemeral::Ephemeral;
+use crate::friend::CurrencyConfig;
+use crate::state::FunderState;
+use crate::types::FunderIncoming;
+
+async fn apply_control(
+    funder_control: FunderControl<u32>,
+    state: &mut FunderState<u32>,
+    ephemeral: &mut Ephemeral,
+    rng: &mut RngContainer<DummyRandom>,
+    identity_client: &mut IdentityClient,
+) {
+    let incoming_control_message =
+        FunderIncomingControl::new(Uid::from(&[11; Uid::len()]), funder_control);
+    let funder_incoming = FunderIncoming::Control(incoming_control_message);
+    Box::pin(apply_funder_incoming(
+        funder_incoming,
+        state,
+        ephemeral,
+        rng,
+        identity_client,
+    ))
+    .await
+    .unwrap();
+}
+
+fn set_requests_status(
+    friend_public_key: &PublicKey,
+    currency: &Currency,
+    status: RequestsStatus,
+) -> FunderControl<u32> {
+    FunderControl::SetFriendCurrencyRequestsStatus(SetFriendCurrencyRequestsStatus {
+        friend_public_key: friend_public_key.clone(),
+        currency: currency.clone(),
+        status,
+    })
+}
+
+async fn task_handler_currency_config(mut identity_client: IdentityClient) {
+    let pk = identity_client.request_public_key().await.unwrap();
+
+    let mut state = FunderState::<u32>::new(pk, vec![]);
+    let mut ephemeral = Ephemeral::new();
+    let mut rng = RngContainer::new(DummyRandom::new(&[3u8]));
+
+    let friend_public_key = PublicKey::from(&[0xbb; PublicKey::len()]);
+    let currency = Currency::try_from("FST".to_owned()).unwrap();
+    let rate = Rate { mul: 5, add: 1 };
+
+    let funder_controls = vec![
+        FunderControl::AddFriend(AddFriend {
+            friend_public_key: friend_public_key.clone(),
+            relays: vec![dummy_relay_address(1)],
+            name: String::from("friend"),
+        }),
+        FunderControl::SetFriendCurrencyRate(SetFriendCurrencyRate {
+            friend_public_key: friend_public_key.clone(),
+            currency: currency.clone(),
+            rate: rate.clone(),
+        }),
+        FunderControl::SetFriendCurrencyMaxDebt(SetFriendCurrencyMaxDebt {
+            friend_public_key: friend_public_key.clone(),
+            currency: currency.clone(),
+            remote_max_debt: 100,
+        }),
+        set_requests_status(&friend_public_key, &currency, RequestsStatus::Open),
+    ];
+    for funder_control in funder_controls {
+        apply_control(
+            funder_control,
+            &mut state,
+            &mut ephemeral,
+            &mut rng,
+            &mut identity_client,
+        )
+        .await;
+    }
+
+    let expected_config = |is_open| CurrencyConfig {
+        rate: rate.clone(),
+        remote_max_debt: 100,
+        is_open,
+    };
+    let get_config = |state: &FunderState<u32>| {
+        state
+            .friends
+            .get(&friend_public_key)
+            .unwrap()
+            .currency_configs
+            .get(&currency)
+            .unwrap()
+            .clone()
+    };
+    assert_eq!(get_config(&state), expected_config(true));
+
+    // Closing the currency keeps the rate and the max debt:
+    apply_control(
+        set_requests_status(&friend_public_key, &currency, RequestsStatus::Closed),
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        &mut identity_client,
+    )
+    .await;
+    assert_eq!(get_config(&state), expected_config(false));
+
+    // Reopening the currency restores the previous configuration:
+    apply_control(
+        set_requests_status(&friend_public_key, &currency, RequestsStatus::Open),
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        &mut identity_client,
+    )
+    .await;
+    assert_eq!(get_config(&state), expected_config(true));
+}
+
+#[test]
+fn test_handler_currency_config() {
+    let thread_pool = ThreadPool::new().unwrap();
+
+    let rng = DummyRandom::new(&[1u8]);
+    let pkcs8 = PrivateKey::rand_gen(&rng);
+    let identity = SoftwareEd25519Identity::from_private_key(&pkcs8).unwrap();
+    let (requests_sender, identity_server) = create_identity(identity);
+    let identity_client = IdentityClient::new(requests_sender);
+    thread_pool
+        .spawn(identity_server.then(|_| future::ready(())))
+        .unwrap();
+
+    LocalPool::new().run_until(task_handler_currency_config(identity_client));
+}
@@ -0,0 +1,218 @@
+use super::utils::{apply_funder_incoming, dummy_named_relay_address, dummy_relay_address};
+
+use futures::executor::{LocalPool, ThreadPool};
+use futures::task::SpawnExt;
+use futures::{future, FutureExt};
+
+use identity::{create_identity, IdentityClient};
+
+use crypto::identity::SoftwareEd25519Identity;
+use crypto::rand::{RandGen, RngContainer};
+use crypto::test_utils::DummyRandom;
+
+use proto::app_server::messages::RelayAddress;
+use proto::consts::MAX_FRIEND_RELAYS;
+use proto::crypto::{PrivateKey, PublicKey, Uid};
+use proto::funder::messages::{
+    AddFriend, FriendRelaysResult, FunderControl, FunderIncomingControl, FunderOutgoingControl,
+    SetFriendRelays,
+};
+
+use crate::ephemeral::Ephemeral;
+use crate::state::FunderState;
+use crate::types::FunderIncoming;
+
+/// Find the result reported to the user for a friend relays request
+fn friend_relays_result<B: Clone>(
+    outgoing_control: &[FunderOutgoingControl<B>],
+) -> FriendRelaysResult {
+    outgoing_control
+        .iter()
+        .find_map(|funder_outgoing_control| match funder_outgoing_control {
+            FunderOutgoingControl::ResponseFriendRelays(response_friend_relays) => {
+                Some(response_friend_relays.result.clone())
+            }
+            _ => None,
+        })
+        .unwrap()
+}
+
+async fn task_handler_friend_relays(mut identity_client: IdentityClient) {
+    let pk = identity_client.request_public_key().await.unwrap();
+    let friend_pk = PublicKey::from(&[0xbb; PublicKey::len()]);
+
+    let relays = vec![dummy_named_relay_address(1)];
+    let mut state = FunderState::<u32>::new(pk.clone(), relays);
+    let mut ephemeral = Ephemeral::new();
+
+    let mut rng = RngContainer::new(DummyRandom::new(&[3u8]));
+
+    // Initialize:
+    let funder_incoming = FunderIncoming::Init;
+    Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        &mut identity_client,
+    ))
+    .await
+    .unwrap();
+
+    // Attempt to add a friend with too many relays:
+    let add_friend = AddFriend {
+        friend_public_key: friend_pk.clone(),
+        relays: (0..=MAX_FRIEND_RELAYS as u8)
+            .map(dummy_relay_address)
+            .collect(),
+        name: String::from("friend"),
+    };
+    let incoming_control_message = FunderIncomingControl::new(
+        Uid::from(&[11; Uid::len()]),
+        FunderControl::AddFriend(add_friend),
+    );
+    let funder_incoming = FunderIncoming::Control(incoming_control_message);
+    let (_outgoing_comms, outgoing_control) = Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        &mut identity_client,
+    ))
+    .await
+    .unwrap();
+    assert_eq!(
+        friend_relays_result(&outgoing_control),
+        FriendRelaysResult::TooManyRelays
+    );
+
+    // Friend should not have been added:
+    assert!(state.friends.is_empty());
+
+    // Add a friend with duplicate relay public keys.
+    // Duplicates are counted only once against the limit:
+    let mut relays: Vec<RelayAddress<u32>> = (0..MAX_FRIEND_RELAYS as u8)
+        .map(dummy_relay_address)
+        .collect();
+    relays.push(RelayAddress {
+        public_key: PublicKey::from(&[3; PublicKey::len()]),
+        address: 0x100,
+    });
+    let add_friend = AddFriend {
+        friend_public_key: friend_pk.clone(),
+        relays,
+        name: String::from("friend"),
+    };
+    let incoming_control_message = FunderIncomingControl::new(
+        Uid::from(&[12; Uid::len()]),
+        FunderControl::AddFriend(add_friend),
+    );
+    let funder_incoming = FunderIncoming::Control(incoming_control_message);
+    let (_outgoing_comms, outgoing_control) = Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        &mut identity_client,
+    ))
+    .await
+    .unwrap();
+    assert_eq!(
+        friend_relays_result(&outgoing_control),
+        FriendRelaysResult::Success
+    );
+
+    // The first occurrence of every relay public key is kept:
+    let friend = state.friends.get(&friend_pk).unwrap();
+    assert_eq!(
+        friend.remote_relays,
+        (0..MAX_FRIEND_RELAYS as u8)
+            .map(dummy_relay_address)
+            .collect::<Vec<_>>()
+    );
+
+    // Attempt to set too many relays for the friend:
+    let set_friend_relays = SetFriendRelays {
+        friend_public_key: friend_pk.clone(),
+        relays: (0..=MAX_FRIEND_RELAYS as u8)
+            .map(|i| dummy_relay_address(i + 20))
+            .collect(),
+    };
+    let incoming_control_message = FunderIncomingControl::new(
+        Uid::from(&[13; Uid::len()]),
+        FunderControl::SetFriendRelays(set_friend_relays),
+    );
+    let funder_incoming = FunderIncoming::Control(incoming_control_message);
+    let (_outgoing_comms, outgoing_control) = Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        &mut identity_client,
+    ))
+    .await
+    .unwrap();
+    assert_eq!(
+        friend_relays_result(&outgoing_control),
+        FriendRelaysResult::TooManyRelays
+    );
+
+    // Friend relays should not change:
+    let friend = state.friends.get(&friend_pk).unwrap();
+    assert_eq!(
+        friend.remote_relays,
+        (0..MAX_FRIEND_RELAYS as u8)
+            .map(dummy_relay_address)
+            .collect::<Vec<_>>()
+    );
+
+    // Set friend relays with duplicates:
+    let set_friend_relays = SetFriendRelays {
+        friend_public_key: friend_pk.clone(),
+        relays: vec![
+            dummy_relay_address(5),
+            dummy_relay_address(6),
+            dummy_relay_address(5),
+        ],
+    };
+    let incoming_control_message = FunderIncomingControl::new(
+        Uid::from(&[14; Uid::len()]),
+        FunderControl::SetFriendRelays(set_friend_relays),
+    );
+    let funder_incoming = FunderIncoming::Control(incoming_control_message);
+    let (_outgoing_comms, outgoing_control) = Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        &mut identity_client,
+    ))
+    .await
+    .unwrap();
+    assert_eq!(
+        friend_relays_result(&outgoing_control),
+        FriendRelaysResult::Success
+    );
+
+    let friend = state.friends.get(&friend_pk).unwrap();
+    assert_eq!(
+        friend.remote_relays,
+        vec![dummy_relay_address(5), dummy_relay_address(6)]
+    );
+}
+
+#[test]
+fn test_handler_friend_relays() {
+    let thread_pool = ThreadPool::new().unwrap();
+
+    let rng = DummyRandom::new(&[1u8]);
+    let pkcs8 = PrivateKey::rand_gen(&rng);
+    let identity = SoftwareEd25519Identity::from_private_key(&pkcs8).unwrap();
+    let (requests_sender, identity_server) = create_identity(identity);
+    let identity_client = IdentityClient::new(requests_sender);
+    thread_pool
+        .spawn(identity_server.then(|_| future::ready(())))
+        .unwrap();
+
+    LocalPool::new().run_until(task_handler_friend_relays(identity_client));
+}
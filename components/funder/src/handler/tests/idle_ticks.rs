@@ -0,0 +1,251 @@
+use std::cmp::Ordering;
+
+use super::utils::{apply_funder_incoming, dummy_named_relay_address, dummy_relay_address};
+
+use futures::executor::{LocalPool, ThreadPool};
+use futures::task::SpawnExt;
+use futures::{future, FutureExt};
+
+use identity::{create_identity, IdentityClient};
+
+use crypto::identity::{compare_public_key, SoftwareEd25519Identity};
+use crypto::rand::{RandGen, RngContainer};
+use crypto::test_utils::DummyRandom;
+
+use proto::crypto::{PrivateKey, PublicKey, Uid};
+use proto::funder::messages::{
+    AddFriend, FriendMessage, FriendStatus, FunderControl, FunderIncomingControl, SetFriendStatus,
+};
+
+use crate::ephemeral::Ephemeral;
+use crate::report::create_report;
+use crate::state::FunderState;
+use crate::types::{
+    FunderIncoming, FunderIncomingComm, FunderOutgoingComm, IncomingLivenessMessage,
+};
+
+/// Apply an incoming message, and return the single friend message sent in response.
+async fn apply_get_friend_message(
+    funder_incoming: FunderIncoming<u32>,
+    state: &mut FunderState<u32>,
+    ephemeral: &mut Ephemeral,
+    rng: &mut RngContainer<DummyRandom>,
+    identity_client: &mut IdentityClient,
+) -> FriendMessage<u32> {
+    let (outgoing_comms, _outgoing_control) = Box::pin(apply_funder_incoming(
+        funder_incoming,
+        state,
+        ephemeral,
+        rng,
+        identity_client,
+    ))
+    .await
+    .unwrap();
+
+    let mut friend_messages = outgoing_comms
+        .into_iter()
+        .filter_map(|outgoing_comm| match outgoing_comm {
+            FunderOutgoingComm::FriendMessage((_pk, friend_message)) => Some(friend_message),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(friend_messages.len(), 1);
+    friend_messages.pop().unwrap()
+}
+
+/// Add a friend and enable it
+async fn add_enabled_friend(
+    friend_public_key: &PublicKey,
+    relay_index: u8,
+    state: &mut FunderState<u32>,
+    ephemeral: &mut Ephemeral,
+    rng: &mut RngContainer<DummyRandom>,
+    identity_client: &mut IdentityClient,
+) {
+    let funder_controls = vec![
+        FunderControl::AddFriend(AddFriend {
+            friend_public_key: friend_public_key.clone(),
+            relays: vec![dummy_relay_address(relay_index)],
+            name: String::from("friend"),
+        }),
+        FunderControl::SetFriendStatus(SetFriendStatus {
+            friend_public_key: friend_public_key.clone(),
+            status: FriendStatus::Enabled,
+        }),
+    ];
+    for funder_control in funder_controls {
+        let incoming_control_message =
+            FunderIncomingControl::new(Uid::from(&[11; Uid::len()]), funder_control);
+        let funder_incoming = FunderIncoming::Control(incoming_control_message);
+        Box::pin(apply_funder_incoming(
+            funder_incoming,
+            state,
+            ephemeral,
+            rng,
+            identity_client,
+        ))
+        .await
+        .unwrap();
+    }
+}
+
+async fn task_handler_idle_ticks<'a>(
+    identity_client1: &'a mut IdentityClient,
+    identity_client2: &'a mut IdentityClient,
+) {
+    // Sort the identities. identity_client1 will be the first sender:
+    let pk1 = identity_client1.request_public_key().await.unwrap();
+    let pk2 = identity_client2.request_public_key().await.unwrap();
+    let (identity_client1, pk1, identity_client2, pk2) =
+        if compare_public_key(&pk1, &pk2) == Ordering::Less {
+            (identity_client1, pk1, identity_client2, pk2)
+        } else {
+            (identity_client2, pk2, identity_client1, pk1)
+        };
+
+    let mut state1 = FunderState::<u32>::new(pk1.clone(), vec![dummy_named_relay_address(1)]);
+    let mut ephemeral1 = Ephemeral::new();
+    let mut state2 = FunderState::<u32>::new(pk2.clone(), vec![dummy_named_relay_address(2)]);
+    let mut ephemeral2 = Ephemeral::new();
+
+    let mut rng = RngContainer::new(DummyRandom::new(&[3u8]));
+
+    add_enabled_friend(
+        &pk2,
+        2,
+        &mut state1,
+        &mut ephemeral1,
+        &mut rng,
+        identity_client1,
+    )
+    .await;
+    add_enabled_friend(
+        &pk1,
+        1,
+        &mut state2,
+        &mut ephemeral2,
+        &mut rng,
+        identity_client2,
+    )
+    .await;
+
+    let get_idle_ticks = |state: &FunderState<u32>, ephemeral: &Ephemeral, pk: &PublicKey| {
+        let report = create_report(state, ephemeral);
+        report.friends.get(pk).unwrap().idle_ticks
+    };
+    assert_eq!(get_idle_ticks(&state1, &ephemeral1, &pk2), 0);
+
+    // Time passes without any traffic from Node2:
+    for _ in 0..3 {
+        let (_outgoing_comms, outgoing_control) = Box::pin(apply_funder_incoming(
+            FunderIncoming::TimerTick,
+            &mut state1,
+            &mut ephemeral1,
+            &mut rng,
+            identity_client1,
+        ))
+        .await
+        .unwrap();
+        // Ticks do not create report mutations:
+        assert!(outgoing_control.is_empty());
+    }
+    assert_eq!(get_idle_ticks(&state1, &ephemeral1, &pk2), 3);
+
+    // Node1: Notify that Node2 is alive. Node1 sends a move token to Node2:
+    let funder_incoming = FunderIncoming::Comm(FunderIncomingComm::Liveness(
+        IncomingLivenessMessage::Online(pk2.clone()),
+    ));
+    let friend_message = apply_get_friend_message(
+        funder_incoming,
+        &mut state1,
+        &mut ephemeral1,
+        &mut rng,
+        identity_client1,
+    )
+    .await;
+
+    // Sending a move token does not count as activity of the remote friend:
+    assert_eq!(get_idle_ticks(&state1, &ephemeral1, &pk2), 3);
+
+    // Node2: Notify that Node1 is alive:
+    let funder_incoming = FunderIncoming::Comm(FunderIncomingComm::Liveness(
+        IncomingLivenessMessage::Online(pk1.clone()),
+    ));
+    Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state2,
+        &mut ephemeral2,
+        &mut rng,
+        identity_client2,
+    ))
+    .await
+    .unwrap();
+
+    // Node2: Receive the move token from Node1, and send a move token back:
+    let funder_incoming =
+        FunderIncoming::Comm(FunderIncomingComm::Friend((pk1.clone(), friend_message)));
+    let friend_message = apply_get_friend_message(
+        funder_incoming,
+        &mut state2,
+        &mut ephemeral2,
+        &mut rng,
+        identity_client2,
+    )
+    .await;
+
+    // Node1: Receive the move token from Node2:
+    let funder_incoming =
+        FunderIncoming::Comm(FunderIncomingComm::Friend((pk2.clone(), friend_message)));
+    Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state1,
+        &mut ephemeral1,
+        &mut rng,
+        identity_client1,
+    ))
+    .await
+    .unwrap();
+
+    // The idle counter was reset by the exchange:
+    assert_eq!(get_idle_ticks(&state1, &ephemeral1, &pk2), 0);
+
+    // And starts growing again:
+    Box::pin(apply_funder_incoming(
+        FunderIncoming::TimerTick,
+        &mut state1,
+        &mut ephemeral1,
+        &mut rng,
+        identity_client1,
+    ))
+    .await
+    .unwrap();
+    assert_eq!(get_idle_ticks(&state1, &ephemeral1, &pk2), 1);
+}
+
+#[test]
+fn test_handler_idle_ticks() {
+    let thread_pool = ThreadPool::new().unwrap();
+
+    let rng1 = DummyRandom::new(&[1u8]);
+    let pkcs8 = PrivateKey::rand_gen(&rng1);
+    let identity1 = SoftwareEd25519Identity::from_private_key(&pkcs8).unwrap();
+    let (requests_sender1, identity_server1) = create_identity(identity1);
+    let mut identity_client1 = IdentityClient::new(requests_sender1);
+    thread_pool
+        .spawn(identity_server1.then(|_| future::ready(())))
+        .unwrap();
+
+    let rng2 = DummyRandom::new(&[2u8]);
+    let pkcs8 = PrivateKey::rand_gen(&rng2);
+    let identity2 = SoftwareEd25519Identity::from_private_key(&pkcs8).unwrap();
+    let (requests_sender2, identity_server2) = create_identity(identity2);
+    let mut identity_client2 = IdentityClient::new(requests_sender2);
+    thread_pool
+        .spawn(identity_server2.then(|_| future::ready(())))
+        .unwrap();
+
+    LocalPool::new().run_until(task_handler_idle_ticks(
+        &mut identity_client1,
+        &mut identity_client2,
+    ));
+}
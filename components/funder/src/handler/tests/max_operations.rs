@@ -0,0 +1,137 @@
+use std::convert::TryFrom;
+
+use super::utils::{apply_funder_incoming, dummy_named_relay_address, dummy_relay_address};
+
+use futures::executor::{LocalPool, ThreadPool};
+use futures::task::SpawnExt;
+use futures::{future, FutureExt};
+
+use identity::{create_identity, IdentityClient};
+
+use crypto::identity::SoftwareEd25519Identity;
+use crypto::rand::{RandGen, RngContainer};
+use crypto::test_utils::DummyRandom;
+
+use proto::crypto::{HashResult, PrivateKey, PublicKey, RandValue, Signature, Uid};
+use proto::funder::messages::{
+    AddFriend, CancelSendFundsOp, Currency, CurrencyOperations, FriendMessage, FriendTcOp,
+    FunderControl, FunderIncomingControl, MoveToken, MoveTokenRequest,
+};
+
+use crate::ephemeral::Ephemeral;
+use crate::friend::ChannelStatus;
+use crate::state::FunderState;
+use crate::types::{FunderIncoming, FunderIncomingComm};
+
+async fn task_handler_max_operations(mut identity_client: IdentityClient) {
+    let pk = identity_client.request_public_key().await.unwrap();
+    let friend_pk = PublicKey::from(&[0xbb; PublicKey::len()]);
+
+    let relays = vec![dummy_named_relay_address(1)];
+    let mut state = FunderState::<u32>::new(pk.clone(), relays);
+    let mut ephemeral = Ephemeral::new();
+
+    let mut rng = RngContainer::new(DummyRandom::new(&[3u8]));
+
+    // Initialize:
+    let funder_incoming = FunderIncoming::Init;
+    Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        &mut identity_client,
+    ))
+    .await
+    .unwrap();
+
+    let add_friend = AddFriend {
+        friend_public_key: friend_pk.clone(),
+        relays: vec![dummy_relay_address(2)],
+        name: String::from("friend"),
+    };
+    let incoming_control_message = FunderIncomingControl::new(
+        Uid::from(&[11; Uid::len()]),
+        FunderControl::AddFriend(add_friend),
+    );
+    let funder_incoming = FunderIncoming::Control(incoming_control_message);
+    Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        &mut identity_client,
+    ))
+    .await
+    .unwrap();
+
+    let friend = state.friends.get(&friend_pk).unwrap();
+    match &friend.channel_status {
+        ChannelStatus::Consistent(_) => {}
+        ChannelStatus::Inconsistent(_) => unreachable!(),
+    };
+
+    // Friend sends a move token with more operations than allowed in a batch:
+    let operations = (0..17u8)
+        .map(|i| {
+            FriendTcOp::CancelSendFunds(CancelSendFundsOp {
+                request_id: Uid::from(&[i; Uid::len()]),
+                opt_reason: None,
+            })
+        })
+        .collect();
+    let move_token = MoveToken {
+        old_token: Signature::from(&[0; Signature::len()]),
+        currencies_operations: vec![CurrencyOperations {
+            currency: Currency::try_from("FST".to_owned()).unwrap(),
+            operations,
+        }],
+        opt_local_relays: None,
+        opt_active_currencies: None,
+        info_hash: HashResult::from(&[0; HashResult::len()]),
+        rand_nonce: RandValue::from(&[0; RandValue::len()]),
+        new_token: Signature::from(&[0; Signature::len()]),
+    };
+    let friend_message = FriendMessage::MoveTokenRequest(MoveTokenRequest {
+        move_token,
+        token_wanted: false,
+    });
+    let funder_incoming = FunderIncoming::Comm(FunderIncomingComm::Friend((
+        friend_pk.clone(),
+        friend_message,
+    )));
+    Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        &mut identity_client,
+    ))
+    .await
+    .unwrap();
+
+    // The move token was rejected, and the channel is now inconsistent:
+    let friend = state.friends.get(&friend_pk).unwrap();
+    match &friend.channel_status {
+        ChannelStatus::Consistent(_) => unreachable!(),
+        ChannelStatus::Inconsistent(channel_inconsistent) => {
+            assert!(channel_inconsistent.opt_remote_reset_terms.is_none())
+        }
+    };
+}
+
+#[test]
+fn test_handler_max_operations() {
+    let thread_pool = ThreadPool::new().unwrap();
+
+    let rng = DummyRandom::new(&[1u8]);
+    let pkcs8 = PrivateKey::rand_gen(&rng);
+    let identity = SoftwareEd25519Identity::from_private_key(&pkcs8).unwrap();
+    let (requests_sender, identity_server) = create_identity(identity);
+    let identity_client = IdentityClient::new(requests_sender);
+    thread_pool
+        .spawn(identity_server.then(|_| future::ready(())))
+        .unwrap();
+
+    LocalPool::new().run_until(task_handler_max_operations(identity_client));
+}
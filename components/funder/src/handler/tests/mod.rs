@@ -1,4 +1,10 @@
 mod change_address;
+mod currency_config;
+mod friend_relays;
+mod idle_ticks;
+mod max_operations;
 mod pair_basic;
 mod pair_inconsistency;
+mod relay_name;
+mod retransmits;
 pub mod utils;
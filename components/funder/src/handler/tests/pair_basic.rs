@@ -16,8 +16,8 @@ use crypto::test_utils::DummyRandom;
 use proto::crypto::{InvoiceId, PaymentId, PrivateKey, PublicKey, Uid};
 
 use proto::funder::messages::{
-    AckClosePayment, AddFriend, AddInvoice, CreatePayment, CreateTransaction, Currency,
-    FriendMessage, FriendStatus, FriendsRoute, FunderControl, FunderIncomingControl,
+    AckClosePayment, AddFriend, AddInvoice, CommitInvoiceResult, CreatePayment, CreateTransaction,
+    Currency, FriendMessage, FriendStatus, FriendsRoute, FunderControl, FunderIncomingControl,
     FunderOutgoingControl, PaymentStatus, Rate, RequestResult, RequestsStatus,
     SetFriendCurrencyMaxDebt, SetFriendCurrencyRate, SetFriendCurrencyRequestsStatus,
     SetFriendStatus,
@@ -484,6 +484,7 @@ async fn task_handler_pair_basic<'a>(
         invoice_id: InvoiceId::from(&[1u8; InvoiceId::len()]),
         currency: currency.clone(),
         total_dest_payment: 16,
+        opt_expiry_ticks: None,
     };
 
     let incoming_control_message = FunderIncomingControl::new(
@@ -687,7 +688,7 @@ async fn task_handler_pair_basic<'a>(
     // We expect failure, because remote side is not ready:
     assert_eq!(transaction_result.request_id, Uid::from(&[0; Uid::len()]));
     match &transaction_result.result {
-        RequestResult::Failure => {}
+        RequestResult::Failure(_) => {}
         _ => unreachable!(),
     };
 
@@ -967,7 +968,14 @@ async fn task_handler_pair_basic<'a>(
     .unwrap();
 
     assert_eq!(outgoing_comms.len(), 1);
-    assert_eq!(outgoing_control.len(), 1);
+    // Report mutations, and the result of the commit:
+    assert_eq!(outgoing_control.len(), 2);
+    match &outgoing_control[1] {
+        FunderOutgoingControl::ResponseCommitInvoice(response_commit_invoice) => {
+            assert_eq!(response_commit_invoice.result, CommitInvoiceResult::Success)
+        }
+        _ => unreachable!(),
+    };
 
     let friend_message = match &outgoing_comms[0] {
         FunderOutgoingComm::FriendMessage((pk, friend_message)) => {
@@ -1138,6 +1146,41 @@ async fn task_handler_pair_basic<'a>(
     assert_eq!(receipt.dest_payment, 16);
     assert_eq!(receipt.total_dest_payment, 16);
 
+    // Node2: Request for closing the payment again, before acking.
+    // (For example, if the app crashed before it could ack)
+    // We expect to get back the exact same response:
+    let first_response_close_payment = response_close_payment.clone();
+    let incoming_control_message = FunderIncomingControl::new(
+        Uid::from(&[24; Uid::len()]),
+        FunderControl::RequestClosePayment(PaymentId::from(&[4u8; PaymentId::len()])),
+    );
+    let funder_incoming = FunderIncoming::Control(incoming_control_message);
+    let state2_before = state2.clone();
+    let (outgoing_comms, outgoing_control) = Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state2,
+        &mut ephemeral2,
+        &mut rng,
+        identity_client2,
+    ))
+    .await
+    .unwrap();
+
+    assert_eq!(outgoing_comms.len(), 0);
+    // No state was advanced:
+    assert_eq!(state2, state2_before);
+
+    let response_close_payment = outgoing_control
+        .iter()
+        .find_map(|outgoing| match outgoing {
+            FunderOutgoingControl::ResponseClosePayment(response_close_payment) => {
+                Some(response_close_payment)
+            }
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(response_close_payment, &first_response_close_payment);
+
     let ack_close_payment = AckClosePayment {
         payment_id: PaymentId::from(&[4u8; PaymentId::len()]),
         ack_uid: ack_uid.clone(),
@@ -1188,6 +1231,219 @@ async fn task_handler_pair_basic<'a>(
         PaymentStatus::PaymentNotFound => {}
         _ => unreachable!(),
     }
+
+    // Node2: Create a payment that will be canceled:
+    let create_payment = CreatePayment {
+        payment_id: PaymentId::from(&[5u8; PaymentId::len()]),
+        invoice_id: InvoiceId::from(&[2u8; InvoiceId::len()]),
+        currency: currency.clone(),
+        total_dest_payment: 20,
+        dest_public_key: pk3.clone(),
+    };
+    let incoming_control_message = FunderIncomingControl::new(
+        Uid::from(&[25; Uid::len()]),
+        FunderControl::CreatePayment(create_payment),
+    );
+    let funder_incoming = FunderIncoming::Control(incoming_control_message);
+    Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state2,
+        &mut ephemeral2,
+        &mut rng,
+        identity_client2,
+    ))
+    .await
+    .unwrap();
+
+    let create_transaction = |request_index: u8| CreateTransaction {
+        payment_id: PaymentId::from(&[5u8; PaymentId::len()]),
+        request_id: Uid::from(&[request_index; Uid::len()]),
+        route: FriendsRoute {
+            public_keys: vec![pk2.clone(), pk1.clone(), pk3.clone()],
+        },
+        dest_payment: 10,
+        fees: 2,
+    };
+
+    // Node2 holds the token, so the first transaction is sent immediately to Node1:
+    let incoming_control_message = FunderIncomingControl::new(
+        Uid::from(&[26; Uid::len()]),
+        FunderControl::CreateTransaction(create_transaction(2)),
+    );
+    let funder_incoming = FunderIncoming::Control(incoming_control_message);
+    let (outgoing_comms, _outgoing_control) = Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state2,
+        &mut ephemeral2,
+        &mut rng,
+        identity_client2,
+    ))
+    .await
+    .unwrap();
+
+    assert_eq!(outgoing_comms.len(), 1);
+    let friend_message = match &outgoing_comms[0] {
+        FunderOutgoingComm::FriendMessage((pk, friend_message)) => {
+            assert_eq!(pk, &pk1);
+            friend_message.clone()
+        }
+        _ => unreachable!(),
+    };
+
+    // The second transaction waits for the token:
+    let incoming_control_message = FunderIncomingControl::new(
+        Uid::from(&[27; Uid::len()]),
+        FunderControl::CreateTransaction(create_transaction(3)),
+    );
+    let funder_incoming = FunderIncoming::Control(incoming_control_message);
+    Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state2,
+        &mut ephemeral2,
+        &mut rng,
+        identity_client2,
+    ))
+    .await
+    .unwrap();
+
+    let pending_user_requests_len =
+        |state: &FunderState<u32>| match &state.friends.get(&pk1).unwrap().channel_status {
+            ChannelStatus::Consistent(channel_consistent) => {
+                channel_consistent.pending_user_requests.len()
+            }
+            _ => unreachable!(),
+        };
+    assert_eq!(pending_user_requests_len(&state2), 1);
+
+    // Node2: Cancel the payment:
+    let incoming_control_message = FunderIncomingControl::new(
+        Uid::from(&[28; Uid::len()]),
+        FunderControl::CancelPayment(PaymentId::from(&[5u8; PaymentId::len()])),
+    );
+    let funder_incoming = FunderIncoming::Control(incoming_control_message);
+    let (_outgoing_comms, outgoing_control) = Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state2,
+        &mut ephemeral2,
+        &mut rng,
+        identity_client2,
+    ))
+    .await
+    .unwrap();
+
+    // The queued transaction is canceled immediately:
+    assert_eq!(pending_user_requests_len(&state2), 0);
+    let transaction_results = outgoing_control
+        .iter()
+        .filter_map(|outgoing| match outgoing {
+            FunderOutgoingControl::TransactionResult(transaction_result) => {
+                Some(transaction_result.clone())
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(transaction_results.len(), 1);
+    assert_eq!(
+        transaction_results[0].request_id,
+        Uid::from(&[3; Uid::len()])
+    );
+    match &transaction_results[0].result {
+        RequestResult::Failure(_) => {}
+        _ => unreachable!(),
+    };
+
+    // The transaction that was already sent could not be canceled:
+    let response_cancel_payment = outgoing_control
+        .iter()
+        .find_map(|outgoing| match outgoing {
+            FunderOutgoingControl::ResponseCancelPayment(response_cancel_payment) => {
+                Some(response_cancel_payment.clone())
+            }
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(
+        response_cancel_payment.payment_id,
+        PaymentId::from(&[5u8; PaymentId::len()])
+    );
+    assert_eq!(
+        response_cancel_payment.in_flight,
+        vec![Uid::from(&[2; Uid::len()])]
+    );
+
+    // The payment waits for the transaction that was already sent:
+    assert!(!outgoing_control.iter().any(|outgoing| match outgoing {
+        FunderOutgoingControl::ResponseClosePayment(_) => true,
+        _ => false,
+    }));
+
+    // Node1: Receive the first transaction. Node1 can not forward it to pk3, so it cancels it:
+    let funder_incoming =
+        FunderIncoming::Comm(FunderIncomingComm::Friend((pk2.clone(), friend_message)));
+    let (outgoing_comms, _outgoing_control) = Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state1,
+        &mut ephemeral1,
+        &mut rng,
+        identity_client1,
+    ))
+    .await
+    .unwrap();
+
+    assert_eq!(outgoing_comms.len(), 1);
+    let friend_message = match &outgoing_comms[0] {
+        FunderOutgoingComm::FriendMessage((pk, friend_message)) => {
+            assert_eq!(pk, &pk2);
+            friend_message.clone()
+        }
+        _ => unreachable!(),
+    };
+
+    // Node2: Receive the cancellation:
+    let funder_incoming =
+        FunderIncoming::Comm(FunderIncomingComm::Friend((pk1.clone(), friend_message)));
+    let (_outgoing_comms, outgoing_control) = Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state2,
+        &mut ephemeral2,
+        &mut rng,
+        identity_client2,
+    ))
+    .await
+    .unwrap();
+
+    // All the transactions were resolved, so the payment is canceled:
+    let response_close_payment = outgoing_control
+        .iter()
+        .find_map(|outgoing| match outgoing {
+            FunderOutgoingControl::ResponseClosePayment(response_close_payment) => {
+                Some(response_close_payment)
+            }
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(
+        response_close_payment.payment_id,
+        PaymentId::from(&[5u8; PaymentId::len()])
+    );
+    match response_close_payment.status {
+        PaymentStatus::Canceled(_) => {}
+        _ => unreachable!(),
+    }
+
+    // No credits are left frozen:
+    let friend1 = state2.friends.get(&pk1).unwrap();
+    let mutual_credit_state = match &friend1.channel_status {
+        ChannelStatus::Consistent(channel_consistent) => channel_consistent
+            .token_channel
+            .get_mutual_credits()
+            .get(&currency)
+            .unwrap()
+            .state(),
+        _ => unreachable!(),
+    };
+    assert_eq!(mutual_credit_state.balance.balance, -20);
+    assert_eq!(mutual_credit_state.balance.local_pending_debt, 0);
 }
 
 #[test]
@@ -0,0 +1,117 @@
+use super::utils::{apply_funder_incoming, dummy_named_relay_address};
+
+use futures::executor::{LocalPool, ThreadPool};
+use futures::task::SpawnExt;
+use futures::{future, FutureExt};
+
+use identity::{create_identity, IdentityClient};
+
+use crypto::identity::SoftwareEd25519Identity;
+use crypto::rand::{RandGen, RngContainer};
+use crypto::test_utils::DummyRandom;
+
+use proto::crypto::{PrivateKey, Uid};
+use proto::funder::messages::{FunderControl, FunderIncomingControl, SetRelayName};
+
+use crate::ephemeral::Ephemeral;
+use crate::state::FunderState;
+use crate::types::FunderIncoming;
+
+async fn task_handler_relay_name(mut identity_client: IdentityClient) {
+    let pk = identity_client.request_public_key().await.unwrap();
+
+    let relays = vec![dummy_named_relay_address(1), dummy_named_relay_address(2)];
+    let mut state = FunderState::<u32>::new(pk.clone(), relays);
+    let mut ephemeral = Ephemeral::new();
+
+    let mut rng = RngContainer::new(DummyRandom::new(&[3u8]));
+
+    // Initialize:
+    let funder_incoming = FunderIncoming::Init;
+    Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        &mut identity_client,
+    ))
+    .await
+    .unwrap();
+
+    // Rename relay 2:
+    let set_relay_name = SetRelayName {
+        relay_public_key: dummy_named_relay_address(2).public_key,
+        name: String::from("renamed"),
+    };
+    let incoming_control_message = FunderIncomingControl::new(
+        Uid::from(&[11; Uid::len()]),
+        FunderControl::SetRelayName(set_relay_name),
+    );
+    let funder_incoming = FunderIncoming::Control(incoming_control_message);
+    Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        &mut identity_client,
+    ))
+    .await
+    .unwrap();
+
+    // Only the name of relay 2 has changed:
+    assert_eq!(state.relays.len(), 2);
+    assert!(state
+        .relays
+        .iter()
+        .any(|named_relay_address| named_relay_address == &dummy_named_relay_address(1)));
+    let renamed_relay = state
+        .relays
+        .iter()
+        .find(|named_relay_address| {
+            named_relay_address.public_key == dummy_named_relay_address(2).public_key
+        })
+        .unwrap();
+    assert_eq!(
+        renamed_relay,
+        &dummy_named_relay_address(2).rename(String::from("renamed"))
+    );
+
+    // Attempt to rename a relay that does not exist:
+    let set_relay_name = SetRelayName {
+        relay_public_key: dummy_named_relay_address(3).public_key,
+        name: String::from("relay-3"),
+    };
+    let incoming_control_message = FunderIncomingControl::new(
+        Uid::from(&[12; Uid::len()]),
+        FunderControl::SetRelayName(set_relay_name),
+    );
+    let funder_incoming = FunderIncoming::Control(incoming_control_message);
+    Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        &mut identity_client,
+    ))
+    .await
+    .unwrap();
+
+    // No relay was added:
+    assert_eq!(state.relays.len(), 2);
+}
+
+#[test]
+fn test_handler_relay_name() {
+    let thread_pool = ThreadPool::new().unwrap();
+
+    let rng = DummyRandom::new(&[1u8]);
+    let pkcs8 = PrivateKey::rand_gen(&rng);
+    let identity = SoftwareEd25519Identity::from_private_key(&pkcs8).unwrap();
+    let (requests_sender, identity_server) = create_identity(identity);
+    let identity_client = IdentityClient::new(requests_sender);
+    thread_pool
+        .spawn(identity_server.then(|_| future::ready(())))
+        .unwrap();
+
+    LocalPool::new().run_until(task_handler_relay_name(identity_client));
+}
@@ -0,0 +1,167 @@
+use std::cmp::Ordering;
+
+use super::utils::{apply_funder_incoming, dummy_named_relay_address, dummy_relay_address};
+
+use futures::executor::{LocalPool, ThreadPool};
+use futures::task::SpawnExt;
+use futures::{future, FutureExt};
+
+use identity::{create_identity, IdentityClient};
+
+use crypto::identity::{compare_public_key, SoftwareEd25519Identity};
+use crypto::rand::{RandGen, RngContainer};
+use crypto::test_utils::DummyRandom;
+
+use proto::crypto::{HashResult, PrivateKey, PublicKey, RandValue, Signature, Uid};
+use proto::funder::messages::{
+    AddFriend, FriendMessage, FunderControl, FunderIncomingControl, InconsistencyReason, MoveToken,
+    MoveTokenRequest,
+};
+use proto::report::messages::ChannelStatusReport;
+
+use crate::ephemeral::Ephemeral;
+use crate::friend::ChannelStatus;
+use crate::state::FunderState;
+use crate::token_channel::TcDirectionBorrow;
+use crate::types::{FunderIncoming, FunderIncomingComm};
+
+async fn task_handler_retransmits(mut identity_client: IdentityClient) {
+    let pk = identity_client.request_public_key().await.unwrap();
+    // Pick a friend for which we are the first sender, so that we hold the token:
+    let friend_pk = (0..=255u8)
+        .map(|i| PublicKey::from(&[i; PublicKey::len()]))
+        .find(|friend_pk| compare_public_key(&pk, friend_pk) == Ordering::Less)
+        .unwrap();
+
+    let relays = vec![dummy_named_relay_address(1)];
+    let mut state = FunderState::<u32>::new(pk.clone(), relays);
+    let mut ephemeral = Ephemeral::new();
+
+    let mut rng = RngContainer::new(DummyRandom::new(&[3u8]));
+
+    // Initialize:
+    let funder_incoming = FunderIncoming::Init;
+    Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        &mut identity_client,
+    ))
+    .await
+    .unwrap();
+
+    let add_friend = AddFriend {
+        friend_public_key: friend_pk.clone(),
+        relays: vec![dummy_relay_address(2)],
+        name: String::from("friend"),
+    };
+    let incoming_control_message = FunderIncomingControl::new(
+        Uid::from(&[11; Uid::len()]),
+        FunderControl::AddFriend(add_friend),
+    );
+    let funder_incoming = FunderIncoming::Control(incoming_control_message);
+    Box::pin(apply_funder_incoming(
+        funder_incoming,
+        &mut state,
+        &mut ephemeral,
+        &mut rng,
+        &mut identity_client,
+    ))
+    .await
+    .unwrap();
+
+    // Friend asks us to retransmit our outgoing move token:
+    let friend = state.friends.get(&friend_pk).unwrap();
+    let old_token = match &friend.channel_status {
+        ChannelStatus::Consistent(channel_consistent) => {
+            match channel_consistent.token_channel.get_direction() {
+                TcDirectionBorrow::Out(tc_out_borrow) => {
+                    tc_out_borrow.tc_outgoing.move_token_out.old_token.clone()
+                }
+                TcDirectionBorrow::In(_) => unreachable!(),
+            }
+        }
+        ChannelStatus::Inconsistent(_) => unreachable!(),
+    };
+    let move_token = MoveToken {
+        old_token: Signature::from(&[0; Signature::len()]),
+        currencies_operations: Vec::new(),
+        opt_local_relays: None,
+        opt_active_currencies: None,
+        info_hash: HashResult::from(&[0; HashResult::len()]),
+        rand_nonce: RandValue::from(&[0; RandValue::len()]),
+        new_token: old_token,
+    };
+
+    // Up to the allowed amount of retransmissions (TEST_MAX_MOVE_TOKEN_RETRANSMITS) is fine:
+    for i in 0..=8usize {
+        let friend_message = FriendMessage::MoveTokenRequest(MoveTokenRequest {
+            move_token: move_token.clone(),
+            token_wanted: false,
+        });
+        let funder_incoming = FunderIncoming::Comm(FunderIncomingComm::Friend((
+            friend_pk.clone(),
+            friend_message,
+        )));
+        Box::pin(apply_funder_incoming(
+            funder_incoming,
+            &mut state,
+            &mut ephemeral,
+            &mut rng,
+            &mut identity_client,
+        ))
+        .await
+        .unwrap();
+
+        let friend = state.friends.get(&friend_pk).unwrap();
+        match &friend.channel_status {
+            ChannelStatus::Consistent(_) => {
+                assert!(i < 8);
+                assert_eq!(ephemeral.retransmits.get_retransmits(&friend_pk), i + 1);
+            }
+            ChannelStatus::Inconsistent(_) => {
+                // One retransmission too many:
+                assert_eq!(i, 8);
+                assert_eq!(ephemeral.retransmits.get_retransmits(&friend_pk), 0);
+            }
+        };
+    }
+
+    let friend = state.friends.get(&friend_pk).unwrap();
+    match &friend.channel_status {
+        ChannelStatus::Consistent(_) => unreachable!(),
+        ChannelStatus::Inconsistent(channel_inconsistent) => {
+            assert!(channel_inconsistent.opt_remote_reset_terms.is_none());
+            assert_eq!(
+                channel_inconsistent.reason,
+                InconsistencyReason::TooManyRetransmits
+            );
+        }
+    };
+
+    // The reason is reported to the apps:
+    match ChannelStatusReport::from(&friend.channel_status) {
+        ChannelStatusReport::Consistent(_) => unreachable!(),
+        ChannelStatusReport::Inconsistent(channel_inconsistent_report) => assert_eq!(
+            channel_inconsistent_report.reason,
+            InconsistencyReason::TooManyRetransmits
+        ),
+    };
+}
+
+#[test]
+fn test_handler_retransmits() {
+    let thread_pool = ThreadPool::new().unwrap();
+
+    let rng = DummyRandom::new(&[1u8]);
+    let pkcs8 = PrivateKey::rand_gen(&rng);
+    let identity = SoftwareEd25519Identity::from_private_key(&pkcs8).unwrap();
+    let (requests_sender, identity_server) = create_identity(identity);
+    let identity_client = IdentityClient::new(requests_sender);
+    thread_pool
+        .spawn(identity_server.then(|_| future::ready(())))
+        .unwrap();
+
+    LocalPool::new().run_until(task_handler_retransmits(identity_client));
+}
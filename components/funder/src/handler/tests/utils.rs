@@ -18,6 +18,8 @@ use crate::types::{FunderIncoming, FunderOutgoingComm};
 const TEST_MAX_NODE_RELAYS: usize = 16;
 const TEST_MAX_OPERATIONS_IN_BATCH: usize = 16;
 const TEST_MAX_PENDING_USER_REQUESTS: usize = 16;
+const TEST_PENDING_TRANSACTION_TIMEOUT_TICKS: usize = 8;
+const TEST_MAX_MOVE_TOKEN_RETRANSMITS: usize = 8;
 
 /// A helper function to quickly create a dummy NamedRelayAddress.
 pub fn dummy_named_relay_address(index: u8) -> NamedRelayAddress<u32> {
@@ -54,6 +56,8 @@ where
         TEST_MAX_NODE_RELAYS,
         TEST_MAX_OPERATIONS_IN_BATCH,
         TEST_MAX_PENDING_USER_REQUESTS,
+        TEST_PENDING_TRANSACTION_TIMEOUT_TICKS,
+        TEST_MAX_MOVE_TOKEN_RETRANSMITS,
         funder_incoming,
     )
     .await?;
@@ -137,6 +137,9 @@ where
     B: Clone + CanonicalSerialize + PartialEq + Eq + Debug,
 {
     let friend = state.friends.get(friend_public_key).unwrap();
+    if !friend.status.accepts_requests() {
+        return false;
+    }
     if !ephemeral.liveness.is_online(friend_public_key) {
         return false;
     }
@@ -0,0 +1,57 @@
+use im::hashmap::HashMap as ImHashMap;
+
+use proto::crypto::PublicKey;
+
+/// Keeps the tick of the last move token received from every friend.
+/// The amount of idle ticks of a friend is calculated from the current tick, so that timer ticks
+/// do not require any per-friend update.
+///
+/// Kept in memory only. After a restart all friends begin again from zero.
+#[derive(Clone, Default)]
+pub struct IdleTicks {
+    /// Amount of ticks passed since the node was started.
+    pub current_tick: u64,
+    /// The tick of the last activity of every friend.
+    pub last_activity: ImHashMap<PublicKey, u64>,
+}
+
+#[derive(Debug)]
+pub enum IdleTicksMutation {
+    Tick,
+    /// Mark the current tick as the last activity of a friend.
+    SetActive(PublicKey),
+    Remove(PublicKey),
+}
+
+impl IdleTicks {
+    pub fn new() -> IdleTicks {
+        IdleTicks {
+            current_tick: 0,
+            last_activity: ImHashMap::new(),
+        }
+    }
+
+    pub fn mutate(&mut self, mutation: &IdleTicksMutation) {
+        match mutation {
+            IdleTicksMutation::Tick => {
+                self.current_tick = self.current_tick.saturating_add(1);
+            }
+            IdleTicksMutation::SetActive(friend_public_key) => {
+                self.last_activity
+                    .insert(friend_public_key.clone(), self.current_tick);
+            }
+            IdleTicksMutation::Remove(friend_public_key) => {
+                let _ = self.last_activity.remove(friend_public_key);
+            }
+        }
+    }
+
+    /// Amount of ticks passed since the last move token received from a friend.
+    /// Returns 0 for unknown friends.
+    pub fn get_idle_ticks(&self, friend_public_key: &PublicKey) -> u64 {
+        match self.last_activity.get(friend_public_key) {
+            Some(last_activity) => self.current_tick.saturating_sub(*last_activity),
+            None => 0,
+        }
+    }
+}
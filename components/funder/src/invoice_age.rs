@@ -0,0 +1,43 @@
+use im::hashmap::HashMap as ImHashMap;
+
+use proto::crypto::InvoiceId;
+
+/// Amount of ticks passed since we have first seen every open invoice that has an expiry.
+///
+/// Kept in memory only. After a restart all ages begin again from zero, which can only delay
+/// the expiry of invoices.
+#[derive(Clone, Default)]
+pub struct InvoiceAges {
+    pub ages: ImHashMap<InvoiceId, u64>,
+}
+
+#[derive(Debug)]
+pub enum InvoiceAgeMutation {
+    SetAge((InvoiceId, u64)),
+    Remove(InvoiceId),
+}
+
+impl InvoiceAges {
+    pub fn new() -> InvoiceAges {
+        InvoiceAges {
+            ages: ImHashMap::new(),
+        }
+    }
+
+    pub fn mutate(&mut self, mutation: &InvoiceAgeMutation) {
+        match mutation {
+            InvoiceAgeMutation::SetAge((invoice_id, age)) => {
+                self.ages.insert(invoice_id.clone(), *age);
+            }
+            InvoiceAgeMutation::Remove(invoice_id) => {
+                let _ = self.ages.remove(invoice_id);
+            }
+        }
+    }
+
+    /// Amount of ticks an invoice has been open.
+    /// Returns 0 for unknown invoices.
+    pub fn get_age(&self, invoice_id: &InvoiceId) -> u64 {
+        self.ages.get(invoice_id).cloned().unwrap_or(0)
+    }
+}
@@ -22,9 +22,14 @@ mod ephemeral;
 mod friend;
 mod funder;
 mod handler;
+mod idle_ticks;
+mod invoice_age;
 mod liveness;
 mod mutual_credit;
+mod payment_events;
+mod pending_age;
 pub mod report;
+mod retransmits;
 mod state;
 mod token_channel;
 pub mod types;
@@ -33,4 +38,5 @@ pub mod types;
 mod tests;
 
 pub use self::funder::{funder_loop, FunderError};
+pub use self::payment_events::{PaymentEvent, PaymentSuccessEvent};
 pub use self::state::{FunderMutation, FunderState};
@@ -98,7 +98,7 @@ pub fn process_operation(
     friend_tc_op: FriendTcOp,
     remote_max_debt: u128,
 ) -> Result<ProcessOperationOutput, ProcessOperationError> {
-    match friend_tc_op {
+    let output = match friend_tc_op {
         FriendTcOp::RequestSendFunds(request_send_funds) => {
             process_request_send_funds(mutual_credit, request_send_funds, remote_max_debt)
         }
@@ -111,7 +111,9 @@ pub fn process_operation(
         FriendTcOp::CollectSendFunds(collect_send_funds) => {
             process_collect_send_funds(mutual_credit, collect_send_funds)
         }
-    }
+    }?;
+    debug_assert_eq!(mutual_credit.check_invariants(), Ok(()));
+    Ok(output)
 }
 
 /// Process an incoming RequestSendFundsOp
@@ -333,11 +335,14 @@ fn process_collect_send_funds(
     };
 
     // Verify src_plain_lock and dest_plain_lock:
-    if collect_send_funds.src_plain_lock.hash_lock() != pending_transaction.src_hashed_lock {
+    if !collect_send_funds
+        .src_plain_lock
+        .verify(&pending_transaction.src_hashed_lock)
+    {
         return Err(ProcessOperationError::InvalidSrcPlainLock);
     }
 
-    if collect_send_funds.dest_plain_lock.hash_lock() != *dest_hashed_lock {
+    if !collect_send_funds.dest_plain_lock.verify(dest_hashed_lock) {
         return Err(ProcessOperationError::InvalidDestPlainLock);
     }
 
@@ -50,7 +50,7 @@ impl OutgoingMc {
         operation: &FriendTcOp,
     ) -> Result<Vec<McMutation>, QueueOperationError> {
         // TODO: Maybe remove clone from here later:
-        match operation.clone() {
+        let mc_mutations = match operation.clone() {
             FriendTcOp::RequestSendFunds(request_send_funds) => {
                 self.queue_request_send_funds(request_send_funds)
             }
@@ -63,7 +63,9 @@ impl OutgoingMc {
             FriendTcOp::CollectSendFunds(collect_send_funds) => {
                 self.queue_collect_send_funds(collect_send_funds)
             }
-        }
+        }?;
+        debug_assert_eq!(self.mutual_credit.check_invariants(), Ok(()));
+        Ok(mc_mutations)
     }
 
     fn queue_request_send_funds(
@@ -243,11 +245,14 @@ impl OutgoingMc {
         };
 
         // Verify src_plain_lock and dest_plain_lock:
-        if collect_send_funds.src_plain_lock.hash_lock() != pending_transaction.src_hashed_lock {
+        if !collect_send_funds
+            .src_plain_lock
+            .verify(&pending_transaction.src_hashed_lock)
+        {
             return Err(QueueOperationError::InvalidSrcPlainLock);
         }
 
-        if collect_send_funds.dest_plain_lock.hash_lock() != *dest_hashed_lock {
+        if !collect_send_funds.dest_plain_lock.verify(dest_hashed_lock) {
             return Err(QueueOperationError::InvalidDestPlainLock);
         }
 
@@ -7,8 +7,8 @@ use crypto::test_utils::DummyRandom;
 
 use proto::crypto::{InvoiceId, PlainLock, PrivateKey, PublicKey, RandValue, Signature, Uid};
 use proto::funder::messages::{
-    CancelSendFundsOp, CollectSendFundsOp, Currency, FriendTcOp, FriendsRoute, RequestSendFundsOp,
-    ResponseSendFundsOp,
+    CancelReason, CancelSendFundsOp, CollectSendFundsOp, Currency, FriendTcOp, FriendsRoute,
+    RequestSendFundsOp, ResponseSendFundsOp,
 };
 use signature::signature_buff::create_response_signature_buffer;
 
@@ -18,7 +18,7 @@ use crate::mutual_credit::incoming::{
     process_operation, ProcessOperationError, ProcessOperationOutput,
 };
 use crate::mutual_credit::outgoing::{OutgoingMc, QueueOperationError};
-use crate::mutual_credit::types::MutualCredit;
+use crate::mutual_credit::types::{InvariantViolation, McMutation, MutualCredit};
 
 /// Helper function for applying an outgoing operation over a token channel.
 fn apply_outgoing(
@@ -195,7 +195,10 @@ fn test_request_cancel_send_funds() {
 
     // -----[CancelSendFunds]--------
     // ------------------------------
-    let cancel_send_funds = CancelSendFundsOp { request_id };
+    let cancel_send_funds = CancelSendFundsOp {
+        request_id,
+        opt_reason: Some(CancelReason::NoRoute),
+    };
 
     apply_incoming(
         &mut mutual_credit,
@@ -292,7 +295,10 @@ fn test_request_response_cancel_send_funds() {
 
     // -----[CancelSendFunds]--------
     // ------------------------------
-    let cancel_send_funds = CancelSendFundsOp { request_id };
+    let cancel_send_funds = CancelSendFundsOp {
+        request_id,
+        opt_reason: Some(CancelReason::NoRoute),
+    };
 
     apply_incoming(
         &mut mutual_credit,
@@ -305,3 +311,197 @@ fn test_request_response_cancel_send_funds() {
     assert_eq!(mutual_credit.state().balance.local_pending_debt, 0);
     assert_eq!(mutual_credit.state().balance.remote_pending_debt, 0);
 }
+
+#[test]
+fn test_balance_for_reset_overflow() {
+    let currency = Currency::try_from("OFFSET".to_owned()).unwrap();
+
+    let local_public_key = PublicKey::from(&[0xaa; PublicKey::len()]);
+    let remote_public_key = PublicKey::from(&[0xbb; PublicKey::len()]);
+    let balance = i128::max_value() - 5;
+    let mut mutual_credit =
+        MutualCredit::new(&local_public_key, &remote_public_key, &currency, balance);
+
+    mutual_credit.mutate(&McMutation::SetRemotePendingDebt(3));
+    assert_eq!(mutual_credit.balance_for_reset(), i128::max_value() - 2);
+
+    // balance + remote_pending_debt does not fit in an i128. We expect no panic:
+    mutual_credit.mutate(&McMutation::SetRemotePendingDebt(10));
+    assert_eq!(mutual_credit.balance_for_reset(), i128::max_value());
+}
+
+#[test]
+fn test_request_response_collect_invalid_src_plain_lock() {
+    let currency = Currency::try_from("OFFSET".to_owned()).unwrap();
+
+    let local_public_key = PublicKey::from(&[0xaa; PublicKey::len()]);
+    let remote_public_key = PublicKey::from(&[0xbb; PublicKey::len()]);
+    let balance = 0;
+    let mut mutual_credit =
+        MutualCredit::new(&local_public_key, &remote_public_key, &currency, balance);
+
+    // -----[RequestSendFunds]--------
+    // -----------------------------
+    let rng = DummyRandom::new(&[1u8]);
+    let private_key = PrivateKey::rand_gen(&rng);
+    let identity = SoftwareEd25519Identity::from_private_key(&private_key).unwrap();
+    let public_key_c = identity.get_public_key();
+
+    let request_id = Uid::from(&[3; Uid::len()]);
+    let route = FriendsRoute {
+        public_keys: vec![
+            PublicKey::from(&[0xaa; PublicKey::len()]),
+            PublicKey::from(&[0xbb; PublicKey::len()]),
+            public_key_c.clone(),
+        ],
+    };
+    let invoice_id = InvoiceId::from(&[0; InvoiceId::len()]);
+    let src_plain_lock = PlainLock::from(&[1; PlainLock::len()]);
+
+    let request_send_funds = RequestSendFundsOp {
+        request_id: request_id.clone(),
+        src_hashed_lock: src_plain_lock.hash_lock(),
+        route,
+        dest_payment: 10,
+        total_dest_payment: 10,
+        invoice_id,
+        left_fees: 5,
+    };
+
+    let pending_transaction = create_pending_transaction(&request_send_funds);
+    apply_outgoing(
+        &mut mutual_credit,
+        &FriendTcOp::RequestSendFunds(request_send_funds),
+    )
+    .unwrap();
+
+    // -----[ResponseSendFunds]--------
+    // --------------------------------
+    let rand_nonce = RandValue::from(&[5; RandValue::len()]);
+    let dest_plain_lock = PlainLock::from(&[2; PlainLock::len()]);
+
+    let mut response_send_funds = ResponseSendFundsOp {
+        request_id: request_id.clone(),
+        dest_hashed_lock: dest_plain_lock.hash_lock(),
+        is_complete: false,
+        rand_nonce: rand_nonce.clone(),
+        signature: Signature::from(&[0; Signature::len()]),
+    };
+
+    let sign_buffer = create_response_signature_buffer(
+        &currency,
+        response_send_funds.clone(),
+        &pending_transaction,
+    );
+    response_send_funds.signature = identity.sign(&sign_buffer);
+
+    apply_incoming(
+        &mut mutual_credit,
+        FriendTcOp::ResponseSendFunds(response_send_funds),
+        100,
+    )
+    .unwrap();
+
+    // -----[CollectSendFunds]--------
+    // --------------------------------
+    // The src_plain_lock does not match the src_hashed_lock of the request:
+    let collect_send_funds = CollectSendFundsOp {
+        request_id: request_id.clone(),
+        src_plain_lock: PlainLock::from(&[3; PlainLock::len()]),
+        dest_plain_lock,
+    };
+
+    let res = apply_incoming(
+        &mut mutual_credit,
+        FriendTcOp::CollectSendFunds(collect_send_funds),
+        100,
+    );
+    match res {
+        Err(ProcessOperationError::InvalidSrcPlainLock) => {}
+        _ => unreachable!(),
+    }
+
+    // The collect was not applied:
+    assert_eq!(mutual_credit.state().balance.balance, 0);
+    assert_eq!(mutual_credit.state().balance.local_pending_debt, 10 + 5);
+    assert_eq!(mutual_credit.state().balance.remote_pending_debt, 0);
+    assert!(mutual_credit
+        .state()
+        .pending_transactions
+        .local
+        .contains_key(&request_id));
+}
+
+#[test]
+fn test_check_invariants() {
+    let currency = Currency::try_from("OFFSET".to_owned()).unwrap();
+
+    let local_public_key = PublicKey::from(&[0xaa; PublicKey::len()]);
+    let remote_public_key = PublicKey::from(&[0xbb; PublicKey::len()]);
+    let balance = 0;
+    let mut mutual_credit =
+        MutualCredit::new(&local_public_key, &remote_public_key, &currency, balance);
+    assert_eq!(mutual_credit.check_invariants(), Ok(()));
+
+    let src_plain_lock = PlainLock::from(&[1; PlainLock::len()]);
+    let request_send_funds = RequestSendFundsOp {
+        request_id: Uid::from(&[3; Uid::len()]),
+        src_hashed_lock: src_plain_lock.hash_lock(),
+        route: FriendsRoute {
+            public_keys: vec![
+                PublicKey::from(&[0xaa; PublicKey::len()]),
+                PublicKey::from(&[0xbb; PublicKey::len()]),
+            ],
+        },
+        dest_payment: 10,
+        total_dest_payment: 10,
+        invoice_id: InvoiceId::from(&[0; InvoiceId::len()]),
+        left_fees: 5,
+    };
+
+    apply_outgoing(
+        &mut mutual_credit,
+        &FriendTcOp::RequestSendFunds(request_send_funds.clone()),
+    )
+    .unwrap();
+    assert_eq!(mutual_credit.check_invariants(), Ok(()));
+
+    // Corrupt the local pending debt:
+    let mut corrupt_mutual_credit = mutual_credit.clone();
+    corrupt_mutual_credit.mutate(&McMutation::SetLocalPendingDebt(3));
+    assert_eq!(
+        corrupt_mutual_credit.check_invariants(),
+        Err(InvariantViolation::LocalPendingDebtMismatch {
+            expected: 15,
+            found: 3
+        })
+    );
+
+    // Corrupt the remote pending debt:
+    let mut corrupt_mutual_credit = mutual_credit.clone();
+    corrupt_mutual_credit.mutate(&McMutation::SetRemotePendingDebt(7));
+    assert_eq!(
+        corrupt_mutual_credit.check_invariants(),
+        Err(InvariantViolation::RemotePendingDebtMismatch {
+            expected: 0,
+            found: 7
+        })
+    );
+
+    // Frozen credits are consistent, but the balance can not be represented
+    // once the pending transaction is resolved:
+    let mut corrupt_mutual_credit = MutualCredit::new(
+        &local_public_key,
+        &remote_public_key,
+        &currency,
+        i128::max_value(),
+    );
+    corrupt_mutual_credit.mutate(&McMutation::InsertRemotePendingTransaction(
+        create_pending_transaction(&request_send_funds),
+    ));
+    corrupt_mutual_credit.mutate(&McMutation::SetRemotePendingDebt(15));
+    assert_eq!(
+        corrupt_mutual_credit.check_invariants(),
+        Err(InvariantViolation::BalanceOutOfRange)
+    );
+}
@@ -85,6 +85,49 @@ pub struct MutualCredit {
     state: MutualCreditState,
 }
 
+/// A violated invariant of the mutual credit state.
+/// Any of those indicates a logic bug.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvariantViolation {
+    /// A pending transaction is stored under a different request id
+    RequestIdMismatch(Uid),
+    /// A pending transaction's dest_payment exceeds its total_dest_payment
+    DestPaymentExceedsTotal(Uid),
+    /// The sum of frozen credits does not fit in a u128
+    PendingDebtOverflow,
+    /// local_pending_debt differs from the credits frozen by local pending transactions
+    LocalPendingDebtMismatch { expected: u128, found: u128 },
+    /// remote_pending_debt differs from the credits frozen by remote pending transactions
+    RemotePendingDebtMismatch { expected: u128, found: u128 },
+    /// balance - local_pending_debt or balance + remote_pending_debt do not fit in an i128
+    BalanceOutOfRange,
+}
+
+/// Sum the credits frozen by a set of pending transactions.
+fn sum_frozen_credits(
+    pending_transactions: &ImHashMap<Uid, PendingTransaction>,
+) -> Result<u128, InvariantViolation> {
+    let mut sum = 0u128;
+    for (request_id, pending_transaction) in pending_transactions {
+        if request_id != &pending_transaction.request_id {
+            return Err(InvariantViolation::RequestIdMismatch(request_id.clone()));
+        }
+        if pending_transaction.dest_payment > pending_transaction.total_dest_payment {
+            return Err(InvariantViolation::DestPaymentExceedsTotal(
+                request_id.clone(),
+            ));
+        }
+        let freeze_credits = pending_transaction
+            .dest_payment
+            .checked_add(pending_transaction.left_fees)
+            .ok_or(InvariantViolation::PendingDebtOverflow)?;
+        sum = sum
+            .checked_add(freeze_credits)
+            .ok_or(InvariantViolation::PendingDebtOverflow)?;
+    }
+    Ok(sum)
+}
+
 #[derive(Arbitrary, Eq, PartialEq, Debug, Clone)]
 pub enum McMutation {
     SetBalance(i128),
@@ -121,12 +164,22 @@ impl MutualCredit {
 
     /// Calculate required balance for reset.
     /// This would be current balance plus additional future profits.
+    /// Saturates (instead of panicking) if the sum does not fit in an i128, as both values are
+    /// partly controlled by the remote side.
     pub fn balance_for_reset(&self) -> i128 {
-        self.state
+        let balance = &self.state.balance;
+        balance
             .balance
-            .balance
-            .checked_add_unsigned(self.state.balance.remote_pending_debt)
-            .expect("Overflow when calculating balance_for_reset")
+            .checked_add_unsigned(balance.remote_pending_debt)
+            .unwrap_or_else(|| {
+                warn!(
+                    "balance_for_reset(): Overflow: balance = {}, remote_pending_debt = {}",
+                    balance.balance, balance.remote_pending_debt
+                );
+                balance
+                    .balance
+                    .saturating_add_unsigned(balance.remote_pending_debt)
+            })
         // TODO: Is this the correct formula?
         // Other options:
         // *    balance
@@ -137,6 +190,47 @@ impl MutualCredit {
         &self.state
     }
 
+    /// Verify the invariants of the balance fields:
+    /// Pending debts are exactly the credits frozen by pending transactions,
+    /// and the balance stays representable after all pending transactions are resolved.
+    ///
+    /// Note that the max debt is not checked here, as it is configured outside of the mutual
+    /// credit. A remote request that exceeds the max debt is frozen until it is canceled.
+    ///
+    /// Intermediate states (between the mutations of a single operation) may not satisfy the
+    /// invariants. This check should be performed after a full operation was applied.
+    pub fn check_invariants(&self) -> Result<(), InvariantViolation> {
+        let balance = &self.state.balance;
+        let pending_transactions = &self.state.pending_transactions;
+
+        let expected = sum_frozen_credits(&pending_transactions.local)?;
+        if balance.local_pending_debt != expected {
+            return Err(InvariantViolation::LocalPendingDebtMismatch {
+                expected,
+                found: balance.local_pending_debt,
+            });
+        }
+
+        let expected = sum_frozen_credits(&pending_transactions.remote)?;
+        if balance.remote_pending_debt != expected {
+            return Err(InvariantViolation::RemotePendingDebtMismatch {
+                expected,
+                found: balance.remote_pending_debt,
+            });
+        }
+
+        balance
+            .balance
+            .checked_sub_unsigned(balance.local_pending_debt)
+            .ok_or(InvariantViolation::BalanceOutOfRange)?;
+        balance
+            .balance
+            .checked_add_unsigned(balance.remote_pending_debt)
+            .ok_or(InvariantViolation::BalanceOutOfRange)?;
+
+        Ok(())
+    }
+
     pub fn mutate(&mut self, mc_mutation: &McMutation) {
         match mc_mutation {
             McMutation::SetBalance(balance) => self.set_balance(*balance),
@@ -0,0 +1,62 @@
+use proto::crypto::{InvoiceId, PaymentId};
+use proto::funder::messages::{Currency, Receipt};
+
+use crate::state::{FunderMutation, FunderState, PaymentStage};
+
+/// A payment was settled successfully
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentSuccessEvent {
+    pub payment_id: PaymentId,
+    pub invoice_id: InvoiceId,
+    pub currency: Currency,
+    /// Total amount of credits paid to the seller (Not including fees)
+    pub total_dest_payment: u128,
+    pub receipt: Receipt,
+}
+
+/// A final outcome of a payment, for which this node is the buyer.
+/// Every payment produces at most one event, at the moment its outcome is decided.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaymentEvent {
+    Success(PaymentSuccessEvent),
+    Canceled(PaymentId),
+}
+
+/// Check if applying `funder_mutation` over `funder_state` decides the outcome of a payment.
+/// Should be called before the mutation is applied.
+pub fn payment_event<B>(
+    funder_state: &FunderState<B>,
+    funder_mutation: &FunderMutation<B>,
+) -> Option<PaymentEvent>
+where
+    B: Clone,
+{
+    let (payment_id, new_payment) = match funder_mutation {
+        FunderMutation::UpdatePayment((payment_id, new_payment)) => (payment_id, new_payment),
+        _ => return None,
+    };
+
+    // A payment that was already terminal had its event emitted before:
+    if funder_state
+        .payments
+        .get(payment_id)
+        .map(|payment| payment.is_terminal())
+        .unwrap_or(false)
+    {
+        return None;
+    }
+
+    match &new_payment.stage {
+        PaymentStage::Success(_, receipt, _) => Some(PaymentEvent::Success(PaymentSuccessEvent {
+            payment_id: payment_id.clone(),
+            invoice_id: receipt.invoice_id.clone(),
+            currency: receipt.currency.clone(),
+            total_dest_payment: receipt.total_dest_payment,
+            receipt: receipt.clone(),
+        })),
+        PaymentStage::Canceled(_) => Some(PaymentEvent::Canceled(payment_id.clone())),
+        PaymentStage::NewTransactions(_)
+        | PaymentStage::InProgress(_)
+        | PaymentStage::AfterSuccessAck(_) => None,
+    }
+}
@@ -0,0 +1,76 @@
+use im::hashmap::HashMap as ImHashMap;
+
+use proto::crypto::{PublicKey, Uid};
+use proto::funder::messages::Currency;
+
+/// Identifies a local pending transaction: (friend_public_key, currency, request_id)
+pub type PendingKey = (PublicKey, Currency, Uid);
+
+/// Amount of ticks passed since we have sent every local pending transaction (That was not yet
+/// answered by the remote side).
+///
+/// A transaction is tracked from the moment it is sent inside a move token, and forgotten once the
+/// remote side answers it. Kept in memory only. After a restart all ages begin again from zero,
+/// which can only delay cancellation of stale transactions.
+#[derive(Clone, Default)]
+pub struct PendingAges {
+    pub ages: ImHashMap<PendingKey, usize>,
+}
+
+#[derive(Debug)]
+pub enum PendingAgeMutation {
+    SetAge((PendingKey, usize)),
+    Remove(PendingKey),
+}
+
+impl PendingAges {
+    pub fn new() -> PendingAges {
+        PendingAges {
+            ages: ImHashMap::new(),
+        }
+    }
+
+    pub fn mutate(&mut self, mutation: &PendingAgeMutation) {
+        match mutation {
+            PendingAgeMutation::SetAge((pending_key, age)) => {
+                self.ages.insert(pending_key.clone(), *age);
+            }
+            PendingAgeMutation::Remove(pending_key) => {
+                let _ = self.ages.remove(pending_key);
+            }
+        }
+    }
+
+    /// Amount of ticks a pending transaction has been waiting for a response.
+    /// Returns 0 for unknown transactions.
+    pub fn get_age(&self, pending_key: &PendingKey) -> usize {
+        self.ages.get(pending_key).cloned().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_pending_ages_basic() {
+        let pk_a = PublicKey::from(&[0xaa; PublicKey::len()]);
+        let currency = Currency::try_from("FST".to_owned()).unwrap();
+        let key_a = (pk_a.clone(), currency.clone(), Uid::from(&[1; Uid::len()]));
+        let key_b = (pk_a, currency, Uid::from(&[2; Uid::len()]));
+
+        let mut pending_ages = PendingAges::new();
+        assert_eq!(pending_ages.get_age(&key_a), 0);
+
+        pending_ages.mutate(&PendingAgeMutation::SetAge((key_a.clone(), 3)));
+        pending_ages.mutate(&PendingAgeMutation::SetAge((key_b.clone(), 1)));
+        assert_eq!(pending_ages.get_age(&key_a), 3);
+        assert_eq!(pending_ages.get_age(&key_b), 1);
+
+        pending_ages.mutate(&PendingAgeMutation::Remove(key_a.clone()));
+        assert_eq!(pending_ages.get_age(&key_a), 0);
+        assert_eq!(pending_ages.get_age(&key_b), 1);
+    }
+}
@@ -27,13 +27,47 @@ impl From<&McBalance> for McBalanceReport {
     }
 }
 
+// The conversions between MoveTokenHashed and MoveTokenHashedReport destructure their input, so
+// that adding a field to either type will not compile until it is handled here.
+
+impl From<MoveTokenHashed> for MoveTokenHashedReport {
+    fn from(move_token_hashed: MoveTokenHashed) -> MoveTokenHashedReport {
+        let MoveTokenHashed {
+            prefix_hash,
+            token_info,
+            rand_nonce,
+            new_token,
+        } = move_token_hashed;
+
+        MoveTokenHashedReport {
+            prefix_hash,
+            token_info,
+            rand_nonce,
+            new_token,
+        }
+    }
+}
+
 impl From<&MoveTokenHashed> for MoveTokenHashedReport {
     fn from(move_token_hashed: &MoveTokenHashed) -> MoveTokenHashedReport {
-        MoveTokenHashedReport {
-            prefix_hash: move_token_hashed.prefix_hash.clone(),
-            token_info: move_token_hashed.token_info.clone(),
-            rand_nonce: move_token_hashed.rand_nonce.clone(),
-            new_token: move_token_hashed.new_token.clone(),
+        MoveTokenHashedReport::from(move_token_hashed.clone())
+    }
+}
+
+impl From<MoveTokenHashedReport> for MoveTokenHashed {
+    fn from(move_token_hashed_report: MoveTokenHashedReport) -> MoveTokenHashed {
+        let MoveTokenHashedReport {
+            prefix_hash,
+            token_info,
+            rand_nonce,
+            new_token,
+        } = move_token_hashed_report;
+
+        MoveTokenHashed {
+            prefix_hash,
+            token_info,
+            rand_nonce,
+            new_token,
         }
     }
 }
@@ -58,6 +92,7 @@ where
                         .balance_for_reset
                         .clone(),
                     opt_remote_reset_terms,
+                    reason: channel_inconsistent.reason.clone(),
                 };
                 ChannelStatusReport::Inconsistent(channel_inconsistent_report)
             }
@@ -85,6 +120,7 @@ where
 fn create_friend_report<B>(
     friend_state: &FriendState<B>,
     friend_liveness: &FriendLivenessReport,
+    idle_ticks: u64,
 ) -> FriendReport<B>
 where
     B: Clone + CanonicalSerialize,
@@ -109,8 +145,9 @@ where
         opt_last_incoming_move_token: friend_state
             .channel_status
             .get_last_incoming_move_token_hashed()
-            .map(|move_token_hashed| MoveTokenHashedReport::from(&move_token_hashed)),
+            .map(MoveTokenHashedReport::from),
         liveness: friend_liveness.clone(),
+        idle_ticks,
         channel_status,
         status: FriendStatusReport::from(&friend_state.status),
     }
@@ -127,7 +164,8 @@ where
         } else {
             FriendLivenessReport::Offline
         };
-        let friend_report = create_friend_report(&friend_state, &friend_liveness);
+        let idle_ticks = ephemeral.idle_ticks.get_idle_ticks(friend_public_key);
+        let friend_report = create_friend_report(&friend_state, &friend_liveness, idle_ticks);
         friends.insert(friend_public_key.clone(), friend_report);
     }
 
@@ -163,7 +201,7 @@ where
                 friend_after
                     .channel_status
                     .get_last_incoming_move_token_hashed()
-                    .map(|move_token_hashed| MoveTokenHashedReport::from(&move_token_hashed)),
+                    .map(MoveTokenHashedReport::from),
             );
             vec![set_channel_status, set_last_incoming_move_token]
         }
@@ -173,6 +211,7 @@ where
         | FriendMutation::PopFrontPendingBackwardsOp
         | FriendMutation::PushBackPendingUserRequest(_)
         | FriendMutation::PopFrontPendingUserRequest
+        | FriendMutation::RemovePendingUserRequest(_)
         | FriendMutation::RemovePendingRequests
         | FriendMutation::RemovePendingRequestsCurrency(_)
         | FriendMutation::RemovePendingUserRequestsCurrency(_) => vec![],
@@ -203,7 +242,7 @@ where
             let opt_move_token_hashed_report = friend_after
                 .channel_status
                 .get_last_incoming_move_token_hashed()
-                .map(|move_token_hashed| MoveTokenHashedReport::from(&move_token_hashed));
+                .map(MoveTokenHashedReport::from);
             let set_last_incoming_move_token =
                 FriendReportMutation::SetOptLastIncomingMoveToken(opt_move_token_hashed_report);
             vec![set_channel_status, set_last_incoming_move_token]
@@ -258,7 +297,7 @@ where
                 opt_last_incoming_move_token: friend_after
                     .channel_status
                     .get_last_incoming_move_token_hashed()
-                    .map(|move_token_hashed| MoveTokenHashedReport::from(&move_token_hashed)),
+                    .map(MoveTokenHashedReport::from),
                 channel_status: ChannelStatusReport::from(&friend_after.channel_status),
             };
             vec![FunderReportMutation::AddFriend(add_friend_report)]
@@ -325,6 +364,12 @@ where
                 ))]
             }
         },
+        // Idle ticks are calculated when a report is created, pending transaction ages, invoice
+        // ages and retransmissions are not reported:
+        EphemeralMutation::IdleTicksMutation(_)
+        | EphemeralMutation::PendingAgeMutation(_)
+        | EphemeralMutation::InvoiceAgeMutation(_)
+        | EphemeralMutation::RetransmitsMutation(_) => Vec::new(),
     }
 }
 
@@ -334,6 +379,8 @@ mod test {
 
     use std::convert::TryFrom;
 
+    use quickcheck_macros::quickcheck;
+
     use crate::types::create_hashed;
 
     use proto::crypto::{PublicKey, RandValue, Signature};
@@ -391,4 +438,11 @@ mod test {
             move_token_hashed_report.new_token
         );
     }
+
+    #[quickcheck]
+    fn qc_move_token_hashed_report_round_trip(move_token_hashed: MoveTokenHashed) -> bool {
+        let move_token_hashed_report = MoveTokenHashedReport::from(&move_token_hashed);
+        move_token_hashed_report == MoveTokenHashedReport::from(move_token_hashed.clone())
+            && MoveTokenHashed::from(move_token_hashed_report) == move_token_hashed
+    }
 }
@@ -0,0 +1,43 @@
+use im::hashmap::HashMap as ImHashMap;
+
+use proto::crypto::PublicKey;
+
+/// Amount of consecutive old move tokens (Duplicates or retransmission requests) received from
+/// every friend, since the last new move token.
+///
+/// Kept in memory only. After a restart all friends begin again from zero.
+#[derive(Clone, Default)]
+pub struct Retransmits {
+    pub counts: ImHashMap<PublicKey, usize>,
+}
+
+#[derive(Debug)]
+pub enum RetransmitsMutation {
+    SetRetransmits((PublicKey, usize)),
+    Remove(PublicKey),
+}
+
+impl Retransmits {
+    pub fn new() -> Retransmits {
+        Retransmits {
+            counts: ImHashMap::new(),
+        }
+    }
+
+    pub fn mutate(&mut self, mutation: &RetransmitsMutation) {
+        match mutation {
+            RetransmitsMutation::SetRetransmits((friend_public_key, retransmits)) => {
+                self.counts.insert(friend_public_key.clone(), *retransmits);
+            }
+            RetransmitsMutation::Remove(friend_public_key) => {
+                let _ = self.counts.remove(friend_public_key);
+            }
+        }
+    }
+
+    /// Amount of consecutive old move tokens received from a friend.
+    /// Returns 0 for unknown friends.
+    pub fn get_retransmits(&self, friend_public_key: &PublicKey) -> usize {
+        self.counts.get(friend_public_key).cloned().unwrap_or(0)
+    }
+}
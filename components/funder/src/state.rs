@@ -8,10 +8,14 @@ use im::vector::Vector as ImVec;
 use common::ser_utils::{ser_b64, ser_map_b64_any, ser_option_b64, ser_string};
 use signature::canonical::CanonicalSerialize;
 
+use crypto::rand::{CryptoRandom, RandGen};
+
 use proto::crypto::{HashedLock, InvoiceId, PaymentId, PlainLock, PublicKey, Uid};
 
 use proto::app_server::messages::NamedRelayAddress;
-use proto::funder::messages::{AddFriend, Currency, Receipt, ResponseSendFundsOp};
+use proto::funder::messages::{
+    AddFriend, Currency, PaymentStatus, PaymentStatusSuccess, Receipt, ResponseSendFundsOp,
+};
 
 use crate::friend::{FriendMutation, FriendState};
 
@@ -79,6 +83,102 @@ pub struct Payment {
     pub stage: PaymentStage,
 }
 
+#[derive(Debug, PartialEq, Eq)]
+pub enum PaymentError {
+    /// The payment has no open transactions left
+    NoOpenTransactions,
+}
+
+impl Payment {
+    /// Amount of open transactions of this payment
+    pub fn num_transactions(&self) -> u64 {
+        match &self.stage {
+            PaymentStage::NewTransactions(new_transactions) => new_transactions.num_transactions,
+            PaymentStage::InProgress(num_transactions)
+            | PaymentStage::Success(num_transactions, _, _)
+            | PaymentStage::AfterSuccessAck(num_transactions) => *num_transactions,
+            PaymentStage::Canceled(_) => 0,
+        }
+    }
+
+    /// Is the outcome of the payment already decided?
+    /// Remaining open transactions can not change the outcome of a terminal payment.
+    pub fn is_terminal(&self) -> bool {
+        match &self.stage {
+            PaymentStage::NewTransactions(_) | PaymentStage::InProgress(_) => false,
+            PaymentStage::Success(..)
+            | PaymentStage::Canceled(_)
+            | PaymentStage::AfterSuccessAck(_) => true,
+        }
+    }
+
+    /// Update the payment after one of its open transactions was removed.
+    /// Returns the updated payment (None if the payment should be removed), and a status to
+    /// report to the user, if any.
+    pub fn decrement_transactions<R>(
+        &self,
+        rng: &R,
+    ) -> Result<(Option<Payment>, Option<PaymentStatus>), PaymentError>
+    where
+        R: CryptoRandom,
+    {
+        let new_num_transactions = self
+            .num_transactions()
+            .checked_sub(1)
+            .ok_or(PaymentError::NoOpenTransactions)?;
+
+        let (opt_new_stage, opt_payment_status) = match &self.stage {
+            PaymentStage::NewTransactions(new_transactions) => {
+                let mut new_new_transactions = new_transactions.clone();
+                new_new_transactions.num_transactions = new_num_transactions;
+                (
+                    Some(PaymentStage::NewTransactions(new_new_transactions)),
+                    None,
+                )
+            }
+            PaymentStage::InProgress(_) => {
+                if new_num_transactions > 0 {
+                    (Some(PaymentStage::InProgress(new_num_transactions)), None)
+                } else {
+                    // The last transaction was removed, the payment is canceled:
+                    let ack_uid = Uid::rand_gen(rng);
+                    (
+                        Some(PaymentStage::Canceled(ack_uid.clone())),
+                        Some(PaymentStatus::Canceled(ack_uid)),
+                    )
+                }
+            }
+            PaymentStage::Success(_, receipt, ack_uid) => (
+                Some(PaymentStage::Success(
+                    new_num_transactions,
+                    receipt.clone(),
+                    ack_uid.clone(),
+                )),
+                Some(PaymentStatus::Success(PaymentStatusSuccess {
+                    receipt: receipt.clone(),
+                    ack_uid: ack_uid.clone(),
+                })),
+            ),
+            // A canceled payment has no open transactions:
+            PaymentStage::Canceled(_) => unreachable!(),
+            PaymentStage::AfterSuccessAck(_) => (
+                if new_num_transactions > 0 {
+                    Some(PaymentStage::AfterSuccessAck(new_num_transactions))
+                } else {
+                    None
+                },
+                None,
+            ),
+        };
+
+        let opt_new_payment = opt_new_stage.map(|stage| Payment {
+            src_plain_lock: self.src_plain_lock.clone(),
+            stage,
+        });
+        Ok((opt_new_payment, opt_payment_status))
+    }
+}
+
 /*
 #[derive(Arbitrary, Clone, Serialize, Deserialize, Debug)]
 pub struct IncomingTransaction {
@@ -106,16 +206,25 @@ pub struct OpenInvoice {
     /// Multiple transactions are possible for a single invoice in case of a multi-route payment.
     // TODO: Add serde hint
     pub incoming_transactions: ImHashSet<Uid>,
+    /// Amount of timer ticks after which this invoice is canceled.
+    #[serde(default)]
+    pub opt_expiry_ticks: Option<u64>,
 }
 
 impl OpenInvoice {
-    pub fn new(currency: Currency, total_dest_payment: u128, dest_plain_lock: PlainLock) -> Self {
+    pub fn new(
+        currency: Currency,
+        total_dest_payment: u128,
+        dest_plain_lock: PlainLock,
+        opt_expiry_ticks: Option<u64>,
+    ) -> Self {
         OpenInvoice {
             currency,
             total_dest_payment,
             dest_plain_lock,
             opt_src_hashed_lock: None,
             incoming_transactions: ImHashSet::new(),
+            opt_expiry_ticks,
         }
     }
 }
@@ -137,9 +246,9 @@ pub enum FunderMutation<B: Clone> {
     RemoveRelay(PublicKey),
     AddFriend(AddFriend<B>),
     RemoveFriend(PublicKey),
-    AddInvoice((InvoiceId, Currency, u128, PlainLock)), // (invoice_id, currency, total_dest_payment, dest_plain_lock)
-    AddIncomingTransaction((InvoiceId, Uid)),           // (invoice_id, request_id)
-    SetInvoiceSrcHashedLock((InvoiceId, HashedLock)),   // (invoice_id, src_hashed_lock)
+    AddInvoice((InvoiceId, Currency, u128, PlainLock, Option<u64>)), // (invoice_id, currency, total_dest_payment, dest_plain_lock, opt_expiry_ticks)
+    AddIncomingTransaction((InvoiceId, Uid)),                        // (invoice_id, request_id)
+    SetInvoiceSrcHashedLock((InvoiceId, HashedLock)), // (invoice_id, src_hashed_lock)
     RemoveInvoice(InvoiceId),
     AddTransaction((Uid, PaymentId)), // (request_id, payment_id)
     SetTransactionResponse(ResponseSendFundsOp), // (request_id, response_send_funds)
@@ -208,6 +317,7 @@ where
                 currency,
                 total_dest_payment,
                 dest_plain_lock,
+                opt_expiry_ticks,
             )) => {
                 self.open_invoices.insert(
                     invoice_id.clone(),
@@ -215,6 +325,7 @@ where
                         currency.clone(),
                         *total_dest_payment,
                         dest_plain_lock.clone(),
+                        *opt_expiry_ticks,
                     ),
                 );
             }
@@ -262,3 +373,143 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryFrom;
+
+    use crypto::test_utils::DummyRandom;
+    use proto::crypto::{HashResult, Signature};
+
+    fn dummy_payment(stage: PaymentStage) -> Payment {
+        Payment {
+            src_plain_lock: PlainLock::from(&[1; PlainLock::len()]),
+            stage,
+        }
+    }
+
+    fn dummy_receipt() -> Receipt {
+        Receipt {
+            response_hash: HashResult::from(&[2; HashResult::len()]),
+            invoice_id: InvoiceId::from(&[3; InvoiceId::len()]),
+            currency: Currency::try_from("FST".to_owned()).unwrap(),
+            src_plain_lock: PlainLock::from(&[1; PlainLock::len()]),
+            dest_plain_lock: PlainLock::from(&[4; PlainLock::len()]),
+            is_complete: true,
+            dest_payment: 10,
+            total_dest_payment: 10,
+            signature: Signature::from(&[5; Signature::len()]),
+        }
+    }
+
+    #[test]
+    fn test_payment_new_transactions() {
+        let rng = DummyRandom::new(&[1u8]);
+        let payment = dummy_payment(PaymentStage::NewTransactions(NewTransactions {
+            num_transactions: 1,
+            invoice_id: InvoiceId::from(&[3; InvoiceId::len()]),
+            currency: Currency::try_from("FST".to_owned()).unwrap(),
+            total_dest_payment: 10,
+            dest_public_key: PublicKey::from(&[6; PublicKey::len()]),
+        }));
+        assert_eq!(payment.num_transactions(), 1);
+        assert!(!payment.is_terminal());
+
+        // User may still add transactions, so the payment stays open:
+        let (opt_new_payment, opt_payment_status) = payment.decrement_transactions(&rng).unwrap();
+        let new_payment = opt_new_payment.unwrap();
+        assert!(opt_payment_status.is_none());
+        assert_eq!(new_payment.num_transactions(), 0);
+        assert!(!new_payment.is_terminal());
+
+        assert_eq!(
+            new_payment.decrement_transactions(&rng),
+            Err(PaymentError::NoOpenTransactions)
+        );
+    }
+
+    #[test]
+    fn test_payment_in_progress() {
+        let rng = DummyRandom::new(&[1u8]);
+        let payment = dummy_payment(PaymentStage::InProgress(2));
+        assert_eq!(payment.num_transactions(), 2);
+        assert!(!payment.is_terminal());
+
+        let (opt_new_payment, opt_payment_status) = payment.decrement_transactions(&rng).unwrap();
+        let new_payment = opt_new_payment.unwrap();
+        assert!(opt_payment_status.is_none());
+        assert_eq!(new_payment.stage, PaymentStage::InProgress(1));
+
+        // Removing the last transaction cancels the payment:
+        let (opt_new_payment, opt_payment_status) =
+            new_payment.decrement_transactions(&rng).unwrap();
+        let new_payment = opt_new_payment.unwrap();
+        let ack_uid = match &new_payment.stage {
+            PaymentStage::Canceled(ack_uid) => ack_uid.clone(),
+            _ => unreachable!(),
+        };
+        assert_eq!(opt_payment_status, Some(PaymentStatus::Canceled(ack_uid)));
+        assert_eq!(new_payment.num_transactions(), 0);
+        assert!(new_payment.is_terminal());
+
+        assert_eq!(
+            new_payment.decrement_transactions(&rng),
+            Err(PaymentError::NoOpenTransactions)
+        );
+    }
+
+    #[test]
+    fn test_payment_success() {
+        let rng = DummyRandom::new(&[1u8]);
+        let ack_uid = Uid::from(&[7; Uid::len()]);
+        let payment = dummy_payment(PaymentStage::Success(1, dummy_receipt(), ack_uid.clone()));
+        assert_eq!(payment.num_transactions(), 1);
+        assert!(payment.is_terminal());
+
+        let (opt_new_payment, opt_payment_status) = payment.decrement_transactions(&rng).unwrap();
+        assert_eq!(
+            opt_new_payment.unwrap().stage,
+            PaymentStage::Success(0, dummy_receipt(), ack_uid.clone())
+        );
+        assert_eq!(
+            opt_payment_status,
+            Some(PaymentStatus::Success(PaymentStatusSuccess {
+                receipt: dummy_receipt(),
+                ack_uid,
+            }))
+        );
+    }
+
+    #[test]
+    fn test_payment_after_success_ack() {
+        let rng = DummyRandom::new(&[1u8]);
+        let payment = dummy_payment(PaymentStage::AfterSuccessAck(2));
+        assert_eq!(payment.num_transactions(), 2);
+        assert!(payment.is_terminal());
+
+        let (opt_new_payment, opt_payment_status) = payment.decrement_transactions(&rng).unwrap();
+        let new_payment = opt_new_payment.unwrap();
+        assert!(opt_payment_status.is_none());
+        assert_eq!(new_payment.stage, PaymentStage::AfterSuccessAck(1));
+
+        // Removing the last transaction removes the payment:
+        let (opt_new_payment, opt_payment_status) =
+            new_payment.decrement_transactions(&rng).unwrap();
+        assert!(opt_new_payment.is_none());
+        assert!(opt_payment_status.is_none());
+    }
+
+    #[test]
+    fn test_payment_canceled() {
+        let rng = DummyRandom::new(&[1u8]);
+        let payment = dummy_payment(PaymentStage::Canceled(Uid::from(&[7; Uid::len()])));
+        assert_eq!(payment.num_transactions(), 0);
+        assert!(payment.is_terminal());
+        assert_eq!(
+            payment.decrement_transactions(&rng),
+            Err(PaymentError::NoOpenTransactions)
+        );
+    }
+}
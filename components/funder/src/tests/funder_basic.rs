@@ -1,5 +1,7 @@
 use std::convert::TryFrom;
 
+use futures::StreamExt;
+
 use common::test_executor::TestExecutor;
 
 use proto::crypto::{InvoiceId, PaymentId, PublicKey, Uid};
@@ -10,6 +12,8 @@ use proto::funder::messages::{
 
 use signature::verify::verify_receipt;
 
+use crate::payment_events::PaymentEvent;
+
 use super::utils::{create_node_controls, dummy_relay_address};
 
 async fn task_funder_basic(test_executor: TestExecutor) {
@@ -98,6 +102,7 @@ async fn task_funder_basic(test_executor: TestExecutor) {
         invoice_id: InvoiceId::from(&[1u8; InvoiceId::len()]),
         currency: currency1.clone(),
         total_dest_payment: 4,
+        opt_expiry_ticks: None,
     };
     node_controls[1]
         .send(FunderControl::AddInvoice(add_invoice))
@@ -164,7 +169,7 @@ async fn task_funder_basic(test_executor: TestExecutor) {
 
     // Invoice was fully paid. We get a commit message that we can send out of band:
     match transaction_result.result {
-        RequestResult::Failure => {}
+        RequestResult::Failure(_) => {}
         _ => unreachable!(),
     };
 
@@ -251,6 +256,28 @@ async fn task_funder_basic(test_executor: TestExecutor) {
 
     // Verify receipt:
     assert!(verify_receipt(&receipt, &public_keys[1]));
+
+    // The payment had two transactions, but only one payment event is emitted:
+    test_executor.wait().await;
+    match node_controls[0].recv_payment_events.next().await.unwrap() {
+        PaymentEvent::Success(payment_success_event) => {
+            assert_eq!(
+                payment_success_event.payment_id,
+                PaymentId::from(&[2u8; PaymentId::len()])
+            );
+            assert_eq!(
+                payment_success_event.invoice_id,
+                InvoiceId::from(&[1u8; InvoiceId::len()])
+            );
+            assert_eq!(payment_success_event.currency, currency1);
+            assert_eq!(payment_success_event.total_dest_payment, 4);
+            assert_eq!(payment_success_event.receipt, receipt);
+        }
+        PaymentEvent::Canceled(_) => unreachable!(),
+    };
+    assert!(node_controls[0].recv_payment_events.try_next().is_err());
+    // The seller made no payments:
+    assert!(node_controls[1].recv_payment_events.try_next().is_err());
 }
 
 #[test]
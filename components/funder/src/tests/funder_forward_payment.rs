@@ -155,6 +155,7 @@ async fn task_funder_forward_payment(test_executor: TestExecutor) {
         invoice_id: InvoiceId::from(&[1u8; InvoiceId::len()]),
         currency: currency1.clone(),
         total_dest_payment: 15,
+        opt_expiry_ticks: None,
     };
     node_controls[2]
         .send(FunderControl::AddInvoice(add_invoice))
@@ -86,6 +86,7 @@ async fn task_funder_inconsistency_basic(test_executor: TestExecutor) {
         invoice_id: InvoiceId::from(&[1u8; InvoiceId::len()]),
         currency: currency1.clone(),
         total_dest_payment: 4,
+        opt_expiry_ticks: None,
     };
     node_controls[1]
         .send(FunderControl::AddInvoice(add_invoice))
@@ -0,0 +1,205 @@
+use std::convert::TryFrom;
+
+use common::test_executor::TestExecutor;
+
+use proto::crypto::{InvoiceId, PaymentId, PublicKey, Uid};
+use proto::funder::messages::{
+    AddInvoice, CreatePayment, CreateTransaction, Currency, FriendStatus, FriendsRoute,
+    FunderControl, PaymentStatus, Rate, RequestResult, RequestsStatus,
+};
+
+use super::utils::{create_node_controls, dummy_relay_address};
+
+async fn task_funder_paused_friend(test_executor: TestExecutor) {
+    let currency1 = Currency::try_from("FST1".to_owned()).unwrap();
+
+    let num_nodes = 2;
+    let mut node_controls = create_node_controls(num_nodes, test_executor.clone()).await;
+
+    let public_keys = node_controls
+        .iter()
+        .map(|nc| nc.public_key.clone())
+        .collect::<Vec<PublicKey>>();
+
+    let relays0 = vec![dummy_relay_address(0)];
+    let relays1 = vec![dummy_relay_address(1)];
+    node_controls[0]
+        .add_friend(&public_keys[1], relays1, "node1")
+        .await;
+    node_controls[1]
+        .add_friend(&public_keys[0], relays0, "node0")
+        .await;
+
+    node_controls[0]
+        .set_friend_status(&public_keys[1], FriendStatus::Enabled)
+        .await;
+    node_controls[1]
+        .set_friend_status(&public_keys[0], FriendStatus::Enabled)
+        .await;
+
+    node_controls[0]
+        .set_friend_currency_rate(&public_keys[1], &currency1, Rate::new())
+        .await;
+    node_controls[1]
+        .set_friend_currency_rate(&public_keys[0], &currency1, Rate::new())
+        .await;
+
+    node_controls[0]
+        .wait_until_currency_active(&public_keys[1], &currency1)
+        .await;
+    node_controls[1]
+        .wait_until_currency_active(&public_keys[0], &currency1)
+        .await;
+
+    node_controls[1]
+        .set_remote_max_debt(&public_keys[0], &currency1, 100)
+        .await;
+
+    node_controls[0]
+        .set_requests_status(&public_keys[1], &currency1, RequestsStatus::Open)
+        .await;
+    node_controls[1]
+        .set_requests_status(&public_keys[0], &currency1, RequestsStatus::Open)
+        .await;
+
+    node_controls[0]
+        .wait_until_ready(&public_keys[1], &currency1)
+        .await;
+    node_controls[1]
+        .wait_until_ready(&public_keys[0], &currency1)
+        .await;
+
+    // Let node 1 open an invoice:
+    let add_invoice = AddInvoice {
+        invoice_id: InvoiceId::from(&[1u8; InvoiceId::len()]),
+        currency: currency1.clone(),
+        total_dest_payment: 4,
+        opt_expiry_ticks: None,
+    };
+    node_controls[1]
+        .send(FunderControl::AddInvoice(add_invoice))
+        .await;
+
+    // Create payment 0 --> 1
+    let create_payment = CreatePayment {
+        payment_id: PaymentId::from(&[2u8; PaymentId::len()]),
+        invoice_id: InvoiceId::from(&[1u8; InvoiceId::len()]),
+        currency: currency1.clone(),
+        total_dest_payment: 4,
+        dest_public_key: public_keys[1].clone(),
+    };
+    node_controls[0]
+        .send(FunderControl::CreatePayment(create_payment))
+        .await;
+
+    let create_transaction = CreateTransaction {
+        payment_id: PaymentId::from(&[2u8; PaymentId::len()]),
+        request_id: Uid::from(&[5u8; Uid::len()]),
+        route: FriendsRoute {
+            public_keys: vec![public_keys[0].clone(), public_keys[1].clone()],
+        },
+        dest_payment: 4,
+        fees: 1,
+    };
+    node_controls[0]
+        .send(FunderControl::CreateTransaction(create_transaction))
+        .await;
+    let transaction_result = node_controls[0]
+        .recv_until_transaction_result()
+        .await
+        .unwrap();
+    let commit = match transaction_result.result {
+        RequestResult::Complete(commit) => commit,
+        _ => unreachable!(),
+    };
+
+    // Node 1 pauses node 0 while the transaction is still in flight:
+    node_controls[1]
+        .set_friend_status(&public_keys[0], FriendStatus::Paused)
+        .await;
+
+    // The in flight transaction can still complete:
+    node_controls[1]
+        .send(FunderControl::CommitInvoice(commit))
+        .await;
+    test_executor.wait().await;
+
+    node_controls[0]
+        .send(FunderControl::RequestClosePayment(PaymentId::from(
+            &[2u8; PaymentId::len()],
+        )))
+        .await;
+    let response_close_payment = node_controls[0]
+        .recv_until_response_close_payment()
+        .await
+        .unwrap();
+    match response_close_payment.status {
+        PaymentStatus::Success(_) => {}
+        _ => unreachable!(),
+    };
+
+    node_controls[0]
+        .wait_friend_balance(&public_keys[1], &currency1, -5)
+        .await;
+    node_controls[1]
+        .wait_friend_balance(&public_keys[0], &currency1, 5)
+        .await;
+
+    // A new payment is refused by node 1:
+    let add_invoice = AddInvoice {
+        invoice_id: InvoiceId::from(&[3u8; InvoiceId::len()]),
+        currency: currency1.clone(),
+        total_dest_payment: 4,
+        opt_expiry_ticks: None,
+    };
+    node_controls[1]
+        .send(FunderControl::AddInvoice(add_invoice))
+        .await;
+
+    let create_payment = CreatePayment {
+        payment_id: PaymentId::from(&[4u8; PaymentId::len()]),
+        invoice_id: InvoiceId::from(&[3u8; InvoiceId::len()]),
+        currency: currency1.clone(),
+        total_dest_payment: 4,
+        dest_public_key: public_keys[1].clone(),
+    };
+    node_controls[0]
+        .send(FunderControl::CreatePayment(create_payment))
+        .await;
+
+    let create_transaction = CreateTransaction {
+        payment_id: PaymentId::from(&[4u8; PaymentId::len()]),
+        request_id: Uid::from(&[6u8; Uid::len()]),
+        route: FriendsRoute {
+            public_keys: vec![public_keys[0].clone(), public_keys[1].clone()],
+        },
+        dest_payment: 4,
+        fees: 1,
+    };
+    node_controls[0]
+        .send(FunderControl::CreateTransaction(create_transaction))
+        .await;
+    let transaction_result = node_controls[0]
+        .recv_until_transaction_result()
+        .await
+        .unwrap();
+    match transaction_result.result {
+        RequestResult::Failure(_) => {}
+        _ => unreachable!(),
+    };
+
+    // Balances did not change:
+    node_controls[0]
+        .wait_friend_balance(&public_keys[1], &currency1, -5)
+        .await;
+    node_controls[1]
+        .wait_friend_balance(&public_keys[0], &currency1, 5)
+        .await;
+}
+
+#[test]
+fn test_funder_paused_friend() {
+    let test_executor = TestExecutor::new();
+    let res = test_executor.run(task_funder_paused_friend(test_executor.clone()));
+    assert!(res.is_output());
+}
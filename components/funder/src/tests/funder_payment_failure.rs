@@ -4,8 +4,8 @@ use common::test_executor::TestExecutor;
 
 use proto::crypto::{InvoiceId, PaymentId, PublicKey, Uid};
 use proto::funder::messages::{
-    AckClosePayment, CreatePayment, CreateTransaction, Currency, FriendStatus, FriendsRoute,
-    FunderControl, PaymentStatus, Rate, RequestResult, RequestsStatus,
+    AckClosePayment, CancelReason, CreatePayment, CreateTransaction, Currency, FriendStatus,
+    FriendsRoute, FunderControl, PaymentStatus, Rate, RequestResult, RequestsStatus,
 };
 
 use super::utils::{create_node_controls, dummy_relay_address};
@@ -182,7 +182,7 @@ async fn task_funder_payment_failure(test_executor: TestExecutor) {
 
     // We expect failure:
     match transaction_result.result {
-        RequestResult::Failure => {}
+        RequestResult::Failure(Some(CancelReason::NoRoute)) => {}
         _ => unreachable!(),
     }
 
@@ -2,6 +2,7 @@ mod funder_basic;
 mod funder_error_command;
 mod funder_forward_payment;
 mod funder_inconsistency_basic;
+mod funder_paused_friend;
 mod funder_payment_failure;
 
 pub mod utils;
@@ -7,7 +7,7 @@ use signature::canonical::CanonicalSerialize;
 use futures::channel::mpsc;
 use futures::stream::select;
 use futures::task::{Spawn, SpawnExt};
-use futures::{future, FutureExt, SinkExt, StreamExt};
+use futures::{future, stream, FutureExt, SinkExt, StreamExt};
 
 use crypto::identity::SoftwareEd25519Identity;
 use crypto::rand::RandGen;
@@ -22,9 +22,9 @@ use proto::report::messages::{
 use proto::app_server::messages::{NamedRelayAddress, RelayAddress};
 use proto::funder::messages::{
     AddFriend, Currency, FriendStatus, FunderControl, FunderIncomingControl, FunderOutgoingControl,
-    Rate, RemoveFriend, RemoveFriendCurrency, RequestsStatus, ResponseClosePayment,
-    SetFriendCurrencyMaxDebt, SetFriendCurrencyRate, SetFriendCurrencyRequestsStatus,
-    SetFriendStatus, TransactionResult,
+    Rate, RemoveFriend, RemoveFriendCurrency, RequestsStatus, ResponseCancelPayment,
+    ResponseClosePayment, ResponseCommitInvoice, ResponseFriendRelays, SetFriendCurrencyMaxDebt,
+    SetFriendCurrencyRate, SetFriendCurrencyRequestsStatus, SetFriendStatus, TransactionResult,
 };
 
 use database::DatabaseClient;
@@ -33,6 +33,7 @@ use identity::{create_identity, IdentityClient};
 
 use crate::ephemeral::Ephemeral;
 use crate::funder::inner_funder_loop;
+use crate::payment_events::PaymentEvent;
 use crate::report::create_report;
 use crate::state::FunderState;
 
@@ -43,6 +44,8 @@ use crate::types::{
 const TEST_MAX_NODE_RELAYS: usize = 16;
 const TEST_MAX_OPERATIONS_IN_BATCH: usize = 16;
 const TEST_MAX_PENDING_USER_REQUESTS: usize = 16;
+const TEST_PENDING_TRANSACTION_TIMEOUT_TICKS: usize = 8;
+const TEST_MAX_MOVE_TOKEN_RETRANSMITS: usize = 8;
 
 // This is required to make sure the tests are not stuck.
 //
@@ -208,6 +211,7 @@ pub struct NodeControl<B: Clone> {
     pub public_key: PublicKey,
     send_control: mpsc::Sender<FunderIncomingControl<B>>,
     recv_control: mpsc::Receiver<FunderOutgoingControl<B>>,
+    pub recv_payment_events: mpsc::Receiver<PaymentEvent>,
     pub report: FunderReport<B>,
     next_app_request_id: u64,
 }
@@ -216,6 +220,9 @@ pub struct NodeControl<B: Clone> {
 pub enum NodeRecv<B: Clone> {
     ReportMutations(FunderReportMutations<B>),
     ResponseClosePayment(ResponseClosePayment),
+    ResponseCommitInvoice(ResponseCommitInvoice),
+    ResponseFriendRelays(ResponseFriendRelays),
+    ResponseCancelPayment(ResponseCancelPayment),
     TransactionResult(TransactionResult),
 }
 
@@ -269,6 +276,15 @@ where
             FunderOutgoingControl::ResponseClosePayment(response_close_payment) => {
                 Some(NodeRecv::ResponseClosePayment(response_close_payment))
             }
+            FunderOutgoingControl::ResponseCommitInvoice(response_commit_invoice) => {
+                Some(NodeRecv::ResponseCommitInvoice(response_commit_invoice))
+            }
+            FunderOutgoingControl::ResponseFriendRelays(response_friend_relays) => {
+                Some(NodeRecv::ResponseFriendRelays(response_friend_relays))
+            }
+            FunderOutgoingControl::ResponseCancelPayment(response_cancel_payment) => {
+                Some(NodeRecv::ResponseCancelPayment(response_cancel_payment))
+            }
             FunderOutgoingControl::TransactionResult(transaction_result) => {
                 Some(NodeRecv::TransactionResult(transaction_result))
             }
@@ -284,6 +300,9 @@ where
                 NodeRecv::ReportMutations(_) => {}
                 NodeRecv::TransactionResult(_) => unreachable!(),
                 NodeRecv::ResponseClosePayment(_) => unreachable!(),
+                NodeRecv::ResponseCommitInvoice(_) => {}
+                NodeRecv::ResponseFriendRelays(_) => {}
+                NodeRecv::ResponseCancelPayment(_) => {}
             };
         }
     }
@@ -294,6 +313,9 @@ where
                 NodeRecv::ReportMutations(_) => {}
                 NodeRecv::TransactionResult(transaction_result) => return Some(transaction_result),
                 NodeRecv::ResponseClosePayment(_) => {}
+                NodeRecv::ResponseCommitInvoice(_) => {}
+                NodeRecv::ResponseFriendRelays(_) => {}
+                NodeRecv::ResponseCancelPayment(_) => {}
             };
         }
     }
@@ -306,6 +328,9 @@ where
                 NodeRecv::ResponseClosePayment(response_close_payment) => {
                     return Some(response_close_payment)
                 }
+                NodeRecv::ResponseCommitInvoice(_) => {}
+                NodeRecv::ResponseFriendRelays(_) => {}
+                NodeRecv::ResponseCancelPayment(_) => {}
             };
         }
     }
@@ -581,11 +606,15 @@ where
         let (send_comm, incoming_comm) = mpsc::channel(CHANNEL_SIZE);
         let (comm_sender, recv_comm) = mpsc::channel(CHANNEL_SIZE);
 
+        let (payment_event_sender, recv_payment_events) = mpsc::channel(CHANNEL_SIZE);
+
         let funder_fut = inner_funder_loop(
             identity_client.clone(),
             DummyRandom::new(&[i as u8]),
             incoming_control,
             incoming_comm,
+            // Time never passes in these tests:
+            stream::pending::<()>(),
             control_sender,
             comm_sender,
             funder_state,
@@ -593,6 +622,9 @@ where
             TEST_MAX_NODE_RELAYS,
             TEST_MAX_OPERATIONS_IN_BATCH,
             TEST_MAX_PENDING_USER_REQUESTS,
+            TEST_PENDING_TRANSACTION_TIMEOUT_TICKS,
+            TEST_MAX_MOVE_TOKEN_RETRANSMITS,
+            Some(payment_event_sender),
             None,
         );
 
@@ -618,6 +650,7 @@ where
             public_key: identity_client.request_public_key().await.unwrap(),
             send_control,
             recv_control,
+            recv_payment_events,
             report: base_report,
             next_app_request_id: 0,
         });
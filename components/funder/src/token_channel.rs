@@ -594,7 +594,7 @@ impl<'a> TcInBorrow<'a> {
 
         let tc_in_borrow = token_channel.get_incoming().unwrap();
 
-        let mut balances: Vec<_> = tc_in_borrow
+        let balances: Vec<_> = tc_in_borrow
             .mutual_credits
             .iter()
             .map(|(currency, mutual_credit)| CurrencyBalanceInfo {
@@ -607,18 +607,16 @@ impl<'a> TcInBorrow<'a> {
             })
             .collect();
 
-        // Canonicalize balances:
-        balances.sort_by(|cbi1, cbi2| cbi1.currency.cmp(&cbi2.currency));
-
         let tc_in_borrow = token_channel.get_incoming().unwrap();
         let cur_token_info = &tc_in_borrow.tc_incoming.move_token_in.token_info;
 
         let token_info = TokenInfo {
-            mc: McInfo {
-                local_public_key: cur_token_info.mc.remote_public_key.clone(),
-                remote_public_key: cur_token_info.mc.local_public_key.clone(),
+            // Balances are canonicalized (sorted by currency) by McInfo::new():
+            mc: McInfo::new(
+                cur_token_info.mc.remote_public_key.clone(),
+                cur_token_info.mc.local_public_key.clone(),
                 balances,
-            },
+            ),
             counters: CountersInfo {
                 move_token_counter: cur_token_info.counters.move_token_counter.wrapping_add(1),
                 inconsistency_counter: cur_token_info.counters.inconsistency_counter,
@@ -847,9 +845,23 @@ where
         }
         .flip();
 
-        // Verify stated balances:
+        // Verify stated balances and counters:
         let info_hash = hash_token_info(&expected_token_info);
         if new_move_token.info_hash != info_hash {
+            // The move token counter must strictly advance. Check if the remote side reused the
+            // counter of one of the last two move tokens:
+            let cur_counter = tc_out_borrow
+                .tc_outgoing
+                .token_info
+                .counters
+                .move_token_counter;
+            for stale_counter in &[cur_counter, cur_counter.wrapping_sub(1)] {
+                let mut stale_token_info = expected_token_info.clone();
+                stale_token_info.counters.move_token_counter = *stale_counter;
+                if new_move_token.info_hash == hash_token_info(&stale_token_info) {
+                    return Err(ReceiveMoveTokenError::InvalidMoveTokenCounter);
+                }
+            }
             return Err(ReceiveMoveTokenError::InvalidTokenInfo);
         }
 
@@ -1046,6 +1058,55 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_remote_currency_not_configured_locally() {
+        let currency1 = Currency::try_from("FST".to_owned()).unwrap();
+        let currency2 = Currency::try_from("XYZ".to_owned()).unwrap();
+
+        let rng1 = DummyRandom::new(&[1u8]);
+        let pkcs8 = PrivateKey::rand_gen(&rng1);
+        let identity1 = SoftwareEd25519Identity::from_private_key(&pkcs8).unwrap();
+
+        let rng2 = DummyRandom::new(&[2u8]);
+        let pkcs8 = PrivateKey::rand_gen(&rng2);
+        let identity2 = SoftwareEd25519Identity::from_private_key(&pkcs8).unwrap();
+
+        let (identity1, identity2) = sort_sides(identity1, identity2);
+
+        let pk1 = identity1.get_public_key();
+        let pk2 = identity2.get_public_key();
+        let mut tc1 = TokenChannel::<u32>::new(&pk1, &pk2); // (local, remote)
+        let mut tc2 = TokenChannel::<u32>::new(&pk2, &pk1); // (local, remote)
+
+        // tc2 proposes two currencies, but tc1 has not configured any currency yet:
+        add_currencies(
+            &identity1,
+            &identity2,
+            &mut tc1,
+            &mut tc2,
+            &[currency1.clone(), currency2.clone()],
+        );
+        assert!(tc1.get_mutual_credits().is_empty());
+        assert!(tc2.get_mutual_credits().is_empty());
+
+        // tc1 only configures currency1:
+        add_currencies(
+            &identity2,
+            &identity1,
+            &mut tc2,
+            &mut tc1,
+            &[currency1.clone()],
+        );
+
+        // Only currency1 is activated. currency2 was never configured by tc1:
+        for tc in &[&tc1, &tc2] {
+            let mutual_credits = tc.get_mutual_credits();
+            assert_eq!(mutual_credits.len(), 1);
+            assert!(mutual_credits.contains_key(&currency1));
+            assert!(!mutual_credits.contains_key(&currency2));
+        }
+    }
+
     /*
     /// Before: tc1: outgoing, tc2: incoming
     /// Send SetRemoteMaxDebt: tc2 -> tc1
@@ -1172,6 +1233,77 @@ mod tests {
     }
     */
 
+    #[test]
+    fn test_simulate_receive_move_token_stale_counter() {
+        let currency = Currency::try_from("FST".to_owned()).unwrap();
+
+        let rng1 = DummyRandom::new(&[1u8]);
+        let pkcs8 = PrivateKey::rand_gen(&rng1);
+        let identity1 = SoftwareEd25519Identity::from_private_key(&pkcs8).unwrap();
+
+        let rng2 = DummyRandom::new(&[2u8]);
+        let pkcs8 = PrivateKey::rand_gen(&rng2);
+        let identity2 = SoftwareEd25519Identity::from_private_key(&pkcs8).unwrap();
+
+        let (identity1, identity2) = sort_sides(identity1, identity2);
+
+        let pk1 = identity1.get_public_key();
+        let pk2 = identity2.get_public_key();
+        let mut tc1 = TokenChannel::<u32>::new(&pk1, &pk2); // (local, remote)
+        let mut tc2 = TokenChannel::<u32>::new(&pk2, &pk1); // (local, remote)
+
+        add_currencies(
+            &identity1,
+            &identity2,
+            &mut tc1,
+            &mut tc2,
+            &[currency.clone()],
+        );
+        add_currencies(
+            &identity2,
+            &identity1,
+            &mut tc2,
+            &mut tc1,
+            &[currency.clone()],
+        );
+
+        // Current state:  tc1 --> tc2
+        // tc1: outgoing
+        // tc2: incoming
+        let SendMoveTokenOutput {
+            unsigned_move_token,
+            token_info,
+            ..
+        } = tc2
+            .get_incoming()
+            .unwrap()
+            .simulate_send_move_token(vec![], None, None, RandValue::from(&[8; RandValue::len()]))
+            .unwrap();
+
+        // tc2 uses a decremented move token counter:
+        let mut stale_token_info = token_info;
+        stale_token_info.counters.move_token_counter = stale_token_info
+            .counters
+            .move_token_counter
+            .checked_sub(2)
+            .unwrap();
+        let mut stale_unsigned_move_token = unsigned_move_token.clone();
+        stale_unsigned_move_token.info_hash = hash_token_info(&stale_token_info);
+        let stale_move_token = dummy_sign_move_token(stale_unsigned_move_token, &identity2);
+
+        match tc1.simulate_receive_move_token(stale_move_token, &ImHashMap::new()) {
+            Err(ReceiveMoveTokenError::InvalidMoveTokenCounter) => {}
+            _ => unreachable!(),
+        };
+
+        // The correct move token is accepted:
+        let move_token = dummy_sign_move_token(unsigned_move_token, &identity2);
+        match tc1.simulate_receive_move_token(move_token, &ImHashMap::new()) {
+            Ok(ReceiveMoveTokenOutput::Received(_)) => {}
+            _ => unreachable!(),
+        };
+    }
+
     /// This tests sends a SetRemoteMaxDebt(100) in both ways.
     #[test]
     fn test_simulate_receive_move_token_basic() {
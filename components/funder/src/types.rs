@@ -6,8 +6,8 @@ use proto::crypto::{HashResult, HashedLock, PublicKey, RandValue, Signature, Uid
 
 use proto::app_server::messages::RelayAddress;
 use proto::funder::messages::{
-    CancelSendFundsOp, ChannelerUpdateFriend, Currency, CurrencyOperations, FriendMessage,
-    FunderIncomingControl, FunderOutgoingControl, MoveToken, PendingTransaction,
+    CancelReason, CancelSendFundsOp, ChannelerUpdateFriend, Currency, CurrencyOperations,
+    FriendMessage, FunderIncomingControl, FunderOutgoingControl, MoveToken, PendingTransaction,
     RequestSendFundsOp, ResponseSendFundsOp, TokenInfo, TransactionStage, UnsignedMoveToken,
     UnsignedResponseSendFundsOp,
 };
@@ -76,8 +76,11 @@ pub async fn create_response_send_funds<'a>(
     }
 }
 
-pub fn create_cancel_send_funds(request_id: Uid) -> CancelSendFundsOp {
-    CancelSendFundsOp { request_id }
+pub fn create_cancel_send_funds(request_id: Uid, reason: CancelReason) -> CancelSendFundsOp {
+    CancelSendFundsOp {
+        request_id,
+        opt_reason: Some(reason),
+    }
 }
 
 pub fn create_pending_transaction(request_send_funds: &RequestSendFundsOp) -> PendingTransaction {
@@ -221,6 +224,8 @@ pub enum FunderIncoming<B> {
     Init,
     Control(FunderIncomingControl<B>),
     Comm(FunderIncomingComm<B>),
+    /// Time has passed
+    TimerTick,
 }
 
 #[allow(clippy::large_enum_variant)]
@@ -1,6 +1,7 @@
 use std::collections::VecDeque;
 use std::fmt::Debug;
 use std::marker::Unpin;
+use std::mem;
 
 use futures::channel::{mpsc, oneshot};
 use futures::task::{Spawn, SpawnExt};
@@ -88,6 +89,10 @@ struct ServerConnected<ISA> {
     /// Decrementing counter. When reaches 0 we send a SendMutations
     /// to the server and reset this value to keepalive_ticks:
     ticks_to_send_keepalive: usize,
+    /// Mutations waiting to be sent to the server, coalesced per friend and currency.
+    pending_mutations: Vec<IndexMutation>,
+    /// Decrementing counter. When reaches 0 we send all pending mutations to the server.
+    ticks_to_send_mutations: usize,
 }
 
 #[derive(Debug)]
@@ -131,6 +136,7 @@ struct IndexClient<ISA, TAS, ICS, S> {
     num_open_requests: usize,
     keepalive_ticks: usize,
     backoff_ticks: usize,
+    mutations_batch_ticks: usize,
     conn_status: ConnStatus<ISA>,
     db_client: DatabaseClient<IndexClientConfigMutation<ISA>>,
     spawner: S,
@@ -178,6 +184,31 @@ async fn send_full_state(
     Ok(())
 }
 
+/// Add a mutation to a list of pending mutations.
+/// A pending mutation for the same friend and currency is replaced, so that only the latest
+/// state is sent.
+fn coalesce_mutation(pending_mutations: &mut Vec<IndexMutation>, mutation: IndexMutation) {
+    let mutation_key = |mutation: &IndexMutation| match mutation {
+        IndexMutation::UpdateFriendCurrency(update_friend_currency) => (
+            update_friend_currency.public_key.clone(),
+            update_friend_currency.currency.clone(),
+        ),
+        IndexMutation::RemoveFriendCurrency(remove_friend_currency) => (
+            remove_friend_currency.public_key.clone(),
+            remove_friend_currency.currency.clone(),
+        ),
+    };
+
+    let key = mutation_key(&mutation);
+    match pending_mutations
+        .iter_mut()
+        .find(|pending_mutation| mutation_key(pending_mutation) == key)
+    {
+        Some(pending_mutation) => *pending_mutation = mutation,
+        None => pending_mutations.push(mutation),
+    }
+}
+
 impl<ISA, TAS, ICS, S> IndexClient<ISA, TAS, ICS, S>
 where
     ISA: Debug + Eq + Clone + Send + 'static,
@@ -197,6 +228,7 @@ where
         max_open_requests: usize,
         keepalive_ticks: usize,
         backoff_ticks: usize,
+        mutations_batch_ticks: usize,
         db_client: DatabaseClient<IndexClientConfigMutation<ISA>>,
         spawner: S,
     ) -> Self {
@@ -219,6 +251,7 @@ where
             num_open_requests: 0,
             keepalive_ticks,
             backoff_ticks,
+            mutations_batch_ticks,
             conn_status: ConnStatus::Empty(backoff_ticks),
             db_client,
             spawner,
@@ -321,10 +354,16 @@ where
         &mut self,
         request_id: Uid,
     ) -> Result<(), IndexClientError> {
-        let client_response_routes = ClientResponseRoutes {
-            request_id,
-            result: ResponseRoutesResult::Failure,
-        };
+        self.return_response_routes(request_id, ResponseRoutesResult::Failure)
+            .await
+    }
+
+    pub async fn return_response_routes(
+        &mut self,
+        request_id: Uid,
+        result: ResponseRoutesResult,
+    ) -> Result<(), IndexClientError> {
+        let client_response_routes = ClientResponseRoutes { request_id, result };
         self.to_app_server
             .send(IndexClientToAppServer::ResponseRoutes(
                 client_response_routes,
@@ -452,11 +491,15 @@ where
                 .await;
         }
 
-        // Check server connection status:
+        // Check server connection status.
+        // If no server is connected, we let the app know immediately instead of waiting:
         let mut server_connected = match &mut self.conn_status {
             ConnStatus::Empty(_) | ConnStatus::Connecting(_) => {
                 return self
-                    .return_response_routes_failure(request_routes.request_id)
+                    .return_response_routes(
+                        request_routes.request_id,
+                        ResponseRoutesResult::NoIndexServer,
+                    )
                     .await
             }
             ConnStatus::Connected(server_connected) => server_connected,
@@ -502,18 +545,11 @@ where
             .map_err(|_| IndexClientError::SpawnError)
     }
 
-    pub async fn handle_from_app_server_apply_mutations(
+    /// Send mutations to the server, together with the state of one friend chosen sequentially.
+    async fn send_mutations(
         &mut self,
         mut mutations: Vec<IndexMutation>,
     ) -> Result<(), IndexClientError> {
-        // Update state:
-        for mutation in &mutations {
-            self.seq_friends_client
-                .mutate(mutation.clone())
-                .await
-                .map_err(|_| IndexClientError::SeqFriendsError)?;
-        }
-
         // Check if server is ready:
         let server_connected = match &mut self.conn_status {
             ConnStatus::Empty(_) | ConnStatus::Connecting(_) => return Ok(()), // Server is not ready
@@ -551,6 +587,39 @@ where
         Ok(())
     }
 
+    pub async fn handle_from_app_server_apply_mutations(
+        &mut self,
+        mutations: Vec<IndexMutation>,
+    ) -> Result<(), IndexClientError> {
+        // Update state:
+        for mutation in &mutations {
+            self.seq_friends_client
+                .mutate(mutation.clone())
+                .await
+                .map_err(|_| IndexClientError::SeqFriendsError)?;
+        }
+
+        if self.mutations_batch_ticks == 0 {
+            return self.send_mutations(mutations).await;
+        }
+
+        // Check if server is ready:
+        let server_connected = match &mut self.conn_status {
+            ConnStatus::Empty(_) | ConnStatus::Connecting(_) => return Ok(()), // Server is not ready
+            ConnStatus::Connected(server_connected) => server_connected,
+        };
+
+        // Start a new batch window, unless one is already open:
+        if server_connected.pending_mutations.is_empty() {
+            server_connected.ticks_to_send_mutations = self.mutations_batch_ticks;
+        }
+        for mutation in mutations {
+            coalesce_mutation(&mut server_connected.pending_mutations, mutation);
+        }
+
+        Ok(())
+    }
+
     pub async fn handle_from_app_server(
         &mut self,
         app_server_to_index_client: AppServerToIndexClient<ISA>,
@@ -605,6 +674,8 @@ where
             opt_control_sender: Some(control_sender.clone()),
             opt_cancel_sender,
             ticks_to_send_keepalive: self.keepalive_ticks,
+            pending_mutations: Vec::new(),
+            ticks_to_send_mutations: 0,
         });
 
         // Send report:
@@ -678,6 +749,16 @@ where
             ConnStatus::Connected(ref mut server_connected) => server_connected,
         };
 
+        // Send pending mutations once their batch window is over:
+        if !server_connected.pending_mutations.is_empty() {
+            server_connected.ticks_to_send_mutations =
+                server_connected.ticks_to_send_mutations.saturating_sub(1);
+            if server_connected.ticks_to_send_mutations == 0 {
+                let mutations = mem::take(&mut server_connected.pending_mutations);
+                return self.send_mutations(mutations).await;
+            }
+        }
+
         let mut control_sender = match server_connected.opt_control_sender.take() {
             Some(control_sender) => control_sender,
             None => return Ok(()), // Not connected to server
@@ -732,6 +813,7 @@ pub async fn index_client_loop<ISA, FAS, TAS, ICS, TS, S>(
     max_open_requests: usize,
     keepalive_ticks: usize,
     backoff_ticks: usize,
+    mutations_batch_ticks: usize,
     db_client: DatabaseClient<IndexClientConfigMutation<ISA>>,
     timer_stream: TS,
     spawner: S,
@@ -757,6 +839,7 @@ where
         max_open_requests,
         keepalive_ticks,
         backoff_ticks,
+        mutations_batch_ticks,
         db_client,
         spawner,
     );
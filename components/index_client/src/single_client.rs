@@ -354,6 +354,7 @@ mod tests {
             source: PublicKey::from(&[0xcc; PublicKey::len()]),
             destination: PublicKey::from(&[0xdd; PublicKey::len()]),
             opt_exclude: None,
+            opt_max_hops: None,
         };
 
         let (response_sender, response_receiver) = oneshot::channel();
@@ -119,6 +119,7 @@ pub async fn spawn_index_client<ISA, C, R, S>(
     max_open_index_client_requests: usize,
     keepalive_ticks: usize,
     backoff_ticks: usize,
+    mutations_batch_ticks: usize,
     index_connector: C,
     rng: R,
     spawner: S,
@@ -160,6 +161,7 @@ where
         max_open_index_client_requests,
         keepalive_ticks,
         backoff_ticks,
+        mutations_batch_ticks,
         database_client,
         timer_stream,
         spawner.clone(),
@@ -42,21 +42,31 @@ struct IndexClientControl<ISA> {
 }
 
 /// Create a basic IndexClientControl, used for testing
-fn basic_index_client<S>(spawner: S) -> IndexClientControl<u32>
+fn basic_index_client<S>(spawner: S, mutations_batch_ticks: usize) -> IndexClientControl<u32>
 where
     S: Spawn + Clone + Send + 'static,
 {
-    let (app_server_sender, from_app_server) = mpsc::channel(1);
-    let (to_app_server, app_server_receiver) = mpsc::channel(1);
-
     let index_server37 = NamedIndexServerAddress {
         public_key: PublicKey::from(&[0x37; PublicKey::len()]),
         address: 0x1337u32,
         name: "0x1337".to_owned(),
     };
-    let index_client_config = IndexClientConfig {
-        index_servers: vec![index_server37],
-    };
+    index_client_with_servers(spawner, mutations_batch_ticks, vec![index_server37])
+}
+
+/// Create an IndexClientControl with the given configured index servers, used for testing
+fn index_client_with_servers<S>(
+    spawner: S,
+    mutations_batch_ticks: usize,
+    index_servers: Vec<NamedIndexServerAddress<u32>>,
+) -> IndexClientControl<u32>
+where
+    S: Spawn + Clone + Send + 'static,
+{
+    let (app_server_sender, from_app_server) = mpsc::channel(1);
+    let (to_app_server, app_server_receiver) = mpsc::channel(1);
+
+    let index_client_config = IndexClientConfig { index_servers };
 
     let (seq_friends_sender, seq_friends_receiver) = mpsc::channel(0);
     let seq_friends_client = SeqFriendsClient::new(seq_friends_sender);
@@ -82,6 +92,7 @@ where
         max_open_requests,
         keepalive_ticks,
         backoff_ticks,
+        mutations_batch_ticks,
         db_client,
         timer_stream,
         spawner.clone(),
@@ -259,7 +270,7 @@ async fn task_index_client_loop_add_remove_index_server<S>(spawner: S)
 where
     S: Spawn + Clone + Send + 'static,
 {
-    let mut icc = basic_index_client(spawner.clone());
+    let mut icc = basic_index_client(spawner.clone(), 0);
 
     let index_server = IndexServerAddress {
         public_key: PublicKey::from(&[0x37; PublicKey::len()]),
@@ -331,7 +342,7 @@ where
 {
     let currency = Currency::try_from("FST".to_owned()).unwrap();
 
-    let mut icc = basic_index_client(spawner.clone());
+    let mut icc = basic_index_client(spawner.clone(), 0);
     let index_server = IndexServerAddress {
         public_key: PublicKey::from(&[0x37; PublicKey::len()]),
         address: 0x1337,
@@ -394,12 +405,82 @@ fn test_index_client_loop_apply_mutations() {
     block_on(task_index_client_loop_apply_mutations(thread_pool.clone()));
 }
 
+async fn task_index_client_loop_apply_mutations_batch<S>(spawner: S)
+where
+    S: Spawn + Clone + Send + 'static,
+{
+    let currency = Currency::try_from("FST".to_owned()).unwrap();
+    let mutations_batch_ticks = 2;
+
+    let mut icc = basic_index_client(spawner.clone(), mutations_batch_ticks);
+    let index_server = IndexServerAddress {
+        public_key: PublicKey::from(&[0x37; PublicKey::len()]),
+        address: 0x1337,
+    };
+    let (mut control_receiver, _close_sender) = icc.expect_server_connection(index_server).await;
+
+    // Rapid capacity changes for the same friend:
+    let mut index_mutations = Vec::new();
+    for recv_capacity in &[100, 80, 60] {
+        let update_friend_currency = UpdateFriendCurrency {
+            public_key: PublicKey::from(&[0xbb; PublicKey::len()]),
+            currency: currency.clone(),
+            recv_capacity: *recv_capacity,
+            rate: Rate { mul: 0, add: 1 },
+        };
+        let index_mutation = IndexMutation::UpdateFriendCurrency(update_friend_currency);
+        icc.app_server_sender
+            .send(AppServerToIndexClient::ApplyMutations(vec![
+                index_mutation.clone()
+            ]))
+            .await
+            .unwrap();
+
+        // Every mutation is applied to seq_friends:
+        match icc.seq_friends_receiver.next().await.unwrap() {
+            SeqFriendsRequest::Mutate(index_mutation0, response_sender) => {
+                assert_eq!(index_mutation0, index_mutation);
+                response_sender.send(()).unwrap();
+            }
+            _ => unreachable!(),
+        };
+        index_mutations.push(index_mutation);
+    }
+
+    for _ in 0..mutations_batch_ticks {
+        icc.tick_sender.send(()).await.unwrap();
+    }
+
+    match icc.seq_friends_receiver.next().await.unwrap() {
+        SeqFriendsRequest::NextUpdate(response_sender) => {
+            response_sender.send(None).unwrap();
+        }
+        _ => unreachable!(),
+    };
+
+    // Only the latest mutation is sent:
+    match control_receiver.next().await.unwrap() {
+        SingleClientControl::SendMutations(mutations0) => {
+            assert_eq!(mutations0, vec![index_mutations.pop().unwrap()]);
+        }
+        _ => unreachable!(),
+    };
+}
+
+#[test]
+fn test_index_client_loop_apply_mutations_batch() {
+    let thread_pool = ThreadPool::new().unwrap();
+    block_on(task_index_client_loop_apply_mutations_batch(
+        thread_pool.clone(),
+    ));
+}
+
 async fn task_index_client_loop_request_routes_basic<S>(spawner: S)
 where
     S: Spawn + Clone + Send + 'static,
 {
     let currency = Currency::try_from("FST".to_owned()).unwrap();
-    let mut icc = basic_index_client(spawner.clone());
+    let mut icc = basic_index_client(spawner.clone(), 0);
     let index_server = IndexServerAddress {
         public_key: PublicKey::from(&[0x37; PublicKey::len()]),
         address: 0x1337,
@@ -413,6 +494,7 @@ where
         source: PublicKey::from(PublicKey::from(&[0xee; PublicKey::len()])),
         destination: PublicKey::from(PublicKey::from(&[0xff; PublicKey::len()])),
         opt_exclude: None,
+        opt_max_hops: None,
     };
 
     // Request routes from IndexClient (From AppServer):
@@ -477,7 +559,7 @@ where
     S: Spawn + Clone + Send + 'static,
 {
     let currency = Currency::try_from("FST".to_owned()).unwrap();
-    let mut icc = basic_index_client(spawner.clone());
+    let mut icc = basic_index_client(spawner.clone(), 0);
 
     // Wait for a connection request:
     let session_conn_request = icc.session_receiver.next().await.unwrap();
@@ -517,7 +599,7 @@ where
     };
 
     // During the "Connecting" state we expect that IndexClient
-    // will return a failure for RequestRoutes messages:
+    // will immediately report that no index server is connected for RequestRoutes messages:
 
     let request_routes = RequestRoutes {
         request_id: Uid::from(&[3; Uid::len()]),
@@ -526,6 +608,7 @@ where
         source: PublicKey::from(PublicKey::from(&[0xee; PublicKey::len()])),
         destination: PublicKey::from(PublicKey::from(&[0xff; PublicKey::len()])),
         opt_exclude: None,
+        opt_max_hops: None,
     };
 
     // Request routes from IndexClient (From AppServer):
@@ -550,7 +633,7 @@ where
         _ => unreachable!(),
     };
 
-    // IndexClient returns failure in ResponseRoutes:
+    // IndexClient returns NoIndexServer in ResponseRoutes:
     match icc.app_server_receiver.next().await.unwrap() {
         IndexClientToAppServer::ResponseRoutes(client_response_routes) => {
             assert_eq!(
@@ -558,7 +641,7 @@ where
                 Uid::from(&[3; Uid::len()])
             );
             match client_response_routes.result {
-                ResponseRoutesResult::Failure => {}
+                ResponseRoutesResult::NoIndexServer => {}
                 _ => unreachable!(),
             };
         }
@@ -581,4 +664,66 @@ fn test_index_client_loop_connecting_state() {
     block_on(task_index_client_loop_connecting_state(thread_pool.clone()));
 }
 
+async fn task_index_client_loop_no_index_servers<S>(spawner: S)
+where
+    S: Spawn + Clone + Send + 'static,
+{
+    let currency = Currency::try_from("FST".to_owned()).unwrap();
+    // No index servers are configured:
+    let mut icc = index_client_with_servers(spawner.clone(), 0, Vec::new());
+
+    let request_routes = RequestRoutes {
+        request_id: Uid::from(&[3; Uid::len()]),
+        currency,
+        capacity: 250,
+        source: PublicKey::from(&[0xee; PublicKey::len()]),
+        destination: PublicKey::from(&[0xff; PublicKey::len()]),
+        opt_exclude: None,
+        opt_max_hops: None,
+    };
+
+    // Request routes from IndexClient (From AppServer):
+    let app_server_to_index_client = AppServerToIndexClient::AppRequest((
+        Uid::from(&[51; Uid::len()]),
+        IndexClientRequest::RequestRoutes(request_routes),
+    ));
+    icc.app_server_sender
+        .send(app_server_to_index_client)
+        .await
+        .unwrap();
+
+    // Expect empty report mutations:
+    match icc.app_server_receiver.next().await.unwrap() {
+        IndexClientToAppServer::ReportMutations(ic_report_mutations) => {
+            assert_eq!(
+                ic_report_mutations.opt_app_request_id,
+                Some(Uid::from(&[51; Uid::len()]))
+            );
+            assert!(ic_report_mutations.mutations.is_empty());
+        }
+        _ => unreachable!(),
+    };
+
+    // IndexClient responds immediately (Without any time ticks) with NoIndexServer:
+    match icc.app_server_receiver.next().await.unwrap() {
+        IndexClientToAppServer::ResponseRoutes(client_response_routes) => {
+            assert_eq!(
+                client_response_routes.request_id,
+                Uid::from(&[3; Uid::len()])
+            );
+            assert_eq!(
+                client_response_routes.result,
+                ResponseRoutesResult::NoIndexServer
+            );
+        }
+        _ => unreachable!(),
+    };
+}
+
+#[test]
+fn test_index_client_loop_no_index_servers() {
+    let thread_pool = ThreadPool::new().unwrap();
+    block_on(task_index_client_loop_no_index_servers(thread_pool.clone()));
+}
+
 // TODO: Add more tests.
@@ -1,7 +1,12 @@
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::{cmp, hash};
 
-fn bfs_loop<'c, I, N, F>(src: &'c N, dst: &'c N, get_neighbors: F) -> Option<HashMap<N, Option<N>>>
+fn bfs_loop<'c, I, N, F>(
+    src: &'c N,
+    dst: &'c N,
+    get_neighbors: F,
+    opt_max_hops: Option<usize>,
+) -> Option<HashMap<N, Option<N>>>
 where
     I: Iterator<Item = &'c N>,
     F: Fn(&N) -> I,
@@ -9,13 +14,20 @@ where
 {
     let mut backtrack: HashMap<N, Option<N>> = HashMap::new();
     let mut visited: HashSet<N> = HashSet::new();
-    let mut queue: VecDeque<N> = VecDeque::new();
+    // Every queued node is kept together with its distance (in hops) from src:
+    let mut queue: VecDeque<(N, usize)> = VecDeque::new();
 
     backtrack.insert(src.clone(), None);
-    queue.push_back(src.clone());
+    queue.push_back((src.clone(), 0));
     visited.insert(src.clone());
 
-    while let Some(node) = queue.pop_front() {
+    while let Some((node, hops)) = queue.pop_front() {
+        if let Some(max_hops) = opt_max_hops {
+            if hops >= max_hops {
+                // Continuing from this node will create a route that is too long:
+                continue;
+            }
+        }
         for neighbor in get_neighbors(&node) {
             if visited.contains(&neighbor) {
                 continue;
@@ -24,7 +36,7 @@ where
             if neighbor == dst {
                 return Some(backtrack);
             }
-            queue.push_back(neighbor.clone());
+            queue.push_back((neighbor.clone(), hops + 1));
             visited.insert(neighbor.clone());
         }
     }
@@ -49,13 +61,20 @@ where
     Some(route)
 }
 
-pub fn bfs<'c, I, N, F>(src: &'c N, dst: &'c N, get_neighbors: F) -> Option<Vec<N>>
+/// Find a shortest route from src to dst.
+/// If `opt_max_hops` is provided, only routes of at most `max_hops` hops (edges) are considered.
+pub fn bfs<'c, I, N, F>(
+    src: &'c N,
+    dst: &'c N,
+    get_neighbors: F,
+    opt_max_hops: Option<usize>,
+) -> Option<Vec<N>>
 where
     I: Iterator<Item = &'c N>,
     F: Fn(&N) -> I,
     N: Clone + cmp::Eq + hash::Hash,
 {
-    let backtrack = bfs_loop(src, dst, get_neighbors)?;
+    let backtrack = bfs_loop(src, dst, get_neighbors, opt_max_hops)?;
     bfs_backtrack(dst, &backtrack)
 }
 
@@ -128,20 +147,47 @@ mod tests {
         graph.insert(9, vec![]);
 
         let get_neighbors = |node: &u32| graph.get(&node).unwrap().iter();
-        assert_eq!(bfs(&0, &1, get_neighbors), Some(vec![0, 1]));
-        assert_eq!(bfs(&1, &0, get_neighbors), Some(vec![1, 2, 3, 0]));
+        assert_eq!(bfs(&0, &1, get_neighbors, None), Some(vec![0, 1]));
+        assert_eq!(bfs(&1, &0, get_neighbors, None), Some(vec![1, 2, 3, 0]));
 
         assert_eq!(
-            bfs(&0, &9, get_neighbors),
+            bfs(&0, &9, get_neighbors, None),
             Some(vec![0, 1, 2, 3, 4, 6, 8, 9])
         );
 
-        assert_eq!(bfs(&8, &6, get_neighbors), None);
-        assert_eq!(bfs(&9, &8, get_neighbors), None);
-        assert_eq!(bfs(&5, &4, get_neighbors), None);
-        assert_eq!(bfs(&4, &3, get_neighbors), None);
+        assert_eq!(bfs(&8, &6, get_neighbors, None), None);
+        assert_eq!(bfs(&9, &8, get_neighbors, None), None);
+        assert_eq!(bfs(&5, &4, get_neighbors, None), None);
+        assert_eq!(bfs(&4, &3, get_neighbors, None), None);
+
+        assert_eq!(bfs(&6, &7, get_neighbors, None), Some(vec![6, 7]));
+        assert_eq!(bfs(&7, &6, get_neighbors, None), Some(vec![7, 6]));
+    }
 
-        assert_eq!(bfs(&6, &7, get_neighbors), Some(vec![6, 7]));
-        assert_eq!(bfs(&7, &6, get_neighbors), Some(vec![7, 6]));
+    #[test]
+    fn test_bfs_max_hops() {
+        /*
+         Example graph:
+                      0 --> 1 --> 2 --> 3
+                      |                 ^
+                      V                 |
+                      4 --------------> 5
+        */
+
+        let mut graph = HashMap::new();
+        graph.insert(0u32, vec![1u32, 4]);
+        graph.insert(1, vec![2]);
+        graph.insert(2, vec![3]);
+        graph.insert(3, vec![]);
+        graph.insert(4, vec![5]);
+        graph.insert(5, vec![3]);
+
+        let get_neighbors = |node: &u32| graph.get(&node).unwrap().iter();
+        assert_eq!(bfs(&0, &3, get_neighbors, Some(3)), Some(vec![0, 1, 2, 3]));
+        assert_eq!(bfs(&0, &3, get_neighbors, Some(2)), None);
+        assert_eq!(bfs(&0, &5, get_neighbors, Some(2)), Some(vec![0, 4, 5]));
+        assert_eq!(bfs(&0, &5, get_neighbors, Some(1)), None);
+        assert_eq!(bfs(&0, &1, get_neighbors, Some(1)), Some(vec![0, 1]));
+        assert_eq!(bfs(&0, &1, get_neighbors, Some(0)), None);
     }
 }
@@ -78,14 +78,20 @@ pub trait CapacityGraph {
     ///
     /// opt_exclude is an optional edge to exclude (All of the returned routes must not go through this
     /// edge). This can be useful for finding non trivial loops.
+    ///
+    /// opt_max_hops is an optional bound on the amount of hops (edges) in every returned route.
     fn get_multi_routes(
         &self,
         a: &Self::Node,
         b: &Self::Node,
         capacity: Self::Capacity,
         opt_exclude: Option<(&Self::Node, &Self::Node)>,
+        opt_max_hops: Option<usize>,
     ) -> Vec<CapacityMultiRoute<Self::Node, Self::Capacity, Self::Rate>>;
 
     /// Simulate advancement of time. Used to remove old edges.
     fn tick(&mut self, a: &Self::Node);
+
+    /// Total amount of directed edges in the graph
+    fn num_edges(&self) -> usize;
 }
@@ -23,16 +23,20 @@ pub enum GraphRequest<G, N, C, T> {
     RemoveNode(N, oneshot::Sender<()>),
     /// Get some routes from one node to another of at least certain capacity.
     /// If an exclude directed edge is provided, the routes must not contain this directed edge.
+    /// If max hops is provided, every route must contain at most this amount of hops (edges).
     GetMultiRoutes(
         G,
         N,
         N,
         C,
         Option<(N, N)>,
+        Option<usize>,
         oneshot::Sender<Vec<CapacityMultiRoute<N, C, T>>>,
-    ), // (from, to, capacity, opt_exclude)
+    ), // (from, to, capacity, opt_exclude, opt_max_hops)
     /// Expire old outgoing edges for the specified node
     Tick(N, oneshot::Sender<()>),
+    /// Count the directed edges of all graphs
+    NumEdges(oneshot::Sender<usize>),
 }
 
 #[derive(Debug)]
@@ -65,13 +69,17 @@ fn process_request<G, N, C, T, CG>(
             capacity_graphs.retain(|_g, capacity_graph| capacity_graph.remove_node(&a));
             let _ = sender.send(());
         }
-        GraphRequest::GetMultiRoutes(g, a, b, capacity, opt_exclude, sender) => {
+        GraphRequest::GetMultiRoutes(g, a, b, capacity, opt_exclude, opt_max_hops, sender) => {
             let routes = if let Some(capacity_graph) = capacity_graphs.get_mut(&g) {
                 match opt_exclude {
-                    Some((c, d)) => {
-                        capacity_graph.get_multi_routes(&a, &b, capacity, Some((&c, &d)))
-                    }
-                    None => capacity_graph.get_multi_routes(&a, &b, capacity, None),
+                    Some((c, d)) => capacity_graph.get_multi_routes(
+                        &a,
+                        &b,
+                        capacity,
+                        Some((&c, &d)),
+                        opt_max_hops,
+                    ),
+                    None => capacity_graph.get_multi_routes(&a, &b, capacity, None, opt_max_hops),
                 }
             } else {
                 vec![]
@@ -84,6 +92,13 @@ fn process_request<G, N, C, T, CG>(
             }
             let _ = sender.send(());
         }
+        GraphRequest::NumEdges(sender) => {
+            let num_edges = capacity_graphs
+                .values()
+                .map(|capacity_graph| capacity_graph.num_edges())
+                .sum();
+            let _ = sender.send(num_edges);
+        }
     }
 }
 
@@ -192,6 +207,8 @@ impl<G, N, C, T> GraphClient<G, N, C, T> {
     ///
     /// opt_exclude is an optional edge to exclude (The returned route must not go through this
     /// edge). This can be useful for finding non trivial loops.
+    ///
+    /// opt_max_hops is an optional bound on the amount of hops (edges) in every returned route.
     pub async fn get_multi_routes(
         &mut self,
         g: G,
@@ -199,6 +216,7 @@ impl<G, N, C, T> GraphClient<G, N, C, T> {
         b: N,
         capacity: C,
         opt_exclude: Option<(N, N)>,
+        opt_max_hops: Option<usize>,
     ) -> Result<Vec<CapacityMultiRoute<N, C, T>>, GraphClientError> {
         let (sender, receiver) = oneshot::channel();
         self.requests_sender
@@ -208,6 +226,7 @@ impl<G, N, C, T> GraphClient<G, N, C, T> {
                 b,
                 capacity,
                 opt_exclude,
+                opt_max_hops,
                 sender,
             ))
             .await?;
@@ -222,6 +241,15 @@ impl<G, N, C, T> GraphClient<G, N, C, T> {
             .await?;
         Ok(receiver.await?)
     }
+
+    /// Count the directed edges of all graphs
+    pub async fn num_edges(&mut self) -> Result<usize, GraphClientError> {
+        let (sender, receiver) = oneshot::channel();
+        self.requests_sender
+            .send(GraphRequest::NumEdges(sender))
+            .await?;
+        Ok(receiver.await?)
+    }
 }
 
 /// Spawn a graph service, returning a GraphClient on success.
@@ -292,7 +320,7 @@ mod tests {
 
         assert_eq!(
             graph_client
-                .get_multi_routes(currency1, 2, 5, 29, None)
+                .get_multi_routes(currency1, 2, 5, 29, None, None)
                 .await
                 .unwrap(),
             vec![CapacityMultiRoute {
@@ -305,7 +333,7 @@ mod tests {
         );
         assert_eq!(
             graph_client
-                .get_multi_routes(currency1, 2, 5, 30, None)
+                .get_multi_routes(currency1, 2, 5, 30, None, None)
                 .await
                 .unwrap(),
             vec![CapacityMultiRoute {
@@ -318,12 +346,14 @@ mod tests {
         );
         assert_eq!(
             graph_client
-                .get_multi_routes(currency1, 2, 5, 31, None)
+                .get_multi_routes(currency1, 2, 5, 31, None, None)
                 .await
                 .unwrap(),
             vec![]
         );
 
+        assert_eq!(graph_client.num_edges().await.unwrap(), 2);
+
         graph_client.tick(2).await.unwrap();
 
         assert_eq!(
@@ -145,12 +145,15 @@ where
     ///
     /// opt_exclude is an optional edge to exclude (The returned route must not go through this
     /// edge). This can be useful for finding non trivial loops.
+    ///
+    /// opt_max_hops is an optional bound on the amount of hops (edges) in the returned route.
     fn get_multi_route(
         &self,
         a: &N,
         b: &N,
         capacity: u128,
         opt_exclude: Option<(&N, &N)>,
+        opt_max_hops: Option<usize>,
     ) -> Option<CapacityMultiRoute<N, u128, T>> {
         // TODO: Update this implementation:
         // Currently get_route does not attemp to find the cheapest route (according to rate)
@@ -165,7 +168,7 @@ where
             self.neighbors_with_send_capacity(cur_node.clone(), capacity)
                 .filter(move |&next_node| !cur_node_is_e_start || Some(next_node) != opt_e_end)
         };
-        let route = bfs(a, b, get_neighbors)?;
+        let route = bfs(a, b, get_neighbors, opt_max_hops)?;
         // We assert that we will always have valid capacity here:
         let capacity = self.get_route_capacity(&route).unwrap();
 
@@ -243,8 +246,9 @@ where
         b: &N,
         capacity: u128,
         opt_exclude: Option<(&N, &N)>,
+        opt_max_hops: Option<usize>,
     ) -> Vec<CapacityMultiRoute<N, u128, T>> {
-        option_to_vec(self.get_multi_route(a, b, capacity, opt_exclude))
+        option_to_vec(self.get_multi_route(a, b, capacity, opt_exclude, opt_max_hops))
     }
 
     fn tick(&mut self, a: &N) {
@@ -252,6 +256,13 @@ where
             node_edges.tick();
         }
     }
+
+    fn num_edges(&self) -> usize {
+        self.nodes
+            .values()
+            .map(|node_edges| node_edges.edges.len())
+            .sum()
+    }
 }
 
 #[cfg(test)]
@@ -285,12 +296,14 @@ mod tests {
         assert_eq!(cg.remove_edge(&0, &1), None);
         cg.update_edge(0, 1, CapacityEdge::new(20, ConstRate(1)));
         assert_eq!(cg.nodes.len(), 1);
+        assert_eq!(cg.num_edges(), 1);
 
         assert_eq!(
             cg.remove_edge(&0, &1),
             Some(CapacityEdge::new(20, ConstRate(1)))
         );
         assert_eq!(cg.nodes.len(), 0);
+        assert_eq!(cg.num_edges(), 0);
 
         cg.update_edge(0, 1, CapacityEdge::new(20, ConstRate(1)));
         assert_eq!(cg.nodes.len(), 1);
@@ -336,50 +349,63 @@ mod tests {
     fn test_get_multi_route() {
         let cg = example_capacity_graph();
 
-        let multi_route = cg.get_multi_route(&2, &5, 29, None).unwrap();
+        let multi_route = cg.get_multi_route(&2, &5, 29, None, None).unwrap();
         assert_eq!(multi_route.routes[0].route, vec![2, 5]);
         assert_eq!(multi_route.routes[0].capacity, 30);
 
-        let multi_route = cg.get_multi_route(&2, &5, 30, None).unwrap();
+        let multi_route = cg.get_multi_route(&2, &5, 30, None, None).unwrap();
         assert_eq!(multi_route.routes[0].route, vec![2, 5]);
         assert_eq!(multi_route.routes[0].capacity, 30);
 
-        assert!(cg.get_multi_route(&2, &5, 31, None).is_none());
+        assert!(cg.get_multi_route(&2, &5, 31, None, None).is_none());
 
-        let multi_route = cg.get_multi_route(&0, &5, 25, None).unwrap();
+        let multi_route = cg.get_multi_route(&0, &5, 25, None, None).unwrap();
         assert_eq!(multi_route.routes[0].route, vec![0, 1, 3, 4, 2, 5]);
         assert_eq!(multi_route.routes[0].capacity, 30);
 
-        let multi_route = cg.get_multi_route(&0, &5, 29, None).unwrap();
+        let multi_route = cg.get_multi_route(&0, &5, 29, None, None).unwrap();
         assert_eq!(multi_route.routes[0].route, vec![0, 1, 3, 4, 2, 5]);
         assert_eq!(multi_route.routes[0].capacity, 30);
 
-        let multi_route = cg.get_multi_route(&0, &5, 30, None).unwrap();
+        let multi_route = cg.get_multi_route(&0, &5, 30, None, None).unwrap();
         assert_eq!(multi_route.routes[0].route, vec![0, 1, 3, 4, 2, 5]);
         assert_eq!(multi_route.routes[0].capacity, 30);
 
-        assert!(cg.get_multi_route(&0, &5, 31, None).is_none());
+        assert!(cg.get_multi_route(&0, &5, 31, None, None).is_none());
 
         // Block an essential edge:
-        assert!(cg.get_multi_route(&0, &5, 25, Some((&3, &4))).is_none());
+        assert!(cg
+            .get_multi_route(&0, &5, 25, Some((&3, &4)), None)
+            .is_none());
 
         // Block an essential edge but the at the reversed direction:
-        let multi_route = cg.get_multi_route(&0, &5, 25, Some((&4, &3))).unwrap();
+        let multi_route = cg
+            .get_multi_route(&0, &5, 25, Some((&4, &3)), None)
+            .unwrap();
         assert_eq!(multi_route.routes[0].route, vec![0, 1, 3, 4, 2, 5]);
         assert_eq!(multi_route.routes[0].capacity, 30);
 
         // Block an edge not used for the route:
-        let multi_route = cg.get_multi_route(&0, &5, 25, Some((&1, &2))).unwrap();
+        let multi_route = cg
+            .get_multi_route(&0, &5, 25, Some((&1, &2)), None)
+            .unwrap();
         assert_eq!(multi_route.routes[0].route, vec![0, 1, 3, 4, 2, 5]);
         assert_eq!(multi_route.routes[0].capacity, 30);
 
         // Use excluded edge to find a loop from 1 to 1:
-        let multi_route = cg.get_multi_route(&2, &1, 6, Some((&2, &1))).unwrap();
+        let multi_route = cg.get_multi_route(&2, &1, 6, Some((&2, &1)), None).unwrap();
         assert_eq!(multi_route.routes[0].route, vec![2, 4, 3, 1]);
         assert_eq!(multi_route.routes[0].capacity, 6);
 
         // Request for too much capacity:
-        assert!(cg.get_multi_route(&2, &1, 7, Some((&2, &1))).is_none());
+        assert!(cg
+            .get_multi_route(&2, &1, 7, Some((&2, &1)), None)
+            .is_none());
+
+        // Limit the amount of hops:
+        let multi_route = cg.get_multi_route(&0, &5, 25, None, Some(5)).unwrap();
+        assert_eq!(multi_route.routes[0].route, vec![0, 1, 3, 4, 2, 5]);
+        assert!(cg.get_multi_route(&0, &5, 25, None, Some(4)).is_none());
     }
 
     #[test]
@@ -392,11 +418,11 @@ mod tests {
         cg.update_edge(2, 3, CapacityEdge::new(10, ConstRate(1)));
         cg.update_edge(3, 2, CapacityEdge::new(30, ConstRate(1)));
 
-        let multi_route = cg.get_multi_route(&0, &1, 30, None).unwrap();
+        let multi_route = cg.get_multi_route(&0, &1, 30, None, None).unwrap();
         assert_eq!(multi_route.routes[0].route, vec![0, 1]);
         assert_eq!(multi_route.routes[0].capacity, 30);
 
-        let multi_route = cg.get_multi_route(&2, &3, 30, None).unwrap();
+        let multi_route = cg.get_multi_route(&2, &3, 30, None, None).unwrap();
         assert_eq!(multi_route.routes[0].route, vec![2, 3]);
         assert_eq!(multi_route.routes[0].capacity, 30);
 
@@ -404,20 +430,20 @@ mod tests {
         for _ in 0..max_edge_age - 1 {
             cg.tick(&0);
 
-            let multi_route = cg.get_multi_route(&0, &1, 30, None).unwrap();
+            let multi_route = cg.get_multi_route(&0, &1, 30, None, None).unwrap();
             assert_eq!(multi_route.routes[0].route, vec![0, 1]);
             assert_eq!(multi_route.routes[0].capacity, 30);
 
-            let multi_route = cg.get_multi_route(&2, &3, 30, None).unwrap();
+            let multi_route = cg.get_multi_route(&2, &3, 30, None, None).unwrap();
             assert_eq!(multi_route.routes[0].route, vec![2, 3]);
             assert_eq!(multi_route.routes[0].capacity, 30);
         }
 
         // At this point 0->1 and 1->0 should expire, but 2->3 and 3->2 don't expire:
         cg.tick(&0);
-        assert!(cg.get_multi_route(&0, &1, 30, None).is_none());
+        assert!(cg.get_multi_route(&0, &1, 30, None, None).is_none());
 
-        let multi_route = cg.get_multi_route(&2, &3, 30, None).unwrap();
+        let multi_route = cg.get_multi_route(&2, &3, 30, None, None).unwrap();
         assert_eq!(multi_route.routes[0].route, vec![2, 3]);
         assert_eq!(multi_route.routes[0].capacity, 30);
     }
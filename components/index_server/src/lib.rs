@@ -21,3 +21,7 @@ mod server_loop;
 mod verifier;
 
 pub use server::{index_server, IndexServerError};
+pub use server_loop::{
+    AdminConn, ClientPeerDump, IndexServerAdminRequest, ServerPeerDump, ServerPeerState,
+    TopologyDump,
+};
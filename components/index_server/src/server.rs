@@ -14,7 +14,7 @@ use timer::TimerClient;
 use crypto::identity::compare_public_key;
 use crypto::rand::CryptoRandom;
 
-use crate::server_loop::{server_loop, ClientConn, ServerConn, ServerLoopError};
+use crate::server_loop::{server_loop, AdminConn, ClientConn, ServerConn, ServerLoopError};
 
 use crate::backoff_connector::BackoffConnector;
 use crate::graph::graph_service::create_graph_service;
@@ -30,11 +30,15 @@ pub enum IndexServerError {
 
 /// Run an index server
 /// Will keep running until an error occurs.
-pub async fn index_server<A, IS, IC, SC, R, GS, S>(
+///
+/// `incoming_admin_connections` may be used to inspect the state of the server
+/// (See `IndexServerAdminRequest`).
+pub async fn index_server<A, IS, IC, IA, SC, R, GS, S>(
     local_public_key: PublicKey,
     trusted_servers: HashMap<PublicKey, A>,
     incoming_server_connections: IS,
     incoming_client_connections: IC,
+    incoming_admin_connections: IA,
     server_connector: SC,
     mut timer_client: TimerClient,
     ticks_to_live: usize,
@@ -47,6 +51,7 @@ where
     A: Debug + Send + Sync + Clone + 'static,
     IS: Stream<Item = (PublicKey, ServerConn)> + Unpin + Send,
     IC: Stream<Item = (PublicKey, ClientConn)> + Unpin + Send,
+    IA: Stream<Item = AdminConn> + Unpin + Send,
     SC: FutTransform<Input = (PublicKey, A), Output = Option<ServerConn>> + Clone + Send + 'static,
     R: CryptoRandom,
     S: Spawn + Clone + Send,
@@ -72,6 +77,7 @@ where
         trusted_servers,
         incoming_server_connections,
         incoming_client_connections,
+        incoming_admin_connections,
         backoff_connector,
         graph_client,
         compare_public_key,
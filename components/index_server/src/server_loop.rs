@@ -1,5 +1,6 @@
 use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::marker::Unpin;
 
 use futures::channel::{mpsc, oneshot};
@@ -28,6 +29,7 @@ use crate::verifier::Verifier;
 
 pub type ServerConn = ConnPair<IndexServerToServer, IndexServerToServer>;
 pub type ClientConn = ConnPair<IndexServerToClient, IndexClientToServer>;
+pub type AdminConn = ConnPair<TopologyDump, IndexServerAdminRequest>;
 
 // TODO: Find a more scalable solution to the EVENT_BUFFER issue.
 // It might be true that a deadlock could happen to the event buffer
@@ -63,19 +65,70 @@ pub enum ServerLoopError {
     GraphClientError,
     ClientEventSenderError,
     ClientSenderError,
+    AdminEventSenderError,
+    AdminSenderError,
     RemoteSendError,
 }
 
+/// A request sent to the index server over an admin connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexServerAdminRequest {
+    /// Get the current view of the index server
+    DumpTopology,
+}
+
+/// State of a trusted remote index server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerPeerState {
+    Connected,
+    /// We are attempting to connect to the remote server
+    Initiating,
+    /// Waiting for the remote server to connect to us
+    Listening,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerPeerDump {
+    pub public_key: PublicKey,
+    pub state: ServerPeerState,
+    /// Tick of the last message received from the server during the current connection
+    pub opt_last_update: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientPeerDump {
+    pub public_key: PublicKey,
+    /// Tick of the last mutations update received from the client
+    pub opt_last_update: Option<u64>,
+}
+
+/// The current view of an index server, used for debugging
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopologyDump {
+    pub local_public_key: PublicKey,
+    /// Amount of timer ticks handled so far
+    pub ticks: u64,
+    /// All trusted servers, sorted by public key
+    pub servers: Vec<ServerPeerDump>,
+    /// All connected clients, sorted by public key
+    pub clients: Vec<ClientPeerDump>,
+    /// Amount of known directed friend edges, summed over all currencies
+    pub num_edges: usize,
+}
+
 /// A connected remote entity
 #[derive(Debug)]
 struct Connected<T> {
     opt_sender: Option<mpsc::Sender<T>>,
+    /// Tick of the last update received from the remote entity
+    opt_last_update: Option<u64>,
 }
 
 impl<T> Connected<T> {
     pub fn new(sender: mpsc::Sender<T>) -> Self {
         Connected {
             opt_sender: Some(sender),
+            opt_last_update: None,
         }
     }
 
@@ -124,6 +177,8 @@ struct IndexServer<A, S, SC, V, CMP> {
     compare_public_key: CMP,
     remote_servers: HashMap<PublicKey, RemoteServer<A>>,
     clients: HashMap<PublicKey, Connected<IndexServerToClient>>,
+    /// Amount of timer ticks handled so far
+    ticks: u64,
     event_sender: mpsc::Sender<IndexServerEvent>,
     spawner: S,
 }
@@ -141,7 +196,9 @@ enum IndexServerEvent {
     FromServer((PublicKey, Option<IndexServerToServer>)),
     ClientConnection((PublicKey, ClientConn)),
     ClientClosed(PublicKey),
-    ClientMutationsUpdate(MutationsUpdate),
+    ClientMutationsUpdate((PublicKey, MutationsUpdate)),
+    AdminConnection(AdminConn),
+    AdminDumpTopology(oneshot::Sender<TopologyDump>),
     TimerTick,
     TimerClosed,
     ClientListenerClosed,
@@ -188,6 +245,7 @@ where
             compare_public_key,
             remote_servers: HashMap::new(),
             clients: HashMap::new(),
+            ticks: 0,
             event_sender,
             spawner,
         };
@@ -380,6 +438,14 @@ where
         public_key: PublicKey,
         server_msg: IndexServerToServer,
     ) -> Result<(), ServerLoopError> {
+        if let Some(RemoteServer {
+            state: RemoteServerState::Connected(server_connected),
+            ..
+        }) = self.remote_servers.get_mut(&public_key)
+        {
+            server_connected.opt_last_update = Some(self.ticks);
+        }
+
         match server_msg {
             IndexServerToServer::TimeHash(time_hash) => {
                 let _ = self.verifier.neighbor_tick(public_key, time_hash);
@@ -393,6 +459,7 @@ where
     }
 
     pub async fn handle_timer_tick(&mut self) -> Result<(), ServerLoopError> {
+        self.ticks = self.ticks.wrapping_add(1);
         let (time_hash, removed_nodes) = self.verifier.tick();
 
         // Try to send the time tick to all servers. Sending to some of them might fail:
@@ -412,11 +479,51 @@ where
 
         Ok(())
     }
+
+    pub async fn topology_dump(&mut self) -> Result<TopologyDump, ServerLoopError> {
+        let mut servers = self
+            .remote_servers
+            .iter()
+            .map(|(public_key, remote_server)| {
+                let (state, opt_last_update) = match &remote_server.state {
+                    RemoteServerState::Connected(server_connected) => {
+                        (ServerPeerState::Connected, server_connected.opt_last_update)
+                    }
+                    RemoteServerState::Initiating(_) => (ServerPeerState::Initiating, None),
+                    RemoteServerState::Listening => (ServerPeerState::Listening, None),
+                };
+                ServerPeerDump {
+                    public_key: public_key.clone(),
+                    state,
+                    opt_last_update,
+                }
+            })
+            .collect::<Vec<_>>();
+        servers.sort_by(|a, b| a.public_key.cmp(&b.public_key));
+
+        let mut clients = self
+            .clients
+            .iter()
+            .map(|(public_key, client_connected)| ClientPeerDump {
+                public_key: public_key.clone(),
+                opt_last_update: client_connected.opt_last_update,
+            })
+            .collect::<Vec<_>>();
+        clients.sort_by(|a, b| a.public_key.cmp(&b.public_key));
+
+        Ok(TopologyDump {
+            local_public_key: self.local_public_key.clone(),
+            ticks: self.ticks,
+            servers,
+            clients,
+            num_edges: self.graph_client.num_edges().await?,
+        })
+    }
 }
 
 async fn client_handler(
     mut graph_client: GraphClient<Currency, PublicKey, u128, Rate>,
-    _public_key: PublicKey, // TODO: unused?
+    public_key: PublicKey,
     client_conn: ClientConn,
     mut event_sender: mpsc::Sender<IndexServerEvent>,
 ) -> Result<(), ServerLoopError> {
@@ -427,7 +534,10 @@ async fn client_handler(
             IndexClientToServer::MutationsUpdate(mutations_update) => {
                 // Forward to main server future to process:
                 event_sender
-                    .send(IndexServerEvent::ClientMutationsUpdate(mutations_update))
+                    .send(IndexServerEvent::ClientMutationsUpdate((
+                        public_key.clone(),
+                        mutations_update,
+                    )))
                     .await
                     .map_err(|_| ServerLoopError::ClientEventSenderError)?;
             }
@@ -435,6 +545,10 @@ async fn client_handler(
                 let opt_exclude_edge = request_routes
                     .opt_exclude
                     .map(|edge| (edge.from_public_key.clone(), edge.to_public_key));
+                // A bound that does not fit into a usize does not limit anything:
+                let opt_max_hops = request_routes
+                    .opt_max_hops
+                    .map(|max_hops| usize::try_from(max_hops).unwrap_or(usize::max_value()));
 
                 let graph_multi_routes = graph_client
                     .get_multi_routes(
@@ -443,6 +557,7 @@ async fn client_handler(
                         request_routes.destination.clone(),
                         request_routes.capacity,
                         opt_exclude_edge,
+                        opt_max_hops,
                     )
                     .await?;
                 let multi_routes = graph_multi_routes
@@ -477,11 +592,39 @@ async fn client_handler(
     Ok(())
 }
 
-pub async fn server_loop<A, IS, IC, SC, CMP, V, TS, S>(
+async fn admin_handler(
+    admin_conn: AdminConn,
+    mut event_sender: mpsc::Sender<IndexServerEvent>,
+) -> Result<(), ServerLoopError> {
+    let (mut sender, mut receiver) = admin_conn.split();
+
+    while let Some(admin_request) = receiver.next().await {
+        match admin_request {
+            IndexServerAdminRequest::DumpTopology => {
+                let (response_sender, response_receiver) = oneshot::channel();
+                event_sender
+                    .send(IndexServerEvent::AdminDumpTopology(response_sender))
+                    .await
+                    .map_err(|_| ServerLoopError::AdminEventSenderError)?;
+                let topology_dump = response_receiver
+                    .await
+                    .map_err(|_| ServerLoopError::AdminEventSenderError)?;
+                sender
+                    .send(topology_dump)
+                    .await
+                    .map_err(|_| ServerLoopError::AdminSenderError)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub async fn server_loop<A, IS, IC, IA, SC, CMP, V, TS, S>(
     local_public_key: PublicKey,
     trusted_servers: HashMap<PublicKey, A>,
     incoming_server_connections: IS,
     incoming_client_connections: IC,
+    incoming_admin_connections: IA,
     server_connector: SC,
     graph_client: GraphClient<Currency, PublicKey, u128, Rate>,
     compare_public_key: CMP,
@@ -494,6 +637,7 @@ where
     A: Clone + Send + std::fmt::Debug + 'static,
     IS: Stream<Item = (PublicKey, ServerConn)> + Unpin + Send,
     IC: Stream<Item = (PublicKey, ClientConn)> + Unpin + Send,
+    IA: Stream<Item = AdminConn> + Unpin + Send,
     SC: FutTransform<Input = (PublicKey, A), Output = Option<ServerConn>> + Clone + Send + 'static,
     V: Verifier<Node = PublicKey, Neighbor = PublicKey, SessionId = Uid>,
     CMP: Clone + Fn(&PublicKey, &PublicKey) -> Ordering + Sync,
@@ -539,6 +683,11 @@ where
             IndexServerEvent::ClientListenerClosed,
         )));
 
+    // Admin connections are optional, therefore closing the admin listener does not close the
+    // server:
+    let incoming_admin_connections =
+        incoming_admin_connections.map(IndexServerEvent::AdminConnection);
+
     let timer_stream = timer_stream
         .map(|_| IndexServerEvent::TimerTick)
         .chain(stream::once(future::ready(IndexServerEvent::TimerClosed)));
@@ -547,6 +696,7 @@ where
         event_receiver,
         incoming_server_connections,
         incoming_client_connections,
+        incoming_admin_connections,
         timer_stream
     ];
 
@@ -661,7 +811,10 @@ where
                     .clients
                     .insert(public_key, Connected::new(c_sender));
             }
-            IndexServerEvent::ClientMutationsUpdate(mutations_update) => {
+            IndexServerEvent::ClientMutationsUpdate((public_key, mutations_update)) => {
+                if let Some(client_connected) = index_server.clients.get_mut(&public_key) {
+                    client_connected.opt_last_update = Some(index_server.ticks);
+                }
                 let forward_mutations_update = ForwardMutationsUpdate {
                     mutations_update,
                     time_proof_chain: Vec::new(),
@@ -677,6 +830,20 @@ where
                     error!("A non existent client {:?} was closed.", public_key);
                 }
             }
+            IndexServerEvent::AdminConnection(admin_conn) => {
+                let admin_handler_fut =
+                    admin_handler(admin_conn, index_server.event_sender.clone())
+                        .map_err(|e| error!("admin_handler() error: {:?}", e))
+                        .map(|_| ());
+                index_server
+                    .spawner
+                    .spawn(admin_handler_fut)
+                    .map_err(|_| ServerLoopError::SpawnError)?;
+            }
+            IndexServerEvent::AdminDumpTopology(response_sender) => {
+                let topology_dump = index_server.topology_dump().await?;
+                let _ = response_sender.send(topology_dump);
+            }
             IndexServerEvent::TimerTick => index_server.handle_timer_tick().await?,
             IndexServerEvent::TimerClosed => {
                 warn!("server_loop() timer closed!");
@@ -723,6 +890,7 @@ mod tests {
 
     use signature::signature_buff::create_mutations_update_signature_buff;
 
+    use crate::graph::capacity_graph::{CapacityMultiRoute, CapacityRoute};
     use crate::graph::graph_service::GraphRequest;
     use crate::verifier::simple_verifier::SimpleVerifier;
 
@@ -758,6 +926,7 @@ mod tests {
 
         let (_server_connections_sender, incoming_server_connections) = mpsc::channel(0);
         let (mut client_connections_sender, incoming_client_connections) = mpsc::channel(0);
+        let (_admin_connections_sender, incoming_admin_connections) = mpsc::channel(0);
 
         let (conn_request_sender, _conn_request_receiver) = mpsc::channel(0);
         let server_connector = DummyConnector::new(conn_request_sender);
@@ -779,6 +948,7 @@ mod tests {
             trusted_servers,
             incoming_server_connections,
             incoming_client_connections,
+            incoming_admin_connections,
             server_connector,
             graph_client,
             compare_public_key,
@@ -814,6 +984,7 @@ mod tests {
             source: PublicKey::from(&[8; PublicKey::len()]),
             destination: PublicKey::from(&[9; PublicKey::len()]),
             opt_exclude: None,
+            opt_max_hops: None,
         };
         client_sender
             .send(IndexClientToServer::RequestRoutes(request_routes))
@@ -828,6 +999,7 @@ mod tests {
                 dest,
                 capacity,
                 opt_exclude,
+                opt_max_hops,
                 response_sender,
             ) => {
                 assert_eq!(currency, currency1);
@@ -835,6 +1007,7 @@ mod tests {
                 assert_eq!(dest, PublicKey::from(&[9; PublicKey::len()]));
                 assert_eq!(capacity, 100);
                 assert_eq!(opt_exclude, None);
+                assert_eq!(opt_max_hops, None);
                 response_sender.send(Vec::new()).unwrap();
             }
             _ => unreachable!(),
@@ -848,6 +1021,64 @@ mod tests {
             _ => unreachable!(),
         };
 
+        // Client requests routes of at most 2 hops:
+        let request_id = Uid::from(&[1; Uid::len()]);
+        let request_routes = RequestRoutes {
+            request_id: request_id.clone(),
+            currency: currency1.clone(),
+            capacity: 100,
+            source: PublicKey::from(&[8; PublicKey::len()]),
+            destination: PublicKey::from(&[9; PublicKey::len()]),
+            opt_exclude: None,
+            opt_max_hops: Some(2),
+        };
+        client_sender
+            .send(IndexClientToServer::RequestRoutes(request_routes))
+            .await
+            .unwrap();
+
+        let short_route = CapacityRoute {
+            route: [8, 10, 9]
+                .iter()
+                .map(|&i| PublicKey::from(&[i; PublicKey::len()]))
+                .collect(),
+            capacity: 100,
+            rate: Rate::new(),
+        };
+
+        // The bound is passed on to the graph search:
+        match graph_requests_receiver.next().await.unwrap() {
+            GraphRequest::GetMultiRoutes(_, _, _, _, _, opt_max_hops, response_sender) => {
+                assert_eq!(opt_max_hops, Some(2));
+                response_sender
+                    .send(vec![CapacityMultiRoute {
+                        routes: vec![short_route.clone()],
+                    }])
+                    .unwrap();
+            }
+            _ => unreachable!(),
+        }
+
+        // The route found by the graph is returned:
+        match client_receiver.next().await.unwrap() {
+            IndexServerToClient::ResponseRoutes(response_routes) => {
+                assert_eq!(response_routes.request_id, request_id);
+                assert_eq!(
+                    response_routes.multi_routes,
+                    vec![MultiRoute {
+                        routes: vec![RouteCapacityRate {
+                            route: FriendsRoute {
+                                public_keys: short_route.route,
+                            },
+                            capacity: 100,
+                            rate: Rate::new(),
+                        }],
+                    }]
+                );
+            }
+            _ => unreachable!(),
+        };
+
         // Server should periodically send time hashes to the client:
         tick_sender.send(()).await.unwrap();
 
@@ -918,6 +1149,7 @@ mod tests {
         tick_sender: mpsc::Sender<()>,
         server_connections_sender: mpsc::Sender<(PublicKey, ServerConn)>,
         client_connections_sender: mpsc::Sender<(PublicKey, ClientConn)>,
+        admin_connections_sender: mpsc::Sender<AdminConn>,
         graph_requests_receiver: mpsc::Receiver<GraphRequest<Currency, PublicKey, u128, Rate>>,
         server_conn_request_receiver:
             mpsc::Receiver<ConnRequest<(PublicKey, u8), Option<ServerConn>>>,
@@ -938,6 +1170,7 @@ mod tests {
 
         let (server_connections_sender, incoming_server_connections) = mpsc::channel(0);
         let (client_connections_sender, incoming_client_connections) = mpsc::channel(0);
+        let (admin_connections_sender, incoming_admin_connections) = mpsc::channel(0);
 
         let (server_conn_request_sender, server_conn_request_receiver) = mpsc::channel(0);
         let server_connector = DummyConnector::new(server_conn_request_sender);
@@ -961,6 +1194,7 @@ mod tests {
             trusted_servers,
             incoming_server_connections,
             incoming_client_connections,
+            incoming_admin_connections,
             server_connector,
             graph_client,
             compare_public_key,
@@ -984,6 +1218,7 @@ mod tests {
             tick_sender,
             server_connections_sender,
             client_connections_sender,
+            admin_connections_sender,
             graph_requests_receiver,
             server_conn_request_receiver,
             debug_event_receiver,
@@ -1112,6 +1347,7 @@ mod tests {
             source: PublicKey::from(&[8; PublicKey::len()]),
             destination: PublicKey::from(&[9; PublicKey::len()]),
             opt_exclude: None,
+            opt_max_hops: None,
         };
         client_sender
             .send(IndexClientToServer::RequestRoutes(request_routes))
@@ -1131,6 +1367,7 @@ mod tests {
                 dest,
                 capacity,
                 opt_exclude,
+                opt_max_hops,
                 response_sender,
             ) => {
                 assert_eq!(currency, currency1);
@@ -1138,6 +1375,7 @@ mod tests {
                 assert_eq!(dest, PublicKey::from(&[9; PublicKey::len()]));
                 assert_eq!(capacity, 100);
                 assert_eq!(opt_exclude, None);
+                assert_eq!(opt_max_hops, None);
                 response_sender.send(Vec::new()).unwrap();
             }
             _ => unreachable!(),
@@ -1253,5 +1491,122 @@ mod tests {
         block_on(task_index_server_loop_multi_server(thread_pool.clone()));
     }
 
+    /// Open an admin connection to a test server and request a topology dump.
+    /// The graph of the server reports `num_edges` edges.
+    async fn dump_topology(test_server: &mut TestServer, num_edges: usize) -> TopologyDump {
+        let (mut admin_sender, server_receiver) = mpsc::channel(CHANNEL_SIZE);
+        let (server_sender, mut admin_receiver) = mpsc::channel(CHANNEL_SIZE);
+        test_server
+            .admin_connections_sender
+            .send(ConnPair::from_raw(server_sender, server_receiver))
+            .await
+            .unwrap();
+        test_server.debug_event_receiver.next().await.unwrap();
+
+        admin_sender
+            .send(IndexServerAdminRequest::DumpTopology)
+            .await
+            .unwrap();
+
+        match test_server.graph_requests_receiver.next().await.unwrap() {
+            GraphRequest::NumEdges(response_sender) => response_sender.send(num_edges).unwrap(),
+            _ => unreachable!(),
+        }
+        test_server.debug_event_receiver.next().await.unwrap();
+
+        admin_receiver.next().await.unwrap()
+    }
+
+    async fn task_index_server_loop_dump_topology<S>(spawner: S)
+    where
+        S: Spawn + Clone + Send + 'static,
+    {
+        /*
+         *  Servers layout:
+         *
+         *    0 -- 2 -- 1
+         */
+
+        let pk = |index: u8| PublicKey::from(&[index; PublicKey::len()]);
+
+        let mut test_servers = Vec::new();
+        test_servers.push(create_test_server(0, &[2], spawner.clone()));
+        test_servers.push(create_test_server(1, &[2], spawner.clone()));
+        test_servers.push(create_test_server(2, &[0, 1], spawner.clone()));
+
+        // Server 0 waits for server 2 to connect:
+        let topology_dump = dump_topology(&mut test_servers[0], 0).await;
+        assert_eq!(
+            topology_dump,
+            TopologyDump {
+                local_public_key: pk(0),
+                ticks: 0,
+                servers: vec![ServerPeerDump {
+                    public_key: pk(2),
+                    state: ServerPeerState::Listening,
+                    opt_last_update: None,
+                }],
+                clients: Vec::new(),
+                num_edges: 0,
+            }
+        );
+
+        // Server 2 connects to {0, 1}:
+        handle_connect(&mut test_servers[..], 2).await;
+        handle_connect(&mut test_servers[..], 2).await;
+
+        // Server 0 sends a time hash to server 2:
+        test_servers[0].tick_sender.send(()).await.unwrap();
+        test_servers[0].debug_event_receiver.next().await.unwrap();
+        test_servers[2].debug_event_receiver.next().await.unwrap();
+
+        // Server 2 sends a time hash to servers {0, 1}:
+        test_servers[2].tick_sender.send(()).await.unwrap();
+        test_servers[2].debug_event_receiver.next().await.unwrap();
+        for &j in &[0usize, 1] {
+            test_servers[j].debug_event_receiver.next().await.unwrap();
+        }
+
+        let topology_dump = dump_topology(&mut test_servers[0], 3).await;
+        assert_eq!(topology_dump.ticks, 1);
+        assert_eq!(
+            topology_dump.servers,
+            vec![ServerPeerDump {
+                public_key: pk(2),
+                state: ServerPeerState::Connected,
+                opt_last_update: Some(1),
+            }]
+        );
+        assert_eq!(topology_dump.num_edges, 3);
+
+        let topology_dump = dump_topology(&mut test_servers[2], 5).await;
+        assert_eq!(topology_dump.local_public_key, pk(2));
+        assert_eq!(topology_dump.ticks, 1);
+        assert_eq!(
+            topology_dump.servers,
+            vec![
+                ServerPeerDump {
+                    public_key: pk(0),
+                    state: ServerPeerState::Connected,
+                    opt_last_update: Some(0),
+                },
+                // Server 1 has not sent anything yet:
+                ServerPeerDump {
+                    public_key: pk(1),
+                    state: ServerPeerState::Connected,
+                    opt_last_update: None,
+                },
+            ]
+        );
+        assert!(topology_dump.clients.is_empty());
+        assert_eq!(topology_dump.num_edges, 5);
+    }
+
+    #[test]
+    fn test_index_server_loop_dump_topology() {
+        let thread_pool = ThreadPool::new().unwrap();
+        block_on(task_index_server_loop_dump_topology(thread_pool.clone()));
+    }
+
     // TODO: Add tests.
 }
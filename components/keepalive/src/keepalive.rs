@@ -21,6 +21,12 @@ pub enum KeepAliveError {
     ProtoSerializeError(ProtoSerializeError),
 }
 
+#[derive(Debug)]
+pub enum KeepAliveConfigError {
+    /// The remote side must be given more time than the interval between our keepalives
+    TimeoutNotAboveInterval,
+}
+
 #[derive(Debug, Clone)]
 enum KeepAliveEvent {
     TimerTick,
@@ -59,7 +65,8 @@ async fn inner_keepalive_loop<TR, FR, TU, FU, TS>(
     mut to_user: TU,
     from_user: FU,
     timer_stream: TS,
-    keepalive_ticks: usize,
+    ping_interval_ticks: usize,
+    timeout_ticks: usize,
     mut opt_event_sender: Option<mpsc::Sender<KeepAliveEvent>>,
 ) -> Result<(), KeepAliveError>
 where
@@ -88,10 +95,10 @@ where
     let mut events = select_streams![timer_stream, from_remote, from_user];
 
     // Amount of ticks remaining until we decide to close this connection (Because remote is idle):
-    let mut ticks_to_close = keepalive_ticks;
+    let mut ticks_to_close = timeout_ticks;
     // Amount of ticks remaining until we need to send a new keepalive (To make sure remote side
     // knows we are alive).
-    let mut ticks_to_send_keepalive = keepalive_ticks / 2;
+    let mut ticks_to_send_keepalive = ping_interval_ticks;
 
     while let Some(event) = events.next().await {
         if let Some(ref mut event_sender) = opt_event_sender {
@@ -100,7 +107,7 @@ where
         match event {
             KeepAliveEvent::MessageFromRemote(ser_ka_message) => {
                 let ka_message = KaMessage::proto_deserialize(&ser_ka_message)?;
-                ticks_to_close = keepalive_ticks;
+                ticks_to_close = timeout_ticks;
                 if let KaMessage::Message(message) = ka_message {
                     if to_user.send(message).await.is_err() {
                         warn!("keepalive_loop(): Can not send to local side");
@@ -115,7 +122,7 @@ where
                     warn!("keepalive_loop(): Can not send to remote side");
                     break;
                 }
-                ticks_to_send_keepalive = keepalive_ticks / 2;
+                ticks_to_send_keepalive = ping_interval_ticks;
             }
             KeepAliveEvent::TimerTick => {
                 ticks_to_close = ticks_to_close.saturating_sub(1);
@@ -130,7 +137,7 @@ where
                         warn!("Keepalive_loop(): Can not send to remote side");
                         break;
                     }
-                    ticks_to_send_keepalive = keepalive_ticks / 2;
+                    ticks_to_send_keepalive = ping_interval_ticks;
                 }
             }
             KeepAliveEvent::TimerClosed
@@ -144,7 +151,8 @@ where
 #[derive(Clone)]
 pub struct KeepAliveChannel<S> {
     timer_client: TimerClient,
-    keepalive_ticks: usize,
+    ping_interval_ticks: usize,
+    timeout_ticks: usize,
     spawner: S,
 }
 
@@ -152,6 +160,8 @@ impl<S> KeepAliveChannel<S>
 where
     S: Spawn + Send,
 {
+    /// The remote side is considered dead after `keepalive_ticks` idle ticks.
+    /// We send keepalives every `keepalive_ticks / 2` ticks.
     pub fn new(
         timer_client: TimerClient,
         keepalive_ticks: usize,
@@ -159,11 +169,32 @@ where
     ) -> KeepAliveChannel<S> {
         KeepAliveChannel {
             timer_client,
-            keepalive_ticks,
+            ping_interval_ticks: keepalive_ticks / 2,
+            timeout_ticks: keepalive_ticks,
             spawner,
         }
     }
 
+    /// Send keepalives every `ping_interval_ticks` ticks, and consider the remote side dead after
+    /// `timeout_ticks` idle ticks. On lossy links, `timeout_ticks` should be a few times larger
+    /// than `ping_interval_ticks`, so that a few lost keepalives will not close the connection.
+    pub fn with_ping_interval(
+        timer_client: TimerClient,
+        ping_interval_ticks: usize,
+        timeout_ticks: usize,
+        spawner: S,
+    ) -> Result<KeepAliveChannel<S>, KeepAliveConfigError> {
+        if timeout_ticks <= ping_interval_ticks {
+            return Err(KeepAliveConfigError::TimeoutNotAboveInterval);
+        }
+        Ok(KeepAliveChannel {
+            timer_client,
+            ping_interval_ticks,
+            timeout_ticks,
+            spawner,
+        })
+    }
+
     /// Transform a usual `Vec<u8>` connection end into a connection end that performs
     /// keepalives automatically. The output `conn_pair` looks exactly like the input pair, however
     /// it also maintains keepalives.
@@ -185,7 +216,8 @@ where
                     to_user,
                     from_user,
                     timer_stream,
-                    self.keepalive_ticks,
+                    self.ping_interval_ticks,
+                    self.timeout_ticks,
                     None,
                 )
                 .map_err(|e| {
@@ -254,6 +286,7 @@ mod tests {
             to_user,
             from_user,
             timer_stream,
+            keepalive_ticks / 2,
             keepalive_ticks,
             None,
         )
@@ -289,6 +322,7 @@ mod tests {
             to_user,
             from_user,
             timer_stream,
+            keepalive_ticks / 2,
             keepalive_ticks,
             Some(event_sender),
         )
@@ -348,6 +382,100 @@ mod tests {
         LocalPool::new().run_until(task_keepalive_loop_basic(thread_pool.clone()));
     }
 
+    async fn task_keepalive_loop_ping_interval(spawner: impl Spawn + Clone) {
+        // Create a mock time service:
+        let (mut tick_sender, tick_receiver) = mpsc::channel::<()>(0);
+        let mut timer_client = create_timer_incoming(tick_receiver, spawner.clone()).unwrap();
+
+        let (event_sender, mut event_receiver) = mpsc::channel(0);
+
+        let (to_remote, mut remote_receiver) = mpsc::channel::<Vec<u8>>(1);
+        let (mut remote_sender, from_remote) = mpsc::channel::<Vec<u8>>(1);
+
+        let (to_user, mut user_receiver) = mpsc::channel::<Vec<u8>>(0);
+        let (_user_sender, from_user) = mpsc::channel::<Vec<u8>>(0);
+
+        let timer_stream = timer_client
+            .request_timer_stream("task_keepalive_loop_ping_interval".to_owned())
+            .await
+            .unwrap();
+        let ping_interval_ticks = 2;
+        let timeout_ticks = 16;
+        let fut_keepalive_loop = inner_keepalive_loop(
+            to_remote,
+            from_remote,
+            to_user,
+            from_user,
+            timer_stream,
+            ping_interval_ticks,
+            timeout_ticks,
+            Some(event_sender),
+        )
+        .map(|_| ());
+
+        spawner.spawn(fut_keepalive_loop).unwrap();
+
+        // Remote answers only once every 12 ticks, but stays alive:
+        for _ in 0..4usize {
+            for _ in 0..6usize {
+                for _ in 0..ping_interval_ticks {
+                    tick_sender.send(()).await.unwrap();
+                    event_receiver.next().await.unwrap();
+                }
+                // We send a keepalive every `ping_interval_ticks` ticks:
+                let vec = remote_receiver.next().await.unwrap();
+                assert_eq!(vec, KaMessage::KeepAlive.proto_serialize());
+            }
+
+            let vec = KaMessage::KeepAlive.proto_serialize();
+            remote_sender.send(vec).await.unwrap();
+            event_receiver.next().await.unwrap();
+        }
+
+        // The connection is still open:
+        let vec = KaMessage::Message(vec![1, 2, 3]).proto_serialize();
+        remote_sender.send(vec).await.unwrap();
+        event_receiver.next().await.unwrap();
+        assert_eq!(user_receiver.next().await.unwrap(), vec![1, 2, 3]);
+
+        // Remote stops answering:
+        let num_intervals = timeout_ticks / ping_interval_ticks;
+        for i in 0..num_intervals {
+            for _ in 0..ping_interval_ticks {
+                tick_sender.send(()).await.unwrap();
+                event_receiver.next().await.unwrap();
+            }
+            // On the last tick the connection is closed, and no keepalive is sent:
+            if i < num_intervals - 1 {
+                let vec = remote_receiver.next().await.unwrap();
+                assert_eq!(vec, KaMessage::KeepAlive.proto_serialize());
+            }
+        }
+
+        // Channel should be closed, because remote was idle for `timeout_ticks` ticks:
+        let res = user_receiver.next().await;
+        assert!(res.is_none());
+    }
+
+    #[test]
+    fn test_keepalive_loop_ping_interval() {
+        let thread_pool = ThreadPool::new().unwrap();
+        LocalPool::new().run_until(task_keepalive_loop_ping_interval(thread_pool.clone()));
+    }
+
+    #[test]
+    fn test_keepalive_channel_invalid_ping_interval() {
+        let thread_pool = ThreadPool::new().unwrap();
+        let (_tick_sender, tick_receiver) = mpsc::channel::<()>(0);
+        let timer_client = create_timer_incoming(tick_receiver, thread_pool.clone()).unwrap();
+
+        let res =
+            KeepAliveChannel::with_ping_interval(timer_client.clone(), 8, 8, thread_pool.clone());
+        assert!(res.is_err());
+        let res = KeepAliveChannel::with_ping_interval(timer_client, 2, 8, thread_pool);
+        assert!(res.is_ok());
+    }
+
     async fn task_keepalive_channel_basic(test_executor: TestExecutor) {
         // Create a mock time service:
         let (mut tick_sender, tick_receiver) = mpsc::channel::<()>(0);
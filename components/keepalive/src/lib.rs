@@ -16,4 +16,4 @@ extern crate common;
 
 mod keepalive;
 
-pub use self::keepalive::KeepAliveChannel;
+pub use self::keepalive::{KeepAliveChannel, KeepAliveConfigError};
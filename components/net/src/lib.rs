@@ -11,6 +11,8 @@
 #[macro_use]
 extern crate log;
 
+mod loopback;
+mod resolver;
 mod tcp_connector;
 mod tcp_listener;
 #[cfg(test)]
@@ -18,5 +20,7 @@ mod tests;
 mod types;
 mod utils;
 
-pub use self::tcp_connector::TcpConnector;
+pub use self::loopback::{LoopbackConnector, LoopbackListener, LoopbackNetwork};
+pub use self::resolver::{CachedResolver, StdResolver};
+pub use self::tcp_connector::{TcpConnector, DNS_CACHE_TTL};
 pub use self::tcp_listener::TcpListener;
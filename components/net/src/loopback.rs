@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::channel::mpsc;
+use futures::task::{Spawn, SpawnExt};
+use futures::{SinkExt, StreamExt};
+
+use async_std::task::sleep;
+
+use common::conn::{BoxFuture, ConnPairVec, FutTransform, Listener};
+use proto::net::messages::NetAddress;
+
+/// An in-process network. Connects `LoopbackConnector`s to `LoopbackListener`s over channels,
+/// without using real sockets. Useful for testing transforms that usually run over TCP.
+#[derive(Clone)]
+pub struct LoopbackNetwork<S> {
+    listeners: Arc<Mutex<HashMap<NetAddress, mpsc::Sender<ConnPairVec>>>>,
+    /// Delay applied to every frame
+    latency: Duration,
+    /// Sending a frame larger than this closes the connection
+    max_frame_length: usize,
+    spawner: S,
+}
+
+impl<S> LoopbackNetwork<S>
+where
+    S: Clone,
+{
+    pub fn new(latency: Duration, max_frame_length: usize, spawner: S) -> Self {
+        LoopbackNetwork {
+            listeners: Arc::new(Mutex::new(HashMap::new())),
+            latency,
+            max_frame_length,
+            spawner,
+        }
+    }
+
+    pub fn listener(&self) -> LoopbackListener<S> {
+        LoopbackListener {
+            network: self.clone(),
+        }
+    }
+
+    pub fn connector(&self) -> LoopbackConnector<S> {
+        LoopbackConnector {
+            network: self.clone(),
+        }
+    }
+}
+
+/// Forward frames from `receiver` to `sender`, delaying every frame by `latency`.
+/// Frames are forwarded in order, so the delays of consecutive frames add up.
+async fn forward_frames(
+    mut receiver: mpsc::Receiver<Vec<u8>>,
+    mut sender: mpsc::Sender<Vec<u8>>,
+    latency: Duration,
+    max_frame_length: usize,
+) {
+    while let Some(frame) = receiver.next().await {
+        if frame.len() > max_frame_length {
+            warn!(
+                "forward_frames(): Frame too large: {} > {}",
+                frame.len(),
+                max_frame_length
+            );
+            return;
+        }
+        if latency > Duration::from_secs(0) {
+            sleep(latency).await;
+        }
+        if sender.send(frame).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Listen for incoming connections on a `LoopbackNetwork`
+pub struct LoopbackListener<S> {
+    network: LoopbackNetwork<S>,
+}
+
+impl<S> Listener for LoopbackListener<S> {
+    type Connection = ConnPairVec;
+    type Config = ();
+    type Arg = NetAddress;
+
+    fn listen(
+        self,
+        net_address: Self::Arg,
+    ) -> (mpsc::Sender<Self::Config>, mpsc::Receiver<Self::Connection>) {
+        let (config_sender, _config_sender_receiver) = mpsc::channel(0);
+        let (conn_sender, conn_receiver) = mpsc::channel(0);
+
+        // Listening is immediate, so it is safe to connect as soon as this function returns:
+        if self
+            .network
+            .listeners
+            .lock()
+            .unwrap()
+            .insert(net_address.clone(), conn_sender)
+            .is_some()
+        {
+            warn!(
+                "LoopbackListener: Replacing a listener at {:?}",
+                net_address.as_str()
+            );
+        }
+
+        (config_sender, conn_receiver)
+    }
+}
+
+/// Connect to `LoopbackListener`s of a `LoopbackNetwork`
+#[derive(Clone)]
+pub struct LoopbackConnector<S> {
+    network: LoopbackNetwork<S>,
+}
+
+impl<S> FutTransform for LoopbackConnector<S>
+where
+    S: Spawn + Send,
+{
+    type Input = NetAddress;
+    type Output = Option<ConnPairVec>;
+
+    fn transform(&mut self, net_address: Self::Input) -> BoxFuture<'_, Self::Output> {
+        Box::pin(async move {
+            let opt_conn_sender = self
+                .network
+                .listeners
+                .lock()
+                .unwrap()
+                .get(&net_address)
+                .cloned();
+            let mut conn_sender = opt_conn_sender?;
+
+            let (client_sender, forward_receiver) = mpsc::channel(0);
+            let (forward_sender, server_receiver) = mpsc::channel(0);
+            let (server_sender, backward_receiver) = mpsc::channel(0);
+            let (backward_sender, client_receiver) = mpsc::channel(0);
+
+            let latency = self.network.latency;
+            let max_frame_length = self.network.max_frame_length;
+            self.network
+                .spawner
+                .spawn(forward_frames(
+                    forward_receiver,
+                    forward_sender,
+                    latency,
+                    max_frame_length,
+                ))
+                .ok()?;
+            self.network
+                .spawner
+                .spawn(forward_frames(
+                    backward_receiver,
+                    backward_sender,
+                    latency,
+                    max_frame_length,
+                ))
+                .ok()?;
+
+            let server_conn_pair = ConnPairVec::from_raw(server_sender, server_receiver);
+            if conn_sender.send(server_conn_pair).await.is_err() {
+                // The listener was dropped:
+                self.network.listeners.lock().unwrap().remove(&net_address);
+                return None;
+            }
+            Some(ConnPairVec::from_raw(client_sender, client_receiver))
+        })
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_std::net::ToSocketAddrs;
+
+use common::conn::{BoxFuture, FutTransform};
+
+use proto::net::messages::NetAddress;
+
+/// Resolve a `NetAddress` into a list of socket addresses using the system resolver.
+#[derive(Debug, Clone)]
+pub struct StdResolver;
+
+impl FutTransform for StdResolver {
+    type Input = NetAddress;
+    type Output = Option<Vec<SocketAddr>>;
+
+    fn transform(&mut self, net_address: Self::Input) -> BoxFuture<'_, Self::Output> {
+        Box::pin(async move {
+            let socket_addrs: Vec<_> = net_address.as_str().to_socket_addrs().await.ok()?.collect();
+            if socket_addrs.is_empty() {
+                None
+            } else {
+                Some(socket_addrs)
+            }
+        })
+    }
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    socket_addrs: Vec<SocketAddr>,
+    resolved_at: Instant,
+}
+
+/// A resolver that remembers successful resolutions for `ttl`.
+/// Clones share the same cache.
+#[derive(Debug, Clone)]
+pub struct CachedResolver<R> {
+    resolver: R,
+    ttl: Duration,
+    cache: Arc<Mutex<HashMap<NetAddress, CacheEntry>>>,
+}
+
+impl<R> CachedResolver<R> {
+    pub fn new(resolver: R, ttl: Duration) -> Self {
+        CachedResolver {
+            resolver,
+            ttl,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Forget the cached resolution of `net_address`, if any.
+    /// Used when connecting to the cached addresses fails.
+    pub fn invalidate(&self, net_address: &NetAddress) {
+        self.cache.lock().unwrap().remove(net_address);
+    }
+
+    fn get_cached(&self, net_address: &NetAddress) -> Option<Vec<SocketAddr>> {
+        let mut cache = self.cache.lock().unwrap();
+        let is_fresh = cache.get(net_address)?.resolved_at.elapsed() < self.ttl;
+        if is_fresh {
+            Some(cache[net_address].socket_addrs.clone())
+        } else {
+            cache.remove(net_address);
+            None
+        }
+    }
+}
+
+impl<R> FutTransform for CachedResolver<R>
+where
+    R: FutTransform<Input = NetAddress, Output = Option<Vec<SocketAddr>>> + Send,
+{
+    type Input = NetAddress;
+    type Output = Option<Vec<SocketAddr>>;
+
+    fn transform(&mut self, net_address: Self::Input) -> BoxFuture<'_, Self::Output> {
+        Box::pin(async move {
+            if let Some(socket_addrs) = self.get_cached(&net_address) {
+                return Some(socket_addrs);
+            }
+            let socket_addrs = self.resolver.transform(net_address.clone()).await?;
+            self.cache.lock().unwrap().insert(
+                net_address,
+                CacheEntry {
+                    socket_addrs: socket_addrs.clone(),
+                    resolved_at: Instant::now(),
+                },
+            );
+            Some(socket_addrs)
+        })
+    }
+}
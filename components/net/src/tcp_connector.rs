@@ -1,32 +1,49 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
 use common::conn::{BoxFuture, ConnPairVec, FutTransform};
 
 use futures::task::Spawn;
 
-// use std::net::SocketAddr;
 use async_std::net::TcpStream;
 
 use proto::net::messages::NetAddress;
 
+use crate::resolver::{CachedResolver, StdResolver};
 use crate::utils::tcp_stream_to_conn_pair;
 
+/// Amount of time we keep a DNS resolution before resolving again.
+pub const DNS_CACHE_TTL: Duration = Duration::from_secs(60);
+
 #[derive(Debug, Clone)]
-pub struct TcpConnector<S> {
+pub struct TcpConnector<S, R = StdResolver> {
     max_frame_length: usize,
+    resolver: CachedResolver<R>,
     spawner: S,
 }
 
 impl<S> TcpConnector<S> {
     pub fn new(max_frame_length: usize, spawner: S) -> Self {
+        TcpConnector::with_resolver(max_frame_length, StdResolver, DNS_CACHE_TTL, spawner)
+    }
+}
+
+impl<S, R> TcpConnector<S, R> {
+    /// Create a connector that resolves addresses using `resolver`,
+    /// caching every successful resolution for `ttl`.
+    pub fn with_resolver(max_frame_length: usize, resolver: R, ttl: Duration, spawner: S) -> Self {
         TcpConnector {
             max_frame_length,
+            resolver: CachedResolver::new(resolver, ttl),
             spawner,
         }
     }
 }
 
-impl<S> FutTransform for TcpConnector<S>
+impl<S, R> FutTransform for TcpConnector<S, R>
 where
     S: Spawn + Send,
+    R: FutTransform<Input = NetAddress, Output = Option<Vec<SocketAddr>>> + Send,
 {
     type Input = NetAddress;
     type Output = Option<ConnPairVec>;
@@ -34,7 +51,15 @@ where
     fn transform(&mut self, net_address: Self::Input) -> BoxFuture<'_, Self::Output> {
         Box::pin(async move {
             info!("TcpConnector: Connecting to {:?}", net_address.as_str());
-            let tcp_stream = TcpStream::connect(net_address.as_str()).await.ok()?;
+            let socket_addrs = self.resolver.transform(net_address.clone()).await?;
+            let tcp_stream = match TcpStream::connect(&socket_addrs[..]).await {
+                Ok(tcp_stream) => tcp_stream,
+                Err(_) => {
+                    // The cached addresses might be stale:
+                    self.resolver.invalidate(&net_address);
+                    return None;
+                }
+            };
 
             Some(tcp_stream_to_conn_pair(
                 tcp_stream,
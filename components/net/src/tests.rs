@@ -1,16 +1,19 @@
 use std::convert::TryFrom;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
-use std::time::Duration;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use futures::channel::mpsc;
 use futures::executor::{block_on, ThreadPool};
 use futures::task::Spawn;
 use futures::{SinkExt, StreamExt};
 
-use common::conn::{ConnPairVec, FutTransform, Listener};
+use common::conn::{BoxFuture, ConnPairVec, FutTransform, Listener};
 use proto::net::messages::NetAddress;
 
 // use crate::net_connector::NetConnector;
+use crate::loopback::LoopbackNetwork;
 use crate::tcp_connector::TcpConnector;
 use crate::tcp_listener::TcpListener;
 
@@ -125,3 +128,130 @@ fn test_net_connector_v4_drop_sender() {
     let thread_pool = ThreadPool::new().unwrap();
     block_on(task_net_connector_v4_drop_sender(thread_pool.clone()));
 }
+
+/// A resolver that always resolves to `socket_addr`, counting the amount of resolutions.
+#[derive(Clone)]
+struct MockResolver {
+    socket_addr: SocketAddr,
+    num_resolves: Arc<AtomicUsize>,
+}
+
+impl FutTransform for MockResolver {
+    type Input = NetAddress;
+    type Output = Option<Vec<SocketAddr>>;
+
+    fn transform(&mut self, _net_address: Self::Input) -> BoxFuture<'_, Self::Output> {
+        self.num_resolves.fetch_add(1, Ordering::SeqCst);
+        let socket_addr = self.socket_addr;
+        Box::pin(async move { Some(vec![socket_addr]) })
+    }
+}
+
+async fn task_tcp_connector_caches_resolution<S>(spawner: S)
+where
+    S: Spawn + Clone + Send + 'static,
+{
+    let (_tcp_connector, mut incoming_connections, listen_address) =
+        get_conn(spawner.clone()).await;
+
+    let num_resolves = Arc::new(AtomicUsize::new(0));
+    let mock_resolver = MockResolver {
+        socket_addr: listen_address.as_str().parse().unwrap(),
+        num_resolves: num_resolves.clone(),
+    };
+    let mut tcp_connector = TcpConnector::with_resolver(
+        TEST_MAX_FRAME_LEN,
+        mock_resolver,
+        Duration::from_secs(3600),
+        spawner.clone(),
+    );
+
+    let net_address = NetAddress::try_from("relay.example.com:1337".to_owned()).unwrap();
+    for _ in 0..2usize {
+        let _client_conn = tcp_connector.transform(net_address.clone()).await.unwrap();
+        let _server_conn = incoming_connections.next().await.unwrap();
+    }
+    // The second connection used the cached resolution:
+    assert_eq!(num_resolves.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_tcp_connector_caches_resolution() {
+    let thread_pool = ThreadPool::new().unwrap();
+    block_on(task_tcp_connector_caches_resolution(thread_pool.clone()));
+}
+
+async fn task_tcp_connector_invalidates_on_failure<S>(spawner: S)
+where
+    S: Spawn + Clone + Send + 'static,
+{
+    // Nobody listens on this port:
+    let available_port = get_available_port_v4().await;
+    let num_resolves = Arc::new(AtomicUsize::new(0));
+    let mock_resolver = MockResolver {
+        socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), available_port),
+        num_resolves: num_resolves.clone(),
+    };
+    let mut tcp_connector = TcpConnector::with_resolver(
+        TEST_MAX_FRAME_LEN,
+        mock_resolver,
+        Duration::from_secs(3600),
+        spawner.clone(),
+    );
+
+    let net_address = NetAddress::try_from("relay.example.com:1337".to_owned()).unwrap();
+    for _ in 0..2usize {
+        assert!(tcp_connector.transform(net_address.clone()).await.is_none());
+    }
+    // A failed connection invalidates the cached resolution:
+    assert_eq!(num_resolves.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_tcp_connector_invalidates_on_failure() {
+    let thread_pool = ThreadPool::new().unwrap();
+    block_on(task_tcp_connector_invalidates_on_failure(
+        thread_pool.clone(),
+    ));
+}
+
+async fn task_loopback_latency<S>(spawner: S)
+where
+    S: Spawn + Clone + Send + 'static,
+{
+    let latency = Duration::from_millis(50);
+    let network = LoopbackNetwork::new(latency, TEST_MAX_FRAME_LEN, spawner);
+    let net_address = NetAddress::try_from("loopback:1337".to_owned()).unwrap();
+
+    let (_config_sender, mut incoming_conns) = network.listener().listen(net_address.clone());
+    let mut connector = network.connector();
+
+    // Nobody listens on this address:
+    let other_address = NetAddress::try_from("loopback:1338".to_owned()).unwrap();
+    assert!(connector.transform(other_address).await.is_none());
+
+    let (mut client_sender, mut client_receiver) =
+        connector.transform(net_address).await.unwrap().split();
+    let (mut server_sender, mut server_receiver) = incoming_conns.next().await.unwrap().split();
+
+    let start = Instant::now();
+    client_sender.send(vec![1, 2, 3]).await.unwrap();
+    assert_eq!(server_receiver.next().await.unwrap(), vec![1, 2, 3]);
+    server_sender.send(vec![3, 2, 1]).await.unwrap();
+    assert_eq!(client_receiver.next().await.unwrap(), vec![3, 2, 1]);
+    // Both frames were delayed:
+    assert!(start.elapsed() >= latency * 2);
+
+    // A frame that is too large closes the connection:
+    client_sender
+        .send(vec![0; TEST_MAX_FRAME_LEN + 1])
+        .await
+        .unwrap();
+    assert!(server_receiver.next().await.is_none());
+}
+
+#[test]
+fn test_loopback_latency() {
+    let thread_pool = ThreadPool::new().unwrap();
+    block_on(task_loopback_latency(thread_pool.clone()));
+}
@@ -0,0 +1,147 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::types::NodeState;
+
+/// Version of the exported node state format.
+/// Should be increased whenever the serialized structure of NodeState changes.
+pub const NODE_STATE_EXPORT_VERSION: u32 = 2;
+
+/// The oldest version of the exported node state format that can still be imported.
+/// Version 1 has no `currency_infos` field. It is imported with no currency display metadata.
+const MIN_NODE_STATE_EXPORT_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum ImportStateError {
+    SerdeJsonError(serde_json::Error),
+    /// The state was exported using an unsupported version of the export format
+    VersionMismatch {
+        expected: u32,
+        found: u32,
+    },
+}
+
+impl From<serde_json::Error> for ImportStateError {
+    fn from(e: serde_json::Error) -> Self {
+        ImportStateError::SerdeJsonError(e)
+    }
+}
+
+#[derive(Serialize)]
+struct ExportedStateRef<'a, B: Clone> {
+    version: u32,
+    node_state: &'a NodeState<B>,
+}
+
+/// Used to read the version of an exported state before parsing the full state.
+#[derive(Deserialize)]
+struct ExportedVersion {
+    version: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(bound = "B: DeserializeOwned")]
+struct ExportedState<B: Clone> {
+    node_state: NodeState<B>,
+}
+
+/// Export a node's state, to be imported later (possibly on another machine) using
+/// `import_state()`.
+pub fn export_state<B>(node_state: &NodeState<B>) -> Vec<u8>
+where
+    B: Clone + Serialize,
+{
+    let exported_state = ExportedStateRef {
+        version: NODE_STATE_EXPORT_VERSION,
+        node_state,
+    };
+    // Serializing a NodeState into memory should never fail:
+    serde_json::to_vec(&exported_state).unwrap()
+}
+
+/// Import a node's state that was previously exported using `export_state()`.
+/// States exported using an older supported version of the export format are migrated.
+/// States exported using an unsupported version are rejected.
+pub fn import_state<B>(data: &[u8]) -> Result<NodeState<B>, ImportStateError>
+where
+    B: Clone + DeserializeOwned,
+{
+    let exported_version: ExportedVersion = serde_json::from_slice(data)?;
+    if exported_version.version < MIN_NODE_STATE_EXPORT_VERSION
+        || exported_version.version > NODE_STATE_EXPORT_VERSION
+    {
+        return Err(ImportStateError::VersionMismatch {
+            expected: NODE_STATE_EXPORT_VERSION,
+            found: exported_version.version,
+        });
+    }
+    // Fields added since version 1 are filled with their serde defaults:
+    let exported_state: ExportedState<B> = serde_json::from_slice(data)?;
+    Ok(exported_state.node_state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use proto::crypto::PublicKey;
+    use proto::net::messages::NetAddress;
+
+    #[test]
+    fn test_export_import_state() {
+        let local_public_key = PublicKey::from(&[0xaa; PublicKey::len()]);
+        let node_state = NodeState::<NetAddress>::new(local_public_key.clone());
+
+        let data = export_state(&node_state);
+        let imported_state = import_state::<NetAddress>(&data).unwrap();
+        assert_eq!(
+            imported_state.funder_state.local_public_key,
+            local_public_key
+        );
+    }
+
+    #[test]
+    fn test_import_state_version1() {
+        let local_public_key = PublicKey::from(&[0xaa; PublicKey::len()]);
+        let node_state = NodeState::<NetAddress>::new(local_public_key.clone());
+
+        // Create an export of version 1, which has no currency_infos field:
+        let mut exported: serde_json::Value =
+            serde_json::from_slice(&export_state(&node_state)).unwrap();
+        exported["version"] = serde_json::Value::from(1);
+        let removed = exported["node_state"]
+            .as_object_mut()
+            .unwrap()
+            .remove("currency_infos");
+        assert!(removed.is_some());
+        let data = serde_json::to_vec(&exported).unwrap();
+
+        let imported_state = import_state::<NetAddress>(&data).unwrap();
+        assert_eq!(
+            imported_state.funder_state.local_public_key,
+            local_public_key
+        );
+        assert!(imported_state.currency_infos.is_empty());
+    }
+
+    #[test]
+    fn test_import_state_version_mismatch() {
+        let data = br#"{"version":0,"node_state":null}"#;
+        match import_state::<NetAddress>(&data[..]) {
+            Err(ImportStateError::VersionMismatch {
+                expected: NODE_STATE_EXPORT_VERSION,
+                found: 0,
+            }) => {}
+            _ => unreachable!(),
+        }
+
+        let data = br#"{"version":3,"node_state":null}"#;
+        match import_state::<NetAddress>(&data[..]) {
+            Err(ImportStateError::VersionMismatch {
+                expected: NODE_STATE_EXPORT_VERSION,
+                found: 3,
+            }) => {}
+            _ => unreachable!(),
+        }
+    }
+}
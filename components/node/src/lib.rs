@@ -17,9 +17,14 @@ extern crate serde;
 #[macro_use]
 extern crate quickcheck_derive;
 
+mod export;
 mod node;
+mod trace;
 mod types;
 
+pub use self::export::{export_state, import_state, ImportStateError, NODE_STATE_EXPORT_VERSION};
 pub use self::node::{node, NodeError};
+pub use self::trace::{FriendTrace, MessageTracer, TracedMessage};
 pub use self::types::{NodeConfig, NodeMutation, NodeState};
 pub use app_server::{ConnPairServer, IncomingAppConnection};
+pub use funder::{PaymentEvent, PaymentSuccessEvent};
@@ -11,7 +11,7 @@ use proto::crypto::PublicKey;
 
 use database::DatabaseClient;
 use identity::IdentityClient;
-use timer::TimerClient;
+use timer::{TimerClient, TimerTick};
 
 use app_server::{app_server_loop, AppServerError, IncomingAppConnection};
 use channeler::{spawn_channeler, ChannelerError};
@@ -36,11 +36,13 @@ use proto::index_server::messages::IndexServerAddress;
 use proto::net::messages::NetAddress;
 use proto::report::convert::funder_report_to_index_client_state;
 
+use crate::trace::{trace_channeler_to_funder, trace_funder_to_channeler};
 use crate::types::{create_node_report, NodeConfig, NodeMutation, NodeState};
 
 #[derive(Debug, From)]
 pub enum NodeError {
     RequestPublicKeyError,
+    RequestTimerStreamError,
     DatabaseIdentityMismatch,
     SpawnError,
     ChannelerError(ChannelerError),
@@ -97,13 +99,56 @@ where
         .map_err(|_| NodeError::SpawnError)
 }
 
+fn funder_to_channeler_message(
+    funder_message: FunderOutgoingComm<NetAddress>,
+) -> FunderToChanneler<RelayAddress> {
+    match funder_message {
+        FunderOutgoingComm::ChannelerConfig(channeler_config) => match channeler_config {
+            ChannelerConfig::SetRelays(relay_addresses) => {
+                FunderToChanneler::SetRelays(relay_addresses)
+            }
+            ChannelerConfig::UpdateFriend(channeler_update_friend) => {
+                FunderToChanneler::UpdateFriend(channeler_update_friend)
+            }
+            ChannelerConfig::RemoveFriend(friend_public_key) => {
+                FunderToChanneler::RemoveFriend(friend_public_key)
+            }
+        },
+        FunderOutgoingComm::FriendMessage((public_key, friend_message)) => {
+            // let data = serialize_friend_message(&friend_message);
+            let data = friend_message.proto_serialize();
+            FunderToChanneler::Message((public_key, data))
+        }
+    }
+}
+
+/// Forward messages from the funder to the channeler.
+///
+/// Both sides are bounded channels, and a message is only taken from the funder after the
+/// previous one was accepted by the channeler. Therefore a slow channeler applies backpressure to
+/// the funder (The funder blocks on sending), instead of messages being dropped or buffered
+/// without bound.
+async fn funder_to_channeler_adapter<ST>(
+    mut to_channeler_messages: ST,
+    mut to_channeler: mpsc::Sender<FunderToChanneler<RelayAddress>>,
+) where
+    ST: Stream<Item = FunderToChanneler<RelayAddress>> + Unpin,
+{
+    while let Some(to_channeler_message) = to_channeler_messages.next().await {
+        if to_channeler.send(to_channeler_message).await.is_err() {
+            return;
+        }
+    }
+}
+
 fn node_spawn_funder<R, S>(
     node_config: &NodeConfig,
     identity_client: IdentityClient,
+    timer_stream: mpsc::Receiver<TimerTick>,
     funder_state: FunderState<NetAddress>,
     mut database_client: DatabaseClient<NodeMutation<NetAddress>>,
-    mut from_channeler: mpsc::Receiver<ChannelerToFunder>,
-    mut to_channeler: mpsc::Sender<FunderToChanneler<RelayAddress>>,
+    from_channeler: mpsc::Receiver<ChannelerToFunder>,
+    to_channeler: mpsc::Sender<FunderToChanneler<RelayAddress>>,
     from_app_server: mpsc::Receiver<FunderIncomingControl<NetAddress>>,
     to_app_server: mpsc::Sender<FunderOutgoingControl<NetAddress>>,
     rng: R,
@@ -140,6 +185,8 @@ where
         .map_err(|_| NodeError::SpawnError)?;
 
     // Channeler to funder adapter:
+    let mut from_channeler =
+        trace_channeler_to_funder(from_channeler, node_config.opt_message_tracer.clone());
     let (mut incoming_comm_sender, incoming_comm) = mpsc::channel(0);
     let channeler_to_funder_adapter = async move {
         while let Some(channeler_message) = from_channeler.next().await {
@@ -171,37 +218,19 @@ where
         .spawn(channeler_to_funder_adapter)
         .map_err(|_| NodeError::SpawnError)?;
 
-    let (outgoing_comm_sender, mut outgoing_comm) = mpsc::channel(0);
+    let (outgoing_comm_sender, outgoing_comm) = mpsc::channel(0);
 
     // Funder to Channeler adapter:
-    let funder_to_channeler_adapter = async move {
-        while let Some(funder_message) = outgoing_comm.next().await {
-            let to_channeler_message = match funder_message {
-                FunderOutgoingComm::ChannelerConfig(channeler_config) => match channeler_config {
-                    ChannelerConfig::SetRelays(relay_addresses) => {
-                        FunderToChanneler::SetRelays(relay_addresses)
-                    }
-                    ChannelerConfig::UpdateFriend(channeler_update_friend) => {
-                        FunderToChanneler::UpdateFriend(channeler_update_friend)
-                    }
-                    ChannelerConfig::RemoveFriend(friend_public_key) => {
-                        FunderToChanneler::RemoveFriend(friend_public_key)
-                    }
-                },
-                FunderOutgoingComm::FriendMessage((public_key, friend_message)) => {
-                    // let data = serialize_friend_message(&friend_message);
-                    let data = friend_message.proto_serialize();
-                    FunderToChanneler::Message((public_key, data))
-                }
-            };
-            if to_channeler.send(to_channeler_message).await.is_err() {
-                return;
-            }
-        }
-    };
+    let to_channeler_messages = trace_funder_to_channeler(
+        outgoing_comm.map(funder_to_channeler_message),
+        node_config.opt_message_tracer.clone(),
+    );
 
     spawner
-        .spawn(funder_to_channeler_adapter)
+        .spawn(funder_to_channeler_adapter(
+            to_channeler_messages,
+            to_channeler,
+        ))
         .map_err(|_| NodeError::SpawnError)?;
 
     let funder_fut = funder_loop(
@@ -209,13 +238,17 @@ where
         rng,
         from_app_server,
         incoming_comm,
+        timer_stream,
         to_app_server,
         outgoing_comm_sender,
         node_config.max_node_relays,
         node_config.max_operations_in_batch,
         node_config.max_pending_user_requests,
+        node_config.pending_transaction_timeout_ticks,
+        node_config.max_move_token_retransmits,
         funder_state,
         funder_db_client,
+        node_config.opt_payment_event_sender.clone(),
     );
 
     spawner
@@ -322,6 +355,7 @@ where
         node_config.max_open_index_client_requests,
         node_config.keepalive_ticks,
         node_config.backoff_ticks,
+        node_config.index_mutations_batch_ticks,
         index_connector,
         rng,
         spawner.clone(),
@@ -396,9 +430,16 @@ where
     let (funder_to_app_server_sender, funder_to_app_server_receiver) =
         mpsc::channel(node_config.channel_len);
 
+    let funder_timer_stream = timer_client
+        .clone()
+        .request_timer_stream("node_spawn_funder".to_owned())
+        .await
+        .map_err(|_| NodeError::RequestTimerStreamError)?;
+
     let funder_handle = node_spawn_funder(
         &node_config,
         identity_client.clone(),
+        funder_timer_stream,
         node_state.funder_state.clone(),
         database_client.clone(),
         channeler_to_funder_receiver,
@@ -422,6 +463,7 @@ where
         app_server_to_index_client_sender,
         incoming_apps,
         initial_node_report.clone(),
+        node_config.max_app_queued_messages,
         spawner.clone(),
     );
 
@@ -453,3 +495,70 @@ where
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::executor::LocalPool;
+    use futures::future;
+    use futures::task::Poll;
+
+    const CHANNEL_LEN: usize = 4;
+
+    fn remove_friend_message(index: u8) -> FunderOutgoingComm<NetAddress> {
+        FunderOutgoingComm::ChannelerConfig(ChannelerConfig::RemoveFriend(PublicKey::from(
+            &[index; PublicKey::len()],
+        )))
+    }
+
+    #[test]
+    fn test_funder_to_channeler_backpressure() {
+        let mut local_pool = LocalPool::new();
+
+        let (mut funder_sender, outgoing_comm) = mpsc::channel(0);
+        let (to_channeler, mut from_funder) = mpsc::channel(CHANNEL_LEN);
+
+        local_pool
+            .spawner()
+            .spawn(funder_to_channeler_adapter(
+                outgoing_comm.map(funder_to_channeler_message),
+                to_channeler,
+            ))
+            .unwrap();
+
+        // The channeler is stalled. The funder can only send a bounded amount of messages before
+        // it blocks:
+        let mut num_sent = 0usize;
+        for index in 0..0x40u8 {
+            local_pool.run_until_stalled();
+            if funder_sender
+                .try_send(remove_friend_message(index))
+                .is_err()
+            {
+                break;
+            }
+            num_sent += 1;
+        }
+        // Channeler channel + one message held by the adapter + funder channel:
+        assert!(num_sent <= CHANNEL_LEN + 3);
+        assert!(num_sent < 0x40);
+
+        // A blocked send does not complete while the channeler is stalled:
+        let mut send_fut = funder_sender.send(remove_friend_message(0xff));
+        let res = local_pool.run_until(future::poll_fn(|context| {
+            Poll::Ready(send_fut.poll_unpin(context))
+        }));
+        assert!(res.is_pending());
+
+        // Once the channeler reads, the funder can proceed, and the messages arrive in order:
+        let message = local_pool.run_until(from_funder.next()).unwrap();
+        match message {
+            FunderToChanneler::RemoveFriend(public_key) => {
+                assert_eq!(public_key, PublicKey::from(&[0; PublicKey::len()]))
+            }
+            _ => unreachable!(),
+        }
+        local_pool.run_until(send_fut).unwrap();
+    }
+}
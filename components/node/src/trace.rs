@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use futures::{Stream, StreamExt};
+
+use proto::crypto::PublicKey;
+use proto::funder::messages::{ChannelerToFunder, FunderToChanneler};
+
+/// A short summary of a message passed between the Channeler and the Funder.
+/// We keep only a summary (and not the message itself), to avoid holding large buffers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TracedMessage {
+    /// Friend is now online (Channeler -> Funder)
+    Online,
+    /// Friend is now offline (Channeler -> Funder)
+    Offline,
+    /// Incoming message from a friend (Channeler -> Funder), with its length in bytes
+    Incoming(usize),
+    /// Outgoing message to a friend (Funder -> Channeler), with its length in bytes
+    Outgoing(usize),
+    /// Friend's information was updated (Funder -> Channeler)
+    UpdateFriend,
+    /// Friend was removed (Funder -> Channeler)
+    RemoveFriend,
+}
+
+/// Message counters for a single friend
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FriendTrace {
+    pub online: u64,
+    pub offline: u64,
+    pub incoming: u64,
+    pub outgoing: u64,
+    pub update_friend: u64,
+    pub remove_friend: u64,
+    pub opt_last_message: Option<TracedMessage>,
+}
+
+impl FriendTrace {
+    fn record(&mut self, traced_message: TracedMessage) {
+        let counter = match &traced_message {
+            TracedMessage::Online => &mut self.online,
+            TracedMessage::Offline => &mut self.offline,
+            TracedMessage::Incoming(_) => &mut self.incoming,
+            TracedMessage::Outgoing(_) => &mut self.outgoing,
+            TracedMessage::UpdateFriend => &mut self.update_friend,
+            TracedMessage::RemoveFriend => &mut self.remove_friend,
+        };
+        *counter = counter.saturating_add(1);
+        self.opt_last_message = Some(traced_message);
+    }
+}
+
+/// A shared map of per friend message counters.
+/// Cloning a `MessageTracer` gives another handle to the same map.
+#[derive(Debug, Clone, Default)]
+pub struct MessageTracer {
+    friends: Arc<Mutex<HashMap<PublicKey, FriendTrace>>>,
+}
+
+impl MessageTracer {
+    pub fn new() -> Self {
+        MessageTracer {
+            friends: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Get the current counters for a friend, if any message was traced for this friend.
+    pub fn friend_trace(&self, friend_public_key: &PublicKey) -> Option<FriendTrace> {
+        self.friends.lock().unwrap().get(friend_public_key).cloned()
+    }
+
+    /// Get a copy of the counters of all friends
+    pub fn snapshot(&self) -> HashMap<PublicKey, FriendTrace> {
+        self.friends.lock().unwrap().clone()
+    }
+
+    fn record(&self, friend_public_key: &PublicKey, traced_message: TracedMessage) {
+        self.friends
+            .lock()
+            .unwrap()
+            .entry(friend_public_key.clone())
+            .or_default()
+            .record(traced_message);
+    }
+
+    fn trace_from_channeler(&self, message: &ChannelerToFunder) {
+        match message {
+            ChannelerToFunder::Online(public_key) => self.record(public_key, TracedMessage::Online),
+            ChannelerToFunder::Offline(public_key) => {
+                self.record(public_key, TracedMessage::Offline)
+            }
+            ChannelerToFunder::Message((public_key, data)) => {
+                self.record(public_key, TracedMessage::Incoming(data.len()))
+            }
+        }
+    }
+
+    fn trace_to_channeler<RA>(&self, message: &FunderToChanneler<RA>) {
+        match message {
+            FunderToChanneler::Message((public_key, data)) => {
+                self.record(public_key, TracedMessage::Outgoing(data.len()))
+            }
+            // Local relays are not related to any specific friend:
+            FunderToChanneler::SetRelays(_) => {}
+            FunderToChanneler::UpdateFriend(update_friend) => self.record(
+                &update_friend.friend_public_key,
+                TracedMessage::UpdateFriend,
+            ),
+            FunderToChanneler::RemoveFriend(public_key) => {
+                self.record(public_key, TracedMessage::RemoveFriend)
+            }
+        }
+    }
+}
+
+/// Forward messages from the Channeler to the Funder unchanged,
+/// updating `opt_tracer` (if provided) on every message.
+pub fn trace_channeler_to_funder<ST>(
+    stream: ST,
+    opt_tracer: Option<MessageTracer>,
+) -> impl Stream<Item = ChannelerToFunder> + Unpin
+where
+    ST: Stream<Item = ChannelerToFunder> + Unpin,
+{
+    stream.inspect(move |message| {
+        if let Some(tracer) = &opt_tracer {
+            tracer.trace_from_channeler(message);
+        }
+    })
+}
+
+/// Forward messages from the Funder to the Channeler unchanged,
+/// updating `opt_tracer` (if provided) on every message.
+pub fn trace_funder_to_channeler<ST, RA>(
+    stream: ST,
+    opt_tracer: Option<MessageTracer>,
+) -> impl Stream<Item = FunderToChanneler<RA>> + Unpin
+where
+    ST: Stream<Item = FunderToChanneler<RA>> + Unpin,
+{
+    stream.inspect(move |message| {
+        if let Some(tracer) = &opt_tracer {
+            tracer.trace_to_channeler(message);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::executor::block_on;
+    use futures::stream;
+
+    #[test]
+    fn test_trace_online_offline() {
+        let pk_a = PublicKey::from(&[0xaa; PublicKey::len()]);
+        let pk_b = PublicKey::from(&[0xbb; PublicKey::len()]);
+
+        let messages = vec![
+            ChannelerToFunder::Online(pk_a.clone()),
+            ChannelerToFunder::Offline(pk_a.clone()),
+            ChannelerToFunder::Online(pk_a.clone()),
+            ChannelerToFunder::Online(pk_b.clone()),
+            ChannelerToFunder::Message((pk_b.clone(), vec![1, 2, 3])),
+        ];
+
+        let tracer = MessageTracer::new();
+        let traced = trace_channeler_to_funder(stream::iter(messages), Some(tracer.clone()));
+        let output = block_on(traced.collect::<Vec<_>>());
+
+        // Messages are forwarded unchanged:
+        assert_eq!(output.len(), 5);
+        match &output[1] {
+            ChannelerToFunder::Offline(public_key) => assert_eq!(public_key, &pk_a),
+            _ => unreachable!(),
+        }
+
+        let trace_a = tracer.friend_trace(&pk_a).unwrap();
+        assert_eq!(trace_a.online, 2);
+        assert_eq!(trace_a.offline, 1);
+        assert_eq!(trace_a.incoming, 0);
+        assert_eq!(trace_a.opt_last_message, Some(TracedMessage::Online));
+
+        let trace_b = tracer.friend_trace(&pk_b).unwrap();
+        assert_eq!(trace_b.online, 1);
+        assert_eq!(trace_b.offline, 0);
+        assert_eq!(trace_b.incoming, 1);
+        assert_eq!(trace_b.opt_last_message, Some(TracedMessage::Incoming(3)));
+    }
+
+    #[test]
+    fn test_trace_funder_to_channeler() {
+        let pk_a = PublicKey::from(&[0xaa; PublicKey::len()]);
+
+        let messages: Vec<FunderToChanneler<u32>> = vec![
+            FunderToChanneler::SetRelays(vec![1, 2]),
+            FunderToChanneler::Message((pk_a.clone(), vec![0; 8])),
+            FunderToChanneler::RemoveFriend(pk_a.clone()),
+        ];
+
+        let tracer = MessageTracer::new();
+        let traced = trace_funder_to_channeler(stream::iter(messages), Some(tracer.clone()));
+        let output = block_on(traced.collect::<Vec<_>>());
+        assert_eq!(output.len(), 3);
+
+        let trace_a = tracer.friend_trace(&pk_a).unwrap();
+        assert_eq!(trace_a.outgoing, 1);
+        assert_eq!(trace_a.remove_friend, 1);
+        assert_eq!(trace_a.opt_last_message, Some(TracedMessage::RemoveFriend));
+        assert_eq!(tracer.snapshot().len(), 1);
+    }
+
+    #[test]
+    fn test_trace_disabled() {
+        let pk_a = PublicKey::from(&[0xaa; PublicKey::len()]);
+        let messages = vec![ChannelerToFunder::Online(pk_a.clone())];
+        let traced = trace_channeler_to_funder(stream::iter(messages), None);
+        let output = block_on(traced.collect::<Vec<_>>());
+        assert_eq!(output.len(), 1);
+    }
+}
@@ -1,26 +1,38 @@
+use futures::channel::mpsc;
+
 use common::mutable_state::MutableState;
 
 use funder::report::create_initial_report;
-use funder::{FunderMutation, FunderState};
+use funder::{FunderMutation, FunderState, PaymentEvent};
 use index_client::{IndexClientConfig, IndexClientConfigMutation};
 
 use proto::app_server::messages::NodeReport;
 use proto::crypto::PublicKey;
+use proto::funder::messages::Currency;
 use proto::index_client::messages::IndexClientReport;
+use proto::report::messages::CurrencyInfo;
 
 use signature::canonical::CanonicalSerialize;
 
+use crate::trace::MessageTracer;
+
 // TODO: Can we remote the Clone bound here?
 #[derive(Arbitrary, Debug, Clone)]
 pub enum NodeMutation<B: Clone> {
     Funder(FunderMutation<B>),
     IndexClient(IndexClientConfigMutation<B>),
+    /// Set the display metadata of a currency, replacing any previous metadata
+    SetCurrencyInfo(CurrencyInfo),
+    RemoveCurrencyInfo(Currency),
 }
 
 #[derive(Arbitrary, Debug, Clone, Serialize, Deserialize)]
 pub struct NodeState<B: Clone> {
     pub funder_state: FunderState<B>,
     pub index_client_config: IndexClientConfig<B>,
+    /// Display metadata of currencies. Used only for presentation.
+    #[serde(default)]
+    pub currency_infos: Vec<CurrencyInfo>,
 }
 
 impl<B> NodeState<B>
@@ -31,6 +43,7 @@ where
         NodeState {
             funder_state: FunderState::new(local_public_key, Vec::new()),
             index_client_config: IndexClientConfig::new(),
+            currency_infos: Vec::new(),
         }
     }
 }
@@ -55,6 +68,17 @@ where
                 .index_client_config
                 .mutate(index_client_mutation)
                 .map_err(|_| NodeMutateError),
+            NodeMutation::SetCurrencyInfo(currency_info) => {
+                self.currency_infos
+                    .retain(|cur_info| cur_info.currency != currency_info.currency);
+                self.currency_infos.push(currency_info.clone());
+                Ok(())
+            }
+            NodeMutation::RemoveCurrencyInfo(currency) => {
+                self.currency_infos
+                    .retain(|cur_info| &cur_info.currency != currency);
+                Ok(())
+            }
         }
     }
 }
@@ -79,6 +103,7 @@ where
     NodeReport {
         funder_report: create_initial_report(&node_state.funder_state),
         index_client_report: create_index_client_report(&node_state.index_client_config),
+        currency_infos: node_state.currency_infos.clone(),
     }
 }
 
@@ -99,14 +124,39 @@ pub struct NodeConfig {
     pub max_concurrent_encrypt: usize,
     /// The amount of ticks we are willing to wait until a connection is established.
     pub conn_timeout_ticks: usize,
-    /// Maximum amount of operations in one move token message
+    /// Maximum amount of operations in one outgoing move token message.
+    /// Capped by the protocol limit (`MAX_OPERATIONS_IN_BATCH`), which applies to incoming move
+    /// tokens.
     pub max_operations_in_batch: usize,
     /// The size we allocate for the user send funds requests queue.
     pub max_pending_user_requests: usize,
     /// Maximum amount of concurrent index client requests:
     pub max_open_index_client_requests: usize,
+    /// The amount of ticks the index client collects mutations before sending them to the
+    /// index server. 0 means that mutations are sent immediately.
+    pub index_mutations_batch_ticks: usize,
     /// Maximum amount of relays a node may use.
     pub max_node_relays: usize,
+    /// The amount of ticks we wait for a response to a pending request before canceling it
+    /// (Upstream, for a forwarded request). 0 means that requests never time out.
+    pub pending_transaction_timeout_ticks: usize,
+    /// Maximum amount of old move tokens (Duplicates or retransmission requests) we accept from
+    /// a friend in a row before declaring the channel inconsistent. 0 means no limit.
+    pub max_move_token_retransmits: usize,
+    /// Optional tap over the messages passed between the Channeler and the Funder.
+    /// Useful for debugging connectivity.
+    pub opt_message_tracer: Option<MessageTracer>,
+    /// Optional sink for the outcomes of payments made by this node (Successes and
+    /// cancellations). Useful for pushing settled payments to an external ledger.
+    /// The funder never waits for the receiving side. If the channel is full, the sender is
+    /// dropped and no more events are sent.
+    pub opt_payment_event_sender: Option<mpsc::Sender<PaymentEvent>>,
+    /// Maximum amount of concurrently open incoming app connections.
+    /// App connections beyond this amount are closed immediately.
+    pub max_incoming_app_conns: usize,
+    /// Maximum amount of messages queued for sending to a single app.
+    /// An app that lets more messages pile up is disconnected.
+    pub max_app_queued_messages: usize,
     /*
     /// Maximum amount of encryption set ups we allow to occur at the same time
     /// for incoming app connections
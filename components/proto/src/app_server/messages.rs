@@ -9,15 +9,18 @@ use crate::crypto::{InvoiceId, PaymentId, PublicKey, Uid};
 
 use crate::funder::messages::{
     AckClosePayment, AddFriend, AddInvoice, Commit, CreatePayment, CreateTransaction, Currency,
-    RemoveFriendCurrency, ResetFriendChannel, ResponseClosePayment, SetFriendCurrencyMaxDebt,
-    SetFriendCurrencyRate, SetFriendName, SetFriendRelays, TransactionResult,
+    RemoveFriendCurrency, ResetFriendChannel, ResponseCancelPayment, ResponseClosePayment,
+    ResponseCommitInvoice, ResponseFriendRelays, SetFriendCurrencyMaxDebt, SetFriendCurrencyRate,
+    SetFriendName, SetFriendRelays, SetRelayName, TransactionResult,
 };
 use crate::index_client::messages::{
     ClientResponseRoutes, IndexClientReport, IndexClientReportMutation,
 };
 use crate::index_server::messages::{NamedIndexServerAddress, RequestRoutes};
 use crate::net::messages::NetAddress;
-use crate::report::messages::{FunderReport, FunderReportMutation};
+use crate::report::messages::{
+    CurrencyInfo, FunderReport, FunderReportMutateError, FunderReportMutation,
+};
 
 // TODO: Move NamedRelayAddress and RelayAddress to another place in offset-proto?
 
@@ -40,6 +43,24 @@ pub struct RelayAddress<B = NetAddress> {
     pub address: B,
 }
 
+impl<B> NamedRelayAddress<B> {
+    /// Change the name of the relay, keeping its public key and address
+    pub fn rename(self, name: String) -> Self {
+        NamedRelayAddress { name, ..self }
+    }
+}
+
+impl<B> RelayAddress<B> {
+    /// Attach a name to the relay address
+    pub fn with_name(self, name: String) -> NamedRelayAddress<B> {
+        NamedRelayAddress {
+            public_key: self.public_key,
+            address: self.address,
+            name,
+        }
+    }
+}
+
 impl<B> From<NamedRelayAddress<B>> for RelayAddress<B> {
     fn from(from: NamedRelayAddress<B>) -> Self {
         RelayAddress {
@@ -54,6 +75,9 @@ impl<B> From<NamedRelayAddress<B>> for RelayAddress<B> {
 pub struct NodeReport<B = NetAddress> {
     pub funder_report: FunderReport<B>,
     pub index_client_report: IndexClientReport<B>,
+    /// Display metadata of currencies, as configured in the node's state.
+    /// Does not change while the node is running.
+    pub currency_infos: Vec<CurrencyInfo>,
 }
 
 #[capnp_conv(crate::report_capnp::node_report_mutation)]
@@ -98,7 +122,7 @@ pub struct ReportMutations<B = NetAddress> {
 
 #[allow(clippy::large_enum_variant)]
 #[capnp_conv(crate::app_server_capnp::app_server_to_app)]
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum AppServerToApp<B = NetAddress> {
     /// Funds:
     TransactionResult(TransactionResult),
@@ -107,6 +131,9 @@ pub enum AppServerToApp<B = NetAddress> {
     // Report(NodeReport<B>),
     ReportMutations(ReportMutations<B>),
     ResponseRoutes(ClientResponseRoutes),
+    ResponseCommitInvoice(ResponseCommitInvoice),
+    ResponseFriendRelays(ResponseFriendRelays),
+    ResponseCancelPayment(ResponseCancelPayment),
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -136,6 +163,7 @@ pub enum AppRequest<B = NetAddress> {
     /// Manage locally used relays:
     AddRelay(NamedRelayAddress<B>),
     RemoveRelay(PublicKey),
+    SetRelayName(SetRelayName),
     /// Friend management:
     AddFriend(AddFriend<B>),
     SetFriendRelays(SetFriendRelays<B>),
@@ -143,6 +171,8 @@ pub enum AppRequest<B = NetAddress> {
     RemoveFriend(PublicKey),
     EnableFriend(PublicKey),
     DisableFriend(PublicKey),
+    /// Stop new requests with a friend, while letting in-flight transactions complete
+    PauseFriend(PublicKey),
     OpenFriendCurrency(OpenFriendCurrency),
     CloseFriendCurrency(CloseFriendCurrency),
     SetFriendCurrencyMaxDebt(SetFriendCurrencyMaxDebt),
@@ -153,6 +183,8 @@ pub enum AppRequest<B = NetAddress> {
     CreatePayment(CreatePayment),
     CreateTransaction(CreateTransaction),
     RequestClosePayment(PaymentId),
+    /// Cancel all the transactions of a payment that were not yet sent, and close the payment
+    CancelPayment(PaymentId),
     AckClosePayment(AckClosePayment),
     /// Seller:
     AddInvoice(AddInvoice),
@@ -163,6 +195,44 @@ pub enum AppRequest<B = NetAddress> {
     /// Manage index servers:
     AddIndexServer(NamedIndexServerAddress<B>),
     RemoveIndexServer(PublicKey),
+    /// Has no effect. Acknowledged by the node with an empty ReportMutations:
+    Ping,
+}
+
+impl<B> AppRequest<B> {
+    /// The kind of permission an app needs to issue this request
+    pub fn kind(&self) -> AppRequestKind {
+        match self {
+            AppRequest::AddRelay(_)
+            | AppRequest::RemoveRelay(_)
+            | AppRequest::SetRelayName(_)
+            | AppRequest::AddFriend(_)
+            | AppRequest::SetFriendRelays(_)
+            | AppRequest::SetFriendName(_)
+            | AppRequest::RemoveFriend(_)
+            | AppRequest::EnableFriend(_)
+            | AppRequest::DisableFriend(_)
+            | AppRequest::PauseFriend(_)
+            | AppRequest::OpenFriendCurrency(_)
+            | AppRequest::CloseFriendCurrency(_)
+            | AppRequest::SetFriendCurrencyMaxDebt(_)
+            | AppRequest::SetFriendCurrencyRate(_)
+            | AppRequest::RemoveFriendCurrency(_)
+            | AppRequest::ResetFriendChannel(_)
+            | AppRequest::AddIndexServer(_)
+            | AppRequest::RemoveIndexServer(_) => AppRequestKind::Config,
+            AppRequest::CreatePayment(_)
+            | AppRequest::CreateTransaction(_)
+            | AppRequest::RequestClosePayment(_)
+            | AppRequest::CancelPayment(_)
+            | AppRequest::AckClosePayment(_) => AppRequestKind::Buyer,
+            AppRequest::AddInvoice(_)
+            | AppRequest::CancelInvoice(_)
+            | AppRequest::CommitInvoice(_) => AppRequestKind::Seller,
+            AppRequest::RequestRoutes(_) => AppRequestKind::Routes,
+            AppRequest::Ping => AppRequestKind::Ping,
+        }
+    }
 }
 #[capnp_conv(crate::app_server_capnp::app_to_app_server)]
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -182,8 +252,19 @@ impl<B> AppToAppServer<B> {
 
 // TODO: Move this code to a separate module:
 
-#[derive(Debug)]
-pub struct NodeReportMutateError;
+#[derive(Debug, PartialEq, Eq)]
+pub enum NodeReportMutateError {
+    FunderReportMutateError(FunderReportMutateError),
+}
+
+/// Failure to apply a list of report mutations
+#[derive(Debug, PartialEq, Eq)]
+pub struct ReportMutationsError {
+    /// Index of the mutation that could not be applied.
+    /// All mutations before this index were applied.
+    pub index: usize,
+    pub error: NodeReportMutateError,
+}
 
 impl<B> NodeReport<B>
 where
@@ -197,13 +278,46 @@ where
             NodeReportMutation::<B>::Funder(mutation) => self
                 .funder_report
                 .mutate(mutation)
-                .map_err(|_| NodeReportMutateError)?,
+                .map_err(NodeReportMutateError::FunderReportMutateError)?,
             NodeReportMutation::<B>::IndexClient(mutation) => {
                 self.index_client_report.mutate(mutation)
             }
         };
         Ok(())
     }
+
+    /// Apply a list of mutations, in order.
+    /// On failure, returns the index of the failing mutation, and the reason for the failure.
+    pub fn mutate_all(
+        &mut self,
+        mutations: &[NodeReportMutation<B>],
+    ) -> Result<(), ReportMutationsError> {
+        for (index, mutation) in mutations.iter().enumerate() {
+            self.mutate(mutation)
+                .map_err(|error| ReportMutationsError { index, error })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(any(test, feature = "test-utils"))]
+impl<B> NodeReport<B> {
+    /// A report of a node with no relays, friends, index servers or currencies.
+    /// Used for testing.
+    pub fn dummy() -> Self {
+        NodeReport {
+            funder_report: FunderReport {
+                local_public_key: PublicKey::from(&[0xaa; PublicKey::len()]),
+                relays: Vec::new(),
+                friends: std::collections::HashMap::new(),
+            },
+            index_client_report: IndexClientReport {
+                index_servers: Vec::new(),
+                opt_connected_server: None,
+            },
+            currency_infos: Vec::new(),
+        }
+    }
 }
 
 impl<B> MutableState for NodeReport<B>
@@ -231,3 +345,195 @@ pub struct AppPermissions {
     /// Can configure friends
     pub config: bool,
 }
+
+/// The kind of permission required for an app request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AppRequestKind {
+    Routes,
+    Buyer,
+    Seller,
+    Config,
+    /// Allowed for every app
+    Ping,
+}
+
+impl AppPermissions {
+    /// Can only receive reports from the node
+    pub fn read_only() -> Self {
+        AppPermissions {
+            routes: false,
+            buyer: false,
+            seller: false,
+            config: false,
+        }
+    }
+
+    /// Can find routes and send credits
+    pub fn buyer_only() -> Self {
+        AppPermissions {
+            routes: true,
+            buyer: true,
+            seller: false,
+            config: false,
+        }
+    }
+
+    /// Can issue any request
+    pub fn full() -> Self {
+        AppPermissions {
+            routes: true,
+            buyer: true,
+            seller: true,
+            config: true,
+        }
+    }
+
+    /// Do these permissions allow issuing requests of kind `app_request_kind`?
+    pub fn allows(&self, app_request_kind: AppRequestKind) -> bool {
+        match app_request_kind {
+            AppRequestKind::Routes => self.routes,
+            AppRequestKind::Buyer => self.buyer,
+            AppRequestKind::Seller => self.seller,
+            AppRequestKind::Config => self.config,
+            AppRequestKind::Ping => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::report::messages::FriendReportMutation;
+
+    #[test]
+    fn test_node_report_mutate_all_unknown_friend() {
+        let mut node_report = NodeReport::<u32>::dummy();
+
+        let relay_public_key = PublicKey::from(&[0xbb; PublicKey::len()]);
+        let friend_public_key = PublicKey::from(&[0xcc; PublicKey::len()]);
+        let mutations = vec![
+            NodeReportMutation::Funder(FunderReportMutation::AddRelay(NamedRelayAddress {
+                public_key: relay_public_key.clone(),
+                address: 0x1234u32,
+                name: "relay".to_owned(),
+            })),
+            // Mutation of a friend that was not added yet:
+            NodeReportMutation::Funder(FunderReportMutation::PkFriendReportMutation((
+                friend_public_key.clone(),
+                FriendReportMutation::SetName("friend".to_owned()),
+            ))),
+        ];
+
+        assert_eq!(
+            node_report.mutate_all(&mutations),
+            Err(ReportMutationsError {
+                index: 1,
+                error: NodeReportMutateError::FunderReportMutateError(
+                    FunderReportMutateError::FriendDoesNotExist(friend_public_key)
+                ),
+            })
+        );
+        // Mutations before the failing mutation were applied:
+        assert_eq!(node_report.funder_report.relays.len(), 1);
+        assert_eq!(
+            node_report.funder_report.relays[0].public_key,
+            relay_public_key
+        );
+    }
+
+    #[test]
+    fn test_relay_address_with_name() {
+        let named_relay_address = NamedRelayAddress {
+            public_key: PublicKey::from(&[0xaa; PublicKey::len()]),
+            address: 0x1234u32,
+            name: "relay".to_owned(),
+        };
+        let relay_address = RelayAddress::from(named_relay_address.clone());
+        assert_eq!(relay_address.public_key, named_relay_address.public_key);
+        assert_eq!(relay_address.address, named_relay_address.address);
+
+        // Adding the name back restores the original named relay address:
+        assert_eq!(
+            relay_address.with_name("relay".to_owned()),
+            named_relay_address
+        );
+    }
+
+    #[test]
+    fn test_named_relay_address_rename() {
+        let named_relay_address = NamedRelayAddress {
+            public_key: PublicKey::from(&[0xaa; PublicKey::len()]),
+            address: 0x1234u32,
+            name: "relay".to_owned(),
+        };
+        let renamed = named_relay_address.clone().rename("new_relay".to_owned());
+        assert_eq!(renamed.name, "new_relay");
+        assert_eq!(renamed.public_key, named_relay_address.public_key);
+        assert_eq!(renamed.address, named_relay_address.address);
+    }
+
+    #[test]
+    fn test_app_permissions_allows() {
+        // (permissions, [routes, buyer, seller, config])
+        let matrix = vec![
+            (AppPermissions::read_only(), [false, false, false, false]),
+            (AppPermissions::buyer_only(), [true, true, false, false]),
+            (AppPermissions::full(), [true, true, true, true]),
+        ];
+        let kinds = [
+            AppRequestKind::Routes,
+            AppRequestKind::Buyer,
+            AppRequestKind::Seller,
+            AppRequestKind::Config,
+        ];
+
+        for (app_permissions, expected) in &matrix {
+            for (kind, allowed) in kinds.iter().zip(expected.iter()) {
+                assert_eq!(app_permissions.allows(*kind), *allowed);
+            }
+        }
+    }
+
+    #[test]
+    fn test_app_request_kind() {
+        let pk = PublicKey::from(&[0xaa; PublicKey::len()]);
+        let payment_id = PaymentId::from(&[0xbb; PaymentId::len()]);
+        let invoice_id = InvoiceId::from(&[0xcc; InvoiceId::len()]);
+
+        let requests: Vec<(AppRequest<u32>, AppRequestKind)> = vec![
+            (AppRequest::RemoveRelay(pk.clone()), AppRequestKind::Config),
+            (AppRequest::RemoveFriend(pk.clone()), AppRequestKind::Config),
+            (AppRequest::EnableFriend(pk.clone()), AppRequestKind::Config),
+            (
+                AppRequest::DisableFriend(pk.clone()),
+                AppRequestKind::Config,
+            ),
+            (AppRequest::PauseFriend(pk.clone()), AppRequestKind::Config),
+            (
+                AppRequest::RemoveIndexServer(pk.clone()),
+                AppRequestKind::Config,
+            ),
+            (
+                AppRequest::RequestClosePayment(payment_id.clone()),
+                AppRequestKind::Buyer,
+            ),
+            (AppRequest::CancelPayment(payment_id), AppRequestKind::Buyer),
+            (
+                AppRequest::CancelInvoice(invoice_id),
+                AppRequestKind::Seller,
+            ),
+        ];
+
+        for (app_request, kind) in &requests {
+            assert_eq!(app_request.kind(), *kind);
+            assert!(AppPermissions::full().allows(app_request.kind()));
+            assert!(!AppPermissions::read_only().allows(app_request.kind()));
+        }
+
+        // Every app may ping:
+        let ping = AppRequest::<u32>::Ping;
+        assert_eq!(ping.kind(), AppRequestKind::Ping);
+        assert!(AppPermissions::read_only().allows(ping.kind()));
+    }
+}
@@ -17,6 +17,25 @@ pub const TICK_MS: usize = 1000;
 /// Amount of ticks to wait before rekeying a secure channel.
 pub const TICKS_TO_REKEY: usize = 60 * 60 * (1000 / TICK_MS); // 1 hour
 
+/// Amount of ticks to wait for the initial exchange of a secure channel to complete before
+/// giving up on the connection.
+pub const SC_HANDSHAKE_TIMEOUT_TICKS: usize = 0x10;
+
+/// Funder: The amount of ticks to wait for a response to a pending request before canceling it.
+pub const PENDING_TRANSACTION_TIMEOUT_TICKS: usize = 60 * 60 * (1000 / TICK_MS); // 1 hour
+
+/// Funder: Maximum amount of old move tokens a friend may send in a row before the channel
+/// is considered inconsistent.
+pub const MAX_MOVE_TOKEN_RETRANSMITS: usize = 0x40;
+
+/// App server: Maximum amount of messages that may be queued for sending to a single app.
+/// An app that lets more messages pile up is considered lagging, and is disconnected.
+pub const MAX_APP_QUEUED_MESSAGES: usize = 0x400;
+
+/// Index client: The amount of ticks to collect index mutations before sending them to the index
+/// server. Multiple mutations for the same friend and currency are coalesced into one.
+pub const INDEX_MUTATIONS_BATCH_TICKS: usize = 2;
+
 /// If no message was sent for this amount of ticks, the connection will be closed
 pub const KEEPALIVE_TICKS: usize = 0x20;
 
@@ -24,6 +43,10 @@ pub const KEEPALIVE_TICKS: usize = 0x20;
 /// sends identification of which type of connection it is.
 pub const RELAY_CONN_TIMEOUT_TICKS: usize = 4;
 
+/// Relay server: The amount of ticks a connection may go without receiving anything from the
+/// remote side (Including keepalives) before it is closed.
+pub const RELAY_IDLE_TIMEOUT_TICKS: usize = 60 * 60 * (1000 / TICK_MS); // 1 hour
+
 /// The stream TCP connection is split into prefix length frames. This is the maximum allowed
 /// length for such frame, measured in bytes.
 pub const MAX_FRAME_LENGTH: usize = 1 << 20; // 1[MB]
@@ -39,3 +62,8 @@ pub const MAX_NET_ADDRESS_LENGTH: usize = 256;
 /// We limit this number because sending many relays in a single move token message
 /// might exceed frame length
 pub const MAX_NODE_RELAYS: usize = 16;
+
+/// Maximum amount of relays that may be configured for a friend (After removing relays with
+/// duplicate public keys). The relays of a friend are the relays of the remote node, hence the
+/// same limit as `MAX_NODE_RELAYS` applies.
+pub const MAX_FRIEND_RELAYS: usize = MAX_NODE_RELAYS;
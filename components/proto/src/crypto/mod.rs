@@ -57,3 +57,40 @@ type_capnp_serde!(RandValue, common_capnp::rand_value, (x0, x1));
 // An Universally Unique Identifier (UUID).
 define_fixed_bytes!(Uid, 16);
 type_capnp_serde!(Uid, common_capnp::uid, (x0, x1));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use common::ser_utils::ser_b64;
+
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+    struct B64Keys {
+        #[serde(with = "ser_b64")]
+        url_safe: PublicKey,
+        #[serde(with = "ser_b64::standard")]
+        standard: PublicKey,
+    }
+
+    #[test]
+    fn test_ser_b64_standard_public_key() {
+        // 0xfb bytes encode to '+' and '/' in standard base64, and to '-' and '_' in URL-safe
+        // base64:
+        let public_key = PublicKey::from(&[0xfb; PublicKey::len()]);
+        let b64_keys = B64Keys {
+            url_safe: public_key.clone(),
+            standard: public_key,
+        };
+        let ser_str = serde_json::to_string(&b64_keys).unwrap();
+        assert_eq!(
+            ser_str,
+            concat!(
+                r#"{"url_safe":"-_v7-_v7-_v7-_v7-_v7-_v7-_v7-_v7-_v7-_v7-_s","#,
+                r#""standard":"+/v7+/v7+/v7+/v7+/v7+/v7+/v7+/v7+/v7+/v7+/s="}"#
+            )
+        );
+
+        let b64_keys2: B64Keys = serde_json::from_str(&ser_str).unwrap();
+        assert_eq!(b64_keys, b64_keys2);
+    }
+}
@@ -1,21 +1,71 @@
 use serde::{Deserialize, Serialize};
 
-use crate::crypto::{PrivateKey, PublicKey};
+use crate::crypto::{InvoiceId, PrivateKey, PublicKey};
 
 use mutual_from::mutual_from;
 
 use common::ser_utils::{ser_b64, ser_string};
 
 use crate::app_server::messages::{AppPermissions, RelayAddress};
+use crate::funder::messages::Currency;
 use crate::net::messages::NetAddress;
 
-/// A helper structure for serialize and deserializing IndexServerAddress.
+/// A trusted application, and its permissions.
+///
+/// The effective permissions of the app are calculated as follows:
+/// - Start from the permissions of the named template, if `template` is specified.
+///   Otherwise, start with no permissions at all.
+/// - Every permission explicitly specified in `permissions` overrides the starting permission.
 #[derive(Arbitrary, Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct TrustedAppFile {
     #[serde(with = "ser_b64")]
     pub public_key: PublicKey,
-    pub permissions: AppPermissions,
+    /// Name of a shared permissions template
+    #[serde(rename = "template")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opt_template: Option<String>,
+    #[serde(default)]
+    pub permissions: AppPermissionsFile,
+}
+
+/// Permissions of a trusted app, as written in a trusted app file.
+/// Permissions that are not specified are taken from the template of the app.
+#[derive(Arbitrary, Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AppPermissionsFile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub routes: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub buyer: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seller: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<bool>,
+}
+
+impl AppPermissionsFile {
+    /// Calculate effective permissions: Explicitly specified permissions override the
+    /// permissions of `template`.
+    pub fn merge(&self, template: &AppPermissions) -> AppPermissions {
+        AppPermissions {
+            routes: self.routes.unwrap_or(template.routes),
+            buyer: self.buyer.unwrap_or(template.buyer),
+            seller: self.seller.unwrap_or(template.seller),
+            config: self.config.unwrap_or(template.config),
+        }
+    }
+}
+
+impl From<AppPermissions> for AppPermissionsFile {
+    fn from(app_permissions: AppPermissions) -> Self {
+        AppPermissionsFile {
+            routes: Some(app_permissions.routes),
+            buyer: Some(app_permissions.buyer),
+            seller: Some(app_permissions.seller),
+            config: Some(app_permissions.config),
+        }
+    }
 }
 
 #[derive(Arbitrary, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
@@ -82,3 +132,17 @@ pub struct NodeEntryFile {
     #[serde(with = "ser_b64")]
     pub app_private_key: PrivateKey,
 }
+
+/// An invoice, issued by a seller and given (out of band) to a buyer.
+#[derive(Arbitrary, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct InvoiceFile {
+    #[serde(with = "ser_b64")]
+    pub invoice_id: InvoiceId,
+    #[serde(with = "ser_string")]
+    pub currency: Currency,
+    #[serde(with = "ser_b64")]
+    pub dest_public_key: PublicKey,
+    #[serde(with = "ser_string")]
+    pub dest_payment: u128,
+}
@@ -120,11 +120,36 @@ pub struct UnsignedResponseSendFundsOp {
     pub rand_nonce: RandValue,
 }
 
+/// The reason for canceling a request
+#[capnp_conv(crate::common_capnp::cancel_reason)]
+#[derive(Arbitrary, Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub enum CancelReason {
+    /// The request could not be forwarded to the next node on the route
+    NoRoute,
+    /// A node on the route does not trust the previous node with the requested amount of credits
+    InsufficientTrust,
+    /// The request was pending for too long
+    Timeout,
+    /// A node on the route does not accept new requests
+    ClosedRequests,
+}
+
+#[capnp_conv(crate::common_capnp::opt_cancel_reason)]
+#[derive(Arbitrary, Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub enum OptCancelReason {
+    Empty,
+    Reason(CancelReason),
+}
+
 #[capnp_conv(crate::funder_capnp::cancel_send_funds_op)]
 #[derive(Arbitrary, Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
 pub struct CancelSendFundsOp {
     #[serde(with = "ser_b64")]
     pub request_id: Uid,
+    /// An untrusted hint about the reason for the cancellation. It is not signed, and any node
+    /// along the route may set it to any value.
+    #[capnp_conv(with = OptCancelReason)]
+    pub opt_reason: Option<CancelReason>,
 }
 
 #[allow(clippy::large_enum_variant)]
@@ -171,6 +196,11 @@ pub enum FriendTcOp {
     CollectSendFunds(CollectSendFundsOp),
 }
 
+/// Local relays, as sent inside a move token.
+///
+/// Note that `Empty` and `Relays(vec![])` are not the same:
+/// `Empty` means that the local relays did not change, while `Relays(vec![])` means that the
+/// sender currently has no relays. Both forms are kept as is during conversion.
 #[capnp_conv(crate::funder_capnp::move_token::opt_local_relays)]
 #[derive(Arbitrary, Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
 pub enum OptLocalRelays<B = NetAddress> {
@@ -216,6 +246,24 @@ impl From<OptLocalRelays<NetAddress>> for Option<Vec<RelayAddress<NetAddress>>>
     }
 }
 
+impl From<Option<CancelReason>> for OptCancelReason {
+    fn from(opt: Option<CancelReason>) -> Self {
+        match opt {
+            Some(reason) => OptCancelReason::Reason(reason),
+            None => OptCancelReason::Empty,
+        }
+    }
+}
+
+impl From<OptCancelReason> for Option<CancelReason> {
+    fn from(opt: OptCancelReason) -> Self {
+        match opt {
+            OptCancelReason::Reason(reason) => Some(reason),
+            OptCancelReason::Empty => None,
+        }
+    }
+}
+
 impl From<Option<Vec<Currency>>> for OptActiveCurrencies {
     fn from(opt: Option<Vec<Currency>>) -> Self {
         match opt {
@@ -425,6 +473,20 @@ pub struct ResetTerms {
     pub balance_for_reset: Vec<CurrencyBalance>,
 }
 
+/// The reason a token channel became inconsistent
+#[capnp_conv(crate::funder_capnp::inconsistency_reason)]
+#[derive(Arbitrary, Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+pub enum InconsistencyReason {
+    /// The remote side reported an inconsistency
+    RemoteReported,
+    /// A move token received from the remote side was invalid
+    InvalidMoveToken,
+    /// A move token received from the remote side contained too many operations
+    TooManyOperations,
+    /// The remote side sent too many old move tokens in a row
+    TooManyRetransmits,
+}
+
 #[capnp_conv(crate::funder_capnp::move_token_request)]
 #[derive(Arbitrary, PartialEq, Eq, Clone, Serialize, Debug)]
 pub struct MoveTokenRequest<B = NetAddress> {
@@ -544,6 +606,17 @@ impl FriendsRoute {
     pub fn is_part_valid(&self) -> bool {
         is_route_part_valid(&self)
     }
+
+    /// Check if `subroute` appears in the route as a sequence of consecutive public keys.
+    /// An empty subroute is contained in any route.
+    pub fn contains_subroute(&self, subroute: &[PublicKey]) -> bool {
+        if subroute.is_empty() {
+            return true;
+        }
+        self.public_keys
+            .windows(subroute.len())
+            .any(|window| window == subroute)
+    }
 }
 
 use std::ops::Deref;
@@ -610,6 +683,28 @@ fn is_route_part_valid<T: Hash + Eq>(route: &[T]) -> bool {
 pub enum FriendStatus {
     Enabled,
     Disabled,
+    /// Connected, but no new requests are sent or accepted.
+    /// Pending transactions may still complete.
+    Paused,
+}
+
+impl FriendStatus {
+    /// Should we maintain a connection to this friend?
+    pub fn is_connected(&self) -> bool {
+        match self {
+            FriendStatus::Enabled | FriendStatus::Paused => true,
+            FriendStatus::Disabled => false,
+        }
+    }
+
+    /// Can new requests be sent to (or received from) this friend?
+    pub fn accepts_requests(&self) -> bool {
+        if let FriendStatus::Enabled = self {
+            true
+        } else {
+            false
+        }
+    }
 }
 
 #[derive(Arbitrary, Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
@@ -628,6 +723,68 @@ impl RequestsStatus {
     }
 }
 
+/// A string that does not name any status
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseStatusError {
+    pub input: String,
+}
+
+impl fmt::Display for ParseStatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid status: {:?}", self.input)
+    }
+}
+
+impl fmt::Display for FriendStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status_str = match self {
+            FriendStatus::Enabled => "enabled",
+            FriendStatus::Disabled => "disabled",
+            FriendStatus::Paused => "paused",
+        };
+        write!(f, "{}", status_str)
+    }
+}
+
+impl FromStr for FriendStatus {
+    type Err = ParseStatusError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "enabled" => Ok(FriendStatus::Enabled),
+            "disabled" => Ok(FriendStatus::Disabled),
+            "paused" => Ok(FriendStatus::Paused),
+            _ => Err(ParseStatusError {
+                input: s.to_owned(),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for RequestsStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status_str = match self {
+            RequestsStatus::Open => "open",
+            RequestsStatus::Closed => "closed",
+        };
+        write!(f, "{}", status_str)
+    }
+}
+
+impl FromStr for RequestsStatus {
+    type Err = ParseStatusError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "open" => Ok(RequestsStatus::Open),
+            "closed" => Ok(RequestsStatus::Closed),
+            _ => Err(ParseStatusError {
+                input: s.to_owned(),
+            }),
+        }
+    }
+}
+
 /// Rates for forwarding a transaction
 /// For a transaction of `x` credits, the amount of fees will be:
 /// `(x * mul) / 2^32 + add`
@@ -676,8 +833,46 @@ impl Rate {
         let denominator = BigUint::from(self.mul) + (BigUint::from(1u128) << 32);
         (numerator / denominator).to_u128().unwrap()
     }
+
+    /// Create a commission only rate (`add = 0`) from a percentage.
+    /// For example, `from_percent(0.1)` is a commission of 0.1%.
+    ///
+    /// Returns `None` if the percentage is not a finite number in the range `[0, 100)`
+    /// (`mul` is scaled by `2^32`, so a commission of 100% can not be represented).
+    pub fn from_percent(percent: f64) -> Option<Rate> {
+        if !percent.is_finite() || percent < 0.0 {
+            return None;
+        }
+        let mul = (percent / 100.0 * RATE_MUL_SCALE).round();
+        if mul > f64::from(u32::max_value()) {
+            return None;
+        }
+        Some(Rate {
+            mul: mul as u32,
+            add: 0,
+        })
+    }
+
+    /// The commission part of the rate (`mul`), as a percentage.
+    pub fn as_percent(&self) -> f64 {
+        f64::from(self.mul) / RATE_MUL_SCALE * 100.0
+    }
+
+    /// Create a commission only rate (`add = 0`) from basis points.
+    /// One basis point is 0.01%.
+    pub fn from_basis_points(basis_points: f64) -> Option<Rate> {
+        Rate::from_percent(basis_points / 100.0)
+    }
+
+    /// The commission part of the rate (`mul`), in basis points.
+    pub fn as_basis_points(&self) -> f64 {
+        self.as_percent() * 100.0
+    }
 }
 
+/// `Rate::mul` is scaled by `2^32`
+const RATE_MUL_SCALE: f64 = 4_294_967_296.0;
+
 #[capnp_conv(crate::app_server_capnp::add_friend)]
 #[derive(Arbitrary, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct AddFriend<B = NetAddress> {
@@ -721,6 +916,13 @@ pub struct SetFriendName {
     pub name: String,
 }
 
+#[capnp_conv(crate::app_server_capnp::set_relay_name)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetRelayName {
+    pub relay_public_key: PublicKey,
+    pub name: String,
+}
+
 #[capnp_conv(crate::app_server_capnp::set_friend_relays)]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SetFriendRelays<B = NetAddress> {
@@ -805,6 +1007,35 @@ pub struct AddInvoice {
     /// Total amount of credits to be paid.
     #[capnp_conv(with = Wrapper<u128>)]
     pub total_dest_payment: u128,
+    /// Amount of timer ticks after which the invoice expires.
+    /// An expired invoice is canceled, and can not be committed.
+    #[capnp_conv(with = OptExpiryTicks)]
+    pub opt_expiry_ticks: Option<u64>,
+}
+
+#[capnp_conv(crate::app_server_capnp::add_invoice::opt_expiry_ticks)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptExpiryTicks {
+    Empty,
+    ExpiryTicks(u64),
+}
+
+impl From<Option<u64>> for OptExpiryTicks {
+    fn from(opt: Option<u64>) -> Self {
+        match opt {
+            Some(expiry_ticks) => OptExpiryTicks::ExpiryTicks(expiry_ticks),
+            None => OptExpiryTicks::Empty,
+        }
+    }
+}
+
+impl From<OptExpiryTicks> for Option<u64> {
+    fn from(opt: OptExpiryTicks) -> Self {
+        match opt {
+            OptExpiryTicks::ExpiryTicks(expiry_ticks) => Some(expiry_ticks),
+            OptExpiryTicks::Empty => None,
+        }
+    }
 }
 
 /// Start an invoice (A request for payment).
@@ -820,6 +1051,7 @@ pub struct AckClosePayment {
 pub enum FunderControl<B> {
     AddRelay(NamedRelayAddress<B>),
     RemoveRelay(PublicKey),
+    SetRelayName(SetRelayName),
     AddFriend(AddFriend<B>),
     RemoveFriend(RemoveFriend),
     SetFriendStatus(SetFriendStatus),
@@ -834,6 +1066,7 @@ pub enum FunderControl<B> {
     CreatePayment(CreatePayment),
     CreateTransaction(CreateTransaction),
     RequestClosePayment(PaymentId),
+    CancelPayment(PaymentId),
     AckClosePayment(AckClosePayment),
     // Seller API:
     AddInvoice(AddInvoice),
@@ -862,14 +1095,17 @@ impl<B> FunderIncomingControl<B> {
 pub enum RequestResult {
     Complete(Commit),
     Success,
-    // TODO: Should we add more information to the failure here?
-    Failure,
+    /// The request was canceled. Contains the reason for the cancellation, if known.
+    /// A reason reported by a remote node is an untrusted hint, and must not be relied upon.
+    #[capnp_conv(with = OptCancelReason)]
+    Failure(Option<CancelReason>),
 }
 
 #[capnp_conv(crate::app_server_capnp::transaction_result)]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TransactionResult {
     pub request_id: Uid,
+    /// On failure, the cancellation reason is only a hint (See `RequestResult::Failure`)
     pub result: RequestResult,
 }
 
@@ -896,11 +1132,59 @@ pub struct ResponseClosePayment {
     pub status: PaymentStatus,
 }
 
+/// The outcome of committing an invoice
+#[capnp_conv(crate::app_server_capnp::response_commit_invoice::result)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommitInvoiceResult {
+    Success,
+    /// Contains the missing amount
+    #[capnp_conv(with = Wrapper<u128>)]
+    NotFullyPaid(u128),
+    Failure,
+}
+
+#[capnp_conv(crate::app_server_capnp::response_commit_invoice)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseCommitInvoice {
+    pub invoice_id: InvoiceId,
+    pub result: CommitInvoiceResult,
+}
+
+/// The outcome of setting the relays of a friend (Using AddFriend or SetFriendRelays)
+#[capnp_conv(crate::app_server_capnp::response_friend_relays::result)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FriendRelaysResult {
+    Success,
+    /// The relay list is longer than `MAX_FRIEND_RELAYS`, after removing duplicate relays
+    TooManyRelays,
+    Failure,
+}
+
+#[capnp_conv(crate::app_server_capnp::response_friend_relays)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseFriendRelays {
+    pub friend_public_key: PublicKey,
+    pub result: FriendRelaysResult,
+}
+
+/// The outcome of canceling a payment (Using CancelPayment)
+#[capnp_conv(crate::app_server_capnp::response_cancel_payment)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseCancelPayment {
+    pub payment_id: PaymentId,
+    /// Transactions that were already sent to a friend, and could not be canceled.
+    /// Their credits remain frozen until the remote side resolves them.
+    pub in_flight: Vec<Uid>,
+}
+
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 pub enum FunderOutgoingControl<B: Clone> {
     TransactionResult(TransactionResult),
     ResponseClosePayment(ResponseClosePayment),
+    ResponseCommitInvoice(ResponseCommitInvoice),
+    ResponseFriendRelays(ResponseFriendRelays),
+    ResponseCancelPayment(ResponseCancelPayment),
     ReportMutations(FunderReportMutations<B>),
 }
 
@@ -949,6 +1233,21 @@ impl BalanceInfo {
 }
 
 impl McInfo {
+    /// Create a new `McInfo`. Balances are sorted by currency, so that two `McInfo`-s with the
+    /// same balances are equal (and hash equally) regardless of the original order of the balances.
+    pub fn new(
+        local_public_key: PublicKey,
+        remote_public_key: PublicKey,
+        mut balances: Vec<CurrencyBalanceInfo>,
+    ) -> Self {
+        balances.sort_by(|cbi1, cbi2| cbi1.currency.cmp(&cbi2.currency));
+        McInfo {
+            local_public_key,
+            remote_public_key,
+            balances,
+        }
+    }
+
     pub fn flip(self) -> McInfo {
         let balances = self
             .balances
@@ -967,6 +1266,40 @@ impl McInfo {
     }
 }
 
+/// Reasons for a commit not to match the invoice it is supposed to pay
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommitMismatch {
+    /// The commit is for a different currency than the invoice's currency
+    CurrencyMismatch,
+    /// The commit's `total_dest_payment` differs from the invoice's total payment
+    TotalDestPaymentMismatch,
+    /// The commit claims to pay more than the total payment
+    DestPaymentExceedsTotal,
+}
+
+impl Commit {
+    /// Check that the commit is consistent with an invoice of the given currency and total
+    /// payment. Should be called before committing an invoice.
+    ///
+    /// Note that this does not verify the commit's signature (See `signature::verify_commit`).
+    pub fn validate(
+        &self,
+        currency: &Currency,
+        total_dest_payment: u128,
+    ) -> Result<(), CommitMismatch> {
+        if &self.currency != currency {
+            return Err(CommitMismatch::CurrencyMismatch);
+        }
+        if self.total_dest_payment != total_dest_payment {
+            return Err(CommitMismatch::TotalDestPaymentMismatch);
+        }
+        if self.dest_payment > self.total_dest_payment {
+            return Err(CommitMismatch::DestPaymentExceedsTotal);
+        }
+        Ok(())
+    }
+}
+
 impl TokenInfo {
     pub fn flip(self) -> TokenInfo {
         TokenInfo {
@@ -980,6 +1313,80 @@ impl TokenInfo {
 mod tests {
     use super::*;
 
+    use quickcheck::QuickCheck;
+    use rand::{self, rngs::StdRng};
+
+    use crate::proto_ser::{ProtoDeserialize, ProtoSerialize};
+
+    /// Check that a type survives a capnp write and read round-trip.
+    /// Catches drift between a struct and its capnp schema.
+    macro_rules! capnp_ser_de_test {
+        ($test_name:ident, $msg_type:ty) => {
+            #[test]
+            fn $test_name() {
+                fn ser_de(msg: $msg_type) -> bool {
+                    let msg2 = <$msg_type>::proto_deserialize(&msg.proto_serialize()).unwrap();
+                    msg == msg2
+                }
+
+                let rng_seed: [u8; 32] = [1; 32];
+                let rng: StdRng = rand::SeedableRng::from_seed(rng_seed);
+
+                // Limit size, to avoid blowup to type size:
+                let size = 3usize;
+                QuickCheck::with_gen(quickcheck::StdGen::new(rng, size))
+                    .max_tests(100)
+                    .quickcheck(ser_de as fn($msg_type) -> bool);
+            }
+        };
+    }
+
+    capnp_ser_de_test!(qc_capnp_ser_de_move_token, MoveToken<NetAddress>);
+    capnp_ser_de_test!(qc_capnp_ser_de_request_send_funds_op, RequestSendFundsOp);
+    capnp_ser_de_test!(qc_capnp_ser_de_receipt, Receipt);
+    capnp_ser_de_test!(qc_capnp_ser_de_rate, Rate);
+
+    #[test]
+    fn test_status_display_from_str() {
+        for friend_status in vec![
+            FriendStatus::Enabled,
+            FriendStatus::Disabled,
+            FriendStatus::Paused,
+        ] {
+            let status_str = friend_status.to_string();
+            assert_eq!(status_str.parse::<FriendStatus>(), Ok(friend_status));
+        }
+        assert_eq!("enabled".parse::<FriendStatus>(), Ok(FriendStatus::Enabled));
+
+        for requests_status in vec![RequestsStatus::Open, RequestsStatus::Closed] {
+            let status_str = requests_status.to_string();
+            assert_eq!(status_str.parse::<RequestsStatus>(), Ok(requests_status));
+        }
+        assert_eq!(
+            "closed".parse::<RequestsStatus>(),
+            Ok(RequestsStatus::Closed)
+        );
+    }
+
+    #[test]
+    fn test_status_from_str_invalid() {
+        let parse_error = "Enabled".parse::<FriendStatus>().unwrap_err();
+        assert_eq!(
+            parse_error,
+            ParseStatusError {
+                input: "Enabled".to_owned()
+            }
+        );
+        assert_eq!(parse_error.to_string(), "Invalid status: \"Enabled\"");
+
+        assert_eq!(
+            "half-open".parse::<RequestsStatus>(),
+            Err(ParseStatusError {
+                input: "half-open".to_owned()
+            })
+        );
+    }
+
     #[test]
     fn test_friends_is_route_valid() {
         assert_eq!(is_route_valid(&[1]), false); // too short
@@ -999,6 +1406,188 @@ mod tests {
         assert_eq!(is_route_part_valid(&[1, 2, 3, 2, 4]), false); // should have no repetitions in a partial route
     }
 
+    #[test]
+    fn test_commit_validate() {
+        let currency = Currency::try_from("FST".to_owned()).unwrap();
+        let commit = Commit {
+            response_hash: HashResult::from(&[1; HashResult::len()]),
+            src_plain_lock: PlainLock::from(&[2; PlainLock::len()]),
+            dest_hashed_lock: HashedLock::from(&[3; HashedLock::len()]),
+            dest_payment: 40,
+            total_dest_payment: 100,
+            invoice_id: InvoiceId::from(&[4; InvoiceId::len()]),
+            currency: currency.clone(),
+            signature: Signature::from(&[5; Signature::len()]),
+        };
+        assert_eq!(commit.validate(&currency, 100), Ok(()));
+
+        let other_currency = Currency::try_from("FST2".to_owned()).unwrap();
+        assert_eq!(
+            commit.validate(&other_currency, 100),
+            Err(CommitMismatch::CurrencyMismatch)
+        );
+        assert_eq!(
+            commit.validate(&currency, 120),
+            Err(CommitMismatch::TotalDestPaymentMismatch)
+        );
+
+        // The commit's sum exceeds the total payment:
+        let mut large_commit = commit.clone();
+        large_commit.dest_payment = 101;
+        assert_eq!(
+            large_commit.validate(&currency, 100),
+            Err(CommitMismatch::DestPaymentExceedsTotal)
+        );
+    }
+
+    #[test]
+    fn test_opt_local_relays_conversion() {
+        let relay_address = RelayAddress {
+            public_key: PublicKey::from(&[0xaa; PublicKey::len()]),
+            address: NetAddress::try_from("relay:1234".to_owned()).unwrap(),
+        };
+
+        for opt_local_relays in vec![None, Some(vec![]), Some(vec![relay_address])] {
+            let converted = OptLocalRelays::from(opt_local_relays.clone());
+            match (&opt_local_relays, &converted) {
+                (None, OptLocalRelays::Empty) => {}
+                (Some(relays), OptLocalRelays::Relays(converted_relays)) => {
+                    assert_eq!(relays, converted_relays)
+                }
+                _ => unreachable!(),
+            };
+            assert_eq!(
+                Option::<Vec<RelayAddress>>::from(converted),
+                opt_local_relays
+            );
+
+            // `None` and `Some(vec![])` are kept distinct after serialization:
+            let move_token = MoveToken {
+                old_token: Signature::from(&[1; Signature::len()]),
+                currencies_operations: Vec::new(),
+                opt_local_relays: opt_local_relays.clone(),
+                opt_active_currencies: None,
+                info_hash: HashResult::from(&[2; HashResult::len()]),
+                rand_nonce: RandValue::from(&[3; RandValue::len()]),
+                new_token: Signature::from(&[4; Signature::len()]),
+            };
+            let move_token2 = MoveToken::proto_deserialize(&move_token.proto_serialize()).unwrap();
+            assert_eq!(move_token2.opt_local_relays, opt_local_relays);
+        }
+    }
+
+    #[test]
+    fn test_add_invoice_old_format_never_expires() {
+        let invoice_id = InvoiceId::from(&[1; InvoiceId::len()]);
+        let currency = Currency::try_from("FST".to_owned()).unwrap();
+
+        // Write an AddInvoice the way an older app would, without touching optExpiryTicks:
+        let mut builder = capnp::message::Builder::new_default();
+        {
+            let mut add_invoice =
+                builder.init_root::<crate::app_server_capnp::add_invoice::Builder>();
+            invoice_id.write_capnp(&mut add_invoice.reborrow().init_invoice_id());
+            currency.write_capnp(&mut add_invoice.reborrow().init_currency());
+            Wrapper::<u128>::from(100)
+                .write_capnp(&mut add_invoice.reborrow().init_total_dest_payment());
+        }
+        let mut data = Vec::new();
+        capnp::serialize_packed::write_message(&mut data, &builder).unwrap();
+
+        let add_invoice = AddInvoice::proto_deserialize(&data).unwrap();
+        assert_eq!(
+            add_invoice,
+            AddInvoice {
+                invoice_id,
+                currency,
+                total_dest_payment: 100,
+                opt_expiry_ticks: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_friends_route_contains_subroute() {
+        let pk = |i: u8| PublicKey::from(&[i; PublicKey::len()]);
+        let route = FriendsRoute {
+            public_keys: vec![pk(0), pk(1), pk(2), pk(3), pk(4)],
+        };
+
+        assert!(route.contains_subroute(&[]));
+        assert!(route.contains_subroute(&[pk(2)]));
+        assert!(route.contains_subroute(&[pk(0), pk(1)]));
+        assert!(route.contains_subroute(&[pk(1), pk(2), pk(3)]));
+        assert!(route.contains_subroute(&[pk(3), pk(4)]));
+        assert!(route.contains_subroute(&route.public_keys));
+
+        // Keys that appear in the route, but not consecutively or not in order:
+        assert!(!route.contains_subroute(&[pk(1), pk(3)]));
+        assert!(!route.contains_subroute(&[pk(2), pk(1)]));
+        // Partially overlapping with the end of the route:
+        assert!(!route.contains_subroute(&[pk(3), pk(4), pk(5)]));
+        // Not in the route at all:
+        assert!(!route.contains_subroute(&[pk(5)]));
+        // Longer than the route:
+        assert!(!route.contains_subroute(&[pk(0), pk(1), pk(2), pk(3), pk(4), pk(5)]));
+
+        let empty_route = FriendsRoute {
+            public_keys: Vec::new(),
+        };
+        assert!(empty_route.contains_subroute(&[]));
+        assert!(!empty_route.contains_subroute(&[pk(0)]));
+    }
+
+    #[test]
+    fn test_rate_percent_round_trip() {
+        let muls = [
+            0u32,
+            1,
+            2,
+            1000,
+            0x1000_0000,
+            0x8000_0000,
+            u32::max_value() - 1,
+            u32::max_value(),
+        ];
+        for &mul in &muls {
+            let rate = Rate { mul, add: 0 };
+            let rate_percent = Rate::from_percent(rate.as_percent()).unwrap();
+            assert!((i64::from(rate_percent.mul) - i64::from(mul)).abs() <= 1);
+
+            let rate_bps = Rate::from_basis_points(rate.as_basis_points()).unwrap();
+            assert!((i64::from(rate_bps.mul) - i64::from(mul)).abs() <= 1);
+        }
+    }
+
+    #[test]
+    fn test_rate_from_percent() {
+        assert_eq!(Rate::from_percent(0.0), Some(Rate { mul: 0, add: 0 }));
+        assert_eq!(
+            Rate::from_percent(50.0),
+            Some(Rate {
+                mul: 1 << 31,
+                add: 0
+            })
+        );
+        assert_eq!(
+            Rate::from_basis_points(5000.0),
+            Some(Rate {
+                mul: 1 << 31,
+                add: 0
+            })
+        );
+        let rate = Rate::from_percent(0.1).unwrap();
+        assert!((rate.as_percent() - 0.1).abs() < 1e-7);
+        assert!((rate.as_basis_points() - 10.0).abs() < 1e-5);
+
+        // Out of range inputs:
+        assert_eq!(Rate::from_percent(100.0), None);
+        assert_eq!(Rate::from_percent(-0.5), None);
+        assert_eq!(Rate::from_percent(std::f64::NAN), None);
+        assert_eq!(Rate::from_percent(std::f64::INFINITY), None);
+        assert_eq!(Rate::from_basis_points(1_000_000.0), None);
+    }
+
     use im::hashset::HashSet as ImHashSet;
 
     #[derive(Arbitrary, Clone)]
@@ -111,6 +111,8 @@ pub enum IndexClientReportMutation<ISA = NetAddress> {
 pub enum ResponseRoutesResult {
     Success(Vec<MultiRoute>),
     Failure,
+    /// No index server is connected, so no routes can be found at the moment
+    NoIndexServer,
 }
 
 #[capnp_conv(crate::app_server_capnp::client_response_routes)]
@@ -42,6 +42,31 @@ impl From<OptExclude> for Option<Edge> {
     }
 }
 
+#[capnp_conv(crate::index_capnp::request_routes::opt_max_hops)]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum OptMaxHops {
+    Empty,
+    MaxHops(u64),
+}
+
+impl From<Option<u64>> for OptMaxHops {
+    fn from(opt: Option<u64>) -> Self {
+        match opt {
+            Some(max_hops) => OptMaxHops::MaxHops(max_hops),
+            None => OptMaxHops::Empty,
+        }
+    }
+}
+
+impl From<OptMaxHops> for Option<u64> {
+    fn from(opt: OptMaxHops) -> Self {
+        match opt {
+            OptMaxHops::MaxHops(max_hops) => Some(max_hops),
+            OptMaxHops::Empty => None,
+        }
+    }
+}
+
 /// IndexClient -> IndexServer
 #[capnp_conv(crate::index_capnp::request_routes)]
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -58,6 +83,10 @@ pub struct RequestRoutes {
     /// Useful for finding non trivial directed loops.
     #[capnp_conv(with = OptExclude)]
     pub opt_exclude: Option<Edge>,
+    /// Maximal amount of hops (edges) in every returned route.
+    /// Useful for avoiding long routes, that usually have high fees.
+    #[capnp_conv(with = OptMaxHops)]
+    pub opt_max_hops: Option<u64>,
 }
 
 #[capnp_conv(crate::index_capnp::route_capacity_rate)]
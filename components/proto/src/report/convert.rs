@@ -12,7 +12,7 @@ use crate::index_server::messages::{IndexMutation, RemoveFriendCurrency, UpdateF
 
 use crate::report::messages::{
     ChannelStatusReport, FriendLivenessReport, FriendReport, FriendStatusReport, FunderReport,
-    FunderReportMutation,
+    FunderReportMutation, McBalanceReport,
 };
 
 // Conversion to index client mutations and state
@@ -25,12 +25,25 @@ use crate::report::messages::{
 // TODO: Maybe this logic shouldn't be here? Where should we move it to?
 // TODO: Add tests (Mostly for arithmetic stuff here)
 
-/// Calculate send and receive capacities for a given `friend_report`.
+/// The amount of credits the remote friend can still send to us, given the maximum debt we allow
+/// the friend. Returns `None` on an arithmetic overflow.
+fn calc_recv_capacity(remote_max_debt: u128, balance: &McBalanceReport) -> Option<u128> {
+    Some(
+        remote_max_debt.saturating_sub_signed(
+            balance
+                .balance
+                .checked_add_unsigned(balance.remote_pending_debt)?,
+        ),
+    )
+}
+
+/// Calculate the receive capacities for a given `friend_report`.
+/// Currencies whose capacity can not be calculated (Due to an arithmetic overflow) are left out.
 fn calc_friend_capacities<B>(friend_report: &FriendReport<B>) -> HashMap<Currency, (bool, u128)>
 where
     B: Clone,
 {
-    if friend_report.status == FriendStatusReport::Disabled
+    if friend_report.status != FriendStatusReport::Enabled
         || friend_report.liveness == FriendLivenessReport::Offline
     {
         return HashMap::new();
@@ -59,9 +72,7 @@ where
     channel_consistent_report
         .currency_reports
         .iter()
-        .map(|currency_report| {
-            let balance = &currency_report.balance;
-
+        .filter_map(|currency_report| {
             let (remote_max_debt, is_open) = remote_max_debts
                 .get(&currency_report.currency)
                 .cloned()
@@ -70,19 +81,50 @@ where
             let recv_capacity = if !is_open {
                 0
             } else {
-                remote_max_debt.saturating_sub_signed(
-                    balance
-                        .balance
-                        .checked_add_unsigned(balance.remote_pending_debt)
-                        .unwrap(),
-                )
+                calc_recv_capacity(remote_max_debt, &currency_report.balance)?
             };
 
-            (currency_report.currency.clone(), (is_open, recv_capacity))
+            Some((currency_report.currency.clone(), (is_open, recv_capacity)))
         })
         .collect()
 }
 
+/// The rate configured for `currency` with a friend (Zero rate if not configured).
+fn friend_currency_rate<B>(friend_report: &FriendReport<B>, currency: &Currency) -> Rate
+where
+    B: Clone,
+{
+    friend_report
+        .currency_configs
+        .iter()
+        .find(|currency_config| &currency_config.currency == currency)
+        .map(|currency_config| currency_config.rate.clone())
+        .unwrap_or_else(Rate::new)
+}
+
+/// Calculate the maximum amount a friend can push to us through a single hop, in a given
+/// currency: The friend's current receive capacity (The maximum debt we allow the friend, minus
+/// the friend's current and frozen debt), after paying the fees of the rate we charge the friend.
+/// This is the capacity advertised to the index servers for this friend.
+///
+/// Returns `None` if the friend does not exist, the currency is not usable with this friend
+/// (For example, if the friend is offline), or the capacity can not be calculated.
+pub fn friend_max_payable<B>(
+    funder_report: &FunderReport<B>,
+    friend_public_key: &PublicKey,
+    currency: &Currency,
+) -> Option<u128>
+where
+    B: Clone,
+{
+    let friend_report = funder_report.friends.get(friend_public_key)?;
+    let (is_open, recv_capacity) = *calc_friend_capacities(friend_report).get(currency)?;
+    if !is_open {
+        return None;
+    }
+    Some(friend_currency_rate(friend_report, currency).max_payable(recv_capacity))
+}
+
 fn calc_friends_info<B>(
     funder_report: &FunderReport<B>,
 ) -> impl Iterator<Item = ((PublicKey, Currency), FriendInfo)> + '_
@@ -95,12 +137,7 @@ where
         .flat_map(|(friend_public_key, friend_report)| {
             calc_friend_capacities(friend_report).into_iter().map(
                 move |(currency, (is_open, recv_capacity))| {
-                    let rate = friend_report
-                        .currency_configs
-                        .iter()
-                        .find(|currency_config| currency_config.currency == currency)
-                        .map(|currency_config| currency_config.rate.clone())
-                        .unwrap_or_else(Rate::new);
+                    let rate = friend_currency_rate(friend_report, &currency);
 
                     let opt_friend_info = if is_open {
                         Some(FriendInfo {
@@ -231,6 +268,7 @@ mod tests {
                 remote_relays: vec![],
                 opt_last_incoming_move_token: None,
                 liveness: FriendLivenessReport::Online,
+                idle_ticks: 0,
                 channel_status: ChannelStatusReport::Consistent(ChannelConsistentReport {
                     currency_reports: vec![
                         CurrencyReport {
@@ -276,6 +314,7 @@ mod tests {
                 remote_relays: vec![],
                 opt_last_incoming_move_token: None,
                 liveness: FriendLivenessReport::Online,
+                idle_ticks: 0,
                 channel_status: ChannelStatusReport::Consistent(ChannelConsistentReport {
                     currency_reports: vec![CurrencyReport {
                         currency: currency1.clone(),
@@ -314,6 +353,101 @@ mod tests {
         assert_eq!(friend_info.rate, Rate { mul: 2, add: 2 });
     }
 
+    #[test]
+    fn test_calc_recv_capacity() {
+        let balance = McBalanceReport {
+            balance: 50,
+            local_pending_debt: 10,
+            remote_pending_debt: 30,
+        };
+        assert_eq!(calc_recv_capacity(200, &balance), Some(120));
+        assert_eq!(calc_recv_capacity(70, &balance), Some(0));
+
+        let balance = McBalanceReport {
+            balance: i128::max_value(),
+            local_pending_debt: 0,
+            remote_pending_debt: 1,
+        };
+        assert_eq!(calc_recv_capacity(200, &balance), None);
+    }
+
+    #[test]
+    fn test_friend_max_payable() {
+        let currency1 = Currency::try_from("FST1".to_owned()).unwrap();
+        let currency2 = Currency::try_from("FST2".to_owned()).unwrap();
+
+        let pk1 = PublicKey::from(&[1; PublicKey::len()]);
+        let pk2 = PublicKey::from(&[2; PublicKey::len()]);
+        let pk3 = PublicKey::from(&[3; PublicKey::len()]);
+
+        let mut friends = HashMap::new();
+        friends.insert(
+            pk2.clone(),
+            FriendReport::<u32> {
+                name: "friend_name".to_owned(),
+                currency_configs: vec![
+                    CurrencyConfigReport {
+                        currency: currency1.clone(),
+                        // 50% commission, and 30 credits fixed fee:
+                        rate: Rate {
+                            mul: 0x8000_0000,
+                            add: 30,
+                        },
+                        remote_max_debt: 200,
+                        is_open: true,
+                    },
+                    CurrencyConfigReport {
+                        currency: currency2.clone(),
+                        rate: Rate { mul: 0, add: 0 },
+                        remote_max_debt: 200,
+                        is_open: false,
+                    },
+                ],
+                remote_relays: vec![],
+                opt_last_incoming_move_token: None,
+                liveness: FriendLivenessReport::Online,
+                idle_ticks: 0,
+                channel_status: ChannelStatusReport::Consistent(ChannelConsistentReport {
+                    currency_reports: vec![
+                        CurrencyReport {
+                            currency: currency1.clone(),
+                            balance: McBalanceReport {
+                                balance: 50,
+                                local_pending_debt: 10,
+                                remote_pending_debt: 30,
+                            },
+                        },
+                        CurrencyReport {
+                            currency: currency2.clone(),
+                            balance: McBalanceReport {
+                                balance: 0,
+                                local_pending_debt: 0,
+                                remote_pending_debt: 0,
+                            },
+                        },
+                    ],
+                }),
+                status: FriendStatusReport::Enabled,
+            },
+        );
+        let funder_report = FunderReport {
+            local_public_key: pk1.clone(),
+            relays: Vec::new(),
+            friends,
+        };
+
+        // capacity = 200 - (50 + 30) = 120
+        // max_payable = (120 - 30) / 1.5 = 60
+        assert_eq!(
+            friend_max_payable(&funder_report, &pk2, &currency1),
+            Some(60)
+        );
+        // Currency is closed:
+        assert_eq!(friend_max_payable(&funder_report, &pk2, &currency2), None);
+        // Unknown friend:
+        assert_eq!(friend_max_payable(&funder_report, &pk3, &currency1), None);
+    }
+
     #[test]
     fn test_calc_index_mutations() {
         let currency1 = Currency::try_from("FST1".to_owned()).unwrap();
@@ -358,6 +492,7 @@ mod tests {
                 remote_relays: vec![],
                 opt_last_incoming_move_token: None,
                 liveness: FriendLivenessReport::Online,
+                idle_ticks: 0,
                 channel_status: ChannelStatusReport::Consistent(ChannelConsistentReport {
                     currency_reports: vec![
                         CurrencyReport {
@@ -432,6 +567,7 @@ mod tests {
                 remote_relays: vec![],
                 opt_last_incoming_move_token: None,
                 liveness: FriendLivenessReport::Online,
+                idle_ticks: 0,
                 channel_status: ChannelStatusReport::Consistent(ChannelConsistentReport {
                     currency_reports: vec![
                         CurrencyReport {
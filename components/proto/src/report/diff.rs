@@ -0,0 +1,229 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::crypto::PublicKey;
+use crate::funder::messages::Currency;
+use crate::report::messages::{ChannelStatusReport, FriendReport, FunderReport};
+
+/// A single difference between two funder reports
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FunderReportChange {
+    AddFriend {
+        friend_public_key: PublicKey,
+        name: String,
+    },
+    RemoveFriend {
+        friend_public_key: PublicKey,
+        name: String,
+    },
+    /// The balance of a currency with a friend has changed.
+    /// A currency that does not appear in a report is considered to have a zero balance.
+    BalanceChange {
+        friend_public_key: PublicKey,
+        name: String,
+        currency: Currency,
+        old_balance: i128,
+        new_balance: i128,
+    },
+}
+
+/// Balances of all currencies with a friend.
+/// An inconsistent channel has no balances.
+fn friend_balances<B>(friend_report: &FriendReport<B>) -> HashMap<Currency, i128> {
+    match &friend_report.channel_status {
+        ChannelStatusReport::Inconsistent(_) => HashMap::new(),
+        ChannelStatusReport::Consistent(channel_consistent_report) => channel_consistent_report
+            .currency_reports
+            .iter()
+            .map(|currency_report| {
+                (
+                    currency_report.currency.clone(),
+                    currency_report.balance.balance,
+                )
+            })
+            .collect(),
+    }
+}
+
+/// Calculate the added friends, the removed friends and the changed balances between an old and
+/// a new funder report.
+///
+/// Changes are sorted by friend public key, so that the result does not depend on the order of
+/// friends inside the reports.
+pub fn funder_report_diff<B>(
+    old_report: &FunderReport<B>,
+    new_report: &FunderReport<B>,
+) -> Vec<FunderReportChange>
+where
+    B: Clone,
+{
+    let mut friends_changes = BTreeMap::new();
+
+    for (friend_public_key, old_friend) in &old_report.friends {
+        if !new_report.friends.contains_key(friend_public_key) {
+            friends_changes.insert(
+                friend_public_key.clone(),
+                vec![FunderReportChange::RemoveFriend {
+                    friend_public_key: friend_public_key.clone(),
+                    name: old_friend.name.clone(),
+                }],
+            );
+        }
+    }
+
+    for (friend_public_key, new_friend) in &new_report.friends {
+        let old_friend = match old_report.friends.get(friend_public_key) {
+            Some(old_friend) => old_friend,
+            None => {
+                friends_changes.insert(
+                    friend_public_key.clone(),
+                    vec![FunderReportChange::AddFriend {
+                        friend_public_key: friend_public_key.clone(),
+                        name: new_friend.name.clone(),
+                    }],
+                );
+                continue;
+            }
+        };
+
+        let old_balances = friend_balances(old_friend);
+        let new_balances = friend_balances(new_friend);
+        let mut currencies = old_balances
+            .keys()
+            .chain(new_balances.keys())
+            .cloned()
+            .collect::<Vec<_>>();
+        currencies.sort();
+        currencies.dedup();
+
+        let balance_changes = currencies
+            .into_iter()
+            .filter_map(|currency| {
+                let old_balance = old_balances.get(&currency).cloned().unwrap_or(0);
+                let new_balance = new_balances.get(&currency).cloned().unwrap_or(0);
+                if old_balance == new_balance {
+                    return None;
+                }
+                Some(FunderReportChange::BalanceChange {
+                    friend_public_key: friend_public_key.clone(),
+                    name: new_friend.name.clone(),
+                    currency,
+                    old_balance,
+                    new_balance,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        if !balance_changes.is_empty() {
+            friends_changes.insert(friend_public_key.clone(), balance_changes);
+        }
+    }
+
+    friends_changes
+        .into_iter()
+        .flat_map(|(_, changes)| changes)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryFrom;
+
+    use crate::net::messages::NetAddress;
+    use crate::report::messages::{
+        ChannelConsistentReport, CurrencyReport, FriendLivenessReport, FriendStatusReport,
+        McBalanceReport,
+    };
+
+    fn friend_report(name: &str, balances: &[(&str, i128)]) -> FriendReport<NetAddress> {
+        let currency_reports = balances
+            .iter()
+            .map(|(currency, balance)| CurrencyReport {
+                currency: Currency::try_from((*currency).to_owned()).unwrap(),
+                balance: McBalanceReport {
+                    balance: *balance,
+                    local_pending_debt: 0,
+                    remote_pending_debt: 0,
+                },
+            })
+            .collect();
+        FriendReport {
+            name: name.to_owned(),
+            remote_relays: Vec::new(),
+            currency_configs: Vec::new(),
+            opt_last_incoming_move_token: None,
+            liveness: FriendLivenessReport::Offline,
+            idle_ticks: 0,
+            channel_status: ChannelStatusReport::Consistent(ChannelConsistentReport {
+                currency_reports,
+            }),
+            status: FriendStatusReport::Enabled,
+        }
+    }
+
+    fn funder_report(friends: Vec<(u8, FriendReport<NetAddress>)>) -> FunderReport<NetAddress> {
+        FunderReport {
+            local_public_key: PublicKey::from(&[0xaa; PublicKey::len()]),
+            relays: Vec::new(),
+            friends: friends
+                .into_iter()
+                .map(|(i, friend_report)| (PublicKey::from(&[i; PublicKey::len()]), friend_report))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_funder_report_diff() {
+        let pk = |i: u8| PublicKey::from(&[i; PublicKey::len()]);
+        let currency = |currency: &str| Currency::try_from(currency.to_owned()).unwrap();
+
+        let old_report = funder_report(vec![
+            (0, friend_report("removed", &[("FST", 5)])),
+            (1, friend_report("changed", &[("FST", 5), ("USD", 3)])),
+            (2, friend_report("unchanged", &[("FST", 7)])),
+        ]);
+        let new_report = funder_report(vec![
+            (1, friend_report("changed", &[("FST", -2), ("EUR", 4)])),
+            (2, friend_report("unchanged", &[("FST", 7)])),
+            (3, friend_report("added", &[])),
+        ]);
+
+        assert_eq!(
+            funder_report_diff(&old_report, &new_report),
+            vec![
+                FunderReportChange::RemoveFriend {
+                    friend_public_key: pk(0),
+                    name: "removed".to_owned(),
+                },
+                FunderReportChange::BalanceChange {
+                    friend_public_key: pk(1),
+                    name: "changed".to_owned(),
+                    currency: currency("EUR"),
+                    old_balance: 0,
+                    new_balance: 4,
+                },
+                FunderReportChange::BalanceChange {
+                    friend_public_key: pk(1),
+                    name: "changed".to_owned(),
+                    currency: currency("FST"),
+                    old_balance: 5,
+                    new_balance: -2,
+                },
+                FunderReportChange::BalanceChange {
+                    friend_public_key: pk(1),
+                    name: "changed".to_owned(),
+                    currency: currency("USD"),
+                    old_balance: 3,
+                    new_balance: 0,
+                },
+                FunderReportChange::AddFriend {
+                    friend_public_key: pk(3),
+                    name: "added".to_owned(),
+                },
+            ]
+        );
+
+        assert!(funder_report_diff(&new_report, &new_report).is_empty());
+    }
+}
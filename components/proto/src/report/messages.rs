@@ -14,7 +14,7 @@ use crate::crypto::{HashResult, PublicKey, RandValue, Signature, Uid};
 
 use crate::app_server::messages::{NamedRelayAddress, RelayAddress};
 use crate::funder::messages::{
-    Currency, CurrencyBalance, FriendStatus, Rate, RequestsStatus, TokenInfo,
+    Currency, CurrencyBalance, FriendStatus, InconsistencyReason, Rate, RequestsStatus, TokenInfo,
 };
 use crate::net::messages::NetAddress;
 use crate::wrapper::Wrapper;
@@ -33,6 +33,7 @@ pub struct MoveTokenHashedReport {
 pub enum FriendStatusReport {
     Enabled,
     Disabled,
+    Paused,
 }
 
 #[capnp_conv(crate::report_capnp::requests_status_report)]
@@ -123,6 +124,8 @@ pub struct ChannelInconsistentReport {
     pub local_reset_terms: Vec<CurrencyBalance>,
     #[capnp_conv(with = OptRemoteResetTerms)]
     pub opt_remote_reset_terms: Option<ResetTermsReport>,
+    /// The reason the channel became inconsistent
+    pub reason: InconsistencyReason,
 }
 
 #[capnp_conv(crate::report_capnp::channel_consistent_report)]
@@ -184,6 +187,48 @@ pub struct CurrencyConfigReport {
     pub is_open: bool,
 }
 
+/// Display metadata of a currency, configured locally by the user of the node.
+/// Purely presentational: Balances are always kept as integer credits.
+#[capnp_conv(crate::report_capnp::currency_info)]
+#[derive(Arbitrary, Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct CurrencyInfo {
+    pub currency: Currency,
+    /// Amount of digits displayed after the decimal point.
+    /// For example, 8 credits of a currency with 2 decimals are displayed as 0.08
+    pub decimals: u8,
+    /// Displayed after amounts of this currency. May be empty.
+    pub symbol: String,
+}
+
+impl CurrencyInfo {
+    /// Display an amount of credits of this currency
+    pub fn format_credits(&self, credits: u128) -> String {
+        let mut digits = credits.to_string();
+        let decimals = usize::from(self.decimals);
+        if decimals > 0 {
+            // Make sure that there is at least one digit before the decimal point:
+            if digits.len() <= decimals {
+                digits = "0".repeat(decimals + 1 - digits.len()) + &digits;
+            }
+            digits.insert(digits.len() - decimals, '.');
+        }
+        if !self.symbol.is_empty() {
+            digits.push(' ');
+            digits.push_str(&self.symbol);
+        }
+        digits
+    }
+
+    /// Display a (possibly negative) balance of this currency
+    pub fn format_balance(&self, balance: i128) -> String {
+        if balance < 0 {
+            format!("-{}", self.format_credits((balance as u128).wrapping_neg()))
+        } else {
+            self.format_credits(balance as u128)
+        }
+    }
+}
+
 #[capnp_conv(crate::report_capnp::friend_report)]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct FriendReport<B = NetAddress> {
@@ -197,6 +242,10 @@ pub struct FriendReport<B = NetAddress> {
     // TODO: The state of liveness = true with status = disabled should never happen.
     // Can we somehow express this in the type system?
     pub liveness: FriendLivenessReport, // is the friend online/offline?
+    /// Amount of ticks passed since the last move token received from the friend, at the time
+    /// the report was created. This value is not updated by report mutations.
+    /// Counting restarts from 0 when the node restarts.
+    pub idle_ticks: u64,
     pub channel_status: ChannelStatusReport,
     pub status: FriendStatusReport,
 }
@@ -231,15 +280,16 @@ impl From<PkFriendReportList> for HashMap<PublicKey, FriendReport<NetAddress>> {
 
 impl From<HashMap<PublicKey, FriendReport<NetAddress>>> for PkFriendReportList {
     fn from(hash_map: HashMap<PublicKey, FriendReport<NetAddress>>) -> Self {
-        PkFriendReportList {
-            list: hash_map
-                .into_iter()
-                .map(|(friend_public_key, friend_report)| PkFriendReport {
-                    friend_public_key,
-                    friend_report,
-                })
-                .collect(),
-        }
+        let mut list: Vec<_> = hash_map
+            .into_iter()
+            .map(|(friend_public_key, friend_report)| PkFriendReport {
+                friend_public_key,
+                friend_report,
+            })
+            .collect();
+        // Sort friends, so that equal reports are always serialized into the same bytes:
+        list.sort_by(|a, b| a.friend_public_key.cmp(&b.friend_public_key));
+        PkFriendReportList { list }
     }
 }
 
@@ -255,6 +305,30 @@ pub struct FunderReport<B = NetAddress> {
     pub friends: HashMap<PublicKey, FriendReport<B>>,
 }
 
+/// More than one friend has the requested name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AmbiguousFriendName;
+
+impl<B> FunderReport<B> {
+    /// Find a friend by its name.
+    /// Friend names are not required to be unique, so an error is returned if multiple
+    /// friends have the given name.
+    pub fn friend_by_name(
+        &self,
+        friend_name: &str,
+    ) -> Result<Option<(&PublicKey, &FriendReport<B>)>, AmbiguousFriendName> {
+        let mut matches = self
+            .friends
+            .iter()
+            .filter(|(_, friend_report)| friend_report.name == friend_name);
+        let opt_friend = matches.next();
+        if matches.next().is_some() {
+            return Err(AmbiguousFriendName);
+        }
+        Ok(opt_friend)
+    }
+}
+
 #[allow(clippy::large_enum_variant)]
 #[capnp_conv(crate::report_capnp::friend_report_mutation)]
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -328,6 +402,7 @@ impl From<&FriendStatus> for FriendStatusReport {
         match friend_status {
             FriendStatus::Enabled => FriendStatusReport::Enabled,
             FriendStatus::Disabled => FriendStatusReport::Disabled,
+            FriendStatus::Paused => FriendStatusReport::Paused,
         }
     }
 }
@@ -341,10 +416,12 @@ impl From<&RequestsStatus> for RequestsStatusReport {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum FunderReportMutateError {
-    FriendDoesNotExist,
-    FriendAlreadyExists,
+    /// A mutation referenced a friend that is not in the report
+    FriendDoesNotExist(PublicKey),
+    /// Attempt to add a friend that is already in the report
+    FriendAlreadyExists(PublicKey),
 }
 
 impl<B> MutableState for FriendReport<B>
@@ -432,6 +509,7 @@ where
                         .opt_last_incoming_move_token
                         .clone(),
                     liveness: FriendLivenessReport::Offline,
+                    idle_ticks: 0,
                     channel_status: add_friend_report.channel_status.clone(),
                     status: FriendStatusReport::from(&FriendStatus::Disabled),
                 };
@@ -440,14 +518,18 @@ where
                     .insert(add_friend_report.friend_public_key.clone(), friend_report)
                     .is_some()
                 {
-                    Err(FunderReportMutateError::FriendAlreadyExists)
+                    Err(FunderReportMutateError::FriendAlreadyExists(
+                        add_friend_report.friend_public_key.clone(),
+                    ))
                 } else {
                     Ok(())
                 }
             }
             FunderReportMutation::RemoveFriend(friend_public_key) => {
                 if self.friends.remove(&friend_public_key).is_none() {
-                    Err(FunderReportMutateError::FriendDoesNotExist)
+                    Err(FunderReportMutateError::FriendDoesNotExist(
+                        friend_public_key.clone(),
+                    ))
                 } else {
                     Ok(())
                 }
@@ -456,10 +538,9 @@ where
                 friend_public_key,
                 friend_report_mutation,
             )) => {
-                let friend = self
-                    .friends
-                    .get_mut(friend_public_key)
-                    .ok_or(FunderReportMutateError::FriendDoesNotExist)?;
+                let friend = self.friends.get_mut(friend_public_key).ok_or_else(|| {
+                    FunderReportMutateError::FriendDoesNotExist(friend_public_key.clone())
+                })?;
                 friend
                     .mutate(friend_report_mutation)
                     .map_err(|_| unreachable!())?;
@@ -468,3 +549,89 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryFrom;
+
+    fn dummy_friend_report(name: &str) -> FriendReport {
+        FriendReport {
+            name: name.to_owned(),
+            remote_relays: Vec::new(),
+            currency_configs: Vec::new(),
+            opt_last_incoming_move_token: None,
+            liveness: FriendLivenessReport::Offline,
+            idle_ticks: 0,
+            channel_status: ChannelStatusReport::Consistent(ChannelConsistentReport {
+                currency_reports: Vec::new(),
+            }),
+            status: FriendStatusReport::Enabled,
+        }
+    }
+
+    fn dummy_funder_report(names: &[&str]) -> FunderReport {
+        let friends = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                (
+                    PublicKey::from(&[i as u8; PublicKey::len()]),
+                    dummy_friend_report(name),
+                )
+            })
+            .collect();
+        FunderReport {
+            local_public_key: PublicKey::from(&[0xaa; PublicKey::len()]),
+            relays: Vec::new(),
+            friends,
+        }
+    }
+
+    #[test]
+    fn test_friend_by_name_unique() {
+        let funder_report = dummy_funder_report(&["alice", "bob"]);
+        let (friend_public_key, friend_report) =
+            funder_report.friend_by_name("bob").unwrap().unwrap();
+        assert_eq!(friend_public_key, &PublicKey::from(&[1; PublicKey::len()]));
+        assert_eq!(friend_report.name, "bob");
+    }
+
+    #[test]
+    fn test_friend_by_name_missing() {
+        let funder_report = dummy_funder_report(&["alice", "bob"]);
+        assert_eq!(funder_report.friend_by_name("carol"), Ok(None));
+    }
+
+    #[test]
+    fn test_friend_by_name_duplicate() {
+        let funder_report = dummy_funder_report(&["alice", "bob", "alice"]);
+        assert_eq!(
+            funder_report.friend_by_name("alice"),
+            Err(AmbiguousFriendName)
+        );
+        // Other names are not affected:
+        assert!(funder_report.friend_by_name("bob").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_currency_info_format() {
+        let currency_info = |decimals, symbol: &str| CurrencyInfo {
+            currency: Currency::try_from("FST".to_owned()).unwrap(),
+            decimals,
+            symbol: symbol.to_owned(),
+        };
+
+        assert_eq!(currency_info(0, "").format_credits(8), "8");
+        assert_eq!(currency_info(2, "").format_credits(8), "0.08");
+        assert_eq!(currency_info(2, "").format_credits(0), "0.00");
+        assert_eq!(currency_info(2, "$").format_credits(1234), "12.34 $");
+        assert_eq!(currency_info(2, "").format_balance(-8), "-0.08");
+        assert_eq!(currency_info(3, "").format_balance(-1234), "-1.234");
+        assert_eq!(
+            currency_info(0, "").format_balance(i128::min_value()),
+            i128::min_value().to_string()
+        );
+    }
+}
@@ -1,3 +1,5 @@
 // TODO: Possibly move convert module to another crate?
 pub mod convert;
+pub mod diff;
 pub mod messages;
+pub mod snapshot;
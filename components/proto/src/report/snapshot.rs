@@ -0,0 +1,141 @@
+use std::convert::TryFrom;
+
+use crate::proto_ser::{ProtoDeserialize, ProtoSerialize, ProtoSerializeError};
+use crate::report::messages::FunderReport;
+
+/// Version of the FunderReport snapshot format.
+/// Should be increased whenever the serialized structure of FunderReport changes.
+pub const FUNDER_REPORT_SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// The snapshot is too short to contain a version tag
+    MissingVersion,
+    /// The snapshot was created using a different version of the snapshot format
+    VersionMismatch {
+        expected: u32,
+        found: u32,
+    },
+    ProtoSerializeError(ProtoSerializeError),
+}
+
+/// Prefix capnp serialized bytes with a version tag
+fn encode_snapshot(version: u32, report_bytes: Vec<u8>) -> Vec<u8> {
+    let mut snapshot_bytes = version.to_be_bytes().to_vec();
+    snapshot_bytes.extend(report_bytes);
+    snapshot_bytes
+}
+
+/// Verify the version tag of a snapshot, and return the capnp serialized bytes
+fn decode_snapshot(expected_version: u32, snapshot_bytes: &[u8]) -> Result<&[u8], SnapshotError> {
+    if snapshot_bytes.len() < 4 {
+        return Err(SnapshotError::MissingVersion);
+    }
+    let (version_bytes, report_bytes) = snapshot_bytes.split_at(4);
+    let found = u32::from_be_bytes(<[u8; 4]>::try_from(version_bytes).unwrap());
+    if found != expected_version {
+        return Err(SnapshotError::VersionMismatch {
+            expected: expected_version,
+            found,
+        });
+    }
+    Ok(report_bytes)
+}
+
+impl FunderReport {
+    /// Serialize into a versioned binary snapshot, suitable for archiving.
+    /// Equal reports are always serialized into the same bytes.
+    pub fn to_snapshot_bytes(&self) -> Vec<u8> {
+        encode_snapshot(FUNDER_REPORT_SNAPSHOT_VERSION, self.proto_serialize())
+    }
+
+    /// Deserialize a snapshot created by `to_snapshot_bytes`.
+    /// Snapshots created using a different version of the snapshot format are rejected.
+    pub fn from_snapshot_bytes(snapshot_bytes: &[u8]) -> Result<Self, SnapshotError> {
+        let report_bytes = decode_snapshot(FUNDER_REPORT_SNAPSHOT_VERSION, snapshot_bytes)?;
+        FunderReport::proto_deserialize(report_bytes).map_err(SnapshotError::ProtoSerializeError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+    use std::convert::TryFrom;
+
+    use crate::app_server::messages::NamedRelayAddress;
+    use crate::crypto::PublicKey;
+    use crate::net::messages::NetAddress;
+    use crate::report::messages::{
+        ChannelConsistentReport, ChannelStatusReport, FriendLivenessReport, FriendReport,
+        FriendStatusReport,
+    };
+
+    fn dummy_funder_report() -> FunderReport {
+        let mut friends = HashMap::new();
+        for i in 0..4u8 {
+            friends.insert(
+                PublicKey::from(&[i; PublicKey::len()]),
+                FriendReport {
+                    name: format!("friend{}", i),
+                    remote_relays: Vec::new(),
+                    currency_configs: Vec::new(),
+                    opt_last_incoming_move_token: None,
+                    liveness: FriendLivenessReport::Offline,
+                    idle_ticks: 0,
+                    channel_status: ChannelStatusReport::Consistent(ChannelConsistentReport {
+                        currency_reports: Vec::new(),
+                    }),
+                    status: FriendStatusReport::Enabled,
+                },
+            );
+        }
+
+        FunderReport {
+            local_public_key: PublicKey::from(&[0xaa; PublicKey::len()]),
+            relays: vec![NamedRelayAddress {
+                public_key: PublicKey::from(&[0xbb; PublicKey::len()]),
+                address: NetAddress::try_from("relay0:1337".to_owned()).unwrap(),
+                name: "relay0".to_owned(),
+            }],
+            friends,
+        }
+    }
+
+    #[test]
+    fn test_funder_report_snapshot_round_trip() {
+        let funder_report = dummy_funder_report();
+        let snapshot_bytes = funder_report.to_snapshot_bytes();
+        assert_eq!(
+            FunderReport::from_snapshot_bytes(&snapshot_bytes).unwrap(),
+            funder_report
+        );
+
+        // Equal reports result in equal snapshots:
+        assert_eq!(funder_report.clone().to_snapshot_bytes(), snapshot_bytes);
+    }
+
+    #[test]
+    fn test_funder_report_snapshot_version_mismatch() {
+        let funder_report = dummy_funder_report();
+
+        // A v1 snapshot is rejected by a v2 reader:
+        let snapshot_bytes = encode_snapshot(1, funder_report.proto_serialize());
+        match decode_snapshot(2, &snapshot_bytes) {
+            Err(SnapshotError::VersionMismatch {
+                expected: 2,
+                found: 1,
+            }) => {}
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_funder_report_snapshot_missing_version() {
+        match FunderReport::from_snapshot_bytes(&[0, 0]) {
+            Err(SnapshotError::MissingVersion) => {}
+            _ => unreachable!(),
+        }
+    }
+}
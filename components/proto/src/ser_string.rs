@@ -1,4 +1,5 @@
-use derive_more::From;
+use std::fmt;
+use std::path::{Path, PathBuf};
 
 use serde::de::DeserializeOwned;
 use serde::ser::Serialize;
@@ -41,10 +42,39 @@ str_convert_funcs!(
     PublicKey::len()
 );
 
-#[derive(Debug, From)]
+#[derive(Debug)]
 pub enum StringSerdeError {
     // IoError(io::Error),
     JsonSerdeError(serde_json::Error),
+    /// Failed to deserialize the contents of the file at the given path
+    FileSerdeError(PathBuf, serde_json::Error),
+}
+
+impl From<serde_json::Error> for StringSerdeError {
+    fn from(e: serde_json::Error) -> Self {
+        StringSerdeError::JsonSerdeError(e)
+    }
+}
+
+impl StringSerdeError {
+    /// Attach the path of the file whose contents failed to deserialize
+    pub fn with_path(self, path: &Path) -> Self {
+        match self {
+            StringSerdeError::JsonSerdeError(e) | StringSerdeError::FileSerdeError(_, e) => {
+                StringSerdeError::FileSerdeError(path.to_path_buf(), e)
+            }
+        }
+    }
+}
+
+impl fmt::Display for StringSerdeError {
+    /// Describes the error, including the location and the offending key (if any)
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StringSerdeError::JsonSerdeError(e) => write!(f, "{}", e),
+            StringSerdeError::FileSerdeError(path, e) => write!(f, "{}: {}", path.display(), e),
+        }
+    }
 }
 
 pub fn deserialize_from_string<T>(input: &str) -> Result<T, StringSerdeError>
@@ -20,4 +20,4 @@ mod server;
 
 pub use self::client::client_connector::ClientConnector;
 pub use self::client::client_listener::ClientListener;
-pub use self::server::{relay_server, RelayServerError};
+pub use self::server::{relay_server, RelayCounters, RelayServerError, RelayStats};
@@ -1,9 +1,10 @@
-mod conn_limiter;
 mod conn_processor;
 // pub mod net_server;
 mod server;
 mod server_loop;
+mod stats;
 mod types;
 
 pub use server::relay_server;
 pub use server_loop::RelayServerError;
+pub use stats::{RelayCounters, RelayStats};
@@ -11,17 +11,20 @@ use timer::TimerClient;
 
 use crate::server::conn_processor::conn_processor;
 use crate::server::server_loop::{relay_server_loop, RelayServerError};
+use crate::server::stats::RelayStats;
 
 /// A relay server loop. Incoming connections should contain both (sender, receiver) and a
 /// public_key of the remote side (Should be obtained after authentication).
 ///
 /// `conn_timeout_ticks` is the amount of time we are willing to wait for a connection to identify
 /// its purpose.
+/// Counters of served and rejected connections are kept in `relay_stats`.
 pub async fn relay_server<IC, S>(
     incoming_conns: IC,
     timer_client: TimerClient,
     conn_timeout_ticks: usize,
     half_tunnel_ticks: usize,
+    relay_stats: RelayStats,
     spawner: S,
 ) -> Result<(), RelayServerError>
 where
@@ -35,5 +38,12 @@ where
         conn_timeout_ticks,
     ));
 
-    relay_server_loop(timer_client, processed_conns, half_tunnel_ticks, spawner).await
+    relay_server_loop(
+        timer_client,
+        processed_conns,
+        half_tunnel_ticks,
+        relay_stats,
+        spawner,
+    )
+    .await
 }
@@ -15,8 +15,12 @@ use timer::TimerClient;
 use proto::crypto::PublicKey;
 use proto::relay::messages::{IncomingConnection, RejectConnection};
 
+use super::stats::{RelayConnKind, RelayStats};
 use super::types::{IncomingAccept, IncomingConn, IncomingConnInner};
 
+/// Amount of timer ticks between two log lines of the relay's connection counters.
+const STATS_LOG_TICKS: usize = 0x400;
+
 struct HalfTunnel {
     conn_pair: ConnPairVec,
     ticks_to_close: usize,
@@ -140,6 +144,7 @@ pub async fn relay_server_loop<S>(
     mut timer_client: TimerClient,
     incoming_conns: S,
     half_tunnel_ticks: usize,
+    relay_stats: RelayStats,
     spawner: impl Spawn + Clone,
 ) -> Result<(), RelayServerError>
 where
@@ -165,6 +170,7 @@ where
 
     let mut incoming_conns_closed = false;
     let mut listeners: HashMap<PublicKey, Listener> = HashMap::new();
+    let mut stats_log_ticks: usize = 0;
 
     while let Some(relay_server_event) = relay_server_events.next().await {
         let c_event_sender = event_sender.clone().sink_map_err(|_| ());
@@ -174,8 +180,10 @@ where
                 match inner {
                     IncomingConnInner::Listen(incoming_listen) => {
                         if listeners.contains_key(&public_key) {
+                            relay_stats.rejected(RelayConnKind::Listen);
                             continue; // Discard Listen connection
                         }
+                        relay_stats.served(RelayConnKind::Listen);
 
                         let (sender, receiver) = incoming_listen.conn_pair.split();
 
@@ -218,24 +226,33 @@ where
                         let tunnel_closed_sender = c_event_sender.with(|tunnel_closed| {
                             future::ready(Ok(RelayServerEvent::TunnelClosed(tunnel_closed)))
                         });
-                        let _ = handle_accept(
+                        match handle_accept(
                             &mut listeners,
                             public_key.clone(),
                             incoming_accept,
                             tunnel_closed_sender,
                             spawner.clone(),
-                        )
-                        .map_err(|e| warn!("handle_accept() error: {:?}", e));
+                        ) {
+                            Ok(()) => relay_stats.served(RelayConnKind::Accept),
+                            Err(e) => {
+                                warn!("handle_accept() error: {:?}", e);
+                                relay_stats.rejected(RelayConnKind::Accept);
+                            }
+                        }
                     }
                     IncomingConnInner::Connect(incoming_connect) => {
                         let listener = match listeners.get_mut(&incoming_connect.connect_public_key)
                         {
                             Some(listener) => listener,
-                            None => continue, // Discard Connect connection
+                            None => {
+                                relay_stats.rejected(RelayConnKind::Connect);
+                                continue; // Discard Connect connection
+                            }
                         };
                         if listener.half_tunnels.contains_key(&public_key)
                             || listener.tunnels.contains(&public_key)
                         {
+                            relay_stats.rejected(RelayConnKind::Connect);
                             continue;
                         }
 
@@ -243,6 +260,7 @@ where
                             conn_pair: incoming_connect.conn_pair,
                             ticks_to_close: half_tunnel_ticks,
                         };
+                        let mut is_served = false;
                         if let Some(sender) = &mut listener.opt_sender {
                             // Try to send a message to listener about new pending connection:
                             if let Ok(()) = sender.try_send(IncomingConnection {
@@ -251,8 +269,14 @@ where
                                 listener
                                     .half_tunnels
                                     .insert(public_key.clone(), half_tunnel);
+                                is_served = true;
                             }
                         }
+                        if is_served {
+                            relay_stats.served(RelayConnKind::Connect);
+                        } else {
+                            relay_stats.rejected(RelayConnKind::Connect);
+                        }
                     }
                 }
             }
@@ -291,6 +315,12 @@ where
                 }
             }
             RelayServerEvent::TimerTick => {
+                stats_log_ticks += 1;
+                if stats_log_ticks >= STATS_LOG_TICKS {
+                    stats_log_ticks = 0;
+                    info!("Relay connection counters: {:?}", relay_stats.snapshot());
+                }
+
                 // Remove old half tunnels:
                 for listener in listeners.values_mut() {
                     listener
@@ -319,6 +349,7 @@ mod tests {
     use futures::executor::{LocalPool, ThreadPool};
     use futures::task::{Spawn, SpawnExt};
 
+    use crate::server::stats::RelayCounters;
     use crate::server::types::{IncomingAccept, IncomingConnect, IncomingListen};
 
     use common::conn::ConnPair;
@@ -341,6 +372,7 @@ mod tests {
             timer_client,
             incoming_conns,
             half_tunnel_ticks,
+            RelayStats::new(),
             spawner.clone(),
         );
 
@@ -455,6 +487,7 @@ mod tests {
             timer_client,
             incoming_conns,
             half_tunnel_ticks,
+            RelayStats::new(),
             spawner.clone(),
         );
 
@@ -556,6 +589,150 @@ mod tests {
             .unwrap();
     }
 
+    async fn task_relay_server_counters(
+        spawner: impl Spawn + Clone + Send + 'static,
+    ) -> Result<(), ()> {
+        // Create a mock time service:
+        let (_tick_sender, tick_receiver) = mpsc::channel::<()>(0);
+        let timer_client = create_timer_incoming(tick_receiver, spawner.clone()).unwrap();
+
+        let (mut outgoing_conns, incoming_conns) = mpsc::channel::<_>(0);
+
+        let half_tunnel_ticks: usize = 16;
+        let relay_stats = RelayStats::new();
+
+        let fut_relay_server = relay_server_loop(
+            timer_client,
+            incoming_conns,
+            half_tunnel_ticks,
+            relay_stats.clone(),
+            spawner.clone(),
+        );
+
+        spawner
+            .spawn(fut_relay_server.map_err(|_e| ()).map(|_| ()))
+            .unwrap();
+
+        let a_public_key = PublicKey::from(&[0xaa; PublicKey::len()]);
+        let b_public_key = PublicKey::from(&[0xbb; PublicKey::len()]);
+        let c_public_key = PublicKey::from(&[0xcc; PublicKey::len()]);
+        let d_public_key = PublicKey::from(&[0xdd; PublicKey::len()]);
+        let e_public_key = PublicKey::from(&[0xee; PublicKey::len()]);
+
+        // Keep the remote sides of all connections alive until the end of the test:
+        let mut keep_alive = Vec::new();
+
+        // A Listen connection from `public_key`:
+        let mut listen_conn = |public_key: &PublicKey| {
+            let (remote_sender, local_receiver) = mpsc::channel::<RejectConnection>(0);
+            let (local_sender, remote_receiver) = mpsc::channel::<IncomingConnection>(0);
+            keep_alive.push(remote_sender);
+            let incoming_conn = IncomingConn {
+                public_key: public_key.clone(),
+                inner: IncomingConnInner::Listen(IncomingListen {
+                    conn_pair: ConnPair::from_raw(local_sender, local_receiver),
+                }),
+            };
+            (incoming_conn, remote_receiver)
+        };
+
+        let (incoming_conn, mut a_ca) = listen_conn(&a_public_key);
+        outgoing_conns.send(incoming_conn).await.unwrap();
+
+        // A is already listening:
+        let (incoming_conn, _a_ca_dup) = listen_conn(&a_public_key);
+        outgoing_conns.send(incoming_conn).await.unwrap();
+
+        // Connect and Accept connections from `public_key`.
+        // The returned channels should be kept alive until the end of the test:
+        let vec_conn = || {
+            let (remote_sender, local_receiver) = mpsc::channel::<Vec<u8>>(0);
+            let (local_sender, remote_receiver) = mpsc::channel::<Vec<u8>>(0);
+            (
+                ConnPairVec::from_raw(local_sender, local_receiver),
+                (remote_sender, remote_receiver),
+            )
+        };
+        let connect_conn = |public_key: &PublicKey, connect_public_key: &PublicKey| {
+            let (conn_pair, remote) = vec_conn();
+            let incoming_conn = IncomingConn {
+                public_key: public_key.clone(),
+                inner: IncomingConnInner::Connect(IncomingConnect {
+                    connect_public_key: connect_public_key.clone(),
+                    conn_pair,
+                }),
+            };
+            (incoming_conn, remote)
+        };
+        let accept_conn = |public_key: &PublicKey, accept_public_key: &PublicKey| {
+            let (conn_pair, remote) = vec_conn();
+            let incoming_conn = IncomingConn {
+                public_key: public_key.clone(),
+                inner: IncomingConnInner::Accept(IncomingAccept {
+                    accept_public_key: accept_public_key.clone(),
+                    conn_pair,
+                }),
+            };
+            (incoming_conn, remote)
+        };
+
+        // B connects to A:
+        let (incoming_conn, _b_remote) = connect_conn(&b_public_key, &a_public_key);
+        outgoing_conns.send(incoming_conn).await.unwrap();
+        assert_eq!(
+            a_ca.next().await.unwrap(),
+            IncomingConnection {
+                public_key: b_public_key.clone()
+            }
+        );
+
+        // C connects to D, which is not listening:
+        let (incoming_conn, _c_remote) = connect_conn(&c_public_key, &d_public_key);
+        outgoing_conns.send(incoming_conn).await.unwrap();
+
+        // A accepts C, which never connected:
+        let (incoming_conn, _a_remote_c) = accept_conn(&a_public_key, &c_public_key);
+        outgoing_conns.send(incoming_conn).await.unwrap();
+
+        // A accepts B:
+        let (incoming_conn, _a_remote_b) = accept_conn(&a_public_key, &b_public_key);
+        outgoing_conns.send(incoming_conn).await.unwrap();
+
+        // E connects to A. When A is notified, we know that all the previous connections
+        // were processed:
+        let (incoming_conn, _e_remote) = connect_conn(&e_public_key, &a_public_key);
+        outgoing_conns.send(incoming_conn).await.unwrap();
+        assert_eq!(
+            a_ca.next().await.unwrap(),
+            IncomingConnection {
+                public_key: e_public_key.clone()
+            }
+        );
+
+        assert_eq!(
+            relay_stats.snapshot(),
+            RelayCounters {
+                listen_served: 1,
+                listen_rejected: 1,
+                accept_served: 1,
+                accept_rejected: 1,
+                connect_served: 2,
+                connect_rejected: 1,
+            }
+        );
+
+        drop(keep_alive);
+        Ok(())
+    }
+
+    #[test]
+    fn test_relay_server_counters() {
+        let thread_pool = ThreadPool::new().unwrap();
+        LocalPool::new()
+            .run_until(task_relay_server_counters(thread_pool.clone()))
+            .unwrap();
+    }
+
     // TODO: Add tests:
     // - Timeout of half tunnels
     //      (Do some action first, to make sure timer_stream was already obtained).
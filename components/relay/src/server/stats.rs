@@ -0,0 +1,62 @@
+use std::sync::{Arc, Mutex};
+
+/// The kind of an incoming relay connection, as declared by its first message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelayConnKind {
+    Listen,
+    Accept,
+    Connect,
+}
+
+/// Counters of served and rejected connections, for every kind of connection.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RelayCounters {
+    pub listen_served: u64,
+    pub listen_rejected: u64,
+    pub accept_served: u64,
+    pub accept_rejected: u64,
+    pub connect_served: u64,
+    pub connect_rejected: u64,
+}
+
+impl RelayCounters {
+    fn record(&mut self, kind: RelayConnKind, is_served: bool) {
+        let counter = match (kind, is_served) {
+            (RelayConnKind::Listen, true) => &mut self.listen_served,
+            (RelayConnKind::Listen, false) => &mut self.listen_rejected,
+            (RelayConnKind::Accept, true) => &mut self.accept_served,
+            (RelayConnKind::Accept, false) => &mut self.accept_rejected,
+            (RelayConnKind::Connect, true) => &mut self.connect_served,
+            (RelayConnKind::Connect, false) => &mut self.connect_rejected,
+        };
+        *counter = counter.saturating_add(1);
+    }
+}
+
+/// Shared connection counters of a relay server.
+/// Cloning a `RelayStats` gives another handle to the same counters.
+#[derive(Debug, Clone, Default)]
+pub struct RelayStats {
+    counters: Arc<Mutex<RelayCounters>>,
+}
+
+impl RelayStats {
+    pub fn new() -> Self {
+        RelayStats {
+            counters: Arc::new(Mutex::new(RelayCounters::default())),
+        }
+    }
+
+    /// Get a copy of the current counters
+    pub fn snapshot(&self) -> RelayCounters {
+        self.counters.lock().unwrap().clone()
+    }
+
+    pub(crate) fn served(&self, kind: RelayConnKind) {
+        self.counters.lock().unwrap().record(kind, true);
+    }
+
+    pub(crate) fn rejected(&self, kind: RelayConnKind) {
+        self.counters.lock().unwrap().record(kind, false);
+    }
+}
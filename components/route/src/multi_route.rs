@@ -2,7 +2,7 @@ use num_bigint::BigUint;
 use num_traits::cast::ToPrimitive;
 use num_traits::ops::checked::CheckedSub;
 
-use proto::index_server::messages::MultiRoute;
+use proto::index_server::messages::{MultiRoute, RouteCapacityRate};
 
 /// For every route in a multi route: How many credits to push through.
 pub type MultiRouteChoice = Vec<(usize, u128)>; // (route_index, credits_to_push)
@@ -36,10 +36,23 @@ fn fill_amount(amount: u128, sorted_routes: &[(Option<usize>, u128)]) -> Option<
     None
 }
 
+/// Check that pushing `credits` through a route, together with the fees paid along the route,
+/// does not exceed the capacity of the route.
+fn is_route_amount_feasible(route: &RouteCapacityRate, credits: u128) -> bool {
+    route
+        .rate
+        .calc_fee(credits)
+        .and_then(|fee| credits.checked_add(fee))
+        .map_or(false, |total| total <= route.capacity)
+}
+
 /// Find a safe choice for how much credits to push through each route in a MultiRoute.
 /// Returns a vector representing how many credits to push through every chosen route (if successful).
 /// For example: (5usize, 100u128) means: push 100 credits through the route that was given in
 /// index 5.
+///
+/// Routes are filled according to the amount they can carry after paying their fees, so that
+/// for every chosen route the credits pushed together with the route's fee fit in its capacity.
 fn safe_multi_route_amounts(multi_route: &MultiRoute, amount: u128) -> Option<MultiRouteChoice> {
     let routes = &multi_route.routes;
     let sorted_routes = {
@@ -50,7 +63,15 @@ fn safe_multi_route_amounts(multi_route: &MultiRoute, amount: u128) -> Option<Mu
             .collect();
 
         // Reverse sort: (Largest is first)
-        sorted_routes.sort_by(|(_, a), (_, b)| b.cmp(a));
+        // Routes with equal amounts are ordered by their public keys, so that the same routes
+        // always result in the same choice, regardless of their order.
+        sorted_routes.sort_by(|(opt_ja, a), (opt_jb, b)| {
+            b.cmp(a).then_with(|| {
+                let route_a = &routes[opt_ja.unwrap()].route;
+                let route_b = &routes[opt_jb.unwrap()].route;
+                route_a.public_keys.cmp(&route_b.public_keys)
+            })
+        });
         // Add a zero entry in the end:
         sorted_routes.push((None, 0u128));
         sorted_routes
@@ -83,6 +104,14 @@ fn safe_multi_route_amounts(multi_route: &MultiRoute, amount: u128) -> Option<Mu
             accum_credits = accum_credits.checked_add(diff).unwrap();
         }
     }
+
+    // Make sure that the fees of every chosen route fit in its capacity:
+    if !chosen_routes
+        .iter()
+        .all(|&(j, credits)| is_route_amount_feasible(&routes[j], credits))
+    {
+        return None;
+    }
     Some(chosen_routes)
 }
 
@@ -107,7 +136,6 @@ mod tests {
 
     use proto::crypto::PublicKey;
     use proto::funder::messages::{FriendsRoute, Rate};
-    use proto::index_server::messages::RouteCapacityRate;
 
     /// A helper function to create a test public key
     fn pk(i: u8) -> PublicKey {
@@ -175,4 +203,94 @@ mod tests {
         });
         assert!(safe_multi_route_amounts(&multi_route, 10u128).is_some());
     }
+
+    #[test]
+    fn test_safe_multi_route_amounts_fees() {
+        // Both routes have the same capacity, but the second route charges a fee:
+        let multi_route = MultiRoute {
+            routes: vec![
+                RouteCapacityRate {
+                    route: FriendsRoute {
+                        public_keys: vec![pk(0), pk(1), pk(4)],
+                    },
+                    capacity: 100u128,
+                    rate: Rate { add: 0, mul: 0 },
+                },
+                RouteCapacityRate {
+                    route: FriendsRoute {
+                        public_keys: vec![pk(0), pk(2), pk(4)],
+                    },
+                    capacity: 100u128,
+                    rate: Rate { add: 20, mul: 0 },
+                },
+            ],
+        };
+
+        // Splitting by capacity alone would push 85 credits through the second route,
+        // which can not carry the additional fee of 20 credits:
+        let multi_route_choice = safe_multi_route_amounts(&multi_route, 170).unwrap();
+        assert_eq!(multi_route_choice, vec![(1, 75), (0, 95)]);
+        for &(j, credits) in &multi_route_choice {
+            let route = &multi_route.routes[j];
+            let fee = route.rate.calc_fee(credits).unwrap();
+            assert!(credits + fee <= route.capacity);
+        }
+
+        // Both routes are saturated, including the fee:
+        assert_eq!(
+            safe_multi_route_amounts(&multi_route, 180).unwrap(),
+            vec![(1, 80), (0, 100)]
+        );
+        assert!(safe_multi_route_amounts(&multi_route, 181).is_none());
+    }
+
+    #[test]
+    fn test_is_route_amount_feasible() {
+        let route = RouteCapacityRate {
+            route: FriendsRoute {
+                public_keys: vec![pk(0), pk(1), pk(4)],
+            },
+            capacity: 100u128,
+            rate: Rate { add: 20, mul: 0 },
+        };
+        assert!(is_route_amount_feasible(&route, 80));
+        assert!(!is_route_amount_feasible(&route, 81));
+        assert!(!is_route_amount_feasible(&route, u128::max_value()));
+    }
+
+    #[test]
+    fn test_safe_multi_route_amounts_tie_break() {
+        let route_a = RouteCapacityRate {
+            route: FriendsRoute {
+                public_keys: vec![pk(0), pk(1), pk(4)],
+            },
+            capacity: 10u128,
+            rate: Rate { add: 0, mul: 0 },
+        };
+        let route_b = RouteCapacityRate {
+            route: FriendsRoute {
+                public_keys: vec![pk(0), pk(2), pk(4)],
+            },
+            capacity: 10u128,
+            rate: Rate { add: 0, mul: 0 },
+        };
+
+        // Both routes are equally good. The extra credit always goes to route_a,
+        // regardless of the order of the routes:
+        let multi_route = MultiRoute {
+            routes: vec![route_a.clone(), route_b.clone()],
+        };
+        assert_eq!(
+            safe_multi_route_amounts(&multi_route, 11).unwrap(),
+            vec![(1, 5), (0, 6)]
+        );
+
+        let multi_route = MultiRoute {
+            routes: vec![route_b, route_a],
+        };
+        assert_eq!(
+            safe_multi_route_amounts(&multi_route, 11).unwrap(),
+            vec![(0, 5), (1, 6)]
+        );
+    }
 }
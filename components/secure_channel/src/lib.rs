@@ -19,4 +19,4 @@ mod secure_channel;
 mod state;
 mod types;
 
-pub use self::secure_channel::SecureChannel;
+pub use self::secure_channel::{SecureChannel, SecureChannelError};
@@ -12,8 +12,10 @@ use common::select_streams::select_streams;
 
 use crypto::rand::CryptoRandom;
 use identity::IdentityClient;
+use timer::utils::future_timeout;
 use timer::TimerClient;
 
+use proto::consts::SC_HANDSHAKE_TIMEOUT_TICKS;
 use proto::crypto::PublicKey;
 use proto::proto_ser::{ProtoDeserialize, ProtoSerialize, ProtoSerializeError};
 use proto::secure_channel::messages::{ExchangeDh, ExchangeRandNonce};
@@ -22,15 +24,20 @@ use crate::state::{ScState, ScStateError, ScStateInitial};
 use crate::types::{EncryptedData, PlainData};
 
 #[derive(Debug, From)]
-enum SecureChannelError {
+pub enum SecureChannelError {
     IdentityFailure,
     WriterError,
     ReaderClosed,
     ProtoSerializeError(ProtoSerializeError),
     HandleExchangeRandNonceError(ScStateError),
     HandleExchangeScStateError(ScStateError),
-    UnexpectedRemotePublicKey,
+    /// The remote side presented an identity other than the expected one
+    PeerKeyMismatch {
+        expected: PublicKey,
+        got: PublicKey,
+    },
     RequestTimerStreamError,
+    HandshakeTimeout,
     HandleIncomingError,
     SpawnError,
 }
@@ -66,17 +73,20 @@ where
         .ok_or(SecureChannelError::ReaderClosed)?;
 
     let exchange_rand_nonce = ExchangeRandNonce::proto_deserialize(&reader_message)?;
+    if let Some(expected_remote) = opt_expected_remote {
+        if expected_remote != exchange_rand_nonce.src_public_key {
+            return Err(SecureChannelError::PeerKeyMismatch {
+                expected: expected_remote,
+                got: exchange_rand_nonce.src_public_key,
+            });
+        }
+    }
+
     let (dh_state_half, exchange_dh) = dh_state_initial
         .handle_exchange_rand_nonce(exchange_rand_nonce, identity_client.clone(), rng.clone())
         .await
         .map_err(SecureChannelError::HandleExchangeRandNonceError)?;
 
-    if let Some(expected_remote) = opt_expected_remote {
-        if expected_remote != dh_state_half.remote_public_key {
-            return Err(SecureChannelError::UnexpectedRemotePublicKey);
-        }
-    }
-
     let ser_exchange_dh = exchange_dh.proto_serialize();
     writer
         .send(ser_exchange_dh)
@@ -208,14 +218,18 @@ where
 ///
 /// `ticks_to_rekey` is the amount of time ticks it takes to issue a rekey, changing the symmetric
 /// key used for the encryption.
+///
+/// `handshake_timeout_ticks` is the amount of time ticks we are willing to wait for the initial
+/// exchange to complete. A remote side that stalls during the handshake is disconnected.
 async fn create_secure_channel<EK, M, K, R, S>(
     writer: K,
     reader: M,
     identity_client: IdentityClient,
     opt_expected_remote: Option<PublicKey>,
     rng: R,
-    timer_client: TimerClient,
+    mut timer_client: TimerClient,
     ticks_to_rekey: usize,
+    handshake_timeout_ticks: usize,
     spawner: S,
 ) -> Result<(PublicKey, ConnPairVec), SecureChannelError>
 where
@@ -225,14 +239,22 @@ where
     R: CryptoRandom + Clone + 'static,
     S: Spawn,
 {
-    let (dh_state, writer, reader) = initial_exchange(
+    let timer_stream = timer_client
+        .request_timer_stream("secure_channel_handshake".to_owned())
+        .await
+        .map_err(|_| SecureChannelError::RequestTimerStreamError)?;
+
+    let fut_exchange = Box::pin(initial_exchange(
         writer,
         reader,
         identity_client,
         opt_expected_remote,
         rng.clone(),
-    )
-    .await?;
+    ));
+    let (dh_state, writer, reader) =
+        future_timeout(fut_exchange, timer_stream, handshake_timeout_ticks)
+            .await
+            .ok_or(SecureChannelError::HandshakeTimeout)??;
 
     let remote_public_key = dh_state.get_remote_public_key().clone();
 
@@ -271,6 +293,7 @@ pub struct SecureChannel<R, S> {
     rng: R,
     timer_client: TimerClient,
     ticks_to_rekey: usize,
+    handshake_timeout_ticks: usize,
     spawner: S,
 }
 
@@ -287,11 +310,40 @@ impl<R, S> SecureChannel<R, S> {
             rng,
             timer_client,
             ticks_to_rekey,
+            handshake_timeout_ticks: SC_HANDSHAKE_TIMEOUT_TICKS,
             spawner,
         }
     }
 }
 
+impl<R, S> SecureChannel<R, S>
+where
+    R: CryptoRandom + Clone + 'static,
+    S: Spawn + Clone + Send,
+{
+    /// Like `FutTransform::transform()`, but reports the reason of failure.
+    pub async fn try_transform(
+        &mut self,
+        input: (Option<PublicKey>, ConnPairVec),
+    ) -> Result<(PublicKey, ConnPairVec), SecureChannelError> {
+        let (opt_expected_remote, conn_pair) = input;
+        let (sender, receiver) = conn_pair.split();
+
+        create_secure_channel(
+            sender,
+            receiver,
+            self.identity_client.clone(),
+            opt_expected_remote,
+            self.rng.clone(),
+            self.timer_client.clone(),
+            self.ticks_to_rekey,
+            self.handshake_timeout_ticks,
+            self.spawner.clone(),
+        )
+        .await
+    }
+}
+
 impl<R, S> FutTransform for SecureChannel<R, S>
 where
     R: CryptoRandom + Clone + 'static,
@@ -311,24 +363,7 @@ where
         &mut self,
         input: (Option<PublicKey>, ConnPairVec),
     ) -> BoxFuture<'_, Option<(PublicKey, ConnPairVec)>> {
-        let (opt_expected_remote, conn_pair) = input;
-        let (sender, receiver) = conn_pair.split();
-
-        let c_spawner = self.spawner.clone();
-        Box::pin(async move {
-            create_secure_channel(
-                sender,
-                receiver,
-                self.identity_client.clone(),
-                opt_expected_remote.clone(),
-                self.rng.clone(),
-                self.timer_client.clone(),
-                self.ticks_to_rekey,
-                c_spawner,
-            )
-            .await
-            .ok()
-        })
+        Box::pin(async move { self.try_transform(input).await.ok() })
     }
 }
 
@@ -434,6 +469,7 @@ mod tests {
             rng1.clone(),
             timer_client.clone(),
             ticks_to_rekey,
+            SC_HANDSHAKE_TIMEOUT_TICKS,
             test_executor.clone(),
         );
 
@@ -445,6 +481,7 @@ mod tests {
             rng2.clone(),
             timer_client.clone(),
             ticks_to_rekey,
+            SC_HANDSHAKE_TIMEOUT_TICKS,
             test_executor.clone(),
         );
 
@@ -474,4 +511,140 @@ mod tests {
         // assert_eq!(true, LocalPool::new().run_until(output_receiver1).unwrap());
         // assert_eq!(true, LocalPool::new().run_until(output_receiver2).unwrap());
     }
+
+    async fn task_secure_channel_handshake_timeout(
+        mut secure_channel: SecureChannel<DummyRandom, TestExecutor>,
+        mut tick_sender: mpsc::Sender<()>,
+        test_executor: TestExecutor,
+    ) {
+        // The remote side never sends anything, but keeps the connection open:
+        let (local_sender, _remote_receiver) = mpsc::channel::<Vec<u8>>(8);
+        let (_remote_sender, local_receiver) = mpsc::channel::<Vec<u8>>(8);
+        let conn_pair = ConnPairVec::from_raw(local_sender, local_receiver);
+
+        let (output_sender, mut output_receiver) = oneshot::channel();
+        test_executor
+            .spawn(async move {
+                let opt_output = secure_channel.transform((None, conn_pair)).await;
+                output_sender.send(opt_output.is_none()).unwrap();
+            })
+            .unwrap();
+        test_executor.wait().await;
+
+        // Let the handshake stall for a while, right before the timeout:
+        for _ in 0..SC_HANDSHAKE_TIMEOUT_TICKS - 1 {
+            tick_sender.send(()).await.unwrap();
+            test_executor.wait().await;
+        }
+        assert_eq!(output_receiver.try_recv().unwrap(), None);
+
+        // The last tick should cause the transform to give up:
+        tick_sender.send(()).await.unwrap();
+        test_executor.wait().await;
+        assert_eq!(output_receiver.await.unwrap(), true);
+    }
+
+    #[test]
+    fn test_secure_channel_handshake_timeout() {
+        let test_executor = TestExecutor::new();
+
+        // Create a mock time service:
+        let (tick_sender, tick_receiver) = mpsc::channel::<()>(0);
+        let timer_client = create_timer_incoming(tick_receiver, test_executor.clone()).unwrap();
+
+        let rng = DummyRandom::new(&[1u8]);
+        let pkcs8 = PrivateKey::rand_gen(&rng);
+        let identity = SoftwareEd25519Identity::from_private_key(&pkcs8).unwrap();
+        let (requests_sender, identity_server) = create_identity(identity);
+        let identity_client = IdentityClient::new(requests_sender);
+        test_executor
+            .spawn(identity_server.then(|_| future::ready(())))
+            .unwrap();
+
+        let secure_channel = SecureChannel::new(
+            identity_client,
+            rng,
+            timer_client,
+            16,
+            test_executor.clone(),
+        );
+
+        let res = test_executor.run(task_secure_channel_handshake_timeout(
+            secure_channel,
+            tick_sender,
+            test_executor.clone(),
+        ));
+        assert!(res.is_output());
+    }
+
+    #[test]
+    fn test_secure_channel_peer_key_mismatch() {
+        let test_executor = TestExecutor::new();
+
+        // Create a mock time service:
+        let (_tick_sender, tick_receiver) = mpsc::channel::<()>(0);
+        let timer_client = create_timer_incoming(tick_receiver, test_executor.clone()).unwrap();
+
+        let rng1 = DummyRandom::new(&[1u8]);
+        let pkcs8 = PrivateKey::rand_gen(&rng1);
+        let identity1 = SoftwareEd25519Identity::from_private_key(&pkcs8).unwrap();
+        let (requests_sender1, identity_server1) = create_identity(identity1);
+        let identity_client1 = IdentityClient::new(requests_sender1);
+
+        let rng2 = DummyRandom::new(&[2u8]);
+        let pkcs8 = PrivateKey::rand_gen(&rng2);
+        let identity2 = SoftwareEd25519Identity::from_private_key(&pkcs8).unwrap();
+        let public_key2 = identity2.get_public_key();
+        let (requests_sender2, identity_server2) = create_identity(identity2);
+        let identity_client2 = IdentityClient::new(requests_sender2);
+
+        test_executor
+            .spawn(identity_server1.then(|_| future::ready(())))
+            .unwrap();
+        test_executor
+            .spawn(identity_server2.then(|_| future::ready(())))
+            .unwrap();
+
+        let (sender1, receiver2) = mpsc::channel::<Vec<u8>>(1);
+        let (sender2, receiver1) = mpsc::channel::<Vec<u8>>(1);
+
+        // The remote side accepts any identity:
+        let fut_sc2 = create_secure_channel(
+            sender2.sink_map_err(|_| ()),
+            receiver2,
+            identity_client2,
+            None,
+            rng2,
+            timer_client.clone(),
+            16,
+            SC_HANDSHAKE_TIMEOUT_TICKS,
+            test_executor.clone(),
+        );
+        test_executor.spawn(fut_sc2.map(|_| ())).unwrap();
+
+        // We expect to connect to someone else:
+        let expected_public_key = PublicKey::from(&[0xaa; PublicKey::len()]);
+        let mut secure_channel = SecureChannel::new(
+            identity_client1,
+            rng1,
+            timer_client,
+            16,
+            test_executor.clone(),
+        );
+        let conn_pair = ConnPairVec::from_raw(sender1, receiver1);
+        let c_expected_public_key = expected_public_key.clone();
+        let res = test_executor.run(async move {
+            secure_channel
+                .try_transform((Some(c_expected_public_key), conn_pair))
+                .await
+        });
+
+        match res.output().unwrap() {
+            Err(SecureChannelError::PeerKeyMismatch { expected, got }) => {
+                assert_eq!(expected, expected_public_key);
+                assert_eq!(got, public_key2);
+            }
+            _ => unreachable!(),
+        }
+    }
 }
@@ -187,6 +187,8 @@ impl CanonicalSerialize for CancelSendFundsOp {
     fn canonical_serialize(&self) -> Vec<u8> {
         let mut res_bytes = Vec::new();
         res_bytes.extend_from_slice(&self.request_id);
+        // `opt_reason` is informational only, and is deliberately left out of the signed
+        // serialization, so that signatures stay compatible with the current protocol version.
         res_bytes
     }
 }
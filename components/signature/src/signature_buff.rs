@@ -1,6 +1,6 @@
 use byteorder::{BigEndian, WriteBytesExt};
 
-use crypto::hash::{self, sha_512_256};
+use crypto::hash::{self, sha_512_256, HashContext};
 
 use proto::crypto::HashResult;
 
@@ -8,7 +8,8 @@ use common::int_convert::usize_to_u64;
 
 use crate::canonical::CanonicalSerialize;
 use proto::funder::messages::{
-    Currency, PendingTransaction, TokenInfo, UnsignedMoveToken, UnsignedResponseSendFundsOp,
+    Currency, CurrencyOperations, PendingTransaction, TokenInfo, UnsignedMoveToken,
+    UnsignedResponseSendFundsOp,
 };
 use proto::index_server::messages::MutationsUpdate;
 use proto::report::messages::MoveTokenHashedReport;
@@ -56,14 +57,46 @@ where
 // NEXT is used for hashing for the next move token funds.
 pub const TOKEN_NEXT: &[u8] = b"NEXT";
 
+/// Hash the canonical serialization of a list, one item at a time.
+/// Gives the same result as hashing `list.canonical_serialize()`, without keeping the
+/// serialization of the whole list in memory.
+fn hash_update_list<T>(hash_context: &mut HashContext, list: &[T])
+where
+    T: CanonicalSerialize,
+{
+    hash_context.update(&usize_to_u64(list.len()).unwrap().to_be_bytes());
+    for item in list {
+        hash_context.update(&item.canonical_serialize());
+    }
+}
+
+/// Hash the canonical serialization of the operations of all currencies, one operation at a
+/// time.
+/// Gives the same result as hashing `currencies_operations.canonical_serialize()`.
+fn hash_update_currencies_operations(
+    hash_context: &mut HashContext,
+    currencies_operations: &[CurrencyOperations],
+) {
+    hash_context.update(
+        &usize_to_u64(currencies_operations.len())
+            .unwrap()
+            .to_be_bytes(),
+    );
+    for currency_operations in currencies_operations {
+        hash_context.update(&currency_operations.currency.canonical_serialize());
+        hash_update_list(hash_context, &currency_operations.operations);
+    }
+}
+
 /// Combine all operations into one hash value.
 pub fn operations_hash<B, MT>(move_token: MT) -> HashResult
 where
     MT: Into<UnsignedMoveToken<B>>,
 {
     let move_token: UnsignedMoveToken<B> = move_token.into();
-    let operations_data = move_token.currencies_operations.canonical_serialize();
-    sha_512_256(&operations_data)
+    let mut hash_context = HashContext::new();
+    hash_update_currencies_operations(&mut hash_context, &move_token.currencies_operations);
+    hash_context.finalize()
 }
 
 pub fn local_address_hash<B, MT>(move_token: MT) -> HashResult
@@ -86,14 +119,14 @@ where
     MT: Into<UnsignedMoveToken<B>>,
 {
     let move_token: UnsignedMoveToken<B> = move_token.into();
-    let mut hash_buff = Vec::new();
+    let mut hash_context = HashContext::new();
 
-    hash_buff.extend_from_slice(&move_token.old_token);
-    hash_buff.extend_from_slice(&move_token.currencies_operations.canonical_serialize());
-    hash_buff.extend_from_slice(&move_token.opt_local_relays.canonical_serialize());
-    hash_buff.extend_from_slice(&move_token.opt_active_currencies.canonical_serialize());
+    hash_context.update(&move_token.old_token);
+    hash_update_currencies_operations(&mut hash_context, &move_token.currencies_operations);
+    hash_context.update(&move_token.opt_local_relays.canonical_serialize());
+    hash_context.update(&move_token.opt_active_currencies.canonical_serialize());
 
-    sha_512_256(&hash_buff)
+    hash_context.finalize()
 }
 
 pub fn move_token_signature_buff<B, MT>(move_token: MT) -> Vec<u8>
@@ -144,3 +177,156 @@ pub fn move_token_hashed_report_signature_buff(
     sig_buffer.extend_from_slice(&move_token_hashed_report.rand_nonce);
     sig_buffer
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryFrom;
+
+    use proto::app_server::messages::RelayAddress;
+    use proto::crypto::{PublicKey, RandValue, Signature, Uid};
+    use proto::funder::messages::{
+        BalanceInfo, CancelSendFundsOp, CountersInfo, CurrencyBalanceInfo, CurrencyOperations,
+        FriendTcOp, McInfo,
+    };
+
+    /// A fixed TokenInfo. Changing any of the values here will change the golden vectors below.
+    fn golden_token_info() -> TokenInfo {
+        TokenInfo {
+            mc: McInfo {
+                local_public_key: PublicKey::from(&[0xaa; PublicKey::len()]),
+                remote_public_key: PublicKey::from(&[0xbb; PublicKey::len()]),
+                balances: vec![CurrencyBalanceInfo {
+                    currency: Currency::try_from("FST".to_owned()).unwrap(),
+                    balance_info: BalanceInfo {
+                        balance: -5,
+                        local_pending_debt: 7,
+                        remote_pending_debt: 9,
+                    },
+                }],
+            },
+            counters: CountersInfo {
+                inconsistency_counter: 1,
+                move_token_counter: 2,
+            },
+        }
+    }
+
+    const GOLDEN_TOKEN_INFO_HASH: [u8; 32] = [
+        0xe4, 0xef, 0x49, 0x8b, 0x77, 0x87, 0xfc, 0xe1, 0xa5, 0x6d, 0x2f, 0xe7, 0x15, 0x2a, 0x63,
+        0x11, 0xc3, 0x12, 0x68, 0xe8, 0xe7, 0x98, 0x95, 0x62, 0xc4, 0xbe, 0xe5, 0x40, 0xc7, 0xed,
+        0x46, 0x4a,
+    ];
+
+    /// If this test fails, the canonical serialization of TokenInfo has changed.
+    /// Nodes running different versions will not agree on signatures, so this must be a
+    /// deliberate protocol change.
+    #[test]
+    fn test_hash_token_info_golden() {
+        let info_hash = hash_token_info(&golden_token_info());
+        assert_eq!(info_hash.as_ref(), GOLDEN_TOKEN_INFO_HASH);
+    }
+
+    #[test]
+    fn test_token_info_balances_order() {
+        let currency_balance_info = |currency: &str, balance| CurrencyBalanceInfo {
+            currency: Currency::try_from(currency.to_owned()).unwrap(),
+            balance_info: BalanceInfo {
+                balance,
+                local_pending_debt: 0,
+                remote_pending_debt: 0,
+            },
+        };
+        let token_info = |balances| TokenInfo {
+            mc: McInfo::new(
+                PublicKey::from(&[0xaa; PublicKey::len()]),
+                PublicKey::from(&[0xbb; PublicKey::len()]),
+                balances,
+            ),
+            counters: CountersInfo {
+                inconsistency_counter: 1,
+                move_token_counter: 2,
+            },
+        };
+
+        let token_info1 = token_info(vec![
+            currency_balance_info("FST", 5),
+            currency_balance_info("FND", -3),
+            currency_balance_info("USD", 0),
+        ]);
+        let token_info2 = token_info(vec![
+            currency_balance_info("USD", 0),
+            currency_balance_info("FST", 5),
+            currency_balance_info("FND", -3),
+        ]);
+
+        assert_eq!(token_info1, token_info2);
+        assert_eq!(hash_token_info(&token_info1), hash_token_info(&token_info2));
+    }
+
+    #[test]
+    fn test_move_token_signature_buff_golden() {
+        let move_token = UnsignedMoveToken::<u32> {
+            old_token: Signature::from(&[1; Signature::len()]),
+            currencies_operations: Vec::new(),
+            opt_local_relays: Some(vec![RelayAddress {
+                public_key: PublicKey::from(&[0xcc; PublicKey::len()]),
+                address: 0x0102_0304,
+            }]),
+            opt_active_currencies: Some(vec![Currency::try_from("FST".to_owned()).unwrap()]),
+            info_hash: hash_token_info(&golden_token_info()),
+            rand_nonce: RandValue::from(&[3; RandValue::len()]),
+        };
+
+        let sig_buff = move_token_signature_buff(move_token);
+        assert_eq!(sig_buff.len(), 32 + 32 + 32 + 16);
+
+        let expected = [
+            0x37, 0xb0, 0x94, 0x9d, 0x3c, 0x87, 0x26, 0xfa, 0x00, 0xcf, 0x05, 0x8a, 0xed, 0xf1,
+            0xea, 0xd3, 0x76, 0xeb, 0xf2, 0x02, 0x7f, 0x14, 0x70, 0x39, 0x3e, 0x47, 0x80, 0x53,
+            0x5f, 0x4c, 0x8b, 0x8d,
+        ];
+        assert_eq!(sha_512_256(&sig_buff).as_ref(), expected);
+    }
+
+    #[test]
+    fn test_operations_hash_streaming() {
+        let cancel_op = |i: u8| {
+            FriendTcOp::CancelSendFunds(CancelSendFundsOp {
+                request_id: Uid::from(&[i; Uid::len()]),
+                opt_reason: None,
+            })
+        };
+        let move_token = UnsignedMoveToken::<u32> {
+            old_token: Signature::from(&[1; Signature::len()]),
+            currencies_operations: vec![
+                CurrencyOperations {
+                    currency: Currency::try_from("FST".to_owned()).unwrap(),
+                    operations: vec![cancel_op(0), cancel_op(1)],
+                },
+                CurrencyOperations {
+                    currency: Currency::try_from("USD".to_owned()).unwrap(),
+                    operations: vec![cancel_op(2)],
+                },
+            ],
+            opt_local_relays: None,
+            opt_active_currencies: None,
+            info_hash: hash_token_info(&golden_token_info()),
+            rand_nonce: RandValue::from(&[3; RandValue::len()]),
+        };
+
+        // Hashing incrementally gives the same result as hashing the whole serialization:
+        assert_eq!(
+            operations_hash(move_token.clone()),
+            sha_512_256(&move_token.currencies_operations.canonical_serialize())
+        );
+
+        let mut hash_buff = Vec::new();
+        hash_buff.extend_from_slice(&move_token.old_token);
+        hash_buff.extend_from_slice(&move_token.currencies_operations.canonical_serialize());
+        hash_buff.extend_from_slice(&move_token.opt_local_relays.canonical_serialize());
+        hash_buff.extend_from_slice(&move_token.opt_active_currencies.canonical_serialize());
+        assert_eq!(prefix_hash(move_token), sha_512_256(&hash_buff));
+    }
+}
@@ -94,3 +94,182 @@ pub fn verify_move_token_hashed_report(
     let sig_buffer = move_token_hashed_report_signature_buff(move_token_hashed_report);
     verify_signature(&sig_buffer, public_key, &move_token_hashed_report.new_token)
 }
+
+/// Find out which side of the token channel signed a MoveTokenHashedReport.
+/// Returns the public key (Either the local or the remote public key, as specified in the
+/// embedded `token_info`) that the signature validates against, or `None` if the signature is not
+/// valid for either side.
+pub fn verify_move_token_hashed_report_signer(
+    move_token_hashed_report: &MoveTokenHashedReport,
+) -> Option<PublicKey> {
+    let sig_buffer = move_token_hashed_report_signature_buff(move_token_hashed_report);
+    let mc_info = &move_token_hashed_report.token_info.mc;
+    [&mc_info.local_public_key, &mc_info.remote_public_key]
+        .iter()
+        .find(|public_key| {
+            verify_signature(&sig_buffer, public_key, &move_token_hashed_report.new_token)
+        })
+        .map(|public_key| (*public_key).clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crypto::identity::{Identity, SoftwareEd25519Identity};
+    use crypto::rand::RandGen;
+    use crypto::test_utils::DummyRandom;
+
+    use std::convert::TryFrom;
+
+    use proto::crypto::{HashResult, InvoiceId, PlainLock, PrivateKey, RandValue, Signature, Uid};
+    use proto::funder::messages::{
+        CountersInfo, Currency, FriendsRoute, McInfo, PendingTransaction, TokenInfo,
+        TransactionStage, UnsignedResponseSendFundsOp,
+    };
+
+    use crate::signature_buff::create_response_signature_buffer;
+
+    fn create_identity(seed: u8) -> SoftwareEd25519Identity {
+        let rng = DummyRandom::new(&[seed]);
+        let pkcs8 = PrivateKey::rand_gen(&rng);
+        SoftwareEd25519Identity::from_private_key(&pkcs8).unwrap()
+    }
+
+    /// Create a MoveTokenHashedReport signed by `signer`
+    fn create_signed_report(
+        local_public_key: PublicKey,
+        remote_public_key: PublicKey,
+        signer: &SoftwareEd25519Identity,
+    ) -> MoveTokenHashedReport {
+        let mut move_token_hashed_report = MoveTokenHashedReport {
+            prefix_hash: HashResult::from(&[1; HashResult::len()]),
+            token_info: TokenInfo {
+                mc: McInfo {
+                    local_public_key,
+                    remote_public_key,
+                    balances: Vec::new(),
+                },
+                counters: CountersInfo {
+                    inconsistency_counter: 0,
+                    move_token_counter: 3,
+                },
+            },
+            rand_nonce: RandValue::from(&[2; RandValue::len()]),
+            new_token: Signature::from(&[0; Signature::len()]),
+        };
+        let sig_buffer = move_token_hashed_report_signature_buff(&move_token_hashed_report);
+        move_token_hashed_report.new_token = signer.sign(&sig_buffer);
+        move_token_hashed_report
+    }
+
+    #[test]
+    fn test_verify_move_token_hashed_report_signer() {
+        let local_identity = create_identity(1);
+        let remote_identity = create_identity(2);
+        let other_identity = create_identity(3);
+
+        let local_public_key = local_identity.get_public_key();
+        let remote_public_key = remote_identity.get_public_key();
+
+        // Signed by the local side:
+        let report = create_signed_report(
+            local_public_key.clone(),
+            remote_public_key.clone(),
+            &local_identity,
+        );
+        assert_eq!(
+            verify_move_token_hashed_report_signer(&report),
+            Some(local_public_key.clone())
+        );
+        assert!(verify_move_token_hashed_report(&report, &local_public_key));
+
+        // Signed by the remote side:
+        let report = create_signed_report(
+            local_public_key.clone(),
+            remote_public_key.clone(),
+            &remote_identity,
+        );
+        assert_eq!(
+            verify_move_token_hashed_report_signer(&report),
+            Some(remote_public_key.clone())
+        );
+
+        // Signed by someone else:
+        let report = create_signed_report(
+            local_public_key.clone(),
+            remote_public_key.clone(),
+            &other_identity,
+        );
+        assert_eq!(verify_move_token_hashed_report_signer(&report), None);
+
+        // Tampering with the report invalidates the signature:
+        let mut report = create_signed_report(local_public_key, remote_public_key, &local_identity);
+        report.token_info.counters.move_token_counter += 1;
+        assert_eq!(verify_move_token_hashed_report_signer(&report), None);
+    }
+
+    /// Create a receipt for a payment of the given currency, signed by `signer`
+    fn create_signed_receipt(currency: &Currency, signer: &SoftwareEd25519Identity) -> Receipt {
+        let src_plain_lock = PlainLock::from(&[1; PlainLock::len()]);
+        let dest_plain_lock = PlainLock::from(&[2; PlainLock::len()]);
+
+        let pending_transaction = PendingTransaction {
+            request_id: Uid::from(&[3; Uid::len()]),
+            route: FriendsRoute {
+                public_keys: Vec::new(),
+            },
+            dest_payment: 10,
+            total_dest_payment: 15,
+            invoice_id: InvoiceId::from(&[4; InvoiceId::len()]),
+            left_fees: 0,
+            src_hashed_lock: src_plain_lock.hash_lock(),
+            stage: TransactionStage::Request,
+        };
+        let response_send_funds = UnsignedResponseSendFundsOp {
+            request_id: pending_transaction.request_id.clone(),
+            dest_hashed_lock: dest_plain_lock.hash_lock(),
+            is_complete: true,
+            rand_nonce: RandValue::from(&[5; RandValue::len()]),
+        };
+
+        let mut hash_buff = Vec::new();
+        hash_buff.extend_from_slice(&pending_transaction.request_id);
+        hash_buff.extend_from_slice(&response_send_funds.rand_nonce);
+
+        let sig_buffer = create_response_signature_buffer(
+            currency,
+            response_send_funds.clone(),
+            &pending_transaction,
+        );
+
+        Receipt {
+            response_hash: hash::sha_512_256(&hash_buff),
+            invoice_id: pending_transaction.invoice_id.clone(),
+            currency: currency.clone(),
+            src_plain_lock,
+            dest_plain_lock,
+            is_complete: response_send_funds.is_complete,
+            dest_payment: pending_transaction.dest_payment,
+            total_dest_payment: pending_transaction.total_dest_payment,
+            signature: signer.sign(&sig_buffer),
+        }
+    }
+
+    #[test]
+    fn test_verify_receipt_currency() {
+        let identity = create_identity(1);
+        let public_key = identity.get_public_key();
+
+        let currency_a = Currency::try_from("FST".to_owned()).unwrap();
+        let currency_b = Currency::try_from("FST2".to_owned()).unwrap();
+
+        let receipt = create_signed_receipt(&currency_a, &identity);
+        assert!(verify_receipt(&receipt, &public_key));
+
+        // The currency is signed over, so the receipt can not be used for another currency:
+        let mut receipt_b = receipt.clone();
+        receipt_b.currency = currency_b;
+        assert!(!verify_receipt(&receipt_b, &public_key));
+    }
+}
@@ -3,8 +3,8 @@ use app::common::Currency;
 use crate::compact_node::messages::{
     BalanceInfo, ChannelConsistentReport, ChannelInconsistentReport, ChannelStatusReport, Commit,
     CompactReport, ConfigReport, CountersInfo, CurrencyReport, FriendLivenessReport, FriendReport,
-    FriendStatusReport, McInfo, MoveTokenHashedReport, OpenInvoice, OpenPayment, OpenPaymentStatus,
-    RequestsStatusReport, ResetTermsReport, TokenInfo,
+    FriendStatusReport, InconsistencyReason, McInfo, MoveTokenHashedReport, OpenInvoice,
+    OpenPayment, OpenPaymentStatus, RequestsStatusReport, ResetTermsReport, TokenInfo,
 };
 
 use crate::compact_node::persist;
@@ -74,6 +74,24 @@ impl From<app::report::ChannelInconsistentReport> for ChannelInconsistentReport
             opt_remote_reset_terms: from
                 .opt_remote_reset_terms
                 .map(|reset_terms_report| reset_terms_report.into()),
+            reason: from.reason.into(),
+        }
+    }
+}
+
+impl From<app::report::InconsistencyReason> for InconsistencyReason {
+    fn from(inconsistency_reason: app::report::InconsistencyReason) -> Self {
+        match inconsistency_reason {
+            app::report::InconsistencyReason::RemoteReported => InconsistencyReason::RemoteReported,
+            app::report::InconsistencyReason::InvalidMoveToken => {
+                InconsistencyReason::InvalidMoveToken
+            }
+            app::report::InconsistencyReason::TooManyOperations => {
+                InconsistencyReason::TooManyOperations
+            }
+            app::report::InconsistencyReason::TooManyRetransmits => {
+                InconsistencyReason::TooManyRetransmits
+            }
         }
     }
 }
@@ -154,6 +172,7 @@ impl From<app::report::FriendStatusReport> for FriendStatusReport {
         match friend_status_report {
             app::report::FriendStatusReport::Enabled => FriendStatusReport::Enabled,
             app::report::FriendStatusReport::Disabled => FriendStatusReport::Disabled,
+            app::report::FriendStatusReport::Paused => FriendStatusReport::Paused,
         }
     }
 }
@@ -321,3 +340,56 @@ pub fn create_compact_report(
             .collect(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_convert_channel_consistent_report() {
+        let currency1 = Currency::try_from("FST1".to_owned()).unwrap();
+        let currency2 = Currency::try_from("FST2".to_owned()).unwrap();
+
+        let app_report =
+            app::report::ChannelStatusReport::Consistent(app::report::ChannelConsistentReport {
+                currency_reports: vec![
+                    app::report::CurrencyReport {
+                        currency: currency1.clone(),
+                        balance: app::report::McBalanceReport {
+                            balance: -5,
+                            local_pending_debt: 7,
+                            remote_pending_debt: 9,
+                        },
+                    },
+                    app::report::CurrencyReport {
+                        currency: currency2.clone(),
+                        balance: app::report::McBalanceReport {
+                            balance: 100,
+                            local_pending_debt: 0,
+                            remote_pending_debt: 3,
+                        },
+                    },
+                ],
+            });
+
+        let channel_consistent_report = match ChannelStatusReport::from(app_report) {
+            ChannelStatusReport::Consistent(channel_consistent_report) => channel_consistent_report,
+            ChannelStatusReport::Inconsistent(_) => unreachable!(),
+        };
+
+        let currency_reports = &channel_consistent_report.currency_reports;
+        assert_eq!(currency_reports.len(), 2);
+
+        let currency_report1 = &currency_reports[&currency1];
+        assert_eq!(currency_report1.balance, -5);
+        assert_eq!(currency_report1.local_pending_debt, 7);
+        assert_eq!(currency_report1.remote_pending_debt, 9);
+
+        let currency_report2 = &currency_reports[&currency2];
+        assert_eq!(currency_report2.balance, 100);
+        assert_eq!(currency_report2.local_pending_debt, 0);
+        assert_eq!(currency_report2.remote_pending_debt, 3);
+    }
+}
@@ -41,7 +41,7 @@ fn obtain_multi_route(
 ) -> Option<(MultiRoute, MultiRouteChoice, u128)> {
     let multi_routes = match &client_response_routes.result {
         ResponseRoutesResult::Success(multi_routes) => multi_routes,
-        ResponseRoutesResult::Failure => return None,
+        ResponseRoutesResult::Failure | ResponseRoutesResult::NoIndexServer => return None,
     };
 
     let (route_index, multi_route_choice) = choose_multi_route(&multi_routes, dest_payment)?;
@@ -152,7 +152,7 @@ where
                         .await
                         .map_err(|_| CompactNodeError::UserSenderError)?;
                 }
-                (RequestResult::Failure, _) | (RequestResult::Success, true) => {
+                (RequestResult::Failure(_), _) | (RequestResult::Success, true) => {
                     // Set payment as failed:
                     let ack_uid = compact_gen.gen_uid();
                     open_payment.status = OpenPaymentStatus::Failure(ack_uid.clone());
@@ -391,6 +391,27 @@ where
                 .await
                 .map_err(|_| CompactNodeError::UserSenderError)?;
         }
+        AppServerToApp::ResponseCommitInvoice(response_commit_invoice) => {
+            // The user learns about committed invoices through the node report:
+            info!(
+                "ResponseCommitInvoice: invoice_id: {:?}, result: {:?}",
+                response_commit_invoice.invoice_id, response_commit_invoice.result
+            );
+        }
+        AppServerToApp::ResponseFriendRelays(response_friend_relays) => {
+            // The user learns about the relays of friends through the node report:
+            info!(
+                "ResponseFriendRelays: friend_public_key: {:?}, result: {:?}",
+                response_friend_relays.friend_public_key, response_friend_relays.result
+            );
+        }
+        AppServerToApp::ResponseCancelPayment(response_cancel_payment) => {
+            // The payment is closed later, using ResponseClosePayment:
+            info!(
+                "ResponseCancelPayment: payment_id: {:?}, in_flight: {:?}",
+                response_cancel_payment.payment_id, response_cancel_payment.in_flight
+            );
+        }
     }
     Ok(())
 }
@@ -305,6 +305,7 @@ where
 
             // Request routes:
             let opt_exclude = None;
+            let opt_max_hops = None;
             let app_request = routes::request_routes(
                 request_routes_id.clone(),
                 init_payment.currency.clone(),
@@ -316,6 +317,7 @@ where
                     .clone(),
                 init_payment.dest_public_key.clone(),
                 opt_exclude,
+                opt_max_hops,
             );
 
             let app_to_app_server = AppToAppServer {
@@ -703,6 +705,21 @@ where
                     .map_err(|_| CompactNodeError::UserSenderError);
             }
 
+            // Make sure that the commitment matches the invoice:
+            let open_invoice = &compact_state.open_invoices[&invoice_id];
+            if let Err(commit_mismatch) =
+                commit.validate(&open_invoice.currency, open_invoice.total_dest_payment)
+            {
+                warn!(
+                    "RequestCommitInvoice: Invoice: {:?}: Commit mismatch: {:?}",
+                    invoice_id, commit_mismatch
+                );
+                return user_sender
+                    .send(CompactToUserAck::Ack(user_request_id))
+                    .await
+                    .map_err(|_| CompactNodeError::UserSenderError);
+            }
+
             // Send commitment to node:
             let app_request = seller::commit_invoice(commit.clone());
 
@@ -283,12 +283,27 @@ pub struct ResetTermsReport {
     pub balance_for_reset: HashMap<Currency, i128>,
 }
 
+/// The reason a token channel became inconsistent
+#[derive(Arbitrary, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum InconsistencyReason {
+    /// The remote side reported an inconsistency
+    RemoteReported,
+    /// A move token received from the remote side was invalid
+    InvalidMoveToken,
+    /// A move token received from the remote side contained too many operations
+    TooManyOperations,
+    /// The remote side sent too many old move tokens in a row
+    TooManyRetransmits,
+}
+
 #[derive(Arbitrary, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ChannelInconsistentReport {
     #[serde(with = "ser_map_str_str")]
     pub local_reset_terms: HashMap<Currency, i128>,
     pub opt_remote_reset_terms: Option<ResetTermsReport>,
+    pub reason: InconsistencyReason,
 }
 
 #[derive(Arbitrary, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -325,6 +340,7 @@ pub enum ChannelStatusReport {
 pub enum FriendStatusReport {
     Enabled,
     Disabled,
+    Paused,
 }
 
 #[derive(Arbitrary, Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
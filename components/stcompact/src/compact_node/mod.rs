@@ -11,6 +11,6 @@ mod types;
 mod utils;
 
 pub use convert::create_compact_report;
-pub use persist::CompactState;
+pub use persist::{CompactState, COMPACT_STATE_VERSION};
 pub use server::compact_node;
 pub use types::ConnPairCompact;
@@ -78,8 +78,19 @@ pub struct OpenPayment {
     pub status: OpenPaymentStatus,
 }
 
+/// Current layout version of `CompactState`.
+/// Should be bumped whenever a field is added to `CompactState`.
+pub const COMPACT_STATE_VERSION: u32 = 1;
+
+/// Persistent state of the compact node.
+/// Note: New fields must have a serde default, so that states stored by older versions can still
+/// be loaded.
 #[derive(Arbitrary, Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct CompactState {
+    /// Layout version of the stored state.
+    /// States stored before versioning was introduced are considered to be of version 0.
+    #[serde(default)]
+    pub version: u32,
     /// Seller's open invoices:
     #[serde(with = "ser_map_b64_any")]
     pub open_invoices: HashMap<InvoiceId, OpenInvoice>,
@@ -93,11 +104,26 @@ pub struct CompactState {
 impl CompactState {
     pub fn new() -> Self {
         Self {
+            version: COMPACT_STATE_VERSION,
             open_invoices: HashMap::new(),
             open_payments: HashMap::new(),
             generation: Generation(0),
         }
     }
+
+    /// Was this state stored by an older version?
+    pub fn is_outdated(&self) -> bool {
+        self.version < COMPACT_STATE_VERSION
+    }
+
+    /// Bring a state loaded from an older version up to date.
+    /// Missing fields were already filled with their defaults during deserialization.
+    pub fn migrate(self) -> Self {
+        Self {
+            version: COMPACT_STATE_VERSION,
+            ..self
+        }
+    }
 }
 
 impl MutableState for CompactState {
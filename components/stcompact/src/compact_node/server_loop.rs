@@ -105,3 +105,114 @@ where
     )
     .await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryFrom;
+
+    use futures::executor::{block_on, ThreadPool};
+    use futures::task::SpawnExt;
+    use futures::FutureExt;
+
+    use tempfile::tempdir;
+
+    use crypto::test_utils::DummyRandom;
+    use database::file_db::FileDb;
+    use database::{database_loop, AtomicDb};
+
+    use app::common::{Currency, InvoiceId, PaymentId, PublicKey, Uid};
+    use app::conn::{AppPermissions, AppRequest, ConnPairApp};
+    use app::report::NodeReport;
+
+    use crate::compact_node::messages::{InitPayment, UserToCompact, UserToCompactAck};
+    use crate::gen::GenCryptoRandom;
+
+    async fn task_compact_node_concurrent_payments(thread_pool: ThreadPool) {
+        // Spawn a database:
+        let db_dir = tempdir().unwrap();
+        let db_path = db_dir.path().join("compact.db");
+        let atomic_db = FileDb::create(db_path.clone(), CompactState::new()).unwrap();
+        let (db_request_sender, incoming_db_requests) = mpsc::channel(0);
+        thread_pool
+            .spawn(database_loop(atomic_db, incoming_db_requests, thread_pool.clone()).map(|_| ()))
+            .unwrap();
+        let database_client = DatabaseClient::new(db_request_sender);
+
+        let node_report = NodeReport::dummy();
+
+        let (app_sender, mut node_receiver) = mpsc::channel(16);
+        let (_node_sender, app_receiver) = mpsc::channel(16);
+        let conn_pair_app = ConnPairApp::from_raw(app_sender, app_receiver);
+        let app_conn_tuple = (AppPermissions::full(), node_report, conn_pair_app);
+
+        let (mut user_sender, compact_receiver) = mpsc::channel(16);
+        let (compact_sender, _user_receiver) = mpsc::channel(16);
+        let conn_pair_compact = ConnPairCompact::from_raw(compact_sender, compact_receiver);
+
+        let (event_sender, mut event_receiver) = mpsc::channel(0);
+        thread_pool
+            .spawn(
+                inner_compact_node_loop(
+                    app_conn_tuple,
+                    conn_pair_compact,
+                    CompactState::new(),
+                    database_client,
+                    GenCryptoRandom(DummyRandom::new(&[1u8])),
+                    Some(event_sender),
+                )
+                .map(|_| ()),
+            )
+            .unwrap();
+
+        // Fire two payments, without waiting for any response:
+        for i in 0..2u8 {
+            let init_payment = InitPayment {
+                payment_id: PaymentId::from(&[i; PaymentId::len()]),
+                invoice_id: InvoiceId::from(&[0x10 + i; InvoiceId::len()]),
+                currency: Currency::try_from("FST".to_owned()).unwrap(),
+                dest_public_key: PublicKey::from(&[0xbb; PublicKey::len()]),
+                dest_payment: 10 + u128::from(i),
+                description: format!("payment{}", i),
+            };
+            user_sender
+                .send(UserToCompactAck {
+                    user_request_id: Uid::from(&[0x20 + i; Uid::len()]),
+                    inner: UserToCompact::InitPayment(init_payment),
+                })
+                .await
+                .unwrap();
+        }
+
+        // Wait until both requests were handled:
+        for _ in 0..2 {
+            event_receiver.next().await.unwrap();
+        }
+
+        // Routes were requested for both payments:
+        for _ in 0..2 {
+            let app_to_app_server = node_receiver.next().await.unwrap();
+            match app_to_app_server.app_request {
+                AppRequest::RequestRoutes(_) => {}
+                _ => unreachable!(),
+            }
+        }
+
+        // The persisted state reflects both payments:
+        let file_db = FileDb::<CompactState>::load(db_path).unwrap();
+        let open_payments = &file_db.get_state().open_payments;
+        assert_eq!(open_payments.len(), 2);
+        let open_payment0 = &open_payments[&PaymentId::from(&[0; PaymentId::len()])];
+        let open_payment1 = &open_payments[&PaymentId::from(&[1; PaymentId::len()])];
+        assert_eq!(open_payment0.dest_payment, 10);
+        assert_eq!(open_payment1.dest_payment, 11);
+        assert_ne!(open_payment0.generation, open_payment1.generation);
+    }
+
+    #[test]
+    fn test_compact_node_concurrent_payments() {
+        let thread_pool = ThreadPool::new().unwrap();
+        block_on(task_compact_node_concurrent_payments(thread_pool));
+    }
+}
@@ -28,6 +28,9 @@ pub enum CompactNodeError {
     DatabaseMutateError,
 }
 
+/// State of the compact server.
+/// Owned exclusively by the compact node loop, which handles one event at a time. Therefore,
+/// updates to `compact_state` (and to its database) never interleave.
 pub struct CompactServerState {
     node_report: app::report::NodeReport,
     compact_state: CompactState,
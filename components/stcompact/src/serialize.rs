@@ -3,6 +3,9 @@ use futures::task::{Spawn, SpawnExt};
 use futures::channel::mpsc;
 use futures::{FutureExt, SinkExt, StreamExt};
 
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
 use common::conn::ConnPairString;
 
 use crate::messages::UserToServerAck;
@@ -13,6 +16,40 @@ pub enum SerializeConnError {
     SpawnError,
 }
 
+#[derive(Debug)]
+pub enum LineError {
+    /// The line contains no message
+    EmptyLine,
+    /// The line contains a line break before its end
+    MultipleLines,
+    /// The line is not a valid serialized message
+    DeserializeError(serde_json::Error),
+}
+
+/// Serialize a message into a single line (Without a trailing newline)
+pub fn to_line<T>(msg: &T) -> String
+where
+    T: Serialize,
+{
+    // Compact JSON never contains raw line breaks, as they are escaped inside strings:
+    serde_json::to_string(msg).expect("Serialization error!")
+}
+
+/// Deserialize a message from a single line. A trailing newline is allowed.
+pub fn from_line<T>(line: &str) -> Result<T, LineError>
+where
+    T: DeserializeOwned,
+{
+    let line = line.trim_end_matches('\n').trim_end_matches('\r');
+    if line.trim().is_empty() {
+        return Err(LineError::EmptyLine);
+    }
+    if line.contains('\n') {
+        return Err(LineError::MultipleLines);
+    }
+    serde_json::from_str(line).map_err(LineError::DeserializeError)
+}
+
 /// Serialize a strings communication into ConnPairCompactServer
 pub fn serialize_conn_pair<S>(
     conn_pair: ConnPairString,
@@ -28,10 +65,7 @@ where
 
     let send_fut = async move {
         while let Some(server_to_user_ack) = receiver.next().await {
-            // Serialize:
-            let ser_str = serde_json::to_string(&server_to_user_ack).expect("Serialization error!");
-
-            user_sender.send(ser_str).await.ok()?;
+            user_sender.send(to_line(&server_to_user_ack)).await.ok()?;
         }
         Some(())
     };
@@ -41,8 +75,13 @@ where
 
     let recv_fut = async move {
         while let Some(line) = user_receiver.next().await {
-            // Deserialize:
-            let user_to_server_ack: UserToServerAck = serde_json::from_str(&line).ok()?;
+            let user_to_server_ack: UserToServerAck = match from_line(&line) {
+                Ok(user_to_server_ack) => user_to_server_ack,
+                Err(e) => {
+                    error!("serialize_conn_pair(): Malformed line: {:?}", e);
+                    return None;
+                }
+            };
 
             // Forward to user:
             sender.send(user_to_server_ack).await.ok()?;
@@ -76,6 +115,8 @@ mod tests {
     use crate::compact_node::messages::*;
     use crate::messages::*;
 
+    use super::{from_line, to_line, LineError};
+
     #[test]
     fn test_ser_deser_server_to_user_ack1() {
         let msg = ServerToUserAck::Ack(Uid::from(&[21; Uid::len()]));
@@ -130,6 +171,68 @@ mod tests {
         assert_eq!(msg, msg2);
     }
 
+    #[test]
+    fn test_line_round_trip() {
+        let node_name = NodeName::new("node_name".to_owned());
+
+        let server_to_user_acks = vec![
+            ServerToUserAck::Ack(Uid::from(&[21; Uid::len()])),
+            ServerToUserAck::ServerToUser(ServerToUser::NodesStatus(HashMap::new())),
+            ServerToUserAck::ServerToUser(ServerToUser::Node(
+                NodeId(0x100u64),
+                CompactToUser::ResponseVerifyCommit(ResponseVerifyCommit {
+                    request_id: Uid::from(&[22; Uid::len()]),
+                    status: VerifyCommitStatus::Success,
+                }),
+            )),
+        ];
+        for msg in server_to_user_acks {
+            let line = to_line(&msg);
+            assert!(!line.contains('\n'));
+            let msg2: ServerToUserAck = from_line(&format!("{}\n", line)).unwrap();
+            assert_eq!(msg, msg2);
+        }
+
+        let user_to_servers = vec![
+            UserToServer::CreateNode(CreateNode::CreateNodeLocal(CreateNodeLocal {
+                node_name: NodeName::new("multi\nline".to_owned()),
+            })),
+            UserToServer::RemoveNode(node_name.clone()),
+            UserToServer::EnableNode(node_name.clone()),
+            UserToServer::DisableNode(node_name),
+            UserToServer::Node(
+                NodeId(0x100u64),
+                UserToCompact::RemoveRelay(PublicKey::from(&[0xaa; PublicKey::len()])),
+            ),
+        ];
+        for inner in user_to_servers {
+            let msg = UserToServerAck {
+                request_id: Uid::from(&[1; Uid::len()]),
+                inner,
+            };
+            let line = to_line(&msg);
+            assert!(!line.contains('\n'));
+            let msg2: UserToServerAck = from_line(&line).unwrap();
+            assert_eq!(msg, msg2);
+        }
+    }
+
+    #[test]
+    fn test_line_malformed() {
+        match from_line::<UserToServerAck>("\n") {
+            Err(LineError::EmptyLine) => {}
+            _ => unreachable!(),
+        }
+        match from_line::<UserToServerAck>("{}\n{}\n") {
+            Err(LineError::MultipleLines) => {}
+            _ => unreachable!(),
+        }
+        match from_line::<UserToServerAck>("{\"requestId\": 3}\n") {
+            Err(LineError::DeserializeError(_)) => {}
+            _ => unreachable!(),
+        }
+    }
+
     #[derive(Arbitrary, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
     struct ExampleStruct {
         ex_num: i32,
@@ -20,7 +20,11 @@ use app::common::{NetAddress, Uid};
 use app::conn::ConnPairApp;
 use app_client::app_connect_to_node;
 
-use proto::consts::{KEEPALIVE_TICKS, MAX_NODE_RELAYS, MAX_OPERATIONS_IN_BATCH, TICKS_TO_REKEY};
+use proto::consts::{
+    INDEX_MUTATIONS_BATCH_TICKS, KEEPALIVE_TICKS, MAX_APP_QUEUED_MESSAGES,
+    MAX_MOVE_TOKEN_RETRANSMITS, MAX_NODE_RELAYS, MAX_OPERATIONS_IN_BATCH,
+    PENDING_TRANSACTION_TIMEOUT_TICKS, TICKS_TO_REKEY,
+};
 
 use node::{node, ConnPairServer, IncomingAppConnection, NodeConfig};
 use proto::app_server::messages::{AppPermissions, NodeReport};
@@ -55,6 +59,8 @@ const MAX_OPEN_INDEX_CLIENT_REQUESTS: usize = 0x8;
 /// The amount of ticks we are willing to wait until a connection is established (Through
 /// the relay)
 const CONN_TIMEOUT_TICKS: usize = 0x8;
+/// Maximum amount of concurrently open incoming app connections.
+const MAX_INCOMING_APP_CONNS: usize = 0x20;
 
 pub type ConnPairCompactServer = ConnPair<ServerToUserAck, UserToServerAck>;
 
@@ -521,8 +527,22 @@ const NODE_CONFIG: NodeConfig = NodeConfig {
     max_pending_user_requests: MAX_PENDING_USER_REQUESTS,
     /// Maximum amount of concurrent index client requests:
     max_open_index_client_requests: MAX_OPEN_INDEX_CLIENT_REQUESTS,
+    /// The amount of ticks the index client collects mutations before sending them.
+    index_mutations_batch_ticks: INDEX_MUTATIONS_BATCH_TICKS,
     /// Maximum amount of relays a node may use.
     max_node_relays: MAX_NODE_RELAYS,
+    /// The amount of ticks we wait for a response to a pending request before canceling it.
+    pending_transaction_timeout_ticks: PENDING_TRANSACTION_TIMEOUT_TICKS,
+    /// Maximum amount of old move tokens we accept from a friend in a row.
+    max_move_token_retransmits: MAX_MOVE_TOKEN_RETRANSMITS,
+    /// Optional tap over the messages passed between the Channeler and the Funder.
+    opt_message_tracer: None,
+    /// Optional sink for the outcomes of payments made by this node.
+    opt_payment_event_sender: None,
+    /// Maximum amount of concurrently open incoming app connections.
+    max_incoming_app_conns: MAX_INCOMING_APP_CONNS,
+    /// Maximum amount of messages queued for sending to a single app.
+    max_app_queued_messages: MAX_APP_QUEUED_MESSAGES,
 };
 
 async fn open_node_local<ST, R, C, S>(
@@ -546,7 +566,9 @@ where
     };
     let (report_sender, report_receiver) =
         oneshot::channel::<(NodeReport, oneshot::Sender<ConnPairServer<NetAddress>>)>();
+    // The only app is the local compact server, acting on behalf of the node:
     let incoming_app_connection = IncomingAppConnection {
+        app_public_key: local.node_state.funder_state.local_public_key.clone(),
         app_permissions: app_permissions.clone(),
         report_sender,
     };
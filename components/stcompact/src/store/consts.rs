@@ -1,6 +1,7 @@
 pub const LOCKFILE: &str = "lockfile";
 pub const LOCAL: &str = "local";
 pub const REMOTE: &str = "remote";
+pub const RESTORE_READY: &str = "restore.ready";
 pub const NODE_IDENT: &str = "node.ident";
 pub const NODE_CONFIG: &str = "node.config";
 pub const NODE_DB: &str = "node.db";
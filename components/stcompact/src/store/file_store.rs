@@ -34,6 +34,7 @@ use crate::messages::{NodeInfo, NodeInfoLocal, NodeInfoRemote, NodeName};
 use crate::compact_node::CompactState;
 use crate::store::consts::{
     APP_IDENT, COMPACT_DB, LOCAL, LOCKFILE, NODE_CONFIG, NODE_DB, NODE_IDENT, NODE_INFO, REMOTE,
+    RESTORE_READY,
 };
 use crate::store::store::{
     LoadedNode, LoadedNodeLocal, LoadedNodeRemote, Store, StoreError, StoredNode, StoredNodeConfig,
@@ -88,6 +89,7 @@ pub enum FileStoreError {
     LoadIdentityError,
     LoadDbError,
     InvalidNodeName,
+    NodeStateKeyMismatch,
 }
 
 impl StoreError for FileStoreError {
@@ -98,6 +100,7 @@ impl StoreError for FileStoreError {
             | FileStoreError::NodeNotLoaded
             | FileStoreError::NodeDoesNotExist
             | FileStoreError::InvalidNodeName
+            | FileStoreError::NodeStateKeyMismatch
             | FileStoreError::RemoveNodeError => false,
             FileStoreError::SpawnError(_)
             | FileStoreError::LockError
@@ -152,8 +155,19 @@ type FileStoreNodes = HashMap<NodeName, FileStoreNode>;
  *          - node.config
  *          - node.info (public_key + address)
  *          - compact.db
+ *
+ * During a restore, the backup is first copied into `local.restore` and `remote.restore`. The
+ * file `restore.ready` is then created, and the current `local` and `remote` are swapped with
+ * the copied directories, leaving the previous contents in `local.old` and `remote.old` until
+ * the swap is done.
 */
 
+/// Open a file store, creating it if it does not exist.
+///
+/// The store is protected by an advisory lock (`flock`) over its lockfile. The lock is released
+/// by the operating system when the owning process exits (Even if the process was killed), so a
+/// leftover lockfile is never considered locked. Therefore there is no option to break the lock:
+/// If locking fails, another live process is using the store.
 pub async fn open_file_store<FS, S>(
     store_path_buf: PathBuf,
     spawner: S,
@@ -180,6 +194,12 @@ where
         .await
         .map_err(|_| FileStoreError::LockError)?;
 
+    // Complete or roll back a restore that was interrupted:
+    let c_store_path_buf = store_path_buf.clone();
+    file_spawner
+        .spawn_with_handle(async move { finish_restore(&c_store_path_buf) })?
+        .await?;
+
     // Verify file store's integrity:
     verify_store(store_path_buf.clone(), &file_spawner).await?;
 
@@ -192,6 +212,148 @@ where
     })
 }
 
+impl<S, FS> FileStore<S, FS> {
+    /// Close the file store. Loaded nodes are closed first, and then the store's lock is
+    /// released, allowing the store to be opened again.
+    pub fn close(self) {
+        let FileStore {
+            live_nodes,
+            lock_file_handle,
+            ..
+        } = self;
+        drop(live_nodes);
+        drop(lock_file_handle);
+    }
+}
+
+impl<S, FS> FileStore<S, FS>
+where
+    FS: Spawn,
+{
+    /// Copy the contents of the store into a new directory at `backup_path`.
+    ///
+    /// Loaded nodes might write to their databases at any time, so all nodes must be unloaded
+    /// to obtain a consistent copy. As we hold the store's lock, no other process may change
+    /// the store during the backup.
+    /// The backup directory is itself a valid store, and can be restored using `restore()`.
+    pub async fn backup(&self, backup_path: &Path) -> Result<(), FileStoreError> {
+        if !self.live_nodes.is_empty() {
+            return Err(FileStoreError::NodeIsLoaded);
+        }
+
+        let store_path = self.store_path_buf.clone();
+        let backup_path = backup_path.to_owned();
+        self.file_spawner
+            .spawn_with_handle(async move {
+                // Should fail if the directory already exists:
+                fs::create_dir(&backup_path)?;
+                for dir in &[LOCAL, REMOTE] {
+                    let src_path = store_path.join(dir);
+                    if src_path.is_dir() {
+                        copy_dir_all(&src_path, &backup_path.join(dir))?;
+                    }
+                }
+                std::io::Result::Ok(())
+            })?
+            .await?;
+
+        Ok(())
+    }
+
+    /// Replace the contents of the store with the contents of a backup created by `backup()`.
+    ///
+    /// All nodes must be unloaded. The backup is verified and copied into the store before any
+    /// current node is removed. If the restore is interrupted (For example, by a crash), it is
+    /// completed or rolled back the next time the store is opened.
+    pub async fn restore(&mut self, backup_path: &Path) -> Result<(), FileStoreError> {
+        if !self.live_nodes.is_empty() {
+            return Err(FileStoreError::NodeIsLoaded);
+        }
+
+        verify_store(backup_path.to_owned(), &self.file_spawner).await?;
+
+        let store_path = self.store_path_buf.clone();
+        let backup_path = backup_path.to_owned();
+        self.file_spawner
+            .spawn_with_handle(async move {
+                // Remove leftovers of an earlier restore:
+                finish_restore(&store_path)?;
+
+                // First copy the backup next to the current contents:
+                for dir in &[LOCAL, REMOTE] {
+                    let restore_path = store_path.join(format!("{}.restore", dir));
+                    let src_path = backup_path.join(dir);
+                    if src_path.is_dir() {
+                        copy_dir_all(&src_path, &restore_path)?;
+                    } else {
+                        fs::create_dir(&restore_path)?;
+                    }
+                }
+
+                // The backup was fully copied. From this point on, an interrupted restore is
+                // completed (and not rolled back) when the store is opened:
+                fs::File::create(store_path.join(RESTORE_READY))?.sync_all()?;
+
+                // Replace the current contents:
+                finish_restore(&store_path)
+            })?
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Complete a restore whose backup was fully copied into the store, or roll back a restore that
+/// was interrupted before that. Leftovers of previous restores are removed.
+/// This operation blocks.
+fn finish_restore(store_path: &Path) -> std::io::Result<()> {
+    let ready_path = store_path.join(RESTORE_READY);
+    if ready_path.exists() {
+        // Swap in the copied backup. A directory without a `.restore` copy was already swapped:
+        for dir in &[LOCAL, REMOTE] {
+            let dir_path = store_path.join(dir);
+            let restore_path = store_path.join(format!("{}.restore", dir));
+            if !restore_path.exists() {
+                continue;
+            }
+            if dir_path.exists() {
+                fs::rename(&dir_path, store_path.join(format!("{}.old", dir)))?;
+            }
+            fs::rename(&restore_path, &dir_path)?;
+        }
+        fs::remove_file(&ready_path)?;
+    }
+
+    // Without `restore.ready`, these are either a partial copy of a backup, or previous contents
+    // that were already replaced:
+    for dir in &[LOCAL, REMOTE] {
+        for suffix in &["restore", "old"] {
+            let path = store_path.join(format!("{}.{}", dir, suffix));
+            if path.exists() {
+                fs::remove_dir_all(&path)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Recursively copy the directory `src_path` into a new directory `dst_path`.
+/// Copied files are synced to disk. This operation blocks.
+fn copy_dir_all(src_path: &Path, dst_path: &Path) -> std::io::Result<()> {
+    fs::create_dir(dst_path)?;
+    for res in fs::read_dir(src_path)? {
+        let entry = res?;
+        let entry_dst_path = dst_path.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir_all(&entry.path(), &entry_dst_path)?;
+        } else {
+            fs::copy(entry.path(), &entry_dst_path)?;
+            fs::File::open(&entry_dst_path)?.sync_all()?;
+        }
+    }
+    Ok(())
+}
+
 fn read_local_node(node_path: &Path) -> Result<FileStoreNodeLocal, FileStoreError> {
     let node_ident_path = node_path.join(NODE_IDENT);
     let ident_data = fs::read_to_string(&node_ident_path)?;
@@ -310,6 +472,43 @@ where
     Ok(())
 }
 
+/// Rename a node by moving its directory.
+/// The node remains local or remote, according to its original type.
+async fn rename_node<FS>(
+    store_path: &Path,
+    old_node_name: NodeName,
+    new_node_name: NodeName,
+    file_spawner: &FS,
+) -> Result<(), FileStoreError>
+where
+    FS: Spawn,
+{
+    if !is_node_name_valid(&new_node_name) {
+        return Err(FileStoreError::InvalidNodeName);
+    }
+
+    let file_store_nodes = read_all_nodes(store_path.to_owned(), file_spawner).await?;
+    if file_store_nodes.contains_key(&new_node_name) {
+        return Err(FileStoreError::DuplicateNodeName(new_node_name));
+    }
+
+    let node_dir = match file_store_nodes.get(&old_node_name) {
+        Some(FileStoreNode::Local(_)) => LOCAL,
+        Some(FileStoreNode::Remote(_)) => REMOTE,
+        None => return Err(FileStoreError::NodeDoesNotExist),
+    };
+
+    let old_path = store_path.join(node_dir).join(old_node_name.as_str());
+    let new_path = store_path.join(node_dir).join(new_node_name.as_str());
+
+    // Both paths are inside the same directory, so the rename is atomic:
+    file_spawner
+        .spawn_with_handle(async move { fs::rename(&old_path, &new_path) })?
+        .await?;
+
+    Ok(())
+}
+
 /// Verify store's integrity
 pub async fn verify_store<FS>(store_path: PathBuf, file_spawner: &FS) -> Result<(), FileStoreError>
 where
@@ -335,9 +534,12 @@ fn is_node_name_valid(node_name: &NodeName) -> bool {
         || node_name.as_str().contains('.'))
 }
 
+/// Create a new local node.
+/// If `opt_node_state` is not provided, the node is created with an initial empty state.
 async fn create_local_node<FS>(
     node_name: NodeName,
     node_private_key: PrivateKey,
+    opt_node_state: Option<NodeState<NetAddress>>,
     store_path: &Path,
     file_spawner: &FS,
 ) -> Result<(), FileStoreError>
@@ -348,6 +550,19 @@ where
         return Err(FileStoreError::InvalidNodeName);
     }
 
+    let node_public_key =
+        derive_public_key(&node_private_key).map_err(|_| FileStoreError::DerivePublicKeyError)?;
+    let initial_state = match opt_node_state {
+        Some(node_state) => {
+            // The provided state must belong to the node's private key:
+            if node_state.funder_state.local_public_key != node_public_key {
+                return Err(FileStoreError::NodeStateKeyMismatch);
+            }
+            node_state
+        }
+        None => NodeState::<NetAddress>::new(node_public_key),
+    };
+
     let node_path = store_path.join(LOCAL).join(&node_name.as_str());
 
     // Create node's dir. Should fail if the directory already exists:
@@ -358,9 +573,6 @@ where
 
     // Create node database file:
     let node_db_path = node_path.join(NODE_DB);
-    let node_public_key =
-        derive_public_key(&node_private_key).map_err(|_| FileStoreError::DerivePublicKeyError)?;
-    let initial_state = NodeState::<NetAddress>::new(node_public_key);
     let _ = FileDb::create(node_db_path, initial_state).map_err(|_| FileStoreError::FileDbError)?;
 
     // Create compact database file:
@@ -585,6 +797,22 @@ where
     Ok((state, remote_handle, DatabaseClient::new(db_request_sender)))
 }
 
+/// Load a compact database, migrating it if it was stored by an older version.
+/// This operation blocks.
+fn migrate_compact_db(db_path_buf: PathBuf) -> Result<(), FileStoreError> {
+    let mut atomic_db =
+        FileDb::<CompactState>::load(db_path_buf).map_err(|_| FileStoreError::LoadDbError)?;
+
+    let compact_state = atomic_db.get_state();
+    if compact_state.is_outdated() {
+        let migrated_state = compact_state.clone().migrate();
+        atomic_db
+            .mutate_db(&[migrated_state])
+            .map_err(|_| FileStoreError::FileDbError)?;
+    }
+    Ok(())
+}
+
 async fn load_local_node<S, FS>(
     local: &FileStoreNodeLocal,
     spawner: &S,
@@ -598,6 +826,12 @@ where
     let (node_identity_handle, node_identity_client) =
         create_identity_server(&local.node_private_key, spawner)?;
 
+    // Migrate compact database (This operation blocks, so we use the file_spawner):
+    let compact_db_path = local.compact_db.clone();
+    file_spawner
+        .spawn_with_handle(async move { migrate_compact_db(compact_db_path) })?
+        .await?;
+
     // Spawn compact database:
     let (compact_state, compact_db_handle, compact_db_client) =
         spawn_db(local.compact_db.clone(), spawner, file_spawner.clone()).await?;
@@ -637,6 +871,12 @@ where
     let (app_identity_handle, app_identity_client) =
         create_identity_server(&remote.app_private_key, spawner)?;
 
+    // Migrate compact database (This operation blocks, so we use the file_spawner):
+    let compact_db_path = remote.compact_db.clone();
+    file_spawner
+        .spawn_with_handle(async move { migrate_compact_db(compact_db_path) })?
+        .await?;
+
     // Spawn compact database:
     let (compact_state, compact_db_handle, compact_db_client) =
         spawn_db(remote.compact_db.clone(), spawner, file_spawner.clone()).await?;
@@ -673,11 +913,35 @@ where
         Box::pin(create_local_node(
             node_name,
             node_private_key,
+            None,
             &self.store_path_buf,
             &self.file_spawner,
         ))
     }
 
+    fn import_local_node(
+        &mut self,
+        node_name: NodeName,
+        node_private_key: PrivateKey,
+        node_state: NodeState<NetAddress>,
+    ) -> BoxFuture<'_, Result<(), Self::Error>> {
+        Box::pin(async move {
+            let file_store_nodes =
+                read_all_nodes(self.store_path_buf.clone(), &self.file_spawner).await?;
+            if file_store_nodes.contains_key(&node_name) {
+                return Err(FileStoreError::DuplicateNodeName(node_name));
+            }
+            create_local_node(
+                node_name,
+                node_private_key,
+                Some(node_state),
+                &self.store_path_buf,
+                &self.file_spawner,
+            )
+            .await
+        })
+    }
+
     fn create_remote_node(
         &mut self,
         node_name: NodeName,
@@ -795,4 +1059,26 @@ where
             remove_node(&self.store_path_buf, &node_name, &self.file_spawner).await
         })
     }
+
+    /// Rename a node in the store
+    /// A node must be in unloaded state to be renamed, and the new name must not be taken.
+    fn rename_node(
+        &mut self,
+        old_node_name: NodeName,
+        new_node_name: NodeName,
+    ) -> BoxFuture<'_, Result<(), Self::Error>> {
+        Box::pin(async move {
+            // Do not rename node if it is currently loaded:
+            if self.live_nodes.contains_key(&old_node_name) {
+                return Err(FileStoreError::NodeIsLoaded);
+            }
+            rename_node(
+                &self.store_path_buf,
+                old_node_name,
+                new_node_name,
+                &self.file_spawner,
+            )
+            .await
+        })
+    }
 }
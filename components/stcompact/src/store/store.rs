@@ -69,6 +69,15 @@ pub trait Store {
         node_private_key: PrivateKey,
     ) -> BoxFuture<'_, Result<(), Self::Error>>;
 
+    /// Create a new local node from an exported node state (See `node::export_state()`).
+    /// The state must belong to the given private key, and the node name must not be taken.
+    fn import_local_node(
+        &mut self,
+        node_name: NodeName,
+        node_private_key: PrivateKey,
+        node_state: NodeState<NetAddress>,
+    ) -> BoxFuture<'_, Result<(), Self::Error>>;
+
     fn create_remote_node(
         &mut self,
         node_name: NodeName,
@@ -101,4 +110,12 @@ pub trait Store {
     /// Remove a node from the store
     /// A node must be in unloaded state to be removed.
     fn remove_node(&mut self, node_name: NodeName) -> BoxFuture<'_, Result<(), Self::Error>>;
+
+    /// Rename a node in the store
+    /// A node must be in unloaded state to be renamed, and the new name must not be taken.
+    fn rename_node(
+        &mut self,
+        old_node_name: NodeName,
+        new_node_name: NodeName,
+    ) -> BoxFuture<'_, Result<(), Self::Error>>;
 }
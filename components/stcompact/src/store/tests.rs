@@ -1,4 +1,5 @@
 use std::convert::TryFrom;
+use std::fs;
 
 use futures::executor::{block_on, ThreadPool};
 use futures::task::Spawn;
@@ -7,12 +8,20 @@ use crypto::identity::derive_public_key;
 use crypto::rand::RandGen;
 use crypto::test_utils::DummyRandom;
 
-use proto::crypto::PrivateKey;
+use common::mutable_state::MutableState;
+
+use proto::crypto::{PrivateKey, PublicKey};
+use proto::funder::messages::AddFriend;
 use proto::net::messages::NetAddress;
 
+use funder::FunderMutation;
+use node::{export_state, import_state, NodeMutation};
+
+use crate::compact_node::{CompactState, COMPACT_STATE_VERSION};
 use crate::messages::NodeName;
-use crate::store::file_store::open_file_store;
-use crate::store::store::{Store, StoredNodeConfig};
+use crate::store::consts::{COMPACT_DB, LOCAL, RESTORE_READY};
+use crate::store::file_store::{open_file_store, FileStoreError};
+use crate::store::store::{LoadedNode, Store, StoredNodeConfig};
 
 use tempfile::tempdir;
 
@@ -158,3 +167,491 @@ fn test_file_store() {
     let file_spawner = ThreadPool::new().unwrap();
     block_on(task_file_store(spawner, file_spawner))
 }
+
+async fn task_file_store_migrate_compact_state<S, FS>(spawner: S, file_spawner: FS)
+where
+    S: Spawn + Send + Sync,
+    FS: Spawn + Clone + Send + Sync + 'static,
+{
+    let store_dir = tempdir().unwrap();
+    let mut file_store = open_file_store(store_dir.path().into(), spawner, file_spawner)
+        .await
+        .unwrap();
+
+    let rng = DummyRandom::new(&[1u8]);
+    let node_private_key = PrivateKey::rand_gen(&rng);
+    file_store
+        .create_local_node(NodeName::new("node0".to_owned()), node_private_key)
+        .await
+        .unwrap();
+
+    // Overwrite the compact database with a state stored before versioning was introduced:
+    let compact_db_path = store_dir.path().join(LOCAL).join("node0").join(COMPACT_DB);
+    let old_state_string = r#"{"open_invoices": {}, "open_payments": {}, "generation": "0"}"#;
+    fs::write(&compact_db_path, old_state_string).unwrap();
+
+    let loaded_node = file_store
+        .load_node(NodeName::new("node0".to_owned()))
+        .await
+        .unwrap();
+    let compact_state = match &loaded_node {
+        LoadedNode::Local(loaded_node_local) => loaded_node_local.compact_state.clone(),
+        LoadedNode::Remote(_) => unreachable!(),
+    };
+    assert_eq!(compact_state, CompactState::new());
+    drop(loaded_node);
+    file_store
+        .unload_node(&NodeName::new("node0".to_owned()))
+        .await
+        .unwrap();
+
+    // The migrated state was written back:
+    let stored_state: CompactState =
+        serde_json::from_str(&fs::read_to_string(&compact_db_path).unwrap()).unwrap();
+    assert_eq!(stored_state.version, COMPACT_STATE_VERSION);
+}
+
+#[test]
+fn test_file_store_migrate_compact_state() {
+    let spawner = ThreadPool::new().unwrap();
+    let file_spawner = ThreadPool::new().unwrap();
+    block_on(task_file_store_migrate_compact_state(spawner, file_spawner))
+}
+
+async fn task_file_store_lock<S, FS>(spawner: S, file_spawner: FS)
+where
+    S: Spawn + Clone + Send + Sync,
+    FS: Spawn + Clone + Send + Sync + 'static,
+{
+    let store_dir = tempdir().unwrap();
+    let file_store = open_file_store(
+        store_dir.path().into(),
+        spawner.clone(),
+        file_spawner.clone(),
+    )
+    .await
+    .unwrap();
+
+    // The store is already locked:
+    match open_file_store(
+        store_dir.path().into(),
+        spawner.clone(),
+        file_spawner.clone(),
+    )
+    .await
+    {
+        Err(FileStoreError::LockError) => {}
+        _ => unreachable!(),
+    }
+
+    // After closing, the store can be opened again:
+    file_store.close();
+    let _file_store = open_file_store(store_dir.path().into(), spawner, file_spawner)
+        .await
+        .unwrap();
+}
+
+#[test]
+fn test_file_store_lock() {
+    let spawner = ThreadPool::new().unwrap();
+    let file_spawner = ThreadPool::new().unwrap();
+    block_on(task_file_store_lock(spawner, file_spawner))
+}
+
+async fn task_file_store_rename_node<S, FS>(spawner: S, file_spawner: FS)
+where
+    S: Spawn + Send + Sync,
+    FS: Spawn + Clone + Send + Sync + 'static,
+{
+    let store_dir = tempdir().unwrap();
+    let mut file_store = open_file_store(store_dir.path().into(), spawner, file_spawner)
+        .await
+        .unwrap();
+
+    let rng = DummyRandom::new(&[1u8]);
+    let node0_private_key = PrivateKey::rand_gen(&rng);
+    let node1_private_key = PrivateKey::rand_gen(&rng);
+
+    file_store
+        .create_local_node(NodeName::new("node0".to_owned()), node0_private_key)
+        .await
+        .unwrap();
+    file_store
+        .create_local_node(NodeName::new("node1".to_owned()), node1_private_key)
+        .await
+        .unwrap();
+
+    let stored_nodes = file_store.list_nodes().await.unwrap();
+    let stored_node0 = stored_nodes
+        .get(&NodeName::new("node0".to_owned()))
+        .unwrap()
+        .clone();
+
+    // The new name is already taken:
+    match file_store
+        .rename_node(
+            NodeName::new("node0".to_owned()),
+            NodeName::new("node1".to_owned()),
+        )
+        .await
+    {
+        Err(FileStoreError::DuplicateNodeName(_)) => {}
+        _ => unreachable!(),
+    }
+
+    file_store
+        .rename_node(
+            NodeName::new("node0".to_owned()),
+            NodeName::new("renamed0".to_owned()),
+        )
+        .await
+        .unwrap();
+
+    let stored_nodes = file_store.list_nodes().await.unwrap();
+    assert_eq!(stored_nodes.len(), 2);
+    assert!(stored_nodes
+        .get(&NodeName::new("node0".to_owned()))
+        .is_none());
+    assert_eq!(
+        stored_nodes
+            .get(&NodeName::new("renamed0".to_owned()))
+            .unwrap()
+            .info,
+        stored_node0.info
+    );
+
+    // A loaded node can not be renamed:
+    let loaded_node = file_store
+        .load_node(NodeName::new("renamed0".to_owned()))
+        .await
+        .unwrap();
+    match file_store
+        .rename_node(
+            NodeName::new("renamed0".to_owned()),
+            NodeName::new("node0".to_owned()),
+        )
+        .await
+    {
+        Err(FileStoreError::NodeIsLoaded) => {}
+        _ => unreachable!(),
+    }
+    drop(loaded_node);
+    file_store
+        .unload_node(&NodeName::new("renamed0".to_owned()))
+        .await
+        .unwrap();
+}
+
+#[test]
+fn test_file_store_rename_node() {
+    let spawner = ThreadPool::new().unwrap();
+    let file_spawner = ThreadPool::new().unwrap();
+    block_on(task_file_store_rename_node(spawner, file_spawner))
+}
+
+async fn task_file_store_import_local_node<S, FS>(spawner: S, file_spawner: FS)
+where
+    S: Spawn + Send + Sync + Clone,
+    FS: Spawn + Clone + Send + Sync + 'static,
+{
+    let store_dir0 = tempdir().unwrap();
+    let mut file_store0 = open_file_store(
+        store_dir0.path().into(),
+        spawner.clone(),
+        file_spawner.clone(),
+    )
+    .await
+    .unwrap();
+
+    let rng = DummyRandom::new(&[1u8]);
+    let node_private_key = PrivateKey::rand_gen(&rng);
+    let other_private_key = PrivateKey::rand_gen(&rng);
+
+    file_store0
+        .create_local_node(NodeName::new("node0".to_owned()), node_private_key.clone())
+        .await
+        .unwrap();
+
+    let mut node_state = match file_store0
+        .load_node(NodeName::new("node0".to_owned()))
+        .await
+        .unwrap()
+    {
+        LoadedNode::Local(loaded_node_local) => loaded_node_local.node_state,
+        LoadedNode::Remote(_) => unreachable!(),
+    };
+    file_store0
+        .unload_node(&NodeName::new("node0".to_owned()))
+        .await
+        .unwrap();
+
+    // Add a friend to the node:
+    let friend_public_key = PublicKey::from(&[0xbb; PublicKey::len()]);
+    let add_friend = AddFriend {
+        friend_public_key: friend_public_key.clone(),
+        relays: Vec::new(),
+        name: "friend".to_owned(),
+    };
+    node_state
+        .mutate(&NodeMutation::Funder(FunderMutation::AddFriend(add_friend)))
+        .unwrap();
+
+    let exported_state = export_state(&node_state);
+
+    // Import the node into a fresh store:
+    let store_dir1 = tempdir().unwrap();
+    let mut file_store1 = open_file_store(store_dir1.path().into(), spawner, file_spawner)
+        .await
+        .unwrap();
+    let imported_state = import_state::<NetAddress>(&exported_state).unwrap();
+
+    // The state must belong to the provided private key:
+    match file_store1
+        .import_local_node(
+            NodeName::new("node0".to_owned()),
+            other_private_key,
+            imported_state.clone(),
+        )
+        .await
+    {
+        Err(FileStoreError::NodeStateKeyMismatch) => {}
+        _ => unreachable!(),
+    }
+
+    file_store1
+        .import_local_node(
+            NodeName::new("node0".to_owned()),
+            node_private_key.clone(),
+            imported_state.clone(),
+        )
+        .await
+        .unwrap();
+
+    // The node name is already taken:
+    match file_store1
+        .import_local_node(
+            NodeName::new("node0".to_owned()),
+            node_private_key,
+            imported_state,
+        )
+        .await
+    {
+        Err(FileStoreError::DuplicateNodeName(_)) => {}
+        _ => unreachable!(),
+    }
+
+    let loaded_node_state = match file_store1
+        .load_node(NodeName::new("node0".to_owned()))
+        .await
+        .unwrap()
+    {
+        LoadedNode::Local(loaded_node_local) => loaded_node_local.node_state,
+        LoadedNode::Remote(_) => unreachable!(),
+    };
+
+    // The friend survived the migration:
+    let friend = loaded_node_state
+        .funder_state
+        .friends
+        .get(&friend_public_key)
+        .unwrap();
+    assert_eq!(friend.name, "friend");
+}
+
+#[test]
+fn test_file_store_import_local_node() {
+    let spawner = ThreadPool::new().unwrap();
+    let file_spawner = ThreadPool::new().unwrap();
+    block_on(task_file_store_import_local_node(spawner, file_spawner))
+}
+
+async fn task_file_store_backup_restore<S, FS>(spawner: S, file_spawner: FS)
+where
+    S: Spawn + Send + Sync,
+    FS: Spawn + Clone + Send + Sync + 'static,
+{
+    let store_dir = tempdir().unwrap();
+    let backup_dir = tempdir().unwrap();
+    let backup_path = backup_dir.path().join("backup");
+
+    let mut file_store = open_file_store(store_dir.path().into(), spawner, file_spawner)
+        .await
+        .unwrap();
+
+    let rng = DummyRandom::new(&[1u8]);
+    let node0_private_key = PrivateKey::rand_gen(&rng);
+    let app_private_key = PrivateKey::rand_gen(&rng);
+    let node1_public_key = derive_public_key(&PrivateKey::rand_gen(&rng)).unwrap();
+    let node1_address = NetAddress::try_from("node1_address".to_owned()).unwrap();
+
+    file_store
+        .create_local_node(NodeName::new("node0".to_owned()), node0_private_key)
+        .await
+        .unwrap();
+    file_store
+        .create_remote_node(
+            NodeName::new("node1".to_owned()),
+            app_private_key,
+            node1_public_key,
+            node1_address,
+        )
+        .await
+        .unwrap();
+
+    let orig_stored_nodes = file_store.list_nodes().await.unwrap();
+    assert_eq!(orig_stored_nodes.len(), 2);
+
+    // A backup can not be made while a node is loaded:
+    let loaded_node = file_store
+        .load_node(NodeName::new("node0".to_owned()))
+        .await
+        .unwrap();
+    match file_store.backup(&backup_path).await {
+        Err(FileStoreError::NodeIsLoaded) => {}
+        _ => unreachable!(),
+    }
+    drop(loaded_node);
+    file_store
+        .unload_node(&NodeName::new("node0".to_owned()))
+        .await
+        .unwrap();
+
+    file_store.backup(&backup_path).await.unwrap();
+
+    // An existing backup is never overwritten:
+    assert!(file_store.backup(&backup_path).await.is_err());
+
+    // Mutate the store:
+    file_store
+        .remove_node(NodeName::new("node0".to_owned()))
+        .await
+        .unwrap();
+    file_store
+        .config_node(
+            NodeName::new("node1".to_owned()),
+            StoredNodeConfig { is_enabled: true },
+        )
+        .await
+        .unwrap();
+    file_store
+        .create_local_node(
+            NodeName::new("node2".to_owned()),
+            PrivateKey::rand_gen(&rng),
+        )
+        .await
+        .unwrap();
+
+    let stored_nodes = file_store.list_nodes().await.unwrap();
+    assert_eq!(stored_nodes.len(), 2);
+    assert!(stored_nodes
+        .get(&NodeName::new("node0".to_owned()))
+        .is_none());
+
+    file_store.restore(&backup_path).await.unwrap();
+
+    // The original state returns:
+    let stored_nodes = file_store.list_nodes().await.unwrap();
+    assert_eq!(stored_nodes.len(), orig_stored_nodes.len());
+    for (node_name, orig_stored_node) in &orig_stored_nodes {
+        let stored_node = stored_nodes.get(node_name).unwrap();
+        assert_eq!(stored_node.info, orig_stored_node.info);
+        assert_eq!(stored_node.config, orig_stored_node.config);
+    }
+
+    // The restored nodes can be loaded:
+    let loaded_node = file_store
+        .load_node(NodeName::new("node0".to_owned()))
+        .await
+        .unwrap();
+    drop(loaded_node);
+    file_store
+        .unload_node(&NodeName::new("node0".to_owned()))
+        .await
+        .unwrap();
+}
+
+#[test]
+fn test_file_store_backup_restore() {
+    let spawner = ThreadPool::new().unwrap();
+    let file_spawner = ThreadPool::new().unwrap();
+    block_on(task_file_store_backup_restore(spawner, file_spawner))
+}
+
+async fn task_file_store_interrupted_restore<S, FS>(spawner: S, file_spawner: FS)
+where
+    S: Spawn + Clone + Send + Sync,
+    FS: Spawn + Clone + Send + Sync + 'static,
+{
+    let temp_dir = tempdir().unwrap();
+    let store_path = temp_dir.path().join("store");
+    let backup_path = temp_dir.path().join("backup");
+
+    let mut file_store = open_file_store(store_path.clone(), spawner.clone(), file_spawner.clone())
+        .await
+        .unwrap();
+
+    let rng = DummyRandom::new(&[1u8]);
+    file_store
+        .create_local_node(
+            NodeName::new("node0".to_owned()),
+            PrivateKey::rand_gen(&rng),
+        )
+        .await
+        .unwrap();
+    file_store.backup(&backup_path).await.unwrap();
+    file_store
+        .remove_node(NodeName::new("node0".to_owned()))
+        .await
+        .unwrap();
+    file_store
+        .create_local_node(
+            NodeName::new("node1".to_owned()),
+            PrivateKey::rand_gen(&rng),
+        )
+        .await
+        .unwrap();
+    file_store.close();
+
+    // Interrupted while copying the backup. The restore is rolled back:
+    fs::create_dir(store_path.join(format!("{}.restore", LOCAL))).unwrap();
+    let file_store = open_file_store(store_path.clone(), spawner.clone(), file_spawner.clone())
+        .await
+        .unwrap();
+    let stored_nodes = file_store.list_nodes().await.unwrap();
+    assert_eq!(stored_nodes.len(), 1);
+    assert!(stored_nodes
+        .get(&NodeName::new("node1".to_owned()))
+        .is_some());
+    assert!(!store_path.join(format!("{}.restore", LOCAL)).exists());
+    file_store.close();
+
+    // Interrupted while swapping in the copied backup. The restore is completed:
+    fs::rename(
+        store_path.join(LOCAL),
+        store_path.join(format!("{}.old", LOCAL)),
+    )
+    .unwrap();
+    fs::rename(
+        backup_path.join(LOCAL),
+        store_path.join(format!("{}.restore", LOCAL)),
+    )
+    .unwrap();
+    fs::File::create(store_path.join(RESTORE_READY)).unwrap();
+    let file_store = open_file_store(store_path.clone(), spawner, file_spawner)
+        .await
+        .unwrap();
+    let stored_nodes = file_store.list_nodes().await.unwrap();
+    assert_eq!(stored_nodes.len(), 1);
+    assert!(stored_nodes
+        .get(&NodeName::new("node0".to_owned()))
+        .is_some());
+    assert!(!store_path.join(format!("{}.old", LOCAL)).exists());
+    assert!(!store_path.join(RESTORE_READY).exists());
+}
+
+#[test]
+fn test_file_store_interrupted_restore() {
+    let spawner = ThreadPool::new().unwrap();
+    let file_spawner = ThreadPool::new().unwrap();
+    block_on(task_file_store_interrupted_restore(spawner, file_spawner))
+}
@@ -10,19 +10,33 @@
 #[macro_use]
 extern crate log;
 
+use std::env;
 use std::io;
 use structopt::StructOpt;
 
+use stctrl::serve::stctrl_via;
 use stctrl::stctrllib::{stctrl, StCtrlCmd, StCtrlError};
 
 fn run() -> Result<(), StCtrlError> {
     env_logger::init();
+
     let st_ctrl_cmd = StCtrlCmd::from_args();
+
+    // Run the command through a serving stctrl instance.
+    // The serving instance parses the same command line:
+    if let Some(socket) = &st_ctrl_cmd.via {
+        if st_ctrl_cmd.subcommand.requires_connection() {
+            let args = env::args().collect();
+            return stctrl_via(socket, args, &mut io::stdout()).map_err(StCtrlError::ServeError);
+        }
+    }
+
     stctrl(st_ctrl_cmd, &mut io::stdout())
 }
 
 fn main() {
     if let Err(e) = run() {
         error!("error: {:?}", e);
+        std::process::exit(e.exit_code());
     }
 }
@@ -1,7 +1,7 @@
 use std::collections::HashSet;
 use std::fs::{self, File};
 use std::io::{self, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use derive_more::From;
 
@@ -21,6 +21,9 @@ use app::report::NodeReport;
 use app::ser_utils::{deserialize_from_string, serialize_to_string, StringSerdeError};
 
 use crate::file::{CommitFile, InvoiceFile, PaymentFile, ReceiptFile};
+use crate::stctrllib::{
+    EXIT_CONNECTION_LOST, EXIT_FAILURE, EXIT_PAYMENT_CANCELED, EXIT_ROUTE_NOT_FOUND,
+};
 
 use route::choose_multi_route;
 
@@ -33,8 +36,13 @@ pub struct PayInvoiceCmd {
     /// Output payment file (Used to track the payment)
     #[structopt(parse(from_os_str), short = "p", long = "payment")]
     pub payment_path: PathBuf,
-    /// Output commit file
-    #[structopt(parse(from_os_str), short = "c", long = "commit")]
+    /// Output commit file (Written once the payment completes)
+    #[structopt(
+        parse(from_os_str),
+        short = "c",
+        long = "commit",
+        raw(alias = r#""commit-output""#)
+    )]
     pub commit_path: PathBuf,
 }
 
@@ -66,8 +74,12 @@ pub enum BuyerError {
     NoRoutesPermissions,
     InvalidDestination,
     ParseAmountError,
-    AppRoutesError,
     SendBuyerError,
+    /// The node could not find any route to the destination
+    RouteNotFound,
+    /// The node is not connected to any index server, so it can not search for routes
+    NoIndexServer,
+    /// Routes were found, but none of them can carry the requested amount
     NoSuitableRoute,
     CommitFileAlreadyExists,
     ReceiptFileAlreadyExists,
@@ -76,20 +88,34 @@ pub enum BuyerError {
     ReceiptAckError,
     LoadInvoiceError,
     WriteError,
-    CreatePaymentFailed,
     CreateTransactionFailed,
+    /// The payment will not complete: its transactions were canceled
+    PaymentCanceled,
+    /// Connection to the node was lost before we got a response
+    ConnectionLost,
     StoreCommitError,
-    RequestClosePaymentError,
-    AckClosePaymentError,
     ParsePaymentIdError,
     StorePaymentError,
     LoadPaymentError,
     RemovePaymentError,
-    PaymentIncomplete,
     IoError(std::io::Error),
     StringSerdeError(StringSerdeError),
 }
 
+impl BuyerError {
+    /// Process exit code that corresponds to this error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            BuyerError::RouteNotFound | BuyerError::NoSuitableRoute | BuyerError::NoIndexServer => {
+                EXIT_ROUTE_NOT_FOUND
+            }
+            BuyerError::PaymentCanceled => EXIT_PAYMENT_CANCELED,
+            BuyerError::ConnectionLost => EXIT_CONNECTION_LOST,
+            _ => EXIT_FAILURE,
+        }
+    }
+}
+
 async fn request_routes(
     conn_pair: &mut ConnPairApp,
     currency: Currency,
@@ -106,6 +132,7 @@ async fn request_routes(
         src_public_key,
         dest_public_key,
         opt_exclude,
+        None,
     );
 
     // Note: We never use the randomly generated `app_request_id` later.
@@ -119,19 +146,21 @@ async fn request_routes(
         .sender
         .send(app_to_app_server)
         .await
-        .map_err(|_| BuyerError::AppRoutesError)?;
+        .map_err(|_| BuyerError::ConnectionLost)?;
 
     // Wait until we get back response routes:
     while let Some(app_server_to_app) = conn_pair.receiver.next().await {
         if let AppServerToApp::ResponseRoutes(client_response_routes) = app_server_to_app {
             if client_response_routes.request_id == request_routes_id {
-                if let ResponseRoutesResult::Success(multi_routes) = client_response_routes.result {
-                    return Ok(multi_routes);
-                }
+                return match client_response_routes.result {
+                    ResponseRoutesResult::Success(multi_routes) => Ok(multi_routes),
+                    ResponseRoutesResult::Failure => Err(BuyerError::RouteNotFound),
+                    ResponseRoutesResult::NoIndexServer => Err(BuyerError::NoIndexServer),
+                };
             }
         }
     }
-    Err(BuyerError::AppRoutesError)
+    Err(BuyerError::ConnectionLost)
 }
 
 async fn create_payment(
@@ -160,7 +189,7 @@ async fn create_payment(
         .sender
         .send(app_to_app_server)
         .await
-        .map_err(|_| BuyerError::CreatePaymentFailed)?;
+        .map_err(|_| BuyerError::ConnectionLost)?;
 
     while let Some(app_server_to_app) = conn_pair.receiver.next().await {
         if let AppServerToApp::ReportMutations(report_mutations) = app_server_to_app {
@@ -172,7 +201,7 @@ async fn create_payment(
         }
     }
 
-    Err(BuyerError::CreatePaymentFailed)
+    Err(BuyerError::ConnectionLost)
 }
 
 /// Request to close payment, but do not wait for the payment to be closed.
@@ -193,7 +222,7 @@ async fn request_close_payment_nowait(
         .sender
         .send(app_to_app_server)
         .await
-        .map_err(|_| BuyerError::RequestClosePaymentError)?;
+        .map_err(|_| BuyerError::ConnectionLost)?;
 
     while let Some(app_server_to_app) = conn_pair.receiver.next().await {
         if let AppServerToApp::ReportMutations(report_mutations) = app_server_to_app {
@@ -205,7 +234,7 @@ async fn request_close_payment_nowait(
         }
     }
 
-    Err(BuyerError::RequestClosePaymentError)
+    Err(BuyerError::ConnectionLost)
 }
 
 /// Request to close the payment, and wait for the payment to be closed.
@@ -226,7 +255,7 @@ async fn request_close_payment(
         .sender
         .send(app_to_app_server)
         .await
-        .map_err(|_| BuyerError::RequestClosePaymentError)?;
+        .map_err(|_| BuyerError::ConnectionLost)?;
 
     while let Some(app_server_to_app) = conn_pair.receiver.next().await {
         if let AppServerToApp::ResponseClosePayment(response_close_payment) = app_server_to_app {
@@ -236,7 +265,7 @@ async fn request_close_payment(
         }
     }
 
-    Err(BuyerError::RequestClosePaymentError)
+    Err(BuyerError::ConnectionLost)
 }
 
 /// Request to close payment, but do not wait for the payment to be closed.
@@ -258,7 +287,7 @@ async fn ack_close_payment(
         .sender
         .send(app_to_app_server)
         .await
-        .map_err(|_| BuyerError::AckClosePaymentError)?;
+        .map_err(|_| BuyerError::ConnectionLost)?;
 
     while let Some(app_server_to_app) = conn_pair.receiver.next().await {
         if let AppServerToApp::ReportMutations(report_mutations) = app_server_to_app {
@@ -270,7 +299,7 @@ async fn ack_close_payment(
         }
     }
 
-    Err(BuyerError::AckClosePaymentError)
+    Err(BuyerError::ConnectionLost)
 }
 
 /// Pay an invoice
@@ -306,10 +335,9 @@ async fn buyer_pay_invoice(
         invoice_file.dest_payment,
         local_public_key, // source
         invoice_file.dest_public_key.clone(),
-        None,
+        None, // No exclusion of edges
     )
-    .await // No exclusion of edges
-    .map_err(|_| BuyerError::AppRoutesError)?;
+    .await?;
 
     let (route_index, multi_route_choice) =
         choose_multi_route(&multi_routes, invoice_file.dest_payment)
@@ -374,12 +402,27 @@ async fn buyer_pay_invoice(
             .sender
             .send(app_to_app_server)
             .await
-            .map_err(|_| BuyerError::CreateTransactionFailed)?;
+            .map_err(|_| BuyerError::ConnectionLost)?;
     }
 
     // Signal that no new transactions will be created:
     request_close_payment_nowait(&mut conn_pair, payment_id.clone()).await?;
 
+    wait_store_commit(&mut conn_pair, requests, &commit_path).await?;
+
+    writeln!(writer, "Payment successful!").map_err(|_| BuyerError::WriteError)?;
+
+    Ok(())
+}
+
+/// Wait for the results of the transactions in `requests`.
+/// Once a transaction completes, its Commit is stored to `commit_path`.
+/// Nothing is stored if any of the transactions fails.
+async fn wait_store_commit(
+    conn_pair: &mut ConnPairApp,
+    mut requests: HashSet<Uid>,
+    commit_path: &Path,
+) -> Result<(), BuyerError> {
     // Wait for all incoming transaction responses:
     let mut opt_commit = None;
     while let Some(app_server_to_app) = conn_pair.receiver.next().await {
@@ -396,19 +439,14 @@ async fn buyer_pay_invoice(
                     break;
                 }
                 RequestResult::Success => {}
-                RequestResult::Failure => return Err(BuyerError::CreateTransactionFailed),
+                RequestResult::Failure(_) => return Err(BuyerError::PaymentCanceled),
             }
         }
     }
 
-    // We expect that some transaction returned with "Complete" signal:
-    let commit = if let Some(commit) = opt_commit {
-        commit
-    } else {
-        return Err(BuyerError::PaymentIncomplete);
-    };
-
-    writeln!(writer, "Payment successful!").map_err(|_| BuyerError::WriteError)?;
+    // We expect that some transaction returned with "Complete" signal.
+    // Otherwise, the connection was closed before the payment was complete:
+    let commit = opt_commit.ok_or(BuyerError::ConnectionLost)?;
 
     let commit_file = CommitFile::from(commit);
 
@@ -441,6 +479,7 @@ async fn buyer_payment_status(
 
     let payment_status = request_close_payment(&mut conn_pair, payment_id.clone()).await?;
 
+    let mut is_canceled = false;
     let opt_ack_uid = match payment_status {
         PaymentStatus::PaymentNotFound => {
             writeln!(writer, "Payment could not be found").map_err(|_| BuyerError::WriteError)?;
@@ -461,6 +500,7 @@ async fn buyer_payment_status(
         }
         PaymentStatus::Canceled(ack_uid) => {
             writeln!(writer, "Payment was canceled.").map_err(|_| BuyerError::WriteError)?;
+            is_canceled = true;
 
             Some(ack_uid)
         }
@@ -473,6 +513,12 @@ async fn buyer_payment_status(
         fs::remove_file(&payment_path).map_err(|_| BuyerError::RemovePaymentError)?;
     }
 
+    // Report a canceled payment to the caller (Allows scripts to tell it apart using the exit
+    // code):
+    if is_canceled {
+        return Err(BuyerError::PaymentCanceled);
+    }
+
     Ok(())
 }
 
@@ -504,3 +550,186 @@ pub async fn buyer(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryFrom;
+
+    use futures::channel::mpsc;
+    use futures::executor::block_on;
+    use futures::future;
+
+    use tempfile::tempdir;
+
+    use app::common::{Commit, HashResult, HashedLock, PlainLock, Signature};
+    use app::conn::{AppRequest, ClientResponseRoutes, TransactionResult};
+
+    use crate::stctrllib::StCtrlError;
+
+    #[test]
+    fn test_buyer_error_exit_codes() {
+        assert_eq!(
+            BuyerError::PaymentCanceled.exit_code(),
+            EXIT_PAYMENT_CANCELED
+        );
+        assert_eq!(BuyerError::RouteNotFound.exit_code(), EXIT_ROUTE_NOT_FOUND);
+        assert_eq!(
+            BuyerError::NoSuitableRoute.exit_code(),
+            EXIT_ROUTE_NOT_FOUND
+        );
+        assert_eq!(BuyerError::ConnectionLost.exit_code(), EXIT_CONNECTION_LOST);
+        assert_eq!(BuyerError::WriteError.exit_code(), EXIT_FAILURE);
+
+        // Exit codes are preserved when wrapped by `StCtrlError`:
+        assert_eq!(
+            StCtrlError::BuyerError(BuyerError::PaymentCanceled).exit_code(),
+            EXIT_PAYMENT_CANCELED
+        );
+    }
+
+    #[test]
+    fn test_request_routes_not_found() {
+        let (app_sender, mut node_receiver) = mpsc::channel(0);
+        let (mut node_sender, app_receiver) = mpsc::channel(0);
+        let mut conn_pair = ConnPairApp::from_raw(app_sender, app_receiver);
+
+        let fut_routes = request_routes(
+            &mut conn_pair,
+            Currency::try_from("FST".to_owned()).unwrap(),
+            10,
+            PublicKey::from(&[0xaa; PublicKey::len()]),
+            PublicKey::from(&[0xbb; PublicKey::len()]),
+            None,
+        );
+
+        let fut_node = async move {
+            let app_to_app_server: AppToAppServer = node_receiver.next().await.unwrap();
+            let request_id = match app_to_app_server.app_request {
+                AppRequest::RequestRoutes(request_routes) => request_routes.request_id,
+                _ => unreachable!(),
+            };
+            node_sender
+                .send(AppServerToApp::ResponseRoutes(ClientResponseRoutes {
+                    request_id,
+                    result: ResponseRoutesResult::Failure,
+                }))
+                .await
+                .unwrap();
+        };
+
+        let (res, ()) = block_on(future::join(fut_routes, fut_node));
+        match res {
+            Err(BuyerError::RouteNotFound) => {}
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_request_close_payment_connection_lost() {
+        // The node side of the connection is closed:
+        let (app_sender, _) = mpsc::channel(0);
+        let (_, app_receiver) = mpsc::channel(0);
+        let mut conn_pair = ConnPairApp::from_raw(app_sender, app_receiver);
+
+        let res = block_on(request_close_payment(
+            &mut conn_pair,
+            PaymentId::from(&[0x11; PaymentId::len()]),
+        ));
+        match res {
+            Err(e @ BuyerError::ConnectionLost) => assert_eq!(e.exit_code(), EXIT_CONNECTION_LOST),
+            _ => unreachable!(),
+        }
+    }
+
+    fn dummy_commit() -> Commit {
+        Commit {
+            response_hash: HashResult::from(&[0x01; HashResult::len()]),
+            src_plain_lock: PlainLock::from(&[0x02; PlainLock::len()]),
+            dest_hashed_lock: HashedLock::from(&[0x03; HashedLock::len()]),
+            dest_payment: 10,
+            total_dest_payment: 30,
+            invoice_id: InvoiceId::from(&[0x04; InvoiceId::len()]),
+            currency: Currency::try_from("FST".to_owned()).unwrap(),
+            signature: Signature::from(&[0x05; Signature::len()]),
+        }
+    }
+
+    fn transaction_result(request_id: Uid, result: RequestResult) -> AppServerToApp {
+        AppServerToApp::TransactionResult(TransactionResult { request_id, result })
+    }
+
+    #[test]
+    fn test_wait_store_commit_complete() {
+        let dir = tempdir().unwrap();
+        let commit_path = dir.path().join("test.commit");
+
+        let (app_sender, _node_receiver) = mpsc::channel(0);
+        let (mut node_sender, app_receiver) = mpsc::channel(0);
+        let mut conn_pair = ConnPairApp::from_raw(app_sender, app_receiver);
+
+        let request_id0 = Uid::from(&[0; Uid::len()]);
+        let request_id1 = Uid::from(&[1; Uid::len()]);
+        let requests = vec![request_id0.clone(), request_id1.clone()]
+            .into_iter()
+            .collect();
+
+        let fut_node = async move {
+            node_sender
+                .send(transaction_result(request_id0, RequestResult::Success))
+                .await
+                .unwrap();
+            node_sender
+                .send(transaction_result(
+                    request_id1,
+                    RequestResult::Complete(dummy_commit()),
+                ))
+                .await
+                .unwrap();
+        };
+
+        let (res, ()) = block_on(future::join(
+            wait_store_commit(&mut conn_pair, requests, &commit_path),
+            fut_node,
+        ));
+        res.unwrap();
+
+        // The stored commit file round-trips to the original commit:
+        let commit_file: CommitFile =
+            deserialize_from_string(&fs::read_to_string(&commit_path).unwrap()).unwrap();
+        assert_eq!(Commit::from(commit_file), dummy_commit());
+    }
+
+    #[test]
+    fn test_wait_store_commit_failure() {
+        let dir = tempdir().unwrap();
+        let commit_path = dir.path().join("test.commit");
+
+        let (app_sender, _node_receiver) = mpsc::channel(0);
+        let (mut node_sender, app_receiver) = mpsc::channel(0);
+        let mut conn_pair = ConnPairApp::from_raw(app_sender, app_receiver);
+
+        let request_id = Uid::from(&[0; Uid::len()]);
+        let requests = vec![request_id.clone()].into_iter().collect();
+
+        let fut_node = async move {
+            node_sender
+                .send(transaction_result(request_id, RequestResult::Failure(None)))
+                .await
+                .unwrap();
+        };
+
+        let (res, ()) = block_on(future::join(
+            wait_store_commit(&mut conn_pair, requests, &commit_path),
+            fut_node,
+        ));
+        match res {
+            Err(e @ BuyerError::PaymentCanceled) => {
+                assert_eq!(e.exit_code(), EXIT_PAYMENT_CANCELED)
+            }
+            _ => unreachable!(),
+        }
+        assert!(!commit_path.exists());
+    }
+}
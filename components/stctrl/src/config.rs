@@ -1,5 +1,6 @@
 use std::convert::TryFrom;
 use std::fs;
+use std::io::{self, Write};
 use std::path::PathBuf;
 
 use futures::sink::SinkExt;
@@ -10,14 +11,15 @@ use structopt::StructOpt;
 use derive_more::From;
 
 use app::common::{Currency, NamedIndexServerAddress, NamedRelayAddress, Rate, RelayAddress};
+use app::conn::config::ResetFriendChannelError;
 use app::conn::{self, AppRequest, AppServerToApp, AppToAppServer, ConnPairApp};
 use app::gen::gen_uid;
-use app::report::{ChannelStatusReport, NodeReport};
+use app::report::NodeReport;
 
 use app::file::{FriendFile, IndexServerFile, RelayAddressFile};
 use app::ser_utils::{deserialize_from_string, StringSerdeError};
 
-use crate::utils::friend_public_key_by_name;
+use crate::utils::{friend_public_key_by_name, FriendNameError};
 
 /// Add a relay
 #[derive(Clone, Debug, StructOpt)]
@@ -103,6 +105,14 @@ pub struct DisableFriendCmd {
     pub friend_name: String,
 }
 
+/// Pause friend
+#[derive(Clone, Debug, StructOpt)]
+pub struct PauseFriendCmd {
+    /// Friend name to pause
+    #[structopt(long = "name", short = "n")]
+    pub friend_name: String,
+}
+
 /// Enable forwarding of payment requests from friend to us
 #[derive(Clone, Debug, StructOpt)]
 pub struct OpenFriendCurrencyCmd {
@@ -183,7 +193,16 @@ pub struct ResetFriendCmd {
 }
 
 #[derive(Clone, Debug, StructOpt)]
-pub enum ConfigCmd {
+pub struct ConfigCmd {
+    /// Print the request that would be sent to the node, without sending it
+    #[structopt(long = "dry-run")]
+    pub dry_run: bool,
+    #[structopt(subcommand)]
+    pub subcommand: ConfigSubcommand,
+}
+
+#[derive(Clone, Debug, StructOpt)]
+pub enum ConfigSubcommand {
     /// Add a relay server
     #[structopt(name = "add-relay")]
     AddRelay(AddRelayCmd),
@@ -211,6 +230,9 @@ pub enum ConfigCmd {
     /// Disable a friend
     #[structopt(name = "disable-friend")]
     DisableFriend(DisableFriendCmd),
+    /// Pause a friend: Stop new requests, but let in-flight transactions complete
+    #[structopt(name = "pause-friend")]
+    PauseFriend(PauseFriendCmd),
     /// Open requests from friend
     #[structopt(name = "open-currency")]
     OpenFriendCurrency(OpenFriendCurrencyCmd),
@@ -251,6 +273,8 @@ pub enum ConfigError {
     LoadFriendFromFileError,
     FriendPublicKeyMismatch,
     FriendNameNotFound,
+    /// More than one friend has the given name
+    AmbiguousFriendName,
     ParseMaxDebtError,
     ChannelNotInconsistent,
     UnknownRemoteResetTerms,
@@ -259,6 +283,15 @@ pub enum ConfigError {
     InvalidCurrencyName,
 }
 
+impl From<FriendNameError> for ConfigError {
+    fn from(e: FriendNameError) -> Self {
+        match e {
+            FriendNameError::NotFound => ConfigError::FriendNameNotFound,
+            FriendNameError::Ambiguous => ConfigError::AmbiguousFriendName,
+        }
+    }
+}
+
 async fn config_request(
     conn_pair: &mut ConnPairApp,
     app_request: AppRequest,
@@ -288,11 +321,10 @@ async fn config_request(
     Err(ConfigError::AppConfigError)
 }
 
-async fn config_add_relay(
+fn add_relay_request(
     add_relay_cmd: AddRelayCmd,
-    mut conn_pair: ConnPairApp,
     node_report: &NodeReport,
-) -> Result<(), ConfigError> {
+) -> Result<AppRequest, ConfigError> {
     for named_relay_address in &node_report.funder_report.relays {
         if named_relay_address.name == add_relay_cmd.relay_name {
             return Err(ConfigError::RelayNameAlreadyExists);
@@ -313,14 +345,13 @@ async fn config_add_relay(
     };
 
     let app_request = conn::config::add_relay(named_relay_address);
-    config_request(&mut conn_pair, app_request).await
+    Ok(app_request)
 }
 
-async fn config_remove_relay(
+fn remove_relay_request(
     remove_relay_cmd: RemoveRelayCmd,
-    mut conn_pair: ConnPairApp,
     node_report: &NodeReport,
-) -> Result<(), ConfigError> {
+) -> Result<AppRequest, ConfigError> {
     let mut opt_relay_public_key = None;
     for named_relay_address in &node_report.funder_report.relays {
         if named_relay_address.name == remove_relay_cmd.relay_name {
@@ -331,14 +362,13 @@ async fn config_remove_relay(
     let relay_public_key = opt_relay_public_key.ok_or(ConfigError::RelayNameNotFound)?;
 
     let app_request = conn::config::remove_relay(relay_public_key);
-    config_request(&mut conn_pair, app_request).await
+    Ok(app_request)
 }
 
-async fn config_add_index(
+fn add_index_request(
     add_index_cmd: AddIndexCmd,
-    mut conn_pair: ConnPairApp,
     node_report: &NodeReport,
-) -> Result<(), ConfigError> {
+) -> Result<AppRequest, ConfigError> {
     let AddIndexCmd {
         index_path,
         index_name,
@@ -364,14 +394,13 @@ async fn config_add_index(
     };
 
     let app_request = conn::config::add_index_server(named_index_server_address);
-    config_request(&mut conn_pair, app_request).await
+    Ok(app_request)
 }
 
-async fn config_remove_index(
+fn remove_index_request(
     remove_index_cmd: RemoveIndexCmd,
-    mut conn_pair: ConnPairApp,
     node_report: &NodeReport,
-) -> Result<(), ConfigError> {
+) -> Result<AppRequest, ConfigError> {
     let mut opt_index_public_key = None;
     for named_index_server_address in &node_report.index_client_report.index_servers {
         if named_index_server_address.name == remove_index_cmd.index_name {
@@ -382,14 +411,13 @@ async fn config_remove_index(
     let index_public_key = opt_index_public_key.ok_or(ConfigError::RelayNameNotFound)?;
 
     let app_request = conn::config::remove_index_server(index_public_key);
-    config_request(&mut conn_pair, app_request).await
+    Ok(app_request)
 }
 
-async fn config_add_friend(
+fn add_friend_request(
     add_friend_cmd: AddFriendCmd,
-    mut conn_pair: ConnPairApp,
     node_report: &NodeReport,
-) -> Result<(), ConfigError> {
+) -> Result<AppRequest, ConfigError> {
     let AddFriendCmd {
         friend_path,
         friend_name,
@@ -416,22 +444,19 @@ async fn config_add_friend(
             .collect(),
         friend_name.to_owned(),
     );
-    config_request(&mut conn_pair, app_request).await
+    Ok(app_request)
 }
 
-async fn config_set_friend_relays(
+fn set_friend_relays_request(
     set_friend_relays_cmd: SetFriendRelaysCmd,
-    mut conn_pair: ConnPairApp,
     node_report: &NodeReport,
-) -> Result<(), ConfigError> {
+) -> Result<AppRequest, ConfigError> {
     let SetFriendRelaysCmd {
         friend_path,
         friend_name,
     } = set_friend_relays_cmd;
 
-    let friend_public_key = friend_public_key_by_name(&node_report, &friend_name)
-        .ok_or(ConfigError::FriendNameNotFound)?
-        .clone();
+    let friend_public_key = friend_public_key_by_name(&node_report, &friend_name)?.clone();
 
     if !friend_path.exists() {
         return Err(ConfigError::FriendFileNotFound);
@@ -453,111 +478,105 @@ async fn config_set_friend_relays(
             .map(RelayAddress::from)
             .collect(),
     );
-    config_request(&mut conn_pair, app_request).await
+    Ok(app_request)
 }
 
-async fn config_remove_friend(
+fn remove_friend_request(
     remove_friend_cmd: RemoveFriendCmd,
-    mut conn_pair: ConnPairApp,
     node_report: &NodeReport,
-) -> Result<(), ConfigError> {
-    let friend_public_key = friend_public_key_by_name(&node_report, &remove_friend_cmd.friend_name)
-        .ok_or(ConfigError::FriendNameNotFound)?
-        .clone();
+) -> Result<AppRequest, ConfigError> {
+    let friend_public_key =
+        friend_public_key_by_name(&node_report, &remove_friend_cmd.friend_name)?.clone();
 
     let app_request = conn::config::remove_friend(friend_public_key);
-    config_request(&mut conn_pair, app_request).await
+    Ok(app_request)
 }
 
-async fn config_enable_friend(
+fn enable_friend_request(
     enable_friend_cmd: EnableFriendCmd,
-    mut conn_pair: ConnPairApp,
     node_report: &NodeReport,
-) -> Result<(), ConfigError> {
-    let friend_public_key = friend_public_key_by_name(&node_report, &enable_friend_cmd.friend_name)
-        .ok_or(ConfigError::FriendNameNotFound)?
-        .clone();
+) -> Result<AppRequest, ConfigError> {
+    let friend_public_key =
+        friend_public_key_by_name(&node_report, &enable_friend_cmd.friend_name)?.clone();
 
     let app_request = conn::config::enable_friend(friend_public_key);
-    config_request(&mut conn_pair, app_request).await
+    Ok(app_request)
 }
 
-async fn config_disable_friend(
+fn disable_friend_request(
     disable_friend_cmd: DisableFriendCmd,
-    mut conn_pair: ConnPairApp,
     node_report: &NodeReport,
-) -> Result<(), ConfigError> {
+) -> Result<AppRequest, ConfigError> {
     let friend_public_key =
-        friend_public_key_by_name(&node_report, &disable_friend_cmd.friend_name)
-            .ok_or(ConfigError::FriendNameNotFound)?
-            .clone();
+        friend_public_key_by_name(&node_report, &disable_friend_cmd.friend_name)?.clone();
 
     let app_request = conn::config::disable_friend(friend_public_key);
-    config_request(&mut conn_pair, app_request).await
+    Ok(app_request)
+}
+
+fn pause_friend_request(
+    pause_friend_cmd: PauseFriendCmd,
+    node_report: &NodeReport,
+) -> Result<AppRequest, ConfigError> {
+    let friend_public_key =
+        friend_public_key_by_name(&node_report, &pause_friend_cmd.friend_name)?.clone();
+
+    let app_request = conn::config::pause_friend(friend_public_key);
+    Ok(app_request)
 }
 
-async fn config_open_friend_currency(
+fn open_friend_currency_request(
     open_friend_currency_cmd: OpenFriendCurrencyCmd,
-    mut conn_pair: ConnPairApp,
     node_report: &NodeReport,
-) -> Result<(), ConfigError> {
+) -> Result<AppRequest, ConfigError> {
     let friend_public_key =
-        friend_public_key_by_name(&node_report, &open_friend_currency_cmd.friend_name)
-            .ok_or(ConfigError::FriendNameNotFound)?
-            .clone();
+        friend_public_key_by_name(&node_report, &open_friend_currency_cmd.friend_name)?.clone();
 
     let currency = Currency::try_from(open_friend_currency_cmd.currency_name)
         .map_err(|_| ConfigError::InvalidCurrencyName)?;
 
     let app_request = conn::config::open_friend_currency(friend_public_key, currency);
-    config_request(&mut conn_pair, app_request).await
+    Ok(app_request)
 }
 
-async fn config_close_friend_currency(
+fn close_friend_currency_request(
     close_friend_currency_cmd: CloseFriendCurrencyCmd,
-    mut conn_pair: ConnPairApp,
     node_report: &NodeReport,
-) -> Result<(), ConfigError> {
+) -> Result<AppRequest, ConfigError> {
     let friend_public_key =
-        friend_public_key_by_name(&node_report, &close_friend_currency_cmd.friend_name)
-            .ok_or(ConfigError::FriendNameNotFound)?
-            .clone();
+        friend_public_key_by_name(&node_report, &close_friend_currency_cmd.friend_name)?.clone();
 
     let currency = Currency::try_from(close_friend_currency_cmd.currency_name)
         .map_err(|_| ConfigError::InvalidCurrencyName)?;
 
     let app_request = conn::config::close_friend_currency(friend_public_key, currency);
-    config_request(&mut conn_pair, app_request).await
+    Ok(app_request)
 }
 
-async fn config_set_friend_currency_max_debt(
+fn set_friend_currency_max_debt_request(
     set_friend_currency_max_debt_cmd: SetFriendCurrencyMaxDebtCmd,
-    mut conn_pair: ConnPairApp,
     node_report: &NodeReport,
-) -> Result<(), ConfigError> {
+) -> Result<AppRequest, ConfigError> {
     let SetFriendCurrencyMaxDebtCmd {
         friend_name,
         currency_name,
         max_debt,
     } = set_friend_currency_max_debt_cmd;
 
-    let friend_public_key = friend_public_key_by_name(&node_report, &friend_name)
-        .ok_or(ConfigError::FriendNameNotFound)?
-        .clone();
+    let friend_public_key = friend_public_key_by_name(&node_report, &friend_name)?.clone();
 
     let currency =
         Currency::try_from(currency_name).map_err(|_| ConfigError::InvalidCurrencyName)?;
 
     let app_request =
         conn::config::set_friend_currency_max_debt(friend_public_key, currency, max_debt);
-    config_request(&mut conn_pair, app_request).await
+    Ok(app_request)
 }
 
-async fn config_set_friend_currency_rate(
+fn set_friend_currency_rate_request(
     set_friend_currency_rate_cmd: SetFriendCurrencyRateCmd,
-    mut conn_pair: ConnPairApp,
     node_report: &NodeReport,
-) -> Result<(), ConfigError> {
+) -> Result<AppRequest, ConfigError> {
     let SetFriendCurrencyRateCmd {
         friend_name,
         currency_name,
@@ -565,9 +584,7 @@ async fn config_set_friend_currency_rate(
         add,
     } = set_friend_currency_rate_cmd;
 
-    let friend_public_key = friend_public_key_by_name(&node_report, &friend_name)
-        .ok_or(ConfigError::FriendNameNotFound)?
-        .clone();
+    let friend_public_key = friend_public_key_by_name(&node_report, &friend_name)?.clone();
 
     let currency =
         Currency::try_from(currency_name).map_err(|_| ConfigError::InvalidCurrencyName)?;
@@ -575,122 +592,159 @@ async fn config_set_friend_currency_rate(
     let rate = Rate { mul, add };
 
     let app_request = conn::config::set_friend_currency_rate(friend_public_key, currency, rate);
-    config_request(&mut conn_pair, app_request).await
+    Ok(app_request)
 }
 
-async fn config_remove_friend_currency(
+fn remove_friend_currency_request(
     remove_friend_currency_cmd: RemoveFriendCurrencyCmd,
-    mut conn_pair: ConnPairApp,
     node_report: &NodeReport,
-) -> Result<(), ConfigError> {
+) -> Result<AppRequest, ConfigError> {
     let RemoveFriendCurrencyCmd {
         friend_name,
         currency_name,
     } = remove_friend_currency_cmd;
 
-    let friend_public_key = friend_public_key_by_name(&node_report, &friend_name)
-        .ok_or(ConfigError::FriendNameNotFound)?
-        .clone();
+    let friend_public_key = friend_public_key_by_name(&node_report, &friend_name)?.clone();
 
     let currency =
         Currency::try_from(currency_name).map_err(|_| ConfigError::InvalidCurrencyName)?;
 
     let app_request = conn::config::remove_friend_currency(friend_public_key, currency);
-    config_request(&mut conn_pair, app_request).await
+    Ok(app_request)
 }
 
-async fn config_reset_friend(
+fn reset_friend_request(
     reset_friend_cmd: ResetFriendCmd,
-    mut conn_pair: ConnPairApp,
     node_report: &NodeReport,
-) -> Result<(), ConfigError> {
-    let mut opt_friend_pk_report = None;
-    for (friend_public_key, friend_report) in &node_report.funder_report.friends {
-        if friend_report.name == reset_friend_cmd.friend_name {
-            opt_friend_pk_report = Some((friend_public_key, friend_report));
-        }
-    }
-
-    let (friend_public_key, friend_report) =
-        opt_friend_pk_report.ok_or(ConfigError::FriendNameNotFound)?;
-
-    // Obtain the reset token
-    // (Required as a proof that we already received the remote reset terms):
-    let reset_token = match &friend_report.channel_status {
-        ChannelStatusReport::Consistent(_) => return Err(ConfigError::ChannelNotInconsistent),
-        ChannelStatusReport::Inconsistent(channel_inconsistent_report) => {
-            if let Some(remote_reset_terms) = &channel_inconsistent_report.opt_remote_reset_terms {
-                &remote_reset_terms.reset_token
-            } else {
-                return Err(ConfigError::UnknownRemoteResetTerms);
-            }
-        }
-    };
-
-    let app_request =
-        conn::config::reset_friend_channel(friend_public_key.clone(), reset_token.clone());
-    config_request(&mut conn_pair, app_request).await
-}
+) -> Result<AppRequest, ConfigError> {
+    let friend_public_key = friend_public_key_by_name(node_report, &reset_friend_cmd.friend_name)?;
 
-pub async fn config(
-    config_cmd: ConfigCmd,
+    let app_request = conn::config::reset_friend_channel_from_report(
+        node_report,
+        friend_public_key,
+    )
+    .map_err(|e| match e {
+        ResetFriendChannelError::FriendNotFound => ConfigError::FriendNameNotFound,
+        ResetFriendChannelError::ChannelConsistent => ConfigError::ChannelNotInconsistent,
+        ResetFriendChannelError::MissingRemoteResetTerms => ConfigError::UnknownRemoteResetTerms,
+    })?;
+    Ok(app_request)
+}
+
+/// Create the request to the node for a config subcommand
+fn config_app_request(
+    config_subcommand: ConfigSubcommand,
     node_report: &NodeReport,
-    conn_pair: ConnPairApp,
-) -> Result<(), ConfigError> {
-    match config_cmd {
-        ConfigCmd::AddRelay(add_relay_cmd) => {
-            config_add_relay(add_relay_cmd, conn_pair, node_report).await?
-        }
-        ConfigCmd::RemoveRelay(remove_relay_cmd) => {
-            config_remove_relay(remove_relay_cmd, conn_pair, node_report).await?
+) -> Result<AppRequest, ConfigError> {
+    match config_subcommand {
+        ConfigSubcommand::AddRelay(add_relay_cmd) => add_relay_request(add_relay_cmd, node_report),
+        ConfigSubcommand::RemoveRelay(remove_relay_cmd) => {
+            remove_relay_request(remove_relay_cmd, node_report)
         }
-        ConfigCmd::AddIndex(add_index_cmd) => {
-            config_add_index(add_index_cmd, conn_pair, node_report).await?
+        ConfigSubcommand::AddIndex(add_index_cmd) => add_index_request(add_index_cmd, node_report),
+        ConfigSubcommand::RemoveIndex(remove_index_cmd) => {
+            remove_index_request(remove_index_cmd, node_report)
         }
-        ConfigCmd::RemoveIndex(remove_index_cmd) => {
-            config_remove_index(remove_index_cmd, conn_pair, node_report).await?
+        ConfigSubcommand::AddFriend(add_friend_cmd) => {
+            add_friend_request(add_friend_cmd, node_report)
         }
-        ConfigCmd::AddFriend(add_friend_cmd) => {
-            config_add_friend(add_friend_cmd, conn_pair, node_report).await?
+        ConfigSubcommand::SetFriendRelays(set_friend_relays_cmd) => {
+            set_friend_relays_request(set_friend_relays_cmd, node_report)
         }
-        ConfigCmd::SetFriendRelays(set_friend_relays_cmd) => {
-            config_set_friend_relays(set_friend_relays_cmd, conn_pair, node_report).await?
+        ConfigSubcommand::RemoveFriend(remove_friend_cmd) => {
+            remove_friend_request(remove_friend_cmd, node_report)
         }
-        ConfigCmd::RemoveFriend(remove_friend_cmd) => {
-            config_remove_friend(remove_friend_cmd, conn_pair, node_report).await?
+        ConfigSubcommand::EnableFriend(enable_friend_cmd) => {
+            enable_friend_request(enable_friend_cmd, node_report)
         }
-        ConfigCmd::EnableFriend(enable_friend_cmd) => {
-            config_enable_friend(enable_friend_cmd, conn_pair, node_report).await?
+        ConfigSubcommand::DisableFriend(disable_friend_cmd) => {
+            disable_friend_request(disable_friend_cmd, node_report)
         }
-        ConfigCmd::DisableFriend(disable_friend_cmd) => {
-            config_disable_friend(disable_friend_cmd, conn_pair, node_report).await?
+        ConfigSubcommand::PauseFriend(pause_friend_cmd) => {
+            pause_friend_request(pause_friend_cmd, node_report)
         }
-        ConfigCmd::OpenFriendCurrency(open_friend_currency_cmd) => {
-            config_open_friend_currency(open_friend_currency_cmd, conn_pair, node_report).await?
+        ConfigSubcommand::OpenFriendCurrency(open_friend_currency_cmd) => {
+            open_friend_currency_request(open_friend_currency_cmd, node_report)
         }
-        ConfigCmd::CloseFriendCurrency(close_friend_currency_cmd) => {
-            config_close_friend_currency(close_friend_currency_cmd, conn_pair, node_report).await?
+        ConfigSubcommand::CloseFriendCurrency(close_friend_currency_cmd) => {
+            close_friend_currency_request(close_friend_currency_cmd, node_report)
         }
-        ConfigCmd::SetFriendCurrencyMaxDebt(set_friend_currency_max_debt_cmd) => {
-            config_set_friend_currency_max_debt(
-                set_friend_currency_max_debt_cmd,
-                conn_pair,
-                node_report,
-            )
-            .await?
+        ConfigSubcommand::SetFriendCurrencyMaxDebt(set_friend_currency_max_debt_cmd) => {
+            set_friend_currency_max_debt_request(set_friend_currency_max_debt_cmd, node_report)
         }
-        ConfigCmd::SetFriendCurrencyRate(set_friend_currency_rate_cmd) => {
-            config_set_friend_currency_rate(set_friend_currency_rate_cmd, conn_pair, node_report)
-                .await?
+        ConfigSubcommand::SetFriendCurrencyRate(set_friend_currency_rate_cmd) => {
+            set_friend_currency_rate_request(set_friend_currency_rate_cmd, node_report)
         }
-        ConfigCmd::RemoveFriendCurrency(remove_friend_currency_cmd) => {
-            config_remove_friend_currency(remove_friend_currency_cmd, conn_pair, node_report)
-                .await?
+        ConfigSubcommand::RemoveFriendCurrency(remove_friend_currency_cmd) => {
+            remove_friend_currency_request(remove_friend_currency_cmd, node_report)
         }
-        ConfigCmd::ResetFriend(reset_friend_cmd) => {
-            config_reset_friend(reset_friend_cmd, conn_pair, node_report).await?
+        ConfigSubcommand::ResetFriend(reset_friend_cmd) => {
+            reset_friend_request(reset_friend_cmd, node_report)
         }
     }
+}
+
+pub async fn config(
+    config_cmd: ConfigCmd,
+    node_report: &NodeReport,
+    mut conn_pair: ConnPairApp,
+    writer: &mut impl io::Write,
+) -> Result<(), ConfigError> {
+    let ConfigCmd {
+        dry_run,
+        subcommand,
+    } = config_cmd;
+
+    let app_request = config_app_request(subcommand, node_report)?;
+    if dry_run {
+        // Only show the request. Note that the request id will be different when the request is
+        // actually sent:
+        let app_to_app_server = AppToAppServer::new(gen_uid(), app_request);
+        writeln!(writer, "{:#?}", app_to_app_server)?;
+        return Ok(());
+    }
+    config_request(&mut conn_pair, app_request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::channel::mpsc;
+    use futures::executor::block_on;
+
+    use app::common::PublicKey;
 
-    Ok(())
+    #[test]
+    fn test_config_dry_run() {
+        let relay_public_key = PublicKey::from(&[0xbb; PublicKey::len()]);
+        let mut node_report = NodeReport::dummy();
+        node_report.funder_report.relays.push(NamedRelayAddress {
+            public_key: relay_public_key.clone(),
+            address: TryFrom::try_from("127.0.0.1:1337".to_owned()).unwrap(),
+            name: "relay0".to_owned(),
+        });
+
+        let (app_sender, mut node_receiver) = mpsc::channel(0);
+        let (_node_sender, app_receiver) = mpsc::channel::<AppServerToApp>(0);
+        let conn_pair = ConnPairApp::from_raw(app_sender, app_receiver);
+
+        let config_cmd = ConfigCmd {
+            dry_run: true,
+            subcommand: ConfigSubcommand::RemoveRelay(RemoveRelayCmd {
+                relay_name: "relay0".to_owned(),
+            }),
+        };
+
+        let mut output = Vec::new();
+        block_on(config(config_cmd, &node_report, conn_pair, &mut output)).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let expected_request = format!("{:#?}", AppRequest::RemoveRelay(relay_public_key));
+        assert!(output.contains("AppToAppServer"));
+        assert!(output.contains(&expected_request.replace("\n", "\n    ")));
+
+        // Nothing was sent to the node, and the connection was dropped:
+        assert!(block_on(node_receiver.next()).is_none());
+    }
 }
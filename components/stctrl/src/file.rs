@@ -1,28 +1,19 @@
 use app::ser_utils::{ser_b64, ser_string};
 
 use app::common::{
-    Commit, Currency, HashResult, HashedLock, InvoiceId, PaymentId, PlainLock, PublicKey,
-    RandValue, Receipt, Signature,
+    Commit, Currency, HashResult, HashedLock, InvoiceId, PaymentId, PlainLock, RandValue, Receipt,
+    Signature,
 };
 use app::report::{MoveTokenHashedReport, TokenInfo};
 
-use mutual_from::mutual_from;
+pub use app::file::InvoiceFile;
 
-#[derive(Arbitrary, Serialize, Deserialize, Debug, Clone)]
-pub struct InvoiceFile {
-    #[serde(with = "ser_b64")]
-    pub invoice_id: InvoiceId,
-    #[serde(with = "ser_string")]
-    pub currency: Currency,
-    #[serde(with = "ser_b64")]
-    pub dest_public_key: PublicKey,
-    #[serde(with = "ser_string")]
-    pub dest_payment: u128,
-}
+use mutual_from::mutual_from;
 
 /// Representing a Commit in an easy to serialize representation.
 #[mutual_from(Commit)]
 #[derive(Arbitrary, Clone, Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct CommitFile {
     #[serde(with = "ser_b64")]
     pub response_hash: HashResult,
@@ -44,6 +35,7 @@ pub struct CommitFile {
 
 /// A helper structure for serialize and deserializing Payment.
 #[derive(Arbitrary, Clone, Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct PaymentFile {
     #[serde(with = "ser_b64")]
     pub payment_id: PaymentId,
@@ -52,6 +44,7 @@ pub struct PaymentFile {
 /// A helper structure for serialize and deserializing Receipt.
 #[mutual_from(Receipt)]
 #[derive(Arbitrary, Clone, Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct ReceiptFile {
     #[serde(with = "ser_b64")]
     pub response_hash: HashResult,
@@ -75,6 +68,7 @@ pub struct ReceiptFile {
 /// A helper structure for serialize and deserializing Token.
 #[mutual_from(MoveTokenHashedReport)]
 #[derive(Arbitrary, Clone, Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct TokenFile {
     #[serde(with = "ser_b64")]
     pub prefix_hash: HashResult,
@@ -91,8 +85,9 @@ mod test {
 
     use std::convert::TryFrom;
 
+    use app::common::PublicKey;
     use app::report::{BalanceInfo, CountersInfo, CurrencyBalanceInfo, McInfo};
-    use app::ser_utils::serialize_to_string;
+    use app::ser_utils::{deserialize_from_string, serialize_to_string};
 
     #[test]
     fn test_serialize_invoice_file() {
@@ -153,4 +148,53 @@ mod test {
 
         let _ = serialize_to_string(&token_file).unwrap();
     }
+
+    /// Parse a file after replacing `from` with `to` in its serialized form.
+    /// Returns the resulting error message.
+    fn parse_error_with<T>(t: &T, from: &str, to: &str) -> String
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned,
+    {
+        let data = serialize_to_string(t).unwrap();
+        assert!(data.contains(from));
+        let data = data.replacen(from, to, 1);
+        match deserialize_from_string::<T>(&data) {
+            Ok(_) => panic!("Parsing should have failed"),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_invoice_file_unknown_field() {
+        let invoice_file = InvoiceFile {
+            invoice_id: InvoiceId::from(&[1u8; InvoiceId::len()]),
+            currency: Currency::try_from("FST".to_owned()).unwrap(),
+            dest_public_key: PublicKey::from(&[0xbb; PublicKey::len()]),
+            dest_payment: 10u128,
+        };
+
+        // A typo in a key:
+        let error_message =
+            parse_error_with(&invoice_file, "\"dest_payment\"", "\"dest_payement\"");
+        assert!(error_message.contains("unknown field `dest_payement`"));
+    }
+
+    #[test]
+    fn test_receipt_file_unknown_field() {
+        let receipt_file = ReceiptFile {
+            response_hash: HashResult::from(&[0u8; HashResult::len()]),
+            invoice_id: InvoiceId::from(&[1u8; InvoiceId::len()]),
+            currency: Currency::try_from("FST".to_owned()).unwrap(),
+            src_plain_lock: PlainLock::from(&[2u8; PlainLock::len()]),
+            dest_plain_lock: PlainLock::from(&[3u8; PlainLock::len()]),
+            is_complete: true,
+            dest_payment: 4u128,
+            total_dest_payment: 5u128,
+            signature: Signature::from(&[6u8; Signature::len()]),
+        };
+
+        // An extra key:
+        let error_message = parse_error_with(&receipt_file, "{", "{\"extra_key\": 1,");
+        assert!(error_message.contains("unknown field `extra_key`"));
+    }
 }
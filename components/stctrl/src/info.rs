@@ -1,15 +1,19 @@
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{self, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use futures::StreamExt;
 
 use prettytable::Table;
 use structopt::StructOpt;
 
 use derive_more::From;
 
-use app::common::RelayAddress;
+use app::common::{Currency, PublicKey, RelayAddress, RequestsStatus};
+use app::conn::{AppServerToApp, ConnPairApp};
 use app::report::{
-    ChannelStatusReport, CurrencyReport, FriendReport, FriendStatusReport, NodeReport,
+    funder_report_diff, ChannelStatusReport, CurrencyInfo, CurrencyReport, FriendReport,
+    FriendStatusReport, FunderReport, FunderReportChange, InconsistencyReason, NodeReport,
 };
 use app::ser_utils::public_key_to_string;
 
@@ -18,7 +22,7 @@ use app::ser_utils::{serialize_to_string, StringSerdeError};
 
 use crate::file::TokenFile;
 
-use crate::utils::friend_public_key_by_name;
+use crate::utils::{friend_public_key_by_name, FriendNameError};
 
 /*
 /// Display local public key (Used as address for sending funds)
@@ -36,7 +40,19 @@ pub struct IndexCmd {}
 
 /// Show all configured friend servers
 #[derive(Clone, Debug, StructOpt)]
-pub struct FriendsCmd {}
+pub struct FriendsCmd {
+    /// Keep running, and render the friends table again whenever the node's report changes
+    #[structopt(short = "w", long = "watch")]
+    pub watch: bool,
+}
+
+/// Show the detailed state of a friend
+#[derive(Clone, Debug, StructOpt)]
+pub struct FriendCmd {
+    /// Friend's name
+    #[structopt(short = "n", long = "name")]
+    pub friend_name: String,
+}
 
 /// Export last obtained token from a friend
 #[derive(Clone, Debug, StructOpt)]
@@ -61,6 +77,26 @@ pub struct ExportTicketCmd {
     pub ticket_path: PathBuf,
 }
 
+/// Export the current report of the node into a file
+#[derive(Clone, Debug, StructOpt)]
+pub struct ExportReportCmd {
+    /// Path to output report file
+    #[structopt(short = "o", long = "output")]
+    pub report_path: PathBuf,
+}
+
+/// Show the differences between two exported reports.
+/// Works offline, without connecting to the node.
+#[derive(Clone, Debug, StructOpt)]
+pub struct DiffCmd {
+    /// Path to the old report file
+    #[structopt(long = "old")]
+    pub old_report_path: PathBuf,
+    /// Path to the new report file
+    #[structopt(long = "new")]
+    pub new_report_path: PathBuf,
+}
+
 #[derive(Clone, Debug, StructOpt)]
 pub enum InfoCmd {
     // /// Show local public key (Used as address for sending funds)
@@ -75,6 +111,9 @@ pub enum InfoCmd {
     /// Show information about configured friends
     #[structopt(name = "friends")]
     Friends(FriendsCmd),
+    /// Show the detailed token channel state with a friend
+    #[structopt(name = "friend")]
+    Friend(FriendCmd),
     /// Export friend's last token
     #[structopt(name = "friend-last-token")]
     FriendLastToken(FriendLastTokenCmd),
@@ -84,6 +123,12 @@ pub enum InfoCmd {
     /// Export ticket for this node
     #[structopt(name = "export-ticket")]
     ExportTicket(ExportTicketCmd),
+    /// Export the current report of the node
+    #[structopt(name = "export-report")]
+    ExportReport(ExportReportCmd),
+    /// Show added and removed friends and changed balances between two exported reports
+    #[structopt(name = "diff")]
+    Diff(DiffCmd),
 }
 
 #[derive(Debug, From)]
@@ -93,6 +138,8 @@ pub enum InfoError {
     OutputFileAlreadyExists,
     StoreNodeToFileError,
     FriendNameNotFound,
+    /// More than one friend has the given name
+    AmbiguousFriendName,
     MissingLastIncomingMoveToken,
     StoreLastIncomingMoveTokenError,
     WriteError,
@@ -100,13 +147,25 @@ pub enum InfoError {
     LoadTokenError,
     LoadInvoiceError,
     LoadReceiptError,
+    LoadReportError,
     InvalidReceipt,
     DestPaymentMismatch,
     InvoiceIdMismatch,
+    ReportMutationError,
+    ConnectionClosed,
     IoError(std::io::Error),
     StringSerdeError(StringSerdeError),
 }
 
+impl From<FriendNameError> for InfoError {
+    fn from(e: FriendNameError) -> Self {
+        match e {
+            FriendNameError::NotFound => InfoError::FriendNameNotFound,
+            FriendNameError::Ambiguous => InfoError::AmbiguousFriendName,
+        }
+    }
+}
+
 /*
 /// Get a most recently known node report:
 async fn get_report(app_report: &mut AppReport) -> Result<NodeReport, InfoError> {
@@ -211,20 +270,49 @@ fn is_consistent(friend_report: &FriendReport) -> bool {
 }
 */
 
-fn currency_report_str(currency_report: &CurrencyReport) -> String {
+/// Display a balance of a currency, according to the currency's display metadata (if configured)
+fn balance_str(currency_infos: &[CurrencyInfo], currency: &Currency, balance: i128) -> String {
+    match currency_infos
+        .iter()
+        .find(|info| &info.currency == currency)
+    {
+        Some(currency_info) => currency_info.format_balance(balance),
+        None => balance.to_string(),
+    }
+}
+
+/// Display an amount of credits of a currency, according to the currency's display metadata (if
+/// configured)
+fn credits_str(currency_infos: &[CurrencyInfo], currency: &Currency, credits: u128) -> String {
+    match currency_infos
+        .iter()
+        .find(|info| &info.currency == currency)
+    {
+        Some(currency_info) => currency_info.format_credits(credits),
+        None => credits.to_string(),
+    }
+}
+
+fn currency_report_str(
+    currency_infos: &[CurrencyInfo],
+    currency_report: &CurrencyReport,
+) -> String {
     let mut res = String::new();
 
+    let currency = &currency_report.currency;
     let balance = &currency_report.balance;
     res += &format!(
         "B  ={}\nLPD={}\nRPD={}\n",
-        balance.balance, balance.local_pending_debt, balance.remote_pending_debt
+        balance_str(currency_infos, currency, balance.balance),
+        credits_str(currency_infos, currency, balance.local_pending_debt),
+        credits_str(currency_infos, currency, balance.remote_pending_debt)
     );
 
     res
 }
 
 /// A user friendly string explaining the current channel status
-fn friend_channel_status(friend_report: &FriendReport) -> String {
+fn friend_channel_status(currency_infos: &[CurrencyInfo], friend_report: &FriendReport) -> String {
     let mut res = String::new();
     match &friend_report.channel_status {
         ChannelStatusReport::Consistent(channel_consistent_report) => {
@@ -234,17 +322,29 @@ fn friend_channel_status(friend_report: &FriendReport) -> String {
                 res += &format!(
                     "- {}: {}\n",
                     currency_report.currency,
-                    currency_report_str(&currency_report)
+                    currency_report_str(currency_infos, &currency_report)
                 );
             }
         }
         ChannelStatusReport::Inconsistent(channel_inconsistent_report) => {
             res += "Inconsistent:\n";
+            let reason_str = match channel_inconsistent_report.reason {
+                InconsistencyReason::RemoteReported => "Reported by friend",
+                InconsistencyReason::InvalidMoveToken => "Invalid move token",
+                InconsistencyReason::TooManyOperations => "Too many operations in move token",
+                InconsistencyReason::TooManyRetransmits => "Too many retransmitted move tokens",
+            };
+            res += &format!("Reason: {}\n", reason_str);
             res += "Local Reset Terms:\n";
             for currency_balance in &channel_inconsistent_report.local_reset_terms {
                 res += &format!(
                     "- {}: {}\n",
-                    currency_balance.currency, currency_balance.balance
+                    currency_balance.currency,
+                    balance_str(
+                        currency_infos,
+                        &currency_balance.currency,
+                        currency_balance.balance
+                    )
                 );
             }
             match &channel_inconsistent_report.opt_remote_reset_terms {
@@ -253,7 +353,12 @@ fn friend_channel_status(friend_report: &FriendReport) -> String {
                     for currency_balance in &remote_reset_terms.balance_for_reset {
                         res += &format!(
                             "- {}: {}\n",
-                            currency_balance.currency, currency_balance.balance
+                            currency_balance.currency,
+                            balance_str(
+                                currency_infos,
+                                &currency_balance.currency,
+                                currency_balance.balance
+                            )
                         );
                     }
                 }
@@ -272,14 +377,14 @@ pub async fn info_friends(
 ) -> Result<(), InfoError> {
     let mut table = Table::new();
     // Add titlek:
-    table.set_titles(row!["st", "name", "balance"]);
+    table.set_titles(row!["st", "name", "idle", "balance"]);
 
     for friend_report in node_report.funder_report.friends.values() {
         // Is the friend enabled?
-        let status_str = if friend_report.status == FriendStatusReport::Enabled {
-            "E"
-        } else {
-            "D"
+        let status_str = match friend_report.status {
+            FriendStatusReport::Enabled => "E",
+            FriendStatusReport::Disabled => "D",
+            FriendStatusReport::Paused => "P",
         };
 
         let liveness_str = if friend_report.liveness.is_online() {
@@ -295,7 +400,8 @@ pub async fn info_friends(
         table.add_row(row![
             status_string,
             friend_report.name,
-            friend_channel_status(&friend_report),
+            friend_report.idle_ticks,
+            friend_channel_status(&node_report.currency_infos, &friend_report),
         ]);
     }
 
@@ -307,6 +413,132 @@ pub async fn info_friends(
     Ok(())
 }
 
+/// A detailed description of the state of a friend, including the token channel state.
+/// Used for diagnostics.
+fn friend_detail_str(
+    currency_infos: &[CurrencyInfo],
+    friend_public_key: &PublicKey,
+    friend_report: &FriendReport,
+) -> String {
+    let mut res = String::new();
+
+    res += &format!("Name: {}\n", friend_report.name);
+    res += &format!("Public key: {}\n", public_key_to_string(friend_public_key));
+
+    let status_str = match friend_report.status {
+        FriendStatusReport::Enabled => "Enabled",
+        FriendStatusReport::Disabled => "Disabled",
+        FriendStatusReport::Paused => "Paused",
+    };
+    res += &format!("Status: {}\n", status_str);
+
+    let liveness_str = if friend_report.liveness.is_online() {
+        "Online"
+    } else {
+        "Offline"
+    };
+    res += &format!("Liveness: {}\n", liveness_str);
+    res += &format!("Idle ticks: {}\n", friend_report.idle_ticks);
+
+    match &friend_report.opt_last_incoming_move_token {
+        Some(last_incoming_move_token) => {
+            let counters = &last_incoming_move_token.token_info.counters;
+            res += &format!(
+                "Last incoming move token: move_token_counter={}, inconsistency_counter={}\n",
+                counters.move_token_counter, counters.inconsistency_counter
+            );
+        }
+        None => res += "Last incoming move token: None\n",
+    }
+
+    res += "Currency configs:\n";
+    for currency_config in &friend_report.currency_configs {
+        let requests_status = if currency_config.is_open {
+            RequestsStatus::Open
+        } else {
+            RequestsStatus::Closed
+        };
+        res += &format!(
+            "- {}: rate=(mul={}, add={}), remote_max_debt={}, requests={}\n",
+            currency_config.currency,
+            currency_config.rate.mul,
+            currency_config.rate.add,
+            credits_str(
+                currency_infos,
+                &currency_config.currency,
+                currency_config.remote_max_debt
+            ),
+            requests_status
+        );
+    }
+
+    res += &friend_channel_status(currency_infos, friend_report);
+    res
+}
+
+pub async fn info_friend(
+    friend_cmd: FriendCmd,
+    node_report: &NodeReport,
+    writer: &mut impl io::Write,
+) -> Result<(), InfoError> {
+    let FriendCmd { friend_name } = friend_cmd;
+
+    let friend_public_key = friend_public_key_by_name(node_report, &friend_name)?;
+
+    let friend_report = node_report
+        .funder_report
+        .friends
+        .get(&friend_public_key)
+        .unwrap();
+
+    write!(
+        writer,
+        "{}",
+        friend_detail_str(
+            &node_report.currency_infos,
+            friend_public_key,
+            friend_report
+        )
+    )
+    .map_err(|_| InfoError::WriteError)?;
+    Ok(())
+}
+
+/// ANSI escape sequence: Clear the screen and move the cursor to the top left corner.
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+/// Render the friends table, and render it again every time we receive report mutations from the
+/// node. Returns only when the connection to the node is closed.
+pub async fn info_friends_watch(
+    node_report: &NodeReport,
+    conn_pair: ConnPairApp,
+    writer: &mut impl io::Write,
+) -> Result<(), InfoError> {
+    let mut node_report = node_report.clone();
+    // Note: We keep the sender alive, so that the node will not close the connection.
+    let (_sender, mut receiver) = conn_pair.split();
+
+    write!(writer, "{}", CLEAR_SCREEN).map_err(|_| InfoError::WriteError)?;
+    info_friends(&node_report, writer).await?;
+    writer.flush().map_err(|_| InfoError::WriteError)?;
+
+    while let Some(app_server_to_app) = receiver.next().await {
+        if let AppServerToApp::ReportMutations(report_mutations) = app_server_to_app {
+            for mutation in &report_mutations.mutations {
+                node_report
+                    .mutate(mutation)
+                    .map_err(|_| InfoError::ReportMutationError)?;
+            }
+            write!(writer, "{}", CLEAR_SCREEN).map_err(|_| InfoError::WriteError)?;
+            info_friends(&node_report, writer).await?;
+            writer.flush().map_err(|_| InfoError::WriteError)?;
+        }
+    }
+
+    // We are not supposed to stop watching unless interrupted by the user:
+    Err(InfoError::ConnectionClosed)
+}
+
 /// Obtain the last incoming move token messages from a friend.  
 /// This is the last signed commitment made by the friend to the mutual balance.
 pub async fn info_friend_last_token(
@@ -322,8 +554,7 @@ pub async fn info_friend_last_token(
         return Err(InfoError::OutputFileAlreadyExists);
     }
 
-    let friend_public_key = friend_public_key_by_name(node_report, &friend_name)
-        .ok_or(InfoError::FriendNameNotFound)?;
+    let friend_public_key = friend_public_key_by_name(node_report, &friend_name)?;
 
     let friend_report = node_report
         .funder_report
@@ -405,16 +636,106 @@ pub async fn info_export_ticket(
     Ok(())
 }
 
+pub async fn info_export_report(
+    export_report_cmd: ExportReportCmd,
+    node_report: &NodeReport,
+) -> Result<(), InfoError> {
+    let ExportReportCmd { report_path } = export_report_cmd;
+
+    if report_path.exists() {
+        return Err(InfoError::OutputFileAlreadyExists);
+    }
+
+    let mut file = File::create(report_path)?;
+    file.write_all(&node_report.funder_report.to_snapshot_bytes())?;
+
+    Ok(())
+}
+
+fn load_report(report_path: &Path) -> Result<FunderReport, InfoError> {
+    let snapshot_bytes = fs::read(report_path)?;
+    FunderReport::from_snapshot_bytes(&snapshot_bytes).map_err(|_| InfoError::LoadReportError)
+}
+
+/// Render the changes between two funder reports, one change per line.
+fn funder_report_diff_str(old_report: &FunderReport, new_report: &FunderReport) -> String {
+    let changes = funder_report_diff(old_report, new_report);
+    if changes.is_empty() {
+        return "No changes.\n".to_owned();
+    }
+
+    let mut res = String::new();
+    for change in changes {
+        res += &match change {
+            FunderReportChange::AddFriend {
+                friend_public_key,
+                name,
+            } => format!(
+                "+ friend {} ({})\n",
+                name,
+                public_key_to_string(&friend_public_key)
+            ),
+            FunderReportChange::RemoveFriend {
+                friend_public_key,
+                name,
+            } => format!(
+                "- friend {} ({})\n",
+                name,
+                public_key_to_string(&friend_public_key)
+            ),
+            FunderReportChange::BalanceChange {
+                name,
+                currency,
+                old_balance,
+                new_balance,
+                ..
+            } => format!(
+                "~ friend {}: {} {} -> {}\n",
+                name, currency, old_balance, new_balance
+            ),
+        };
+    }
+    res
+}
+
+/// Show the changes between two exported reports.
+/// Does not require a connection to the node.
+pub fn info_diff(diff_cmd: DiffCmd, writer: &mut impl io::Write) -> Result<(), InfoError> {
+    let DiffCmd {
+        old_report_path,
+        new_report_path,
+    } = diff_cmd;
+
+    let old_report = load_report(&old_report_path)?;
+    let new_report = load_report(&new_report_path)?;
+
+    write!(
+        writer,
+        "{}",
+        funder_report_diff_str(&old_report, &new_report)
+    )
+    .map_err(|_| InfoError::WriteError)?;
+    Ok(())
+}
+
 pub async fn info(
     info_cmd: InfoCmd,
     node_report: &NodeReport,
+    conn_pair: ConnPairApp,
     writer: &mut impl io::Write,
 ) -> Result<(), InfoError> {
     match info_cmd {
         // InfoCmd::PublicKey(_public_key_cmd) => info_public_key(node_report, writer).await?,
         InfoCmd::Relays(_relays_cmd) => info_relays(node_report, writer).await?,
         InfoCmd::Index(_index_cmd) => info_index(node_report, writer).await?,
-        InfoCmd::Friends(_friends_cmd) => info_friends(node_report, writer).await?,
+        InfoCmd::Friends(friends_cmd) => {
+            if friends_cmd.watch {
+                info_friends_watch(node_report, conn_pair, writer).await?
+            } else {
+                info_friends(node_report, writer).await?
+            }
+        }
+        InfoCmd::Friend(friend_cmd) => info_friend(friend_cmd, node_report, writer).await?,
         InfoCmd::FriendLastToken(friend_last_token_cmd) => {
             info_friend_last_token(friend_last_token_cmd, node_report).await?
         }
@@ -422,6 +743,287 @@ pub async fn info(
         InfoCmd::ExportTicket(export_ticket_cmd) => {
             info_export_ticket(export_ticket_cmd, node_report).await?
         }
+        InfoCmd::ExportReport(export_report_cmd) => {
+            info_export_report(export_report_cmd, node_report).await?
+        }
+        InfoCmd::Diff(diff_cmd) => info_diff(diff_cmd, writer)?,
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryFrom;
+
+    use futures::channel::mpsc;
+    use futures::executor::block_on;
+
+    use futures::SinkExt;
+    use tempfile::tempdir;
+
+    use app::common::{Rate, Signature};
+    use app::report::{
+        ChannelConsistentReport, ChannelInconsistentReport, CurrencyBalance, CurrencyConfigReport,
+        FriendLivenessReport, FriendReportMutation, FunderReport, FunderReportMutation,
+        McBalanceReport, NodeReportMutation, ReportMutations, ResetTermsReport,
+    };
+
+    use crate::stctrllib::{stctrl, StCtrlCmd};
+
+    fn consistent_status(currency: &Currency, balance: i128) -> ChannelStatusReport {
+        ChannelStatusReport::Consistent(ChannelConsistentReport {
+            currency_reports: vec![CurrencyReport {
+                currency: currency.clone(),
+                balance: McBalanceReport {
+                    balance,
+                    local_pending_debt: 0,
+                    remote_pending_debt: 0,
+                },
+            }],
+        })
+    }
+
+    #[test]
+    fn test_info_friends_watch() {
+        let friend_public_key = PublicKey::from(&[0xbb; PublicKey::len()]);
+        let currency = Currency::try_from("FST".to_owned()).unwrap();
+
+        let friend_report = FriendReport {
+            name: "friend".to_owned(),
+            remote_relays: Vec::new(),
+            currency_configs: Vec::new(),
+            opt_last_incoming_move_token: None,
+            liveness: FriendLivenessReport::Offline,
+            idle_ticks: 0,
+            channel_status: consistent_status(&currency, 17),
+            status: FriendStatusReport::Enabled,
+        };
+        let mut node_report = NodeReport::dummy();
+        node_report
+            .funder_report
+            .friends
+            .insert(friend_public_key.clone(), friend_report);
+
+        let (app_sender, _node_receiver) = mpsc::channel(0);
+        let (mut node_sender, app_receiver) = mpsc::channel(8);
+        let conn_pair = ConnPairApp::from_raw(app_sender, app_receiver);
+
+        let friend_mutation = |friend_report_mutation| {
+            NodeReportMutation::Funder(FunderReportMutation::PkFriendReportMutation((
+                friend_public_key.clone(),
+                friend_report_mutation,
+            )))
+        };
+
+        block_on(async {
+            node_sender
+                .send(AppServerToApp::ReportMutations(ReportMutations {
+                    opt_app_request_id: None,
+                    mutations: vec![
+                        friend_mutation(FriendReportMutation::SetLiveness(
+                            FriendLivenessReport::Online,
+                        )),
+                        friend_mutation(FriendReportMutation::SetChannelStatus(consistent_status(
+                            &currency, 42,
+                        ))),
+                    ],
+                }))
+                .await
+                .unwrap();
+            node_sender
+                .send(AppServerToApp::ReportMutations(ReportMutations {
+                    opt_app_request_id: None,
+                    mutations: vec![friend_mutation(FriendReportMutation::SetChannelStatus(
+                        consistent_status(&currency, -5),
+                    ))],
+                }))
+                .await
+                .unwrap();
+        });
+        // Simulate a closed connection:
+        drop(node_sender);
+
+        let mut output = Vec::new();
+        let res = block_on(info_friends_watch(&node_report, conn_pair, &mut output));
+        // The connection was closed, so we expect an error:
+        match res {
+            Err(InfoError::ConnectionClosed) => {}
+            _ => unreachable!(),
+        };
+
+        let output = String::from_utf8(output).unwrap();
+        // We expect to render the table 3 times:
+        // Once for the initial report and once for every batch of mutations.
+        let renders: Vec<&str> = output.split(CLEAR_SCREEN).skip(1).collect();
+        assert_eq!(renders.len(), 3);
+        assert!(renders[0].contains("B  =17"));
+        assert!(renders[0].contains("E-"));
+        assert!(renders[1].contains("B  =42"));
+        assert!(renders[1].contains("E+"));
+        assert!(renders[2].contains("B  =-5"));
+    }
+
+    #[test]
+    fn test_friend_detail_str_inconsistent() {
+        let friend_public_key = PublicKey::from(&[0xbb; PublicKey::len()]);
+        let currency = Currency::try_from("FST".to_owned()).unwrap();
+
+        let channel_status = ChannelStatusReport::Inconsistent(ChannelInconsistentReport {
+            local_reset_terms: vec![CurrencyBalance {
+                currency: currency.clone(),
+                balance: 12,
+            }],
+            opt_remote_reset_terms: Some(ResetTermsReport {
+                reset_token: Signature::from(&[1; Signature::len()]),
+                balance_for_reset: vec![CurrencyBalance {
+                    currency: currency.clone(),
+                    balance: -12,
+                }],
+            }),
+            reason: InconsistencyReason::TooManyRetransmits,
+        });
+
+        let friend_report = FriendReport {
+            name: "friend".to_owned(),
+            remote_relays: Vec::new(),
+            currency_configs: vec![CurrencyConfigReport {
+                currency: currency.clone(),
+                rate: Rate { mul: 0, add: 1 },
+                remote_max_debt: 100,
+                is_open: true,
+            }],
+            opt_last_incoming_move_token: None,
+            liveness: FriendLivenessReport::Online,
+            idle_ticks: 7,
+            channel_status,
+            status: FriendStatusReport::Enabled,
+        };
+
+        let detail = friend_detail_str(&[], &friend_public_key, &friend_report);
+        assert!(detail.contains("Name: friend\n"));
+        assert!(detail.contains(&format!(
+            "Public key: {}\n",
+            public_key_to_string(&friend_public_key)
+        )));
+        assert!(detail.contains("Status: Enabled\n"));
+        assert!(detail.contains("Liveness: Online\n"));
+        assert!(detail.contains("Idle ticks: 7\n"));
+        assert!(detail.contains("Last incoming move token: None\n"));
+        assert!(detail.contains("- FST: rate=(mul=0, add=1), remote_max_debt=100, requests=open\n"));
+        assert!(detail.contains(
+            "Inconsistent:\nReason: Too many retransmitted move tokens\nLocal Reset Terms:\n- FST: 12\n"
+        ));
+        assert!(detail.contains("Remote Reset Terms:\n- FST: -12\n"));
+    }
+
+    #[test]
+    fn test_info_diff() {
+        let currency = Currency::try_from("FST".to_owned()).unwrap();
+        let friend_report = |name: &str, balance| FriendReport {
+            name: name.to_owned(),
+            remote_relays: Vec::new(),
+            currency_configs: Vec::new(),
+            opt_last_incoming_move_token: None,
+            liveness: FriendLivenessReport::Offline,
+            idle_ticks: 0,
+            channel_status: consistent_status(&currency, balance),
+            status: FriendStatusReport::Enabled,
+        };
+        let funder_report = |friends: Vec<(u8, FriendReport)>| FunderReport {
+            local_public_key: PublicKey::from(&[0xaa; PublicKey::len()]),
+            relays: Vec::new(),
+            friends: friends
+                .into_iter()
+                .map(|(i, friend_report)| (PublicKey::from(&[i; PublicKey::len()]), friend_report))
+                .collect(),
+        };
+
+        let old_report = funder_report(vec![
+            (1, friend_report("bob", 5)),
+            (2, friend_report("carol", 7)),
+        ]);
+        let new_report = funder_report(vec![
+            (1, friend_report("bob", -3)),
+            (3, friend_report("dave", 0)),
+        ]);
+
+        let dir = tempdir().unwrap();
+        let old_report_path = dir.path().join("old_report");
+        let new_report_path = dir.path().join("new_report");
+        fs::write(&old_report_path, old_report.to_snapshot_bytes()).unwrap();
+        fs::write(&new_report_path, new_report.to_snapshot_bytes()).unwrap();
+
+        let args = vec![
+            "stctrl".to_owned(),
+            "info".to_owned(),
+            "diff".to_owned(),
+            "--old".to_owned(),
+            old_report_path.to_str().unwrap().to_owned(),
+            "--new".to_owned(),
+            new_report_path.to_str().unwrap().to_owned(),
+        ];
+        // Does not require a connection to the node:
+        let st_ctrl_cmd = StCtrlCmd::from_iter(args);
+
+        let mut output = Vec::new();
+        stctrl(st_ctrl_cmd, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert_eq!(
+            output,
+            format!(
+                "~ friend bob: FST 5 -> -3\n- friend carol ({})\n+ friend dave ({})\n",
+                public_key_to_string(&PublicKey::from(&[2; PublicKey::len()])),
+                public_key_to_string(&PublicKey::from(&[3; PublicKey::len()])),
+            )
+        );
+
+        // Diffing a report against itself:
+        let diff_cmd = DiffCmd {
+            old_report_path: new_report_path.clone(),
+            new_report_path,
+        };
+        let mut output = Vec::new();
+        info_diff(diff_cmd, &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "No changes.\n");
+    }
+
+    #[test]
+    fn test_info_friends_currency_decimals() {
+        let currency = Currency::try_from("FST".to_owned()).unwrap();
+        let friend_report = FriendReport {
+            name: "friend".to_owned(),
+            remote_relays: Vec::new(),
+            currency_configs: Vec::new(),
+            opt_last_incoming_move_token: None,
+            liveness: FriendLivenessReport::Online,
+            idle_ticks: 0,
+            channel_status: consistent_status(&currency, 8),
+            status: FriendStatusReport::Enabled,
+        };
+        let mut node_report = NodeReport::dummy();
+        node_report
+            .funder_report
+            .friends
+            .insert(PublicKey::from(&[0xbb; PublicKey::len()]), friend_report);
+
+        // Without display metadata, balances are shown as integer credits:
+        let mut output = Vec::new();
+        block_on(info_friends(&node_report, &mut output)).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("B  =8"));
+
+        node_report.currency_infos.push(CurrencyInfo {
+            currency,
+            decimals: 2,
+            symbol: String::new(),
+        });
+        let mut output = Vec::new();
+        block_on(info_friends(&node_report, &mut output)).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("B  =0.08"));
+        assert!(output.contains("LPD=0.00"));
+    }
+}
@@ -29,6 +29,7 @@ pub mod config;
 pub mod file;
 pub mod info;
 pub mod seller;
+pub mod serve;
 pub mod utils;
 
 pub mod stctrllib;
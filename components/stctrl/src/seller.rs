@@ -16,6 +16,7 @@ use app::ser_utils::{deserialize_from_string, serialize_to_string, StringSerdeEr
 use app::verify::verify_commit;
 
 use crate::file::{CommitFile, InvoiceFile};
+use crate::stctrllib::{EXIT_CONNECTION_LOST, EXIT_FAILURE, EXIT_INVALID_COMMIT};
 
 use structopt::StructOpt;
 
@@ -73,18 +74,27 @@ pub enum SellerError {
     ParsePublicKeyError,
     InvoiceFileAlreadyExists,
     StoreInvoiceError,
-    AddInvoiceError,
     LoadInvoiceError,
-    CancelInvoiceError,
     LoadCommitError,
-    CommitInvoiceError,
     InvoiceCommitMismatch,
     RemoveInvoiceError,
     IoError(std::io::Error),
     StringSerdeError(StringSerdeError),
     InvalidCurrencyName,
     InvalidCommit,
-    SellerRequestError,
+    /// Connection to the node was lost before our request was acked
+    ConnectionLost,
+}
+
+impl SellerError {
+    /// Process exit code that corresponds to this error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            SellerError::InvalidCommit | SellerError::InvoiceCommitMismatch => EXIT_INVALID_COMMIT,
+            SellerError::ConnectionLost => EXIT_CONNECTION_LOST,
+            _ => EXIT_FAILURE,
+        }
+    }
 }
 
 async fn seller_request(
@@ -100,7 +110,7 @@ async fn seller_request(
         .sender
         .send(app_to_app_server)
         .await
-        .map_err(|_| SellerError::SellerRequestError)?;
+        .map_err(|_| SellerError::ConnectionLost)?;
 
     // Wait until we get an ack for our request:
     while let Some(app_server_to_app) = conn_pair.receiver.next().await {
@@ -113,7 +123,7 @@ async fn seller_request(
         }
     }
 
-    Err(SellerError::SellerRequestError)
+    Err(SellerError::ConnectionLost)
 }
 
 async fn seller_create_invoice(
@@ -150,8 +160,7 @@ async fn seller_create_invoice(
         &mut conn_pair,
         conn::seller::add_invoice(invoice_id.clone(), currency, amount),
     )
-    .await
-    .map_err(|_| SellerError::AddInvoiceError)?;
+    .await?;
 
     let mut file = File::create(invoice_path)?;
     file.write_all(&serialize_to_string(&invoice_file)?.as_bytes())?;
@@ -170,8 +179,7 @@ async fn seller_cancel_invoice(
         &mut conn_pair,
         conn::seller::cancel_invoice(invoice_file.invoice_id),
     )
-    .await
-    .map_err(|_| SellerError::CancelInvoiceError)?;
+    .await?;
 
     fs::remove_file(&invoice_path).map_err(|_| SellerError::RemoveInvoiceError)
 }
@@ -202,9 +210,11 @@ async fn seller_commit_invoice(
         return Err(SellerError::InvoiceCommitMismatch);
     }
 
-    seller_request(&mut conn_pair, conn::seller::commit_invoice(commit))
-        .await
-        .map_err(|_| SellerError::CommitInvoiceError)
+    commit
+        .validate(&invoice_file.currency, invoice_file.dest_payment)
+        .map_err(|_| SellerError::InvoiceCommitMismatch)?;
+
+    seller_request(&mut conn_pair, conn::seller::commit_invoice(commit)).await
 }
 
 pub async fn seller(
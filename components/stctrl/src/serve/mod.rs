@@ -0,0 +1,88 @@
+#[cfg(unix)]
+mod unix;
+
+use std::path::PathBuf;
+
+use derive_more::From;
+
+use structopt::StructOpt;
+
+use app::ser_utils::StringSerdeError;
+
+use crate::stctrllib::{EXIT_CONNECTION_LOST, EXIT_FAILURE};
+
+#[cfg(unix)]
+pub use self::unix::{serve, stctrl_via};
+
+#[cfg(not(unix))]
+use std::io;
+#[cfg(not(unix))]
+use std::path::Path;
+
+#[cfg(not(unix))]
+use app::conn::{AppPermissions, ConnPairApp};
+#[cfg(not(unix))]
+use app::report::NodeReport;
+
+/// Hold a connection to the node, and serve commands sent by `stctrl --via <socket>`.
+/// Each command's output is sent back once the command completes.
+/// Relative paths given to commands are resolved relative to the serving process.
+/// Only supported on unix.
+#[derive(Clone, Debug, StructOpt)]
+pub struct ServeCmd {
+    /// Path of the unix socket to listen on
+    #[structopt(parse(from_os_str), short = "s", long = "socket")]
+    pub socket: PathBuf,
+}
+
+#[derive(Debug, From)]
+pub enum ServeError {
+    SocketAlreadyExists,
+    NestedServe,
+    ConnectionLost,
+    /// Serving requires unix sockets
+    UnsupportedPlatform,
+    /// The serving process reported a failure
+    CommandFailed(ServeFailure),
+    IoError(std::io::Error),
+    StringSerdeError(StringSerdeError),
+}
+
+impl ServeError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ServeError::CommandFailed(serve_failure) => serve_failure.exit_code,
+            ServeError::ConnectionLost => EXIT_CONNECTION_LOST,
+            _ => EXIT_FAILURE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServeFailure {
+    pub description: String,
+    pub exit_code: i32,
+}
+
+/// Run a subcommand through a serving stctrl instance.
+/// Not supported on this platform.
+#[cfg(not(unix))]
+pub fn stctrl_via(
+    _socket: &Path,
+    _args: Vec<String>,
+    _writer: &mut impl io::Write,
+) -> Result<(), ServeError> {
+    Err(ServeError::UnsupportedPlatform)
+}
+
+/// Serve commands over a single connection to the node.
+/// Not supported on this platform.
+#[cfg(not(unix))]
+pub async fn serve(
+    _serve_cmd: ServeCmd,
+    _app_permissions: AppPermissions,
+    _node_report: NodeReport,
+    _conn_pair: ConnPairApp,
+) -> Result<(), ServeError> {
+    Err(ServeError::UnsupportedPlatform)
+}
@@ -0,0 +1,314 @@
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::Shutdown;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::thread;
+
+use futures::channel::{mpsc, oneshot};
+use futures::executor::block_on;
+use futures::future::{self, Either};
+use futures::{SinkExt, StreamExt};
+
+use structopt::StructOpt;
+
+use app::conn::{AppPermissions, AppServerToApp, ConnPairApp};
+use app::report::NodeReport;
+use app::ser_utils::{deserialize_from_string, serialize_to_string};
+
+use crate::stctrllib::{run_subcommand, StCtrlCmd, EXIT_CONNECTION_LOST, EXIT_FAILURE};
+
+use super::{ServeCmd, ServeError, ServeFailure};
+
+/// A command sent to a serving stctrl instance.
+#[derive(Debug, Serialize, Deserialize)]
+struct ServeRequest {
+    /// Command line arguments of `stctrl --via <socket> <subcommand...>`
+    args: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ServeResponse {
+    output: String,
+    opt_failure: Option<ServeFailure>,
+}
+
+type IncomingRequest = (Vec<String>, oneshot::Sender<ServeResponse>);
+
+/// Run a subcommand through a serving stctrl instance, reusing its connection to the node.
+/// `args` are the full command line arguments, which are parsed again by the serving instance.
+pub fn stctrl_via(
+    socket: &Path,
+    args: Vec<String>,
+    writer: &mut impl io::Write,
+) -> Result<(), ServeError> {
+    let mut stream = UnixStream::connect(socket)?;
+    stream.write_all(serialize_to_string(&ServeRequest { args })?.as_bytes())?;
+    stream.shutdown(Shutdown::Write)?;
+
+    let mut response_string = String::new();
+    stream.read_to_string(&mut response_string)?;
+    let response: ServeResponse = deserialize_from_string(&response_string)?;
+
+    writer.write_all(response.output.as_bytes())?;
+    match response.opt_failure {
+        None => Ok(()),
+        Some(serve_failure) => Err(ServeError::CommandFailed(serve_failure)),
+    }
+}
+
+fn handle_stream(
+    mut stream: UnixStream,
+    request_sender: &mut mpsc::Sender<IncomingRequest>,
+) -> Result<(), ServeError> {
+    let mut request_string = String::new();
+    stream.read_to_string(&mut request_string)?;
+    let request: ServeRequest = deserialize_from_string(&request_string)?;
+
+    let (response_sender, response_receiver) = oneshot::channel();
+    let response = block_on(async move {
+        request_sender
+            .send((request.args, response_sender))
+            .await
+            .ok()?;
+        response_receiver.await.ok()
+    });
+
+    if let Some(response) = response {
+        stream.write_all(serialize_to_string(&response)?.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Listen on a unix socket, and serve incoming commands over a single connection to the node.
+/// Returns when the connection to the node is lost.
+pub async fn serve(
+    serve_cmd: ServeCmd,
+    app_permissions: AppPermissions,
+    node_report: NodeReport,
+    conn_pair: ConnPairApp,
+) -> Result<(), ServeError> {
+    // Never override an existing file:
+    if serve_cmd.socket.exists() {
+        return Err(ServeError::SocketAlreadyExists);
+    }
+    let listener = UnixListener::bind(&serve_cmd.socket)?;
+
+    let (request_sender, incoming_requests) = mpsc::channel(0);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            if request_sender.is_closed() {
+                break;
+            }
+            if let Ok(stream) = stream {
+                // Every client is handled on its own thread, so that an idle client does not
+                // block other clients:
+                let mut c_request_sender = request_sender.clone();
+                thread::spawn(move || {
+                    let _ = handle_stream(stream, &mut c_request_sender);
+                });
+            }
+        }
+    });
+
+    let res = serve_loop(app_permissions, node_report, conn_pair, incoming_requests).await;
+    let _ = fs::remove_file(&serve_cmd.socket);
+    res
+}
+
+/// Keep the node report up to date with report mutations sent by the node.
+fn apply_report_mutations(node_report: &mut NodeReport, app_server_to_app: &AppServerToApp) {
+    if let AppServerToApp::ReportMutations(report_mutations) = app_server_to_app {
+        for mutation in &report_mutations.mutations {
+            let _ = node_report.mutate(mutation);
+        }
+    }
+}
+
+async fn serve_loop(
+    app_permissions: AppPermissions,
+    mut node_report: NodeReport,
+    mut conn_pair: ConnPairApp,
+    mut incoming_requests: mpsc::Receiver<IncomingRequest>,
+) -> Result<(), ServeError> {
+    loop {
+        match future::select(incoming_requests.next(), conn_pair.receiver.next()).await {
+            Either::Left((Some((args, response_sender)), _)) => {
+                let response =
+                    handle_request(args, &app_permissions, &mut node_report, &mut conn_pair).await;
+                let _ = response_sender.send(response);
+            }
+            Either::Left((None, _)) => return Ok(()),
+            Either::Right((Some(app_server_to_app), _)) => {
+                apply_report_mutations(&mut node_report, &app_server_to_app)
+            }
+            Either::Right((None, _)) => return Err(ServeError::ConnectionLost),
+        }
+    }
+}
+
+/// Run a single command over the held connection.
+/// Messages from the node are forwarded to the command while it runs.
+async fn handle_request(
+    args: Vec<String>,
+    app_permissions: &AppPermissions,
+    node_report: &mut NodeReport,
+    conn_pair: &mut ConnPairApp,
+) -> ServeResponse {
+    let subcommand = match StCtrlCmd::from_iter_safe(args) {
+        Ok(st_ctrl_cmd) => st_ctrl_cmd.subcommand,
+        Err(e) => {
+            return ServeResponse {
+                output: String::new(),
+                opt_failure: Some(ServeFailure {
+                    description: e.message,
+                    exit_code: EXIT_FAILURE,
+                }),
+            }
+        }
+    };
+
+    let (user_sender, mut from_user) = mpsc::channel(0);
+    let (to_user, user_receiver) = mpsc::unbounded();
+    let user_conn_pair = ConnPairApp::from_raw(user_sender, user_receiver);
+
+    let node_report_snapshot = node_report.clone();
+    let mut output = Vec::new();
+    let command_fut = Box::pin(run_subcommand(
+        subcommand,
+        app_permissions,
+        &node_report_snapshot,
+        user_conn_pair,
+        &mut output,
+    ));
+
+    let proxy_fut = Box::pin(async move {
+        let mut user_closed = false;
+        loop {
+            let event = if user_closed {
+                Either::Right(conn_pair.receiver.next().await)
+            } else {
+                match future::select(from_user.next(), conn_pair.receiver.next()).await {
+                    Either::Left((opt_app_to_app_server, _)) => Either::Left(opt_app_to_app_server),
+                    Either::Right((opt_app_server_to_app, _)) => {
+                        Either::Right(opt_app_server_to_app)
+                    }
+                }
+            };
+            match event {
+                Either::Left(Some(app_to_app_server)) => {
+                    if conn_pair.sender.send(app_to_app_server).await.is_err() {
+                        return;
+                    }
+                }
+                Either::Left(None) => user_closed = true,
+                Either::Right(Some(app_server_to_app)) => {
+                    apply_report_mutations(node_report, &app_server_to_app);
+                    let _ = to_user.unbounded_send(app_server_to_app);
+                }
+                Either::Right(None) => return,
+            }
+        }
+    });
+
+    let opt_failure = match future::select(command_fut, proxy_fut).await {
+        Either::Left((Ok(()), _)) => None,
+        Either::Left((Err(e), _)) => Some(ServeFailure {
+            description: format!("{:?}", e),
+            exit_code: e.exit_code(),
+        }),
+        Either::Right(((), _)) => Some(ServeFailure {
+            description: "Connection to the node was lost".to_owned(),
+            exit_code: EXIT_CONNECTION_LOST,
+        }),
+    };
+
+    ServeResponse {
+        output: String::from_utf8_lossy(&output).into_owned(),
+        opt_failure,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryFrom;
+
+    use app::common::{NamedRelayAddress, PublicKey};
+    use app::conn::{AppRequest, AppToAppServer};
+    use app::report::{FunderReportMutation, NodeReportMutation, ReportMutations};
+
+    fn to_args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| (*arg).to_owned()).collect()
+    }
+
+    #[test]
+    fn test_serve_two_commands_one_connection() {
+        let relay_public_key = PublicKey::from(&[0xbb; PublicKey::len()]);
+        let mut node_report = NodeReport::dummy();
+        node_report.funder_report.relays.push(NamedRelayAddress {
+            public_key: relay_public_key.clone(),
+            address: TryFrom::try_from("127.0.0.1:1337".to_owned()).unwrap(),
+            name: "relay0".to_owned(),
+        });
+        let app_permissions = AppPermissions {
+            routes: true,
+            buyer: true,
+            seller: true,
+            config: true,
+        };
+
+        // The single connection to the node:
+        let (app_sender, mut node_receiver) = mpsc::channel(0);
+        let (mut node_sender, app_receiver) = mpsc::channel(0);
+        let conn_pair = ConnPairApp::from_raw(app_sender, app_receiver);
+
+        let (mut request_sender, incoming_requests) = mpsc::channel(0);
+        let serve_fut = serve_loop(app_permissions, node_report, conn_pair, incoming_requests);
+
+        let client_fut = async move {
+            // First command, handled by the node:
+            let (response_sender, response_receiver) = oneshot::channel();
+            let args = to_args(&[
+                "stctrl",
+                "--via",
+                "/tmp/s",
+                "config",
+                "remove-relay",
+                "--name",
+                "relay0",
+            ]);
+            request_sender.send((args, response_sender)).await.unwrap();
+
+            let app_to_app_server: AppToAppServer = node_receiver.next().await.unwrap();
+            match app_to_app_server.app_request {
+                AppRequest::RemoveRelay(public_key) => assert_eq!(public_key, relay_public_key),
+                _ => unreachable!(),
+            };
+            node_sender
+                .send(AppServerToApp::ReportMutations(ReportMutations {
+                    opt_app_request_id: Some(app_to_app_server.app_request_id),
+                    mutations: vec![NodeReportMutation::Funder(
+                        FunderReportMutation::RemoveRelay(relay_public_key),
+                    )],
+                }))
+                .await
+                .unwrap();
+
+            let response = response_receiver.await.unwrap();
+            assert_eq!(response.opt_failure, None);
+
+            // Second command, over the same connection, sees the updated report:
+            let (response_sender, response_receiver) = oneshot::channel();
+            let args = to_args(&["stctrl", "--via", "/tmp/s", "info", "relays"]);
+            request_sender.send((args, response_sender)).await.unwrap();
+            let response = response_receiver.await.unwrap();
+            assert_eq!(response.opt_failure, None);
+            assert_eq!(response.output, "No configured relay servers.\n");
+        };
+
+        let (res, ()) = block_on(future::join(serve_fut, client_fut));
+        res.unwrap();
+    }
+}
@@ -10,19 +10,35 @@ use structopt::StructOpt;
 
 use crate::buyer::{buyer, BuyerCmd, BuyerError};
 use crate::config::{config, ConfigCmd, ConfigError};
-use crate::info::{info, InfoCmd, InfoError};
+use crate::info::{info, info_diff, InfoCmd, InfoError};
 use crate::seller::{seller, SellerCmd, SellerError};
+use crate::serve::{serve, ServeCmd, ServeError};
+use crate::stverifylib::{verify, StVerifyError, VerifyCmd};
 
-use app::conn::{connect, identity_from_file};
+use app::conn::{connect, identity_from_file, AppPermissions, ConnPairApp};
 use app::file::NodeAddressFile;
+use app::report::NodeReport;
 use app::ser_utils::{deserialize_from_string, StringSerdeError};
 
+/// Process exit code: General failure
+pub const EXIT_FAILURE: i32 = 1;
+/// Process exit code: Connection to the node failed or was lost
+pub const EXIT_CONNECTION_LOST: i32 = 2;
+/// Process exit code: No route to the destination could be found
+pub const EXIT_ROUTE_NOT_FOUND: i32 = 3;
+/// Process exit code: The payment was canceled
+pub const EXIT_PAYMENT_CANCELED: i32 = 4;
+/// Process exit code: The given commit is invalid, or does not match the invoice
+pub const EXIT_INVALID_COMMIT: i32 = 5;
+/// Process exit code: The given receipt or token is invalid
+pub const EXIT_VERIFY_FAILED: i32 = 6;
+
 #[derive(Debug, From)]
 pub enum StCtrlError {
     CreateThreadPoolError,
-    // MissingIdFileArgument,
+    MissingIdFileArgument,
     IdFileDoesNotExist,
-    // MissingNodeTicketArgument,
+    MissingNodeTicketArgument,
     NodeTicketFileDoesNotExist,
     InvalidNodeTicketFile,
     SpawnIdentityServiceError,
@@ -32,10 +48,27 @@ pub enum StCtrlError {
     ConfigError(ConfigError),
     BuyerError(BuyerError),
     SellerError(SellerError),
+    ServeError(ServeError),
+    VerifyError(StVerifyError),
     IoError(std::io::Error),
     StringSerdeError(StringSerdeError),
 }
 
+impl StCtrlError {
+    /// Process exit code that corresponds to this error.
+    /// Allows scripts to distinguish between different kinds of failures.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            StCtrlError::ConnectionError => EXIT_CONNECTION_LOST,
+            StCtrlError::BuyerError(buyer_error) => buyer_error.exit_code(),
+            StCtrlError::SellerError(seller_error) => seller_error.exit_code(),
+            StCtrlError::ServeError(serve_error) => serve_error.exit_code(),
+            StCtrlError::VerifyError(verify_error) => verify_error.exit_code(),
+            _ => EXIT_FAILURE,
+        }
+    }
+}
+
 #[derive(Clone, Debug, StructOpt)]
 pub enum StCtrlSubcommand {
     /// Get information about current state of node
@@ -50,6 +83,22 @@ pub enum StCtrlSubcommand {
     /// Receiving funds (Seller)
     #[structopt(name = "seller")]
     Seller(SellerCmd),
+    /// Hold a connection to the node, serving commands of `stctrl --via <socket>`
+    #[structopt(name = "serve")]
+    Serve(ServeCmd),
+    /// Verify receipts and tokens received out of band
+    #[structopt(name = "verify")]
+    Verify(VerifyCmd),
+}
+
+impl StCtrlSubcommand {
+    /// Does this subcommand require a connection to the node?
+    pub fn requires_connection(&self) -> bool {
+        match self {
+            StCtrlSubcommand::Info(InfoCmd::Diff(_)) | StCtrlSubcommand::Verify(_) => false,
+            _ => true,
+        }
+    }
 }
 
 /// stctrl: offSeT ConTRoL
@@ -60,35 +109,60 @@ pub enum StCtrlSubcommand {
 pub struct StCtrlCmd {
     /// StCtrl app identity file path
     #[structopt(parse(from_os_str), short = "I", long = "idfile")]
-    pub idfile: PathBuf,
+    pub idfile: Option<PathBuf>,
     /// Node ticket file path
     #[structopt(parse(from_os_str), short = "T", long = "ticket")]
-    pub node_ticket: PathBuf,
+    pub node_ticket: Option<PathBuf>,
+    /// Run the command through a serving stctrl instance (See `stctrl serve`)
+    #[structopt(
+        parse(from_os_str),
+        long = "via",
+        raw(conflicts_with_all = r#"&["idfile", "node_ticket"]"#)
+    )]
+    pub via: Option<PathBuf>,
     #[structopt(flatten)]
     pub subcommand: StCtrlSubcommand,
 }
 
+/// Run a stctrl command.
+/// A connection to the node is opened only if the subcommand requires it.
+/// Commands given with `--via` should be sent to the serving instance using `stctrl_via` instead.
 pub fn stctrl(st_ctrl_cmd: StCtrlCmd, writer: &mut impl io::Write) -> Result<(), StCtrlError> {
-    let thread_pool = ThreadPool::new().map_err(|_| StCtrlError::CreateThreadPoolError)?;
-
     let StCtrlCmd {
         idfile,
         node_ticket,
         subcommand,
+        ..
     } = st_ctrl_cmd;
 
+    // Commands that do not require a connection to the node:
+    match subcommand {
+        StCtrlSubcommand::Info(InfoCmd::Diff(diff_cmd)) => {
+            return info_diff(diff_cmd, writer).map_err(StCtrlError::InfoError)
+        }
+        StCtrlSubcommand::Verify(verify_cmd) => {
+            return verify(verify_cmd, writer).map_err(StCtrlError::VerifyError)
+        }
+        _ => {}
+    }
+
+    let thread_pool = ThreadPool::new().map_err(|_| StCtrlError::CreateThreadPoolError)?;
+
     // Get application's identity:
+    let idfile = idfile.ok_or(StCtrlError::MissingIdFileArgument)?;
     if !idfile.exists() {
         return Err(StCtrlError::IdFileDoesNotExist);
     }
 
     // Get node's connection information (node-ticket):
+    let node_ticket = node_ticket.ok_or(StCtrlError::MissingNodeTicketArgument)?;
     if !node_ticket.exists() {
         return Err(StCtrlError::NodeTicketFileDoesNotExist);
     }
 
     let node_address_file: NodeAddressFile =
-        deserialize_from_string(&fs::read_to_string(&node_ticket)?)?;
+        deserialize_from_string(&fs::read_to_string(&node_ticket)?)
+            .map_err(|e| e.with_path(&node_ticket))?;
 
     // Spawn identity service:
     let app_identity_client = identity_from_file(&idfile, thread_pool.clone())
@@ -105,30 +179,56 @@ pub fn stctrl(st_ctrl_cmd: StCtrlCmd, writer: &mut impl io::Write) -> Result<(),
         .await
         .map_err(|_| StCtrlError::ConnectionError)?;
 
-        match subcommand {
-            StCtrlSubcommand::Info(info_cmd) => info(info_cmd, &node_report, writer).await?,
-            StCtrlSubcommand::Config(config_cmd) => {
-                if app_permissions.config {
-                    config(config_cmd, &node_report, conn_pair).await?
-                } else {
-                    return Err(StCtrlError::InsufficientPermissions);
-                }
+        if let StCtrlSubcommand::Serve(serve_cmd) = subcommand {
+            return serve(serve_cmd, app_permissions, node_report, conn_pair)
+                .await
+                .map_err(StCtrlError::ServeError);
+        }
+        run_subcommand(
+            subcommand,
+            &app_permissions,
+            &node_report,
+            conn_pair,
+            writer,
+        )
+        .await
+    })
+}
+
+/// Run a subcommand over an established connection to the node.
+pub async fn run_subcommand(
+    subcommand: StCtrlSubcommand,
+    app_permissions: &AppPermissions,
+    node_report: &NodeReport,
+    conn_pair: ConnPairApp,
+    writer: &mut impl io::Write,
+) -> Result<(), StCtrlError> {
+    match subcommand {
+        StCtrlSubcommand::Info(info_cmd) => info(info_cmd, node_report, conn_pair, writer).await?,
+        StCtrlSubcommand::Config(config_cmd) => {
+            if app_permissions.config {
+                config(config_cmd, node_report, conn_pair, writer).await?
+            } else {
+                return Err(StCtrlError::InsufficientPermissions);
             }
-            StCtrlSubcommand::Buyer(buyer_cmd) => {
-                if app_permissions.buyer {
-                    buyer(buyer_cmd, &node_report, conn_pair, writer).await?
-                } else {
-                    return Err(StCtrlError::InsufficientPermissions);
-                }
+        }
+        StCtrlSubcommand::Buyer(buyer_cmd) => {
+            if app_permissions.buyer {
+                buyer(buyer_cmd, node_report, conn_pair, writer).await?
+            } else {
+                return Err(StCtrlError::InsufficientPermissions);
             }
-            StCtrlSubcommand::Seller(seller_cmd) => {
-                if app_permissions.seller {
-                    seller(seller_cmd, &node_report, conn_pair).await?
-                } else {
-                    return Err(StCtrlError::InsufficientPermissions);
-                }
+        }
+        StCtrlSubcommand::Seller(seller_cmd) => {
+            if app_permissions.seller {
+                seller(seller_cmd, node_report, conn_pair).await?
+            } else {
+                return Err(StCtrlError::InsufficientPermissions);
             }
         }
-        Ok(())
-    })
+        StCtrlSubcommand::Verify(verify_cmd) => verify(verify_cmd, writer)?,
+        // `serve` can not be run over a served connection:
+        StCtrlSubcommand::Serve(_) => return Err(ServeError::NestedServe.into()),
+    }
+    Ok(())
 }
@@ -7,6 +7,7 @@ use derive_more::From;
 use structopt::StructOpt;
 
 use crate::file::{InvoiceFile, ReceiptFile, TokenFile};
+use crate::stctrllib::{EXIT_FAILURE, EXIT_VERIFY_FAILED};
 
 use app::common::Receipt;
 use app::report::MoveTokenHashedReport;
@@ -22,11 +23,43 @@ pub enum StVerifyError {
     LoadReceiptError,
     InvoiceIdMismatch,
     DestPaymentMismatch,
+    CurrencyMismatch,
     InvalidReceipt,
     IoError(std::io::Error),
     StringSerdeError(StringSerdeError),
 }
 
+impl StVerifyError {
+    /// The reason for a failed verification.
+    /// Returns `None` if the error is not a verification failure
+    /// (For example, if one of the files could not be read).
+    pub fn invalid_reason(&self) -> Option<&'static str> {
+        match self {
+            StVerifyError::TokenInvalid => Some("Invalid token signature"),
+            StVerifyError::InvoiceIdMismatch => {
+                Some("Receipt's invoice id does not match the invoice")
+            }
+            StVerifyError::DestPaymentMismatch => {
+                Some("Receipt's total payment does not match the invoice")
+            }
+            StVerifyError::CurrencyMismatch => {
+                Some("Receipt's currency does not match the invoice")
+            }
+            StVerifyError::InvalidReceipt => Some("Invalid receipt signature"),
+            _ => None,
+        }
+    }
+
+    /// Process exit code that corresponds to this error.
+    pub fn exit_code(&self) -> i32 {
+        if self.invalid_reason().is_some() {
+            EXIT_VERIFY_FAILED
+        } else {
+            EXIT_FAILURE
+        }
+    }
+}
+
 /// Verify a token received from a friend.
 /// A token is some recent commitment of a friend to the mutual credit balance.
 #[derive(Clone, Debug, StructOpt)]
@@ -61,6 +94,18 @@ pub enum StVerifyCmd {
     VerifyReceipt(VerifyReceiptCmd),
 }
 
+/// Verify receipts and tokens received out of band
+/// (`stctrl verify <receipt|token> ...`)
+#[derive(Clone, Debug, StructOpt)]
+pub enum VerifyCmd {
+    /// Verify a receipt against an invoice
+    #[structopt(name = "receipt")]
+    Receipt(VerifyReceiptCmd),
+    /// Verify friend's last token
+    #[structopt(name = "token")]
+    Token(VerifyTokenCmd),
+}
+
 /// Verify a given friend token
 /// If the given token is valid, output token details
 fn stverify_verify_token(
@@ -151,6 +196,10 @@ fn stverify_verify_receipt(
     if invoice_file.dest_payment != receipt.total_dest_payment {
         return Err(StVerifyError::DestPaymentMismatch);
     }
+    // Verify currency match:
+    if invoice_file.currency != receipt.currency {
+        return Err(StVerifyError::CurrencyMismatch);
+    }
 
     if verify_receipt(&receipt, &invoice_file.dest_public_key) {
         writeln!(writer, "Receipt is valid!").map_err(|_| StVerifyError::WriteError)?;
@@ -160,6 +209,28 @@ fn stverify_verify_receipt(
     }
 }
 
+/// Verify a receipt or a token.
+/// Outputs whether the verified file is valid. If it is not valid, outputs the reason.
+pub fn verify(verify_cmd: VerifyCmd, writer: &mut impl io::Write) -> Result<(), StVerifyError> {
+    let (kind, res) = match verify_cmd {
+        VerifyCmd::Receipt(verify_receipt_cmd) => (
+            "Receipt",
+            stverify_verify_receipt(verify_receipt_cmd, writer),
+        ),
+        VerifyCmd::Token(verify_token_cmd) => {
+            ("Token", stverify_verify_token(verify_token_cmd, writer))
+        }
+    };
+
+    if let Err(e) = &res {
+        if let Some(reason) = e.invalid_reason() {
+            writeln!(writer, "{} is invalid: {}", kind, reason)
+                .map_err(|_| StVerifyError::WriteError)?;
+        }
+    }
+    res
+}
+
 pub fn stverify(
     st_verify_cmd: StVerifyCmd,
     writer: &mut impl io::Write,
@@ -1,16 +1,22 @@
 use app::common::PublicKey;
 use app::report::NodeReport;
 
+#[derive(Debug)]
+pub enum FriendNameError {
+    NotFound,
+    /// More than one friend has the given name
+    Ambiguous,
+}
+
 /// Find a friend's public key given his name
 pub fn friend_public_key_by_name<'a>(
     node_report: &'a NodeReport,
     friend_name: &str,
-) -> Option<&'a PublicKey> {
-    // Search for the friend:
-    for (friend_public_key, friend_report) in &node_report.funder_report.friends {
-        if friend_report.name == friend_name {
-            return Some(friend_public_key);
-        }
-    }
-    None
+) -> Result<&'a PublicKey, FriendNameError> {
+    let (friend_public_key, _friend_report) = node_report
+        .funder_report
+        .friend_by_name(friend_name)
+        .map_err(|_| FriendNameError::Ambiguous)?
+        .ok_or(FriendNameError::NotFound)?;
+    Ok(friend_public_key)
 }
@@ -6,7 +6,8 @@ use app::conn::{
     self, AppRequest, AppServerToApp, AppToAppServer, ConnPairApp, RequestResult,
     ResponseRoutesResult,
 };
-use app::gen::gen_uid;
+
+use crate::utils::gen_seeded_uid;
 
 #[derive(Debug)]
 pub struct AppWrapperError;
@@ -16,7 +17,7 @@ pub async fn send_request(
     conn_pair: &mut ConnPairApp,
     app_request: AppRequest,
 ) -> Result<(), AppWrapperError> {
-    let app_request_id = gen_uid();
+    let app_request_id = gen_seeded_uid();
     let app_to_app_server = AppToAppServer {
         app_request_id: app_request_id.clone(),
         app_request,
@@ -49,7 +50,7 @@ pub async fn request_routes(
     dest_public_key: PublicKey,
     opt_exclude: Option<(PublicKey, PublicKey)>,
 ) -> Result<Vec<MultiRoute>, AppWrapperError> {
-    let request_routes_id = gen_uid();
+    let request_routes_id = gen_seeded_uid();
     let app_request = conn::routes::request_routes(
         request_routes_id.clone(),
         currency,
@@ -57,13 +58,14 @@ pub async fn request_routes(
         src_public_key,
         dest_public_key,
         opt_exclude,
+        None,
     );
 
     // Note: We never use the randomly generated `app_request_id` later.
     // Instead, we are waiting
     // We generate it here just because we need to put some value into `app_request_id`.
     let app_to_app_server = AppToAppServer {
-        app_request_id: gen_uid(),
+        app_request_id: gen_seeded_uid(),
         app_request,
     };
     conn_pair
@@ -92,7 +94,7 @@ pub async fn request_close_payment_nowait(
     payment_id: PaymentId,
 ) -> Result<(), AppWrapperError> {
     let app_request = conn::buyer::request_close_payment(payment_id.clone());
-    let app_request_id = gen_uid();
+    let app_request_id = gen_seeded_uid();
     let app_to_app_server = AppToAppServer {
         // We don't really care about app_request_id here, as we can wait on `request_id`
         // instead.
@@ -126,7 +128,7 @@ pub async fn request_close_payment(
     payment_id: PaymentId,
 ) -> Result<PaymentStatus, AppWrapperError> {
     let app_request = conn::buyer::request_close_payment(payment_id.clone());
-    let app_request_id = gen_uid();
+    let app_request_id = gen_seeded_uid();
     let app_to_app_server = AppToAppServer {
         // We don't really care about app_request_id here, as we can wait on `request_id`
         // instead.
@@ -158,7 +160,7 @@ pub async fn ack_close_payment(
     ack_uid: Uid,
 ) -> Result<(), AppWrapperError> {
     let app_request = conn::buyer::ack_close_payment(payment_id.clone(), ack_uid.clone());
-    let app_request_id = gen_uid();
+    let app_request_id = gen_seeded_uid();
     let app_to_app_server = AppToAppServer {
         // We don't really care about app_request_id here, as we can wait on `request_id`
         // instead.
@@ -201,7 +203,7 @@ pub async fn create_transaction(
         dest_payment,
         fees,
     );
-    let app_request_id = gen_uid();
+    let app_request_id = gen_seeded_uid();
     let app_to_app_server = AppToAppServer {
         // We don't really care about app_request_id here, as we can wait on `request_id`
         // instead.
@@ -1,4 +1,5 @@
-use std::{str, thread, time};
+use std::convert::TryFrom;
+use std::{fs, str, thread, time};
 
 use tempfile::tempdir;
 
@@ -8,16 +9,22 @@ use bin::stnode::{stnode, StNodeCmd};
 use bin::strelay::{strelay, StRelayCmd};
 
 use stctrl::config::{
-    AddFriendCmd, AddIndexCmd, AddRelayCmd, CloseFriendCurrencyCmd, ConfigCmd, DisableFriendCmd,
-    EnableFriendCmd, OpenFriendCurrencyCmd, RemoveFriendCurrencyCmd, SetFriendCurrencyMaxDebtCmd,
-    SetFriendCurrencyRateCmd,
+    AddFriendCmd, AddIndexCmd, AddRelayCmd, CloseFriendCurrencyCmd, ConfigCmd, ConfigSubcommand,
+    DisableFriendCmd, EnableFriendCmd, OpenFriendCurrencyCmd, RemoveFriendCurrencyCmd,
+    SetFriendCurrencyMaxDebtCmd, SetFriendCurrencyRateCmd,
 };
 
+use app::common::Currency;
+use app::ser_utils::{deserialize_from_string, serialize_to_string};
 use stctrl::buyer::{BuyerCmd, BuyerError, PayInvoiceCmd, PaymentStatusCmd};
 use stctrl::info::{ExportTicketCmd, FriendLastTokenCmd, FriendsCmd, InfoCmd};
 use stctrl::seller::{CancelInvoiceCmd, CommitInvoiceCmd, CreateInvoiceCmd, SellerCmd};
-use stctrl::stctrllib::{stctrl, StCtrlCmd, StCtrlError, StCtrlSubcommand};
-use stctrl::stverifylib::{stverify, StVerifyCmd, VerifyReceiptCmd, VerifyTokenCmd};
+
+use stctrl::file::ReceiptFile;
+use stctrl::stctrllib::{stctrl, StCtrlCmd, StCtrlError, StCtrlSubcommand, EXIT_VERIFY_FAILED};
+use stctrl::stverifylib::{
+    stverify, verify, StVerifyCmd, VerifyCmd, VerifyReceiptCmd, VerifyTokenCmd,
+};
 
 use crate::cli_tests::stctrl_setup::{create_stctrl_setup, StCtrlSetup};
 
@@ -63,6 +70,7 @@ fn spawn_entities(stctrl_setup: &StCtrlSetup) {
             .join("relay0")
             .join("relay0.ident"),
         laddr: stctrl_setup.relay0_addr.parse().unwrap(),
+        opt_max_conns: None,
     };
     // TODO: How can we close this thread?
     thread::spawn(move || {
@@ -77,6 +85,7 @@ fn spawn_entities(stctrl_setup: &StCtrlSetup) {
             .join("relay1")
             .join("relay1.ident"),
         laddr: stctrl_setup.relay1_addr.parse().unwrap(),
+        opt_max_conns: None,
     };
     // TODO: How can we close this thread?
     thread::spawn(move || {
@@ -90,6 +99,9 @@ fn spawn_entities(stctrl_setup: &StCtrlSetup) {
         laddr: stctrl_setup.node0_addr.clone().parse().unwrap(),
         database: stctrl_setup.temp_dir_path.join("node0").join("node0.db"),
         trusted: stctrl_setup.temp_dir_path.join("node0").join("trusted"),
+        relays: Vec::new(),
+        init: false,
+        opt_tick_ms: None,
     };
     // TODO: How can we close this thread?
     thread::spawn(move || {
@@ -103,6 +115,9 @@ fn spawn_entities(stctrl_setup: &StCtrlSetup) {
         laddr: stctrl_setup.node1_addr.clone().parse().unwrap(),
         database: stctrl_setup.temp_dir_path.join("node1").join("node1.db"),
         trusted: stctrl_setup.temp_dir_path.join("node1").join("trusted"),
+        relays: Vec::new(),
+        init: false,
+        opt_tick_ms: None,
     };
     // TODO: How can we close this thread?
     thread::spawn(move || {
@@ -119,14 +134,19 @@ fn get_node_public_key(stctrl_setup: &StCtrlSetup, index: usize) -> String {
     let subcommand = StCtrlSubcommand::Info(info_cmd);
 
     let st_ctrl_cmd = StCtrlCmd {
-        idfile: stctrl_setup
-            .temp_dir_path
-            .join(format!("app{}", index))
-            .join(format!("app{}.ident", index)),
-        node_ticket: stctrl_setup
-            .temp_dir_path
-            .join(format!("node{}", index))
-            .join(format!("node{}.ticket", index)),
+        idfile: Some(
+            stctrl_setup
+                .temp_dir_path
+                .join(format!("app{}", index))
+                .join(format!("app{}.ident", index)),
+        ),
+        node_ticket: Some(
+            stctrl_setup
+                .temp_dir_path
+                .join(format!("node{}", index))
+                .join(format!("node{}.ticket", index)),
+        ),
+        via: None,
         subcommand,
     };
 
@@ -146,19 +166,24 @@ fn get_node_public_key(stctrl_setup: &StCtrlSetup, index: usize) -> String {
 fn configure_mutual_credit(stctrl_setup: &StCtrlSetup) {
     // Wait until apps can connect to nodes:
     for j in 0..2 {
-        let friends_cmd = FriendsCmd {};
+        let friends_cmd = FriendsCmd { watch: false };
         let info_cmd = InfoCmd::Friends(friends_cmd);
         let subcommand = StCtrlSubcommand::Info(info_cmd);
 
         let st_ctrl_cmd = StCtrlCmd {
-            idfile: stctrl_setup
-                .temp_dir_path
-                .join(format!("app{}", j))
-                .join(format!("app{}.ident", j)),
-            node_ticket: stctrl_setup
-                .temp_dir_path
-                .join(format!("node{}", j))
-                .join(format!("node{}.ticket", j)),
+            idfile: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("app{}", j))
+                    .join(format!("app{}.ident", j)),
+            ),
+            node_ticket: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("node{}", j))
+                    .join(format!("node{}.ticket", j)),
+            ),
+            via: None,
             subcommand,
         };
 
@@ -184,18 +209,26 @@ fn configure_mutual_credit(stctrl_setup: &StCtrlSetup) {
                 .join(format!("relay{}.ticket", j)),
             relay_name: format!("relay{}", j),
         };
-        let config_cmd = ConfigCmd::AddRelay(add_relay_cmd);
+        let config_cmd = ConfigCmd {
+            dry_run: false,
+            subcommand: ConfigSubcommand::AddRelay(add_relay_cmd),
+        };
         let subcommand = StCtrlSubcommand::Config(config_cmd);
 
         let st_ctrl_cmd = StCtrlCmd {
-            idfile: stctrl_setup
-                .temp_dir_path
-                .join(format!("app{}", j))
-                .join(format!("app{}.ident", j)),
-            node_ticket: stctrl_setup
-                .temp_dir_path
-                .join(format!("node{}", j))
-                .join(format!("node{}.ticket", j)),
+            idfile: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("app{}", j))
+                    .join(format!("app{}.ident", j)),
+            ),
+            node_ticket: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("node{}", j))
+                    .join(format!("node{}.ticket", j)),
+            ),
+            via: None,
             subcommand,
         };
         stctrl(st_ctrl_cmd, &mut Vec::new()).unwrap();
@@ -213,18 +246,26 @@ fn configure_mutual_credit(stctrl_setup: &StCtrlSetup) {
                 .join(format!("index{}_client.ticket", j)),
             index_name: format!("index{}", j),
         };
-        let config_cmd = ConfigCmd::AddIndex(add_index_cmd);
+        let config_cmd = ConfigCmd {
+            dry_run: false,
+            subcommand: ConfigSubcommand::AddIndex(add_index_cmd),
+        };
         let subcommand = StCtrlSubcommand::Config(config_cmd);
 
         let st_ctrl_cmd = StCtrlCmd {
-            idfile: stctrl_setup
-                .temp_dir_path
-                .join(format!("app{}", j))
-                .join(format!("app{}.ident", j)),
-            node_ticket: stctrl_setup
-                .temp_dir_path
-                .join(format!("node{}", j))
-                .join(format!("node{}.ticket", j)),
+            idfile: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("app{}", j))
+                    .join(format!("app{}.ident", j)),
+            ),
+            node_ticket: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("node{}", j))
+                    .join(format!("node{}.ticket", j)),
+            ),
+            via: None,
             subcommand,
         };
         stctrl(st_ctrl_cmd, &mut Vec::new()).unwrap();
@@ -245,14 +286,19 @@ fn configure_mutual_credit(stctrl_setup: &StCtrlSetup) {
         let subcommand = StCtrlSubcommand::Info(info_cmd);
 
         let st_ctrl_cmd = StCtrlCmd {
-            idfile: stctrl_setup
-                .temp_dir_path
-                .join(format!("app{}", j))
-                .join(format!("app{}.ident", j)),
-            node_ticket: stctrl_setup
-                .temp_dir_path
-                .join(format!("node{}", j))
-                .join(format!("node{}.ticket", j)),
+            idfile: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("app{}", j))
+                    .join(format!("app{}.ident", j)),
+            ),
+            node_ticket: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("node{}", j))
+                    .join(format!("node{}.ticket", j)),
+            ),
+            via: None,
             subcommand,
         };
         stctrl(st_ctrl_cmd, &mut Vec::new()).unwrap();
@@ -270,18 +316,26 @@ fn configure_mutual_credit(stctrl_setup: &StCtrlSetup) {
                 .join(format!("node{}.friend", 1 - j)),
             friend_name: format!("node{}", 1 - j),
         };
-        let config_cmd = ConfigCmd::AddFriend(add_friend_cmd);
+        let config_cmd = ConfigCmd {
+            dry_run: false,
+            subcommand: ConfigSubcommand::AddFriend(add_friend_cmd),
+        };
         let subcommand = StCtrlSubcommand::Config(config_cmd);
 
         let st_ctrl_cmd = StCtrlCmd {
-            idfile: stctrl_setup
-                .temp_dir_path
-                .join(format!("app{}", j))
-                .join(format!("app{}.ident", j)),
-            node_ticket: stctrl_setup
-                .temp_dir_path
-                .join(format!("node{}", j))
-                .join(format!("node{}.ticket", j)),
+            idfile: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("app{}", j))
+                    .join(format!("app{}.ident", j)),
+            ),
+            node_ticket: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("node{}", j))
+                    .join(format!("node{}.ticket", j)),
+            ),
+            via: None,
             subcommand,
         };
         stctrl(st_ctrl_cmd, &mut Vec::new()).unwrap();
@@ -298,18 +352,26 @@ fn configure_mutual_credit(stctrl_setup: &StCtrlSetup) {
             mul: 0,
             add: 1,
         };
-        let config_cmd = ConfigCmd::SetFriendCurrencyRate(set_friend_currency_rate_cmd);
+        let config_cmd = ConfigCmd {
+            dry_run: false,
+            subcommand: ConfigSubcommand::SetFriendCurrencyRate(set_friend_currency_rate_cmd),
+        };
         let subcommand = StCtrlSubcommand::Config(config_cmd);
 
         let st_ctrl_cmd = StCtrlCmd {
-            idfile: stctrl_setup
-                .temp_dir_path
-                .join(format!("app{}", j))
-                .join(format!("app{}.ident", j)),
-            node_ticket: stctrl_setup
-                .temp_dir_path
-                .join(format!("node{}", j))
-                .join(format!("node{}.ticket", j)),
+            idfile: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("app{}", j))
+                    .join(format!("app{}.ident", j)),
+            ),
+            node_ticket: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("node{}", j))
+                    .join(format!("node{}.ticket", j)),
+            ),
+            via: None,
             subcommand,
         };
         stctrl(st_ctrl_cmd, &mut Vec::new()).unwrap();
@@ -321,18 +383,26 @@ fn configure_mutual_credit(stctrl_setup: &StCtrlSetup) {
         let enable_friend_cmd = EnableFriendCmd {
             friend_name: format!("node{}", 1 - j),
         };
-        let config_cmd = ConfigCmd::EnableFriend(enable_friend_cmd);
+        let config_cmd = ConfigCmd {
+            dry_run: false,
+            subcommand: ConfigSubcommand::EnableFriend(enable_friend_cmd),
+        };
         let subcommand = StCtrlSubcommand::Config(config_cmd);
 
         let st_ctrl_cmd = StCtrlCmd {
-            idfile: stctrl_setup
-                .temp_dir_path
-                .join(format!("app{}", j))
-                .join(format!("app{}.ident", j)),
-            node_ticket: stctrl_setup
-                .temp_dir_path
-                .join(format!("node{}", j))
-                .join(format!("node{}.ticket", j)),
+            idfile: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("app{}", j))
+                    .join(format!("app{}.ident", j)),
+            ),
+            node_ticket: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("node{}", j))
+                    .join(format!("node{}.ticket", j)),
+            ),
+            via: None,
             subcommand,
         };
         stctrl(st_ctrl_cmd, &mut Vec::new()).unwrap();
@@ -340,19 +410,24 @@ fn configure_mutual_credit(stctrl_setup: &StCtrlSetup) {
 
     // Wait until friends are seen enabled and online:
     for j in 0..2 {
-        let friends_cmd = FriendsCmd {};
+        let friends_cmd = FriendsCmd { watch: false };
         let info_cmd = InfoCmd::Friends(friends_cmd);
         let subcommand = StCtrlSubcommand::Info(info_cmd);
 
         let st_ctrl_cmd = StCtrlCmd {
-            idfile: stctrl_setup
-                .temp_dir_path
-                .join(format!("app{}", j))
-                .join(format!("app{}.ident", j)),
-            node_ticket: stctrl_setup
-                .temp_dir_path
-                .join(format!("node{}", j))
-                .join(format!("node{}.ticket", j)),
+            idfile: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("app{}", j))
+                    .join(format!("app{}.ident", j)),
+            ),
+            node_ticket: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("node{}", j))
+                    .join(format!("node{}.ticket", j)),
+            ),
+            via: None,
             subcommand,
         };
 
@@ -375,18 +450,26 @@ fn configure_mutual_credit(stctrl_setup: &StCtrlSetup) {
             friend_name: format!("node{}", 1 - j),
             currency_name: "FST".to_owned(),
         };
-        let config_cmd = ConfigCmd::OpenFriendCurrency(open_friend_cmd);
+        let config_cmd = ConfigCmd {
+            dry_run: false,
+            subcommand: ConfigSubcommand::OpenFriendCurrency(open_friend_cmd),
+        };
         let subcommand = StCtrlSubcommand::Config(config_cmd);
 
         let st_ctrl_cmd = StCtrlCmd {
-            idfile: stctrl_setup
-                .temp_dir_path
-                .join(format!("app{}", j))
-                .join(format!("app{}.ident", j)),
-            node_ticket: stctrl_setup
-                .temp_dir_path
-                .join(format!("node{}", j))
-                .join(format!("node{}.ticket", j)),
+            idfile: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("app{}", j))
+                    .join(format!("app{}.ident", j)),
+            ),
+            node_ticket: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("node{}", j))
+                    .join(format!("node{}.ticket", j)),
+            ),
+            via: None,
             subcommand,
         };
         stctrl(st_ctrl_cmd, &mut Vec::new()).unwrap();
@@ -416,18 +499,26 @@ fn add_remove_currency(stctrl_setup: &StCtrlSetup) {
             mul: 0,
             add: 1,
         };
-        let config_cmd = ConfigCmd::SetFriendCurrencyRate(set_friend_currency_rate_cmd);
+        let config_cmd = ConfigCmd {
+            dry_run: false,
+            subcommand: ConfigSubcommand::SetFriendCurrencyRate(set_friend_currency_rate_cmd),
+        };
         let subcommand = StCtrlSubcommand::Config(config_cmd);
 
         let st_ctrl_cmd = StCtrlCmd {
-            idfile: stctrl_setup
-                .temp_dir_path
-                .join(format!("app{}", j))
-                .join(format!("app{}.ident", j)),
-            node_ticket: stctrl_setup
-                .temp_dir_path
-                .join(format!("node{}", j))
-                .join(format!("node{}.ticket", j)),
+            idfile: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("app{}", j))
+                    .join(format!("app{}.ident", j)),
+            ),
+            node_ticket: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("node{}", j))
+                    .join(format!("node{}.ticket", j)),
+            ),
+            via: None,
             subcommand,
         };
         stctrl(st_ctrl_cmd, &mut Vec::new()).unwrap();
@@ -442,18 +533,26 @@ fn add_remove_currency(stctrl_setup: &StCtrlSetup) {
             friend_name: format!("node{}", 1 - j),
             currency_name: "FST2".to_owned(),
         };
-        let config_cmd = ConfigCmd::RemoveFriendCurrency(remove_friend_currency_cmd);
+        let config_cmd = ConfigCmd {
+            dry_run: false,
+            subcommand: ConfigSubcommand::RemoveFriendCurrency(remove_friend_currency_cmd),
+        };
         let subcommand = StCtrlSubcommand::Config(config_cmd);
 
         let st_ctrl_cmd = StCtrlCmd {
-            idfile: stctrl_setup
-                .temp_dir_path
-                .join(format!("app{}", j))
-                .join(format!("app{}.ident", j)),
-            node_ticket: stctrl_setup
-                .temp_dir_path
-                .join(format!("node{}", j))
-                .join(format!("node{}.ticket", j)),
+            idfile: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("app{}", j))
+                    .join(format!("app{}.ident", j)),
+            ),
+            node_ticket: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("node{}", j))
+                    .join(format!("node{}.ticket", j)),
+            ),
+            via: None,
             subcommand,
         };
         stctrl(st_ctrl_cmd, &mut Vec::new()).unwrap();
@@ -468,15 +567,21 @@ fn set_max_debt(stctrl_setup: &StCtrlSetup) {
         currency_name: "FST".to_owned(),
         max_debt: 200,
     };
-    let config_cmd = ConfigCmd::SetFriendCurrencyMaxDebt(set_friend_currency_max_debt_cmd);
+    let config_cmd = ConfigCmd {
+        dry_run: false,
+        subcommand: ConfigSubcommand::SetFriendCurrencyMaxDebt(set_friend_currency_max_debt_cmd),
+    };
     let subcommand = StCtrlSubcommand::Config(config_cmd);
 
     let st_ctrl_cmd = StCtrlCmd {
-        idfile: stctrl_setup.temp_dir_path.join("app0").join("app0.ident"),
-        node_ticket: stctrl_setup
-            .temp_dir_path
-            .join("node0")
-            .join("node0.ticket"),
+        idfile: Some(stctrl_setup.temp_dir_path.join("app0").join("app0.ident")),
+        node_ticket: Some(
+            stctrl_setup
+                .temp_dir_path
+                .join("node0")
+                .join("node0.ticket"),
+        ),
+        via: None,
         subcommand,
     };
     stctrl(st_ctrl_cmd, &mut Vec::new()).unwrap();
@@ -500,11 +605,14 @@ fn create_cancel_invoice(stctrl_setup: &StCtrlSetup) {
     let subcommand = StCtrlSubcommand::Seller(seller_cmd);
 
     let st_ctrl_cmd = StCtrlCmd {
-        idfile: stctrl_setup.temp_dir_path.join("app0").join("app0.ident"),
-        node_ticket: stctrl_setup
-            .temp_dir_path
-            .join("node0")
-            .join("node0.ticket"),
+        idfile: Some(stctrl_setup.temp_dir_path.join("app0").join("app0.ident")),
+        node_ticket: Some(
+            stctrl_setup
+                .temp_dir_path
+                .join("node0")
+                .join("node0.ticket"),
+        ),
+        via: None,
         subcommand,
     };
     stctrl(st_ctrl_cmd.clone(), &mut Vec::new()).unwrap();
@@ -521,11 +629,14 @@ fn create_cancel_invoice(stctrl_setup: &StCtrlSetup) {
     let subcommand = StCtrlSubcommand::Seller(seller_cmd);
 
     let st_ctrl_cmd = StCtrlCmd {
-        idfile: stctrl_setup.temp_dir_path.join("app0").join("app0.ident"),
-        node_ticket: stctrl_setup
-            .temp_dir_path
-            .join("node0")
-            .join("node0.ticket"),
+        idfile: Some(stctrl_setup.temp_dir_path.join("app0").join("app0.ident")),
+        node_ticket: Some(
+            stctrl_setup
+                .temp_dir_path
+                .join("node0")
+                .join("node0.ticket"),
+        ),
+        via: None,
         subcommand,
     };
     stctrl(st_ctrl_cmd.clone(), &mut Vec::new()).unwrap();
@@ -557,11 +668,14 @@ fn pay_invoice(stctrl_setup: &StCtrlSetup) {
     let subcommand = StCtrlSubcommand::Seller(seller_cmd);
 
     let st_ctrl_cmd = StCtrlCmd {
-        idfile: stctrl_setup.temp_dir_path.join("app0").join("app0.ident"),
-        node_ticket: stctrl_setup
-            .temp_dir_path
-            .join("node0")
-            .join("node0.ticket"),
+        idfile: Some(stctrl_setup.temp_dir_path.join("app0").join("app0.ident")),
+        node_ticket: Some(
+            stctrl_setup
+                .temp_dir_path
+                .join("node0")
+                .join("node0.ticket"),
+        ),
+        via: None,
         subcommand,
     };
     stctrl(st_ctrl_cmd.clone(), &mut Vec::new()).unwrap();
@@ -587,11 +701,14 @@ fn pay_invoice(stctrl_setup: &StCtrlSetup) {
         let subcommand = StCtrlSubcommand::Buyer(buyer_cmd);
 
         let st_ctrl_cmd = StCtrlCmd {
-            idfile: stctrl_setup.temp_dir_path.join("app1").join("app1.ident"),
-            node_ticket: stctrl_setup
-                .temp_dir_path
-                .join("node1")
-                .join("node1.ticket"),
+            idfile: Some(stctrl_setup.temp_dir_path.join("app1").join("app1.ident")),
+            node_ticket: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join("node1")
+                    .join("node1.ticket"),
+            ),
+            via: None,
             subcommand,
         };
 
@@ -599,7 +716,7 @@ fn pay_invoice(stctrl_setup: &StCtrlSetup) {
         match stctrl(st_ctrl_cmd.clone(), &mut Vec::new()) {
             Ok(_) => break,
             Err(StCtrlError::BuyerError(BuyerError::NoSuitableRoute))
-            | Err(StCtrlError::BuyerError(BuyerError::AppRoutesError)) => {}
+            | Err(StCtrlError::BuyerError(BuyerError::RouteNotFound)) => {}
             Err(_) => {
                 unreachable!();
             }
@@ -623,11 +740,14 @@ fn pay_invoice(stctrl_setup: &StCtrlSetup) {
     let subcommand = StCtrlSubcommand::Seller(seller_cmd);
 
     let st_ctrl_cmd = StCtrlCmd {
-        idfile: stctrl_setup.temp_dir_path.join("app0").join("app0.ident"),
-        node_ticket: stctrl_setup
-            .temp_dir_path
-            .join("node0")
-            .join("node0.ticket"),
+        idfile: Some(stctrl_setup.temp_dir_path.join("app0").join("app0.ident")),
+        node_ticket: Some(
+            stctrl_setup
+                .temp_dir_path
+                .join("node0")
+                .join("node0.ticket"),
+        ),
+        via: None,
         subcommand,
     };
     stctrl(st_ctrl_cmd.clone(), &mut Vec::new()).unwrap();
@@ -648,11 +768,14 @@ fn pay_invoice(stctrl_setup: &StCtrlSetup) {
     let subcommand = StCtrlSubcommand::Buyer(buyer_cmd);
 
     let st_ctrl_cmd = StCtrlCmd {
-        idfile: stctrl_setup.temp_dir_path.join("app1").join("app1.ident"),
-        node_ticket: stctrl_setup
-            .temp_dir_path
-            .join("node1")
-            .join("node1.ticket"),
+        idfile: Some(stctrl_setup.temp_dir_path.join("app1").join("app1.ident")),
+        node_ticket: Some(
+            stctrl_setup
+                .temp_dir_path
+                .join("node1")
+                .join("node1.ticket"),
+        ),
+        via: None,
         subcommand,
     };
 
@@ -687,10 +810,70 @@ fn pay_invoice(stctrl_setup: &StCtrlSetup) {
             .join("test1.receipt"),
     };
 
-    let stverify_cmd = StVerifyCmd::VerifyReceipt(verify_receipt_cmd);
+    let stverify_cmd = StVerifyCmd::VerifyReceipt(verify_receipt_cmd.clone());
     let mut output = Vec::new();
     stverify(stverify_cmd, &mut output).unwrap();
     assert!(str::from_utf8(&output).unwrap().contains("is valid!"));
+
+    // Verify the receipt using `stctrl verify receipt`.
+    // Does not require a connection to the node:
+    let st_ctrl_cmd = StCtrlCmd {
+        idfile: None,
+        node_ticket: None,
+        via: None,
+        subcommand: StCtrlSubcommand::Verify(VerifyCmd::Receipt(verify_receipt_cmd.clone())),
+    };
+    let mut output = Vec::new();
+    stctrl(st_ctrl_cmd, &mut output).unwrap();
+    assert!(str::from_utf8(&output)
+        .unwrap()
+        .contains("Receipt is valid!"));
+
+    // Tamper with the receipt. Verification should fail:
+    let mut receipt_file: ReceiptFile =
+        deserialize_from_string(&fs::read_to_string(&verify_receipt_cmd.receipt_path).unwrap())
+            .unwrap();
+    receipt_file.dest_payment += 1;
+    let tampered_receipt_path = stctrl_setup
+        .temp_dir_path
+        .join("node1")
+        .join("test1_tampered.receipt");
+    fs::write(
+        &tampered_receipt_path,
+        serialize_to_string(&receipt_file).unwrap(),
+    )
+    .unwrap();
+
+    let verify_cmd = VerifyCmd::Receipt(VerifyReceiptCmd {
+        invoice_path: verify_receipt_cmd.invoice_path.clone(),
+        receipt_path: tampered_receipt_path.clone(),
+    });
+    let mut output = Vec::new();
+    let verify_error = verify(verify_cmd, &mut output).unwrap_err();
+    assert_eq!(verify_error.exit_code(), EXIT_VERIFY_FAILED);
+    assert!(str::from_utf8(&output)
+        .unwrap()
+        .contains("Receipt is invalid: Invalid receipt signature"));
+
+    // A receipt of another currency does not match the invoice:
+    receipt_file.dest_payment -= 1;
+    receipt_file.currency = Currency::try_from("FST2".to_owned()).unwrap();
+    fs::write(
+        &tampered_receipt_path,
+        serialize_to_string(&receipt_file).unwrap(),
+    )
+    .unwrap();
+
+    let verify_cmd = VerifyCmd::Receipt(VerifyReceiptCmd {
+        invoice_path: verify_receipt_cmd.invoice_path,
+        receipt_path: tampered_receipt_path,
+    });
+    let mut output = Vec::new();
+    let verify_error = verify(verify_cmd, &mut output).unwrap_err();
+    assert_eq!(verify_error.exit_code(), EXIT_VERIFY_FAILED);
+    assert!(str::from_utf8(&output)
+        .unwrap()
+        .contains("Receipt is invalid: Receipt's currency does not match the invoice"));
 }
 
 /*
@@ -701,11 +884,9 @@ fn check_balance(stctrl_setup: &StCtrlSetup) {
     let subcommand = StCtrlSubcommand::Info(info_cmd);
 
     let st_ctrl_cmd = StCtrlCmd {
-        idfile: stctrl_setup.temp_dir_path.join("app1").join("app1.ident"),
-        node_ticket: stctrl_setup
-            .temp_dir_path
-            .join("node1")
-            .join("node1.ticket"),
+        idfile: Some(stctrl_setup.temp_dir_path.join("app1").join("app1.ident")),
+        node_ticket: Some(stctrl_setup.temp_dir_path.join("node1").join("node1.ticket")),
+        via: None,
         subcommand,
     };
 
@@ -726,11 +907,14 @@ fn export_token(stctrl_setup: &StCtrlSetup) {
     let subcommand = StCtrlSubcommand::Info(info_cmd);
 
     let st_ctrl_cmd = StCtrlCmd {
-        idfile: stctrl_setup.temp_dir_path.join("app1").join("app1.ident"),
-        node_ticket: stctrl_setup
-            .temp_dir_path
-            .join("node1")
-            .join("node1.ticket"),
+        idfile: Some(stctrl_setup.temp_dir_path.join("app1").join("app1.ident")),
+        node_ticket: Some(
+            stctrl_setup
+                .temp_dir_path
+                .join("node1")
+                .join("node1.ticket"),
+        ),
+        via: None,
         subcommand,
     };
     stctrl(st_ctrl_cmd, &mut Vec::new()).unwrap();
@@ -758,18 +942,26 @@ fn close_disable(stctrl_setup: &StCtrlSetup) {
             friend_name: format!("node{}", 1 - j),
             currency_name: "FST".to_owned(),
         };
-        let config_cmd = ConfigCmd::CloseFriendCurrency(close_friend_currency_cmd);
+        let config_cmd = ConfigCmd {
+            dry_run: false,
+            subcommand: ConfigSubcommand::CloseFriendCurrency(close_friend_currency_cmd),
+        };
         let subcommand = StCtrlSubcommand::Config(config_cmd);
 
         let st_ctrl_cmd = StCtrlCmd {
-            idfile: stctrl_setup
-                .temp_dir_path
-                .join(format!("app{}", j))
-                .join(format!("app{}.ident", j)),
-            node_ticket: stctrl_setup
-                .temp_dir_path
-                .join(format!("node{}", j))
-                .join(format!("node{}.ticket", j)),
+            idfile: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("app{}", j))
+                    .join(format!("app{}.ident", j)),
+            ),
+            node_ticket: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("node{}", j))
+                    .join(format!("node{}.ticket", j)),
+            ),
+            via: None,
             subcommand,
         };
         stctrl(st_ctrl_cmd, &mut Vec::new()).unwrap();
@@ -783,18 +975,26 @@ fn close_disable(stctrl_setup: &StCtrlSetup) {
             friend_name: format!("node{}", 1 - j),
             currency_name: "FST".to_owned(),
         };
-        let config_cmd = ConfigCmd::RemoveFriendCurrency(remove_friend_currency_cmd);
+        let config_cmd = ConfigCmd {
+            dry_run: false,
+            subcommand: ConfigSubcommand::RemoveFriendCurrency(remove_friend_currency_cmd),
+        };
         let subcommand = StCtrlSubcommand::Config(config_cmd);
 
         let st_ctrl_cmd = StCtrlCmd {
-            idfile: stctrl_setup
-                .temp_dir_path
-                .join(format!("app{}", j))
-                .join(format!("app{}.ident", j)),
-            node_ticket: stctrl_setup
-                .temp_dir_path
-                .join(format!("node{}", j))
-                .join(format!("node{}.ticket", j)),
+            idfile: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("app{}", j))
+                    .join(format!("app{}.ident", j)),
+            ),
+            node_ticket: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("node{}", j))
+                    .join(format!("node{}.ticket", j)),
+            ),
+            via: None,
             subcommand,
         };
         stctrl(st_ctrl_cmd, &mut Vec::new()).unwrap();
@@ -805,18 +1005,26 @@ fn close_disable(stctrl_setup: &StCtrlSetup) {
         let disable_friend_cmd = DisableFriendCmd {
             friend_name: format!("node{}", 1 - j),
         };
-        let config_cmd = ConfigCmd::DisableFriend(disable_friend_cmd);
+        let config_cmd = ConfigCmd {
+            dry_run: false,
+            subcommand: ConfigSubcommand::DisableFriend(disable_friend_cmd),
+        };
         let subcommand = StCtrlSubcommand::Config(config_cmd);
 
         let st_ctrl_cmd = StCtrlCmd {
-            idfile: stctrl_setup
-                .temp_dir_path
-                .join(format!("app{}", j))
-                .join(format!("app{}.ident", j)),
-            node_ticket: stctrl_setup
-                .temp_dir_path
-                .join(format!("node{}", j))
-                .join(format!("node{}.ticket", j)),
+            idfile: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("app{}", j))
+                    .join(format!("app{}.ident", j)),
+            ),
+            node_ticket: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("node{}", j))
+                    .join(format!("node{}.ticket", j)),
+            ),
+            via: None,
             subcommand,
         };
         stctrl(st_ctrl_cmd, &mut Vec::new()).unwrap();
@@ -824,19 +1032,24 @@ fn close_disable(stctrl_setup: &StCtrlSetup) {
 
     // Wait until friends are seen disabled and offline:
     for j in 0..2 {
-        let friends_cmd = FriendsCmd {};
+        let friends_cmd = FriendsCmd { watch: false };
         let info_cmd = InfoCmd::Friends(friends_cmd);
         let subcommand = StCtrlSubcommand::Info(info_cmd);
 
         let st_ctrl_cmd = StCtrlCmd {
-            idfile: stctrl_setup
-                .temp_dir_path
-                .join(format!("app{}", j))
-                .join(format!("app{}.ident", j)),
-            node_ticket: stctrl_setup
-                .temp_dir_path
-                .join(format!("node{}", j))
-                .join(format!("node{}.ticket", j)),
+            idfile: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("app{}", j))
+                    .join(format!("app{}.ident", j)),
+            ),
+            node_ticket: Some(
+                stctrl_setup
+                    .temp_dir_path
+                    .join(format!("node{}", j))
+                    .join(format!("node{}.ticket", j)),
+            ),
+            via: None,
             subcommand,
         };
 
@@ -0,0 +1,47 @@
+use std::fs;
+
+use futures::executor::{block_on, ThreadPool};
+
+use tempfile::tempdir;
+
+use crypto::identity::{Identity, SoftwareEd25519Identity};
+
+use proto::file::IdentityFile;
+use proto::ser_string::deserialize_from_string;
+
+use app::conn::identity_from_file;
+
+use bin::stmgrlib::{stmgr, GenIdentCmd, GenIdentityError, StMgrCmd, StmError};
+
+#[test]
+fn test_gen_ident_load() {
+    let dir = tempdir().unwrap();
+    let idfile_path = dir.path().join("app.ident");
+
+    let gen_ident_cmd = GenIdentCmd {
+        output_path: idfile_path.clone(),
+    };
+    stmgr(StMgrCmd::GenIdent(gen_ident_cmd)).unwrap();
+
+    let identity_file: IdentityFile =
+        deserialize_from_string(&fs::read_to_string(&idfile_path).unwrap()).unwrap();
+    let identity = SoftwareEd25519Identity::from_private_key(&identity_file.private_key).unwrap();
+
+    // The generated identity file can be loaded back:
+    let thread_pool = ThreadPool::new().unwrap();
+    let identity_client = identity_from_file(&idfile_path, thread_pool).unwrap();
+    let public_key = block_on(identity_client.request_public_key()).unwrap();
+    assert_eq!(public_key, identity.get_public_key());
+
+    // An existing identity file is never overwritten:
+    let gen_ident_cmd = GenIdentCmd {
+        output_path: idfile_path.clone(),
+    };
+    match stmgr(StMgrCmd::GenIdent(gen_ident_cmd)) {
+        Err(StmError::GenIdentityError(GenIdentityError::OutputAlreadyExists)) => {}
+        _ => unreachable!(),
+    };
+    let identity_file_after: IdentityFile =
+        deserialize_from_string(&fs::read_to_string(&idfile_path).unwrap()).unwrap();
+    assert_eq!(identity_file_after, identity_file);
+}
@@ -1,2 +1,3 @@
 mod basic_cli;
+mod gen_ident;
 mod stctrl_setup;
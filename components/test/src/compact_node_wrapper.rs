@@ -3,9 +3,10 @@ use futures::stream::StreamExt;
 
 use common::conn::ConnPair;
 
-use app::gen::gen_uid;
 use stcompact::compact_node::messages::{CompactToUserAck, UserToCompact, UserToCompactAck};
 
+use crate::utils::gen_seeded_uid;
+
 #[derive(Debug)]
 pub struct CompactNodeWrapperError;
 
@@ -14,7 +15,7 @@ pub async fn send_request(
     conn_pair: &mut ConnPair<UserToCompactAck, CompactToUserAck>,
     user_to_compact: UserToCompact,
 ) -> Result<(), CompactNodeWrapperError> {
-    let user_request_id = gen_uid();
+    let user_request_id = gen_seeded_uid();
     let user_to_compact_ack = UserToCompactAck {
         user_request_id: user_request_id.clone(),
         inner: user_to_compact,
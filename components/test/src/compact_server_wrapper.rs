@@ -5,11 +5,12 @@ use futures::stream::StreamExt;
 
 use common::conn::ConnPair;
 
-use app::gen::gen_uid;
 use stcompact::messages::{
     NodesStatus, ServerToUser, ServerToUserAck, UserToServer, UserToServerAck,
 };
 
+use crate::utils::gen_seeded_uid;
+
 #[derive(Debug)]
 pub struct CompactServerWrapperError;
 
@@ -19,7 +20,7 @@ pub async fn send_request(
     nodes_status: &mut NodesStatus,
     user_to_server: UserToServer,
 ) -> Result<(), CompactServerWrapperError> {
-    let request_id = gen_uid();
+    let request_id = gen_seeded_uid();
     let user_to_server_ack = UserToServerAck {
         request_id: request_id.clone(),
         inner: user_to_server,
@@ -1,27 +1,60 @@
 use futures::channel::{mpsc, oneshot};
 
+use std::collections::VecDeque;
+
+use futures::future::Either;
 use futures::task::{Spawn, SpawnExt};
 use futures::{future, stream, SinkExt, Stream, StreamExt};
 
 use common::conn::BoxStream;
 use common::select_streams::select_streams;
 
+use timer::TimerClient;
+
 use app::conn::AppServerToApp;
-use app::report::NodeReport;
+use app::report::{NodeReport, ReportMutations};
+
+type NodeReportPredicate = Box<dyn Fn(&NodeReport) -> bool + Send>;
 
 #[derive(Debug)]
 struct NodeReportRequest {
     response_sender: oneshot::Sender<NodeReport>,
 }
 
+/// A request to be notified once the report satisfies a predicate.
+struct NodeReportWaiter {
+    predicate: NodeReportPredicate,
+    response_sender: oneshot::Sender<NodeReport>,
+}
+
+impl std::fmt::Debug for NodeReportWaiter {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "NodeReportWaiter")
+    }
+}
+
+#[derive(Debug)]
+pub enum WaitForError {
+    RequestTimerStreamError,
+    TimerClosed,
+    Timeout,
+}
+
 #[derive(Debug, Clone)]
 pub struct NodeReportClient {
     requests_sender: mpsc::Sender<NodeReportRequest>,
+    waiters_sender: mpsc::Sender<NodeReportWaiter>,
 }
 
 impl NodeReportClient {
-    fn new(requests_sender: mpsc::Sender<NodeReportRequest>) -> Self {
-        NodeReportClient { requests_sender }
+    fn new(
+        requests_sender: mpsc::Sender<NodeReportRequest>,
+        waiters_sender: mpsc::Sender<NodeReportWaiter>,
+    ) -> Self {
+        NodeReportClient {
+            requests_sender,
+            waiters_sender,
+        }
     }
 
     pub async fn request_report(&mut self) -> NodeReport {
@@ -31,42 +64,239 @@ impl NodeReportClient {
 
         response_receiver.await.unwrap()
     }
+
+    /// Wait until the report satisfies `predicate`, and return the satisfying report.
+    /// The predicate is checked against the current report, and then again after every incoming
+    /// report mutation. Gives up after `max_ticks` ticks of `timer_client`.
+    pub async fn wait_for<F>(
+        &mut self,
+        predicate: F,
+        mut timer_client: TimerClient,
+        max_ticks: usize,
+    ) -> Result<NodeReport, WaitForError>
+    where
+        F: Fn(&NodeReport) -> bool + Send + 'static,
+    {
+        let timer_stream = timer_client
+            .request_timer_stream("NodeReportClient::wait_for".to_owned())
+            .await
+            .map_err(|_| WaitForError::RequestTimerStreamError)?;
+
+        let (response_sender, response_receiver) = oneshot::channel();
+        let waiter = NodeReportWaiter {
+            predicate: Box::new(predicate),
+            response_sender,
+        };
+        self.waiters_sender.send(waiter).await.unwrap();
+
+        let mut timer_stream = timer_stream;
+        let mut response_receiver = response_receiver;
+        let mut ticks = 0usize;
+        loop {
+            match future::select(timer_stream.next(), response_receiver).await {
+                Either::Left((Some(_tick), new_response_receiver)) => {
+                    ticks = ticks.saturating_add(1);
+                    if ticks >= max_ticks {
+                        return Err(WaitForError::Timeout);
+                    }
+                    response_receiver = new_response_receiver;
+                }
+                Either::Left((None, _)) => return Err(WaitForError::TimerClosed),
+                Either::Right((res, _)) => return Ok(res.unwrap()),
+            }
+        }
+    }
+}
+
+/// Resolve all waiters whose predicate is satisfied by `node_report`.
+/// Waiters that were abandoned by their clients are removed.
+fn resolve_waiters(waiters: &mut Vec<NodeReportWaiter>, node_report: &NodeReport) {
+    let mut remaining = Vec::new();
+    for waiter in waiters.drain(..) {
+        if waiter.response_sender.is_canceled() {
+            continue;
+        }
+        if (waiter.predicate)(node_report) {
+            let _ = waiter.response_sender.send(node_report.clone());
+        } else {
+            remaining.push(waiter);
+        }
+    }
+    *waiters = remaining;
 }
 
 #[derive(Debug)]
 enum NodeReportServiceEvent {
     Request(NodeReportRequest),
+    Waiter(NodeReportWaiter),
     AppServerToApp(AppServerToApp),
     ServerClosed,
 }
 
 const APP_SERVER_TO_APP_CHANNEL_LEN: usize = 0x200;
 
-/// A service for maintaining knowledge of the current report
+/// Maximum amount of messages waiting for a lagging consumer.
+/// When reached, no more messages are taken from the server until the consumer catches up.
+const MAX_PENDING: usize = 0x200;
+
+/// A message sent to the consumer of the node report service
+#[derive(Debug)]
+pub enum NodeReportServiceOutput {
+    /// A message from the server
+    AppServerToApp(AppServerToApp),
+    /// The full current report. Sent in place of the report mutations a lagging consumer missed.
+    /// Should replace the consumer's knowledge of the report.
+    Snapshot(NodeReport),
+}
+
+impl NodeReportServiceOutput {
+    /// Get the message from the server, if this is not a snapshot
+    pub fn into_app_server_to_app(self) -> Option<AppServerToApp> {
+        match self {
+            NodeReportServiceOutput::AppServerToApp(app_server_to_app) => Some(app_server_to_app),
+            NodeReportServiceOutput::Snapshot(_) => None,
+        }
+    }
+}
+
+/// Messages from the server sent by the node report service, without report snapshots.
+/// Useful for consumers that only learn about the report through `NodeReportClient`.
+pub fn without_snapshots(
+    receiver: mpsc::Receiver<NodeReportServiceOutput>,
+) -> impl Stream<Item = AppServerToApp> {
+    receiver.filter_map(|output| future::ready(output.into_app_server_to_app()))
+}
+
+/// A message waiting for the consumer to catch up
+enum Pending {
+    AppServerToApp(AppServerToApp),
+    /// The report snapshot is taken when the message is sent, so that it contains all the
+    /// mutations received until then.
+    Snapshot,
+}
+
+impl Pending {
+    fn is_snapshot(&self) -> bool {
+        match self {
+            Pending::Snapshot => true,
+            Pending::AppServerToApp(_) => false,
+        }
+    }
+}
+
+/// Queue a message while the consumer lags behind.
+/// Report mutations are not queued. Instead, a full snapshot of the report is queued (unless one
+/// is already pending). Acknowledgements carried by report mutations are queued after the snapshot.
+fn queue_lagging(pending: &mut VecDeque<Pending>, app_server_to_app: AppServerToApp) {
+    match app_server_to_app {
+        AppServerToApp::ReportMutations(report_mutations) => {
+            if !report_mutations.mutations.is_empty()
+                && !pending
+                    .iter()
+                    .any(|pending_message| pending_message.is_snapshot())
+            {
+                pending.push_back(Pending::Snapshot);
+            }
+            if let Some(app_request_id) = report_mutations.opt_app_request_id {
+                pending.push_back(Pending::AppServerToApp(AppServerToApp::ReportMutations(
+                    ReportMutations {
+                        opt_app_request_id: Some(app_request_id),
+                        mutations: Vec::new(),
+                    },
+                )));
+            }
+        }
+        app_server_to_app => pending.push_back(Pending::AppServerToApp(app_server_to_app)),
+    }
+}
+
+/// Take the next message waiting for the consumer
+fn pop_pending(
+    pending: &mut VecDeque<Pending>,
+    node_report: &NodeReport,
+) -> NodeReportServiceOutput {
+    match pending.pop_front().unwrap() {
+        Pending::AppServerToApp(app_server_to_app) => {
+            NodeReportServiceOutput::AppServerToApp(app_server_to_app)
+        }
+        Pending::Snapshot => NodeReportServiceOutput::Snapshot(node_report.clone()),
+    }
+}
+
+/// A service for maintaining knowledge of the current report.
+///
+/// Messages from the server are forwarded through a bounded channel. If the consumer lags behind,
+/// the report mutations it missed are replaced by one full snapshot of the report (Only the
+/// acknowledgements they carry are kept). The current full report can also be obtained using
+/// `NodeReportClient::request_report()`.
 pub fn node_report_service<S, FS>(
     mut node_report: NodeReport,
     from_server: FS,
     spawner: &S,
-) -> (mpsc::Receiver<AppServerToApp>, NodeReportClient)
+) -> (mpsc::Receiver<NodeReportServiceOutput>, NodeReportClient)
 where
     S: Spawn,
     FS: Stream<Item = AppServerToApp> + Unpin + Send + 'static,
 {
     let (requests_sender, requests_receiver) = mpsc::channel(1);
+    let (waiters_sender, waiters_receiver) = mpsc::channel(1);
     let (mut app_sender, app_receiver) = mpsc::channel(APP_SERVER_TO_APP_CHANNEL_LEN);
 
     let requests_receiver = requests_receiver.map(NodeReportServiceEvent::Request);
+    let waiters_receiver = waiters_receiver.map(NodeReportServiceEvent::Waiter);
     let from_server = from_server
         .map(NodeReportServiceEvent::AppServerToApp)
         .chain(stream::once(future::ready(
             NodeReportServiceEvent::ServerClosed,
         )));
 
-    let mut incoming_events = select_streams![from_server, requests_receiver];
+    let mut incoming_events = select_streams![from_server, requests_receiver, waiters_receiver];
 
     spawner
         .spawn(async move {
-            while let Some(incoming_event) = incoming_events.next().await {
+            // Messages waiting for the consumer to catch up:
+            let mut pending = VecDeque::new();
+            // Clients waiting for the report to satisfy a predicate:
+            let mut waiters = Vec::new();
+            loop {
+                let opt_incoming_event = if pending.is_empty() {
+                    incoming_events.next().await
+                } else if pending.len() >= MAX_PENDING {
+                    // Too many messages are waiting. Wait for the consumer to catch up:
+                    match future::poll_fn(|context| app_sender.poll_ready(context)).await {
+                        Ok(()) => {
+                            let _ = app_sender.start_send(pop_pending(&mut pending, &node_report));
+                        }
+                        // The consumer is gone:
+                        Err(_) => pending.clear(),
+                    }
+                    continue;
+                } else {
+                    // Wait for either a new event, or for the consumer to be ready:
+                    let ready_fut = future::poll_fn(|context| app_sender.poll_ready(context));
+                    let select_res = match future::select(incoming_events.next(), ready_fut).await {
+                        Either::Left((opt_incoming_event, _)) => Either::Left(opt_incoming_event),
+                        Either::Right((ready_res, _)) => Either::Right(ready_res),
+                    };
+                    match select_res {
+                        Either::Left(opt_incoming_event) => opt_incoming_event,
+                        Either::Right(Ok(())) => {
+                            let _ = app_sender.start_send(pop_pending(&mut pending, &node_report));
+                            continue;
+                        }
+                        Either::Right(Err(_)) => {
+                            // The consumer is gone:
+                            pending.clear();
+                            continue;
+                        }
+                    }
+                };
+
+                let incoming_event = match opt_incoming_event {
+                    Some(incoming_event) => incoming_event,
+                    None => return,
+                };
+
                 match incoming_event {
                     NodeReportServiceEvent::Request(report_request) => {
                         report_request
@@ -74,6 +304,10 @@ where
                             .send(node_report.clone())
                             .unwrap();
                     }
+                    NodeReportServiceEvent::Waiter(waiter) => {
+                        waiters.push(waiter);
+                        resolve_waiters(&mut waiters, &node_report);
+                    }
                     NodeReportServiceEvent::AppServerToApp(app_server_to_app) => {
                         if let AppServerToApp::ReportMutations(report_mutations) =
                             &app_server_to_app
@@ -81,8 +315,25 @@ where
                             for mutation in &report_mutations.mutations {
                                 node_report.mutate(&mutation).unwrap();
                             }
+                            resolve_waiters(&mut waiters, &node_report);
                         }
-                        let _ = app_sender.send(app_server_to_app).await;
+
+                        // Keep the original order of messages:
+                        let app_server_to_app = if pending.is_empty() {
+                            let output = NodeReportServiceOutput::AppServerToApp(app_server_to_app);
+                            match app_sender.try_send(output) {
+                                Ok(()) => continue,
+                                Err(e) if e.is_full() => {
+                                    e.into_inner().into_app_server_to_app().unwrap()
+                                }
+                                // The consumer is gone:
+                                Err(_) => continue,
+                            }
+                        } else {
+                            app_server_to_app
+                        };
+
+                        queue_lagging(&mut pending, app_server_to_app);
                     }
                     NodeReportServiceEvent::ServerClosed => {
                         return;
@@ -92,5 +343,224 @@ where
         })
         .unwrap();
 
-    (app_receiver, NodeReportClient::new(requests_sender))
+    (
+        app_receiver,
+        NodeReportClient::new(requests_sender, waiters_sender),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryFrom;
+
+    use futures::executor::LocalPool;
+
+    use timer::create_timer_incoming;
+
+    use app::common::{InvoiceId, NamedRelayAddress, NetAddress, PublicKey, Uid};
+    use app::conn::{CommitInvoiceResult, ResponseCommitInvoice};
+    use app::report::{FunderReportMutation, NodeReportMutation};
+
+    fn relay_address(index: u16) -> NamedRelayAddress {
+        let mut public_key = [0u8; PublicKey::len()];
+        public_key[..2].copy_from_slice(&index.to_be_bytes());
+        NamedRelayAddress {
+            public_key: PublicKey::from(&public_key),
+            address: NetAddress::try_from(format!("relay{}", index)).unwrap(),
+            name: format!("relay{}", index),
+        }
+    }
+
+    #[test]
+    fn test_node_report_service_slow_consumer() {
+        let node_report = NodeReport::dummy();
+
+        let mut local_pool = LocalPool::new();
+        let (mut server_sender, from_server) = mpsc::channel(0);
+        let (mut app_receiver, mut report_client) =
+            node_report_service(node_report, from_server, &local_pool.spawner());
+
+        let num_relays = 4 * APP_SERVER_TO_APP_CHANNEL_LEN as u16;
+        let app_request_id = Uid::from(&[1; Uid::len()]);
+
+        local_pool.run_until(async move {
+            // The consumer does not read anything while the server sends many mutations:
+            for i in 0..num_relays {
+                let mutation =
+                    NodeReportMutation::Funder(FunderReportMutation::AddRelay(relay_address(i)));
+                let opt_app_request_id = if i == num_relays - 1 {
+                    Some(app_request_id.clone())
+                } else {
+                    None
+                };
+                server_sender
+                    .send(AppServerToApp::ReportMutations(ReportMutations {
+                        opt_app_request_id,
+                        mutations: vec![mutation],
+                    }))
+                    .await
+                    .unwrap();
+            }
+
+            // The current report is still available, and contains all the mutations:
+            let node_report = report_client.request_report().await;
+            assert_eq!(
+                node_report.funder_report.relays,
+                (0..num_relays).map(relay_address).collect::<Vec<_>>()
+            );
+
+            // The consumer catches up. Only a bounded amount of messages was buffered, the
+            // consumer's view of the report is correct, and the acknowledgement was not lost:
+            let mut consumer_report: NodeReport = NodeReport::dummy();
+            let mut num_received = 0usize;
+            loop {
+                num_received += 1;
+                match app_receiver.next().await.unwrap() {
+                    NodeReportServiceOutput::AppServerToApp(AppServerToApp::ReportMutations(
+                        report_mutations,
+                    )) => {
+                        for mutation in &report_mutations.mutations {
+                            consumer_report.mutate(mutation).unwrap();
+                        }
+                        if report_mutations.opt_app_request_id == Some(app_request_id.clone()) {
+                            break;
+                        }
+                    }
+                    NodeReportServiceOutput::Snapshot(snapshot) => consumer_report = snapshot,
+                    _ => unreachable!(),
+                }
+            }
+            assert!(num_received <= APP_SERVER_TO_APP_CHANNEL_LEN + 3);
+            assert_eq!(consumer_report, node_report);
+        });
+    }
+
+    #[test]
+    fn test_node_report_service_max_pending() {
+        let mut local_pool = LocalPool::new();
+        let (mut server_sender, from_server) = mpsc::channel(0);
+        let (mut app_receiver, _report_client) =
+            node_report_service(NodeReport::dummy(), from_server, &local_pool.spawner());
+
+        let num_messages = APP_SERVER_TO_APP_CHANNEL_LEN + MAX_PENDING + 0x10;
+        let invoice_id = |i: usize| {
+            let mut invoice_id = [0u8; InvoiceId::len()];
+            invoice_id[..8].copy_from_slice(&(i as u64).to_be_bytes());
+            InvoiceId::from(&invoice_id)
+        };
+
+        // The server sends many messages that can not be coalesced:
+        let (done_sender, mut done_receiver) = oneshot::channel();
+        local_pool
+            .spawner()
+            .spawn(async move {
+                for i in 0..num_messages {
+                    server_sender
+                        .send(AppServerToApp::ResponseCommitInvoice(
+                            ResponseCommitInvoice {
+                                invoice_id: invoice_id(i),
+                                result: CommitInvoiceResult::Success,
+                            },
+                        ))
+                        .await
+                        .unwrap();
+                }
+                done_sender.send(()).unwrap();
+            })
+            .unwrap();
+
+        // The consumer does not read anything. Once the queue is full, the server has to wait:
+        local_pool.run_until_stalled();
+        assert_eq!(done_receiver.try_recv(), Ok(None));
+
+        // The consumer catches up, and receives all the messages in order:
+        local_pool.run_until(async move {
+            for i in 0..num_messages {
+                match app_receiver.next().await.unwrap() {
+                    NodeReportServiceOutput::AppServerToApp(
+                        AppServerToApp::ResponseCommitInvoice(response_commit_invoice),
+                    ) => assert_eq!(response_commit_invoice.invoice_id, invoice_id(i)),
+                    _ => unreachable!(),
+                }
+            }
+            done_receiver.await.unwrap();
+        });
+    }
+
+    #[test]
+    fn test_node_report_service_wait_for() {
+        let mut local_pool = LocalPool::new();
+        let (mut tick_sender, tick_receiver) = mpsc::channel(0);
+        let timer_client = create_timer_incoming(tick_receiver, local_pool.spawner()).unwrap();
+
+        let (mut server_sender, from_server) = mpsc::channel(0);
+        let (_app_receiver, report_client) =
+            node_report_service(NodeReport::dummy(), from_server, &local_pool.spawner());
+
+        // Wait for a relay to be added:
+        let mut c_report_client = report_client.clone();
+        let c_timer_client = timer_client.clone();
+        let wait_handle = local_pool
+            .spawner()
+            .spawn_with_handle(async move {
+                c_report_client
+                    .wait_for(
+                        |node_report| node_report.funder_report.relays.len() >= 2,
+                        c_timer_client,
+                        4,
+                    )
+                    .await
+            })
+            .unwrap();
+
+        let node_report = local_pool.run_until(async move {
+            for i in 0..2 {
+                let mutation =
+                    NodeReportMutation::Funder(FunderReportMutation::AddRelay(relay_address(i)));
+                server_sender
+                    .send(AppServerToApp::ReportMutations(ReportMutations {
+                        opt_app_request_id: None,
+                        mutations: vec![mutation],
+                    }))
+                    .await
+                    .unwrap();
+                tick_sender.send(()).await.unwrap();
+            }
+            wait_handle.await.unwrap()
+        });
+        assert_eq!(
+            node_report.funder_report.relays,
+            vec![relay_address(0), relay_address(1)]
+        );
+    }
+
+    #[test]
+    fn test_node_report_service_wait_for_timeout() {
+        let mut local_pool = LocalPool::new();
+        let (mut tick_sender, tick_receiver) = mpsc::channel(0);
+        let timer_client = create_timer_incoming(tick_receiver, local_pool.spawner()).unwrap();
+
+        let (_server_sender, from_server) = mpsc::channel(0);
+        let (_app_receiver, mut report_client) =
+            node_report_service(NodeReport::dummy(), from_server, &local_pool.spawner());
+
+        // Time keeps passing:
+        local_pool
+            .spawner()
+            .spawn(async move { while tick_sender.send(()).await.is_ok() {} })
+            .unwrap();
+
+        // The predicate never holds:
+        let res = local_pool.run_until(report_client.wait_for(
+            |node_report| !node_report.funder_report.relays.is_empty(),
+            timer_client,
+            4,
+        ));
+        match res {
+            Err(WaitForError::Timeout) => {}
+            _ => unreachable!(),
+        }
+    }
 }
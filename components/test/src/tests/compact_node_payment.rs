@@ -15,8 +15,6 @@ use proto::funder::messages::{Currency, Rate, Receipt};
 
 use timer::create_timer_incoming;
 
-use app::gen::gen_uid;
-
 use stcompact::compact_node::messages::{
     AddFriend, AddInvoice, CompactToUser, CompactToUserAck, ConfirmPaymentFees,
     FriendLivenessReport, InitPayment, OpenFriendCurrency, OpenPaymentStatus, PaymentDoneStatus,
@@ -28,7 +26,8 @@ use crate::compact_node_wrapper::send_request;
 use crate::sim_network::create_sim_network;
 use crate::utils::{
     advance_time, create_compact_node, create_index_server, create_node, create_relay,
-    named_index_server_address, named_relay_address, node_public_key, relay_address, SimDb,
+    gen_seeded_uid, named_index_server_address, named_relay_address, node_public_key,
+    relay_address, SimDb,
 };
 
 use crate::compact_report_service::{compact_report_service, CompactReportClient};
@@ -152,7 +151,7 @@ async fn make_test_payment(
     // ... Node0 now passes the commit to Node1 out of band ...
 
     // Node1: Verify the commit:
-    let verify_request_id = gen_uid();
+    let verify_request_id = gen_seeded_uid();
     let request_verify_commit = RequestVerifyCommit {
         request_id: verify_request_id.clone(),
         // seller_public_key: seller_public_key.clone(),
@@ -15,8 +15,6 @@ use proto::funder::messages::{Currency, Rate, Receipt};
 
 use timer::create_timer_incoming;
 
-use app::gen::gen_uid;
-
 use stcompact::compact_node::messages::{
     AddFriend, AddInvoice, CompactToUser, ConfirmPaymentFees, InitPayment, OpenFriendCurrency,
     PaymentDoneStatus, PaymentFeesResponse, RequestVerifyCommit, SetFriendCurrencyMaxDebt,
@@ -31,8 +29,8 @@ use crate::compact_server_wrapper::send_request;
 use crate::sim_network::create_sim_network;
 use crate::utils::{
     advance_time, app_private_key, create_compact_server, create_index_server, create_node,
-    create_relay, listen_node_address, named_index_server_address, named_relay_address,
-    node_public_key, relay_address, SimDb,
+    create_relay, gen_seeded_uid, listen_node_address, named_index_server_address,
+    named_relay_address, node_public_key, relay_address, SimDb,
 };
 
 const TIMER_CHANNEL_LEN: usize = 0;
@@ -177,7 +175,7 @@ async fn make_test_payment(
     // ... Node0 now passes the commit to Node1 out of band ...
 
     // Node1: Verify the commit:
-    let verify_request_id = gen_uid();
+    let verify_request_id = gen_seeded_uid();
     let request_verify_commit = RequestVerifyCommit {
         request_id: verify_request_id.clone(),
         commit: commit.clone(),
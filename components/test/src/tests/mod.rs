@@ -5,4 +5,5 @@ mod nodes_chain;
 mod relay_migration;
 mod resolve_inconsistency;
 mod serialize;
+mod sim_determinism;
 mod two_nodes_payment;
@@ -25,7 +25,7 @@ use crate::utils::{
     named_index_server_address, named_relay_address, node_public_key, relay_address, SimDb,
 };
 
-use crate::node_report_service::{node_report_service, NodeReportClient};
+use crate::node_report_service::{node_report_service, without_snapshots, NodeReportClient};
 
 const TIMER_CHANNEL_LEN: usize = 0;
 
@@ -95,7 +95,7 @@ async fn task_nodes_chain(mut test_executor: TestExecutor) {
         // Create report service (Allowing to query reports):
         let (sender, receiver) = conn_pair.split();
         let (receiver, report_client) = node_report_service(node_report, receiver, &test_executor);
-        let conn_pair = ConnPairApp::from_raw(sender, receiver);
+        let conn_pair = ConnPairApp::from_raw(sender, without_snapshots(receiver));
 
         let app = AppControl {
             permissions,
@@ -17,7 +17,7 @@ use crate::utils::{
     relay_address, relay_public_key, SimDb,
 };
 
-use crate::node_report_service::{node_report_service, NodeReportClient};
+use crate::node_report_service::{node_report_service, without_snapshots, NodeReportClient};
 
 use crate::app_wrapper::send_request;
 use crate::sim_network::create_sim_network;
@@ -191,12 +191,12 @@ async fn task_relay_migration(mut test_executor: TestExecutor) {
     let (sender0, receiver0) = conn_pair0.split();
     let (receiver0, mut report_client0) =
         node_report_service(node_report0, receiver0, &test_executor);
-    let mut conn_pair0 = ConnPairApp::from_raw(sender0, receiver0);
+    let mut conn_pair0 = ConnPairApp::from_raw(sender0, without_snapshots(receiver0));
 
     let (sender1, receiver1) = conn_pair1.split();
     let (receiver1, mut report_client1) =
         node_report_service(node_report1, receiver1, &test_executor);
-    let mut conn_pair1 = ConnPairApp::from_raw(sender1, receiver1);
+    let mut conn_pair1 = ConnPairApp::from_raw(sender1, without_snapshots(receiver1));
 
     // Configure relays:
     send_request(
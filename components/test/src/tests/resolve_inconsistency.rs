@@ -13,13 +13,14 @@ use proto::funder::messages::{Currency, FriendsRoute, PaymentStatus, PaymentStat
 use proto::report::messages::ChannelStatusReport;
 
 use app::conn::{self, ConnPairApp, RequestResult};
+use app::report::NodeReport;
 
 use timer::create_timer_incoming;
 
-use crate::node_report_service::node_report_service;
+use crate::node_report_service::{node_report_service, without_snapshots};
 use crate::utils::{
-    advance_time, create_app, create_node, create_relay, named_relay_address, node_public_key,
-    relay_address, SimDb,
+    advance_time, advance_until, create_app, create_node, create_relay, named_relay_address,
+    node_public_key, relay_address, SimDb,
 };
 
 use crate::sim_network::create_sim_network;
@@ -30,6 +31,18 @@ use crate::app_wrapper::{
 
 const TIMER_CHANNEL_LEN: usize = 0;
 
+/// Does `node_report` show a consistent channel with the given friend?
+fn is_channel_consistent(node_report: &NodeReport, friend_public_key: &PublicKey) -> bool {
+    node_report
+        .funder_report
+        .friends
+        .get(friend_public_key)
+        .map_or(false, |friend_report| match &friend_report.channel_status {
+            ChannelStatusReport::Consistent(_) => true,
+            ChannelStatusReport::Inconsistent(_) => false,
+        })
+}
+
 /// Perform a basic payment between a buyer and a seller.
 /// Use a trivial route of [buyer, seller] instead of using an index server.
 /// Node0 sends credits to Node1
@@ -252,12 +265,12 @@ async fn task_resolve_inconsistency(mut test_executor: TestExecutor) {
     let (sender0, receiver0) = conn_pair0.split();
     let (receiver0, mut report_client0) =
         node_report_service(node_report0, receiver0, &test_executor);
-    let mut conn_pair0 = ConnPairApp::from_raw(sender0, receiver0);
+    let mut conn_pair0 = ConnPairApp::from_raw(sender0, without_snapshots(receiver0));
 
     let (sender1, receiver1) = conn_pair1.split();
     let (receiver1, mut report_client1) =
         node_report_service(node_report1, receiver1, &test_executor);
-    let mut conn_pair1 = ConnPairApp::from_raw(sender1, receiver1);
+    let mut conn_pair1 = ConnPairApp::from_raw(sender1, without_snapshots(receiver1));
 
     // Configure relays:
     send_request(
@@ -475,41 +488,25 @@ async fn task_resolve_inconsistency(mut test_executor: TestExecutor) {
     .await
     .unwrap();
 
-    advance_time(40, &mut tick_sender, &test_executor).await;
-
-    // Node0: Channel should be consistent now:
-    loop {
-        let node_report = report_client0.request_report().await;
-        let opt_friend_report = node_report.funder_report.friends.get(&node_public_key(1));
-
-        let friend_report = if let Some(friend_report) = opt_friend_report {
-            friend_report
-        } else {
-            continue;
-        };
-
-        match &friend_report.channel_status {
-            ChannelStatusReport::Consistent(_) => break,
-            ChannelStatusReport::Inconsistent(_) => continue,
-        };
-    }
-
-    // Node1: Channel should be consistent now:
-    loop {
-        let node_report = report_client1.request_report().await;
-        let opt_friend_report = node_report.funder_report.friends.get(&node_public_key(0));
-
-        let friend_report = if let Some(friend_report) = opt_friend_report {
-            friend_report
-        } else {
-            continue;
-        };
-
-        match &friend_report.channel_status {
-            ChannelStatusReport::Consistent(_) => break,
-            ChannelStatusReport::Inconsistent(_) => continue,
-        };
-    }
+    // Both sides should perceive the channel to be consistent now:
+    advance_until(
+        || {
+            let mut report_client0 = report_client0.clone();
+            let mut report_client1 = report_client1.clone();
+            async move {
+                is_channel_consistent(&report_client0.request_report().await, &node_public_key(1))
+                    && is_channel_consistent(
+                        &report_client1.request_report().await,
+                        &node_public_key(0),
+                    )
+            }
+        },
+        &mut tick_sender,
+        &test_executor,
+        40,
+    )
+    .await
+    .unwrap();
 
     // Let both sides open the channel:
     send_request(
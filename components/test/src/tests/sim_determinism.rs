@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use futures::channel::mpsc;
+
+use tempfile::tempdir;
+
+use common::test_executor::TestExecutor;
+
+use proto::app_server::messages::AppPermissions;
+use proto::crypto::Uid;
+use proto::funder::messages::{Currency, Rate};
+
+use timer::create_timer_incoming;
+
+use app::conn::{self, ConnPairApp};
+use app::report::NodeReport;
+
+use crate::app_wrapper::send_request;
+use crate::sim_network::create_sim_network;
+use crate::utils::{
+    advance_time, create_app, create_node, create_relay, env_test_seed, gen_seeded_uid,
+    named_relay_address, node_public_key, relay_address, SimDb, TestSeedGuard,
+};
+
+use crate::node_report_service::{node_report_service, without_snapshots};
+
+const TIMER_CHANNEL_LEN: usize = 0;
+
+/// Everything we compare between two runs of the same scenario.
+#[derive(Debug, PartialEq, Eq)]
+struct SimOutcome {
+    node_report0: NodeReport,
+    node_report1: NodeReport,
+    last_uid: Uid,
+}
+
+/// Two nodes become friends and open a currency.
+async fn task_sim_determinism(mut test_executor: TestExecutor) -> SimOutcome {
+    let currency1 = Currency::try_from("FST1".to_owned()).unwrap();
+
+    // Create timer_client:
+    let (mut tick_sender, tick_receiver) = mpsc::channel(TIMER_CHANNEL_LEN);
+    let timer_client = create_timer_incoming(tick_receiver, test_executor.clone()).unwrap();
+
+    // Create a temporary directory.
+    // Should be deleted when gets out of scope:
+    let temp_dir = tempdir().unwrap();
+
+    // Create a database manager at the temporary directory:
+    let sim_db = SimDb::new(temp_dir.path().to_path_buf());
+
+    // A network simulator:
+    let sim_net_client = create_sim_network(&mut test_executor);
+
+    let mut conn_pairs = Vec::new();
+    let mut report_clients = Vec::new();
+    for index in 0..2u8 {
+        sim_db.init_node_db(index).unwrap();
+
+        let mut trusted_apps = HashMap::new();
+        trusted_apps.insert(
+            index,
+            AppPermissions {
+                routes: true,
+                buyer: true,
+                seller: true,
+                config: true,
+            },
+        );
+
+        create_node(
+            index,
+            sim_db.clone(),
+            timer_client.clone(),
+            sim_net_client.clone(),
+            trusted_apps,
+            test_executor.clone(),
+        )
+        .await
+        .forget();
+
+        let (_permissions, node_report, conn_pair) = create_app(
+            index,
+            sim_net_client.clone(),
+            timer_client.clone(),
+            index,
+            test_executor.clone(),
+        )
+        .await
+        .unwrap();
+
+        create_relay(
+            index,
+            timer_client.clone(),
+            sim_net_client.clone(),
+            test_executor.clone(),
+        )
+        .await;
+
+        let (sender, receiver) = conn_pair.split();
+        let (receiver, report_client) = node_report_service(node_report, receiver, &test_executor);
+        conn_pairs.push(ConnPairApp::from_raw(sender, without_snapshots(receiver)));
+        report_clients.push(report_client);
+    }
+
+    for index in 0..2u8 {
+        let friend_index = 1 - index;
+        let conn_pair = &mut conn_pairs[usize::from(index)];
+        send_request(
+            conn_pair,
+            conn::config::add_relay(named_relay_address(index)),
+        )
+        .await
+        .unwrap();
+        send_request(
+            conn_pair,
+            conn::config::add_friend(
+                node_public_key(friend_index),
+                vec![relay_address(friend_index)],
+                format!("node{}", friend_index),
+            ),
+        )
+        .await
+        .unwrap();
+        send_request(
+            conn_pair,
+            conn::config::enable_friend(node_public_key(friend_index)),
+        )
+        .await
+        .unwrap();
+        send_request(
+            conn_pair,
+            conn::config::set_friend_currency_rate(
+                node_public_key(friend_index),
+                currency1.clone(),
+                Rate::new(),
+            ),
+        )
+        .await
+        .unwrap();
+        send_request(
+            conn_pair,
+            conn::config::open_friend_currency(node_public_key(friend_index), currency1.clone()),
+        )
+        .await
+        .unwrap();
+    }
+
+    advance_time(40, &mut tick_sender, &test_executor).await;
+
+    SimOutcome {
+        node_report0: report_clients[0].request_report().await,
+        node_report1: report_clients[1].request_report().await,
+        last_uid: gen_seeded_uid(),
+    }
+}
+
+fn run_sim_determinism(seed: u64) -> SimOutcome {
+    let _seed_guard = TestSeedGuard::new(seed);
+    let test_executor = TestExecutor::new();
+    let res = test_executor.run(task_sim_determinism(test_executor.clone()));
+    res.output().unwrap()
+}
+
+#[test]
+fn test_sim_determinism() {
+    let seed = env_test_seed();
+    let outcome1 = run_sim_determinism(seed);
+    let outcome2 = run_sim_determinism(seed);
+    assert_eq!(outcome1, outcome2);
+
+    // The friends should have become online during the simulation:
+    let friend_report = outcome1
+        .node_report0
+        .funder_report
+        .friends
+        .get(&node_public_key(1))
+        .unwrap();
+    assert!(friend_report.liveness.is_online());
+}
@@ -13,7 +13,8 @@ use proto::funder::messages::{Currency, FriendsRoute, PaymentStatus, PaymentStat
 
 use timer::create_timer_incoming;
 
-use app::conn::{self, ConnPairApp, RequestResult};
+use app::conn::{self, CancelReason, ConnPairApp, RequestResult};
+use app::report::NodeReport;
 
 use crate::app_wrapper::{
     ack_close_payment, create_transaction, request_close_payment, request_routes, send_request,
@@ -22,12 +23,24 @@ use crate::sim_network::create_sim_network;
 use crate::utils::{
     advance_time, create_app, create_index_server, create_node, create_relay,
     named_index_server_address, named_relay_address, node_public_key, relay_address, SimDb,
+    TestSeedGuard,
 };
 
-use crate::node_report_service::node_report_service;
+use crate::node_report_service::{node_report_service, without_snapshots};
 
 const TIMER_CHANNEL_LEN: usize = 0;
 
+/// A predicate that holds when the given friend is online.
+fn friend_online(friend_public_key: PublicKey) -> impl Fn(&NodeReport) -> bool {
+    move |node_report| {
+        node_report
+            .funder_report
+            .friends
+            .get(&friend_public_key)
+            .map_or(false, |friend_report| friend_report.liveness.is_online())
+    }
+}
+
 /// Perform a basic payment between a buyer and a seller.
 /// Node0 sends credits to Node1
 async fn make_test_payment(
@@ -292,12 +305,12 @@ async fn task_two_nodes_payment(mut test_executor: TestExecutor) {
     let (sender0, receiver0) = conn_pair0.split();
     let (receiver0, mut report_client0) =
         node_report_service(node_report0, receiver0, &test_executor);
-    let mut conn_pair0 = ConnPairApp::from_raw(sender0, receiver0);
+    let mut conn_pair0 = ConnPairApp::from_raw(sender0, without_snapshots(receiver0));
 
     let (sender1, receiver1) = conn_pair1.split();
     let (receiver1, mut report_client1) =
         node_report_service(node_report1, receiver1, &test_executor);
-    let mut conn_pair1 = ConnPairApp::from_raw(sender1, receiver1);
+    let mut conn_pair1 = ConnPairApp::from_raw(sender1, without_snapshots(receiver1));
 
     // Configure relays:
     send_request(
@@ -387,27 +400,22 @@ async fn task_two_nodes_payment(mut test_executor: TestExecutor) {
 
     advance_time(40, &mut tick_sender, &test_executor).await;
 
-    loop {
-        let node_report0 = report_client0.request_report().await;
-        let friend_report = match node_report0.funder_report.friends.get(&node_public_key(1)) {
-            None => continue,
-            Some(friend_report) => friend_report,
-        };
-        if friend_report.liveness.is_online() {
-            break;
-        }
-    }
-
-    loop {
-        let node_report1 = report_client1.request_report().await;
-        let friend_report = match node_report1.funder_report.friends.get(&node_public_key(0)) {
-            None => continue,
-            Some(friend_report) => friend_report,
-        };
-        if friend_report.liveness.is_online() {
-            break;
-        }
-    }
+    report_client0
+        .wait_for(
+            friend_online(node_public_key(1)),
+            timer_client.clone(),
+            0x40,
+        )
+        .await
+        .unwrap();
+    report_client1
+        .wait_for(
+            friend_online(node_public_key(0)),
+            timer_client.clone(),
+            0x40,
+        )
+        .await
+        .unwrap();
 
     // Set active currencies for both sides:
     for currency in [&currency1, &currency2, &currency3].iter() {
@@ -592,7 +600,11 @@ async fn task_two_nodes_payment(mut test_executor: TestExecutor) {
     .await
     .unwrap();
 
-    assert_eq!(res, RequestResult::Failure);
+    // Node0 does not trust Node1 with this amount of credits:
+    assert_eq!(
+        res,
+        RequestResult::Failure(Some(CancelReason::InsufficientTrust))
+    );
 
     // Node0: Check the payment's result:
     let payment_status = request_close_payment(&mut conn_pair1, payment_id.clone())
@@ -608,10 +620,75 @@ async fn task_two_nodes_payment(mut test_executor: TestExecutor) {
         }
         _ => unreachable!(),
     }
+
+    // Node0: Stop trading currency3 with Node1:
+    let node_report0 = report_client0.request_report().await;
+    let app_requests =
+        conn::config::stop_friend_currency(&node_report0, &node_public_key(1), &currency3).unwrap();
+    for app_request in app_requests {
+        send_request(&mut conn_pair0, app_request).await.unwrap();
+    }
+
+    // Node0: Stop trading currency1 with Node1.
+    // currency1 is active, so it can not be removed:
+    let node_report0 = report_client0.request_report().await;
+    let app_requests =
+        conn::config::stop_friend_currency(&node_report0, &node_public_key(1), &currency1).unwrap();
+    for app_request in app_requests {
+        send_request(&mut conn_pair0, app_request).await.unwrap();
+    }
+
+    // Only currency3 should be removed:
+    let node_report0 = report_client0.request_report().await;
+    let friend_report = node_report0
+        .funder_report
+        .friends
+        .get(&node_public_key(1))
+        .unwrap();
+    let configured_currencies: Vec<_> = friend_report
+        .currency_configs
+        .iter()
+        .map(|currency_config| currency_config.currency.clone())
+        .collect();
+    assert!(!configured_currencies.contains(&currency3));
+    assert!(configured_currencies.contains(&currency1));
+    assert!(configured_currencies.contains(&currency2));
+
+    // currency1 is closed, and Node1 can not take new credit in currency1:
+    let currency1_config = friend_report
+        .currency_configs
+        .iter()
+        .find(|currency_config| currency_config.currency == currency1)
+        .unwrap();
+    assert!(!currency1_config.is_open);
+    assert_eq!(currency1_config.remote_max_debt, 0);
+
+    advance_time(40, &mut tick_sender, &test_executor).await;
+
+    // Other currencies still function.
+    // Send 3 = 1 + 2 currency2 credits from node0 to node1:
+    let payment_status = make_test_payment(
+        &mut conn_pair0,
+        &mut conn_pair1,
+        node_public_key(0),
+        node_public_key(1),
+        currency2.clone(),
+        1u128, // total_dest_payment
+        2u128, // fees
+        tick_sender.clone(),
+        test_executor.clone(),
+    )
+    .await;
+
+    if let PaymentStatus::Success(_) = payment_status {
+    } else {
+        unreachable!();
+    };
 }
 
 #[test]
 fn test_two_nodes_payment() {
+    let _seed_guard = TestSeedGuard::from_env();
     let test_executor = TestExecutor::new();
     let res = test_executor.run(task_two_nodes_payment(test_executor.clone()));
     assert!(res.is_output());
@@ -1,10 +1,12 @@
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
+use std::env;
 use std::path::PathBuf;
 
 use futures::channel::mpsc;
 use futures::future::RemoteHandle;
 use futures::task::{Spawn, SpawnExt};
-use futures::{future, FutureExt, SinkExt, TryFutureExt};
+use futures::{future, Future, FutureExt, SinkExt, TryFutureExt};
 
 use crypto::identity::{Identity, SoftwareEd25519Identity};
 
@@ -15,10 +17,14 @@ use common::test_executor::TestExecutor;
 
 use common::conn::{BoxFuture, ConnPair};
 
-use proto::crypto::{PrivateKey, PublicKey};
+use proto::crypto::{PrivateKey, PublicKey, Uid};
 
 use proto::app_server::messages::{AppPermissions, NamedRelayAddress, RelayAddress};
-use proto::consts::{KEEPALIVE_TICKS, MAX_NODE_RELAYS, MAX_OPERATIONS_IN_BATCH, TICKS_TO_REKEY};
+use proto::consts::{
+    INDEX_MUTATIONS_BATCH_TICKS, KEEPALIVE_TICKS, MAX_APP_QUEUED_MESSAGES,
+    MAX_MOVE_TOKEN_RETRANSMITS, MAX_NODE_RELAYS, MAX_OPERATIONS_IN_BATCH,
+    PENDING_TRANSACTION_TIMEOUT_TICKS, TICKS_TO_REKEY,
+};
 use proto::index_server::messages::NamedIndexServerAddress;
 use proto::net::messages::NetAddress;
 
@@ -63,6 +69,96 @@ const MAX_OPEN_INDEX_CLIENT_REQUESTS: usize = 0x8;
 /// The amount of ticks we are willing to wait until a connection is established (Through
 /// the relay)
 const CONN_TIMEOUT_TICKS: usize = 0x8;
+/// Maximum amount of concurrently open incoming app connections.
+const MAX_INCOMING_APP_CONNS: usize = 0x20;
+/// Maximum amount of concurrently open incoming connections to a relay.
+const MAX_RELAY_CONNS: usize = 0x100;
+/// Environment variable used to pick the simulation seed (Defaults to 0).
+const TEST_SEED_ENV: &str = "OFFSET_TEST_SEED";
+
+thread_local! {
+    /// Seed installed by the currently active `TestSeedGuard` (If any).
+    static TEST_SEED: Cell<Option<u64>> = Cell::new(None);
+    /// Generator used for request ids sent by the test wrappers.
+    static TEST_UID_RNG: RefCell<Option<DummyRandom>> = RefCell::new(None);
+}
+
+/// Read the simulation seed from the environment.
+pub fn env_test_seed() -> u64 {
+    env::var(TEST_SEED_ENV)
+        .ok()
+        .and_then(|seed_str| seed_str.parse().ok())
+        .unwrap_or(0)
+}
+
+/// The seed used for all the randomness of the current simulation.
+///
+/// Note that the simulated network and the `TestExecutor` schedule tasks in a fixed order, so
+/// the seed is the only source of variation between two runs of the same scenario.
+pub fn test_seed() -> u64 {
+    TEST_SEED.with(|test_seed| test_seed.get().unwrap_or_else(env_test_seed))
+}
+
+/// Installs a simulation seed for the current thread.
+/// If the thread panics while the guard is alive (A failing test), the seed is printed, so that
+/// the failure can be reproduced by setting `OFFSET_TEST_SEED`.
+pub struct TestSeedGuard {
+    seed: u64,
+}
+
+impl TestSeedGuard {
+    pub fn new(seed: u64) -> Self {
+        TEST_SEED.with(|test_seed| test_seed.set(Some(seed)));
+        TEST_UID_RNG.with(|uid_rng| *uid_rng.borrow_mut() = None);
+        TestSeedGuard { seed }
+    }
+
+    /// Use the seed from the environment (See `env_test_seed()`)
+    pub fn from_env() -> Self {
+        TestSeedGuard::new(env_test_seed())
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl Drop for TestSeedGuard {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            eprintln!(
+                "Simulation failed with seed {}. Reproduce with {}={}",
+                self.seed, TEST_SEED_ENV, self.seed
+            );
+        }
+        TEST_SEED.with(|test_seed| test_seed.set(None));
+        TEST_UID_RNG.with(|uid_rng| *uid_rng.borrow_mut() = None);
+    }
+}
+
+/// Create a random generator for a simulated entity.
+/// The entity seed is extended with the simulation seed. The default seed (0) leaves the entity
+/// seed as is, resulting in the same generator as `DummyRandom::new(entity_seed)`.
+///
+/// Identities are not seeded: Tests refer to nodes, relays and index servers by index.
+fn seeded_rng(entity_seed: &[u8]) -> DummyRandom {
+    let test_seed = test_seed();
+    if test_seed == 0 {
+        return DummyRandom::new(entity_seed);
+    }
+    let mut seed = entity_seed.to_vec();
+    seed.extend_from_slice(&test_seed.to_be_bytes());
+    DummyRandom::new(&seed)
+}
+
+/// Generate a Uid deterministically from the simulation seed.
+pub fn gen_seeded_uid() -> Uid {
+    TEST_UID_RNG.with(|uid_rng| {
+        let mut opt_rng = uid_rng.borrow_mut();
+        let rng = opt_rng.get_or_insert_with(|| seeded_rng(&[0xff, 0x13, 0x3c]));
+        Uid::rand_gen(rng)
+    })
+}
 
 fn gen_identity<R>(rng: &R) -> impl Identity
 where
@@ -131,8 +227,22 @@ fn default_node_config() -> NodeConfig {
         max_pending_user_requests: MAX_PENDING_USER_REQUESTS,
         /// Maximum amount of concurrent index client requests:
         max_open_index_client_requests: MAX_OPEN_INDEX_CLIENT_REQUESTS,
+        /// The amount of ticks the index client collects mutations before sending them.
+        index_mutations_batch_ticks: INDEX_MUTATIONS_BATCH_TICKS,
         /// Maximum amount of relays a node may use.
         max_node_relays: MAX_NODE_RELAYS,
+        /// The amount of ticks we wait for a response to a pending request before canceling it.
+        pending_transaction_timeout_ticks: PENDING_TRANSACTION_TIMEOUT_TICKS,
+        /// Maximum amount of old move tokens we accept from a friend in a row.
+        max_move_token_retransmits: MAX_MOVE_TOKEN_RETRANSMITS,
+        /// Optional tap over the messages passed between the Channeler and the Funder.
+        opt_message_tracer: None,
+        /// Optional sink for the outcomes of payments made by this node.
+        opt_payment_event_sender: None,
+        /// Maximum amount of concurrently open incoming app connections.
+        max_incoming_app_conns: MAX_INCOMING_APP_CONNS,
+        /// Maximum amount of messages queued for sending to a single app.
+        max_app_queued_messages: MAX_APP_QUEUED_MESSAGES,
         /*
         /// Maximum amount of incoming app connections we set up at the same time
         max_concurrent_incoming_apps: MAX_CONCURRENT_INCOMING_APPS,
@@ -258,7 +368,7 @@ where
 
     let node_public_key = get_node_identity(node_index).get_public_key();
 
-    let rng = DummyRandom::new(&[0xff, 0x13, 0x36, app_index]);
+    let rng = seeded_rng(&[0xff, 0x13, 0x36, app_index]);
     let secure_connector = create_secure_connector(
         sim_network_client,
         timer_client,
@@ -325,7 +435,7 @@ where
 
     let conn_pair_compact = ConnPairCompact::from_raw(compact_sender, compact_receiver);
 
-    let compact_gen = GenCryptoRandom(DummyRandom::new(&[0xff, 0x13, 0x3a, app_index]));
+    let compact_gen = GenCryptoRandom(seeded_rng(&[0xff, 0x13, 0x3a, app_index]));
     let compact_fut = compact_node(
         app_conn_tuple,
         conn_pair_compact,
@@ -354,7 +464,7 @@ where
 {
     let store_path_buf = sim_db.store_path(store_index).unwrap();
 
-    let rng = DummyRandom::new(&[0xff, 0x13, 0x3b, store_index]);
+    let rng = seeded_rng(&[0xff, 0x13, 0x3b, store_index]);
 
     let file_store = open_file_store(store_path_buf, spawner.clone(), spawner.clone())
         .await
@@ -426,7 +536,7 @@ where
     };
     // let get_trusted_apps = move || Some(trusted_apps.clone());
 
-    let rng = DummyRandom::new(&[0xff, 0x13, 0x37, index]);
+    let rng = seeded_rng(&[0xff, 0x13, 0x37, index]);
 
     let atomic_db = sim_db.load_node_db(index).unwrap();
 
@@ -498,7 +608,7 @@ pub async fn create_index_server<S>(
         })
         .collect::<HashMap<_, _>>();
 
-    let rng = DummyRandom::new(&[0xff, 0x13, 0x38, index]);
+    let rng = seeded_rng(&[0xff, 0x13, 0x38, index]);
     // We use the same spawner for both required spawners.
     // We do this to make it easier to simulate the passage of time in tests.
     let net_index_server_fut = net_index_server(
@@ -534,13 +644,14 @@ pub async fn create_relay<S>(
     let listen_address = listen_relay_address(index);
     let incoming_raw_conns = sim_network_client.listen(listen_address).await.unwrap();
 
-    let rng = DummyRandom::new(&[0xff, 0x13, 0x39, index]);
+    let rng = seeded_rng(&[0xff, 0x13, 0x39, index]);
     let net_relay_server_fut = net_relay_server(
         incoming_raw_conns,
         identity_client,
         timer_client,
         rng,
         MAX_CONCURRENT_ENCRYPT,
+        MAX_RELAY_CONNS,
         spawner.clone(),
     )
     .map_err(|e| error!("net_relay_server() error: {:?}", e))
@@ -560,3 +671,35 @@ pub async fn advance_time<'a>(
         test_executor.wait().await;
     }
 }
+
+#[derive(Debug)]
+pub struct AdvanceUntilError;
+
+/// Advance time one tick at a time, until `cond` holds.
+/// `cond` is checked before the first tick, and again after every tick.
+/// Returns the amount of ticks advanced, or an error if `cond` still does not hold after
+/// `max_ticks` ticks.
+pub async fn advance_until<'a, F, FR>(
+    mut cond: F,
+    tick_sender: &'a mut mpsc::Sender<()>,
+    test_executor: &'a TestExecutor,
+    max_ticks: usize,
+) -> Result<usize, AdvanceUntilError>
+where
+    F: FnMut() -> FR,
+    FR: Future<Output = bool>,
+{
+    test_executor.wait().await;
+    let mut ticks = 0;
+    loop {
+        if cond().await {
+            return Ok(ticks);
+        }
+        if ticks >= max_ticks {
+            return Err(AdvanceUntilError);
+        }
+        tick_sender.send(()).await.unwrap();
+        test_executor.wait().await;
+        ticks += 1;
+    }
+}
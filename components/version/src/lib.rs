@@ -11,6 +11,8 @@
 #[macro_use]
 extern crate log;
 
+mod negotiate;
 mod version_prefix;
 
+pub use self::negotiate::{negotiate_version, NegotiateVersionError, VersionPolicy};
 pub use self::version_prefix::VersionPrefix;
@@ -0,0 +1,170 @@
+use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
+use futures::{SinkExt, StreamExt};
+
+use common::conn::ConnPairVec;
+
+/// The remote protocol versions we are willing to talk to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionPolicy {
+    /// Only the given version is allowed
+    Exact(u32),
+    /// The given version or any newer version is allowed
+    AtLeast(u32),
+}
+
+impl VersionPolicy {
+    pub fn allows(&self, remote_version: u32) -> bool {
+        match self {
+            VersionPolicy::Exact(version) => remote_version == *version,
+            VersionPolicy::AtLeast(min_version) => remote_version >= *min_version,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum NegotiateVersionError {
+    SendVersionError,
+    RecvVersionError,
+    InvalidVersionData,
+    /// The remote side declared a version that is not allowed by the policy
+    IncompatibleVersion(u32),
+}
+
+/// Declare our version to the remote side, and wait for the remote side to declare its version.
+/// Unlike `VersionPrefix`, the remote version is checked before the connection is returned,
+/// so that the caller can find out why a connection was refused.
+///
+/// On success, returns the remote version together with the rest of the connection.
+pub async fn negotiate_version(
+    conn_pair: ConnPairVec,
+    local_version: u32,
+    version_policy: VersionPolicy,
+) -> Result<(u32, ConnPairVec), NegotiateVersionError> {
+    let (mut sender, mut receiver) = conn_pair.split();
+
+    let mut version_data = Vec::new();
+    version_data.write_u32::<BigEndian>(local_version).unwrap();
+    sender
+        .send(version_data)
+        .await
+        .map_err(|_| NegotiateVersionError::SendVersionError)?;
+
+    let version_data = receiver
+        .next()
+        .await
+        .ok_or(NegotiateVersionError::RecvVersionError)?;
+
+    if version_data.len() != 4 {
+        return Err(NegotiateVersionError::InvalidVersionData);
+    }
+
+    let remote_version = BigEndian::read_u32(&version_data);
+    if !version_policy.allows(remote_version) {
+        return Err(NegotiateVersionError::IncompatibleVersion(remote_version));
+    }
+
+    Ok((remote_version, ConnPairVec::from_raw(sender, receiver)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::channel::mpsc;
+    use futures::executor::{block_on, ThreadPool};
+    use futures::future;
+
+    use crate::VersionPrefix;
+
+    #[test]
+    fn test_version_policy_allows() {
+        assert!(VersionPolicy::Exact(3).allows(3));
+        assert!(!VersionPolicy::Exact(3).allows(4));
+        assert!(!VersionPolicy::AtLeast(3).allows(2));
+        assert!(VersionPolicy::AtLeast(3).allows(3));
+        assert!(VersionPolicy::AtLeast(3).allows(4));
+    }
+
+    /// Negotiate against a simulated remote side that uses `VersionPrefix` with `remote_version`.
+    async fn task_negotiate_version(
+        remote_version: u32,
+        version_policy: VersionPolicy,
+        thread_pool: ThreadPool,
+    ) -> Result<u32, NegotiateVersionError> {
+        let (a_sender, b_receiver) = mpsc::channel(1);
+        let (b_sender, a_receiver) = mpsc::channel(1);
+
+        let mut version_prefix = VersionPrefix::new(remote_version, thread_pool);
+        let (_b_sender, _b_receiver) = version_prefix
+            .spawn_prefix(ConnPairVec::from_raw(b_sender, b_receiver))
+            .split();
+
+        let (remote_version, conn_pair) = negotiate_version(
+            ConnPairVec::from_raw(a_sender, a_receiver),
+            3,
+            version_policy,
+        )
+        .await?;
+        drop(conn_pair);
+        Ok(remote_version)
+    }
+
+    #[test]
+    fn test_negotiate_version() {
+        let thread_pool = ThreadPool::new().unwrap();
+
+        assert_eq!(
+            block_on(task_negotiate_version(
+                3,
+                VersionPolicy::Exact(3),
+                thread_pool.clone()
+            )),
+            Ok(3)
+        );
+        assert_eq!(
+            block_on(task_negotiate_version(
+                4,
+                VersionPolicy::AtLeast(3),
+                thread_pool.clone()
+            )),
+            Ok(4)
+        );
+
+        // The simulated remote side advertises an incompatible version:
+        assert_eq!(
+            block_on(task_negotiate_version(
+                2,
+                VersionPolicy::AtLeast(3),
+                thread_pool.clone()
+            )),
+            Err(NegotiateVersionError::IncompatibleVersion(2))
+        );
+        assert_eq!(
+            block_on(task_negotiate_version(
+                4,
+                VersionPolicy::Exact(3),
+                thread_pool
+            )),
+            Err(NegotiateVersionError::IncompatibleVersion(4))
+        );
+    }
+
+    #[test]
+    fn test_negotiate_version_invalid_data() {
+        let (a_sender, mut b_receiver) = mpsc::channel::<Vec<u8>>(1);
+        let (mut b_sender, a_receiver) = mpsc::channel(1);
+
+        block_on(async move {
+            b_sender.send(vec![1, 2, 3]).await.unwrap();
+            let (res, _) = future::join(
+                negotiate_version(
+                    ConnPairVec::from_raw(a_sender, a_receiver),
+                    3,
+                    VersionPolicy::Exact(3),
+                ),
+                b_receiver.next(),
+            )
+            .await;
+            assert_eq!(res.err(), Some(NegotiateVersionError::InvalidVersionData));
+        });
+    }
+}